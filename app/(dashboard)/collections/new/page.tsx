@@ -14,27 +14,51 @@ import {
     FileText,
     Check,
     Loader2,
-    EyeOff
+    EyeOff,
+    ScrollText
 } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
 import { ScrollArea } from "@/components/ui/scroll-area";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
 import { format, parse } from "date-fns";
 import { ro } from "date-fns/locale";
 import {
     getPartners,
     getClientBalances,
     recordCollectionGroup,
+    getNumberingWarnings,
+    checkCashLimits,
+    allocatePayment,
+    getPartnerAdvances,
+    applyPartnerAdvances,
+    sendAdvanceApplications,
     printCollectionToHtml,
     sendCollection,
     hideClientBalance
 } from "@/lib/tauri/commands";
-import type { PartnerWithLocations, ClientBalance, CreateCollectionGroupRequest } from "@/lib/tauri/types";
+import type {
+    PartnerWithLocations,
+    ClientBalance,
+    CreateCollectionGroupRequest,
+    PaymentAllocationStrategy,
+    PartnerAdvance,
+    PaymentMethod,
+} from "@/lib/tauri/types";
 import { toast } from "sonner";
 import { cn } from "@/lib/utils";
 import { useAuth } from "@/app/contexts/AuthContext";
+import { PartnerStatementDialog } from "@/app/components/collections/PartnerStatementDialog";
+
+const PAYMENT_METHODS: { value: PaymentMethod; label: string; reference: string }[] = [
+    { value: "cash", label: "Numerar", reference: "" },
+    { value: "card", label: "Card (POS)", reference: "Nr. bon POS" },
+    { value: "bank_transfer", label: "Ordin de plată", reference: "Referință OP" },
+    { value: "promissory_note", label: "Bilet la ordin", reference: "Serie și nr. BO" },
+    { value: "cheque", label: "Cec", reference: "Serie și nr. cec" },
+];
 
 export default function NewCollectionPage() {
     const router = useRouter();
@@ -58,6 +82,17 @@ export default function NewCollectionPage() {
     const [saving, setSaving] = useState(false);
     const [search, setSearch] = useState("");
     const [hidingId, setHidingId] = useState<string | null>(null);
+    const [paymentAmount, setPaymentAmount] = useState("");
+    const [allocationStrategy, setAllocationStrategy] = useState<PaymentAllocationStrategy>("oldest_due");
+    const [allocating, setAllocating] = useState(false);
+    const [advanceAmount, setAdvanceAmount] = useState("");
+    const [advances, setAdvances] = useState<PartnerAdvance[]>([]);
+    const [applyingAdvances, setApplyingAdvances] = useState(false);
+    const [paymentMethod, setPaymentMethod] = useState<PaymentMethod>("cash");
+    const [paymentReference, setPaymentReference] = useState("");
+    const [paymentDueDate, setPaymentDueDate] = useState("");
+    const [bankAccount, setBankAccount] = useState("");
+    const [statementOpen, setStatementOpen] = useState(false);
 
     const steps = [
         { key: "partner" as const, title: "Partener", icon: Building2 },
@@ -156,6 +191,7 @@ export default function NewCollectionPage() {
         setSelectedBalanceKeys([]);
         setSelectedBalances([]);
         setAllocatedAmounts({});
+        setAdvanceAmount("");
     };
 
     const getBalanceKey = (balance: ClientBalance) =>
@@ -217,12 +253,60 @@ export default function NewCollectionPage() {
         }
     };
 
+    // Splits the sum received over the open invoices and selects them with
+    // the proposed amounts
+    const handleAutoAllocate = async () => {
+        if (!selectedPartner) return;
+        const amount = Number.parseFloat(paymentAmount.replace(",", "."));
+        if (!Number.isFinite(amount) || amount <= 0) {
+            toast.error("Introdu suma primită.");
+            return;
+        }
+
+        setAllocating(true);
+        try {
+            const allocation = await allocatePayment(selectedPartner.id, amount, allocationStrategy);
+            const sameDocument = (balance: ClientBalance, line: (typeof allocation.lines)[number]) =>
+                normalizeDocPart(balance.serie) === normalizeDocPart(line.serie_factura) &&
+                normalizeDocPart(balance.numar) === normalizeDocPart(line.numar_factura) &&
+                normalizeDocPart(balance.cod_document) === normalizeDocPart(line.cod_document);
+
+            const chosen: ClientBalance[] = [];
+            const amounts: Record<string, string> = {};
+            for (const line of allocation.lines) {
+                const balance = balances.find((item) => sameDocument(item, line));
+                if (!balance) continue;
+                chosen.push(balance);
+                amounts[getBalanceKey(balance)] = line.valoare.toFixed(2);
+            }
+
+            setSelectedBalances(chosen);
+            setSelectedBalanceKeys(chosen.map(getBalanceKey));
+            setAllocatedAmounts(amounts);
+            setAdvanceAmount(allocation.unallocated > 0 ? allocation.unallocated.toFixed(2) : "");
+            if (allocation.unallocated > 0) {
+                toast.info(`Diferența de ${allocation.unallocated.toFixed(2)} lei va fi înregistrată ca avans.`);
+            }
+            setStep("details");
+        } catch (error) {
+            console.error("Failed to allocate payment:", error);
+            toast.error("Eroare la împărțirea sumei: " + error);
+        } finally {
+            setAllocating(false);
+        }
+    };
+
     const parseAllocated = (key: string) => {
         const raw = (allocatedAmounts[key] || "").replace(",", ".");
         const value = Number.parseFloat(raw);
         return Number.isFinite(value) ? value : 0;
     };
 
+    const parseAdvance = () => {
+        const value = Number.parseFloat(advanceAmount.replace(",", "."));
+        return Number.isFinite(value) && value > 0 ? Math.round(value * 100) / 100 : 0;
+    };
+
     const isAllocationValid = (balance: ClientBalance) => {
         const key = getBalanceKey(balance);
         const value = parseAllocated(key);
@@ -234,7 +318,9 @@ export default function NewCollectionPage() {
     const canGoNext = () => {
         if (step === "partner") return selectedPartner !== null;
         if (step === "invoice") return selectedBalances.length > 0;
-        if (step === "details") return selectedBalances.length > 0 && selectedBalances.every(isAllocationValid);
+        if (step === "details") {
+            return (selectedBalances.length > 0 || parseAdvance() > 0) && selectedBalances.every(isAllocationValid);
+        }
         return false;
     };
 
@@ -264,6 +350,7 @@ export default function NewCollectionPage() {
             setSelectedBalanceKeys([]);
             setSelectedBalances([]);
             setAllocatedAmounts({});
+            setAdvanceAmount("");
             return;
         }
         router.push("/collections");
@@ -275,8 +362,12 @@ export default function NewCollectionPage() {
 
             setLoadingBalances(true);
             try {
-                const data = await getClientBalances(selectedPartner.id);
+                const [data, open] = await Promise.all([
+                    getClientBalances(selectedPartner.id),
+                    getPartnerAdvances(selectedPartner.id),
+                ]);
                 setBalances(dedupeBalances(data));
+                setAdvances(open);
             } catch (error) {
                 console.error("Failed to load balances:", error);
                 toast.error("Eroare la încărcarea soldurilor");
@@ -288,8 +379,52 @@ export default function NewCollectionPage() {
         loadBalances();
     }, [step, selectedPartner]);
 
+    const availableAdvance = advances.reduce((sum, advance) => sum + advance.available, 0);
+
+    // Sets the partner's advances against the invoices now open, then sends that to WME
+    const handleApplyAdvances = async () => {
+        if (!selectedPartner) return;
+        setApplyingAdvances(true);
+        try {
+            const applications = await applyPartnerAdvances(selectedPartner.id);
+            if (applications.length === 0) {
+                toast.info("Nu există facturi pe care să se compenseze avansul.");
+                return;
+            }
+            const applied = applications.reduce((sum, application) => sum + application.valoare, 0);
+            toast.success(`Avans de ${applied.toFixed(2)} lei compensat pe ${applications.length} factur${applications.length === 1 ? "ă" : "i"}.`);
+
+            try {
+                const sent = await sendAdvanceApplications(applications[0].group_id);
+                const waiting = sent.find((application) => application.status === "pending");
+                if (waiting) {
+                    toast.warning(waiting.error_message || "Compensarea va fi trimisă în WME ulterior.");
+                }
+            } catch (sendError) {
+                console.error("Failed to send advance applications:", sendError);
+                toast.warning("Compensarea a fost salvată, dar nu s-a putut trimite în WME.");
+            }
+
+            const [data, open] = await Promise.all([
+                getClientBalances(selectedPartner.id),
+                getPartnerAdvances(selectedPartner.id),
+            ]);
+            setBalances(dedupeBalances(data));
+            setAdvances(open);
+            setSelectedBalanceKeys([]);
+            setSelectedBalances([]);
+            setAllocatedAmounts({});
+        } catch (error) {
+            console.error("Failed to apply advances:", error);
+            toast.error("Eroare la compensarea avansului: " + error);
+        } finally {
+            setApplyingAdvances(false);
+        }
+    };
+
     const handleSave = async () => {
-        if (!selectedPartner || selectedBalances.length === 0) return;
+        const advance = parseAdvance();
+        if (!selectedPartner || (selectedBalances.length === 0 && advance <= 0)) return;
 
         const allocations = selectedBalances
             .map((balance) => {
@@ -304,31 +439,67 @@ export default function NewCollectionPage() {
             })
             .filter((item) => item.valoare > 0);
 
-        if (allocations.length === 0) {
+        if (allocations.length === 0 && advance <= 0) {
             toast.error("Introdu valori valide pentru cel puțin o factură.");
             return;
         }
 
+        const isCash = paymentMethod === "cash";
+        if (!isCash && !paymentReference.trim()) {
+            toast.error("Introdu numărul documentului de plată.");
+            return;
+        }
+
         setSaving(true);
         try {
+            // Only cash counts against the cash limits
+            if (isCash) {
+                const cash = advance > 0
+                    ? [...allocations, { valoare: advance }]
+                    : allocations;
+                const exceeded = await checkCashLimits(selectedPartner.id, cash);
+                const blocking = exceeded.find((limit) => limit.blocking);
+                if (blocking) {
+                    toast.error(blocking.message);
+                    return;
+                }
+                if (exceeded.length > 0 && !confirm(`${exceeded.map((limit) => limit.message).join("\n")}\n\nSalvezi chitanța?`)) {
+                    return;
+                }
+            }
+
             const request: CreateCollectionGroupRequest = {
                 id_partener: selectedPartner.id,
                 partner_name: selectedPartner.name,
                 allocations,
+                advance: advance > 0 ? advance : undefined,
+                payment: isCash
+                    ? undefined
+                    : {
+                        method: paymentMethod,
+                        reference: paymentReference.trim(),
+                        due_date: paymentDueDate || null,
+                        bank_account: bankAccount.trim() || null,
+                    },
             };
 
             const collectionId = await recordCollectionGroup(request);
-            toast.success("Chitanță salvată cu succes");
+            toast.success(isCash ? "Chitanță salvată cu succes" : "Încasare salvată cu succes");
 
-            try {
-                const selectedPrinter = typeof window !== "undefined"
-                    ? localStorage.getItem("selectedPrinter")
-                    : null;
-                await printCollectionToHtml(collectionId, selectedPrinter || undefined);
-                toast.success("Chitanța a fost trimisă la imprimantă.");
-            } catch (printError) {
-                console.error("Auto-print collection failed:", printError);
-                toast.warning("Chitanța a fost salvată, dar printarea automată a eșuat.");
+            // A non-cash payment has its own document, so there is no receipt to print
+            if (isCash) {
+                getNumberingWarnings().then((warnings) => warnings.forEach((warning) => toast.warning(warning))).catch(console.error);
+
+                try {
+                    const selectedPrinter = typeof window !== "undefined"
+                        ? localStorage.getItem("selectedPrinter")
+                        : null;
+                    await printCollectionToHtml(collectionId, selectedPrinter || undefined);
+                    toast.success("Chitanța a fost trimisă la imprimantă.");
+                } catch (printError) {
+                    console.error("Auto-print collection failed:", printError);
+                    toast.warning("Chitanța a fost salvată, dar printarea automată a eșuat.");
+                }
             }
 
             try {
@@ -494,10 +665,89 @@ export default function NewCollectionPage() {
                 {step === "invoice" && selectedPartner && (
                     <Card className="flex-1 flex flex-col min-h-0">
                         <CardHeader className="pb-3 border-b bg-muted/20">
-                            <div>
-                                <CardTitle>{selectedPartner.name}</CardTitle>
-                                <CardDescription>Selectează factura pentru plată</CardDescription>
+                            <div className="flex flex-wrap items-end justify-between gap-4">
+                                <div>
+                                    <CardTitle>{selectedPartner.name}</CardTitle>
+                                    <CardDescription>Selectează factura pentru plată</CardDescription>
+                                    <Button
+                                        variant="link"
+                                        className="h-auto p-0 gap-1 text-xs"
+                                        onClick={() => setStatementOpen(true)}
+                                    >
+                                        <ScrollText className="h-3.5 w-3.5" />
+                                        Fișă de cont
+                                    </Button>
+                                    <PartnerStatementDialog
+                                        partnerId={selectedPartner.id}
+                                        partnerName={selectedPartner.name}
+                                        open={statementOpen}
+                                        onOpenChange={setStatementOpen}
+                                    />
+                                </div>
+                                <div className="flex items-end gap-2">
+                                    <div className="space-y-1">
+                                        <Label htmlFor="payment-amount" className="text-xs">Sumă primită</Label>
+                                        <Input
+                                            id="payment-amount"
+                                            inputMode="decimal"
+                                            placeholder="0.00"
+                                            className="h-9 w-32"
+                                            value={paymentAmount}
+                                            onChange={(e) => setPaymentAmount(e.target.value)}
+                                        />
+                                    </div>
+                                    <Select
+                                        value={allocationStrategy}
+                                        onValueChange={(v) => setAllocationStrategy(v as PaymentAllocationStrategy)}
+                                    >
+                                        <SelectTrigger className="h-9 w-48">
+                                            <SelectValue />
+                                        </SelectTrigger>
+                                        <SelectContent>
+                                            <SelectItem value="oldest_due">Scadența cea mai veche</SelectItem>
+                                            <SelectItem value="overdue_first">Întâi cele restante</SelectItem>
+                                            <SelectItem value="exact_match">Factura cu suma exactă</SelectItem>
+                                        </SelectContent>
+                                    </Select>
+                                    <Button
+                                        variant="outline"
+                                        className="h-9"
+                                        onClick={handleAutoAllocate}
+                                        disabled={allocating || loadingBalances || balances.length === 0}
+                                    >
+                                        {allocating && <Loader2 className="h-4 w-4 animate-spin mr-2" />}
+                                        Împarte automat
+                                    </Button>
+                                    <Button
+                                        variant="ghost"
+                                        className="h-9"
+                                        onClick={() => {
+                                            setSelectedBalanceKeys([]);
+                                            setSelectedBalances([]);
+                                            setAllocatedAmounts({});
+                                            setAdvanceAmount(paymentAmount);
+                                            setStep("details");
+                                        }}
+                                    >
+                                        Doar avans
+                                    </Button>
+                                </div>
                             </div>
+                            {availableAdvance > 0.005 && balances.length > 0 && (
+                                <div className="mt-3 flex items-center justify-between gap-3 rounded-md border border-primary/30 bg-primary/5 px-3 py-2 text-sm">
+                                    <span>
+                                        Partenerul are un avans disponibil de{" "}
+                                        <span className="font-semibold">
+                                            {new Intl.NumberFormat('ro-RO', { style: 'currency', currency: 'RON' }).format(availableAdvance)}
+                                        </span>
+                                        .
+                                    </span>
+                                    <Button size="sm" onClick={handleApplyAdvances} disabled={applyingAdvances}>
+                                        {applyingAdvances && <Loader2 className="h-4 w-4 animate-spin mr-2" />}
+                                        Compensează cu facturile
+                                    </Button>
+                                </div>
+                            )}
                         </CardHeader>
                         <CardContent className="flex-1 overflow-auto p-0 pb-6">
                             {loadingBalances ? (
@@ -600,7 +850,7 @@ export default function NewCollectionPage() {
                     </Card>
                 )}
 
-                {step === "details" && selectedBalances.length > 0 && (
+                {step === "details" && selectedPartner && (
                     <Card>
                         <CardHeader>
                             <CardTitle>Detalii Plată</CardTitle>
@@ -625,7 +875,7 @@ export default function NewCollectionPage() {
                                             selectedBalances.reduce((sum, balance) => {
                                                 const key = getBalanceKey(balance);
                                                 return sum + parseAllocated(key);
-                                            }, 0)
+                                            }, parseAdvance())
                                         )}
                                     </div>
                                 </div>
@@ -677,6 +927,94 @@ export default function NewCollectionPage() {
                                     );
                                 })}
                             </div>
+
+                            <div className="rounded-lg border border-dashed p-3">
+                                <div className="flex flex-col gap-2 sm:flex-row sm:items-center sm:justify-between">
+                                    <div>
+                                        <div className="text-sm font-semibold">Avans</div>
+                                        <div className="text-xs text-muted-foreground">
+                                            Suma primită peste facturi rămâne în contul partenerului
+                                        </div>
+                                    </div>
+                                    <div className="w-full sm:w-52">
+                                        <Label htmlFor="advance-amount" className="text-xs">Suma în avans</Label>
+                                        <Input
+                                            id="advance-amount"
+                                            type="number"
+                                            step="0.01"
+                                            min="0"
+                                            placeholder="0.00"
+                                            value={advanceAmount}
+                                            onChange={(e) => setAdvanceAmount(e.target.value)}
+                                            className="h-10 [appearance:textfield] [&::-webkit-outer-spin-button]:appearance-none [&::-webkit-inner-spin-button]:appearance-none"
+                                        />
+                                    </div>
+                                </div>
+                            </div>
+
+                            <div className="rounded-lg border p-3 space-y-3">
+                                <div className="flex flex-col gap-2 sm:flex-row sm:items-center sm:justify-between">
+                                    <div>
+                                        <div className="text-sm font-semibold">Modalitate de plată</div>
+                                        <div className="text-xs text-muted-foreground">
+                                            {paymentMethod === "cash"
+                                                ? "Se emite chitanță și intră în registrul de casă"
+                                                : "Se înregistrează în bancă pe documentul de plată, fără chitanță"}
+                                        </div>
+                                    </div>
+                                    <Select value={paymentMethod} onValueChange={(v) => setPaymentMethod(v as PaymentMethod)}>
+                                        <SelectTrigger className="h-10 w-full sm:w-52">
+                                            <SelectValue />
+                                        </SelectTrigger>
+                                        <SelectContent>
+                                            {PAYMENT_METHODS.map((method) => (
+                                                <SelectItem key={method.value} value={method.value}>
+                                                    {method.label}
+                                                </SelectItem>
+                                            ))}
+                                        </SelectContent>
+                                    </Select>
+                                </div>
+                                {paymentMethod !== "cash" && (
+                                    <div className="grid grid-cols-1 sm:grid-cols-3 gap-2">
+                                        <div>
+                                            <Label htmlFor="payment-reference" className="text-xs">
+                                                {PAYMENT_METHODS.find((method) => method.value === paymentMethod)?.reference}
+                                            </Label>
+                                            <Input
+                                                id="payment-reference"
+                                                value={paymentReference}
+                                                onChange={(e) => setPaymentReference(e.target.value)}
+                                                className="h-10"
+                                            />
+                                        </div>
+                                        {(paymentMethod === "promissory_note" || paymentMethod === "cheque") && (
+                                            <div>
+                                                <Label htmlFor="payment-due-date" className="text-xs">
+                                                    Scadență{paymentMethod === "cheque" ? " (opțional)" : ""}
+                                                </Label>
+                                                <Input
+                                                    id="payment-due-date"
+                                                    type="date"
+                                                    value={paymentDueDate}
+                                                    onChange={(e) => setPaymentDueDate(e.target.value)}
+                                                    className="h-10"
+                                                />
+                                            </div>
+                                        )}
+                                        <div>
+                                            <Label htmlFor="bank-account" className="text-xs">Cont bancar (opțional)</Label>
+                                            <Input
+                                                id="bank-account"
+                                                placeholder="Contul principal al firmei"
+                                                value={bankAccount}
+                                                onChange={(e) => setBankAccount(e.target.value)}
+                                                className="h-10"
+                                            />
+                                        </div>
+                                    </div>
+                                )}
+                            </div>
                         </CardContent>
                     </Card>
                 )}
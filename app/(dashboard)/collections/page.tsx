@@ -33,7 +33,7 @@ import {
     DropdownMenuSeparator,
     DropdownMenuTrigger,
 } from "@/components/ui/dropdown-menu";
-import { getCollections, sendCollection, printCollectionToHtml, deleteCollection } from "@/lib/tauri/commands";
+import { getCollections, sendCollection, printCollectionToHtml, cancelReceipt } from "@/lib/tauri/commands";
 import type { Collection, CollectionStatus } from "@/lib/tauri/types";
 import { toast } from "sonner";
 import { format } from "date-fns";
@@ -191,15 +191,22 @@ export default function CollectionsPage() {
     };
 
     const handleDeleteCollection = async (collectionId: string) => {
+        const reason = window.prompt("Motivul anulării chitanței:");
+        if (!reason || !reason.trim()) return;
+
         setActionId(collectionId);
         setActionType("delete");
         try {
-            await deleteCollection(collectionId);
-            toast.success("Chitanța a fost ștearsă.");
+            const cancelled = await cancelReceipt(collectionId, reason.trim());
+            if (cancelled.error_message?.startsWith("Anulată local, netrimisă în WME")) {
+                toast.warning(cancelled.error_message);
+            } else {
+                toast.success("Chitanța a fost anulată și retipărită.");
+            }
             await loadData();
         } catch (error) {
-            console.error("Delete collection error:", error);
-            toast.error(`Eroare la ștergerea chitanței: ${String(error)}`);
+            console.error("Cancel collection error:", error);
+            toast.error(`Eroare la anularea chitanței: ${String(error)}`);
         } finally {
             setActionId(null);
             setActionType(null);
@@ -397,7 +404,7 @@ export default function CollectionsPage() {
                                                             )}
                                                         </DropdownMenuItem>
                                                     )}
-                                                    {isAdmin && (
+                                                    {isAdmin && (collection.status === "pending" || collection.status === "failed" || collection.status === "synced") && (
                                                         <>
                                                             <DropdownMenuSeparator />
                                                             <DropdownMenuItem
@@ -409,7 +416,7 @@ export default function CollectionsPage() {
                                                                 }}
                                                             >
                                                                 <Trash2 className="mr-2 h-4 w-4" />
-                                                                Șterge
+                                                                Anulează
                                                             </DropdownMenuItem>
                                                         </>
                                                     )}
@@ -509,13 +516,13 @@ export default function CollectionsPage() {
                                             )}
                                         </Button>
                                     )}
-                                    {isAdmin && (
+                                    {isAdmin && (collection.status === "pending" || collection.status === "failed" || collection.status === "synced") && (
                                         <Button
                                             variant="ghost"
                                             className="h-9 w-9 p-0 flex-shrink-0 text-red-600 hover:text-red-700 hover:bg-red-50 dark:text-red-400 dark:hover:bg-red-900/20"
                                             disabled={actionId === collection.id}
                                             onClick={() => handleDeleteCollection(collection.id)}
-                                            title="Șterge chitanța"
+                                            title="Anulează chitanța"
                                         >
                                             {actionId === collection.id && actionType === "delete" ? (
                                                 <Loader2 className="h-3.5 w-3.5 animate-spin" />
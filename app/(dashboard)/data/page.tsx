@@ -24,9 +24,11 @@ import { useProducts } from "@/hooks/useProducts";
 import { Search, Building2, Package, MapPin, Loader2, Info, Phone, Mail, FileText, CreditCard, Calendar, Trash2 } from "lucide-react";
 import { Separator } from "@/components/ui/separator";
 import { BarcodeScanner } from "@/app/components/barcode/BarcodeScanner";
+import { InvoiceDetailDialog } from "@/app/components/invoices/InvoiceDetailDialog";
+import { CollectionDetailDialog } from "@/app/components/collections/CollectionDetailDialog";
 import { formatCurrency } from "@/lib/utils";
-import { getClientBalances, syncClientBalances, deletePartnersAndLocations } from "@/lib/tauri/commands";
-import type { ClientBalance } from "@/lib/tauri/types";
+import { getClientBalances, syncClientBalances, deletePartnersAndLocations, lookupDocumentByCode, getCollections } from "@/lib/tauri/commands";
+import type { ClientBalance, Collection } from "@/lib/tauri/types";
 import { toast } from "sonner";
 import { useAuth } from "@/app/contexts/AuthContext";
 
@@ -53,6 +55,8 @@ export default function DataPage() {
   const [loadingPartnerBalances, setLoadingPartnerBalances] = useState(false);
   const [syncingPartnerBalances, setSyncingPartnerBalances] = useState(false);
   const [deletingPartners, setDeletingPartners] = useState(false);
+  const [scannedInvoiceId, setScannedInvoiceId] = useState<string | null>(null);
+  const [scannedCollection, setScannedCollection] = useState<Collection | null>(null);
 
   const { partners, isLoading: partnersLoading, search: searchPartners, refresh: refreshPartners } = usePartners();
   const { products: partnerProducts, isLoading: partnerProductsLoading, search: searchPartnerProducts, refresh: refreshPartnerProducts } = useProducts(selectedPartnerId || undefined);
@@ -103,6 +107,35 @@ export default function DataPage() {
     }
   };
 
+  // Codes printed on our invoices, receipts and certificates open the document;
+  // anything else is searched as a partner code
+  const handleScan = async (code: string) => {
+    try {
+      const document = await lookupDocumentByCode(code);
+      if (document) {
+        if (document.document_type === "receipt") {
+          const collections = await getCollections();
+          const collection = collections.find((c) => c.id === document.document_id);
+          if (!collection) {
+            toast.error(`Chitanța ${document.series} ${document.number} nu a fost găsită`);
+            return;
+          }
+          setScannedCollection(collection);
+        } else {
+          setScannedInvoiceId(document.document_id);
+        }
+        return;
+      }
+    } catch (error) {
+      console.error("Failed to look up scanned document:", error);
+      toast.error(String(error));
+      return;
+    }
+
+    setPartnerSearch(code);
+    searchPartners(code);
+  };
+
   const handleSyncPartnerBalances = async () => {
     if (!selectedPartnerId) return;
 
@@ -224,12 +257,7 @@ export default function DataPage() {
                 className="pl-10 h-14 text-base"
               />
             </div>
-            <BarcodeScanner
-              onScan={(code) => {
-                setPartnerSearch(code);
-                searchPartners(code);
-              }}
-            />
+            <BarcodeScanner onScan={handleScan} />
           </div>
 
           <div className="flex-1 min-h-0 overflow-y-auto overflow-x-hidden">
@@ -646,6 +674,17 @@ export default function DataPage() {
           </Tabs>
         </DialogContent>
       </Dialog>
+
+      <InvoiceDetailDialog
+        invoiceId={scannedInvoiceId}
+        open={scannedInvoiceId !== null}
+        onOpenChange={(open) => !open && setScannedInvoiceId(null)}
+      />
+      <CollectionDetailDialog
+        collection={scannedCollection}
+        open={scannedCollection !== null}
+        onOpenChange={(open) => !open && setScannedCollection(null)}
+      />
     </div >
   );
 }
@@ -26,7 +26,7 @@ import {
   DropdownMenuTrigger,
   DropdownMenuSeparator,
 } from "@/components/ui/dropdown-menu";
-import { Eye, Send, MoreHorizontal, Trash2, Printer, XCircle, RotateCcw } from "lucide-react";
+import { Eye, Send, MoreHorizontal, Trash2, Printer, XCircle, RotateCcw, Undo2 } from "lucide-react";
 import { useAuth } from "@/app/contexts/AuthContext";
 import { formatCurrency, formatDate, formatTime } from "@/lib/utils";
 
@@ -42,7 +42,7 @@ export default function InvoicesPage() {
   const { printInvoice, receiptDialog } = usePrintInvoice();
 
   const statusFilter = activeTab === "all" ? undefined : activeTab;
-  const { invoices: rawInvoices, isLoading, send, remove, refresh } = useInvoices(statusFilter);
+  const { invoices: rawInvoices, isLoading, send, remove, restore, refresh } = useInvoices(statusFilter);
   const { invoices: allRawInvoices } = useInvoices();
 
   // Filter invoices for agent (today only)
@@ -83,7 +83,15 @@ export default function InvoicesPage() {
   };
 
   const handleDelete = async (id: string) => {
-    await remove(id);
+    const reason = window.prompt(
+      "Motivul anulării facturii (factura își păstrează numărul și se tipărește cu mențiunea ANULAT):"
+    );
+    if (!reason || !reason.trim()) return;
+    await remove(id, reason.trim());
+  };
+
+  const handleRestore = async (id: string) => {
+    await restore(id);
   };
 
   const handleCancel = async (id: string) => {
@@ -279,7 +287,16 @@ export default function InvoicesPage() {
                                 Anulează
                               </DropdownMenuItem>
                             )}
-                            {isAdmin && (
+                            {isAdmin && invoice.status === "cancelled" && !invoice.sent_at && (
+                              <>
+                                <DropdownMenuSeparator />
+                                <DropdownMenuItem onClick={() => handleRestore(invoice.id)}>
+                                  <Undo2 className="mr-2 h-4 w-4" />
+                                  Restaurează
+                                </DropdownMenuItem>
+                              </>
+                            )}
+                            {isAdmin && invoice.status !== "cancelled" && invoice.status !== "sending" && (
                               <>
                                 <DropdownMenuSeparator />
                                 <DropdownMenuItem
@@ -287,7 +304,7 @@ export default function InvoicesPage() {
                                   className="text-red-600"
                                 >
                                   <Trash2 className="mr-2 h-4 w-4" />
-                                  Șterge
+                                  Anulează factura
                                 </DropdownMenuItem>
                               </>
                             )}
@@ -308,6 +325,7 @@ export default function InvoicesPage() {
                 invoice={invoice}
                 onSend={handleSend}
                 onDelete={handleDelete}
+                onRestore={handleRestore}
                 onView={handleView}
                 onCancel={handleCancel}
                 isAdmin={isAdmin}
@@ -13,11 +13,14 @@ import {
   TableHeader,
   TableRow,
 } from "@/components/ui/table";
-import { getDailyCollectionsReport, getSalesPrintReport, printReportHtml } from "@/lib/tauri/commands";
+import { getDailyCollectionsReport, getSalesPrintReport, printSalesSummary } from "@/lib/tauri/commands";
 import type { DailyCollectionsReport, SalesPrintItem } from "@/lib/tauri/types";
 import { formatCurrency } from "@/lib/utils";
 import { toast } from "sonner";
 import { useAuth } from "@/app/contexts/AuthContext";
+import { CashRegisterCard } from "@/app/components/reports/CashRegisterCard";
+import { BalanceConfirmationsCard } from "@/app/components/reports/BalanceConfirmationsCard";
+import { AgingReportCard } from "@/app/components/reports/AgingReportCard";
 
 function toInputDate(date: Date): string {
   const year = date.getFullYear();
@@ -147,225 +150,19 @@ export default function ReportsPage() {
   }, [collectionsReport]);
 
   const printSales = async () => {
-    const reportDate = salesEndDate || salesStartDate;
-    const collections = await getDailyCollectionsReport(reportDate || undefined);
-
-    const formatPrintDate = (dateStr: string) => {
-      const [year, month, day] = dateStr.split("-");
-      return `${day}.${month}.${year}`;
-    };
-    const printDateLabel = reportDate ? formatPrintDate(reportDate) : "";
-
-    const formatPrintNumber = (value: number, decimals = 2) =>
-      value.toLocaleString("ro-RO", {
-        minimumFractionDigits: decimals,
-        maximumFractionDigits: decimals,
-      });
-
-    const invoiceRows = salesByPartner
-      .map((row) => `
-        <tr>
-          <td class="partner-code">${row.partner_name.slice(0, 7)}</td>
-          <td class="right num">${formatPrintNumber(row.total_quantity, 0)}</td>
-          <td class="right num">${formatPrintNumber(row.total_cofrage, 2)}</td>
-          <td class="right num">${formatPrintNumber(row.total_without_vat, 2)}</td>
-          <td class="right num">${formatPrintNumber(row.total_with_vat, 2)}</td>
-        </tr>
-      `)
-      .join("");
-
-    const invoicesTotalQty = salesByPartner.reduce((acc, row) => acc + row.total_quantity, 0);
-    const invoicesTotalCofrage = salesByPartner.reduce((acc, row) => acc + row.total_cofrage, 0);
-    const invoicesTotalWithoutVat = salesByPartner.reduce((acc, row) => acc + row.total_without_vat, 0);
-    const invoicesTotalValue = salesByPartner.reduce((acc, row) => acc + row.total_with_vat, 0);
-
-    const receiptsRows = collections.items
-      .map((row) => `
-        <tr>
-          <td class="partner-code">${row.partner_name.slice(0, 7)}</td>
-          <td class="right num">${formatPrintNumber(row.total_amount, 2)}</td>
-        </tr>
-      `)
-      .join("");
-
-    const receiptsTotalDay = collections.items.reduce(
-      (acc, item) => acc + item.amount_from_today_sales,
-      0
-    );
-    const receiptsTotalDebt = collections.items.reduce(
-      (acc, item) => acc + item.amount_from_previous_debt,
-      0
-    );
-    const receiptsGrandTotal = receiptsTotalDay + receiptsTotalDebt;
-
-    const htmlDocument = `
-      <html>
-        <head>
-          <title>Centralizator zi</title>
-          <style>
-            @media print {
-              @page {
-                size: 80mm 297mm;
-                margin: 3mm 6mm 3mm 0.5mm;
-              }
-              body { margin: 0; padding: 0; }
-            }
-            html { height: 100%; }
-            body {
-              font-family: Arial, Helvetica, sans-serif;
-              width: 68mm;
-              margin: 0 auto;
-              padding: 2mm;
-              font-size: 14px;
-              font-weight: bold;
-              color: #000;
-              line-height: 1.15;
-              box-sizing: border-box;
-            }
-            h1 {
-              font-size: 22px;
-              text-align: center;
-              margin: 0 0 8px 0;
-              border-bottom: 2px solid #000;
-              text-transform: uppercase;
-              padding-bottom: 4px;
-            }
-            .section {
-              margin-top: 8px;
-              padding-bottom: 4px;
-            }
-            h2 {
-              font-size: 16px;
-              margin: 0 0 4px;
-              text-decoration: underline;
-            }
-            table {
-              width: 100%;
-              border-collapse: collapse;
-              margin-top: 3px;
-              font-size: 10.5px;
-              table-layout: fixed;
-            }
-            th, td {
-              padding: 5px 3px;
-              text-align: left;
-              vertical-align: middle;
-              box-sizing: border-box;
-              overflow: visible;
-            }
-            th {
-              border-bottom: 1px solid #000;
-              font-size: 12px;
-              line-height: 1.2;
-              white-space: normal;
-              word-break: break-word;
-              padding-bottom: 4px;
-            }
-            .right { text-align: right; }
-            .num {
-              white-space: nowrap;
-              font-variant-numeric: tabular-nums;
-              font-size: 0.9em;
-            }
-            .invoice-table th:first-child,
-            .invoice-table td:first-child,
-            .receipts-table th:first-child,
-            .receipts-table td:first-child {
-              text-align: left;
-              font-size: 11px;
-            }
-            .partner-code {
-              white-space: nowrap;
-              font-size: 11px;
-              letter-spacing: 0.2px;
-              text-overflow: unset;
-            }
-            .invoice-table th:not(:first-child),
-            .invoice-table td:not(:first-child),
-            .receipts-table th:nth-child(2),
-            .receipts-table td:nth-child(2) {
-              text-align: right;
-            }
-            .invoice-table th:nth-child(1), .invoice-table td:nth-child(1) { width: 20%; }
-            .invoice-table th:nth-child(2), .invoice-table td:nth-child(2) { width: 20%; }
-            .invoice-table th:nth-child(3), .invoice-table td:nth-child(3) { width: 20%; }
-            .invoice-table th:nth-child(4), .invoice-table td:nth-child(4) { width: 20%; }
-            .invoice-table th:nth-child(5), .invoice-table td:nth-child(5) { width: 20%; }
-            .receipts-table th:nth-child(1), .receipts-table td:nth-child(1) { width: 50%; }
-            .receipts-table th:nth-child(2), .receipts-table td:nth-child(2) { width: 50%; }
-            .totals {
-              margin-top: 6px;
-              border-top: 1px solid #000;
-              padding-top: 4px;
-              font-size: 11px;
-            }
-            .totals strong {
-              font-size: 13px;
-            }
-            .total-facturi-label {
-              font-size: 13px;
-            }
-          </style>
-        </head>
-        <body>
-          <h1>Centralizator zi</h1>
-
-          <div class="section">
-            <h2>Facturi eliberate ${printDateLabel}</h2>
-            <table class="invoice-table">
-              <thead>
-                <tr>
-                  <th>Cod ext</th>
-                  <th class="right">Ouă</th>
-                  <th class="right">Cof</th>
-                  <th class="right">Fără TVA</th>
-                  <th class="right">Cu TVA</th>
-                </tr>
-              </thead>
-              <tbody>${invoiceRows || "<tr><td colspan=\"5\">Fără date</td></tr>"}</tbody>
-            </table>
-            <div class="totals">
-              <strong class="total-facturi-label">Total facturi:</strong>
-              Ouă ${formatPrintNumber(invoicesTotalQty, 0)} |
-              Cofraje ${formatPrintNumber(invoicesTotalCofrage, 2)} |
-              Fără TVA ${formatPrintNumber(invoicesTotalWithoutVat, 2)} |
-              Cu TVA ${formatPrintNumber(invoicesTotalValue, 2)}
-            </div>
-          </div>
-
-          <div class="section">
-            <h2>Chitanțe ${printDateLabel}</h2>
-            <table class="receipts-table">
-              <thead>
-                <tr>
-                  <th>Cod ext</th>
-                  <th class="right">Încasat</th>
-                </tr>
-              </thead>
-              <tbody>${receiptsRows || "<tr><td colspan=\"2\">Fără date</td></tr>"}</tbody>
-            </table>
-            <div class="totals">
-              <strong>Total chitanțe:</strong>
-              Total chitanțe pe zi ${collections.receipts_today_invoices_count} |
-              Total chitanțe pe solduri ${collections.receipts_previous_debt_count} |
-              Total încasat pe zi ${formatPrintNumber(receiptsTotalDay, 2)} |
-              Total din solduri ${formatPrintNumber(receiptsTotalDebt, 2)} |
-              Total general ${formatPrintNumber(receiptsGrandTotal, 2)}
-            </div>
-          </div>
-        </body>
-      </html>
-    `;
-
     try {
       const selectedPrinter = typeof window !== "undefined"
         ? localStorage.getItem("selectedPrinter")
         : null;
-      const path = await printReportHtml("centralizator_zi_vanzari", htmlDocument, selectedPrinter || undefined);
+      const path = await printSalesSummary(
+        salesStartDate || undefined,
+        salesEndDate || undefined,
+        selectedPrinter || undefined
+      );
       toast.success(`Raport salvat și trimis la print: ${path}`);
     } catch (error) {
-      console.error("Failed to print sales report html:", error);
-      toast.error("Raportul de vânzări nu a putut fi trimis la print");
+      console.error("Failed to print sales summary:", error);
+      toast.error(`Raportul de vânzări nu a putut fi trimis la print: ${String(error)}`);
     }
   };
 
@@ -500,6 +297,12 @@ export default function ReportsPage() {
           </CardContent>
         </Card>
       </div>
+
+      <CashRegisterCard date={salesEndDate || today} />
+
+      <AgingReportCard date={today} />
+
+      <BalanceConfirmationsCard />
     </div>
   );
 }
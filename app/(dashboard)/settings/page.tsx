@@ -2,15 +2,15 @@
 
 import { useState, useEffect, useRef } from "react";
 import { useRouter } from "next/navigation";
-import { Settings, Loader2, Printer, FileText, User, RefreshCw, Server, AlertTriangle } from "lucide-react";
+import { Settings, Loader2, Printer, FileText, User, RefreshCw, Server, AlertTriangle, Banknote } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
 import { Switch } from "@/components/ui/switch";
-import { getAvailablePrinters, getAgentSettings, saveAgentSettings, deletePartnersAndLocations } from "@/lib/tauri/commands";
-import type { AgentSettings } from "@/lib/tauri/types";
+import { getAvailablePrinters, getAgentSettings, saveAgentSettings, deletePartnersAndLocations, getPrintBarcode, setPrintBarcode, getCashLimitRules, saveCashLimitRule, getNumberRanges, saveNumberRange, getNumberingWarnings } from "@/lib/tauri/commands";
+import type { AgentSettings, CashLimitRule, CashLimitAction, NumberRange, NumberRangeStatus, NumberedDocumentType } from "@/lib/tauri/types";
 import { toast } from "sonner";
 import { useSyncStatus } from "@/hooks/useSyncStatus";
 import { useOnlineStatus } from "@/hooks/useOnlineStatus";
@@ -65,6 +65,10 @@ export default function SettingsPage() {
     wme_port: null,
   });
   const [savingAgent, setSavingAgent] = useState(false);
+  const [printBarcode, setPrintBarcodeState] = useState(false);
+  const [cashLimitRules, setCashLimitRules] = useState<CashLimitRule[]>([]);
+  const [numberRanges, setNumberRanges] = useState<NumberRange[]>([]);
+  const [numberingWarnings, setNumberingWarnings] = useState<string[]>([]);
   const [loadingAgentSettings, setLoadingAgentSettings] = useState(true);
   const savedMarcaAgentRef = useRef<string>("");
 
@@ -111,11 +115,37 @@ export default function SettingsPage() {
     loadSettings();
     loadCachedPrinters();
     loadAgentSettings();
+    getPrintBarcode().then(setPrintBarcodeState).catch((e) => console.error("Failed to load barcode setting:", e));
+    getCashLimitRules().then(setCashLimitRules).catch((e) => console.error("Failed to load cash limit rules:", e));
+    loadNumberRanges();
 
     // Load printers - this is the slow operation
     loadPrinters();
   }, []);
 
+  const handlePrintBarcodeChange = async (enabled: boolean) => {
+    try {
+      setPrintBarcodeState(await setPrintBarcode(enabled));
+    } catch (error) {
+      console.error("Failed to save barcode setting:", error);
+      toast.error(`Eroare la salvarea setării: ${error}`);
+    }
+  };
+
+  const updateCashLimitRule = (id: number, changes: Partial<CashLimitRule>) => {
+    setCashLimitRules((rules) => rules.map((rule) => (rule.id === id ? { ...rule, ...changes } : rule)));
+  };
+
+  const handleSaveCashLimitRule = async (rule: CashLimitRule) => {
+    try {
+      setCashLimitRules(await saveCashLimitRule(rule));
+      toast.success("Plafonul a fost salvat");
+    } catch (error) {
+      console.error("Failed to save cash limit rule:", error);
+      toast.error(`Eroare la salvarea plafonului: ${error}`);
+    }
+  };
+
   const loadAgentSettings = async () => {
     setLoadingAgentSettings(true);
     try {
@@ -129,6 +159,51 @@ export default function SettingsPage() {
     }
   };
 
+  const loadNumberRanges = async () => {
+    try {
+      setNumberRanges(await getNumberRanges());
+      setNumberingWarnings(await getNumberingWarnings());
+    } catch (error) {
+      console.error("Failed to load number ranges:", error);
+    }
+  };
+
+  const updateNumberRange = (index: number, changes: Partial<NumberRange>) => {
+    setNumberRanges((ranges) => ranges.map((range, i) => (i === index ? { ...range, ...changes } : range)));
+  };
+
+  const addNumberRange = (documentType: NumberedDocumentType) => {
+    const last = numberRanges.filter((range) => range.document_type === documentType).pop();
+    const start = last?.range_end ? last.range_end + 1 : 1;
+    setNumberRanges((ranges) => [
+      ...ranges,
+      {
+        id: 0,
+        document_type: documentType,
+        series: last?.series ?? "",
+        range_start: start,
+        range_end: null,
+        next_number: start,
+        position: 0,
+        status: "active",
+        remaining: null,
+        created_at: "",
+      },
+    ]);
+  };
+
+  const handleSaveNumberRange = async (range: NumberRange) => {
+    try {
+      await saveNumberRange(range);
+      await loadNumberRanges();
+      await loadAgentSettings();
+      toast.success("Carnetul a fost salvat");
+    } catch (error) {
+      console.error("Failed to save number range:", error);
+      toast.error(`Eroare la salvarea carnetului: ${error}`);
+    }
+  };
+
   const handleSaveAgentSettings = async () => {
     setSavingAgent(true);
     try {
@@ -186,6 +261,7 @@ export default function SettingsPage() {
 
       // Reload settings to update UI with latest values from database
       await loadAgentSettings();
+      await loadNumberRanges();
       toast.success("Setările agentului au fost salvate!");
     } catch (error) {
       console.error("Failed to save agent settings:", error);
@@ -243,6 +319,10 @@ export default function SettingsPage() {
         const parsed = JSON.parse(saved);
         if (parsed.printer && list.includes(parsed.printer)) {
           setSettings(prev => ({ ...prev, printer: parsed.printer }));
+        } else if (parsed.printer === "Default") {
+          // Placeholder of older versions when no printer was found, not a printer
+          setSettings(prev => ({ ...prev, printer: list[0] ?? "" }));
+          localStorage.setItem("selectedPrinter", list[0] ?? "");
         } else if (list.length > 0 && !parsed.printer) {
           setSettings(prev => ({ ...prev, printer: list[0] }));
         }
@@ -254,7 +334,7 @@ export default function SettingsPage() {
       // Only show error if we don't have cached printers
       if (printers.length === 0) {
         toast.error("Eroare la încărcarea imprimantelor");
-        setPrinters(["Default"]);
+        setPrinters([]);
       }
     } finally {
       setLoadingPrinters(false);
@@ -1011,6 +1091,23 @@ export default function SettingsPage() {
                   />
                 </div>
 
+                {/* Barcode Toggle */}
+                <div className="flex items-center justify-between space-x-2 rounded-lg border p-4">
+                  <div className="space-y-0.5">
+                    <Label htmlFor="printBarcode" className="text-base cursor-pointer">
+                      Cod de bare pe documente
+                    </Label>
+                    <p className="text-sm text-muted-foreground">
+                      Adaugă sub codul QR un cod de bare (serie și număr) pe facturi, chitanțe și certificate
+                    </p>
+                  </div>
+                  <Switch
+                    id="printBarcode"
+                    checked={printBarcode}
+                    onCheckedChange={handlePrintBarcodeChange}
+                  />
+                </div>
+
                 {/* Action Buttons */}
                 <div className="flex gap-3">
                   <Button onClick={handleSaveSettings} className="flex-1">
@@ -1031,6 +1128,160 @@ export default function SettingsPage() {
           </CardContent>
         </Card>
 
+        {/* Cash Limits */}
+        <Card>
+          <CardHeader>
+            <CardTitle className="flex items-center gap-2">
+              <Banknote className="h-5 w-5" />
+              Plafoane încasări numerar
+            </CardTitle>
+            <CardDescription>
+              Limitele legale pentru încasările în numerar de la un partener (Legea 70/2015)
+            </CardDescription>
+          </CardHeader>
+          <CardContent className="space-y-3">
+            {cashLimitRules.map((rule) => (
+              <div key={rule.id} className="flex flex-wrap items-end gap-3 rounded-lg border p-4">
+                <div className="flex-1 min-w-[180px] space-y-0.5">
+                  <Label className="text-base">
+                    {rule.partner_type === "individual" ? "Persoană fizică" : "Persoană juridică"}
+                  </Label>
+                  <p className="text-sm text-muted-foreground">
+                    {rule.scope === "per_day" ? "Total pe zi de la același partener" : "Total pe o factură (plata nu se fracționează)"}
+                  </p>
+                </div>
+                <div className="w-32 space-y-1">
+                  <Label htmlFor={`cash-limit-${rule.id}`}>Plafon (lei)</Label>
+                  <Input
+                    id={`cash-limit-${rule.id}`}
+                    type="number"
+                    min="0"
+                    step="0.01"
+                    value={rule.limit_amount}
+                    onChange={(e) => updateCashLimitRule(rule.id, { limit_amount: parseFloat(e.target.value) || 0 })}
+                  />
+                </div>
+                <div className="w-40 space-y-1">
+                  <Label>La depășire</Label>
+                  <Select
+                    value={rule.action}
+                    onValueChange={(v) => updateCashLimitRule(rule.id, { action: v as CashLimitAction })}
+                  >
+                    <SelectTrigger>
+                      <SelectValue />
+                    </SelectTrigger>
+                    <SelectContent>
+                      <SelectItem value="block">Blochează</SelectItem>
+                      <SelectItem value="warn">Avertizează</SelectItem>
+                      <SelectItem value="off">Nu verifica</SelectItem>
+                    </SelectContent>
+                  </Select>
+                </div>
+                <Button variant="outline" onClick={() => handleSaveCashLimitRule(rule)}>
+                  Salvează
+                </Button>
+              </div>
+            ))}
+          </CardContent>
+        </Card>
+
+        {/* Number Carnets */}
+        <Card>
+          <CardHeader>
+            <CardTitle className="flex items-center gap-2">
+              <FileText className="h-5 w-5" />
+              Carnete facturi și chitanțe
+            </CardTitle>
+            <CardDescription>
+              Carnetele se folosesc în ordine; când unul se termină, numerotarea continuă automat cu următorul
+            </CardDescription>
+          </CardHeader>
+          <CardContent className="space-y-3">
+            {numberingWarnings.map((warning) => (
+              <div key={warning} className="flex items-center gap-2 rounded-lg border border-amber-300 bg-amber-50 p-3 text-sm text-amber-800">
+                <AlertTriangle className="h-4 w-4 shrink-0" />
+                {warning}
+              </div>
+            ))}
+            {(["invoice", "receipt"] as NumberedDocumentType[]).map((documentType) => (
+              <div key={documentType} className="space-y-2">
+                <div className="flex items-center justify-between">
+                  <Label className="text-base">{documentType === "invoice" ? "Facturi" : "Chitanțe"}</Label>
+                  <Button variant="outline" size="sm" onClick={() => addNumberRange(documentType)}>
+                    Adaugă carnet
+                  </Button>
+                </div>
+                {numberRanges.every((range) => range.document_type !== documentType) && (
+                  <p className="text-sm text-muted-foreground">
+                    {documentType === "invoice"
+                      ? "Niciun carnet configurat."
+                      : "Niciun carnet configurat; chitanțele sunt numerotate după dată și oră."}
+                  </p>
+                )}
+                {numberRanges.map((range, index) =>
+                  range.document_type !== documentType ? null : (
+                    <div key={`${range.id}-${index}`} className="flex flex-wrap items-end gap-3 rounded-lg border p-4">
+                      <div className="w-24 space-y-1">
+                        <Label>Serie</Label>
+                        <Input value={range.series} onChange={(e) => updateNumberRange(index, { series: e.target.value })} />
+                      </div>
+                      <div className="w-28 space-y-1">
+                        <Label>De la</Label>
+                        <Input
+                          type="number"
+                          min="1"
+                          value={range.range_start}
+                          onChange={(e) => updateNumberRange(index, { range_start: parseInt(e.target.value) || 1 })}
+                        />
+                      </div>
+                      <div className="w-28 space-y-1">
+                        <Label>Până la</Label>
+                        <Input
+                          type="number"
+                          min="1"
+                          value={range.range_end ?? ""}
+                          onChange={(e) => updateNumberRange(index, { range_end: e.target.value ? parseInt(e.target.value) : null })}
+                        />
+                      </div>
+                      <div className="w-28 space-y-1">
+                        <Label>Următorul</Label>
+                        <Input
+                          type="number"
+                          min="1"
+                          value={range.next_number}
+                          onChange={(e) => updateNumberRange(index, { next_number: parseInt(e.target.value) || 1 })}
+                        />
+                      </div>
+                      <div className="w-36 space-y-1">
+                        <Label>Stare</Label>
+                        <Select
+                          value={range.status}
+                          onValueChange={(v) => updateNumberRange(index, { status: v as NumberRangeStatus })}
+                        >
+                          <SelectTrigger>
+                            <SelectValue />
+                          </SelectTrigger>
+                          <SelectContent>
+                            <SelectItem value="active">Activ</SelectItem>
+                            <SelectItem value="exhausted">Epuizat</SelectItem>
+                            <SelectItem value="disabled">Dezactivat</SelectItem>
+                          </SelectContent>
+                        </Select>
+                      </div>
+                      {range.remaining !== null && range.id !== 0 && (
+                        <p className="pb-2 text-sm text-muted-foreground">{range.remaining} numere rămase</p>
+                      )}
+                      <Button variant="outline" onClick={() => handleSaveNumberRange(range)}>
+                        Salvează
+                      </Button>
+                    </div>
+                  )
+                )}
+              </div>
+            ))}
+          </CardContent>
+        </Card>
+
         {/* Info Section */}
         <Card>
           <CardHeader>
@@ -1,7 +1,7 @@
 "use client";
 
 import { Badge } from "@/components/ui/badge";
-import { Loader2, Check, X, Clock } from "lucide-react";
+import { Loader2, Check, X, Clock, Ban } from "lucide-react";
 import type { CollectionStatus } from "@/lib/tauri/types";
 
 interface CollectionStatusBadgeProps {
@@ -38,6 +38,12 @@ const statusConfig: Record<CollectionStatus, {
         icon: X,
         className: "bg-red-100 text-red-800 dark:bg-red-900/30 dark:text-red-400 border-red-200 dark:border-red-800",
     },
+    cancelled: {
+        label: "Anulată",
+        variant: "outline",
+        icon: Ban,
+        className: "bg-gray-100 text-gray-700 dark:bg-gray-800/50 dark:text-gray-400 border-gray-300 dark:border-gray-700 line-through",
+    },
 };
 
 export function CollectionStatusBadge({ status }: CollectionStatusBadgeProps) {
@@ -0,0 +1,149 @@
+"use client";
+
+import {
+    Dialog,
+    DialogContent,
+    DialogHeader,
+    DialogTitle,
+    DialogFooter,
+} from "@/components/ui/dialog";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+import { ScrollArea } from "@/components/ui/scroll-area";
+import { toast } from "sonner";
+import { useState } from "react";
+import { format } from "date-fns";
+import { Loader2, Printer, FileText } from "lucide-react";
+import type { PartnerStatement } from "@/lib/tauri/types";
+import { generatePartnerStatement, printPartnerStatement } from "@/lib/tauri/commands";
+import { cn, formatCurrency } from "@/lib/utils";
+
+interface PartnerStatementDialogProps {
+    partnerId: string;
+    partnerName: string;
+    open: boolean;
+    onOpenChange: (open: boolean) => void;
+}
+
+export function PartnerStatementDialog({
+    partnerId,
+    partnerName,
+    open,
+    onOpenChange,
+}: PartnerStatementDialogProps) {
+    const today = new Date();
+    const [from, setFrom] = useState(format(new Date(today.getFullYear(), 0, 1), "yyyy-MM-dd"));
+    const [to, setTo] = useState(format(today, "yyyy-MM-dd"));
+    const [statement, setStatement] = useState<PartnerStatement | null>(null);
+    const [loading, setLoading] = useState(false);
+    const [isPrinting, setIsPrinting] = useState(false);
+
+    const handleGenerate = async () => {
+        setLoading(true);
+        try {
+            setStatement(await generatePartnerStatement(partnerId, from, to));
+        } catch (error) {
+            console.error("Partner statement error:", error);
+            toast.error(`Fișa de cont nu a putut fi generată: ${String(error)}`);
+        } finally {
+            setLoading(false);
+        }
+    };
+
+    const handlePrint = async () => {
+        setIsPrinting(true);
+        try {
+            const selectedPrinter = typeof window !== "undefined" ? localStorage.getItem("selectedPrinter") : null;
+            toast.success(await printPartnerStatement(partnerId, from, to, selectedPrinter || undefined));
+        } catch (error) {
+            console.error("Print partner statement error:", error);
+            toast.error(`Eroare la imprimarea fișei de cont: ${String(error)}`);
+        } finally {
+            setIsPrinting(false);
+        }
+    };
+
+    return (
+        <Dialog open={open} onOpenChange={onOpenChange}>
+            <DialogContent className="max-w-3xl">
+                <DialogHeader>
+                    <DialogTitle>Fișă de cont • {partnerName}</DialogTitle>
+                </DialogHeader>
+
+                <div className="flex flex-wrap items-end gap-2">
+                    <div className="space-y-1">
+                        <Label htmlFor="statement-from" className="text-xs">De la</Label>
+                        <Input id="statement-from" type="date" value={from} onChange={(e) => setFrom(e.target.value)} className="h-9" />
+                    </div>
+                    <div className="space-y-1">
+                        <Label htmlFor="statement-to" className="text-xs">Până la</Label>
+                        <Input id="statement-to" type="date" value={to} onChange={(e) => setTo(e.target.value)} className="h-9" />
+                    </div>
+                    <Button variant="outline" className="h-9 gap-2" onClick={handleGenerate} disabled={loading || !from || !to}>
+                        {loading ? <Loader2 className="h-4 w-4 animate-spin" /> : <FileText className="h-4 w-4" />}
+                        Generează
+                    </Button>
+                </div>
+
+                {statement && (
+                    <div className="space-y-2">
+                        <ScrollArea className="h-72 rounded-md border">
+                            <table className="w-full text-sm">
+                                <thead className="bg-muted/40 text-left">
+                                    <tr>
+                                        <th className="p-2">Data</th>
+                                        <th className="p-2">Document</th>
+                                        <th className="p-2">Explicații</th>
+                                        <th className="p-2 text-right">Debit</th>
+                                        <th className="p-2 text-right">Credit</th>
+                                        <th className="p-2 text-right">Sold</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    <tr className="border-t">
+                                        <td className="p-2 text-muted-foreground" colSpan={5}>Sold inițial</td>
+                                        <td className="p-2 text-right">{formatCurrency(statement.opening_balance)}</td>
+                                    </tr>
+                                    {statement.lines.map((line, idx) => (
+                                        <tr key={`${line.date}-${line.document}-${idx}`} className="border-t">
+                                            <td className="p-2 whitespace-nowrap">{line.date}</td>
+                                            <td className="p-2">{line.document}</td>
+                                            <td className="p-2">
+                                                {line.description}
+                                                {line.days_overdue ? (
+                                                    <span className="ml-1 text-xs text-red-600">({line.days_overdue} zile întârziere)</span>
+                                                ) : null}
+                                            </td>
+                                            <td className="p-2 text-right">{line.debit ? formatCurrency(line.debit) : ""}</td>
+                                            <td className="p-2 text-right">{line.credit ? formatCurrency(line.credit) : ""}</td>
+                                            <td className="p-2 text-right">{formatCurrency(line.balance)}</td>
+                                        </tr>
+                                    ))}
+                                </tbody>
+                            </table>
+                        </ScrollArea>
+                        <div className="flex flex-wrap justify-end gap-4 text-sm">
+                            <span>
+                                Sold final: <span className="font-semibold">{formatCurrency(statement.closing_balance)}</span>
+                            </span>
+                            <span className={cn(statement.overdue_amount > 0 && "text-red-600")}>
+                                Restant: <span className="font-semibold">{formatCurrency(statement.overdue_amount)}</span>
+                            </span>
+                        </div>
+                    </div>
+                )}
+
+                <DialogFooter>
+                    <Button variant="outline" onClick={() => onOpenChange(false)}>
+                        Închide
+                    </Button>
+                    <Button onClick={handlePrint} disabled={isPrinting || !from || !to} className="gap-2">
+                        {isPrinting ? <Loader2 className="h-4 w-4 animate-spin" /> : <Printer className="h-4 w-4" />}
+                        Printează
+                    </Button>
+                </DialogFooter>
+            </DialogContent>
+        </Dialog>
+    );
+}
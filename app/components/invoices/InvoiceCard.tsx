@@ -4,38 +4,29 @@ import { useState } from "react";
 import { Card, CardContent, CardFooter, CardHeader } from "@/components/ui/card";
 import { Button } from "@/components/ui/button";
 import { InvoiceStatusBadge } from "./InvoiceStatusBadge";
-import { Send, Trash2, Eye, RotateCcw, MapPin, Package, Printer, Loader2, XCircle, FileText } from "lucide-react";
+import { Send, Trash2, Eye, RotateCcw, MapPin, Package, Printer, Loader2, XCircle, FileText, Undo2 } from "lucide-react";
 import type { Invoice } from "@/lib/tauri/types";
 import { cancelInvoiceSending } from "@/lib/tauri/commands";
 import { toast } from "sonner";
 import { usePrintInvoice } from "@/hooks/usePrintInvoice";
 import { formatCurrency, formatDateTime } from "@/lib/utils";
-import {
-  AlertDialog,
-  AlertDialogAction,
-  AlertDialogCancel,
-  AlertDialogContent,
-  AlertDialogDescription,
-  AlertDialogFooter,
-  AlertDialogHeader,
-  AlertDialogTitle,
-  AlertDialogTrigger,
-} from "@/components/ui/alert-dialog";
 
 interface InvoiceCardProps {
   invoice: Invoice;
   onSend: (id: string) => void;
   onDelete: (id: string) => void;
+  onRestore?: (id: string) => void;
   onView: (id: string) => void;
   onCancel?: (id: string) => void;
   isAdmin?: boolean; // Add admin check
 }
 
-export function InvoiceCard({ invoice, onSend, onDelete, onView, onCancel, isAdmin = true }: InvoiceCardProps) {
+export function InvoiceCard({ invoice, onSend, onDelete, onRestore, onView, onCancel, isAdmin = true }: InvoiceCardProps) {
   const [isCancelling, setIsCancelling] = useState(false);
   const { printInvoice, isPrinting, receiptDialog } = usePrintInvoice();
   const canSend = invoice.status === "pending" || invoice.status === "failed";
-  const canDelete = isAdmin; // Only admin can delete
+  const canDelete = isAdmin && invoice.status !== "cancelled" && invoice.status !== "sending"; // Only admin can cancel
+  const canRestore = isAdmin && !!onRestore && invoice.status === "cancelled" && !invoice.sent_at; // Never reached WME
   const isSending = invoice.status === "sending";
 
   const handleCancel = async () => {
@@ -163,35 +154,26 @@ export function InvoiceCard({ invoice, onSend, onDelete, onView, onCancel, isAdm
             </Button>
           )}
 
+          {canRestore && (
+            <Button
+              variant="ghost"
+              className="h-9 w-9 p-0 flex-shrink-0"
+              title="Restaurează factura"
+              onClick={() => onRestore?.(invoice.id)}
+            >
+              <Undo2 className="h-3.5 w-3.5" />
+            </Button>
+          )}
+
           {canDelete && (
-            <AlertDialog>
-              <AlertDialogTrigger asChild>
-                <Button
-                  variant="ghost"
-                  className="h-9 w-9 p-0 flex-shrink-0 text-red-600 hover:text-red-700 hover:bg-red-50 dark:text-red-400 dark:hover:bg-red-900/20"
-                  title="Șterge factura"
-                >
-                  <Trash2 className="h-3.5 w-3.5" />
-                </Button>
-              </AlertDialogTrigger>
-              <AlertDialogContent>
-                <AlertDialogHeader>
-                  <AlertDialogTitle>Șterge factura?</AlertDialogTitle>
-                  <AlertDialogDescription>
-                    Această acțiune nu poate fi anulată. Factura va fi ștearsă permanent.
-                  </AlertDialogDescription>
-                </AlertDialogHeader>
-                <AlertDialogFooter>
-                  <AlertDialogCancel>Anulează</AlertDialogCancel>
-                  <AlertDialogAction
-                    onClick={() => onDelete(invoice.id)}
-                    className="bg-red-600 hover:bg-red-700"
-                  >
-                    Șterge
-                  </AlertDialogAction>
-                </AlertDialogFooter>
-              </AlertDialogContent>
-            </AlertDialog>
+            <Button
+              variant="ghost"
+              className="h-9 w-9 p-0 flex-shrink-0 text-red-600 hover:text-red-700 hover:bg-red-50 dark:text-red-400 dark:hover:bg-red-900/20"
+              title="Anulează factura"
+              onClick={() => onDelete(invoice.id)}
+            >
+              <Trash2 className="h-3.5 w-3.5" />
+            </Button>
           )}
         </CardFooter>
       </Card>
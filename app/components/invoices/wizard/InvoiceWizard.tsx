@@ -8,7 +8,14 @@ import { PartnerStep } from "./PartnerStep";
 import { LocationStep } from "./LocationStep";
 import { ProductsStep } from "./ProductsStep";
 import { ReviewStep } from "./ReviewStep";
-import { createInvoice, sendInvoice } from "@/lib/tauri/commands";
+import {
+  createInvoice,
+  sendInvoice,
+  getPartnerAdvances,
+  applyPartnerAdvances,
+  sendAdvanceApplications,
+  getNumberingWarnings,
+} from "@/lib/tauri/commands";
 import { toast } from "sonner";
 import {
   ArrowLeft,
@@ -135,6 +142,8 @@ export function InvoiceWizard() {
       // Create the invoice
       const invoice = await createInvoice(request);
       toast.success("Factura a fost creată cu succes!");
+      getNumberingWarnings().then((warnings) => warnings.forEach((warning) => toast.warning(warning))).catch(console.error);
+      offerPartnerAdvance(selectedPartner.id);
 
       // Print invoice and open receipt flow. Navigate after receipt flow ends.
       await printInvoice(invoice.id, () => router.push("/invoices"));
@@ -173,6 +182,38 @@ export function InvoiceWizard() {
     }
   };
 
+  // A partner who paid in advance gets the advance offered against the new invoice
+  const offerPartnerAdvance = async (partnerId: string) => {
+    try {
+      const advances = await getPartnerAdvances(partnerId);
+      const available = advances.reduce((sum, advance) => sum + advance.available, 0);
+      if (available <= 0.005) return;
+
+      toast.info(`Partenerul are un avans disponibil de ${available.toFixed(2)} lei.`, {
+        duration: 15000,
+        action: {
+          label: "Compensează",
+          onClick: async () => {
+            try {
+              const applications = await applyPartnerAdvances(partnerId);
+              if (applications.length === 0) {
+                toast.info("Nu există facturi pe care să se compenseze avansul.");
+                return;
+              }
+              const applied = applications.reduce((sum, application) => sum + application.valoare, 0);
+              toast.success(`Avans de ${applied.toFixed(2)} lei compensat.`);
+              await sendAdvanceApplications(applications[0].group_id);
+            } catch (e) {
+              toast.error(`Eroare la compensarea avansului: ${String(e)}`);
+            }
+          },
+        },
+      });
+    } catch (e) {
+      console.error("Failed to load partner advances:", e);
+    }
+  };
+
   return (
     <>
     <div className="space-y-3">
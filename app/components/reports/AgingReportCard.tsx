@@ -0,0 +1,178 @@
+"use client";
+
+import { useCallback, useEffect, useState } from "react";
+import { Download, Hourglass, Loader2, Printer } from "lucide-react";
+import { toast } from "sonner";
+import { Button } from "@/components/ui/button";
+import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
+import {
+  Table,
+  TableBody,
+  TableCell,
+  TableHead,
+  TableHeader,
+  TableRow,
+} from "@/components/ui/table";
+import { exportAgingReport, getAgingReport, printAgingReport } from "@/lib/tauri/commands";
+import type { AgingBuckets, AgingGroupBy, AgingReport } from "@/lib/tauri/types";
+import { formatCurrency } from "@/lib/utils";
+
+const GROUPS: { value: AgingGroupBy; label: string; column: string }[] = [
+  { value: "partner", label: "Pe partener", column: "Partener" },
+  { value: "location", label: "Pe sediu", column: "Partener / sediu" },
+  { value: "agent", label: "Pe agent", column: "Agent" },
+];
+
+const BUCKETS: { key: keyof AgingBuckets; label: string }[] = [
+  { key: "current", label: "Curent" },
+  { key: "days_1_30", label: "1-30" },
+  { key: "days_31_60", label: "31-60" },
+  { key: "days_61_90", label: "61-90" },
+  { key: "days_over_90", label: "> 90" },
+];
+
+interface AgingReportCardProps {
+  date: string; // YYYY-MM-DD
+}
+
+export function AgingReportCard({ date }: AgingReportCardProps) {
+  const [asOf, setAsOf] = useState(date);
+  const [groupBy, setGroupBy] = useState<AgingGroupBy>("partner");
+  const [report, setReport] = useState<AgingReport | null>(null);
+  const [loading, setLoading] = useState(true);
+  const [busy, setBusy] = useState(false);
+
+  const selectedPrinter = () =>
+    typeof window !== "undefined" ? localStorage.getItem("selectedPrinter") || undefined : undefined;
+
+  const load = useCallback(async () => {
+    setLoading(true);
+    try {
+      setReport(await getAgingReport(asOf || undefined, groupBy));
+    } catch (error) {
+      console.error("Failed to load aging report:", error);
+      toast.error(`Raportul de vechime nu a putut fi încărcat: ${String(error)}`);
+    } finally {
+      setLoading(false);
+    }
+  }, [asOf, groupBy]);
+
+  useEffect(() => {
+    load();
+  }, [load]);
+
+  const handlePrint = async () => {
+    setBusy(true);
+    try {
+      toast.success(await printAgingReport(asOf || undefined, groupBy, selectedPrinter()));
+    } catch (error) {
+      toast.error(`Raportul de vechime nu a putut fi printat: ${String(error)}`);
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleExport = async () => {
+    setBusy(true);
+    try {
+      const path = await exportAgingReport(asOf || undefined, groupBy);
+      toast.success(`Raport exportat: ${path}`);
+    } catch (error) {
+      toast.error(`Raportul de vechime nu a putut fi exportat: ${String(error)}`);
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const amount = (value: number) => (Math.abs(value) < 0.005 ? "" : formatCurrency(value));
+
+  return (
+    <Card>
+      <CardHeader>
+        <CardTitle className="flex items-center gap-2">
+          <Hourglass className="h-5 w-5" />
+          Vechimea creanțelor
+        </CardTitle>
+        <CardDescription>
+          {report
+            ? `Restant: ${formatCurrency(report.totals.total - report.totals.current)} din ${formatCurrency(report.totals.total)}`
+            : "Solduri deschise pe zile de întârziere"}
+        </CardDescription>
+        <div className="flex flex-wrap items-center gap-2 pt-1">
+          <Input type="date" value={asOf} onChange={(e) => setAsOf(e.target.value)} className="h-10 w-44" />
+          <Select value={groupBy} onValueChange={(v) => setGroupBy(v as AgingGroupBy)}>
+            <SelectTrigger className="h-10 w-40">
+              <SelectValue />
+            </SelectTrigger>
+            <SelectContent>
+              {GROUPS.map((group) => (
+                <SelectItem key={group.value} value={group.value}>
+                  {group.label}
+                </SelectItem>
+              ))}
+            </SelectContent>
+          </Select>
+          <Button variant="outline" onClick={handlePrint} disabled={busy || loading} className="gap-2">
+            <Printer className="h-4 w-4" />
+            Printează
+          </Button>
+          <Button variant="outline" onClick={handleExport} disabled={busy || loading} className="gap-2">
+            <Download className="h-4 w-4" />
+            Export CSV
+          </Button>
+        </div>
+      </CardHeader>
+      <CardContent>
+        {loading || !report ? (
+          <div className="h-24 flex items-center justify-center">
+            <Loader2 className="h-5 w-5 animate-spin text-muted-foreground" />
+          </div>
+        ) : report.rows.length === 0 ? (
+          <p className="text-sm text-muted-foreground">Nu există solduri deschise.</p>
+        ) : (
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>{GROUPS.find((group) => group.value === report.group_by)?.column}</TableHead>
+                <TableHead className="text-right">Zile</TableHead>
+                {BUCKETS.map((bucket) => (
+                  <TableHead key={bucket.key} className="text-right">
+                    {bucket.label}
+                  </TableHead>
+                ))}
+                <TableHead className="text-right">Total</TableHead>
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {report.rows.map((row) => (
+                <TableRow key={row.key}>
+                  <TableCell>{row.name}</TableCell>
+                  <TableCell className={row.max_days_overdue > 90 ? "text-right text-red-600 font-medium" : "text-right"}>
+                    {row.max_days_overdue > 0 ? row.max_days_overdue : ""}
+                  </TableCell>
+                  {BUCKETS.map((bucket) => (
+                    <TableCell key={bucket.key} className="text-right">
+                      {amount(row[bucket.key])}
+                    </TableCell>
+                  ))}
+                  <TableCell className="text-right font-medium">{formatCurrency(row.total)}</TableCell>
+                </TableRow>
+              ))}
+              <TableRow>
+                <TableCell colSpan={2} className="font-semibold">TOTAL</TableCell>
+                {BUCKETS.map((bucket) => (
+                  <TableCell key={bucket.key} className="text-right font-semibold">
+                    {formatCurrency(report.totals[bucket.key])}
+                  </TableCell>
+                ))}
+                <TableCell className="text-right font-semibold">{formatCurrency(report.totals.total)}</TableCell>
+              </TableRow>
+            </TableBody>
+          </Table>
+        )}
+      </CardContent>
+    </Card>
+  );
+}
@@ -0,0 +1,222 @@
+"use client";
+
+import { useCallback, useEffect, useState } from "react";
+import { Check, FileSignature, Loader2, Printer, RotateCcw, X } from "lucide-react";
+import { toast } from "sonner";
+import { Button } from "@/components/ui/button";
+import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+import {
+  Table,
+  TableBody,
+  TableCell,
+  TableHead,
+  TableHeader,
+  TableRow,
+} from "@/components/ui/table";
+import {
+  generateBalanceConfirmations,
+  getBalanceConfirmations,
+  printBalanceConfirmations,
+  recordBalanceConfirmationReturn,
+} from "@/lib/tauri/commands";
+import type { BalanceConfirmation, BalanceConfirmationStatus } from "@/lib/tauri/types";
+import { formatCurrency } from "@/lib/utils";
+
+const STATUS_LABELS: Record<BalanceConfirmationStatus, string> = {
+  sent: "Așteaptă semnătura",
+  confirmed: "Confirmat",
+  disputed: "Contestat",
+};
+
+// Year-end letters are usually for 31 December of the previous year
+function defaultReferenceDate(): string {
+  return `${new Date().getFullYear() - 1}-12-31`;
+}
+
+export function BalanceConfirmationsCard() {
+  const [referenceDate, setReferenceDate] = useState(defaultReferenceDate);
+  const [letters, setLetters] = useState<BalanceConfirmation[]>([]);
+  const [loading, setLoading] = useState(true);
+  const [busy, setBusy] = useState(false);
+
+  const selectedPrinter = () =>
+    typeof window !== "undefined" ? localStorage.getItem("selectedPrinter") || undefined : undefined;
+
+  const load = useCallback(async () => {
+    if (!referenceDate) return;
+    setLoading(true);
+    try {
+      setLetters(await getBalanceConfirmations(referenceDate));
+    } catch (error) {
+      console.error("Failed to load balance confirmations:", error);
+      toast.error(`Confirmările de sold nu au putut fi încărcate: ${String(error)}`);
+    } finally {
+      setLoading(false);
+    }
+  }, [referenceDate]);
+
+  useEffect(() => {
+    load();
+  }, [load]);
+
+  const handleGenerate = async () => {
+    setBusy(true);
+    try {
+      const generated = await generateBalanceConfirmations(referenceDate);
+      toast.success(`${generated.length} confirmări de sold generate.`);
+      await load();
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handlePrint = async (partnerId?: string) => {
+    setBusy(true);
+    try {
+      toast.success(await printBalanceConfirmations(referenceDate, partnerId, selectedPrinter()));
+    } catch (error) {
+      toast.error(`Confirmările de sold nu au putut fi printate: ${String(error)}`);
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleReturn = async (letter: BalanceConfirmation, status: BalanceConfirmationStatus) => {
+    let partnerBalance: number | undefined;
+    let notes: string | undefined;
+    if (status === "disputed") {
+      const answer = window.prompt(`Soldul comunicat de ${letter.partner_name}:`, letter.balance.toFixed(2));
+      if (answer === null) return;
+      partnerBalance = parseFloat(answer.replace(",", "."));
+      if (!isFinite(partnerBalance)) {
+        toast.error("Sold invalid");
+        return;
+      }
+      notes = window.prompt("Explicații (opțional):") || undefined;
+    }
+    setBusy(true);
+    try {
+      const updated = await recordBalanceConfirmationReturn(letter.id, status, partnerBalance, notes);
+      setLetters((prev) => prev.map((item) => (item.id === updated.id ? updated : item)));
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const returned = letters.filter((letter) => letter.return_status !== "sent").length;
+
+  return (
+    <Card>
+      <CardHeader>
+        <CardTitle className="flex items-center gap-2">
+          <FileSignature className="h-5 w-5" />
+          Confirmări de sold
+        </CardTitle>
+        <CardDescription>
+          {letters.length > 0
+            ? `${returned} din ${letters.length} returnate semnate`
+            : "Scrisori de confirmare a soldului pentru partenerii agentului"}
+        </CardDescription>
+        <div className="flex flex-wrap items-center gap-2 pt-1">
+          <Input
+            type="date"
+            value={referenceDate}
+            onChange={(e) => setReferenceDate(e.target.value)}
+            className="h-10 w-44"
+          />
+          <Button onClick={handleGenerate} disabled={busy || !referenceDate} className="gap-2">
+            {busy ? <Loader2 className="h-4 w-4 animate-spin" /> : <FileSignature className="h-4 w-4" />}
+            Generează
+          </Button>
+          <Button
+            variant="outline"
+            onClick={() => handlePrint()}
+            disabled={busy || letters.length === 0}
+            className="gap-2"
+          >
+            <Printer className="h-4 w-4" />
+            Printează nereturnate
+          </Button>
+        </div>
+      </CardHeader>
+      <CardContent>
+        {loading ? (
+          <div className="h-24 flex items-center justify-center">
+            <Loader2 className="h-5 w-5 animate-spin text-muted-foreground" />
+          </div>
+        ) : letters.length === 0 ? (
+          <p className="text-sm text-muted-foreground">Nu există confirmări pentru această dată.</p>
+        ) : (
+          <Table>
+            <TableHeader>
+              <TableRow>
+                <TableHead>Partener</TableHead>
+                <TableHead className="text-right">Documente</TableHead>
+                <TableHead className="text-right">Sold</TableHead>
+                <TableHead>Stare</TableHead>
+                <TableHead />
+              </TableRow>
+            </TableHeader>
+            <TableBody>
+              {letters.map((letter) => (
+                <TableRow key={letter.id}>
+                  <TableCell>{letter.partner_name}</TableCell>
+                  <TableCell className="text-right">{letter.documents.length}</TableCell>
+                  <TableCell className="text-right">{formatCurrency(letter.balance)}</TableCell>
+                  <TableCell>
+                    {STATUS_LABELS[letter.return_status]}
+                    {letter.return_status === "disputed" && letter.partner_balance !== null
+                      ? ` (${formatCurrency(letter.partner_balance)})`
+                      : ""}
+                  </TableCell>
+                  <TableCell className="text-right whitespace-nowrap">
+                    <Button variant="ghost" size="sm" onClick={() => handlePrint(letter.partner_id)} disabled={busy}>
+                      <Printer className="h-4 w-4" />
+                    </Button>
+                    {letter.return_status === "sent" ? (
+                      <>
+                        <Button
+                          variant="ghost"
+                          size="sm"
+                          title="Confirmat"
+                          onClick={() => handleReturn(letter, "confirmed")}
+                          disabled={busy}
+                        >
+                          <Check className="h-4 w-4" />
+                        </Button>
+                        <Button
+                          variant="ghost"
+                          size="sm"
+                          title="Contestat"
+                          onClick={() => handleReturn(letter, "disputed")}
+                          disabled={busy}
+                        >
+                          <X className="h-4 w-4" />
+                        </Button>
+                      </>
+                    ) : (
+                      <Button
+                        variant="ghost"
+                        size="sm"
+                        title="Anulează returul"
+                        onClick={() => handleReturn(letter, "sent")}
+                        disabled={busy}
+                      >
+                        <RotateCcw className="h-4 w-4" />
+                      </Button>
+                    )}
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        )}
+      </CardContent>
+    </Card>
+  );
+}
@@ -0,0 +1,307 @@
+"use client";
+
+import { useCallback, useEffect, useState } from "react";
+import { Loader2, Lock, Printer, Trash2, Wallet } from "lucide-react";
+import { toast } from "sonner";
+import { Button } from "@/components/ui/button";
+import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
+import {
+  Table,
+  TableBody,
+  TableCell,
+  TableHead,
+  TableHeader,
+  TableRow,
+} from "@/components/ui/table";
+import {
+  addCashMovement,
+  closeCashDay,
+  deleteCashMovement,
+  getCashRegister,
+  printCashRegister,
+  setCashRegisterStart,
+} from "@/lib/tauri/commands";
+import type { CashMovementKind, CashRegisterDay } from "@/lib/tauri/types";
+import { formatCurrency } from "@/lib/utils";
+
+const MOVEMENT_KINDS: { value: CashMovementKind; label: string }[] = [
+  { value: "handover", label: "Predare la sediu" },
+  { value: "deposit", label: "Depunere la bancă" },
+  { value: "expense", label: "Cheltuială" },
+];
+
+interface CashRegisterCardProps {
+  date: string; // YYYY-MM-DD
+}
+
+export function CashRegisterCard({ date }: CashRegisterCardProps) {
+  const [page, setPage] = useState<CashRegisterDay | null>(null);
+  const [loading, setLoading] = useState(true);
+  const [busy, setBusy] = useState(false);
+  const [kind, setKind] = useState<CashMovementKind>("handover");
+  const [amount, setAmount] = useState("");
+  const [description, setDescription] = useState("");
+  const [document, setDocument] = useState("");
+  const [startBalance, setStartBalance] = useState("");
+
+  const selectedPrinter = () =>
+    typeof window !== "undefined" ? localStorage.getItem("selectedPrinter") || undefined : undefined;
+
+  const load = useCallback(async () => {
+    setLoading(true);
+    try {
+      setPage(await getCashRegister(date || undefined));
+    } catch (error) {
+      console.error("Failed to load cash register:", error);
+      toast.error(`Registrul de casă nu a putut fi încărcat: ${String(error)}`);
+    } finally {
+      setLoading(false);
+    }
+  }, [date]);
+
+  useEffect(() => {
+    load();
+  }, [load]);
+
+  const closed = !!page?.closed_at;
+
+  const handleAdd = async () => {
+    const value = parseFloat(amount.replace(",", "."));
+    if (!(value > 0) || !description.trim()) {
+      toast.error("Completează suma și descrierea operațiunii");
+      return;
+    }
+    setBusy(true);
+    try {
+      await addCashMovement({
+        id: "",
+        nume_casa: "",
+        day: date,
+        kind,
+        amount: value,
+        description: description.trim(),
+        document: document.trim() || null,
+        created_at: "",
+      });
+      setAmount("");
+      setDescription("");
+      setDocument("");
+      await load();
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleStart = async () => {
+    const value = parseFloat((startBalance || "0").replace(",", "."));
+    if (!(value >= 0)) {
+      toast.error("Completează numerarul găsit în casă");
+      return;
+    }
+    setBusy(true);
+    try {
+      setPage(await setCashRegisterStart(date, value));
+      setStartBalance("");
+      toast.success(`Registrul de casă începe pe ${date}.`);
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleDelete = async (id: string) => {
+    setBusy(true);
+    try {
+      await deleteCashMovement(id);
+      await load();
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleClose = async () => {
+    if (!window.confirm(`Închizi registrul de casă pe ${date}? Chitanțele zilei nu vor mai putea fi modificate.`)) {
+      return;
+    }
+    setBusy(true);
+    try {
+      const closedPage = await closeCashDay(date, selectedPrinter());
+      setPage(closedPage);
+      toast.success(`Ziua a fost închisă (fila nr. ${closedPage.page_number}).`);
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handlePrint = async () => {
+    setBusy(true);
+    try {
+      toast.success(await printCashRegister(date, selectedPrinter()));
+    } catch (error) {
+      toast.error(`Registrul de casă nu a putut fi printat: ${String(error)}`);
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  return (
+    <Card>
+      <CardHeader>
+        <CardTitle className="flex items-center gap-2">
+          <Wallet className="h-5 w-5" />
+          Registru de casă
+        </CardTitle>
+        <CardDescription>
+          {page ? `${page.nume_casa} • ${date}` : date}
+          {page?.page_number ? ` • fila nr. ${page.page_number}` : ""}
+          {closed ? " • zi închisă" : ""}
+        </CardDescription>
+        <div className="flex flex-wrap gap-2 pt-1">
+          <Button variant="outline" onClick={handlePrint} disabled={busy || loading} className="gap-2">
+            <Printer className="h-4 w-4" />
+            Printează
+          </Button>
+          {!closed && (
+            <Button onClick={handleClose} disabled={busy || loading} className="gap-2">
+              <Lock className="h-4 w-4" />
+              Închide ziua
+            </Button>
+          )}
+        </div>
+      </CardHeader>
+      <CardContent className="space-y-4">
+        {loading || !page ? (
+          <div className="h-24 flex items-center justify-center">
+            <Loader2 className="h-5 w-5 animate-spin text-muted-foreground" />
+          </div>
+        ) : (
+          <>
+            <Table>
+              <TableHeader>
+                <TableRow>
+                  <TableHead>Ora</TableHead>
+                  <TableHead>Document</TableHead>
+                  <TableHead>Explicații</TableHead>
+                  <TableHead className="text-right">Încasări</TableHead>
+                  <TableHead className="text-right">Plăți</TableHead>
+                </TableRow>
+              </TableHeader>
+              <TableBody>
+                <TableRow>
+                  <TableCell colSpan={3} className="text-muted-foreground">
+                    {page.register_start === page.day ? "Sold inițial" : "Sold din ziua precedentă"}
+                  </TableCell>
+                  <TableCell className="text-right">{formatCurrency(page.opening_balance)}</TableCell>
+                  <TableCell />
+                </TableRow>
+                {page.lines.map((line, idx) => (
+                  <TableRow key={`${line.time}-${idx}`}>
+                    <TableCell>{line.time}</TableCell>
+                    <TableCell>{line.document}</TableCell>
+                    <TableCell>{line.description}</TableCell>
+                    <TableCell className="text-right">{line.receipt ? formatCurrency(line.receipt) : ""}</TableCell>
+                    <TableCell className="text-right">{line.payment ? formatCurrency(line.payment) : ""}</TableCell>
+                  </TableRow>
+                ))}
+                <TableRow>
+                  <TableCell colSpan={3} className="font-semibold">TOTAL ZI</TableCell>
+                  <TableCell className="text-right font-semibold">{formatCurrency(page.total_receipts)}</TableCell>
+                  <TableCell className="text-right font-semibold">{formatCurrency(page.total_payments)}</TableCell>
+                </TableRow>
+                <TableRow>
+                  <TableCell colSpan={3} className="font-semibold">SOLD FINAL</TableCell>
+                  <TableCell colSpan={2} className="text-right font-semibold">{formatCurrency(page.closing_balance)}</TableCell>
+                </TableRow>
+              </TableBody>
+            </Table>
+
+            {page.start_editable && (
+              <div className="flex flex-wrap items-center gap-2 text-sm">
+                <span className="text-muted-foreground">
+                  {page.register_start
+                    ? `Registrul începe pe ${page.register_start}.`
+                    : "Registrul începe cu prima zi cu numerar."}{" "}
+                  Până la închiderea primei zile îl poți porni din această zi cu numerarul găsit în casă:
+                </span>
+                <Input
+                  inputMode="decimal"
+                  placeholder="Sold inițial"
+                  value={startBalance}
+                  onChange={(e) => setStartBalance(e.target.value)}
+                  className="h-9 w-32"
+                />
+                <Button variant="outline" size="sm" onClick={handleStart} disabled={busy}>
+                  Începe din {date}
+                </Button>
+              </div>
+            )}
+
+            {page.movements.length > 0 && !closed && (
+              <div className="space-y-1">
+                {page.movements.map((movement) => (
+                  <div key={movement.id} className="flex items-center justify-between text-sm">
+                    <span>
+                      {MOVEMENT_KINDS.find((k) => k.value === movement.kind)?.label}: {movement.description} •{" "}
+                      {formatCurrency(movement.amount)}
+                    </span>
+                    <Button variant="ghost" size="sm" onClick={() => handleDelete(movement.id)} disabled={busy}>
+                      <Trash2 className="h-4 w-4" />
+                    </Button>
+                  </div>
+                ))}
+              </div>
+            )}
+
+            {!closed && (
+              <div className="grid grid-cols-1 sm:grid-cols-5 gap-2">
+                <Select value={kind} onValueChange={(v) => setKind(v as CashMovementKind)}>
+                  <SelectTrigger className="h-10">
+                    <SelectValue />
+                  </SelectTrigger>
+                  <SelectContent>
+                    {MOVEMENT_KINDS.map((k) => (
+                      <SelectItem key={k.value} value={k.value}>
+                        {k.label}
+                      </SelectItem>
+                    ))}
+                  </SelectContent>
+                </Select>
+                <Input
+                  inputMode="decimal"
+                  placeholder="Sumă"
+                  value={amount}
+                  onChange={(e) => setAmount(e.target.value)}
+                  className="h-10"
+                />
+                <Input
+                  placeholder="Descriere"
+                  value={description}
+                  onChange={(e) => setDescription(e.target.value)}
+                  className="h-10"
+                />
+                <Input
+                  placeholder="Document (opțional)"
+                  value={document}
+                  onChange={(e) => setDocument(e.target.value)}
+                  className="h-10"
+                />
+                <Button onClick={handleAdd} disabled={busy} className="h-10">
+                  Adaugă plată
+                </Button>
+              </div>
+            )}
+          </>
+        )}
+      </CardContent>
+    </Card>
+  );
+}
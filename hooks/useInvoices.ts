@@ -4,7 +4,8 @@ import { useState, useEffect, useCallback } from "react";
 import {
   getInvoices,
   sendInvoice,
-  deleteInvoice,
+  cancelInvoice,
+  restoreInvoice,
   getInvoiceDetail,
   printInvoiceToHtml,
 } from "@/lib/tauri/commands";
@@ -63,11 +64,32 @@ export function useInvoices(statusFilter?: InvoiceStatus) {
   );
 
   const remove = useCallback(
+    async (invoiceId: string, reason: string) => {
+      try {
+        const updated = await cancelInvoice(invoiceId, reason);
+        setInvoices((prev) =>
+          prev.map((inv) => (inv.id === invoiceId ? updated : inv))
+        );
+        if (updated.error_message?.startsWith("Anulată local, netrimisă în WME")) {
+          toast.warning(updated.error_message);
+        } else {
+          toast.success("Factura a fost anulată");
+        }
+      } catch (e) {
+        toast.error(String(e));
+      }
+    },
+    []
+  );
+
+  const restore = useCallback(
     async (invoiceId: string) => {
       try {
-        await deleteInvoice(invoiceId);
-        setInvoices((prev) => prev.filter((inv) => inv.id !== invoiceId));
-        toast.success("Factura a fost ștearsă");
+        const updated = await restoreInvoice(invoiceId);
+        setInvoices((prev) =>
+          prev.map((inv) => (inv.id === invoiceId ? updated : inv))
+        );
+        toast.success("Factura a fost restaurată");
       } catch (e) {
         toast.error(String(e));
       }
@@ -103,7 +125,7 @@ export function useInvoices(statusFilter?: InvoiceStatus) {
     failed: invoices.filter((i) => i.status === "failed").length,
   };
 
-  return { invoices, isLoading, error, refresh, send, remove, counts };
+  return { invoices, isLoading, error, refresh, send, remove, restore, counts };
 }
 
 export function useInvoiceDetail(invoiceId: string | null) {
@@ -8,6 +8,7 @@ import {
   recordCollectionFromInvoice,
   getInvoiceRemainingForCollection,
   sendCollection,
+  getNumberingWarnings,
 } from "@/lib/tauri/commands";
 import { toast } from "sonner";
 import {
@@ -66,6 +67,7 @@ export function usePrintInvoice() {
     setSavingReceipt(true);
     try {
       const collectionId = await recordCollectionFromInvoice(receiptInvoiceId, paidAmount);
+      getNumberingWarnings().then((warnings) => warnings.forEach((warning) => toast.warning(warning))).catch(console.error);
 
       const selectedPrinter = typeof window !== "undefined"
         ? localStorage.getItem("selectedPrinter")
@@ -16,6 +16,42 @@ import type {
   SalesProductReportItem,
   CollectionsReportItem,
   DailyCollectionsReport,
+  ArchiveResult,
+  ArchiveStatus,
+  IntegrityReport,
+  DocumentEvent,
+  DocumentType,
+  ThermalPrinterSettings,
+  PrinterProfile,
+  PrintRoute,
+  PrintJob,
+  PrintJobStatus,
+  CompanyProfile,
+  CompanyImageKind,
+  DocumentTemplateInfo,
+  DocumentTemplateType,
+  UblRuleViolation,
+  EfacturaSettings,
+  EfacturaSubmission,
+  ScannedDocument,
+  CashLimitRule,
+  CashLimitCheck,
+  NumberRange,
+  NumberRegistryEntry,
+  NumberedDocumentType,
+  CollectionAllocationRequest,
+  PaymentAllocation,
+  PaymentAllocationStrategy,
+  PaymentDetails,
+  PartnerAdvance,
+  AdvanceApplication,
+  CashMovement,
+  CashRegisterDay,
+  PartnerStatement,
+  BalanceConfirmation,
+  BalanceConfirmationStatus,
+  AgingGroupBy,
+  AgingReport,
 } from "./types";
 
 // ==================== SYNC COMMANDS ====================
@@ -106,8 +142,16 @@ export async function cancelInvoiceSending(invoiceId: string): Promise<Invoice>
   return invoke<Invoice>("cancel_invoice_sending", { invoiceId });
 }
 
-export async function deleteInvoice(invoiceId: string): Promise<void> {
-  return invoke<void>("delete_invoice", { invoiceId });
+export async function cancelInvoice(
+  invoiceId: string,
+  reason?: string,
+  sendToWme?: boolean
+): Promise<Invoice> {
+  return invoke<Invoice>("cancel_invoice", { invoiceId, reason, sendToWme });
+}
+
+export async function restoreInvoice(invoiceId: string): Promise<Invoice> {
+  return invoke<Invoice>("restore_invoice", { invoiceId });
 }
 
 // ==================== PRINT COMMANDS ====================
@@ -132,6 +176,139 @@ export async function printCollectionToHtml(collectionId: string, printerName?:
   return invoke<string>("print_collection_to_html", { collectionId, printerName });
 }
 
+// ==================== THERMAL PRINTER COMMANDS ====================
+
+export async function getThermalPrinterSettings(): Promise<ThermalPrinterSettings> {
+  return invoke<ThermalPrinterSettings>("get_thermal_printer_settings");
+}
+
+export async function saveThermalPrinterSettings(
+  settings: ThermalPrinterSettings
+): Promise<ThermalPrinterSettings> {
+  return invoke<ThermalPrinterSettings>("save_thermal_printer_settings", { settings });
+}
+
+export async function testThermalPrinter(): Promise<void> {
+  return invoke<void>("test_thermal_printer");
+}
+
+export async function printCollectionEscpos(collectionId: string): Promise<void> {
+  return invoke<void>("print_collection_escpos", { collectionId });
+}
+
+export async function printInvoiceEscpos(invoiceId: string): Promise<void> {
+  return invoke<void>("print_invoice_escpos", { invoiceId });
+}
+
+export async function printCollectionsReportEscpos(date?: string): Promise<void> {
+  return invoke<void>("print_collections_report_escpos", { date });
+}
+
+// ==================== PRINTER PROFILE COMMANDS ====================
+
+export async function getPrinterProfiles(): Promise<PrinterProfile[]> {
+  return invoke<PrinterProfile[]>("get_printer_profiles");
+}
+
+export async function savePrinterProfile(profile: PrinterProfile): Promise<PrinterProfile> {
+  return invoke<PrinterProfile>("save_printer_profile", { profile });
+}
+
+export async function deletePrinterProfile(profileId: number): Promise<void> {
+  return invoke<void>("delete_printer_profile", { profileId });
+}
+
+export async function getPrintRoutes(): Promise<PrintRoute[]> {
+  return invoke<PrintRoute[]>("get_print_routes");
+}
+
+export async function savePrintRoutes(routes: PrintRoute[]): Promise<PrintRoute[]> {
+  return invoke<PrintRoute[]>("save_print_routes", { routes });
+}
+
+// ==================== PRINT JOB COMMANDS ====================
+
+export async function getPrintJobs(status?: PrintJobStatus, limit?: number): Promise<PrintJob[]> {
+  return invoke<PrintJob[]>("get_print_jobs", { status, limit });
+}
+
+export async function reprintJob(jobId: number, copies?: number): Promise<PrintJob> {
+  return invoke<PrintJob>("reprint_job", { jobId, copies });
+}
+
+// ==================== DOCUMENT TEMPLATE COMMANDS ====================
+
+// id: invoice id (invoice, quality_certificate), collection id (receipt) or YYYY-MM-DD (daily_report)
+export async function renderDocumentPreview(
+  docType: DocumentTemplateType,
+  id: string
+): Promise<string> {
+  return invoke<string>("render_document_preview", { docType, id });
+}
+
+export async function getDocumentTemplates(): Promise<DocumentTemplateInfo[]> {
+  return invoke<DocumentTemplateInfo[]>("get_document_templates");
+}
+
+export async function createDocumentTemplateOverride(
+  docType: DocumentTemplateType
+): Promise<DocumentTemplateInfo> {
+  return invoke<DocumentTemplateInfo>("create_document_template_override", { docType });
+}
+
+// ==================== DOCUMENT CODE COMMANDS ====================
+
+export async function getPrintBarcode(): Promise<boolean> {
+  return invoke<boolean>("get_print_barcode");
+}
+
+// Code128 barcode under the QR code on invoices, receipts and certificates
+export async function setPrintBarcode(enabled: boolean): Promise<boolean> {
+  return invoke<boolean>("set_print_barcode", { enabled });
+}
+
+// QR code or barcode read from a printed document; null when the code is not one of ours
+export async function lookupDocumentByCode(code: string): Promise<ScannedDocument | null> {
+  return invoke<ScannedDocument | null>("lookup_document_by_code", { code });
+}
+
+// ==================== COMPANY PROFILE COMMANDS ====================
+
+export async function getCompanyProfiles(): Promise<CompanyProfile[]> {
+  return invoke<CompanyProfile[]>("get_company_profiles");
+}
+
+export async function getActiveCompanyProfile(): Promise<CompanyProfile> {
+  return invoke<CompanyProfile>("get_active_company_profile");
+}
+
+export async function saveCompanyProfile(profile: CompanyProfile): Promise<CompanyProfile> {
+  return invoke<CompanyProfile>("save_company_profile", { profile });
+}
+
+export async function selectCompanyProfile(companyId: number): Promise<CompanyProfile> {
+  return invoke<CompanyProfile>("select_company_profile", { companyId });
+}
+
+export async function deleteCompanyProfile(companyId: number): Promise<void> {
+  return invoke<void>("delete_company_profile", { companyId });
+}
+
+export async function setCompanyImage(
+  companyId: number,
+  kind: CompanyImageKind,
+  pngBase64: string | null
+): Promise<void> {
+  return invoke<void>("set_company_image", { companyId, kind, pngBase64 });
+}
+
+export async function getCompanyImage(
+  companyId: number,
+  kind: CompanyImageKind
+): Promise<string | null> {
+  return invoke<string | null>("get_company_image", { companyId, kind });
+}
+
 // ==================== AGENT SETTINGS COMMANDS ====================
 
 export async function getAgentSettings(): Promise<AgentSettings> {
@@ -210,6 +387,45 @@ export async function hideClientBalance(idPartener: string, codDocument: string,
   return invoke<void>("hide_client_balance", { idPartener, codDocument, serie, numar });
 }
 
+export async function getCashLimitRules(): Promise<CashLimitRule[]> {
+  return invoke<CashLimitRule[]>("get_cash_limit_rules");
+}
+
+export async function saveCashLimitRule(rule: CashLimitRule): Promise<CashLimitRule[]> {
+  return invoke<CashLimitRule[]>("save_cash_limit_rule", { rule });
+}
+
+// Invoice and receipt carnets, in the order they are used
+export async function getNumberRanges(documentType?: NumberedDocumentType): Promise<NumberRange[]> {
+  return invoke<NumberRange[]>("get_number_ranges", { documentType });
+}
+
+// id 0 adds a carnet after the existing ones
+export async function saveNumberRange(range: NumberRange): Promise<NumberRange[]> {
+  return invoke<NumberRange[]>("save_number_range", { range });
+}
+
+export async function getNumberRegistry(
+  documentType?: NumberedDocumentType,
+  series?: string,
+  limit?: number
+): Promise<NumberRegistryEntry[]> {
+  return invoke<NumberRegistryEntry[]>("get_number_registry", { documentType, series, limit });
+}
+
+// Carnets nearly or fully used up
+export async function getNumberingWarnings(): Promise<string[]> {
+  return invoke<string[]>("get_numbering_warnings");
+}
+
+// Cash caps a receipt of today would exceed; recording refuses the blocking ones
+export async function checkCashLimits(
+  partnerId: string,
+  allocations: CollectionAllocationRequest[]
+): Promise<CashLimitCheck[]> {
+  return invoke<CashLimitCheck[]>("check_cash_limits", { partnerId, allocations });
+}
+
 export async function recordCollection(collection: Collection): Promise<string> {
   return invoke<string>("record_collection", { collection });
 }
@@ -218,8 +434,34 @@ export async function recordCollectionGroup(request: CreateCollectionGroupReques
   return invoke<string>("record_collection_group", { request });
 }
 
-export async function recordCollectionFromInvoice(invoiceId: string, paidAmount: number): Promise<string> {
-  return invoke<string>("record_collection_from_invoice", { invoiceId, paidAmount });
+export async function allocatePayment(
+  partnerId: string,
+  amount: number,
+  strategy: PaymentAllocationStrategy,
+  record = false,
+  payment?: PaymentDetails
+): Promise<PaymentAllocation> {
+  return invoke<PaymentAllocation>("allocate_payment", { partnerId, amount, strategy, record, payment });
+}
+
+export async function getPartnerAdvances(partnerId: string): Promise<PartnerAdvance[]> {
+  return invoke<PartnerAdvance[]>("get_partner_advances", { partnerId });
+}
+
+export async function applyPartnerAdvances(partnerId: string): Promise<AdvanceApplication[]> {
+  return invoke<AdvanceApplication[]>("apply_partner_advances", { partnerId });
+}
+
+export async function sendAdvanceApplications(groupId: string): Promise<AdvanceApplication[]> {
+  return invoke<AdvanceApplication[]>("send_advance_applications", { groupId });
+}
+
+export async function recordCollectionFromInvoice(
+  invoiceId: string,
+  paidAmount: number,
+  payment?: PaymentDetails
+): Promise<string> {
+  return invoke<string>("record_collection_from_invoice", { invoiceId, paidAmount, payment });
 }
 
 export async function getInvoiceRemainingForCollection(invoiceId: string): Promise<number> {
@@ -238,24 +480,44 @@ export async function sendCollection(collectionId: string): Promise<Collection>
   return invoke<Collection>("send_collection", { collectionId });
 }
 
-export async function deleteCollection(collectionId: string): Promise<void> {
-  return invoke<void>("delete_collection", { collectionId });
+export async function cancelReceipt(receiptGroupId: string, reason: string): Promise<Collection> {
+  return invoke<Collection>("cancel_receipt", { receiptGroupId, reason });
+}
+
+export async function restoreCollection(collectionId: string): Promise<void> {
+  return invoke<void>("restore_collection", { collectionId });
 }
 
-export async function getSalesReport(startDate?: string, endDate?: string): Promise<SalesReportItem[]> {
-  return invoke<SalesReportItem[]>("get_sales_report", { startDate, endDate });
+export async function getSalesReport(
+  startDate?: string,
+  endDate?: string,
+  includeArchived?: boolean
+): Promise<SalesReportItem[]> {
+  return invoke<SalesReportItem[]>("get_sales_report", { startDate, endDate, includeArchived });
 }
 
-export async function getSalesPrintReport(startDate?: string, endDate?: string): Promise<SalesPrintItem[]> {
-  return invoke<SalesPrintItem[]>("get_sales_print_report", { startDate, endDate });
+export async function getSalesPrintReport(
+  startDate?: string,
+  endDate?: string,
+  includeArchived?: boolean
+): Promise<SalesPrintItem[]> {
+  return invoke<SalesPrintItem[]>("get_sales_print_report", { startDate, endDate, includeArchived });
 }
 
-export async function getSalesProductsReport(startDate?: string, endDate?: string): Promise<SalesProductReportItem[]> {
-  return invoke<SalesProductReportItem[]>("get_sales_products_report", { startDate, endDate });
+export async function getSalesProductsReport(
+  startDate?: string,
+  endDate?: string,
+  includeArchived?: boolean
+): Promise<SalesProductReportItem[]> {
+  return invoke<SalesProductReportItem[]>("get_sales_products_report", { startDate, endDate, includeArchived });
 }
 
-export async function getCollectionsReport(startDate?: string, endDate?: string): Promise<CollectionsReportItem[]> {
-  return invoke<CollectionsReportItem[]>("get_collections_report", { startDate, endDate });
+export async function getCollectionsReport(
+  startDate?: string,
+  endDate?: string,
+  includeArchived?: boolean
+): Promise<CollectionsReportItem[]> {
+  return invoke<CollectionsReportItem[]>("get_collections_report", { startDate, endDate, includeArchived });
 }
 
 export async function getDailyCollectionsReport(date?: string): Promise<DailyCollectionsReport> {
@@ -266,12 +528,171 @@ export async function printDailyReport(date?: string, printerName?: string): Pro
   return invoke<string>("print_daily_report", { date, printerName });
 }
 
+// Page of the current cash desk; today when no date is given
+export async function getCashRegister(date?: string): Promise<CashRegisterDay> {
+  return invoke<CashRegisterDay>("get_cash_register", { date });
+}
+
+export async function setCashRegisterStart(date: string, openingBalance: number): Promise<CashRegisterDay> {
+  return invoke<CashRegisterDay>("set_cash_register_start", { date, openingBalance });
+}
+
+export async function addCashMovement(movement: CashMovement): Promise<string> {
+  return invoke<string>("add_cash_movement", { movement });
+}
+
+export async function deleteCashMovement(id: string): Promise<void> {
+  return invoke<void>("delete_cash_movement", { id });
+}
+
+// Closes the day (no more receipts, cancellations or movements on it) and prints the page
+export async function closeCashDay(date?: string, printerName?: string): Promise<CashRegisterDay> {
+  return invoke<CashRegisterDay>("close_cash_day", { date, printerName });
+}
+
+export async function printCashRegister(date?: string, printerName?: string): Promise<string> {
+  return invoke<string>("print_cash_register", { date, printerName });
+}
+
+export async function generatePartnerStatement(partnerId: string, from: string, to: string): Promise<PartnerStatement> {
+  return invoke<PartnerStatement>("generate_partner_statement", { partnerId, from, to });
+}
+
+export async function printPartnerStatement(
+  partnerId: string,
+  from: string,
+  to: string,
+  printerName?: string
+): Promise<string> {
+  return invoke<string>("print_partner_statement", { partnerId, from, to, printerName });
+}
+
+export async function generateBalanceConfirmations(
+  referenceDate: string,
+  partnerId?: string
+): Promise<BalanceConfirmation[]> {
+  return invoke<BalanceConfirmation[]>("generate_balance_confirmations", { referenceDate, partnerId });
+}
+
+export async function getBalanceConfirmations(referenceDate: string): Promise<BalanceConfirmation[]> {
+  return invoke<BalanceConfirmation[]>("get_balance_confirmations", { referenceDate });
+}
+
+export async function printBalanceConfirmations(
+  referenceDate: string,
+  partnerId?: string,
+  printerName?: string
+): Promise<string> {
+  return invoke<string>("print_balance_confirmations", { referenceDate, partnerId, printerName });
+}
+
+export async function recordBalanceConfirmationReturn(
+  id: string,
+  status: BalanceConfirmationStatus,
+  partnerBalance?: number,
+  notes?: string
+): Promise<BalanceConfirmation> {
+  return invoke<BalanceConfirmation>("record_balance_confirmation_return", { id, status, partnerBalance, notes });
+}
+
+export async function getAgingReport(asOf?: string, groupBy?: AgingGroupBy): Promise<AgingReport> {
+  return invoke<AgingReport>("get_aging_report", { asOf, groupBy });
+}
+
+export async function printAgingReport(asOf?: string, groupBy?: AgingGroupBy, printerName?: string): Promise<string> {
+  return invoke<string>("print_aging_report", { asOf, groupBy, printerName });
+}
+
+export async function exportAgingReport(asOf?: string, groupBy?: AgingGroupBy, path?: string): Promise<string> {
+  return invoke<string>("export_aging_report", { asOf, groupBy, path });
+}
+
 export async function saveReportHtml(reportName: string, htmlContent: string): Promise<string> {
   return invoke<string>("save_report_html", { reportName, htmlContent });
 }
 
-export async function printReportHtml(reportName: string, htmlContent: string, printerName?: string): Promise<string> {
-  return invoke<string>("print_report_html", { reportName, htmlContent, printerName });
+export async function printSalesSummary(startDate?: string, endDate?: string, printerName?: string): Promise<string> {
+  return invoke<string>("print_sales_summary", { startDate, endDate, printerName });
+}
+
+// ==================== MAINTENANCE COMMANDS ====================
+
+export async function getArchiveStatus(): Promise<ArchiveStatus> {
+  return invoke<ArchiveStatus>("get_archive_status");
+}
+
+export async function setRetentionMonths(months: number): Promise<ArchiveStatus> {
+  return invoke<ArchiveStatus>("set_retention_months", { months });
+}
+
+export async function archiveOldDocuments(): Promise<ArchiveResult> {
+  return invoke<ArchiveResult>("archive_old_documents");
+}
+
+export async function vacuumDatabase(): Promise<string> {
+  return invoke<string>("vacuum_database");
+}
+
+export async function runIntegrityCheck(): Promise<IntegrityReport> {
+  return invoke<IntegrityReport>("run_integrity_check");
+}
+
+export async function repairIntegrityIssues(issueCodes: string[]): Promise<IntegrityReport> {
+  return invoke<IntegrityReport>("repair_integrity_issues", { issueCodes });
+}
+
+// A number reported in number_gaps, recorded as skipped with its reason
+export async function explainNumberGap(
+  documentType: NumberedDocumentType,
+  series: string,
+  number: number,
+  reason: string
+): Promise<IntegrityReport> {
+  return invoke<IntegrityReport>("explain_number_gap", { documentType, series, number, reason });
+}
+
+// ==================== AUDIT COMMANDS ====================
+
+export async function getDocumentHistory(documentType: DocumentType, documentId: string): Promise<DocumentEvent[]> {
+  return invoke<DocumentEvent[]>("get_document_history", { documentType, documentId });
+}
+
+// ==================== E-FACTURA COMMANDS ====================
+
+/** Pre-check of the e-Factura rules the app knows; ANAF's validator decides on upload */
+export async function validateInvoiceUbl(invoiceId: string): Promise<UblRuleViolation[]> {
+  return invoke<UblRuleViolation[]>("validate_invoice_ubl", { invoiceId });
+}
+
+/** Writes the invoice as CIUS-RO UBL XML to a file or folder; returns the file path */
+export async function exportInvoiceUbl(invoiceId: string, path: string): Promise<string> {
+  return invoke<string>("export_invoice_ubl", { invoiceId, path });
+}
+
+export async function getEfacturaSettings(): Promise<EfacturaSettings> {
+  return invoke<EfacturaSettings>("get_efactura_settings");
+}
+
+export async function saveEfacturaSettings(settings: EfacturaSettings): Promise<EfacturaSettings> {
+  return invoke<EfacturaSettings>("save_efactura_settings", { settings });
+}
+
+/** Uploads the invoice to ANAF; refused if it was already uploaded and not rejected */
+export async function submitInvoiceEfactura(invoiceId: string): Promise<EfacturaSubmission> {
+  return invoke<EfacturaSubmission>("submit_invoice_efactura", { invoiceId });
+}
+
+export async function checkEfacturaStatus(submissionId: number): Promise<EfacturaSubmission> {
+  return invoke<EfacturaSubmission>("check_efactura_status", { submissionId });
+}
+
+/** Checks all uploads ANAF is still processing; returns the ones checked */
+export async function pollEfacturaSubmissions(): Promise<EfacturaSubmission[]> {
+  return invoke<EfacturaSubmission[]>("poll_efactura_submissions");
+}
+
+export async function getEfacturaSubmissions(invoiceId: string): Promise<EfacturaSubmission[]> {
+  return invoke<EfacturaSubmission[]>("get_efactura_submissions", { invoiceId });
 }
 
 // ==================== DEBUG COMMANDS ====================
@@ -62,7 +62,7 @@ export interface Product {
   tva_percent: number | null;
 }
 
-export type InvoiceStatus = "pending" | "sending" | "sent" | "failed";
+export type InvoiceStatus = "pending" | "sending" | "sent" | "failed" | "cancelled";
 
 export interface Invoice {
   id: string;
@@ -156,7 +156,7 @@ export interface CartItem {
   quantity: number;
 }
 
-export type CollectionStatus = "pending" | "sending" | "synced" | "failed";
+export type CollectionStatus = "pending" | "sending" | "synced" | "failed" | "cancelled";
 
 export interface Collection {
   id: string;
@@ -173,6 +173,16 @@ export interface Collection {
   created_at: string;
   receipt_series?: string;
   receipt_number?: string;
+  payment?: PaymentDetails;
+}
+
+export type PaymentMethod = "cash" | "card" | "bank_transfer" | "promissory_note" | "cheque";
+
+export interface PaymentDetails {
+  method: PaymentMethod;
+  reference?: string | null; // POS slip, transfer reference, BO or cheque number
+  due_date?: string | null; // YYYY-MM-DD, promissory notes and cheques
+  bank_account?: string | null; // IBAN; empty = the company's primary account
 }
 
 export interface CollectionAllocationRequest {
@@ -182,10 +192,117 @@ export interface CollectionAllocationRequest {
   valoare: number;
 }
 
+export type CashLimitPartnerType = "legal_entity" | "individual"; // individual = persoana_fizica
+export type CashLimitScope = "per_day" | "per_document"; // per_document = one invoice over all its receipts
+export type CashLimitAction = "block" | "warn" | "off";
+
+export interface CashLimitRule {
+  id: number;
+  partner_type: CashLimitPartnerType;
+  scope: CashLimitScope;
+  limit_amount: number; // lei
+  action: CashLimitAction;
+  updated_at: string;
+}
+
+export interface CashLimitCheck {
+  scope: CashLimitScope;
+  partner_type: CashLimitPartnerType;
+  document: string | null; // invoice, for per_document caps
+  limit_amount: number;
+  already_collected: number;
+  amount: number;
+  blocking: boolean; // the receipt will be refused
+  message: string;
+}
+
+export type NumberedDocumentType = "invoice" | "receipt";
+export type NumberRangeStatus = "active" | "exhausted" | "disabled";
+
+export interface NumberRange {
+  id: number; // 0 for a new carnet
+  document_type: NumberedDocumentType;
+  series: string;
+  range_start: number;
+  range_end: number | null; // null = no last number
+  next_number: number;
+  position: number; // carnets are used in ascending order
+  status: NumberRangeStatus;
+  remaining: number | null;
+  created_at: string;
+}
+
+export interface NumberRegistryEntry {
+  id: number;
+  document_type: NumberedDocumentType;
+  series: string;
+  number: number;
+  range_id: number | null;
+  document_id: string | null; // invoice id or receipt group id
+  status: "used" | "skipped";
+  note: string | null; // why a number was skipped
+  allocated_at: string;
+}
+
 export interface CreateCollectionGroupRequest {
   id_partener: string;
   partner_name?: string;
   allocations: CollectionAllocationRequest[];
+  advance?: number; // paid on account, over what the invoices take
+  payment?: PaymentDetails; // cash when missing
+}
+
+export interface PartnerAdvance {
+  id: string; // collection row of the advance
+  receipt_group_id: string;
+  receipt_series: string | null;
+  receipt_number: string | null;
+  data_incasare: string;
+  valoare: number;
+  status: CollectionStatus;
+  applied: number;
+  available: number;
+}
+
+export interface AdvanceApplication {
+  id: string;
+  group_id: string;
+  advance_id: string;
+  id_partener: string;
+  serie_factura: string | null;
+  numar_factura: string | null;
+  cod_document: string | null;
+  valoare: number;
+  status: "pending" | "synced" | "cancelled";
+  synced_at: string | null;
+  error_message: string | null; // why it is still pending
+  created_at: string;
+  receipt_series: string | null; // compensation receipt, one per advance
+  receipt_number: string | null;
+}
+
+export type PaymentAllocationStrategy = "oldest_due" | "overdue_first" | "exact_match";
+
+export interface PaymentAllocationLine {
+  serie_factura: string | null;
+  numar_factura: string | null;
+  cod_document: string | null;
+  data: string | null;
+  termen: string | null;
+  rest: number; // left to pay before this payment
+  valoare: number;
+  days_overdue: number;
+  fully_paid: boolean;
+}
+
+export interface PaymentAllocation {
+  strategy: PaymentAllocationStrategy;
+  amount: number;
+  allocated: number;
+  unallocated: number; // more than the partner owes; recorded as an advance
+  open_balance: number;
+  lines: PaymentAllocationLine[];
+  receipt_group_id: string | null; // set when recorded
 }
 
 export interface ClientBalance {
@@ -268,3 +385,335 @@ export interface DailyCollectionsReport {
   previous_day_collections_total: number;
   total_day_collections: number;
 }
+
+export type CashMovementKind = "handover" | "deposit" | "expense";
+
+export interface CashMovement {
+  id: string; // empty for a new movement
+  nume_casa: string; // empty = the cash desk from the settings
+  day: string; // YYYY-MM-DD
+  kind: CashMovementKind;
+  amount: number;
+  description: string;
+  document: string | null; // handover slip, bank slip, fuel receipt
+  created_at: string;
+}
+
+export interface CashRegisterLine {
+  time: string; // HH:MM
+  kind: "receipt" | CashMovementKind;
+  document: string;
+  description: string;
+  receipt: number;
+  payment: number;
+}
+
+export interface CashRegisterDay {
+  nume_casa: string;
+  day: string;
+  page_number: number | null; // given when the day is closed
+  opening_balance: number;
+  lines: CashRegisterLine[];
+  movements: CashMovement[];
+  total_receipts: number;
+  total_payments: number;
+  closing_balance: number;
+  closed_at: string | null;
+  document_path: string | null; // PDF of the printed page
+  register_start: string | null; // first day of the register, YYYY-MM-DD
+  start_editable: boolean; // until the first page is closed
+}
+
+export type PartnerStatementLineKind = "invoice" | "credit_note" | "collection" | "prior_payment";
+
+export interface PartnerStatementLine {
+  date: string; // YYYY-MM-DD
+  kind: PartnerStatementLineKind; // prior_payment = paid in WME before the document reached the app
+  document: string;
+  description: string;
+  due_date: string | null;
+  debit: number;
+  credit: number;
+  balance: number; // after this line
+  days_overdue: number | null; // unpaid invoices past due at the end of the period
+}
+
+export interface PartnerStatement {
+  partner_id: string;
+  partner_name: string;
+  partner_cif: string | null;
+  from: string;
+  to: string;
+  opening_balance: number;
+  lines: PartnerStatementLine[];
+  total_debit: number;
+  total_credit: number;
+  closing_balance: number;
+  overdue_amount: number; // part of the closing balance past due
+  generated_at: string;
+  document_path: string | null; // PDF
+}
+
+export interface BalanceConfirmationDocument {
+  tip_document: string;
+  document: string; // series and number
+  data: string | null;
+  termen: string | null;
+  valoare: number;
+  rest: number;
+}
+
+export type BalanceConfirmationStatus = "sent" | "confirmed" | "disputed";
+
+export interface BalanceConfirmation {
+  id: string;
+  partner_id: string;
+  partner_name: string;
+  partner_cif: string | null;
+  partner_reg_com: string | null;
+  partner_address: string | null;
+  reference_date: string; // YYYY-MM-DD
+  documents: BalanceConfirmationDocument[];
+  balance: number;
+  generated_at: string;
+  document_path: string | null; // PDF
+  return_status: BalanceConfirmationStatus;
+  returned_at: string | null; // when the signed copy came back
+  partner_balance: number | null; // balance stated by the client when disputed
+  notes: string | null;
+}
+
+export type AgingGroupBy = "partner" | "location" | "agent";
+
+export interface AgingBuckets {
+  current: number; // not due yet, or without a due date
+  days_1_30: number;
+  days_31_60: number;
+  days_61_90: number;
+  days_over_90: number;
+  total: number;
+}
+
+export interface AgingRow extends AgingBuckets {
+  key: string;
+  name: string;
+  partner_id: string | null; // null when grouped by agent
+  document_count: number;
+  max_days_overdue: number;
+}
+
+export interface AgingReport {
+  as_of: string; // YYYY-MM-DD
+  group_by: AgingGroupBy;
+  rows: AgingRow[]; // most overdue first
+  totals: AgingBuckets;
+  generated_at: string;
+}
+
+export interface ArchiveResult {
+  cutoff_date: string;
+  invoices: number;
+  invoice_items: number;
+  certificates: number;
+  collections: number;
+}
+
+export interface ArchiveStatus {
+  retention_months: number;
+  archived_invoices: number;
+  archived_collections: number;
+  database_size_bytes: number;
+  archive_size_bytes: number;
+}
+
+export interface IntegrityIssue {
+  code: string;
+  severity: "error" | "warning";
+  message: string;
+  count: number;
+  details: string[];
+  fixable: boolean;
+}
+
+export interface IntegrityReport {
+  checked_at: string;
+  ok: boolean;
+  issues: IntegrityIssue[];
+  applied_fixes: string[];
+}
+
+export type DocumentType = "invoice" | "collection";
+
+export interface DocumentEvent {
+  id: number;
+  document_type: DocumentType;
+  document_id: string;
+  document_group_id?: string | null;
+  document_number?: string | null;
+  event_type:
+    | "created"
+    | "edited"
+    | "status_changed"
+    | "printed"
+    | "deleted"
+    | "efactura_uploaded"
+    | "efactura_accepted"
+    | "efactura_rejected"
+    | "cash_limit_warning"
+    | "wme_reversed";
+  previous_status?: string | null;
+  new_status?: string | null;
+  agent?: string | null;
+  message?: string | null;
+  created_at: string;
+}
+
+export type ThermalCodePage = "cp852" | "cp1250" | "ascii";
+
+export interface ThermalPrinterSettings {
+  target: string | null; // tcp:host[:port], serial:PORT[@baud] or a device path
+  paper_width_mm: 58 | 80;
+  code_page: ThermalCodePage;
+  code_page_table: number | null; // null = the usual ESC t number for the code page
+  print_logo: boolean;
+  cut: boolean;
+  feed_lines: number;
+}
+
+export type PrinterBackend = "system" | "sumatra" | "escpos" | "lp";
+
+export type PrintDocumentType = "invoice" | "quality_certificate" | "receipt" | "daily_report" | "report";
+
+export interface PrinterProfile {
+  id: number | null;
+  name: string;
+  printer: string | null; // printer name or ESC/POS target; null = default printer
+  backend: PrinterBackend;
+  paper_size: string | null; // "A4", "A5"; "58mm"/"80mm" for ESC/POS
+  copies: number;
+  duplex: boolean;
+}
+
+export interface PrintRoute {
+  doc_type: PrintDocumentType;
+  profile_id: number | null; // null = default printer
+  copies: number | null; // null = the profile's copies; 0 = not printed
+}
+
+export type PrintJobStatus = "queued" | "printing" | "printed" | "failed";
+
+export interface PrintJob {
+  id: number;
+  doc_type: PrintDocumentType;
+  document_id: string; // invoice or collection id; the date or name for reports
+  file_path: string; // PDF, HTML or ESC/POS bytes sent to the printer
+  backend: PrinterBackend;
+  printer: string | null;
+  profile_name: string | null;
+  paper_size: string | null;
+  duplex: boolean;
+  copies: number;
+  status: PrintJobStatus;
+  attempts: number; // failed jobs are retried automatically up to 3 attempts
+  error_message: string | null;
+  reprint_of: number | null;
+  created_at: string;
+  updated_at: string;
+  printed_at: string | null;
+}
+
+export interface UblRuleViolation {
+  rule: string; // EN 16931 / CIUS-RO rule id, e.g. "BR-16", "BR-RO-100"
+  message: string;
+}
+
+export type EfacturaEnvironment = "test" | "prod" | "local"; // local = stand-in on this computer, development builds only
+
+export interface EfacturaSettings {
+  environment: EfacturaEnvironment;
+  base_url: string | null; // overrides the environment's API address
+  client_id: string | null;
+  client_secret: string | null; // write-only; null keeps the stored value
+  access_token: string | null; // write-only
+  refresh_token: string | null; // write-only
+  token_expires_at: string | null;
+  has_client_secret: boolean;
+  has_access_token: boolean;
+  has_refresh_token: boolean;
+}
+
+export type EfacturaState = "sending" | "uploaded" | "processing" | "accepted" | "rejected" | "invalid";
+
+export interface EfacturaSubmission {
+  id: number;
+  invoice_id: string;
+  invoice_number: string;
+  environment: EfacturaEnvironment;
+  upload_index: string | null; // null when the upload was refused
+  state: EfacturaState;
+  download_id: string | null;
+  errors: string[];
+  xml_path: string;
+  response_path: string | null; // ANAF answer ZIP: signed invoice or errors
+  created_at: string;
+  updated_at: string;
+  checked_at: string | null;
+}
+
+export interface CompanyBankAccount {
+  id: number | null;
+  bank_name: string;
+  iban: string;
+  currency: string; // RON when empty
+  is_primary: boolean;
+}
+
+export interface CompanyProfile {
+  id: number | null; // null = new profile
+  name: string;
+  cif: string;
+  reg_com: string;
+  address: string;
+  localitate: string;
+  judet: string;
+  cod_postal: string;
+  capital: string;
+  phone: string | null;
+  email: string | null;
+  vat_on_collection: boolean; // prints "TVA la încasare" on invoices
+  bank_accounts: CompanyBankAccount[];
+  has_logo: boolean; // false = the bundled logo is printed
+  has_stamp: boolean;
+  is_active: boolean;
+}
+
+export type CompanyImageKind = "logo" | "stamp";
+
+export type DocumentTemplateType =
+  | "invoice"
+  | "receipt"
+  | "daily_report"
+  | "quality_certificate"
+  | "cash_register"
+  | "partner_statement"
+  | "balance_confirmation"
+  | "aging_report";
+
+export interface DocumentTemplateInfo {
+  doc_type: DocumentTemplateType;
+  path: string | null; // the user's copy of the HTML template (preview and HTML copy only)
+  overridden: boolean; // false = the bundled template is used
+  footer_path: string | null; // footer text, also used for PDF and thermal printing
+  footer_overridden: boolean;
+}
+
+export interface ScannedDocument {
+  document_type: "invoice" | "receipt" | "quality_certificate";
+  document_id: string; // invoice id; for receipts the id of their first collection
+  series: string;
+  number: string;
+  partner_name: string;
+  date: string;
+  total: number; // with VAT
+  status: string;
+}
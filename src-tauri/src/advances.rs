@@ -0,0 +1,307 @@
+use chrono::Utc;
+use log::info;
+use rusqlite::{params, Connection};
+use uuid::Uuid;
+
+use crate::models::{AdvanceApplication, PartnerAdvance, PaymentAllocationLine};
+use crate::numbering::{self, Allocated};
+
+// Money received from a partner that is not (yet) owed on any invoice: a
+// payment on account, or what is left of an overpayment once its invoices
+// are paid. It is a `collections` row of its own transaction type (no invoice
+// reference) on the same receipt, and reaches WME on that receipt as the
+// difference going to advance (`DiferentaPeAvans`).
+//
+// When the partner has invoices again the advance is set against them. Each
+// such application is a row in `advance_applications`; it is not new cash, so
+// it stays out of `collections` and of the cash totals, but it lowers the
+// invoice balance like a collection does (see the `invoice_payments` view).
+//
+// WME gets the compensation as a receipt of its own: the advance receipt is
+// already booked there, so each advance set against invoices takes a new
+// number from the receipt carnet, kept on its applications.
+
+/// Payment on an invoice
+pub const INVOICE: &str = "invoice";
+/// Payment on account
+pub const ADVANCE: &str = "advance";
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS advance_applications (
+        id TEXT PRIMARY KEY,
+        group_id TEXT NOT NULL,
+        advance_id TEXT NOT NULL,
+        id_partener TEXT NOT NULL,
+        serie_factura TEXT,
+        numar_factura TEXT,
+        cod_document TEXT,
+        valoare REAL NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        synced_at TEXT,
+        error_message TEXT,
+        created_at TEXT NOT NULL,
+        receipt_series TEXT,
+        receipt_number TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_advance_applications_advance ON advance_applications(advance_id);
+    CREATE INDEX IF NOT EXISTS idx_advance_applications_group ON advance_applications(group_id);
+
+    -- Everything that pays an invoice: collections on it and advances set against it
+    CREATE VIEW IF NOT EXISTS invoice_payments AS
+        SELECT id_partener, serie_factura, numar_factura, cod_document, valoare, status
+        FROM collections WHERE COALESCE(transaction_type, 'invoice') != 'advance'
+        UNION ALL
+        SELECT id_partener, serie_factura, numar_factura, cod_document, valoare, status
+        FROM advance_applications;
+"#;
+
+const EPSILON: f64 = 0.005;
+
+/// Advances of the partner with something left to set against invoices,
+/// oldest first. Applications are only undone by cancelling them.
+pub fn open_advances(conn: &Connection, partner_id: &str) -> rusqlite::Result<Vec<PartnerAdvance>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.id, COALESCE(c.receipt_group_id, c.id), c.receipt_series, c.receipt_number,
+                c.data_incasare, c.valoare, c.status,
+                (SELECT COALESCE(SUM(a.valoare), 0) FROM advance_applications a
+                 WHERE a.advance_id = c.id AND a.status != 'cancelled')
+         FROM collections c
+         WHERE TRIM(c.id_partener) = TRIM(?1) AND c.transaction_type = 'advance' AND c.status != 'cancelled'
+         ORDER BY c.data_incasare, c.created_at",
+    )?;
+    let advances = stmt
+        .query_map([partner_id], |row| {
+            let valoare: f64 = row.get(5)?;
+            let applied: f64 = row.get(7)?;
+            Ok(PartnerAdvance {
+                id: row.get(0)?,
+                receipt_group_id: row.get(1)?,
+                receipt_series: row.get(2)?,
+                receipt_number: row.get(3)?,
+                data_incasare: row.get(4)?,
+                valoare,
+                status: row.get(6)?,
+                applied,
+                available: ((valoare - applied) * 100.0).round() / 100.0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(advances.into_iter().filter(|advance| advance.available > EPSILON).collect())
+}
+
+/// Sets the advances against the invoice lines proposed for their total,
+/// oldest advance first; an invoice may take money from two advances.
+/// Each advance used gets its compensation receipt number here.
+/// Returns the group the applications were recorded under.
+pub fn apply(
+    conn: &Connection,
+    partner_id: &str,
+    advances: &[PartnerAdvance],
+    lines: &[PaymentAllocationLine],
+) -> Result<String, String> {
+    let group_id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let mut left: Vec<(&PartnerAdvance, f64, Option<Allocated>)> =
+        advances.iter().map(|advance| (advance, advance.available, None)).collect();
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", []).map_err(|e| e.to_string())?;
+    for line in lines {
+        let mut owed = line.valoare;
+        for (advance, available, receipt) in left.iter_mut() {
+            if owed <= EPSILON {
+                break;
+            }
+            let taken = ((owed.min(*available)) * 100.0).round() / 100.0;
+            if taken <= EPSILON {
+                continue;
+            }
+            *available -= taken;
+            owed -= taken;
+            let receipt = match receipt {
+                Some(receipt) => receipt,
+                None => match numbering::allocate(conn, numbering::RECEIPT, &group_id) {
+                    Ok(allocated) => receipt.insert(allocated),
+                    Err(e) => {
+                        let _ = conn.execute("ROLLBACK", []);
+                        return Err(e);
+                    }
+                },
+            };
+            if let Err(e) = conn.execute(
+                "INSERT INTO advance_applications (
+                    id, group_id, advance_id, id_partener, serie_factura, numar_factura,
+                    cod_document, valoare, status, created_at, receipt_series, receipt_number
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 'pending', ?9, ?10, ?11)",
+                params![
+                    Uuid::new_v4().to_string(),
+                    group_id,
+                    advance.id,
+                    partner_id,
+                    line.serie_factura,
+                    line.numar_factura,
+                    line.cod_document,
+                    taken,
+                    now,
+                    receipt.series,
+                    receipt.number.to_string()
+                ],
+            ) {
+                let _ = conn.execute("ROLLBACK", []);
+                return Err(e.to_string());
+            }
+        }
+    }
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+
+    info!(
+        "[ADVANCES] Partner {}: {:.2} set against {} invoice(s) (group {})",
+        partner_id,
+        lines.iter().map(|line| line.valoare).sum::<f64>(),
+        lines.len(),
+        group_id
+    );
+    Ok(group_id)
+}
+
+pub fn applications(conn: &Connection, group_id: &str) -> rusqlite::Result<Vec<AdvanceApplication>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, group_id, advance_id, id_partener, serie_factura, numar_factura, cod_document,
+                valoare, status, synced_at, error_message, created_at, receipt_series, receipt_number
+         FROM advance_applications WHERE group_id = ?1 ORDER BY created_at, rowid",
+    )?;
+    let applications = stmt
+        .query_map([group_id], |row| {
+            Ok(AdvanceApplication {
+                id: row.get(0)?,
+                group_id: row.get(1)?,
+                advance_id: row.get(2)?,
+                id_partener: row.get(3)?,
+                serie_factura: row.get(4)?,
+                numar_factura: row.get(5)?,
+                cod_document: row.get(6)?,
+                valoare: row.get(7)?,
+                status: row.get(8)?,
+                synced_at: row.get(9)?,
+                error_message: row.get(10)?,
+                created_at: row.get(11)?,
+                receipt_series: row.get(12)?,
+                receipt_number: row.get(13)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(applications)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agent_settings (id INTEGER PRIMARY KEY, carnet_series TEXT,
+                 invoice_number_start INTEGER, invoice_number_end INTEGER, invoice_number_current INTEGER,
+                 receipt_series TEXT, receipt_number_start INTEGER, receipt_number_end INTEGER, receipt_number_current INTEGER);
+             INSERT INTO agent_settings (id, receipt_series, receipt_number_start, receipt_number_end, receipt_number_current)
+                 VALUES (1, 'CH', 1, 100, 8);
+             CREATE TABLE collections (id TEXT PRIMARY KEY, id_partener TEXT, serie_factura TEXT, numar_factura TEXT,
+                 cod_document TEXT, valoare REAL, data_incasare TEXT, status TEXT, created_at TEXT,
+                 receipt_series TEXT, receipt_number TEXT, receipt_group_id TEXT,
+                 transaction_type TEXT NOT NULL DEFAULT 'invoice');",
+        )
+        .unwrap();
+        conn.execute_batch(numbering::SCHEMA).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    /// Advance paid on receipt CH `number`, already in WME.
+    fn advance(conn: &Connection, number: u32, valoare: f64) {
+        conn.execute(
+            "INSERT INTO collections (id, id_partener, valoare, data_incasare, status, created_at,
+                 receipt_series, receipt_number, transaction_type)
+             VALUES (?1, 'P001', ?2, ?3, 'synced', ?3, 'CH', ?1, 'advance')",
+            params![number.to_string(), valoare, format!("2026-03-0{}T10:00:00+02:00", number % 10)],
+        )
+        .unwrap();
+    }
+
+    fn line(numar_factura: &str, valoare: f64) -> PaymentAllocationLine {
+        PaymentAllocationLine {
+            serie_factura: Some("KRN".to_string()),
+            numar_factura: Some(numar_factura.to_string()),
+            cod_document: None,
+            data: None,
+            termen: None,
+            rest: valoare,
+            valoare,
+            days_overdue: 0,
+            fully_paid: true,
+        }
+    }
+
+    fn receipt(application: &AdvanceApplication) -> (String, String) {
+        (
+            application.receipt_series.clone().unwrap_or_default(),
+            application.receipt_number.clone().unwrap_or_default(),
+        )
+    }
+
+    #[test]
+    fn compensation_takes_a_receipt_number_of_its_own() {
+        let conn = test_db();
+        advance(&conn, 7, 100.0);
+        let open = open_advances(&conn, "P001").unwrap();
+
+        let group_id = apply(&conn, "P001", &open, &[line("40", 60.0), line("41", 30.0)]).unwrap();
+
+        let applied = applications(&conn, &group_id).unwrap();
+        assert_eq!(applied.len(), 2);
+        assert!(applied.iter().all(|application| receipt(application) == ("CH".to_string(), "8".to_string())));
+        let registered: String = conn
+            .query_row(
+                "SELECT document_id FROM number_registry WHERE document_type = 'receipt' AND series = 'CH' AND number = 8",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(registered, group_id);
+        assert_eq!(open_advances(&conn, "P001").unwrap()[0].available, 10.0);
+    }
+
+    #[test]
+    fn each_advance_is_compensated_on_its_own_receipt() {
+        let conn = test_db();
+        advance(&conn, 5, 50.0);
+        advance(&conn, 6, 50.0);
+        let open = open_advances(&conn, "P001").unwrap();
+
+        let group_id = apply(&conn, "P001", &open, &[line("40", 80.0)]).unwrap();
+
+        let applied = applications(&conn, &group_id).unwrap();
+        let numbers: Vec<(String, f64, (String, String))> = applied
+            .iter()
+            .map(|application| (application.advance_id.clone(), application.valoare, receipt(application)))
+            .collect();
+        assert_eq!(
+            numbers,
+            vec![
+                ("5".to_string(), 50.0, ("CH".to_string(), "8".to_string())),
+                ("6".to_string(), 30.0, ("CH".to_string(), "9".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn nothing_is_recorded_when_the_carnet_is_used_up() {
+        let conn = test_db();
+        conn.execute("UPDATE agent_settings SET receipt_number_end = 7", []).unwrap();
+        advance(&conn, 7, 100.0);
+        let open = open_advances(&conn, "P001").unwrap();
+
+        assert!(apply(&conn, "P001", &open, &[line("40", 60.0)]).is_err());
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM advance_applications", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(open_advances(&conn, "P001").unwrap()[0].available, 100.0);
+    }
+}
@@ -0,0 +1,351 @@
+use chrono::{Local, NaiveDate};
+
+use crate::models::{AgingBuckets, AgingReport, AgingRow, ClientBalance};
+use crate::payment_allocation::parse_date;
+
+// Vechimea creanțelor: what partners still owe, split by how long it has
+// been due. Built on the open balances as the balances screen shows them
+// (WME documents and local invoices, less the local collections not yet in
+// WME), so both always agree.
+//
+// A document without a due date is not overdue, as when splitting payments.
+// Rows are ordered by what is overdue, the oldest debt first among equals:
+// the agent goes to the top of the list first.
+
+pub const BY_PARTNER: &str = "partner";
+pub const BY_LOCATION: &str = "location";
+pub const BY_AGENT: &str = "agent";
+
+const EPSILON: f64 = 0.005;
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+impl AgingBuckets {
+    fn add(&mut self, days_overdue: i64, amount: f64) {
+        match days_overdue {
+            ..=0 => self.current += amount,
+            1..=30 => self.days_1_30 += amount,
+            31..=60 => self.days_31_60 += amount,
+            61..=90 => self.days_61_90 += amount,
+            _ => self.days_over_90 += amount,
+        }
+        self.total += amount;
+    }
+
+    fn overdue(&self) -> f64 {
+        self.total - self.current
+    }
+
+    fn rounded(&self) -> AgingBuckets {
+        AgingBuckets {
+            current: round(self.current),
+            days_1_30: round(self.days_1_30),
+            days_31_60: round(self.days_31_60),
+            days_61_90: round(self.days_61_90),
+            days_over_90: round(self.days_over_90),
+            total: round(self.total),
+        }
+    }
+}
+
+fn text(value: &Option<String>) -> &str {
+    value.as_deref().map(str::trim).unwrap_or_default()
+}
+
+/// Group of a balance: key and name shown on the report.
+fn group(balance: &ClientBalance, group_by: &str) -> (String, String) {
+    let partner = text(&balance.denumire);
+    let partner = if partner.is_empty() { balance.id_partener.trim() } else { partner };
+    match group_by {
+        BY_LOCATION => {
+            let location = text(&balance.sediu);
+            let key = if text(&balance.id_sediu).is_empty() { location } else { text(&balance.id_sediu) };
+            (
+                format!("{}|{}", balance.id_partener.trim(), key),
+                if location.is_empty() { partner.to_string() } else { format!("{} - {}", partner, location) },
+            )
+        }
+        BY_AGENT => {
+            let agent = text(&balance.marca_agent);
+            (agent.to_string(), if agent.is_empty() { "Fără agent".to_string() } else { format!("Agent {}", agent) })
+        }
+        _ => (balance.id_partener.trim().to_string(), partner.to_string()),
+    }
+}
+
+pub fn build(balances: &[ClientBalance], as_of: &str, group_by: &str) -> Result<AgingReport, String> {
+    let as_of_date =
+        NaiveDate::parse_from_str(as_of, "%Y-%m-%d").map_err(|e| format!("Dată invalidă {}: {}", as_of, e))?;
+    if ![BY_PARTNER, BY_LOCATION, BY_AGENT].contains(&group_by) {
+        return Err(format!("Grupare necunoscută: {}", group_by));
+    }
+
+    let mut rows: Vec<AgingRow> = Vec::new();
+    let mut totals = AgingBuckets::default();
+    for balance in balances {
+        let rest = balance.rest.unwrap_or(0.0);
+        if rest <= EPSILON {
+            continue;
+        }
+        // Documents issued after the date did not exist yet
+        if balance
+            .data
+            .as_deref()
+            .and_then(parse_date)
+            .is_some_and(|issued| issued > as_of_date)
+        {
+            continue;
+        }
+        let days_overdue = balance
+            .termen
+            .as_deref()
+            .and_then(parse_date)
+            .map(|due| (as_of_date - due).num_days())
+            .unwrap_or(0);
+
+        let (key, name) = group(balance, group_by);
+        let index = match rows.iter().position(|row| row.key == key) {
+            Some(index) => index,
+            None => {
+                rows.push(AgingRow {
+                    key,
+                    name,
+                    partner_id: (group_by != BY_AGENT).then(|| balance.id_partener.trim().to_string()),
+                    document_count: 0,
+                    max_days_overdue: 0,
+                    buckets: AgingBuckets::default(),
+                });
+                rows.len() - 1
+            }
+        };
+        let row = &mut rows[index];
+        row.document_count += 1;
+        row.max_days_overdue = row.max_days_overdue.max(days_overdue);
+        row.buckets.add(days_overdue, rest);
+        totals.add(days_overdue, rest);
+    }
+
+    rows.sort_by(|a, b| {
+        b.buckets
+            .overdue()
+            .total_cmp(&a.buckets.overdue())
+            .then_with(|| b.max_days_overdue.cmp(&a.max_days_overdue))
+            .then_with(|| b.buckets.total.total_cmp(&a.buckets.total))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    for row in rows.iter_mut() {
+        row.buckets = row.buckets.rounded();
+    }
+
+    Ok(AgingReport {
+        as_of: as_of.to_string(),
+        group_by: group_by.to_string(),
+        rows,
+        totals: totals.rounded(),
+        generated_at: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+    })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([';', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The report as CSV for a spreadsheet: `;` separated, decimal comma, with a
+/// BOM so Excel reads the diacritics.
+pub fn to_csv(report: &AgingReport) -> String {
+    let amount = |value: f64| format!("{:.2}", value).replace('.', ",");
+    let mut csv = String::from("\u{feff}");
+    csv.push_str("Nume;Documente;Zile max. întârziere;Curent;1-30 zile;31-60 zile;61-90 zile;Peste 90 zile;Total\r\n");
+    let mut line = |name: &str, documents: String, days: String, buckets: &AgingBuckets| {
+        csv.push_str(&format!(
+            "{};{};{};{};{};{};{};{};{}\r\n",
+            csv_field(name),
+            documents,
+            days,
+            amount(buckets.current),
+            amount(buckets.days_1_30),
+            amount(buckets.days_31_60),
+            amount(buckets.days_61_90),
+            amount(buckets.days_over_90),
+            amount(buckets.total)
+        ));
+    };
+    for row in &report.rows {
+        line(&row.name, row.document_count.to_string(), row.max_days_overdue.max(0).to_string(), &row.buckets);
+    }
+    line("TOTAL", String::new(), String::new(), &report.totals);
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AS_OF: &str = "2026-03-31";
+
+    fn balance(partner: &str, data: Option<&str>, termen: Option<&str>, rest: f64) -> ClientBalance {
+        ClientBalance {
+            id: None,
+            id_partener: partner.to_string(),
+            cod_fiscal: None,
+            denumire: Some(format!("{} SRL", partner)),
+            tip_document: Some("FACTURA".to_string()),
+            cod_document: None,
+            serie: Some("KRN".to_string()),
+            numar: None,
+            data: data.map(str::to_string),
+            valoare: Some(rest),
+            rest: Some(rest),
+            termen: termen.map(str::to_string),
+            moneda: Some("RON".to_string()),
+            sediu: None,
+            id_sediu: None,
+            curs: None,
+            observatii: None,
+            cod_obligatie: None,
+            marca_agent: None,
+            synced_at: None,
+        }
+    }
+
+    fn due(termen: &str, rest: f64) -> ClientBalance {
+        balance("Alfa", Some("01/12/2025"), Some(termen), rest)
+    }
+
+    fn amounts(buckets: &AgingBuckets) -> [f64; 6] {
+        [
+            buckets.current,
+            buckets.days_1_30,
+            buckets.days_31_60,
+            buckets.days_61_90,
+            buckets.days_over_90,
+            buckets.total,
+        ]
+    }
+
+    #[test]
+    fn documents_fall_in_the_bucket_of_their_days_overdue() {
+        // 0, 1, 30, 31, 60, 61, 90 and 91 days overdue, and one not due yet
+        let balances = [
+            due("31/03/2026", 1.0),
+            due("30/03/2026", 2.0),
+            due("01/03/2026", 4.0),
+            due("28/02/2026", 8.0),
+            due("30/01/2026", 16.0),
+            due("29/01/2026", 32.0),
+            due("31/12/2025", 64.0),
+            due("30/12/2025", 128.0),
+            due("15/04/2026", 256.0),
+        ];
+        let report = build(&balances, AS_OF, BY_PARTNER).unwrap();
+
+        assert_eq!(amounts(&report.totals), [257.0, 6.0, 24.0, 96.0, 128.0, 511.0]);
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].document_count, 9);
+        assert_eq!(report.rows[0].max_days_overdue, 91);
+        assert_eq!(amounts(&report.rows[0].buckets), amounts(&report.totals));
+    }
+
+    #[test]
+    fn documents_issued_after_the_date_are_left_out() {
+        let balances = [
+            balance("Alfa", Some("31/03/2026"), Some("15/03/2026"), 10.0),
+            balance("Alfa", Some("01/04/2026"), Some("15/03/2026"), 20.0),
+            balance("Alfa", Some("2026-04-01T09:00:00"), None, 40.0),
+        ];
+        let report = build(&balances, AS_OF, BY_PARTNER).unwrap();
+
+        assert_eq!(report.totals.total, 10.0);
+        assert_eq!(report.rows[0].document_count, 1);
+    }
+
+    #[test]
+    fn documents_without_a_due_date_are_not_overdue() {
+        let balances = [
+            balance("Alfa", Some("01/01/2025"), None, 10.0),
+            balance("Alfa", None, Some(""), 20.0),
+            balance("Alfa", None, None, 0.001),
+            balance("Alfa", Some("01/01/2025"), Some("01/02/2025"), -50.0),
+        ];
+        let report = build(&balances, AS_OF, BY_PARTNER).unwrap();
+
+        assert_eq!(amounts(&report.totals), [30.0, 0.0, 0.0, 0.0, 0.0, 30.0]);
+        assert_eq!(report.rows[0].document_count, 2);
+        assert_eq!(report.rows[0].max_days_overdue, 0);
+    }
+
+    fn located(partner: &str, id_sediu: &str, agent: &str, termen: &str, rest: f64) -> ClientBalance {
+        ClientBalance {
+            sediu: Some(format!("Sediu {}", id_sediu)),
+            id_sediu: Some(id_sediu.to_string()),
+            marca_agent: Some(agent.to_string()).filter(|agent| !agent.is_empty()),
+            ..balance(partner, Some("01/01/2026"), Some(termen), rest)
+        }
+    }
+
+    fn spread() -> Vec<ClientBalance> {
+        vec![
+            located("Alfa", "1", "12", "31/03/2026", 100.0),
+            located("Alfa", "2", "12", "01/03/2026", 10.0),
+            located("Beta", "3", "12", "15/03/2026", 50.0),
+            located("Gama", "4", "", "01/12/2025", 5.0),
+        ]
+    }
+
+    fn rows(report: &AgingReport) -> Vec<(&str, &str, Option<&str>, f64)> {
+        report
+            .rows
+            .iter()
+            .map(|row| (row.key.as_str(), row.name.as_str(), row.partner_id.as_deref(), row.buckets.total))
+            .collect()
+    }
+
+    #[test]
+    fn groups_by_partner() {
+        let report = build(&spread(), AS_OF, BY_PARTNER).unwrap();
+        // Most overdue first, whatever the total
+        assert_eq!(
+            rows(&report),
+            [
+                ("Beta", "Beta SRL", Some("Beta"), 50.0),
+                ("Alfa", "Alfa SRL", Some("Alfa"), 110.0),
+                ("Gama", "Gama SRL", Some("Gama"), 5.0),
+            ]
+        );
+        assert_eq!(report.rows[1].document_count, 2);
+        assert_eq!(report.totals.total, 165.0);
+    }
+
+    #[test]
+    fn groups_by_location() {
+        let report = build(&spread(), AS_OF, BY_LOCATION).unwrap();
+        assert_eq!(
+            rows(&report),
+            [
+                ("Beta|3", "Beta SRL - Sediu 3", Some("Beta"), 50.0),
+                ("Alfa|2", "Alfa SRL - Sediu 2", Some("Alfa"), 10.0),
+                ("Gama|4", "Gama SRL - Sediu 4", Some("Gama"), 5.0),
+                ("Alfa|1", "Alfa SRL - Sediu 1", Some("Alfa"), 100.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_by_agent() {
+        let report = build(&spread(), AS_OF, BY_AGENT).unwrap();
+        assert_eq!(rows(&report), [("12", "Agent 12", None, 160.0), ("", "Fără agent", None, 5.0)]);
+        assert_eq!(report.rows[0].document_count, 3);
+    }
+
+    #[test]
+    fn refuses_bad_dates_and_groupings() {
+        assert!(build(&spread(), "31/03/2026", BY_PARTNER).is_err());
+        assert!(build(&spread(), AS_OF, "region").is_err());
+    }
+}
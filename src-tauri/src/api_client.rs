@@ -209,7 +209,6 @@ pub struct ArticleInfo {
     #[serde(rename = "ID")]
     pub id: String,
     #[serde(rename = "CodObiect")]
-    #[allow(dead_code)]
     pub cod_obiect: Option<String>,
     #[serde(rename = "Denumire")]
     pub denumire: String,
@@ -224,10 +223,8 @@ pub struct ArticleInfo {
     #[allow(dead_code)]
     pub procent_tva: Option<String>,
     #[serde(rename = "CodExtern")]
-    #[allow(dead_code)]
     pub cod_extern: Option<String>,
     #[serde(rename = "CodIntern")]
-    #[allow(dead_code)]
     pub cod_intern: Option<String>,
     #[serde(rename = "Clasa")]
     pub clasa: Option<String>,
@@ -0,0 +1,220 @@
+use chrono::{Months, Utc};
+use log::info;
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+use crate::models::ArchiveResult;
+
+// Retention / archiving of old documents.
+//
+// Sent invoices and synced collections older than the retention window are
+// moved from facturi.db into archive.db, which stays attached as the
+// `archive` schema so reports can still read it on demand. Only documents the
+// server has confirmed (invoices `sent`, collections `synced`) are ever moved.
+
+pub const DEFAULT_RETENTION_MONTHS: i64 = 12;
+/// Never archive anything newer than this, whatever the agent configures:
+/// daily reports and the balances screen need the recent documents.
+pub const MIN_RETENTION_MONTHS: i64 = 3;
+
+const ARCHIVED_TABLES: [&str; 4] = ["invoices", "invoice_items", "collections", "invoice_certificate_cache"];
+
+/// Attaches archive.db as the `archive` schema and brings its tables in line
+/// with the current columns of the live tables.
+pub fn attach(conn: &Connection, archive_path: &Path) -> rusqlite::Result<()> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS archive",
+        [archive_path.to_string_lossy().to_string()],
+    )?;
+    sync_schema(conn)
+}
+
+fn table_columns(conn: &Connection, schema: &str, table: &str) -> rusqlite::Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA {}.table_info({})", schema, table))?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get(1)?, row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(columns)
+}
+
+/// Creates missing archive tables and adds columns introduced by later
+/// migrations, so `INSERT INTO archive.x SELECT ... FROM main.x` keeps working.
+fn sync_schema(conn: &Connection) -> rusqlite::Result<()> {
+    for table in ARCHIVED_TABLES {
+        let archive_columns = table_columns(conn, "archive", table)?;
+
+        if archive_columns.is_empty() {
+            conn.execute_batch(&format!(
+                "CREATE TABLE archive.{table} AS SELECT * FROM main.{table} WHERE 0;
+                 ALTER TABLE archive.{table} ADD COLUMN archived_at TEXT;",
+                table = table
+            ))?;
+            continue;
+        }
+
+        for (name, decl_type) in table_columns(conn, "main", table)? {
+            if !archive_columns.iter().any(|(existing, _)| existing == &name) {
+                conn.execute(
+                    &format!("ALTER TABLE archive.{} ADD COLUMN \"{}\" {}", table, name, decl_type),
+                    [],
+                )?;
+            }
+        }
+    }
+
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS archive.idx_archive_invoices_created ON invoices(created_at);
+         CREATE INDEX IF NOT EXISTS archive.idx_archive_invoice_items_invoice ON invoice_items(invoice_id);
+         CREATE INDEX IF NOT EXISTS archive.idx_archive_collections_data ON collections(data_incasare);",
+    )?;
+
+    Ok(())
+}
+
+fn column_list(conn: &Connection, table: &str) -> rusqlite::Result<String> {
+    let columns: Vec<String> = table_columns(conn, "main", table)?
+        .into_iter()
+        .map(|(name, _)| format!("\"{}\"", name))
+        .collect();
+    Ok(columns.join(", "))
+}
+
+/// Table expression for report queries: the live table alone, or the live
+/// table together with its archived rows.
+pub fn report_source(conn: &Connection, table: &str, include_archived: bool) -> rusqlite::Result<String> {
+    if !include_archived {
+        return Ok(table.to_string());
+    }
+
+    let columns = column_list(conn, table)?;
+    Ok(format!(
+        "(SELECT {cols} FROM main.{table} UNION ALL SELECT {cols} FROM archive.{table})",
+        cols = columns,
+        table = table
+    ))
+}
+
+pub fn retention_months(conn: &Connection) -> i64 {
+    conn.query_row(
+        "SELECT retention_months FROM agent_settings WHERE id = 1",
+        [],
+        |row| row.get::<_, Option<i64>>(0),
+    )
+    .ok()
+    .flatten()
+    .unwrap_or(DEFAULT_RETENTION_MONTHS)
+    .max(MIN_RETENTION_MONTHS)
+}
+
+fn move_rows(conn: &Connection, table: &str, condition: &str, now: &str) -> rusqlite::Result<usize> {
+    let columns = column_list(conn, table)?;
+    let copied = conn.execute(
+        &format!(
+            "INSERT INTO archive.{table} ({cols}, archived_at) SELECT {cols}, ?1 FROM main.{table} WHERE {cond}",
+            table = table,
+            cols = columns,
+            cond = condition
+        ),
+        [now],
+    )?;
+    let deleted = conn.execute(&format!("DELETE FROM main.{} WHERE {}", table, condition), [])?;
+
+    if copied != deleted {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ABORT),
+            Some(format!("archive {}: copied {} rows but deleted {}", table, copied, deleted)),
+        ));
+    }
+
+    Ok(copied)
+}
+
+/// Moves documents older than `months` into the archive. Must run inside a
+/// transaction opened by the caller.
+pub fn archive_documents(conn: &Connection, months: i64) -> rusqlite::Result<ArchiveResult> {
+    let months = months.max(MIN_RETENTION_MONTHS);
+    let cutoff = Utc::now()
+        .date_naive()
+        .checked_sub_months(Months::new(months as u32))
+        .unwrap_or_else(|| Utc::now().date_naive())
+        .format("%Y-%m-%d")
+        .to_string();
+    let now = Utc::now().to_rfc3339();
+
+    conn.execute(
+        "CREATE TEMP TABLE IF NOT EXISTS archive_invoice_ids (id TEXT PRIMARY KEY)",
+        [],
+    )?;
+    conn.execute("DELETE FROM temp.archive_invoice_ids", [])?;
+    conn.execute(
+        "INSERT INTO temp.archive_invoice_ids (id)
+         SELECT id FROM main.invoices
+         WHERE status = 'sent'
+           AND substr(COALESCE(sent_at, created_at), 1, 10) < ?1",
+        params![cutoff],
+    )?;
+
+    let invoice_items = move_rows(
+        conn,
+        "invoice_items",
+        "invoice_id IN (SELECT id FROM temp.archive_invoice_ids)",
+        &now,
+    )?;
+    let certificates = move_rows(
+        conn,
+        "invoice_certificate_cache",
+        "invoice_id IN (SELECT id FROM temp.archive_invoice_ids)",
+        &now,
+    )?;
+    let invoices = move_rows(conn, "invoices", "id IN (SELECT id FROM temp.archive_invoice_ids)", &now)?;
+
+    // A receipt is archived as a whole, and only once every row of it is synced
+    // and the balances pulled from WME after that sync already reflect it.
+    let collections_condition = format!(
+        "COALESCE(receipt_group_id, id) IN (
+            SELECT COALESCE(c.receipt_group_id, c.id) FROM main.collections c
+            GROUP BY COALESCE(c.receipt_group_id, c.id)
+            HAVING SUM(CASE WHEN c.status = 'synced' THEN 0 ELSE 1 END) = 0
+               AND MAX(substr(c.data_incasare, 1, 10)) < '{cutoff}'
+               AND MAX(c.synced_at) < COALESCE((SELECT MAX(synced_at) FROM main.client_balances), '')
+        )
+        -- An advance stays until it is set against invoices in full
+        AND COALESCE(receipt_group_id, id) NOT IN (
+            SELECT COALESCE(a.receipt_group_id, a.id) FROM main.collections a
+            WHERE a.transaction_type = 'advance'
+              AND a.valoare - (
+                  SELECT COALESCE(SUM(x.valoare), 0) FROM main.advance_applications x
+                  WHERE x.advance_id = a.id AND x.status != 'cancelled'
+              ) > 0.005
+        )",
+        cutoff = cutoff
+    );
+    let collections = move_rows(conn, "collections", &collections_condition, &now)?;
+
+    conn.execute("DELETE FROM temp.archive_invoice_ids", [])?;
+
+    info!(
+        "[ARCHIVE] Cutoff {}: moved {} invoices ({} items, {} certificates), {} collections",
+        cutoff, invoices, invoice_items, certificates, collections
+    );
+
+    Ok(ArchiveResult {
+        cutoff_date: cutoff,
+        invoices,
+        invoice_items,
+        certificates,
+        collections,
+    })
+}
+
+/// Size in bytes of an attached schema (`main` or `archive`).
+pub fn schema_size(conn: &Connection, schema: &str) -> i64 {
+    let pages: i64 = conn
+        .query_row(&format!("PRAGMA {}.page_count", schema), [], |row| row.get(0))
+        .unwrap_or(0);
+    let page_size: i64 = conn
+        .query_row(&format!("PRAGMA {}.page_size", schema), [], |row| row.get(0))
+        .unwrap_or(0);
+    pages * page_size
+}
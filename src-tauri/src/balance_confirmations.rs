@@ -0,0 +1,344 @@
+use chrono::{Local, NaiveDate, Utc};
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+
+use crate::models::{BalanceConfirmation, BalanceConfirmationDocument};
+use crate::payment_allocation::parse_date;
+
+// Confirmare de sold: the letter sent to a client at year end listing what
+// it owes on a reference date, to be signed and sent back.
+//
+// The open documents come from the WME balances (client_balances), the
+// accounting's view of the account; hidden documents are left out. WME only
+// knows what is open now, so a document counts when it was issued on or
+// before the reference date, with its current rest.
+//
+// Each letter keeps a copy of the documents it listed, so it prints the same
+// until it is generated again. A letter whose signed copy came back is no
+// longer regenerated.
+
+/// Letter printed, signed copy not back yet
+pub const SENT: &str = "sent";
+/// Signed copy back, the client agrees with the balance
+pub const CONFIRMED: &str = "confirmed";
+/// Signed copy back with a different balance
+pub const DISPUTED: &str = "disputed";
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS balance_confirmations (
+        id TEXT PRIMARY KEY,
+        partner_id TEXT NOT NULL,
+        partner_name TEXT NOT NULL,
+        partner_cif TEXT,
+        partner_reg_com TEXT,
+        partner_address TEXT,
+        reference_date TEXT NOT NULL,
+        balance REAL NOT NULL,
+        generated_at TEXT NOT NULL,
+        document_path TEXT,
+        return_status TEXT NOT NULL DEFAULT 'sent',
+        returned_at TEXT,
+        partner_balance REAL,
+        notes TEXT,
+        UNIQUE(partner_id, reference_date)
+    );
+
+    CREATE TABLE IF NOT EXISTS balance_confirmation_documents (
+        confirmation_id TEXT NOT NULL,
+        line_no INTEGER NOT NULL,
+        tip_document TEXT NOT NULL,
+        document TEXT NOT NULL,
+        data TEXT,
+        termen TEXT,
+        valoare REAL NOT NULL,
+        rest REAL NOT NULL,
+        PRIMARY KEY (confirmation_id, line_no),
+        FOREIGN KEY (confirmation_id) REFERENCES balance_confirmations(id) ON DELETE CASCADE
+    );
+"#;
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn parse_reference(reference_date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(reference_date, "%Y-%m-%d")
+        .map_err(|e| format!("Dată de referință invalidă {}: {}", reference_date, e))
+}
+
+/// Partners of the agent with documents in the WME balances, by name. With
+/// no agent set, every partner with a balance.
+pub fn agent_partners(conn: &Connection, marca_agent: Option<&str>) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT TRIM(cb.id_partener), p.name
+         FROM client_balances cb
+         JOIN partners p ON TRIM(p.id) = TRIM(cb.id_partener)
+         WHERE ?1 IS NULL OR TRIM(COALESCE(cb.marca_agent, '')) = TRIM(?1)
+         ORDER BY p.name",
+    )?;
+    let rows = stmt.query_map([marca_agent], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Open WME documents of the partner issued by the reference date.
+pub fn open_documents(
+    conn: &Connection,
+    partner_id: &str,
+    reference: NaiveDate,
+) -> rusqlite::Result<Vec<BalanceConfirmationDocument>> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(NULLIF(TRIM(cb.tip_document), ''), 'FACTURA'),
+                TRIM(COALESCE(cb.serie, '')) || TRIM(COALESCE(cb.numar, '')),
+                cb.data, cb.termen, COALESCE(cb.valoare, 0), COALESCE(cb.rest, 0)
+         FROM client_balances cb
+         WHERE TRIM(cb.id_partener) = TRIM(?1)
+           AND NOT EXISTS (
+               SELECT 1 FROM ignored_balances ib
+               WHERE ib.id_partener = cb.id_partener
+                 AND ib.cod_document = COALESCE(cb.cod_document, '')
+                 AND ib.serie = COALESCE(cb.serie, '')
+                 AND ib.numar = COALESCE(cb.numar, '')
+           )",
+    )?;
+    let rows = stmt.query_map([partner_id], |row| {
+        Ok(BalanceConfirmationDocument {
+            tip_document: row.get(0)?,
+            document: row.get(1)?,
+            data: row.get(2)?,
+            termen: row.get(3)?,
+            valoare: row.get(4)?,
+            rest: row.get(5)?,
+        })
+    })?;
+
+    let mut documents = Vec::new();
+    for row in rows {
+        let document = row?;
+        let issued = document.data.as_deref().and_then(parse_date);
+        if issued.map_or(true, |issued| issued <= reference) {
+            documents.push((issued, document));
+        }
+    }
+    documents.sort_by(|a, b| (a.0.is_none(), a.0, &a.1.document).cmp(&(b.0.is_none(), b.0, &b.1.document)));
+    Ok(documents.into_iter().map(|(_, document)| document).collect())
+}
+
+/// Status of the partner's letter for the reference date, if there is one.
+pub fn return_status(conn: &Connection, partner_id: &str, reference_date: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT return_status FROM balance_confirmations WHERE partner_id = ?1 AND reference_date = ?2",
+        params![partner_id, reference_date],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Writes the partner's letter for the reference date, replacing one not yet
+/// returned. Run inside a transaction.
+pub fn generate(conn: &Connection, partner_id: &str, reference_date: &str) -> Result<BalanceConfirmation, String> {
+    let reference = parse_reference(reference_date)?;
+    if return_status(conn, partner_id, reference_date)
+        .map_err(|e| e.to_string())?
+        .is_some_and(|status| status != SENT)
+    {
+        return Err(format!(
+            "Confirmarea de sold din {} pentru partenerul {} a fost deja returnată semnată",
+            reference_date, partner_id
+        ));
+    }
+
+    let (partner_name, partner_cif, partner_reg_com, partner_address): (
+        String,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    ) = conn
+        .query_row(
+            "SELECT p.name, p.cif, p.reg_com,
+                    NULLIF(TRIM(COALESCE(l.address, '') ||
+                        CASE WHEN TRIM(COALESCE(l.localitate, '')) <> '' THEN ', ' || TRIM(l.localitate) ELSE '' END ||
+                        CASE WHEN TRIM(COALESCE(l.judet, '')) <> '' THEN ', jud. ' || TRIM(l.judet) ELSE '' END, ', '), '')
+             FROM partners p
+             LEFT JOIN locations l ON l.partner_id = p.id
+             WHERE TRIM(p.id) = TRIM(?1)
+             ORDER BY
+                 CASE WHEN IFNULL(l.inactiv, 'NU') IN ('DA', '1', 'true', 'TRUE') THEN 1 ELSE 0 END,
+                 l.id
+             LIMIT 1",
+            [partner_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| format!("Partenerul nu a fost găsit: {}", e))?;
+
+    let documents = open_documents(conn, partner_id, reference).map_err(|e| e.to_string())?;
+    let balance = round(documents.iter().map(|document| document.rest).sum());
+    let generated_at = Local::now().format("%Y-%m-%d %H:%M").to_string();
+
+    let id: String = conn
+        .query_row(
+            "SELECT id FROM balance_confirmations WHERE partner_id = ?1 AND reference_date = ?2",
+            params![partner_id, reference_date],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    conn.execute(
+        "INSERT INTO balance_confirmations (
+            id, partner_id, partner_name, partner_cif, partner_reg_com, partner_address,
+            reference_date, balance, generated_at, document_path, return_status
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, NULL, ?10)
+         ON CONFLICT(id) DO UPDATE SET
+            partner_name = excluded.partner_name, partner_cif = excluded.partner_cif,
+            partner_reg_com = excluded.partner_reg_com, partner_address = excluded.partner_address,
+            balance = excluded.balance, generated_at = excluded.generated_at, document_path = NULL",
+        params![
+            id,
+            partner_id,
+            partner_name,
+            partner_cif,
+            partner_reg_com,
+            partner_address,
+            reference_date,
+            balance,
+            generated_at,
+            SENT
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM balance_confirmation_documents WHERE confirmation_id = ?1", [&id])
+        .map_err(|e| e.to_string())?;
+    for (line_no, document) in documents.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO balance_confirmation_documents (
+                confirmation_id, line_no, tip_document, document, data, termen, valoare, rest
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                id,
+                line_no as i64,
+                document.tip_document,
+                document.document,
+                document.data,
+                document.termen,
+                document.valoare,
+                document.rest
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    info!(
+        "[BALANCE_CONFIRMATION] Letter for {} on {}: {} documents, balance {:.2}",
+        partner_id,
+        reference_date,
+        documents.len(),
+        balance
+    );
+    load(conn, &id)
+}
+
+pub fn load(conn: &Connection, id: &str) -> Result<BalanceConfirmation, String> {
+    let mut confirmation = conn
+        .query_row(
+            "SELECT id, partner_id, partner_name, partner_cif, partner_reg_com, partner_address,
+                    reference_date, balance, generated_at, document_path, return_status,
+                    returned_at, partner_balance, notes
+             FROM balance_confirmations WHERE id = ?1",
+            [id],
+            |row| {
+                Ok(BalanceConfirmation {
+                    id: row.get(0)?,
+                    partner_id: row.get(1)?,
+                    partner_name: row.get(2)?,
+                    partner_cif: row.get(3)?,
+                    partner_reg_com: row.get(4)?,
+                    partner_address: row.get(5)?,
+                    reference_date: row.get(6)?,
+                    documents: Vec::new(),
+                    balance: row.get(7)?,
+                    generated_at: row.get(8)?,
+                    document_path: row.get(9)?,
+                    return_status: row.get(10)?,
+                    returned_at: row.get(11)?,
+                    partner_balance: row.get(12)?,
+                    notes: row.get(13)?,
+                })
+            },
+        )
+        .map_err(|e| format!("Confirmarea de sold nu a fost găsită: {}", e))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT tip_document, document, data, termen, valoare, rest
+             FROM balance_confirmation_documents WHERE confirmation_id = ?1 ORDER BY line_no",
+        )
+        .map_err(|e| e.to_string())?;
+    confirmation.documents = stmt
+        .query_map([id], |row| {
+            Ok(BalanceConfirmationDocument {
+                tip_document: row.get(0)?,
+                document: row.get(1)?,
+                data: row.get(2)?,
+                termen: row.get(3)?,
+                valoare: row.get(4)?,
+                rest: row.get(5)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    Ok(confirmation)
+}
+
+/// Letters for the reference date, by partner name.
+pub fn list(conn: &Connection, reference_date: &str) -> Result<Vec<BalanceConfirmation>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM balance_confirmations WHERE reference_date = ?1 ORDER BY partner_name")
+        .map_err(|e| e.to_string())?;
+    let ids: Vec<String> = stmt
+        .query_map([reference_date], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    ids.iter().map(|id| load(conn, id)).collect()
+}
+
+pub fn set_document_path(conn: &Connection, id: &str, path: &str) -> rusqlite::Result<usize> {
+    conn.execute("UPDATE balance_confirmations SET document_path = ?1 WHERE id = ?2", params![path, id])
+}
+
+/// Records whether the signed copy came back and with what answer. Setting
+/// the letter back to `SENT` clears the answer.
+pub fn record_return(
+    conn: &Connection,
+    id: &str,
+    status: &str,
+    partner_balance: Option<f64>,
+    notes: Option<&str>,
+) -> Result<BalanceConfirmation, String> {
+    if ![SENT, CONFIRMED, DISPUTED].contains(&status) {
+        return Err(format!("Stare necunoscută: {}", status));
+    }
+    if status == DISPUTED && partner_balance.is_none() {
+        return Err("Introdu soldul comunicat de client".to_string());
+    }
+    let notes = notes.map(str::trim).filter(|notes| !notes.is_empty());
+    let (returned_at, partner_balance, notes) = if status == SENT {
+        (None, None, None)
+    } else {
+        (Some(Utc::now().to_rfc3339()), partner_balance.filter(|_| status == DISPUTED), notes)
+    };
+    let updated = conn
+        .execute(
+            "UPDATE balance_confirmations
+             SET return_status = ?1, returned_at = ?2, partner_balance = ?3, notes = ?4
+             WHERE id = ?5",
+            params![status, returned_at, partner_balance, notes, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Confirmarea de sold nu a fost găsită".to_string());
+    }
+    info!("[BALANCE_CONFIRMATION] Letter {} marked {}", id, status);
+    load(conn, id)
+}
@@ -0,0 +1,267 @@
+use chrono::Utc;
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::models::{CashLimitCheck, CashLimitRule, CollectionAllocationRequest};
+
+// Legal limits on cash collections (Legea 70/2015).
+//
+// Cash received from one partner is capped per day, and a transaction above
+// the cap may not be split into several cash payments. The caps differ for
+// legal entities and for natural persons (`partners.persoana_fizica`). Each
+// (partner type, scope) pair is a rule the office can change: the amount and
+// whether going over it blocks the receipt, only warns, or is not checked.
+
+pub const LEGAL_ENTITY: &str = "legal_entity";
+pub const INDIVIDUAL: &str = "individual";
+
+/// Cash from one partner on one day
+pub const PER_DAY: &str = "per_day";
+/// Cash on one invoice, over all its receipts
+pub const PER_DOCUMENT: &str = "per_document";
+
+pub const BLOCK: &str = "block";
+pub const WARN: &str = "warn";
+pub const OFF: &str = "off";
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS cash_limit_rules (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        partner_type TEXT NOT NULL,
+        scope TEXT NOT NULL,
+        limit_amount REAL NOT NULL,
+        action TEXT NOT NULL DEFAULT 'block',
+        updated_at TEXT NOT NULL,
+        UNIQUE(partner_type, scope)
+    );
+"#;
+
+// Caps in force since 2015: 5.000 lei between companies, 10.000 lei from a
+// natural person
+const DEFAULT_RULES: [(&str, &str, f64); 4] = [
+    (LEGAL_ENTITY, PER_DAY, 5000.0),
+    (LEGAL_ENTITY, PER_DOCUMENT, 5000.0),
+    (INDIVIDUAL, PER_DAY, 10000.0),
+    (INDIVIDUAL, PER_DOCUMENT, 10000.0),
+];
+
+const EPSILON: f64 = 0.005;
+
+pub fn seed(conn: &Connection) -> rusqlite::Result<()> {
+    let now = Utc::now().to_rfc3339();
+    for (partner_type, scope, limit) in DEFAULT_RULES {
+        conn.execute(
+            "INSERT OR IGNORE INTO cash_limit_rules (partner_type, scope, limit_amount, action, updated_at)
+             VALUES (?1, ?2, ?3, 'block', ?4)",
+            params![partner_type, scope, limit, now],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn rules(conn: &Connection) -> rusqlite::Result<Vec<CashLimitRule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, partner_type, scope, limit_amount, action, updated_at FROM cash_limit_rules
+         ORDER BY partner_type, scope",
+    )?;
+    let rules = stmt
+        .query_map([], |row| {
+            Ok(CashLimitRule {
+                id: row.get(0)?,
+                partner_type: row.get(1)?,
+                scope: row.get(2)?,
+                limit_amount: row.get(3)?,
+                action: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rules)
+}
+
+pub fn save_rule(conn: &Connection, rule: &CashLimitRule) -> Result<(), String> {
+    if ![LEGAL_ENTITY, INDIVIDUAL].contains(&rule.partner_type.as_str()) {
+        return Err(format!("Tip de partener necunoscut: {}", rule.partner_type));
+    }
+    if ![PER_DAY, PER_DOCUMENT].contains(&rule.scope.as_str()) {
+        return Err(format!("Tip de plafon necunoscut: {}", rule.scope));
+    }
+    if ![BLOCK, WARN, OFF].contains(&rule.action.as_str()) {
+        return Err(format!("Acțiune necunoscută: {}", rule.action));
+    }
+    if !rule.limit_amount.is_finite() || rule.limit_amount <= 0.0 {
+        return Err("Plafonul trebuie să fie mai mare decât 0".to_string());
+    }
+
+    conn.execute(
+        "INSERT INTO cash_limit_rules (partner_type, scope, limit_amount, action, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(partner_type, scope) DO UPDATE SET
+            limit_amount = excluded.limit_amount, action = excluded.action, updated_at = excluded.updated_at",
+        params![rule.partner_type, rule.scope, rule.limit_amount, rule.action, Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+    info!(
+        "[CASH_LIMITS] Rule {} {} set to {:.2} ({})",
+        rule.partner_type, rule.scope, rule.limit_amount, rule.action
+    );
+    Ok(())
+}
+
+/// `persoana_fizica` comes from WME as "DA"/"NU"; unknown partners are
+/// treated as legal entities, which have the lower cap.
+pub fn partner_type(conn: &Connection, partner_id: &str) -> &'static str {
+    let flag: Option<String> = conn
+        .query_row("SELECT persoana_fizica FROM partners WHERE id = ?1", [partner_id], |row| row.get(0))
+        .optional()
+        .ok()
+        .flatten()
+        .flatten();
+    match flag.as_deref().map(|value| value.trim().to_uppercase()) {
+        Some(value) if ["DA", "1", "TRUE", "Y"].contains(&value.as_str()) => INDIVIDUAL,
+        _ => LEGAL_ENTITY,
+    }
+}
+
+fn rule(conn: &Connection, partner_type: &str, scope: &str) -> rusqlite::Result<Option<(f64, String)>> {
+    conn.query_row(
+        "SELECT limit_amount, action FROM cash_limit_rules WHERE partner_type = ?1 AND scope = ?2",
+        params![partner_type, scope],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Cash received from the partner on the local `day` (YYYY-MM-DD),
+/// cancelled receipts and other payment methods excluded. A receipt whose
+/// sync failed was still paid.
+fn collected_on_day(conn: &Connection, partner_id: &str, day: &str) -> rusqlite::Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(valoare), 0) FROM collections
+         WHERE id_partener = ?1 AND local_day(data_incasare) = ?2 AND status != 'cancelled'
+           AND payment_method = 'cash'",
+        params![partner_id, day],
+        |row| row.get(0),
+    )
+}
+
+fn collected_on_document(
+    conn: &Connection,
+    partner_id: &str,
+    allocation: &CollectionAllocationRequest,
+) -> rusqlite::Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(valoare), 0) FROM collections
+         WHERE id_partener = ?1
+           AND trim(COALESCE(serie_factura, '')) = ?2
+           AND trim(COALESCE(numar_factura, '')) = ?3
+           AND trim(COALESCE(cod_document, '')) = ?4
+           AND status != 'cancelled' AND payment_method = 'cash'",
+        params![
+            partner_id,
+            allocation.serie_factura.as_deref().unwrap_or_default().trim(),
+            allocation.numar_factura.as_deref().unwrap_or_default().trim(),
+            allocation.cod_document.as_deref().unwrap_or_default().trim(),
+        ],
+        |row| row.get(0),
+    )
+}
+
+fn document_label(allocation: &CollectionAllocationRequest) -> String {
+    let label = format!(
+        "{} {}",
+        allocation.serie_factura.as_deref().unwrap_or_default().trim(),
+        allocation.numar_factura.as_deref().unwrap_or_default().trim()
+    );
+    if label.trim().is_empty() {
+        allocation.cod_document.clone().unwrap_or_default()
+    } else {
+        label.trim().to_string()
+    }
+}
+
+/// Checks a receipt about to be recorded for `partner_id` on `day`
+/// (YYYY-MM-DD) against the rules. Returns every cap that would be exceeded;
+/// `blocking` ones must stop the receipt.
+pub fn check(
+    conn: &Connection,
+    partner_id: &str,
+    day: &str,
+    allocations: &[CollectionAllocationRequest],
+) -> Result<Vec<CashLimitCheck>, String> {
+    let partner_type = partner_type(conn, partner_id);
+    let mut exceeded = Vec::new();
+
+    if let Some((limit, action)) = rule(conn, partner_type, PER_DAY).map_err(|e| e.to_string())? {
+        if action != OFF {
+            let already = collected_on_day(conn, partner_id, day).map_err(|e| e.to_string())?;
+            let amount: f64 = allocations.iter().map(|allocation| allocation.valoare).sum();
+            if already + amount > limit + EPSILON {
+                exceeded.push(CashLimitCheck {
+                    scope: PER_DAY.to_string(),
+                    partner_type: partner_type.to_string(),
+                    document: None,
+                    limit_amount: limit,
+                    already_collected: already,
+                    amount,
+                    blocking: action == BLOCK,
+                    message: format!(
+                        "Plafonul zilnic de numerar de {:.2} lei pentru acest partener ar fi depășit: \
+                         {:.2} lei încasați deja pe {} și {:.2} lei pe această chitanță.",
+                        limit, already, day, amount
+                    ),
+                });
+            }
+        }
+    }
+
+    if let Some((limit, action)) = rule(conn, partner_type, PER_DOCUMENT).map_err(|e| e.to_string())? {
+        if action != OFF {
+            for allocation in allocations {
+                // An advance is only capped per day
+                if document_label(allocation).is_empty() {
+                    continue;
+                }
+                let already = collected_on_document(conn, partner_id, allocation).map_err(|e| e.to_string())?;
+                if already + allocation.valoare > limit + EPSILON {
+                    let document = document_label(allocation);
+                    exceeded.push(CashLimitCheck {
+                        scope: PER_DOCUMENT.to_string(),
+                        partner_type: partner_type.to_string(),
+                        document: Some(document.clone()),
+                        limit_amount: limit,
+                        already_collected: already,
+                        amount: allocation.valoare,
+                        blocking: action == BLOCK,
+                        message: format!(
+                            "Factura {} nu poate fi încasată în numerar peste {:.2} lei \
+                             ({:.2} lei încasați deja, {:.2} lei acum); plata nu poate fi fracționată.",
+                            document, limit, already, allocation.valoare
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(exceeded)
+}
+
+/// Runs `check` before a receipt is recorded: a blocking cap is returned as
+/// the error, the warnings are logged and returned for the audit trail.
+pub fn enforce(
+    conn: &Connection,
+    partner_id: &str,
+    day: &str,
+    allocations: &[CollectionAllocationRequest],
+) -> Result<Vec<CashLimitCheck>, String> {
+    let exceeded = check(conn, partner_id, day, allocations)?;
+    if let Some(blocking) = exceeded.iter().find(|limit| limit.blocking) {
+        warn!("[CASH_LIMITS] Receipt for partner {} blocked: {}", partner_id, blocking.message);
+        return Err(blocking.message.clone());
+    }
+    for limit in &exceeded {
+        warn!("[CASH_LIMITS] Receipt for partner {} over the cap: {}", partner_id, limit.message);
+    }
+    Ok(exceeded)
+}
@@ -0,0 +1,699 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use log::info;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+
+use crate::models::{CashMovement, CashRegisterDay, CashRegisterLine};
+
+// The agent's cash drawer (registrul de casă), one register per cash desk
+// (`nume_casa`). A day's page starts from the balance the previous day left,
+// adds the receipts issued that day and takes out the cash that left the
+// drawer: handed over to the office, deposited at the bank, or spent on the
+// road (fuel, ...).
+//
+// Closing a day freezes its page: the totals are stored, the page gets the
+// next number of the register and the day's receipts can no longer be
+// recorded, cancelled or restored.
+//
+// Only cash is in the drawer: collections paid by card, transfer, promissory
+// note or cheque stay out of the register.
+//
+// A register starts on a day with the cash found in the drawer then
+// (`cash_register_starts`); what happened before is not part of it. A cash
+// desk without a start begins on its first day with cash, from zero.
+//
+// `data_incasare` is stored in UTC; the day of a receipt is its local
+// calendar day (`day_of`, `local_day()` in SQL), the day the agent sees.
+
+/// Line of a receipt issued that day
+pub const RECEIPT: &str = "receipt";
+/// Cash handed over to the office
+pub const HANDOVER: &str = "handover";
+/// Cash deposited at the bank
+pub const DEPOSIT: &str = "deposit";
+/// Expense paid from the drawer
+pub const EXPENSE: &str = "expense";
+
+/// Cash desk of receipts issued before one was set in the settings
+pub const DEFAULT_CASA: &str = "CASA LEI";
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS cash_movements (
+        id TEXT PRIMARY KEY,
+        nume_casa TEXT NOT NULL,
+        day TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        amount REAL NOT NULL,
+        description TEXT NOT NULL,
+        document TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_cash_movements_day ON cash_movements(nume_casa, day);
+
+    CREATE TABLE IF NOT EXISTS cash_days (
+        nume_casa TEXT NOT NULL,
+        day TEXT NOT NULL,
+        page_number INTEGER NOT NULL,
+        opening_balance REAL NOT NULL,
+        total_receipts REAL NOT NULL,
+        total_payments REAL NOT NULL,
+        closing_balance REAL NOT NULL,
+        closed_at TEXT NOT NULL,
+        document_path TEXT,
+        PRIMARY KEY (nume_casa, day)
+    );
+
+    CREATE TABLE IF NOT EXISTS cash_register_starts (
+        nume_casa TEXT PRIMARY KEY,
+        day TEXT NOT NULL,
+        opening_balance REAL NOT NULL,
+        created_at TEXT NOT NULL
+    );
+"#;
+
+const EPSILON: f64 = 0.005;
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Cash desk set in the settings, where new receipts go.
+pub fn current_casa(conn: &Connection) -> String {
+    conn.query_row("SELECT nume_casa FROM agent_settings WHERE id = 1", [], |row| {
+        row.get::<_, Option<String>>(0)
+    })
+    .ok()
+    .flatten()
+    .map(|casa| casa.trim().to_string())
+    .filter(|casa| !casa.is_empty())
+    .unwrap_or_else(|| DEFAULT_CASA.to_string())
+}
+
+/// Local calendar day (YYYY-MM-DD) of a `data_incasare` value. Timestamps
+/// with an offset are moved to local time; a value without one is already
+/// local.
+pub fn day_of(data_incasare: &str) -> String {
+    match DateTime::parse_from_rfc3339(data_incasare) {
+        Ok(at) => at.with_timezone(&Local).format("%Y-%m-%d").to_string(),
+        Err(_) => data_incasare.get(..10).unwrap_or(data_incasare).to_string(),
+    }
+}
+
+/// Makes `day_of` available to SQL as `local_day(data_incasare)`, so the
+/// queries group receipts by the same day as the code does.
+pub fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("local_day", 1, FunctionFlags::SQLITE_UTF8, |ctx| {
+        Ok(ctx.get::<Option<String>>(0)?.map(|value| day_of(&value)))
+    })
+}
+
+fn parse_day(day: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|e| format!("Dată invalidă {}: {}", day, e))
+}
+
+fn closed_at(conn: &Connection, casa: &str, day: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT closed_at FROM cash_days WHERE nume_casa = ?1 AND day = ?2",
+        params![casa, day],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Refuses a change to a day whose register page is closed.
+pub fn ensure_open(conn: &Connection, casa: &str, day: &str) -> Result<(), String> {
+    if closed_at(conn, casa, day).map_err(|e| e.to_string())?.is_some() {
+        return Err(format!(
+            "Registrul de casă {} pe {} este închis; operațiunile din acea zi nu mai pot fi modificate.",
+            casa, day
+        ));
+    }
+    Ok(())
+}
+
+/// `ensure_open` for the day and cash desk of a receipt.
+pub fn ensure_receipt_open(conn: &Connection, receipt_group_id: &str) -> Result<(), String> {
+    let receipt: Option<(Option<String>, String)> = conn
+        .query_row(
+            "SELECT nume_casa, local_day(data_incasare) FROM collections
+             WHERE (COALESCE(receipt_group_id, id) = ?1 OR id = ?1) AND payment_method = 'cash' LIMIT 1",
+            [receipt_group_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match receipt {
+        Some((casa, day)) => ensure_open(conn, casa.as_deref().unwrap_or(DEFAULT_CASA), &day),
+        None => Ok(()),
+    }
+}
+
+fn receipts_between(conn: &Connection, casa: &str, after: &str, before: &str) -> rusqlite::Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(valoare), 0) FROM collections
+         WHERE nume_casa = ?1 AND local_day(data_incasare) > ?2 AND local_day(data_incasare) < ?3
+           AND status != 'cancelled' AND payment_method = 'cash'",
+        params![casa, after, before],
+        |row| row.get(0),
+    )
+}
+
+fn payments_between(conn: &Connection, casa: &str, after: &str, before: &str) -> rusqlite::Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(amount), 0) FROM cash_movements WHERE nume_casa = ?1 AND day > ?2 AND day < ?3",
+        params![casa, after, before],
+        |row| row.get(0),
+    )
+}
+
+/// Last closed page before `day`: its day and closing balance.
+fn last_closed_before(conn: &Connection, casa: &str, day: &str) -> rusqlite::Result<Option<(String, f64)>> {
+    conn.query_row(
+        "SELECT day, closing_balance FROM cash_days WHERE nume_casa = ?1 AND day < ?2 ORDER BY day DESC LIMIT 1",
+        params![casa, day],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// First day of the register of `casa` and the cash in the drawer then.
+pub fn start(conn: &Connection, casa: &str) -> rusqlite::Result<Option<(String, f64)>> {
+    conn.query_row(
+        "SELECT day, opening_balance FROM cash_register_starts WHERE nume_casa = ?1",
+        [casa],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+fn has_closed_pages(conn: &Connection, casa: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM cash_days WHERE nume_casa = ?1)",
+        [casa],
+        |row| row.get(0),
+    )
+}
+
+/// Gives a start to the registers already in use. One with closed pages
+/// starts on its first page; the others start today from zero, and the agent
+/// enters the cash found in the drawer before closing a day.
+pub fn seed_starts(conn: &Connection) -> rusqlite::Result<()> {
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT OR IGNORE INTO cash_register_starts (nume_casa, day, opening_balance, created_at)
+         SELECT nume_casa, day, opening_balance, ?1 FROM cash_days
+         WHERE (nume_casa, day) IN (SELECT nume_casa, MIN(day) FROM cash_days GROUP BY nume_casa)",
+        [&now],
+    )?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let mut stmt = conn.prepare(
+        "SELECT nume_casa FROM collections WHERE nume_casa IS NOT NULL AND payment_method = 'cash'
+         UNION SELECT nume_casa FROM cash_movements",
+    )?;
+    let mut casas = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    casas.push(current_casa(conn));
+    for casa in casas {
+        conn.execute(
+            "INSERT OR IGNORE INTO cash_register_starts (nume_casa, day, opening_balance, created_at)
+             VALUES (?1, ?2, 0, ?3)",
+            params![casa, today, now],
+        )?;
+    }
+    info!("[CASH_REGISTER] Register starts set for the cash desks in use");
+    Ok(())
+}
+
+/// Sets the day the register of `casa` starts on and the cash counted in
+/// the drawer that morning. Allowed until the first page is closed.
+pub fn set_start(conn: &Connection, casa: &str, day: &str, opening_balance: f64) -> Result<(), String> {
+    parse_day(day)?;
+    if !opening_balance.is_finite() || opening_balance < 0.0 {
+        return Err("Soldul inițial nu poate fi negativ".to_string());
+    }
+    if has_closed_pages(conn, casa).map_err(|e| e.to_string())? {
+        return Err(format!(
+            "Registrul de casă {} are deja file închise; începutul și soldul inițial nu mai pot fi modificate.",
+            casa
+        ));
+    }
+    let opening_balance = round(opening_balance);
+    conn.execute(
+        "INSERT INTO cash_register_starts (nume_casa, day, opening_balance, created_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(nume_casa) DO UPDATE SET day = excluded.day, opening_balance = excluded.opening_balance,
+             created_at = excluded.created_at",
+        params![casa, day, opening_balance, Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+    info!("[CASH_REGISTER] {} starts on {} with {:.2}", casa, day, opening_balance);
+    Ok(())
+}
+
+/// Day before `day`, for the exclusive bounds of `receipts_between`.
+fn day_before(day: &str) -> Result<String, String> {
+    Ok((parse_day(day)? - Duration::days(1)).format("%Y-%m-%d").to_string())
+}
+
+/// Cash in the drawer at the start of `day`: the last closed balance, or the
+/// opening balance of the register, plus what happened on the days since,
+/// closed or not. Nothing before the start of the register counts.
+fn opening_balance(conn: &Connection, casa: &str, day: &str) -> Result<f64, String> {
+    let closed = last_closed_before(conn, casa, day).map_err(|e| e.to_string())?;
+    let (from, balance) = match closed {
+        Some(closed) => closed,
+        None => match start(conn, casa).map_err(|e| e.to_string())? {
+            Some((start_day, _)) if day < start_day.as_str() => return Ok(0.0),
+            Some((start_day, balance)) => (day_before(&start_day)?, balance),
+            None => (String::new(), 0.0),
+        },
+    };
+    let receipts = receipts_between(conn, casa, &from, day).map_err(|e| e.to_string())?;
+    let payments = payments_between(conn, casa, &from, day).map_err(|e| e.to_string())?;
+    Ok(round(balance + receipts - payments))
+}
+
+fn local_time(created_at: &str) -> String {
+    DateTime::parse_from_rfc3339(created_at)
+        .map(|at| at.with_timezone(&Local).format("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+pub fn movements(conn: &Connection, casa: &str, day: &str) -> rusqlite::Result<Vec<CashMovement>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, nume_casa, day, kind, amount, description, document, created_at
+         FROM cash_movements WHERE nume_casa = ?1 AND day = ?2 ORDER BY created_at",
+    )?;
+    let movements = stmt
+        .query_map(params![casa, day], |row| {
+            Ok(CashMovement {
+                id: row.get(0)?,
+                nume_casa: row.get(1)?,
+                day: row.get(2)?,
+                kind: row.get(3)?,
+                amount: row.get(4)?,
+                description: row.get(5)?,
+                document: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(movements)
+}
+
+fn movement_label(kind: &str) -> &'static str {
+    match kind {
+        HANDOVER => "Predare numerar la sediu",
+        DEPOSIT => "Depunere numerar la bancă",
+        _ => "Plată din casă",
+    }
+}
+
+/// The receipts of the day (one line per receipt) and the movements, in the
+/// order they happened.
+fn lines(conn: &Connection, casa: &str, day: &str, movements: &[CashMovement]) -> rusqlite::Result<Vec<(String, CashRegisterLine)>> {
+    let mut stmt = conn.prepare(
+        "SELECT MIN(created_at), COALESCE(receipt_series, ''), COALESCE(receipt_number, ''),
+                COALESCE(MAX(partner_name), MAX(id_partener)), SUM(valoare)
+         FROM collections
+         WHERE nume_casa = ?1 AND local_day(data_incasare) = ?2 AND status != 'cancelled'
+           AND payment_method = 'cash'
+         GROUP BY COALESCE(receipt_group_id, id)",
+    )?;
+    let mut lines = stmt
+        .query_map(params![casa, day], |row| {
+            let created_at: String = row.get(0)?;
+            let series: String = row.get(1)?;
+            let number: String = row.get(2)?;
+            let partner: String = row.get(3)?;
+            Ok((
+                created_at.clone(),
+                CashRegisterLine {
+                    time: local_time(&created_at),
+                    kind: RECEIPT.to_string(),
+                    document: format!("Chitanța {} {}", series, number).trim().to_string(),
+                    description: format!("Încasare de la {}", partner),
+                    receipt: round(row.get(4)?),
+                    payment: 0.0,
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    lines.extend(movements.iter().map(|movement| {
+        (
+            movement.created_at.clone(),
+            CashRegisterLine {
+                time: local_time(&movement.created_at),
+                kind: movement.kind.clone(),
+                document: movement.document.clone().unwrap_or_default(),
+                description: format!("{}: {}", movement_label(&movement.kind), movement.description),
+                receipt: 0.0,
+                payment: movement.amount,
+            },
+        )
+    }));
+    lines.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(lines)
+}
+
+/// The page of `day` for the cash desk `casa`; for a closed day the stored
+/// totals, otherwise as it stands now.
+pub fn day(conn: &Connection, casa: &str, day: &str) -> Result<CashRegisterDay, String> {
+    parse_day(day)?;
+    let register_start = start(conn, casa).map_err(|e| e.to_string())?.map(|(start_day, _)| start_day);
+    let start_editable = !has_closed_pages(conn, casa).map_err(|e| e.to_string())?;
+    let movements = movements(conn, casa, day).map_err(|e| e.to_string())?;
+    let lines: Vec<CashRegisterLine> = lines(conn, casa, day, &movements)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect();
+
+    let closed: Option<(i64, [f64; 4], String, Option<String>)> = conn
+        .query_row(
+            "SELECT page_number, opening_balance, total_receipts, total_payments, closing_balance, closed_at, document_path
+             FROM cash_days WHERE nume_casa = ?1 AND day = ?2",
+            params![casa, day],
+            |row| Ok((row.get(0)?, [row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?], row.get(5)?, row.get(6)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    if let Some((page_number, [opening, receipts, payments, closing], closed_at, document_path)) = closed {
+        return Ok(CashRegisterDay {
+            nume_casa: casa.to_string(),
+            day: day.to_string(),
+            page_number: Some(page_number),
+            opening_balance: opening,
+            lines,
+            movements,
+            total_receipts: receipts,
+            total_payments: payments,
+            closing_balance: closing,
+            closed_at: Some(closed_at),
+            document_path,
+            register_start,
+            start_editable,
+        });
+    }
+
+    let opening = opening_balance(conn, casa, day)?;
+    let total_receipts = round(lines.iter().map(|line| line.receipt).sum());
+    let total_payments = round(lines.iter().map(|line| line.payment).sum());
+    Ok(CashRegisterDay {
+        nume_casa: casa.to_string(),
+        day: day.to_string(),
+        page_number: None,
+        opening_balance: opening,
+        lines,
+        movements,
+        total_receipts,
+        total_payments,
+        closing_balance: round(opening + total_receipts - total_payments),
+        closed_at: None,
+        document_path: None,
+        register_start,
+        start_editable,
+    })
+}
+
+pub fn add_movement(conn: &Connection, movement: &CashMovement) -> Result<String, String> {
+    if ![HANDOVER, DEPOSIT, EXPENSE].contains(&movement.kind.as_str()) {
+        return Err(format!("Tip de operațiune necunoscut: {}", movement.kind));
+    }
+    if !movement.amount.is_finite() || movement.amount <= 0.0 {
+        return Err("Suma trebuie să fie mai mare decât 0".to_string());
+    }
+    let description = movement.description.trim();
+    if description.is_empty() {
+        return Err("Descrierea operațiunii este obligatorie".to_string());
+    }
+    let casa = Some(movement.nume_casa.trim())
+        .filter(|casa| !casa.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| current_casa(conn));
+    parse_day(&movement.day)?;
+    ensure_open(conn, &casa, &movement.day)?;
+
+    // The drawer cannot give out more cash than it holds
+    let page = day(conn, &casa, &movement.day)?;
+    let amount = round(movement.amount);
+    if page.closing_balance - amount < -EPSILON {
+        return Err(format!(
+            "Sold insuficient în casă: {:.2} lei disponibili, {:.2} lei de plătit.",
+            page.closing_balance, amount
+        ));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO cash_movements (id, nume_casa, day, kind, amount, description, document, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            id,
+            casa,
+            movement.day,
+            movement.kind,
+            amount,
+            description,
+            movement.document.as_deref().map(str::trim).filter(|document| !document.is_empty()),
+            Utc::now().to_rfc3339()
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    info!(
+        "[CASH_REGISTER] {} {} {:.2} on {}: {}",
+        casa, movement.kind, amount, movement.day, description
+    );
+    Ok(id)
+}
+
+/// Removes a movement of an open day. Returns its cash desk and day.
+pub fn delete_movement(conn: &Connection, id: &str) -> Result<(String, String), String> {
+    let (casa, day): (String, String) = conn
+        .query_row("SELECT nume_casa, day FROM cash_movements WHERE id = ?1", [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Operațiunea nu a fost găsită".to_string())?;
+    ensure_open(conn, &casa, &day)?;
+    conn.execute("DELETE FROM cash_movements WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    info!("[CASH_REGISTER] Movement {} of {} on {} deleted", id, casa, day);
+    Ok((casa, day))
+}
+
+/// First day after the last closed page (or from the start of the register)
+/// and before `day` with receipts or movements, which has to be closed first.
+fn first_open_day_before(conn: &Connection, casa: &str, day: &str) -> Result<Option<String>, String> {
+    let from = match last_closed_before(conn, casa, day).map_err(|e| e.to_string())? {
+        Some((closed_day, _)) => closed_day,
+        None => match start(conn, casa).map_err(|e| e.to_string())? {
+            Some((start_day, _)) => day_before(&start_day)?,
+            None => String::new(),
+        },
+    };
+    conn.query_row(
+        "SELECT MIN(d) FROM (
+            SELECT local_day(data_incasare) AS d FROM collections
+            WHERE nume_casa = ?1 AND status != 'cancelled' AND payment_method = 'cash'
+            UNION ALL
+            SELECT day AS d FROM cash_movements WHERE nume_casa = ?1
+         ) WHERE d > ?2 AND d < ?3",
+        params![casa, from, day],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Closes the page of `day`: stores its totals under the next page number.
+/// Days are closed in order, and a day that has not ended yet can be closed
+/// (end of the agent's route) but not one in the future.
+pub fn close(conn: &Connection, casa: &str, day: &str) -> Result<CashRegisterDay, String> {
+    if parse_day(day)? > Local::now().date_naive() {
+        return Err("O zi viitoare nu poate fi închisă".to_string());
+    }
+    if let Some((start_day, _)) = start(conn, casa).map_err(|e| e.to_string())? {
+        if day < start_day.as_str() {
+            return Err(format!("Registrul de casă {} începe pe {}", casa, start_day));
+        }
+    }
+    ensure_open(conn, casa, day)?;
+    if let Some(open) = first_open_day_before(conn, casa, day)? {
+        return Err(format!("Închide mai întâi registrul de casă pe {}", open));
+    }
+    let sending: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM collections
+             WHERE nume_casa = ?1 AND local_day(data_incasare) = ?2 AND status = 'sending'
+               AND payment_method = 'cash'",
+            params![casa, day],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if sending > 0 {
+        return Err("Unele chitanțe ale zilei sunt în curs de trimitere; încearcă din nou după ce se termină.".to_string());
+    }
+
+    let mut page = self::day(conn, casa, day)?;
+    if page.closing_balance < -EPSILON {
+        return Err(format!("Soldul final nu poate fi negativ ({:.2} lei)", page.closing_balance));
+    }
+    let page_number: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(page_number), 0) + 1 FROM cash_days WHERE nume_casa = ?1",
+            [casa],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO cash_days (nume_casa, day, page_number, opening_balance, total_receipts, total_payments, closing_balance, closed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            casa,
+            day,
+            page_number,
+            page.opening_balance,
+            page.total_receipts,
+            page.total_payments,
+            page.closing_balance,
+            now
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    info!(
+        "[CASH_REGISTER] {} closed on {} as page {}: {:.2} + {:.2} - {:.2} = {:.2}",
+        casa, day, page_number, page.opening_balance, page.total_receipts, page.total_payments, page.closing_balance
+    );
+    page.page_number = Some(page_number);
+    page.closed_at = Some(now);
+    page.start_editable = false;
+    Ok(page)
+}
+
+pub fn set_document_path(conn: &Connection, casa: &str, day: &str, path: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE cash_days SET document_path = ?1 WHERE nume_casa = ?2 AND day = ?3",
+        params![path, casa, day],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const CASA: &str = "CASA LEI";
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        register_functions(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agent_settings (id INTEGER PRIMARY KEY, nume_casa TEXT);
+             INSERT INTO agent_settings (id, nume_casa) VALUES (1, 'CASA LEI');
+             CREATE TABLE collections (id TEXT PRIMARY KEY, id_partener TEXT, partner_name TEXT, valoare REAL,
+                 data_incasare TEXT, status TEXT, created_at TEXT, receipt_series TEXT, receipt_number TEXT,
+                 receipt_group_id TEXT, nume_casa TEXT, payment_method TEXT NOT NULL DEFAULT 'cash');",
+        )
+        .unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    /// `data_incasare` as the app stores it: the local moment, in UTC.
+    fn at(day: u32, hour: u32, minute: u32) -> String {
+        Local.with_ymd_and_hms(2026, 3, day, hour, minute, 0).unwrap().with_timezone(&Utc).to_rfc3339()
+    }
+
+    fn receipt(conn: &Connection, number: u32, data_incasare: &str, valoare: f64) {
+        conn.execute(
+            "INSERT INTO collections (id, id_partener, partner_name, valoare, data_incasare, status, created_at,
+                 receipt_series, receipt_number, nume_casa)
+             VALUES (?1, 'P001', 'Alimentara Bucovina', ?2, ?3, 'synced', ?3, 'CH', ?1, ?4)",
+            params![number.to_string(), valoare, data_incasare, CASA],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn day_of_is_the_local_calendar_day() {
+        assert_eq!(day_of(&at(2, 0, 30)), "2026-03-02");
+        assert_eq!(day_of(&at(2, 23, 45)), "2026-03-02");
+        assert_eq!(day_of("2026-03-02T00:30:00"), "2026-03-02");
+        assert_eq!(day_of("2026-03-02"), "2026-03-02");
+
+        let conn = test_db();
+        let in_sql: String = conn.query_row("SELECT local_day(?1)", [at(2, 0, 30)], |row| row.get(0)).unwrap();
+        assert_eq!(in_sql, "2026-03-02");
+    }
+
+    #[test]
+    fn receipt_after_midnight_is_on_the_local_day() {
+        let conn = test_db();
+        receipt(&conn, 1, &at(2, 0, 30), 100.0);
+        receipt(&conn, 2, &at(1, 23, 50), 40.0);
+
+        let page = day(&conn, CASA, "2026-03-02").unwrap();
+        assert_eq!(page.total_receipts, 100.0);
+        assert_eq!(page.lines.len(), 1);
+        assert_eq!(page.lines[0].time, "00:30");
+        assert_eq!(day(&conn, CASA, "2026-03-01").unwrap().total_receipts, 40.0);
+    }
+
+    #[test]
+    fn register_without_start_begins_from_zero_on_its_first_day() {
+        let conn = test_db();
+        receipt(&conn, 1, &at(1, 10, 0), 40.0);
+        let page = day(&conn, CASA, "2026-03-02").unwrap();
+        assert_eq!(page.opening_balance, 40.0);
+        assert_eq!(page.register_start, None);
+        assert!(page.start_editable);
+        assert_eq!(close(&conn, CASA, "2026-03-02").unwrap_err(), "Închide mai întâi registrul de casă pe 2026-03-01");
+    }
+
+    #[test]
+    fn history_before_the_start_is_left_out() {
+        let conn = test_db();
+        receipt(&conn, 1, &at(1, 10, 0), 40.0);
+        receipt(&conn, 2, &at(2, 10, 0), 100.0);
+        set_start(&conn, CASA, "2026-03-02", 250.0).unwrap();
+
+        let page = day(&conn, CASA, "2026-03-02").unwrap();
+        assert_eq!(page.opening_balance, 250.0);
+        assert_eq!(page.closing_balance, 350.0);
+        assert_eq!(page.register_start.as_deref(), Some("2026-03-02"));
+        assert_eq!(day(&conn, CASA, "2026-03-03").unwrap().opening_balance, 350.0);
+        assert_eq!(day(&conn, CASA, "2026-03-01").unwrap().opening_balance, 0.0);
+
+        assert_eq!(close(&conn, CASA, "2026-03-01").unwrap_err(), "Registrul de casă CASA LEI începe pe 2026-03-02");
+        let closed = close(&conn, CASA, "2026-03-02").unwrap();
+        assert_eq!(closed.page_number, Some(1));
+        assert!(!closed.start_editable);
+        assert!(set_start(&conn, CASA, "2026-03-01", 0.0).is_err());
+    }
+
+    #[test]
+    fn registers_in_use_get_a_start() {
+        let conn = test_db();
+        receipt(&conn, 1, &at(1, 10, 0), 40.0);
+        conn.execute(
+            "INSERT INTO cash_days (nume_casa, day, page_number, opening_balance, total_receipts, total_payments,
+                 closing_balance, closed_at)
+             VALUES ('CASA EUR', '2026-02-10', 1, 12.5, 0, 0, 12.5, '2026-02-10T18:00:00+00:00'),
+                    ('CASA EUR', '2026-02-11', 2, 12.5, 0, 0, 12.5, '2026-02-11T18:00:00+00:00')",
+            [],
+        )
+        .unwrap();
+        seed_starts(&conn).unwrap();
+
+        assert_eq!(start(&conn, "CASA EUR").unwrap(), Some(("2026-02-10".to_string(), 12.5)));
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(start(&conn, CASA).unwrap(), Some((today, 0.0)));
+    }
+}
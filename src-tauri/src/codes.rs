@@ -0,0 +1,591 @@
+use base64::{engine::general_purpose, Engine as _};
+use qrcode::{Color, EcLevel, QrCode};
+use rusqlite::{params, Connection};
+
+use crate::archive;
+use crate::escpos::{Raster, Ticket};
+use crate::models::ScannedDocument;
+use crate::pdf::{self, Flow, Font, Image};
+use crate::templates::Images;
+
+// Machine-readable identifiers on printed invoices, receipts and quality
+// certificates, so a document that comes back to the office is opened by
+// scanning it instead of retyping its series and number.
+//
+// The QR code carries everything needed to recognise the document:
+//     FACT|series|number|YYYY-MM-DD|total|issuer CIF|document id
+// (CHIT for receipts, CERT for certificates). The optional Code128 barcode
+// only carries the kind letter, series and number ("FSCF-1234") so it stays
+// short enough for a thermal printer and a handheld scanner.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Invoice,
+    Receipt,
+    QualityCertificate,
+}
+
+impl Kind {
+    const ALL: [Kind; 3] = [Kind::Invoice, Kind::Receipt, Kind::QualityCertificate];
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Kind::Invoice => "FACT",
+            Kind::Receipt => "CHIT",
+            Kind::QualityCertificate => "CERT",
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Kind::Invoice => 'F',
+            Kind::Receipt => 'C',
+            Kind::QualityCertificate => 'Q',
+        }
+    }
+}
+
+/// What a printed document is identified by.
+pub struct DocumentCode {
+    pub kind: Kind,
+    pub series: String,
+    pub number: String,
+    /// YYYY-MM-DD
+    pub date: String,
+    pub total: f64,
+    pub cif: String,
+    /// Invoice id; collection or receipt group id for receipts
+    pub id: String,
+    /// Print the Code128 barcode too
+    pub barcode: bool,
+}
+
+/// A decoded QR code or barcode.
+#[derive(Debug, PartialEq)]
+pub struct Scanned {
+    pub kind: Kind,
+    pub series: String,
+    pub number: String,
+    /// Only in QR codes
+    pub id: Option<String>,
+}
+
+/// Black and white modules, quiet zone included, row by row.
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub dark: Vec<bool>,
+}
+
+/// YYYY-MM-DD from an RFC 3339 timestamp or a printed date (31.12.2025,
+/// 31-12-2025); the text itself when it is neither.
+pub fn iso_date(text: &str) -> String {
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(text) {
+        return date.format("%Y-%m-%d").to_string();
+    }
+    ["%d.%m.%Y", "%d-%m-%Y", "%Y-%m-%d"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(text.trim(), format).ok())
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| text.trim().to_string())
+}
+
+fn clean(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace() && *c != '|').collect()
+}
+
+impl DocumentCode {
+    pub fn qr_text(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{:.2}|{}|{}",
+            self.kind.prefix(),
+            clean(&self.series),
+            clean(&self.number),
+            self.date,
+            self.total,
+            clean(&self.cif),
+            self.id
+        )
+    }
+
+    pub fn barcode_text(&self) -> String {
+        format!("{}{}-{}", self.kind.letter(), clean(&self.series), clean(&self.number))
+    }
+
+    pub fn qr(&self) -> Option<Bitmap> {
+        qr_bitmap(&self.qr_text())
+    }
+
+    /// None when the barcode is turned off or the series is not plain ASCII.
+    pub fn code128(&self) -> Option<Bitmap> {
+        if !self.barcode {
+            return None;
+        }
+        code128_bitmap(&self.barcode_text())
+    }
+
+    /// Adds the codes to the template images (`images.qr`, `images.barcode`).
+    pub fn add_to(&self, images: &mut Images) {
+        images.qr = self.qr().map(|bitmap| svg_data_url(&bitmap));
+        images.barcode = self.code128().map(|bitmap| svg_data_url(&bitmap));
+    }
+
+    /// QR code centred, the barcode and its text under it.
+    pub fn draw_pdf(&self, flow: &mut Flow) {
+        if let Some(image) = self.qr().and_then(|bitmap| Image::from_bitmap(&bitmap, 4).ok()) {
+            flow.space(2.0);
+            flow.image(&image, 24.0, 0.0, pdf::Align::Center);
+        }
+        if let Some(image) = self.code128().and_then(|bitmap| Image::from_bitmap(&bitmap, 2).ok()) {
+            flow.space(1.0);
+            flow.image(&image, 55.0, 0.0, pdf::Align::Center);
+            flow.text(&self.barcode_text(), Font::Regular, 7.5, pdf::Align::Center);
+        }
+    }
+
+    /// Same as `draw_pdf` on a thermal printer.
+    pub fn print_escpos(&self, ticket: &mut Ticket) {
+        let dots = ticket.dots();
+        if let Some(bitmap) = self.qr() {
+            let scale = (dots / 2 / bitmap.width).clamp(1, 6);
+            ticket.code(&Raster::from_bitmap(&bitmap, scale));
+        }
+        if let Some(bitmap) = self.code128() {
+            let scale = (dots / bitmap.width).clamp(1, 3);
+            ticket.code(&Raster::from_bitmap(&bitmap, scale));
+            ticket.align(pdf::Align::Center);
+            ticket.text(&self.barcode_text());
+            ticket.align(pdf::Align::Left);
+        }
+    }
+}
+
+/// Reads a scanned QR code or barcode; None when it is not one of ours.
+pub fn parse(code: &str) -> Option<Scanned> {
+    let code = code.trim();
+
+    let fields: Vec<&str> = code.split('|').collect();
+    if fields.len() == 7 {
+        let kind = Kind::ALL.into_iter().find(|kind| kind.prefix() == fields[0])?;
+        return Some(Scanned {
+            kind,
+            series: fields[1].to_string(),
+            number: fields[2].to_string(),
+            id: Some(fields[6].to_string()).filter(|id| !id.is_empty()),
+        });
+    }
+
+    let letter = code.chars().next()?.to_ascii_uppercase();
+    let kind = Kind::ALL.into_iter().find(|kind| kind.letter() == letter)?;
+    let (series, number) = code[1..].rsplit_once('-')?;
+    if number.is_empty() {
+        return None;
+    }
+    Some(Scanned {
+        kind,
+        series: series.to_string(),
+        number: number.to_string(),
+        id: None,
+    })
+}
+
+/// The invoice, receipt or quality certificate a scanned code was printed on,
+/// archived ones included. The total is the one printed: VAT rounded per line.
+pub fn find(conn: &Connection, scanned: &Scanned) -> rusqlite::Result<ScannedDocument> {
+    match scanned.kind {
+        Kind::Invoice | Kind::QualityCertificate => {
+            let document_type = if scanned.kind == Kind::Invoice { "invoice" } else { "quality_certificate" };
+            let select = format!(
+                "SELECT i.id, COALESCE(i.invoice_series, ''), i.invoice_number, p.name, i.created_at, i.status,
+                        (SELECT COALESCE(SUM(ii.total_price
+                                    + ROUND(ii.total_price * COALESCE(CAST(pr.procent_tva AS REAL), 19.0) / 100.0, 2)), 0)
+                           FROM {items} ii LEFT JOIN products pr ON pr.id = ii.product_id
+                          WHERE ii.invoice_id = i.id)
+                 FROM {invoices} i
+                 JOIN partners p ON p.id = i.partner_id",
+                invoices = archive::report_source(conn, "invoices", true)?,
+                items = archive::report_source(conn, "invoice_items", true)?,
+            );
+            let map = |row: &rusqlite::Row| -> rusqlite::Result<ScannedDocument> {
+                Ok(ScannedDocument {
+                    document_type: document_type.to_string(),
+                    document_id: row.get(0)?,
+                    series: row.get(1)?,
+                    number: row.get::<_, i64>(2)?.to_string(),
+                    partner_name: row.get(3)?,
+                    date: row.get(4)?,
+                    status: row.get(5)?,
+                    total: row.get(6)?,
+                })
+            };
+            match &scanned.id {
+                Some(id) => conn.query_row(&format!("{} WHERE i.id = ?1", select), [id], map),
+                // Invoices saved before the series was stored have none
+                None => conn.query_row(
+                    &format!(
+                        "{} WHERE CAST(i.invoice_number AS TEXT) = ?2
+                           AND (COALESCE(i.invoice_series, '') = ?1 OR trim(COALESCE(i.invoice_series, '')) = '')
+                         ORDER BY COALESCE(i.invoice_series, '') = ?1 DESC, i.created_at DESC LIMIT 1",
+                        select
+                    ),
+                    params![scanned.series, scanned.number],
+                    map,
+                ),
+            }
+        }
+        Kind::Receipt => {
+            let collections = archive::report_source(conn, "collections", true)?;
+            // A receipt covers every collection of its group
+            let (group, values) = match &scanned.id {
+                Some(id) => (
+                    format!(
+                        "SELECT COALESCE(receipt_group_id, id) FROM {} WHERE id = ?1 OR receipt_group_id = ?1 LIMIT 1",
+                        collections
+                    ),
+                    vec![id.as_str()],
+                ),
+                None => (
+                    format!(
+                        "SELECT COALESCE(receipt_group_id, id) FROM {} WHERE receipt_series = ?1 AND receipt_number = ?2 LIMIT 1",
+                        collections
+                    ),
+                    vec![scanned.series.as_str(), scanned.number.as_str()],
+                ),
+            };
+            let sql = format!(
+                "SELECT MIN(c.id), COALESCE(c.receipt_series, ''), COALESCE(c.receipt_number, ''), COALESCE(c.partner_name, ''),
+                        MIN(c.data_incasare), c.status, SUM(c.valoare)
+                 FROM {} c
+                 WHERE COALESCE(c.receipt_group_id, c.id) = ({})
+                 GROUP BY COALESCE(c.receipt_group_id, c.id)",
+                collections, group
+            );
+            conn.query_row(&sql, rusqlite::params_from_iter(values.iter()), |row| {
+                Ok(ScannedDocument {
+                    document_type: "receipt".to_string(),
+                    document_id: row.get(0)?,
+                    series: row.get(1)?,
+                    number: row.get(2)?,
+                    partner_name: row.get(3)?,
+                    date: row.get(4)?,
+                    status: row.get(5)?,
+                    total: row.get(6)?,
+                })
+            })
+        }
+    }
+}
+
+fn with_quiet_zone(width: usize, height: usize, dark: impl Fn(usize, usize) -> bool, margin_x: usize, margin_y: usize) -> Bitmap {
+    let (full_width, full_height) = (width + 2 * margin_x, height + 2 * margin_y);
+    let mut modules = vec![false; full_width * full_height];
+    for y in 0..height {
+        for x in 0..width {
+            modules[(y + margin_y) * full_width + x + margin_x] = dark(x, y);
+        }
+    }
+    Bitmap {
+        width: full_width,
+        height: full_height,
+        dark: modules,
+    }
+}
+
+fn qr_bitmap(text: &str) -> Option<Bitmap> {
+    let code = QrCode::with_error_correction_level(text.as_bytes(), EcLevel::M)
+        .map_err(|e| log::warn!("[CODES] QR code for '{}' failed: {}", text, e))
+        .ok()?;
+    let width = code.width();
+    let colors = code.to_colors();
+    Some(with_quiet_zone(width, width, |x, y| colors[y * width + x] == Color::Dark, 4, 4))
+}
+
+// Bar and space widths of the Code128 symbols 0..=105; the stop symbol has
+// a final 2-module bar
+const CODE128: [&str; 106] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212", "221213",
+    "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221", "223211", "221132",
+    "221231", "213212", "223112", "312131", "311222", "321122", "321221", "312212", "322112", "322211",
+    "212123", "212321", "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121", "313121", "211331",
+    "231131", "213113", "213311", "213131", "311123", "311321", "331121", "312113", "312311", "332111",
+    "314111", "221411", "431111", "111224", "111422", "121124", "121421", "141122", "141221", "112214",
+    "112412", "122114", "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311", "113141",
+    "114131", "311141", "411131", "211412", "211214", "211232",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: &str = "2331112";
+
+/// Code128 (code set B) of printable ASCII text, 10 modules of quiet zone
+/// each side.
+fn code128_bitmap(text: &str) -> Option<Bitmap> {
+    if text.is_empty() || !text.bytes().all(|b| (32..=126).contains(&b)) {
+        return None;
+    }
+
+    let values: Vec<usize> = text.bytes().map(|b| (b - 32) as usize).collect();
+    let checksum = values
+        .iter()
+        .enumerate()
+        .fold(CODE128_START_B, |sum, (position, value)| sum + (position + 1) * value)
+        % 103;
+
+    let mut patterns = vec![CODE128[CODE128_START_B]];
+    patterns.extend(values.iter().map(|&value| CODE128[value]));
+    patterns.push(CODE128[checksum]);
+    patterns.push(CODE128_STOP);
+
+    // Widths alternate bar, space, bar...
+    let mut bars = Vec::new();
+    for pattern in patterns {
+        for (i, width) in pattern.bytes().enumerate() {
+            bars.extend(std::iter::repeat(i % 2 == 0).take((width - b'0') as usize));
+        }
+    }
+
+    let height = (bars.len() / 6).max(20);
+    Some(with_quiet_zone(bars.len(), height, |x, _| bars[x], 10, 0))
+}
+
+/// SVG of a bitmap (one unit per module) as a data URL for the templates.
+fn svg_data_url(bitmap: &Bitmap) -> String {
+    let mut path = String::new();
+    for y in 0..bitmap.height {
+        let row = &bitmap.dark[y * bitmap.width..(y + 1) * bitmap.width];
+        let mut x = 0;
+        while x < bitmap.width {
+            if row[x] {
+                let start = x;
+                while x < bitmap.width && row[x] {
+                    x += 1;
+                }
+                path.push_str(&format!("M{} {}h{}v1h-{}z", start, y, x - start, x - start));
+            } else {
+                x += 1;
+            }
+        }
+    }
+
+    let svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" preserveAspectRatio="none" shape-rendering="crispEdges"><rect width="100%" height="100%" fill="#fff"/><path d="{}" fill="#000"/></svg>"##,
+        bitmap.width, bitmap.height, path
+    );
+    format!("data:image/svg+xml;base64,{}", general_purpose::STANDARD.encode(svg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    /// Bar and space widths of the first row, quiet zone left out.
+    fn run_lengths(bitmap: &Bitmap) -> String {
+        let row = &bitmap.dark[..bitmap.width];
+        let mut widths = String::new();
+        let mut x = row.iter().position(|&dark| dark).unwrap();
+        let end = row.iter().rposition(|&dark| dark).unwrap() + 1;
+        while x < end {
+            let start = x;
+            while x < end && row[x] == row[start] {
+                x += 1;
+            }
+            widths.push_str(&(x - start).to_string());
+        }
+        widths
+    }
+
+    #[test]
+    fn code128_of_a_known_value() {
+        // Code set B values: F=38 S=51 C=35 F=38 -=13 1=17 2=18 3=19 4=20;
+        // checksum (104 + 1·38 + 2·51 + 3·35 + 4·38 + 5·13 + 6·17 + 7·18
+        // + 8·19 + 9·20) mod 103 = 1126 mod 103 = 96
+        let expected = [
+            "211214", // start B
+            "132311", "213113", "131321", "132311", "122132", "123221", "223211", "221132", "221231",
+            "114311", // checksum 96
+            "2331112", // stop
+        ]
+        .concat();
+
+        let bitmap = code128_bitmap("FSCF-1234").unwrap();
+        assert_eq!(run_lengths(&bitmap), expected);
+        // 11 symbols of 11 modules, the 13-module stop, 10 modules of quiet zone each side
+        assert_eq!(bitmap.width, 11 * 11 + 13 + 20);
+        assert!(bitmap.dark[..10].iter().all(|&dark| !dark));
+        assert!(bitmap.dark[bitmap.width - 10..bitmap.width].iter().all(|&dark| !dark));
+        // Every row is the same
+        let first_row = &bitmap.dark[..bitmap.width];
+        assert!((1..bitmap.height).all(|y| &bitmap.dark[y * bitmap.width..(y + 1) * bitmap.width] == first_row));
+    }
+
+    #[test]
+    fn code128_symbols_are_eleven_modules_with_even_bars() {
+        for pattern in CODE128 {
+            let widths: Vec<u32> = pattern.bytes().map(|b| (b - b'0') as u32).collect();
+            assert_eq!(widths.iter().sum::<u32>(), 11, "{}", pattern);
+            assert_eq!((widths[0] + widths[2] + widths[4]) % 2, 0, "{}", pattern);
+        }
+        let mut unique = CODE128.to_vec();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), CODE128.len());
+    }
+
+    #[test]
+    fn code128_refuses_text_outside_code_set_b() {
+        assert!(code128_bitmap("").is_none());
+        assert!(code128_bitmap("FȘCF-1").is_none());
+        let mut code = test_fixtures::code(Kind::Invoice);
+        code.barcode = false;
+        assert!(code.code128().is_none());
+    }
+
+    #[test]
+    fn qr_text_round_trips() {
+        for kind in Kind::ALL {
+            let code = test_fixtures::code(kind);
+            assert_eq!(
+                parse(&code.qr_text()),
+                Some(Scanned {
+                    kind,
+                    series: "KRN".to_string(),
+                    number: "42".to_string(),
+                    id: Some(code.id.clone()),
+                })
+            );
+        }
+        assert_eq!(
+            test_fixtures::code(Kind::Invoice).qr_text(),
+            "FACT|KRN|42|2026-03-02|154.70|RO123456|0b7d4c1e-2f4a-4f43-9d55-6a1c2e3f4a5b"
+        );
+    }
+
+    #[test]
+    fn barcode_text_round_trips() {
+        for kind in Kind::ALL {
+            let code = test_fixtures::code(kind);
+            assert_eq!(
+                parse(&code.barcode_text()),
+                Some(Scanned {
+                    kind,
+                    series: "KRN".to_string(),
+                    number: "42".to_string(),
+                    id: None,
+                })
+            );
+        }
+
+        // A series with a dash or spaces, and a number with a pipe
+        let mut code = test_fixtures::code(Kind::Receipt);
+        code.series = "CH-B 2".to_string();
+        code.number = "7|1".to_string();
+        assert_eq!(code.barcode_text(), "CCH-B2-71");
+        let scanned = parse(&code.barcode_text()).unwrap();
+        assert_eq!((scanned.series.as_str(), scanned.number.as_str()), ("CH-B2", "71"));
+        let scanned = parse(&code.qr_text()).unwrap();
+        assert_eq!((scanned.series.as_str(), scanned.number.as_str()), ("CH-B2", "71"));
+    }
+
+    #[test]
+    fn foreign_codes_are_not_parsed() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("5941234567890"), None);
+        assert_eq!(parse("FKRN-"), None);
+        assert_eq!(parse("XKRN-42"), None);
+        assert_eq!(parse("BON|KRN|42|2026-03-02|1.00|RO1|id"), None);
+        assert_eq!(parse(" fkrn-42 ").map(|scanned| scanned.kind), Some(Kind::Invoice));
+    }
+
+    #[test]
+    fn qr_has_a_four_module_quiet_zone() {
+        let bitmap = test_fixtures::code(Kind::Invoice).qr().unwrap();
+        assert_eq!(bitmap.width, bitmap.height);
+        assert_eq!((bitmap.width - 8 - 17) % 4, 0);
+        assert!(bitmap.dark[..bitmap.width * 4].iter().all(|&dark| !dark));
+        // Top-left finder pattern starts right after the quiet zone
+        assert!(bitmap.dark[4 * bitmap.width + 4..4 * bitmap.width + 11].iter().all(|&dark| dark));
+    }
+
+    /// Invoice KRN/`number` of the fixture partner, 100 eggs at 9% VAT and 10
+    /// trays at 19%, issued on `created_at`.
+    fn save_invoice(conn: &Connection, id: &str, number: i64, created_at: &str) {
+        conn.execute(
+            "INSERT INTO invoices (id, invoice_number, invoice_series, partner_id, location_id, status, created_at, sent_at)
+             VALUES (?1, ?2, 'KRN', 'P001', 'L001', 'sent', ?3, ?3)",
+            params![id, number, created_at],
+        )
+        .unwrap();
+        for item in test_fixtures::invoice_items() {
+            conn.execute(
+                "INSERT INTO invoice_items (id, invoice_id, product_id, quantity, unit_price, total_price)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![format!("{}-{}", id, item.id), id, item.product_id, item.quantity, item.unit_price, item.total_price],
+            )
+            .unwrap();
+        }
+    }
+
+    fn documents_db() -> Connection {
+        let conn = crate::database::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO partners (id, name, created_at, updated_at) VALUES ('P001', 'Ștefănescu Țară SRL', '2026-01-01', '2026-01-01');
+             INSERT INTO locations (id, partner_id, name) VALUES ('L001', 'P001', 'Magazin Iași');
+             INSERT INTO products (id, name, unit_of_measure, price, procent_tva) VALUES
+                 ('OUA-M', 'Ouă M', 'buc', 0.9, '9'), ('COF', 'Cofraje', 'buc', 4.5, '19');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn finds_the_invoice_a_code_was_printed_on() {
+        let conn = documents_db();
+        let invoice = test_fixtures::invoice();
+        save_invoice(&conn, &invoice.id, 42, "2026-03-02T09:15:00+02:00");
+
+        for text in [test_fixtures::code(Kind::Invoice).qr_text(), "FKRN-42".to_string()] {
+            let found = find(&conn, &parse(&text).unwrap()).unwrap();
+            assert_eq!(found.document_type, "invoice");
+            assert_eq!(found.document_id, invoice.id);
+            assert_eq!((found.series.as_str(), found.number.as_str()), ("KRN", "42"));
+            assert_eq!(found.partner_name, "Ștefănescu Țară SRL");
+            // 90 + 8,10 VAT + 45 + 8,55 VAT, as printed
+            assert!((found.total - 151.65).abs() < 0.001, "{}", found.total);
+        }
+
+        let certificate = find(&conn, &parse(&test_fixtures::code(Kind::QualityCertificate).qr_text()).unwrap()).unwrap();
+        assert_eq!(certificate.document_type, "quality_certificate");
+        assert_eq!(certificate.document_id, invoice.id);
+    }
+
+    #[test]
+    fn finds_archived_invoices_and_whole_receipts() {
+        let conn = documents_db();
+        save_invoice(&conn, "old", 7, "2025-01-10T09:00:00+02:00");
+        conn.execute_batch(
+            "INSERT INTO collections (id, id_partener, partner_name, valoare, data_incasare, status, created_at,
+                 receipt_series, receipt_number, receipt_group_id)
+             VALUES ('c-1', 'P001', 'Ștefănescu Țară SRL', 100.0, '2026-03-02T10:00:00+02:00', 'synced',
+                     '2026-03-02T10:00:00+02:00', 'CH', '7', 'g-1'),
+                    ('c-2', 'P001', 'Ștefănescu Țară SRL', 54.7, '2026-03-02T10:00:00+02:00', 'synced',
+                     '2026-03-02T10:00:00+02:00', 'CH', '7', 'g-1');",
+        )
+        .unwrap();
+        crate::archive::archive_documents(&conn, 12).unwrap();
+        let live: i64 = conn.query_row("SELECT COUNT(*) FROM main.invoices", [], |row| row.get(0)).unwrap();
+        assert_eq!(live, 0);
+
+        let archived = find(&conn, &parse("FKRN-7").unwrap()).unwrap();
+        assert_eq!(archived.document_id, "old");
+        assert!((archived.total - 151.65).abs() < 0.001);
+
+        let receipt = find(&conn, &parse("CCH-7").unwrap()).unwrap();
+        assert_eq!(receipt.document_type, "receipt");
+        assert_eq!(receipt.document_id, "c-1");
+        assert!((receipt.total - 154.7).abs() < 0.001);
+
+        assert!(matches!(find(&conn, &parse("FKRN-8").unwrap()), Err(rusqlite::Error::QueryReturnedNoRows)));
+    }
+}
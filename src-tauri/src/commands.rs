@@ -1,30 +1,146 @@
+use crate::advances;
+use crate::aging;
 use crate::api_client;
+use crate::archive;
+use crate::balance_confirmations;
+use crate::cash_limits;
+use crate::cash_register;
+use crate::codes;
+use crate::company;
 use crate::database::Database;
+use crate::document_events;
+use crate::efactura;
+use crate::escpos;
+use crate::integrity;
 use crate::models::*;
+use crate::numbering;
+use crate::partner_statement;
+use crate::payment_allocation;
+use crate::payment_methods;
+use crate::pdf;
+use crate::print_aging_report;
 use crate::print_invoice;
 use crate::print_daily_report;
+use crate::print_balance_confirmation;
+use crate::print_cash_register;
+use crate::print_partner_statement;
+use crate::print_quality_certificate;
 use crate::print_receipt;
+use crate::print_sales_summary;
+use crate::printing;
+use crate::print_jobs;
+use crate::search_index;
+use crate::templates;
+use crate::ubl;
 use chrono::{Utc, Datelike, Local};
 use log::{info, warn};
 use tauri::State;
 use uuid::Uuid;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::Mutex;
 
-// Helper function to read logo and convert to base64
-fn read_logo_to_base64() -> Option<String> {
-    // Embed logo at compile time so it works in packaged builds
-    let logo_data: &[u8] = include_bytes!("../../public/logo.png");
-    if logo_data.is_empty() {
+// Helper function to convert a PNG (logo, stamp) to a base64 data URL
+fn png_data_url(png: &[u8]) -> Option<String> {
+    if png.is_empty() {
         return None;
     }
 
     use base64::{Engine as _, engine::general_purpose};
-    let base64_string = general_purpose::STANDARD.encode(logo_data);
+    let base64_string = general_purpose::STANDARD.encode(png);
     Some(format!("data:image/png;base64,{}", base64_string))
 }
 
+// Company logo and stamp for the HTML templates
+fn template_images(issuer: &company::Issuer) -> templates::Images {
+    templates::Images {
+        logo: png_data_url(&issuer.logo),
+        stamp: png_data_url(&issuer.stamp),
+        ..Default::default()
+    }
+}
+
+fn print_barcode_enabled(conn: &rusqlite::Connection) -> bool {
+    conn.query_row("SELECT print_barcode FROM agent_settings WHERE id = 1", [], |row| {
+        row.get::<_, Option<i64>>(0)
+    })
+    .ok()
+    .flatten()
+    .unwrap_or(0)
+        != 0
+}
+
+// QR code (and barcode, if enabled) of a printed invoice or its quality certificate
+fn invoice_code(
+    conn: &rusqlite::Connection,
+    data: &InvoicePrintData,
+    issuer: &company::Issuer,
+    kind: codes::Kind,
+) -> codes::DocumentCode {
+    codes::DocumentCode {
+        kind,
+        series: data.carnet_series.clone(),
+        number: data.invoice_number.to_string(),
+        date: codes::iso_date(&data.invoice.created_at),
+        total: print_invoice::invoice_total(&data.items),
+        cif: issuer.profile.cif.clone(),
+        id: data.invoice.id.clone(),
+        barcode: print_barcode_enabled(conn),
+    }
+}
+
+// QR code (and barcode, if enabled) of a receipt; `id` is the collection or
+// receipt group id the receipt was printed for
+fn receipt_code(
+    conn: &rusqlite::Connection,
+    collection: &Collection,
+    doc_series: &str,
+    doc_number: &str,
+    issue_date: &str,
+    issuer: &company::Issuer,
+    id: &str,
+) -> codes::DocumentCode {
+    codes::DocumentCode {
+        kind: codes::Kind::Receipt,
+        series: doc_series.to_string(),
+        number: doc_number.to_string(),
+        date: codes::iso_date(issue_date),
+        total: collection.valoare,
+        cif: issuer.profile.cif.clone(),
+        id: id.to_string(),
+        barcode: print_barcode_enabled(conn),
+    }
+}
+
+// Decoding a logo is slow (the bundled one is 2.4 MB), so the last decoded
+// version is kept until the company logo changes.
+fn logo_fingerprint(logo: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    logo.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Logo for the native PDFs
+fn read_logo_image(logo: &[u8]) -> Option<pdf::Image> {
+    static CACHE: Mutex<Option<(u64, Option<pdf::Image>)>> = Mutex::new(None);
+
+    let key = logo_fingerprint(logo);
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_key, image)) = cache.as_ref() {
+        if *cached_key == key {
+            return image.clone();
+        }
+    }
+
+    let image = pdf::Image::from_png(logo, 512)
+        .map_err(|e| warn!("Could not load logo for PDF: {}", e))
+        .ok();
+    *cache = Some((key, image.clone()));
+    image
+}
+
 fn parse_price(value: &Option<String>) -> Option<f64> {
     value.as_ref().and_then(|s| s.replace(',', ".").parse::<f64>().ok())
 }
@@ -60,48 +176,6 @@ fn build_invoice_key(id_partener: &str, serie_factura: &Option<String>, numar_fa
     )
 }
 
-fn get_receipt_series(conn: &rusqlite::Connection) -> Result<String, String> {
-    let (receipt_series_opt, carnet_series_opt): (Option<String>, Option<String>) = conn
-        .query_row(
-            "SELECT receipt_series, carnet_series FROM agent_settings WHERE id = 1",
-            [],
-            |row| Ok((row.get(0).ok(), row.get(1).ok()))
-        )
-        .map_err(|e| e.to_string())?;
-
-    Ok(receipt_series_opt
-        .filter(|s| !s.trim().is_empty())
-        .or(carnet_series_opt)
-        .unwrap_or_else(|| "CH".to_string()))
-}
-
-fn generate_receipt_number(conn: &rusqlite::Connection) -> Result<String, String> {
-    let (current, end): (Option<i64>, Option<i64>) = conn.query_row(
-        "SELECT receipt_number_current, receipt_number_end FROM agent_settings WHERE id = 1",
-        [],
-        |row| Ok((row.get(0).ok(), row.get(1).ok()))
-    ).map_err(|e| e.to_string())?;
-
-    if let Some(val) = current {
-        // Check end limit if set
-        if let Some(limit) = end {
-            if val > limit {
-                 return Err(format!("S-a atins limita de numere pentru chitanțe ({})", limit));
-            }
-        }
-
-        // Update DB with next value
-        let next_val = val + 1;
-        conn.execute("UPDATE agent_settings SET receipt_number_current = ?1 WHERE id = 1", [next_val])
-            .map_err(|e| e.to_string())?;
-
-        Ok(val.to_string())
-    } else {
-        // Fallback to timestamp if not configured
-        Ok(chrono::Local::now().format("%Y%m%d%H%M%S").to_string())
-    }
-}
-
 fn map_product_row(row: &rusqlite::Row) -> rusqlite::Result<Product> {
     // Parse TVA percentage from TEXT to f64
     let tva_percent: Option<f64> = match row.get::<_, Option<String>>(5)? {
@@ -119,88 +193,6 @@ fn map_product_row(row: &rusqlite::Row) -> rusqlite::Result<Product> {
     })
 }
 
-fn wait_for_file_ready(path: &str, timeout_ms: u64, stable_ms: u64) -> bool {
-    let start = std::time::Instant::now();
-    let mut last_size: Option<u64> = None;
-    let mut stable_for = 0u64;
-
-    while start.elapsed().as_millis() as u64 <= timeout_ms {
-        if let Ok(metadata) = std::fs::metadata(path) {
-            let size = metadata.len();
-            if size > 0 {
-                if Some(size) == last_size {
-                    stable_for += 100;
-                    if stable_for >= stable_ms {
-                        return true;
-                    }
-                } else {
-                    last_size = Some(size);
-                    stable_for = 0;
-                }
-            }
-        }
-
-        std::thread::sleep(std::time::Duration::from_millis(100));
-    }
-
-    false
-}
-
-fn try_generate_pdf_from_html(html_path_str: &str, pdf_path_str: &str) -> bool {
-    #[cfg(target_os = "windows")]
-    {
-        let edge_paths = vec![
-            "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe",
-            "C:\\Program Files\\Microsoft\\Edge\\Application\\msedge.exe",
-        ];
-
-        for edge_path in edge_paths {
-            if std::path::Path::new(edge_path).exists() {
-                let file_url = format!(
-                    "file:///{}",
-                    html_path_str.replace('\\', "/").replace(' ', "%20")
-                );
-
-                let temp_dir = std::env::temp_dir().join("esoft_edge_pdf");
-                let _ = std::fs::create_dir_all(&temp_dir);
-                let user_data_arg = format!("--user-data-dir={}", temp_dir.to_string_lossy());
-                let print_arg = format!("--print-to-pdf={}", pdf_path_str);
-                info!("[CERT][PDF] Generating PDF: {}", pdf_path_str);
-
-                let output = std::process::Command::new(edge_path)
-                    .args(&[
-                        "--headless",
-                        "--disable-gpu",
-                        "--no-sandbox",
-                        "--disable-dev-shm-usage",
-                        &user_data_arg,
-                        &print_arg,
-                        &file_url,
-                    ])
-                    .output();
-
-                if let Ok(result) = output {
-                    info!("[CERT][PDF] Edge status: {}, stderr: {}", result.status, String::from_utf8_lossy(&result.stderr));
-                    let mut waited = 0;
-                    while waited < 6000 {
-                        if wait_for_file_ready(pdf_path_str, 1200, 400) {
-                            info!("[CERT][PDF] PDF generated OK");
-                            return true;
-                        }
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                        waited += 100;
-                    }
-                    info!("[CERT][PDF] PDF not ready after 6s");
-                } else {
-                    info!("[CERT][PDF] Edge exec failed");
-                }
-            }
-        }
-    }
-
-    false
-}
-
 fn get_receipts_dirs_to_try() -> Vec<PathBuf> {
     let mut dirs_to_try = Vec::new();
 
@@ -225,32 +217,26 @@ fn get_receipts_dirs_to_try() -> Vec<PathBuf> {
 
 fn save_receipt_html_file(
     collection: &Collection,
-    doc_series: &str,
-    doc_number: &str,
-    issue_date: &str,
-    agent_name: Option<&str>,
+    issuer: &company::Issuer,
+    code: &codes::DocumentCode,
+    details: print_receipt::ReceiptDetails,
     nume_casa: &str,
-    partner_address: Option<&str>,
-    partner_localitate: Option<&str>,
-    partner_judet: Option<&str>,
-    partner_cui: Option<&str>,
-    partner_reg_com: Option<&str>,
     file_id: &str,
 ) -> Result<(String, String), String> {
-    let logo_base64 = read_logo_to_base64();
-    let html = print_receipt::generate_receipt_html(
+    let mut images = template_images(issuer);
+    code.add_to(&mut images);
+    let html = print_receipt::generate_receipt_html(collection, &issuer.profile, &images, details, nume_casa);
+    let html = if collection.status == CollectionStatus::Cancelled {
+        print_invoice::mark_cancelled(html)
+    } else {
+        html
+    };
+    let pdf_bytes = print_receipt::generate_receipt_pdf(
         collection,
-        logo_base64.as_deref(),
-        doc_series,
-        doc_number,
-        issue_date,
-        agent_name,
-        nume_casa,
-        partner_address,
-        partner_localitate,
-        partner_judet,
-        partner_cui,
-        partner_reg_com,
+        &issuer.profile,
+        read_logo_image(&issuer.logo).as_ref(),
+        code,
+        details,
     );
 
     let mut failures = Vec::new();
@@ -264,23 +250,20 @@ fn save_receipt_html_file(
         let html_file_path = dir.join(format!("chitanta_{}.html", file_id));
         let pdf_file_path = dir.join(format!("chitanta_{}.pdf", file_id));
 
-        match std::fs::write(&html_file_path, &html) {
-            Ok(_) => {
-                let html_path = html_file_path.to_string_lossy().to_string();
-                let pdf_path = pdf_file_path.to_string_lossy().to_string();
-                let pdf_generated = try_generate_pdf_from_html(&html_path, &pdf_path);
-                info!("[CHITANTE][SAVE] Saved receipt HTML at {}", html_path);
-                if pdf_generated {
-                    info!("[CHITANTE][SAVE] Saved receipt PDF at {}", pdf_path);
-                } else {
-                    warn!("[CHITANTE][SAVE] Could not generate receipt PDF, HTML is available at {}", html_path);
-                }
-                return Ok((html_path, pdf_path));
-            }
-            Err(e) => {
-                failures.push(format!("write {}: {}", html_file_path.display(), e));
-            }
+        if let Err(e) = std::fs::write(&html_file_path, &html) {
+            failures.push(format!("write {}: {}", html_file_path.display(), e));
+            continue;
         }
+        if let Err(e) = std::fs::write(&pdf_file_path, &pdf_bytes) {
+            failures.push(format!("write {}: {}", pdf_file_path.display(), e));
+            continue;
+        }
+
+        let html_path = html_file_path.to_string_lossy().to_string();
+        let pdf_path = pdf_file_path.to_string_lossy().to_string();
+        info!("[CHITANTE][SAVE] Saved receipt HTML at {}", html_path);
+        info!("[CHITANTE][SAVE] Saved receipt PDF at {} ({} bytes)", pdf_path, pdf_bytes.len());
+        return Ok((html_path, pdf_path));
     }
 
     Err(format!(
@@ -289,14 +272,6 @@ fn save_receipt_html_file(
     ))
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-struct QualityCertificateProductLine {
-    denumire: String,
-    lot: String,
-    data_productie: String,
-    data_expirare: String,
-}
-
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 struct CertificateCachePayload {
     subtitle: String,
@@ -304,18 +279,6 @@ struct CertificateCachePayload {
     product_lines: Vec<QualityCertificateProductLine>,
 }
 
-struct QualityCertificateContext {
-    cert_date: String,
-    subtitle: String,
-    packed_date: String,
-    beneficiary: String,
-    invoice_display: String,
-    invoice_date: String,
-    car_number: String,
-    bon_analiza: String,
-    product_lines: Vec<QualityCertificateProductLine>,
-}
-
 fn parse_comanda_numar(value: Option<&String>) -> i64 {
     let raw = value.map(|v| v.trim()).unwrap_or_default();
     if raw.is_empty() {
@@ -749,140 +712,46 @@ async fn build_quality_certificate_context(
     Ok(ctx)
 }
 
-fn generate_quality_certificate_html(ctx: &QualityCertificateContext) -> String {
-    use base64::{engine::general_purpose, Engine as _};
+/// Data for templates/quality_certificate.html, see templates/README.md.
+#[derive(serde::Serialize)]
+struct QualityCertificateTemplate<'a> {
+    company: templates::CompanyContext<'a>,
+    images: templates::Images,
+    #[serde(flatten)]
+    certificate: &'a QualityCertificateContext,
+    footer_lines: Vec<String>,
+    preview: bool,
+}
 
-    let epc_img = general_purpose::STANDARD.encode(include_bytes!("../../public/EPC 16 EC.png"));
-    let iso_img = general_purpose::STANDARD.encode(include_bytes!("../../public/KARIN-ISO.png"));
-    let stamp_img = general_purpose::STANDARD.encode(include_bytes!("../../public/STAMPILA.png"));
-    let product_lines_html = ctx
-        .product_lines
-        .iter()
-        .map(|line| {
-            format!(
-                r#"<div class="cat-line">{} {} ddm {} Lot {}</div>"#,
-                line.denumire,
-                line.data_productie,
-                line.data_expirare,
-                line.lot
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
+fn quality_certificate_template<'a>(
+    ctx: &'a QualityCertificateContext,
+    issuer: &'a company::Issuer,
+    code: &codes::DocumentCode,
+) -> QualityCertificateTemplate<'a> {
+    let mut images = templates::Images {
+        epc: png_data_url(include_bytes!("../../public/EPC 16 EC.png")),
+        iso: png_data_url(include_bytes!("../../public/KARIN-ISO.png")),
+        ..template_images(issuer)
+    };
+    code.add_to(&mut images);
+
+    QualityCertificateTemplate {
+        company: templates::company_context(&issuer.profile),
+        images,
+        certificate: ctx,
+        footer_lines: templates::footer_lines(templates::DocType::QualityCertificate, &issuer.profile),
+        preview: false,
+    }
+}
 
-    format!(
-        r#"<!DOCTYPE html>
-<html lang="ro">
-<head>
-    <meta charset="UTF-8" />
-    <title>Certificat de calitate - Declarație de conformitate</title>
-    <style>
-        @page {{ size: 80mm 297mm; margin: 2.5mm; }}
-        body {{ font-family: Arial, Helvetica, sans-serif; margin: 0; padding: 0; color: #000; font-weight: 700; }}
-        .page {{ width: 68mm; margin-left: 0.5mm; box-sizing: border-box; padding: 0.5mm; }}
-        .header {{ margin-bottom: 3px; text-align: center; border-bottom: 2px solid #000; padding-bottom: 2px; }}
-        .header-line {{ font-size: 10.5px; font-weight: 800; line-height: 1.2; }}
-        .header-line + .header-line {{ margin-top: 0; }}
-        .logos {{ display: flex; justify-content: center; align-items: center; gap: 8px; margin: 3px 0; }}
-        .logo-box {{ width: calc(50% - 4px); display: flex; align-items: center; justify-content: center; }}
-        .logo-img {{ max-width: 100%; height: auto; object-fit: contain; }}
-        .title {{ text-align: center; font-size: 13px; font-weight: 900; margin: 3px 0 1px; text-decoration: underline; text-transform: uppercase; }}
-        .date {{ text-align: center; font-size: 10.5px; font-weight: 800; margin-top: 1px; }}
-        .cert-subtitle {{ text-align: center; font-size: 12px; font-weight: 800; margin-top: 1px; }}
-        .cert-intro {{ margin-top: 3px; font-size: 10.5px; line-height: 1.2; text-align: left; font-weight: 800; }}
-        .cat-grid {{ display: grid; grid-template-columns: 1fr; gap: 0; margin-top: 3px; }}
-        .cat-group {{ width: 100%; text-align: left; margin: 0 0 4px 0; }}
-        .cat-group:last-child {{ margin-bottom: 2px; }}
-        .cat-line {{ font-size: 10px; line-height: 1.2; font-weight: 700; white-space: normal; overflow-wrap: anywhere; margin: 0; text-align: left; }}
-        .cert-body {{ margin-top: 3px; font-size: 9.5px; line-height: 1.2; text-align: left; font-weight: 700; }}
-        .cert-body p {{ margin: 0 0 1px 0; }}
-        .cert-footer {{ display: flex; justify-content: space-between; align-items: flex-start; margin-top: 4px; font-size: 9.5px; font-weight: 700; }}
-        .footer-col {{ width: 48%; }}
-        .footer-right {{ text-align: right; padding-right: 5mm; }}
-        .stamp-section {{ text-align: center; margin-top: 4px; }}
-        .footer-stamp {{ width: 100px; height: auto; object-fit: contain; }}
-    </style>
-</head>
-<body>
-    <div class="page">
-        <div class="header">
-            <div class="header-line">PO 7.5-03-F01Rev. 8/12012021</div>
-            <div class="header-line">SC KARIN SRL</div>
-            <div class="header-line">J24/380/1994, SEINI, N.BALCESCU, 43</div>
-            <div class="header-line">Jud. MM</div>
-        </div>
-
-        <div class="logos">
-            <div class="logo-box">
-                <img src="data:image/png;base64,{}" class="logo-img" alt="EPC 16 EC" />
-            </div>
-            <div class="logo-box">
-                <img src="data:image/png;base64,{}" class="logo-img" alt="KARIN ISO" />
-            </div>
-        </div>
-
-        <div class="title">Certificat de calitate - Declarație de conformitate</div>
-        <div class="date">Data: {}</div>
-        <div class="cert-subtitle">{}</div>
-
-        <div class="cert-intro">
-            În conformitate cu prevederile legale privind răspunderea,
-            se atestă calitatea produselor livrate: ouă consum categoria A,
-            cu data ouatului:
-        </div>
-
-        <div class="cat-grid">
-            <div class="cat-group">
-                {}
-            </div>
-        </div>
-
-        <div class="cert-body">
-            <p>Ambalate la data de {}. Livrate beneficiarului: {}. Conform facturii/avizului nr. {} din {}.</p>
-            <p>Transport auto: {} indeplinesc parametri de calitate specificati conform {} (salmonella negativ).</p>
-            <p>Caracteristici tehnice de livrare: SALUBRE; Rasa LOHMANN BROWN, LOHMANN SANDY; Aspectul cojii intactă, curată de formă normală, uscată;</p>
-            <p>Camera de aer: imobilă, cu înălțimea maximă 5 mm. Albușul: clar, translucid, consistență gelatinoasă si lipsit de corpuri străine de orice natura.</p>
-            <p>Gălbenuș vizibil, în fascicol de lumina sub formă de umbră. Mirosul și gust caracteristic oului proaspăt, fără miros și gust străin.</p>
-            <p>Data durabilității minime este de 28 zile iar data recomandata pentru vanzare este de 28 de zile de la momentul ouatului.</p>
-            <p>Temperatura de păstrare: 5-18 grade Celsius,În magazine, ferite de razele soarelui si sursa de caldura.</p>
-            <p>In magazinele de desfacere, ouale se pastreaza in locuri racoroase, curate, ferite de alte produse ale caror miros le pot imprumuta.</p>
-            <p>Produs fragil! A se manipula cu atenție la transport și depozitare.</p>
-            <p>Prezentul certificat întocmit conform Reg.(CE) nr.1234/22.10.2007 de instituire a unei organizari comune a pietelor agricole si privind</p>
-            <p>dispozitii specifice referitoare la anumite produse agricole ("Regulamentul unic OCP"). Regulamentul (CE)NR.589/2008 al Comisiei din 23.06.2008</p>
-            <p>de stabilire a normelor de aplicare a Reg.(CE)nr.1234/2007 al Consiliului privind standardele de comercializare a oualelor, modificat de Regulamentul </p>
-            <p>CE 598/2008. Mentionam ca ouale produse de noi cu cod pro.3RO MM 013 provin de la gaini crescute in custi imbunatatie si cu cod producator</p>
-            <p>2RO MM 040 provin de la gaini cresute in sistem volieră. conform standardelor U.E. in vigoare. </p>
-        </div>
-
-        <div class="cert-footer">
-            <div class="footer-col">
-            </div>
-            <div class="footer-col footer-right">
-                Țara de origine:România<br>
-                Cod stație sortare RO MM 023<br>
-                Cod producător 3RO MM 013<br>
-                Cod producător 2RO MM 040
-            </div>
-        </div>
-
-        <div class="stamp-section">
-            <img src="data:image/png;base64,{}" class="footer-stamp" alt="Ștampilă" />
-        </div>
-    </div>
-</body>
-</html>"#,
-        epc_img,
-        iso_img,
-        ctx.cert_date,
-        ctx.subtitle,
-        product_lines_html,
-        ctx.packed_date,
-        ctx.beneficiary,
-        ctx.invoice_display,
-        ctx.invoice_date,
-        ctx.car_number,
-        ctx.bon_analiza,
-        stamp_img,
+fn generate_quality_certificate_html(
+    ctx: &QualityCertificateContext,
+    issuer: &company::Issuer,
+    code: &codes::DocumentCode,
+) -> String {
+    templates::render(
+        templates::DocType::QualityCertificate,
+        &quality_certificate_template(ctx, issuer, code),
     )
 }
 
@@ -892,7 +761,14 @@ async fn save_invoice_certificate_file(
     car_number: &str,
 ) -> Result<(String, String, String), String> {
     let context = build_quality_certificate_context(db, invoice_id, car_number).await?;
-    let html = generate_quality_certificate_html(&context);
+    let (issuer, code) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let issuer = company::invoice_issuer(&conn, invoice_id);
+        let data = load_invoice_print_data(&conn, invoice_id)?;
+        let code = invoice_code(&conn, &data, &issuer, codes::Kind::QualityCertificate);
+        (issuer, code)
+    };
+    let html = generate_quality_certificate_html(&context, &issuer, &code);
 
     let app_data_dir = dirs::config_dir()
         .ok_or("Could not find app data directory")?
@@ -909,10 +785,12 @@ async fn save_invoice_certificate_file(
     std::fs::write(&html_file_path, &html)
         .map_err(|e| format!("Failed to write certificate HTML file: {}", e))?;
 
+    std::fs::write(&pdf_file_path, print_quality_certificate::generate_quality_certificate_pdf(&context, &issuer, &code))
+        .map_err(|e| format!("Failed to write certificate PDF file: {}", e))?;
+
     let html_path = html_file_path.to_string_lossy().to_string();
     let pdf_path = pdf_file_path.to_string_lossy().to_string();
-    let pdf_generated = try_generate_pdf_from_html(&html_path, &pdf_path);
-    let print_target = if pdf_generated { pdf_path.clone() } else { html_path.clone() };
+    let print_target = pdf_path.clone();
 
     Ok((html_path, pdf_path, print_target))
 }
@@ -990,6 +868,15 @@ pub fn delete_partners_and_locations(db: State<'_, Database>) -> Result<String,
                 JOIN invoices i ON i.location_id = l.id
                 WHERE l.partner_id = p.id
             )
+            OR EXISTS (
+                SELECT 1 FROM archive.invoices a WHERE a.partner_id = p.id
+            )
+            OR EXISTS (
+                SELECT 1
+                FROM locations l
+                JOIN archive.invoices a ON a.location_id = l.id
+                WHERE l.partner_id = p.id
+            )
             "#,
             [],
             |row| row.get(0),
@@ -1012,6 +899,16 @@ pub fn delete_partners_and_locations(db: State<'_, Database>) -> Result<String,
                     JOIN invoices i ON i.location_id = l.id
                     WHERE l.partner_id = p.id
                 )
+                -- Archived invoices are still reported on with their partner
+                AND NOT EXISTS (
+                    SELECT 1 FROM archive.invoices a WHERE a.partner_id = p.id
+                )
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM locations l
+                    JOIN archive.invoices a ON a.location_id = l.id
+                    WHERE l.partner_id = p.id
+                )
             )
             "#,
             [],
@@ -1024,6 +921,9 @@ pub fn delete_partners_and_locations(db: State<'_, Database>) -> Result<String,
 
     let deleted_locations = total_locations - remaining_locations;
 
+    search_index::rebuild_partner_index(&conn)
+        .map_err(|e| format!("Failed to rebuild search index: {}", e))?;
+
     Ok(format!(
         "Ștergere finalizată: {} parteneri și {} sedii șterse. {} parteneri păstrați deoarece au facturi asociate.",
         deleted_partners,
@@ -1125,6 +1025,12 @@ pub async fn sync_all_data(db: State<'_, Database>) -> Result<SyncStatus, String
         .unwrap_or_default();
 
     // Convert API data to our models
+    // Product codes are only kept for the search index; conversion preserves article order
+    let article_codes: Vec<(Option<String>, Option<String>, Option<String>)> = api_articles
+        .iter()
+        .map(|article| (article.cod_obiect.clone(), article.cod_extern.clone(), article.cod_intern.clone()))
+        .collect();
+
     let partners = convert_api_partners_to_model(api_partners, marca_agent.clone());
     let products = convert_api_articles_to_model(api_articles);
     let offers = Some(offers_list);
@@ -1210,14 +1116,14 @@ pub async fn sync_all_data(db: State<'_, Database>) -> Result<SyncStatus, String
         }
 
         // Save products
-        for product in &products {
+        for (product, (cod_obiect, cod_extern, cod_intern)) in products.iter().zip(article_codes.iter()) {
             // Convert Option<f64> to Option<String> for database storage
             let tva_str = product.tva_percent.map(|t| t.to_string());
 
             conn.execute(
-                "INSERT INTO products (id, name, unit_of_measure, price, class, procent_tva) VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
-                 ON CONFLICT(id) DO UPDATE SET name = excluded.name, unit_of_measure = excluded.unit_of_measure, price = excluded.price, class = excluded.class, procent_tva = excluded.procent_tva",
-                (&product.id, &product.name, &product.unit_of_measure, product.price, &product.class, &tva_str),
+                "INSERT INTO products (id, name, unit_of_measure, price, class, procent_tva, cod_obiect, cod_extern, cod_intern) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) \
+                 ON CONFLICT(id) DO UPDATE SET name = excluded.name, unit_of_measure = excluded.unit_of_measure, price = excluded.price, class = excluded.class, procent_tva = excluded.procent_tva, cod_obiect = excluded.cod_obiect, cod_extern = excluded.cod_extern, cod_intern = excluded.cod_intern",
+                params![&product.id, &product.name, &product.unit_of_measure, product.price, &product.class, &tva_str, cod_obiect, cod_extern, cod_intern],
             )
             .map_err(|e| format!("Failed to save product: {}", e))?;
         }
@@ -1299,6 +1205,10 @@ pub async fn sync_all_data(db: State<'_, Database>) -> Result<SyncStatus, String
         )
         .map_err(|e| format!("Failed to update sync metadata: {}", e))?;
 
+        // Refresh the full-text search index with the new partners/products
+        search_index::rebuild_all(&conn)
+            .map_err(|e| format!("Failed to rebuild search index: {}", e))?;
+
         // Re-enable foreign key checks
         conn.execute("PRAGMA foreign_keys = ON", [])
             .map_err(|e| format!("Failed to re-enable foreign keys: {}", e))?;
@@ -2017,17 +1927,38 @@ pub fn search_partners(
     query: String,
 ) -> Result<Vec<PartnerWithLocations>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let search_query = format!("%{}%", query);
+
+    // Ranked full-text hits (diacritics-insensitive, prefix matching on name, codes and locations).
+    // A query without any searchable characters falls back to the plain name filter.
+    let ranked_ids: Option<Vec<String>> = match search_index::build_match_query(&query) {
+        Some(match_query) => Some(
+            search_index::search_partner_ids(&conn, &match_query).map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
+
+    let (filter_sql, search_param) = match &ranked_ids {
+        Some(ids) => (
+            "id IN (SELECT value FROM json_each(?1))",
+            serde_json::to_string(ids).map_err(|e| e.to_string())?,
+        ),
+        None => ("name LIKE ?1", format!("%{}%", query)),
+    };
+
+    let sql = format!(
+        "SELECT id, name, cif, reg_com, cod, blocat, tva_la_incasare, persoana_fizica, cod_extern, cod_intern, observatii, data_adaugarii, created_at, updated_at, clasa, simbol_clasa, cod_clasa, inactiv, categorie_pret_implicita, simbol_categorie_pret, scadenta_la_vanzare, scadenta_la_cumparare, credit_client, discount_fix, tip_partener, mod_aplicare_discount, moneda, data_nastere, caracterizare_contabila_denumire, caracterizare_contabila_simbol FROM partners WHERE (simbol_clasa = 'AGENTI' OR clasa = 'AGENTI') AND {} ORDER BY name",
+        filter_sql
+    );
 
     let mut stmt = conn
-        .prepare("SELECT id, name, cif, reg_com, cod, blocat, tva_la_incasare, persoana_fizica, cod_extern, cod_intern, observatii, data_adaugarii, created_at, updated_at, clasa, simbol_clasa, cod_clasa, inactiv, categorie_pret_implicita, simbol_categorie_pret, scadenta_la_vanzare, scadenta_la_cumparare, credit_client, discount_fix, tip_partener, mod_aplicare_discount, moneda, data_nastere, caracterizare_contabila_denumire, caracterizare_contabila_simbol FROM partners WHERE (simbol_clasa = 'AGENTI' OR clasa = 'AGENTI') AND name LIKE ?1 ORDER BY name")
+        .prepare(&sql)
         .map_err(|e| e.to_string())?;
 
     let partners: Vec<(
         String, String, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, String, String,
         Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>
     )> = stmt
-        .query_map([&search_query], |row| {
+        .query_map([&search_param], |row| {
             Ok((
                 row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?, row.get(8)?, row.get(9)?, row.get(10)?, row.get(11)?, row.get(12)?, row.get(13)?,
                 row.get(14)?, row.get(15)?, row.get(16)?, row.get(17)?, row.get(18)?, row.get(19)?, row.get(20)?, row.get(21)?, row.get(22)?, row.get(23)?, row.get(24)?, row.get(25)?, row.get(26)?, row.get(27)?, row.get(28)?, row.get(29)?
@@ -2102,6 +2033,11 @@ pub fn search_partners(
         });
     }
 
+    if let Some(ids) = &ranked_ids {
+        let rank: HashMap<&str, usize> = ids.iter().enumerate().map(|(pos, id)| (id.as_str(), pos)).collect();
+        result.sort_by_key(|partner| rank.get(partner.id.as_str()).copied().unwrap_or(usize::MAX));
+    }
+
     Ok(result)
 }
 
@@ -2139,31 +2075,54 @@ pub fn get_products(db: State<'_, Database>, partner_id: Option<String>) -> Resu
 #[tauri::command]
 pub fn search_products(db: State<'_, Database>, query: String, partner_id: Option<String>) -> Result<Vec<Product>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let search_query = format!("%{}%", query);
 
-    let mut stmt = if partner_id.is_some() {
-        conn.prepare(
+    // Ranked full-text hits on name, class and product codes; empty queries keep the LIKE filter.
+    let ranked_ids: Option<Vec<String>> = match search_index::build_match_query(&query) {
+        Some(match_query) => Some(
+            search_index::search_product_ids(&conn, &match_query).map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
+
+    let search_param = match &ranked_ids {
+        Some(ids) => serde_json::to_string(ids).map_err(|e| e.to_string())?,
+        None => format!("%{}%", query),
+    };
+
+    let mut stmt = match (&ranked_ids, partner_id.is_some()) {
+        (Some(_), true) => conn.prepare(
+            "SELECT p.id, p.name, p.unit_of_measure, COALESCE(oi.pret, p.price) AS price, p.class, p.procent_tva \
+             FROM products p \
+             LEFT JOIN offer_items oi ON oi.product_id = p.id AND oi.id_client = ?2 \
+             WHERE p.id IN (SELECT value FROM json_each(?1)) \
+             ORDER BY p.name",
+        ),
+        (Some(_), false) => conn.prepare("SELECT id, name, unit_of_measure, CASE WHEN price = 0 THEN COALESCE(pret_cu_tva, pret_valuta, pret_referinta, 0) ELSE price END AS price, class, procent_tva FROM products WHERE id IN (SELECT value FROM json_each(?1)) ORDER BY name"),
+        (None, true) => conn.prepare(
             "SELECT p.id, p.name, p.unit_of_measure, COALESCE(oi.pret, p.price) AS price, p.class, p.procent_tva \
              FROM products p \
              LEFT JOIN offer_items oi ON oi.product_id = p.id AND oi.id_client = ?2 \
              WHERE p.name LIKE ?1 OR p.class LIKE ?1 \
              ORDER BY p.name",
-        )
-        .map_err(|e| e.to_string())?
-    } else {
-        conn.prepare("SELECT id, name, unit_of_measure, CASE WHEN price = 0 THEN COALESCE(pret_cu_tva, pret_valuta, pret_referinta, 0) ELSE price END AS price, class, procent_tva FROM products WHERE name LIKE ?1 OR class LIKE ?1 ORDER BY name")
-            .map_err(|e| e.to_string())?
-    };
+        ),
+        (None, false) => conn.prepare("SELECT id, name, unit_of_measure, CASE WHEN price = 0 THEN COALESCE(pret_cu_tva, pret_valuta, pret_referinta, 0) ELSE price END AS price, class, procent_tva FROM products WHERE name LIKE ?1 OR class LIKE ?1 ORDER BY name"),
+    }
+    .map_err(|e| e.to_string())?;
 
-    let products: Vec<Product> = if let Some(pid) = &partner_id {
-        stmt.query_map([&search_query, pid], map_product_row)
+    let mut products: Vec<Product> = if let Some(pid) = &partner_id {
+        stmt.query_map([&search_param, pid], map_product_row)
     } else {
-        stmt.query_map([&search_query], map_product_row)
+        stmt.query_map([&search_param], map_product_row)
     }
         .map_err(|e| e.to_string())?
         .filter_map(|r| r.ok())
         .collect();
 
+    if let Some(ids) = &ranked_ids {
+        let rank: HashMap<&str, usize> = ids.iter().enumerate().map(|(pos, id)| (id.as_str(), pos)).collect();
+        products.sort_by_key(|product| rank.get(product.id.as_str()).copied().unwrap_or(usize::MAX));
+    }
+
     Ok(products)
 }
 
@@ -2248,56 +2207,56 @@ pub fn create_invoice(
     }
     let total_amount = (total_amount * 100.0).round() / 100.0;
 
-    // Get invoice number from agent settings (settings-based numbering)
-    let (invoice_number, invoice_end, carnet_series): (i64, i64, Option<String>) = conn
-        .query_row(
-            "SELECT COALESCE(invoice_number_current, 1), COALESCE(invoice_number_end, 99999), carnet_series FROM agent_settings WHERE id = 1",
-            [],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
-        )
-        .unwrap_or((1, 99999, None));
+    // The number is taken in the same transaction as the insert, so a failed
+    // invoice gives it back
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
 
-    // Validate we haven't exceeded the end number
-    if invoice_number > invoice_end {
-        return Err(format!(
-            "Invoice number {} exceeds maximum configured number {}. Please update the number range in settings.",
-            invoice_number, invoice_end
-        ));
-    }
+    let allocated = match numbering::allocate(&conn, numbering::INVOICE, &invoice_id) {
+        Ok(allocated) => allocated,
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(e);
+        }
+    };
+    let invoice_number = allocated.number;
+    let invoice_series = Some(allocated.series).filter(|series| !series.is_empty());
 
-    info!("Using invoice number {} and series {:?} from settings (max: {})", invoice_number, carnet_series, invoice_end);
+    info!("Using invoice number {} and series {:?}", invoice_number, invoice_series);
 
-    // Insert invoice with number from settings
-    conn.execute(
+    if let Err(e) = conn.execute(
         "INSERT INTO invoices (id, invoice_number, invoice_series, partner_id, location_id, status, total_amount, notes, created_at) VALUES (?1, ?2, ?3, ?4, ?5, 'pending', ?6, ?7, ?8)",
-        (&invoice_id, invoice_number, &carnet_series, &request.partner_id, &request.location_id, total_amount, &request.notes, &now),
-    )
-    .map_err(|e| e.to_string())?;
-
-    // Increment the current invoice number in settings for next invoice
-    // Using UPSERT to handle case when agent_settings has no rows
-    conn.execute(
-        "INSERT INTO agent_settings (id, invoice_number_current) VALUES (1, ?1)
-         ON CONFLICT(id) DO UPDATE SET invoice_number_current = invoice_number_current + 1",
-        [invoice_number + 1],
-    )
-    .map_err(|e| e.to_string())?;
-
-    info!("Invoice created successfully. Next invoice number will be: {}", invoice_number + 1);
+        (&invoice_id, invoice_number, &invoice_series, &request.partner_id, &request.location_id, total_amount, &request.notes, &now),
+    ) {
+        let _ = conn.execute("ROLLBACK", []);
+        return Err(e.to_string());
+    }
+    if let Err(e) = company::record_invoice_issuer(&conn, &invoice_id) {
+        let _ = conn.execute("ROLLBACK", []);
+        return Err(e.to_string());
+    }
 
     // Insert invoice items
     for (item_id, product_id, _, quantity, unit_price, _, total_price) in &items_to_insert {
-        conn.execute(
+        if let Err(e) = conn.execute(
             "INSERT INTO invoice_items (id, invoice_id, product_id, quantity, unit_price, total_price) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             (item_id, &invoice_id, product_id, quantity, unit_price, total_price),
-        )
-        .map_err(|e| e.to_string())?;
+        ) {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(e.to_string());
+        }
     }
 
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+
+    info!("Invoice created successfully with number {}", invoice_number);
+
+    document_events::record_invoice(&conn, &invoice_id, document_events::CREATED, None);
+
     Ok(Invoice {
         id: invoice_id,
         invoice_number: invoice_number as i32,
-        invoice_series: carnet_series,
+        invoice_series,
         partner_id: request.partner_id,
         partner_name,
         partner_cif: None,
@@ -2502,7 +2461,7 @@ pub async fn send_invoice(db: State<'_, Database>, invoice_id: String) -> Result
         let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
         // Get invoice with partner cod
-        let invoice: Invoice = conn
+        let mut invoice: Invoice = conn
             .query_row(
                 r#"
                 SELECT
@@ -2564,10 +2523,11 @@ pub async fn send_invoice(db: State<'_, Database>, invoice_id: String) -> Result
             .ok()
             .flatten();
 
-        // Get invoice number from the invoice record
-        let invoice_number: i64 = conn
-            .query_row("SELECT invoice_number FROM invoices WHERE id = ?1", [&invoice_id], |row| row.get(0))
+        // Get invoice number and series from the invoice record
+        let (invoice_number, invoice_series): (i64, Option<String>) = conn
+            .query_row("SELECT invoice_number, invoice_series FROM invoices WHERE id = ?1", [&invoice_id], |row| Ok((row.get(0)?, row.get(1)?)))
             .map_err(|e| format!("Failed to get invoice number: {}", e))?;
+        invoice.invoice_series = invoice_series;
 
         // Get invoice items with UM from products
         let mut stmt = conn
@@ -2704,7 +2664,8 @@ pub async fn send_invoice(db: State<'_, Database>, invoice_id: String) -> Result
 
             // We make a best-effort check. If this fails (e.g. network), we assume it's NOT sent and proceed.
             if let Ok(response) = api.get_solduri_clienti(filter).await {
-                let current_series = agent_settings.carnet_series.as_deref().unwrap_or("").trim();
+                let current_series = invoice_carnet_series(invoice.invoice_series.as_deref(), agent_settings.carnet_series.as_deref());
+                let current_series = current_series.trim();
                 let current_number = invoice_number.to_string();
 
                 let already_exists = response.info_solduri.iter().any(|sold| {
@@ -2833,7 +2794,7 @@ pub async fn send_invoice(db: State<'_, Database>, invoice_id: String) -> Result
         documente: vec![api_client::WmeDocument {
             tip_document: Some("FACTURA IESIRE".to_string()),
             numar_document: Some(invoice_number.to_string()), // Folosim numărul din aplicație
-            simbol_carnet: Some(agent_settings.carnet_series.clone().unwrap()),
+            simbol_carnet: Some(invoice_carnet_series(invoice.invoice_series.as_deref(), agent_settings.carnet_series.as_deref())),
             nr_livr: Some(invoice_number.to_string()),
             simbol_carnet_livr: Some(agent_settings.simbol_carnet_livr.clone().unwrap()),
             simbol_gestiune_livrare: Some(agent_settings.simbol_gestiune_livrare.clone().unwrap()),
@@ -2988,9 +2949,25 @@ pub async fn send_invoice(db: State<'_, Database>, invoice_id: String) -> Result
     Ok(invoice)
 }
 
-#[tauri::command]
-pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -> Result<String, String> {
-    info!("Previewing JSON for invoice: {}", invoice_id);
+// Helper: carnet an invoice is sent to WME under: its own series, or the
+// configured carnet for invoices numbered before they carried one.
+fn invoice_carnet_series(invoice_series: Option<&str>, carnet_series: Option<&str>) -> String {
+    invoice_series
+        .filter(|series| !series.trim().is_empty())
+        .or(carnet_series)
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Helper: builds the WME request for a stored invoice. `anulat` is "N" for a
+// normal send and "DA" when the invoice is sent to WME as cancelled.
+fn build_wme_invoice_request(
+    db: &State<'_, Database>,
+    invoice_id: &str,
+    anulat: &str,
+) -> Result<api_client::WmeInvoiceRequest, String> {
+    // Get agent settings (before taking the lock: get_agent_settings locks too)
+    let agent_settings = get_agent_settings(db.clone()).map_err(|e| e.to_string())?;
 
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
@@ -2998,19 +2975,19 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
     let (partner_name, location_name, notes, created_at, invoice_number): (String, String, Option<String>, String, i64) = conn
         .query_row(
             "SELECT p.name, l.name, i.notes, i.created_at, i.invoice_number FROM invoices i JOIN partners p ON i.partner_id = p.id JOIN locations l ON i.location_id = l.id WHERE i.id = ?1",
-            [&invoice_id],
+            [invoice_id],
             |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
         )
         .map_err(|e| format!("Invoice not found: {}", e))?;
-
-    // Get agent settings
-    let agent_settings = get_agent_settings(db.clone()).map_err(|e| e.to_string())?;
+    let invoice_series: Option<String> = conn
+        .query_row("SELECT invoice_series FROM invoices WHERE id = ?1", [invoice_id], |row| row.get(0))
+        .map_err(|e| format!("Invoice not found: {}", e))?;
 
     // Get partner CodIntern and location ID
     let (partner_cod, location_id_sediu, partner_moneda, partner_payment_term): (Option<String>, Option<String>, Option<String>, Option<String>) = conn
         .query_row(
             "SELECT p.cod_intern, l.id_sediu, p.moneda, p.scadenta_la_vanzare FROM invoices i JOIN partners p ON i.partner_id = p.id JOIN locations l ON i.location_id = l.id WHERE i.id = ?1",
-            [&invoice_id],
+            [invoice_id],
             |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
         )
         .map_err(|e| format!("Failed to get partner info: {}", e))?;
@@ -3026,7 +3003,7 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
         .map_err(|e| e.to_string())?;
 
     let items: Vec<(String, f64, f64, String)> = stmt
-        .query_map([&invoice_id], |row| {
+        .query_map([invoice_id], |row| {
             Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
         })
         .map_err(|e| e.to_string())?
@@ -3095,7 +3072,7 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
         .filter(|value| !value.is_empty())
         .unwrap_or_default();
 
-    // Build WME items
+    // Build WME items (rounded the same way send_invoice does)
     let gestiune = agent_settings.simbol_gestiune_livrare.clone().unwrap();
     let tip_contabil = agent_settings
         .tip_contabil
@@ -3107,8 +3084,8 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
         .into_iter()
         .map(|(product_id, quantity, price, um)| api_client::WmeInvoiceItem {
             id_articol: product_id,
-            cant: quantity,
-            pret: price,
+            cant: (quantity * 100.0).round() / 100.0,
+            pret: (price * 100.0).round() / 100.0,
             um: Some(um),
             gestiune: Some(gestiune.clone()),
             tip_contabil: Some(tip_contabil.clone()),
@@ -3120,7 +3097,7 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
         .collect();
 
     // Build WME request
-    let wme_request = api_client::WmeInvoiceRequest {
+    Ok(api_client::WmeInvoiceRequest {
         tip_document: Some("FACTURA IESIRE".to_string()),
         an_lucru: Some(an_lucru.to_string()),
         luna_lucru: Some(luna_lucru.to_string()),
@@ -3128,7 +3105,7 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
         documente: vec![api_client::WmeDocument {
             tip_document: Some("FACTURA IESIRE".to_string()),
             numar_document: Some(invoice_number.to_string()), // Folosim numărul din aplicație
-            simbol_carnet: Some(agent_settings.carnet_series.clone().unwrap()),
+            simbol_carnet: Some(invoice_carnet_series(invoice_series.as_deref(), agent_settings.carnet_series.as_deref())),
             nr_livr: Some(invoice_number.to_string()),
             simbol_carnet_livr: Some(agent_settings.simbol_carnet_livr.clone().unwrap()),
             simbol_gestiune_livrare: Some(agent_settings.simbol_gestiune_livrare.clone().unwrap()),
@@ -3136,7 +3113,7 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
             data: Some(data_formatted.clone()),
             data_livr: Some(data_formatted),
             operatie: Some("A".to_string()),
-            anulat: Some("N".to_string()),
+            anulat: Some(anulat.to_string()),
             listat: Some("D".to_string()),
             cod_client: Some(partner_cod.unwrap()),
             id_sediu: location_id_sediu,
@@ -3151,10 +3128,17 @@ pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -
             cod_delegat: Some(cod_delegat),
             emisa_de: None,
             scadenta: Some(scadenta),
-            observatii: notes.clone(),
+            observatii: notes,
             items: Some(wme_items),
         }],
-    };
+    })
+}
+
+#[tauri::command]
+pub async fn preview_invoice_json(db: State<'_, Database>, invoice_id: String) -> Result<String, String> {
+    info!("Previewing JSON for invoice: {}", invoice_id);
+
+    let wme_request = build_wme_invoice_request(&db, &invoice_id, "N")?;
 
     // Return pretty JSON
     serde_json::to_string_pretty(&wme_request)
@@ -3290,32 +3274,170 @@ pub fn cancel_invoice_sending(db: State<'_, Database>, invoice_id: String) -> Re
     Ok(invoice)
 }
 
-#[tauri::command]
-pub fn delete_invoice(db: State<'_, Database>, invoice_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    // Delete invoice items first
-    conn.execute(
-        "DELETE FROM invoice_items WHERE invoice_id = ?1",
-        [&invoice_id],
-    )
-    .map_err(|e| e.to_string())?;
-
-    // Delete invoice
-    conn.execute("DELETE FROM invoices WHERE id = ?1", [&invoice_id])
-        .map_err(|e| e.to_string())?;
-
-    info!("Deleted invoice {}", invoice_id);
-    Ok(())
-}
-
-// ==================== PRINT COMMANDS ====================
-
-#[tauri::command]
-pub fn get_available_printers() -> Result<Vec<String>, String> {
-    #[cfg(target_os = "windows")]
-    {
-        // 1. Try WMIC first (Much faster than PowerShell)
+// Helper: loads a single invoice in the same shape as get_invoices
+fn load_invoice(conn: &rusqlite::Connection, invoice_id: &str) -> Result<Invoice, String> {
+    conn.query_row(
+        r#"
+        SELECT
+            i.id, i.partner_id, p.name, p.cif, p.reg_com, i.location_id, l.name, l.address,
+            i.status, i.total_amount, i.notes, i.created_at, i.sent_at, i.error_message,
+            (SELECT COUNT(*) FROM invoice_items WHERE invoice_id = i.id),
+            p.scadenta_la_vanzare, i.invoice_number, i.invoice_series
+        FROM invoices i
+        JOIN partners p ON i.partner_id = p.id
+        JOIN locations l ON i.location_id = l.id
+        WHERE i.id = ?1
+        "#,
+        [invoice_id],
+        |row| {
+            Ok(Invoice {
+                id: row.get(0)?,
+                partner_id: row.get(1)?,
+                partner_name: row.get(2)?,
+                partner_cif: row.get(3)?,
+                partner_reg_com: row.get(4)?,
+                location_id: row.get(5)?,
+                location_name: row.get(6)?,
+                location_address: row.get(7)?,
+                status: InvoiceStatus::from(row.get::<_, String>(8)?),
+                total_amount: row.get(9)?,
+                notes: row.get(10)?,
+                created_at: row.get(11)?,
+                sent_at: row.get(12)?,
+                error_message: row.get(13)?,
+                item_count: row.get(14)?,
+                partner_payment_term: row.get(15)?,
+                invoice_number: row.get(16)?,
+                invoice_series: row.get(17)?,
+            })
+        },
+    )
+    .map_err(|e| format!("Factura nu a fost găsită: {}", e))
+}
+
+/// Cancels an invoice instead of deleting it: the number stays consumed from
+/// the carnet and the invoice prints as "ANULAT". An invoice already sent to
+/// WME is always sent again with `Anulat = "DA"`; a local-only one only when
+/// `send_to_wme` is set. Calling it again on a cancelled invoice retries a
+/// cancellation that did not reach WME.
+#[tauri::command]
+pub async fn cancel_invoice(
+    db: State<'_, Database>,
+    invoice_id: String,
+    reason: Option<String>,
+    send_to_wme: Option<bool>,
+) -> Result<Invoice, String> {
+    let reason = reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .unwrap_or_else(|| "Anulată de agent".to_string());
+
+    let must_send = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+        let (status, sent_at, cancel_sent_at): (String, Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT status, sent_at, cancel_sent_at FROM invoices WHERE id = ?1",
+                [&invoice_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|e| format!("Factura nu a fost găsită: {}", e))?;
+
+        if status == "sending" {
+            return Err("Factura se trimite acum. Anulați întâi trimiterea.".to_string());
+        }
+
+        if status == "cancelled" {
+            if cancel_sent_at.is_some() {
+                return Err("Factura este deja anulată și în WME.".to_string());
+            }
+        } else {
+            let now = Utc::now().to_rfc3339();
+            conn.execute(
+                "UPDATE invoices SET status = 'cancelled', cancelled_at = ?1, cancel_reason = ?2, error_message = ?3 WHERE id = ?4",
+                rusqlite::params![&now, &reason, format!("Anulată: {}", reason), &invoice_id],
+            )
+            .map_err(|e| e.to_string())?;
+            info!("[INVOICE][CANCEL] Invoice {} cancelled: {}", invoice_id, reason);
+        }
+
+        sent_at.is_some() || send_to_wme.unwrap_or(false)
+    };
+
+    if must_send {
+        let wme_request = build_wme_invoice_request(&db, &invoice_id, "DA");
+
+        // Create the ApiClient in a separate block to ensure MutexGuard is dropped before await
+        let result = match wme_request {
+            Ok(request) => {
+                let api_client_result = {
+                    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                    get_wme_api_client(&conn)
+                };
+                match api_client_result {
+                    Ok(client) => client.send_invoice_to_wme(request).await.map(|_| ()),
+                    Err(e) => Err(format!("Failed to create API client: {}", e)),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        match result {
+            Ok(()) => {
+                conn.execute(
+                    "UPDATE invoices SET cancel_sent_at = ?1, error_message = ?2 WHERE id = ?3",
+                    rusqlite::params![Utc::now().to_rfc3339(), format!("Anulată în WME: {}", reason), &invoice_id],
+                )
+                .map_err(|e| e.to_string())?;
+                info!("[INVOICE][CANCEL] Cancellation of {} sent to WME", invoice_id);
+            }
+            Err(e) => {
+                warn!("[INVOICE][CANCEL] Failed to send cancellation of {} to WME: {}", invoice_id, e);
+                conn.execute(
+                    "UPDATE invoices SET error_message = ?1 WHERE id = ?2",
+                    rusqlite::params![format!("Anulată local, netrimisă în WME: {}", e), &invoice_id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    load_invoice(&conn, &invoice_id)
+}
+
+/// Restores a cancelled invoice to pending, as long as neither the invoice nor
+/// its cancellation ever reached WME.
+#[tauri::command]
+pub fn restore_invoice(db: State<'_, Database>, invoice_id: String) -> Result<Invoice, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    let affected = conn
+        .execute(
+            "UPDATE invoices SET status = 'pending', cancelled_at = NULL, cancel_reason = NULL, error_message = NULL \
+             WHERE id = ?1 AND status = 'cancelled' AND sent_at IS NULL AND cancel_sent_at IS NULL",
+            [&invoice_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    if affected == 0 {
+        return Err("Doar facturile anulate care nu au ajuns în WME pot fi restaurate.".to_string());
+    }
+
+    info!("[INVOICE][CANCEL] Invoice {} restored", invoice_id);
+    load_invoice(&conn, &invoice_id)
+}
+
+// ==================== PRINT COMMANDS ====================
+
+/// Installed printers; empty when there are none, and documents then go to
+/// the system's default printer.
+#[tauri::command]
+pub fn get_available_printers() -> Result<Vec<String>, String> {
+    #[cfg(target_os = "windows")]
+    {
+        // 1. Try WMIC first (Much faster than PowerShell)
         // wmic printer get name
         let wmic_output = std::process::Command::new("wmic")
             .args(&["printer", "get", "name"])
@@ -3349,161 +3471,190 @@ pub fn get_available_printers() -> Result<Vec<String>, String> {
             .map_err(|e| format!("Failed to get printers: {}", e))?;
 
         if !output.status.success() {
-            return Ok(vec!["Default".to_string()]);
+            return Ok(Vec::new());
         }
 
-        let printers: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        Ok(String::from_utf8_lossy(&output.stdout)
             .lines()
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty())
-            .collect();
-
-        Ok(if printers.is_empty() {
-            vec!["Default".to_string()]
-        } else {
-            printers
-        })
+            .collect())
     }
 
     #[cfg(not(target_os = "windows"))]
     {
-        Ok(vec!["Default".to_string()])
+        Ok(printing::cups_printers().unwrap_or_else(|e| {
+            warn!("[PRINT] {}", e);
+            Vec::new()
+        }))
     }
 }
 
-#[tauri::command]
-pub async fn print_invoice_to_html(
-    db: State<'_, Database>,
-    invoice_id: String,
-    printer_name: Option<String>,
-) -> Result<String, String> {
-    let (
-        invoice,
-        items,
-        payment_days,
-        delegate_name,
-        delegate_act,
-        carnet_series,
-        car_number,
-        invoice_number,
-    ) = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+/// Everything an invoice layout needs, loaded in one go for the HTML, PDF and
+/// thermal printouts.
+struct InvoicePrintData {
+    invoice: Invoice,
+    items: Vec<InvoiceItem>,
+    invoice_number: i64,
+    payment_days: i64,
+    delegate_name: Option<String>,
+    delegate_act: Option<String>,
+    carnet_series: String,
+    car_number: Option<String>,
+    /// Successful prints so far; a new print is a duplicate
+    print_count: i64,
+}
 
-        // Get invoice number first
-        let invoice_number: i64 = conn
-            .query_row(
-                "SELECT invoice_number FROM invoices WHERE id = ?1",
-                [&invoice_id],
-                |row| row.get(0),
-            )
-            .map_err(|e| format!("Invoice not found: {}", e))?;
+impl InvoicePrintData {
+    fn details(&self, duplicate: bool) -> print_invoice::InvoiceDetails<'_> {
+        print_invoice::InvoiceDetails {
+            series: &self.carnet_series,
+            number: self.invoice_number,
+            payment_term_days: self.payment_days,
+            delegate_name: self.delegate_name.as_deref(),
+            delegate_act: self.delegate_act.as_deref(),
+            car_number: self.car_number.as_deref(),
+            duplicate,
+        }
+    }
+}
 
-        // Fetch invoice details and payment term
-        let (invoice, payment_term_days) = get_invoice_for_print(&conn, &invoice_id)?;
+fn load_invoice_print_data(conn: &rusqlite::Connection, invoice_id: &str) -> Result<InvoicePrintData, String> {
+    let invoice_id = invoice_id.to_string();
 
-        info!("📅 Payment term retrieved from database for partner '{}': {:?}", invoice.partner_name, payment_term_days);
+    // Get invoice number first
+    let (invoice_number, print_count): (i64, i64) = conn
+        .query_row(
+            "SELECT invoice_number, COALESCE(print_count, 0) FROM invoices WHERE id = ?1",
+            [&invoice_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| format!("Invoice not found: {}", e))?;
 
-        // Get agent settings for delegate info
-        let agent_settings_result = conn.query_row(
-            "SELECT delegate_name, delegate_act FROM agent_settings WHERE id = 1",
-            [],
-            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)),
-        );
+    // Fetch invoice details and payment term
+    let (invoice, payment_term_days) = get_invoice_for_print(conn, &invoice_id)?;
 
-        let (delegate_name, delegate_act) = agent_settings_result.unwrap_or((None, None));
+    info!("📅 Payment term retrieved from database for partner '{}': {:?}", invoice.partner_name, payment_term_days);
 
-        // Fetch invoice items
-        let mut stmt = conn
-            .prepare(
-                r#"
-                SELECT ii.id, ii.product_id, p.name, ii.quantity, ii.unit_price, p.unit_of_measure, ii.total_price, p.procent_tva
-                FROM invoice_items ii
-                JOIN products p ON ii.product_id = p.id
-                WHERE ii.invoice_id = ?1
-                "#,
-            )
-            .map_err(|e| e.to_string())?;
+    // Get agent settings for delegate info
+    let agent_settings_result = conn.query_row(
+        "SELECT delegate_name, delegate_act FROM agent_settings WHERE id = 1",
+        [],
+        |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)),
+    );
 
-        let items: Vec<InvoiceItem> = stmt
-            .query_map([&invoice_id], |row| {
-                // Parse TVA percentage from TEXT to f64
-                let tva_percent: Option<f64> = match row.get::<_, Option<String>>(7)? {
-                    Some(s) => s.parse::<f64>().ok(),
-                    None => None,
-                };
+    let (delegate_name, delegate_act) = agent_settings_result.unwrap_or((None, None));
 
-                Ok(InvoiceItem {
-                    id: row.get(0)?,
-                    invoice_id: invoice_id.clone(),
-                    product_id: row.get(1)?,
-                    product_name: row.get(2)?,
-                    quantity: row.get(3)?,
-                    unit_price: row.get(4)?,
-                    unit_of_measure: row.get(5)?,
-                    total_price: row.get(6)?,
-                    tva_percent,
-                })
-            })
-            .map_err(|e| e.to_string())?
-            .filter_map(|r| r.ok())
-            .collect();
+    // Fetch invoice items
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT ii.id, ii.product_id, p.name, ii.quantity, ii.unit_price, p.unit_of_measure, ii.total_price, p.procent_tva
+            FROM invoice_items ii
+            JOIN products p ON ii.product_id = p.id
+            WHERE ii.invoice_id = ?1
+            "#,
+        )
+        .map_err(|e| e.to_string())?;
 
-        // Use partner's payment term or default to 30 days
-        let payment_days = payment_term_days.unwrap_or(30);
+    let items: Vec<InvoiceItem> = stmt
+        .query_map([&invoice_id], |row| {
+            // Parse TVA percentage from TEXT to f64
+            let tva_percent: Option<f64> = match row.get::<_, Option<String>>(7)? {
+                Some(s) => s.parse::<f64>().ok(),
+                None => None,
+            };
 
-        info!(
-            "📅 Using payment term: {} days (partner: '{}', retrieved: {:?}, final: {})",
-            payment_days, invoice.partner_name, payment_term_days, payment_days
-        );
+            Ok(InvoiceItem {
+                id: row.get(0)?,
+                invoice_id: invoice_id.clone(),
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                quantity: row.get(3)?,
+                unit_price: row.get(4)?,
+                unit_of_measure: row.get(5)?,
+                total_price: row.get(6)?,
+                tva_percent,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
 
-        // Get carnet series from agent settings
-        let carnet_series = conn
-            .query_row(
-                "SELECT carnet_series FROM agent_settings WHERE id = 1",
-                [],
-                |row| row.get::<_, Option<String>>(0),
-            )
-            .ok()
-            .flatten()
-            .unwrap_or_else(|| "FACTURA".to_string());
+    // Use partner's payment term or default to 30 days
+    let payment_days = payment_term_days.unwrap_or(30);
 
-        // Get car number from agent settings
-        let car_number = conn
-            .query_row(
-                "SELECT car_number FROM agent_settings WHERE id = 1",
-                [],
-                |row| row.get::<_, Option<String>>(0),
-            )
-            .ok()
-            .flatten();
+    info!(
+        "📅 Using payment term: {} days (partner: '{}', retrieved: {:?}, final: {})",
+        payment_days, invoice.partner_name, payment_term_days, payment_days
+    );
 
-        (
-            invoice,
-            items,
-            payment_days,
-            delegate_name,
-            delegate_act,
-            carnet_series,
-            car_number,
-            invoice_number,
+    // The series the invoice was numbered in; the carnet in use may have changed since
+    let settings_series = conn
+        .query_row(
+            "SELECT carnet_series FROM agent_settings WHERE id = 1",
+            [],
+            |row| row.get::<_, Option<String>>(0),
         )
-    };
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "FACTURA".to_string());
+    let carnet_series = invoice_carnet_series(invoice.invoice_series.as_deref(), Some(&settings_series));
 
-    // Read logo and convert to base64
-    let logo_base64 = read_logo_to_base64();
+    // Get car number from agent settings
+    let car_number = conn
+        .query_row(
+            "SELECT car_number FROM agent_settings WHERE id = 1",
+            [],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .ok()
+        .flatten();
 
-    // Generate HTML
-    let html = print_invoice::generate_invoice_html(
-        &invoice,
-        &items,
+    Ok(InvoicePrintData {
+        invoice,
+        items,
         invoice_number,
-        logo_base64.as_deref(),
         payment_days,
-        delegate_name.as_deref(),
-        delegate_act.as_deref(),
-        car_number.as_deref(),
-        &carnet_series
+        delegate_name,
+        delegate_act,
+        carnet_series,
+        car_number,
+        print_count,
+    })
+}
+
+/// Writes the invoice HTML and PDF, plus the ESC/POS bytes when it goes to a
+/// thermal printer, and returns (PDF path, file to print). An invoice that
+/// was printed before is marked DUPLICAT.
+fn write_invoice_print_files(
+    conn: &rusqlite::Connection,
+    invoice_id: &str,
+    destination: &mut printing::Destination,
+) -> Result<(String, String), String> {
+    let data = load_invoice_print_data(conn, invoice_id)?;
+    let issuer = company::invoice_issuer(conn, invoice_id);
+    let duplicate = data.print_count > 0;
+    let code = invoice_code(conn, &data, &issuer, codes::Kind::Invoice);
+
+    // Logo, stamp and codes as data URLs
+    let mut images = template_images(&issuer);
+    code.add_to(&mut images);
+
+    // Generate HTML
+    let html = print_invoice::generate_invoice_html(
+        &data.invoice,
+        &data.items,
+        &issuer.profile,
+        &images,
+        data.details(duplicate),
     );
+    let html = if data.invoice.status == InvoiceStatus::Cancelled {
+        print_invoice::mark_cancelled(html)
+    } else {
+        html
+    };
+    let html = if duplicate { print_invoice::mark_duplicate(html) } else { html };
 
     // Save to invoices folder in AppData
     let app_data_dir = dirs::config_dir()
@@ -3520,201 +3671,75 @@ pub async fn print_invoice_to_html(
     std::fs::write(&html_file_path, &html)
         .map_err(|e| format!("Failed to write HTML file: {}", e))?;
 
+    let pdf_bytes = print_invoice::generate_invoice_pdf(
+        &data.invoice,
+        &data.items,
+        &issuer.profile,
+        read_logo_image(&issuer.logo).as_ref(),
+        &code,
+        data.details(duplicate),
+    );
+    std::fs::write(&pdf_file_path, &pdf_bytes)
+        .map_err(|e| format!("Failed to write PDF file: {}", e))?;
+
     let html_path_str = html_file_path.to_string_lossy().to_string();
     let pdf_path_str = pdf_file_path.to_string_lossy().to_string();
 
     info!("Generated invoice HTML at: {}", html_path_str);
+    info!("Generated invoice PDF at: {} ({} bytes)", pdf_path_str, pdf_bytes.len());
 
-    // Convert HTML to PDF using Edge (headless)
-    #[cfg(target_os = "windows")]
-    {
-        // Try to generate PDF using available tools
-        let mut pdf_generated = false;
-        let mut print_file = html_path_str.clone();
-
-        // Try Edge first (Windows 10+)
-        let edge_paths = vec![
-            "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe",
-            "C:\\Program Files\\Microsoft\\Edge\\Application\\msedge.exe",
-        ];
-
-        for edge_path in edge_paths {
-            if std::path::Path::new(edge_path).exists() {
-                let file_url = format!("file:///{}", html_path_str.replace('\\', "/"));
-
-                let output = std::process::Command::new(edge_path)
-                    .args(&[
-                        "--headless",
-                        "--disable-gpu",
-                        "--no-sandbox",
-                        "--disable-dev-shm-usage",
-                        &format!("--print-to-pdf={}", pdf_path_str),
-                        &file_url,
-                    ])
-                    .output();
-
-                match output {
-                    Ok(result) => {
-                        info!("Edge command executed. Status: {}", result.status);
-                        if !result.stderr.is_empty() {
-                            let stderr = String::from_utf8_lossy(&result.stderr);
-                            info!("Edge stderr: {}", stderr);
-                        }
+    if destination.backend != printing::Backend::EscPos {
+        return Ok((pdf_path_str.clone(), pdf_path_str));
+    }
 
-                        // Give Edge time to write the file (poll until fully written)
-                        let mut waited = 0;
-                        while waited < 5000 {
-                            if wait_for_file_ready(&pdf_path_str, 1000, 300) {
-                                pdf_generated = true;
-                                print_file = pdf_path_str.clone();
-                                info!("PDF generated successfully at: {}", pdf_path_str);
-                                break;
-                            }
-                            std::thread::sleep(std::time::Duration::from_millis(100));
-                            waited += 100;
-                        }
-                        if pdf_generated {
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        info!("Failed to use Edge: {}", e);
-                    }
+    let options = escpos_destination(conn, destination)?;
+    let bytes = invoice_escpos(&data, &issuer, &code, &options, duplicate);
+    let escpos_path_str = write_escpos_file("invoices", &format!("factura_{}", invoice_id), &bytes)?;
+    Ok((pdf_path_str, escpos_path_str))
+}
+
+#[tauri::command]
+pub async fn print_invoice_to_html(
+    db: State<'_, Database>,
+    invoice_id: String,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    let (destination, pdf_path_str, print_file, car_number) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let mut destination = printing::destination(&conn, printing::INVOICE, printer_name.as_deref());
+        let (pdf_path_str, print_file) = write_invoice_print_files(&conn, &invoice_id, &mut destination)?;
+        let car_number: Option<String> = conn
+            .query_row("SELECT car_number FROM agent_settings WHERE id = 1", [], |row| row.get(0))
+            .ok()
+            .flatten();
+        (destination, pdf_path_str, print_file, car_number)
+    };
+
+    print_jobs::submit(&db, &destination, &invoice_id, &print_file)?;
+
+    // The quality certificate follows the invoice, on its own route
+    let cert_destination = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        printing::destination(&conn, printing::QUALITY_CERTIFICATE, printer_name.as_deref())
+    };
+    if cert_destination.copies == 0 {
+        info!("[CERT][PRINT] Certificate routed with 0 copies, not printed: {}", invoice_id);
+    } else if cert_destination.backend == printing::Backend::EscPos {
+        warn!("[CERT][PRINT] The certificate has no thermal layout, not printed on {}", cert_destination.label());
+    } else {
+        let cert_car_number = car_number.unwrap_or_default();
+        match save_invoice_certificate_file(&db, &invoice_id, cert_car_number.as_str()).await {
+            Ok((_cert_html_path, _cert_pdf_path, cert_print_file)) => {
+                if let Err(e) = print_jobs::submit(&db, &cert_destination, &invoice_id, &cert_print_file) {
+                    warn!("Certificate print failed: {}", e);
                 }
             }
+            Err(e) => warn!("Certificate generation/print skipped: {}", e),
         }
+    }
 
-        // If PDF generation failed, use HTML directly for printing
-        if !pdf_generated {
-            info!("PDF generation failed, will print HTML directly");
-            print_file = html_path_str.clone();
-        }
-
-        // Print PDF using SumatraPDF
-        let printer = printer_name.unwrap_or_else(|| String::from(""));
-
-        // Check standard installation paths first
-        let user_profile = std::env::var("USERPROFILE").unwrap_or_default();
-
-        // Also check bundled resources path
-        let bundled_path = std::env::current_exe()
-            .ok()
-            .and_then(|exe| exe.parent().map(|p| p.join("resources").join("SumatraPDF.exe")));
-
-        let mut sumatra_paths = vec![
-            format!(r"{}\AppData\Local\SumatraPDF\SumatraPDF.exe", user_profile),
-            r"C:\Program Files\SumatraPDF\SumatraPDF.exe".to_string(),
-            r"C:\Program Files (x86)\SumatraPDF\SumatraPDF.exe".to_string(),
-        ];
-
-        // Add bundled path if exists
-        if let Some(bundled) = bundled_path {
-            sumatra_paths.insert(0, bundled.to_string_lossy().to_string());
-        }
-
-        let mut sumatra_exe = None;
-        for path in &sumatra_paths {
-            if std::path::Path::new(path).exists() {
-                sumatra_exe = Some(path.to_string());
-                info!("Found SumatraPDF at: {}", path);
-                break;
-            }
-        }
-
-        // If not in standard paths, check portable version in app data
-        if sumatra_exe.is_none() {
-            let app_data_dir = dirs::data_dir()
-                .ok_or("Could not get app data directory")?
-                .join("facturi.softconsulting.com");
-            let sumatra_portable = app_data_dir.join("tools").join("SumatraPDF.exe");
-
-            if sumatra_portable.exists() {
-                sumatra_exe = Some(sumatra_portable.to_string_lossy().to_string());
-                info!("Found portable SumatraPDF");
-            }
-        }
-
-        // Use SumatraPDF for printing
-        if let Some(sumatra_path) = sumatra_exe {
-            info!("Printing to '{}' using SumatraPDF", printer);
-
-            let mut invoice_args: Vec<String> = Vec::new();
-            if printer.trim().is_empty() {
-                invoice_args.push("-print-to-default".to_string());
-            } else {
-                invoice_args.push("-print-to".to_string());
-                invoice_args.push(printer.clone());
-            }
-            invoice_args.extend([
-                "-print-settings".to_string(),
-                "noscale".to_string(),
-                print_file.clone(),
-                "-silent".to_string(),
-                "-exit-when-done".to_string(),
-                "-exit-on-print".to_string(),
-            ]);
-
-            match std::process::Command::new(&sumatra_path).args(&invoice_args).spawn() {
-                Ok(_) => info!("Invoice print job sent successfully to printer '{}': {}", printer, invoice_id),
-                Err(e) => warn!("Invoice SumatraPDF print failed: {}", e),
-            }
-
-            let cert_car_number = car_number.clone().unwrap_or_default();
-            match save_invoice_certificate_file(&db, &invoice_id, cert_car_number.as_str()).await {
-                Ok((_cert_html_path, _cert_pdf_path, cert_print_file)) => {
-                    std::thread::sleep(std::time::Duration::from_millis(400));
-
-                    let mut cert_args: Vec<String> = Vec::new();
-                    if printer.trim().is_empty() {
-                        cert_args.push("-print-to-default".to_string());
-                    } else {
-                        cert_args.push("-print-to".to_string());
-                        cert_args.push(printer.clone());
-                    }
-                    cert_args.extend([
-                        "-print-settings".to_string(),
-                        "noscale".to_string(),
-                        cert_print_file.clone(),
-                        "-silent".to_string(),
-                        "-exit-when-done".to_string(),
-                        "-exit-on-print".to_string(),
-                    ]);
-
-                    match std::process::Command::new(&sumatra_path).args(&cert_args).spawn() {
-                        Ok(_) => info!("Certificate print job sent successfully to printer '{}': {}", printer, invoice_id),
-                        Err(e) => warn!("Certificate SumatraPDF print failed: {}", e),
-                    }
-                }
-                Err(e) => warn!("Certificate generation/print skipped: {}", e),
-            }
-        } else {
-            info!("SumatraPDF not found. PDF saved at: {}", print_file);
-        }
-
-        let file_type = if pdf_generated { "PDF" } else { "HTML" };
-        info!("Print dispatched ({}) to printer '{}': {}", file_type, printer, invoice_id);
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        // Use macOS print command on PDF or HTML
-        std::process::Command::new("lp")
-            .arg(&html_path_str)
-            .spawn()
-            .ok();
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        // Use Linux print command on PDF or HTML
-        Command::new("lp")
-            .arg(&html_path_str)
-            .spawn()
-            .map_err(|e| format!("Failed to print: {}", e))?;
-    }
-
-    Ok(pdf_path_str)
-}
+    Ok(pdf_path_str)
+}
 
 #[tauri::command]
 pub async fn print_invoice_certificate(
@@ -3722,96 +3747,25 @@ pub async fn print_invoice_certificate(
     invoice_id: String,
     printer_name: Option<String>,
 ) -> Result<String, String> {
-    let car_number = {
+    let (car_number, mut destination) = {
         let conn = db.conn.lock().map_err(|e| e.to_string())?;
-        conn.query_row(
+        let car_number = conn.query_row(
             "SELECT COALESCE(car_number, '') FROM agent_settings WHERE id = 1",
             [],
             |row| row.get::<_, String>(0),
-        ).unwrap_or_default()
+        ).unwrap_or_default();
+        (car_number, printing::destination(&conn, printing::QUALITY_CERTIFICATE, printer_name.as_deref()))
     };
-    let (_html_path, _pdf_path, target) = save_invoice_certificate_file(&db, &invoice_id, &car_number).await?;
-    let print_file = target.clone();
-    let _is_pdf = print_file.ends_with(".pdf");
-
-    info!("[CERT][PRINT] Printing certificate for invoice {} (file: {})", invoice_id, print_file);
-
-    #[cfg(target_os = "windows")]
-    {
-        let printer = printer_name.unwrap_or_default();
-        let user_profile = std::env::var("USERPROFILE").unwrap_or_default();
-        let bundled_path = std::env::current_exe()
-            .ok()
-            .and_then(|exe| exe.parent().map(|p| p.join("resources").join("SumatraPDF.exe")));
-
-        let mut sumatra_paths = vec![
-            format!(r"{}\AppData\Local\SumatraPDF\SumatraPDF.exe", user_profile),
-            r"C:\Program Files\SumatraPDF\SumatraPDF.exe".to_string(),
-            r"C:\Program Files (x86)\SumatraPDF\SumatraPDF.exe".to_string(),
-        ];
-
-        if let Some(bundled) = bundled_path {
-            sumatra_paths.insert(0, bundled.to_string_lossy().to_string());
-        }
-
-        let mut sumatra_exe = None;
-        for path in &sumatra_paths {
-            if std::path::Path::new(path).exists() {
-                sumatra_exe = Some(path.to_string());
-                break;
-            }
-        }
-
-        if sumatra_exe.is_none() {
-            let app_data_dir = dirs::data_dir()
-                .ok_or("Could not get app data directory")?
-                .join("facturi.softconsulting.com");
-            let sumatra_portable = app_data_dir.join("tools").join("SumatraPDF.exe");
-            if sumatra_portable.exists() {
-                sumatra_exe = Some(sumatra_portable.to_string_lossy().to_string());
-            }
-        }
-
-        if let Some(sumatra_path) = sumatra_exe {
-            let mut command = std::process::Command::new(&sumatra_path);
-
-            if printer.trim().is_empty() {
-                command.arg("-print-to-default");
-            } else {
-                command.arg("-print-to").arg(&printer);
-            }
-
-            command
-                .arg("-print-settings")
-                .arg("noscale")
-                .arg(&print_file)
-                .arg("-silent")
-                .arg("-exit-when-done")
-                .arg("-exit-on-print")
-                .spawn()
-                .map_err(|e| format!("Failed to start print with SumatraPDF: {}", e))?;
-
-            info!("[CERT][PRINT] Sent to SumatraPDF");
-        } else {
-            return Err("SumatraPDF not found. Instalează SumatraPDF sau configurează calea aplicației de printare.".to_string());
-        }
+    if destination.backend == printing::Backend::EscPos {
+        return Err("Certificatul de calitate nu poate fi printat pe imprimanta termică. Alegeți altă imprimantă pentru certificate.".to_string());
     }
+    // Asked for explicitly: a route with 0 copies only stops the automatic print
+    destination.copies = destination.copies.max(1);
 
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("lp")
-            .arg(&print_file)
-            .spawn()
-            .ok();
-    }
+    let (_html_path, _pdf_path, print_file) = save_invoice_certificate_file(&db, &invoice_id, &car_number).await?;
 
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("lp")
-            .arg(&print_file)
-            .spawn()
-            .map_err(|e| format!("Failed to print certificate: {}", e))?;
-    }
+    info!("[CERT][PRINT] Printing certificate for invoice {} (file: {})", invoice_id, print_file);
+    print_jobs::submit(&db, &destination, &invoice_id, &print_file)?;
 
     Ok(print_file)
 }
@@ -3835,15 +3789,39 @@ pub async fn preview_invoice_certificate(
         Ok(target)
 }
 
-#[tauri::command]
-pub async fn print_collection_to_html(
-    db: State<'_, Database>,
-    collection_id: String,
-    printer_name: Option<String>,
-) -> Result<String, String> {
-    info!("[CHITANTE][PRINT] Start print_collection_to_html for collection_id={} printer={:?}", collection_id, printer_name);
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+/// A receipt (all rows of its group merged) with the values its layouts
+/// print, for the HTML, PDF and thermal printouts.
+struct ReceiptPrintData {
+    collection: Collection,
+    doc_series: String,
+    doc_number: String,
+    issue_date: String,
+    agent_display: String,
+    nume_casa: Option<String>,
+    partner_cui: Option<String>,
+    partner_reg_com: Option<String>,
+    partner_address: Option<String>,
+    partner_localitate: Option<String>,
+    partner_judet: Option<String>,
+}
+
+impl ReceiptPrintData {
+    fn details(&self) -> print_receipt::ReceiptDetails<'_> {
+        print_receipt::ReceiptDetails {
+            series: &self.doc_series,
+            number: &self.doc_number,
+            issue_date: &self.issue_date,
+            agent: Some(self.agent_display.as_str()),
+            partner_address: self.partner_address.as_deref(),
+            partner_localitate: self.partner_localitate.as_deref(),
+            partner_judet: self.partner_judet.as_deref(),
+            partner_cui: self.partner_cui.as_deref(),
+            partner_reg_com: self.partner_reg_com.as_deref(),
+        }
+    }
+}
 
+fn load_receipt_print_data(conn: &rusqlite::Connection, collection_id: &str) -> Result<ReceiptPrintData, String> {
     // Check if this collection is part of a group
     let receipt_group_id: Option<String> = conn
         .query_row(
@@ -3853,6 +3831,14 @@ pub async fn print_collection_to_html(
         )
         .map_err(|e| format!("Collection not found: {}", e))?;
 
+    let payment = payment_methods::of_collection(conn, collection_id).map_err(|e| e.to_string())?;
+    if !payment_methods::is_cash(&payment.method) {
+        return Err(format!(
+            "Încasarea prin {} nu are chitanță de printat",
+            payment_methods::label(&payment.method)
+        ));
+    }
+
     let query = if receipt_group_id.is_some() {
         "SELECT
             id,
@@ -3895,7 +3881,7 @@ pub async fn print_collection_to_html(
          ORDER BY created_at DESC"
     };
 
-    let param = if let Some(gid) = &receipt_group_id { gid } else { &collection_id };
+    let param = if let Some(gid) = &receipt_group_id { gid.as_str() } else { collection_id };
 
     let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
     let rows = stmt
@@ -3951,8 +3937,11 @@ pub async fn print_collection_to_html(
         .filter(|s| !s.is_empty())
         .collect();
 
+    // Rows without an invoice are the advance on the receipt
     let invoice_ref_str = if invoice_refs.is_empty() {
         "Avans".to_string()
+    } else if invoice_refs.len() < loaded.len() {
+        format!("{}, Avans", invoice_refs.join(", "))
     } else {
         invoice_refs.join(", ")
     };
@@ -3974,6 +3963,7 @@ pub async fn print_collection_to_html(
         created_at: first.14.clone(),
         receipt_series: first.2.clone(),
         receipt_number: first.3.clone(),
+        payment,
     };
 
     info!(
@@ -4022,143 +4012,66 @@ pub async fn print_collection_to_html(
         .unwrap_or_else(|| "N/A".to_string());
 
     let (partner_cui, partner_reg_com, partner_address, partner_localitate, partner_judet) =
-        get_partner_receipt_info(&conn, &collection.id_partener);
-
-    let (html_path_str, pdf_path_str) = save_receipt_html_file(
-        &collection,
-        &doc_series,
-        &doc_number,
-        &issue_date,
-        Some(agent_display.as_str()),
-        nume_casa.as_deref().unwrap_or("CASA LEI"),
-        partner_address.as_deref(),
-        partner_localitate.as_deref(),
-        partner_judet.as_deref(),
-        partner_cui.as_deref(),
-        partner_reg_com.as_deref(),
-        &collection_id,
-    )?;
-
-    #[cfg(target_os = "windows")]
-    {
-        let mut pdf_generated = false;
-        let mut print_file = html_path_str.clone();
-
-        let edge_paths = vec![
-            "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe",
-            "C:\\Program Files\\Microsoft\\Edge\\Application\\msedge.exe",
-        ];
-
-        for edge_path in edge_paths {
-            if std::path::Path::new(edge_path).exists() {
-                let file_url = format!("file:///{}", html_path_str.replace('\\', "/"));
-
-                let output = std::process::Command::new(edge_path)
-                    .args(&[
-                        "--headless",
-                        "--disable-gpu",
-                        "--no-sandbox",
-                        "--disable-dev-shm-usage",
-                        &format!("--print-to-pdf={}", pdf_path_str),
-                        &file_url,
-                    ])
-                    .output();
-
-                if let Ok(result) = output {
-                    info!("Receipt Edge command executed. Status: {}", result.status);
-                    let mut waited = 0;
-                    while waited < 5000 {
-                        if wait_for_file_ready(&pdf_path_str, 1000, 300) {
-                            pdf_generated = true;
-                            print_file = pdf_path_str.clone();
-                            break;
-                        }
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                        waited += 100;
-                    }
-                    if pdf_generated {
-                        break;
-                    }
-                }
-            }
-        }
-
-        if !pdf_generated {
-            print_file = html_path_str.clone();
-        }
-
-        let printer = printer_name.unwrap_or_default();
-        let user_profile = std::env::var("USERPROFILE").unwrap_or_default();
-        let bundled_path = std::env::current_exe()
-            .ok()
-            .and_then(|exe| exe.parent().map(|p| p.join("resources").join("SumatraPDF.exe")));
-
-        let mut sumatra_paths = vec![
-            format!(r"{}\AppData\Local\SumatraPDF\SumatraPDF.exe", user_profile),
-            r"C:\Program Files\SumatraPDF\SumatraPDF.exe".to_string(),
-            r"C:\Program Files (x86)\SumatraPDF\SumatraPDF.exe".to_string(),
-        ];
-
-        if let Some(bundled) = bundled_path {
-            sumatra_paths.insert(0, bundled.to_string_lossy().to_string());
-        }
+        get_partner_receipt_info(conn, &collection.id_partener);
 
-        let mut sumatra_exe = None;
-        for path in &sumatra_paths {
-            if std::path::Path::new(path).exists() {
-                sumatra_exe = Some(path.to_string());
-                break;
-            }
-        }
-
-        if sumatra_exe.is_none() {
-            let app_data_dir = dirs::data_dir()
-                .ok_or("Could not get app data directory")?
-                .join("facturi.softconsulting.com");
-            let sumatra_portable = app_data_dir.join("tools").join("SumatraPDF.exe");
-            if sumatra_portable.exists() {
-                sumatra_exe = Some(sumatra_portable.to_string_lossy().to_string());
-            }
-        }
+    Ok(ReceiptPrintData {
+        collection,
+        doc_series,
+        doc_number,
+        issue_date,
+        agent_display,
+        nume_casa,
+        partner_cui,
+        partner_reg_com,
+        partner_address,
+        partner_localitate,
+        partner_judet,
+    })
+}
 
-        if let Some(sumatra_path) = sumatra_exe {
-            let mut command = std::process::Command::new(&sumatra_path);
+#[tauri::command]
+pub async fn print_collection_to_html(
+    db: State<'_, Database>,
+    collection_id: String,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    info!("[CHITANTE][PRINT] Start print_collection_to_html for collection_id={} printer={:?}", collection_id, printer_name);
+    let (destination, pdf_path_str, print_file) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let mut destination = printing::destination(&conn, printing::RECEIPT, printer_name.as_deref());
+
+        let receipt = load_receipt_print_data(&conn, &collection_id)?;
+        let issuer = company::issuer(&conn);
+        let code = receipt_code(
+            &conn,
+            &receipt.collection,
+            &receipt.doc_series,
+            &receipt.doc_number,
+            &receipt.issue_date,
+            &issuer,
+            &collection_id,
+        );
 
-            if printer.trim().is_empty() {
-                command.arg("-print-to-default");
-            } else {
-                command.arg("-print-to").arg(&printer);
-            }
+        let (_, pdf_path_str) = save_receipt_html_file(
+            &receipt.collection,
+            &issuer,
+            &code,
+            receipt.details(),
+            receipt.nume_casa.as_deref().unwrap_or("CASA LEI"),
+            &collection_id,
+        )?;
 
-            command
-                .arg("-print-settings")
-                .arg("noscale")
-                .arg(&print_file)
-                .arg("-silent")
-                .arg("-exit-when-done")
-                .arg("-exit-on-print")
-                .spawn()
-                .map_err(|e| format!("Failed to start print with SumatraPDF: {}", e))?;
+        let print_file = if destination.backend == printing::Backend::EscPos {
+            let options = escpos_destination(&conn, &mut destination)?;
+            let bytes = receipt_escpos(&receipt, &issuer, &code, &options);
+            write_escpos_file("receipts", &format!("chitanta_{}", collection_id), &bytes)?
         } else {
-            return Err("SumatraPDF not found. Instalează SumatraPDF sau configurează calea aplicației de printare.".to_string());
-        }
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("lp")
-            .arg(&html_path_str)
-            .spawn()
-            .ok();
-    }
+            pdf_path_str.clone()
+        };
+        (destination, pdf_path_str, print_file)
+    };
 
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("lp")
-            .arg(&html_path_str)
-            .spawn()
-            .map_err(|e| format!("Failed to print receipt: {}", e))?;
-    }
+    print_jobs::submit(&db, &destination, &collection_id, &print_file)?;
 
     Ok(pdf_path_str)
 }
@@ -4173,7 +4086,7 @@ fn get_invoice_for_print(
             i.id, i.partner_id, p.name, p.cif, p.reg_com, i.location_id, l.name, l.address,
             i.status, i.total_amount, i.notes, i.created_at, i.sent_at, i.error_message,
             (SELECT COUNT(*) FROM invoice_items WHERE invoice_id = i.id),
-            p.scadenta_la_vanzare
+            p.scadenta_la_vanzare, i.invoice_number, i.invoice_series
         FROM invoices i
         JOIN partners p ON i.partner_id = p.id
         JOIN locations l ON i.location_id = l.id
@@ -4198,8 +4111,8 @@ fn get_invoice_for_print(
                 error_message: row.get(13)?,
                 item_count: row.get(14)?,
                 partner_payment_term: None,
-                invoice_number: 0,
-                invoice_series: None,
+                invoice_number: row.get::<_, Option<i32>>(16)?.unwrap_or(0),
+                invoice_series: row.get(17)?,
             };
 
             // Parse scadenta_la_vanzare to i64 (days)
@@ -4219,2447 +4132,5074 @@ fn get_invoice_for_print(
     .map_err(|e| format!("Invoice not found: {}", e))
 }
 
-// ==================== AGENT SETTINGS COMMANDS ====================
-
-// Helper: Build an ApiClient from DB settings, returning a descriptive error if host is not set
-fn get_wme_api_client(conn: &rusqlite::Connection) -> Result<api_client::ApiClient, String> {
-    let (wme_host, wme_port): (Option<String>, Option<i64>) = conn
-        .query_row(
-            "SELECT wme_host, wme_port FROM agent_settings WHERE id = 1",
-            [],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .unwrap_or((None, None));
+// ==================== THERMAL PRINTER COMMANDS ====================
 
-    let host = wme_host
-        .as_deref()
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .ok_or_else(|| "Server WME neconfigurat. Mergi la Setări și completează adresa IP a serverului WME înainte de sincronizare.".to_string())?;
+fn load_thermal_printer_settings(conn: &rusqlite::Connection) -> ThermalPrinterSettings {
+    conn.query_row(
+        "SELECT thermal_printer_target, thermal_paper_width, thermal_code_page, thermal_code_page_table,
+                thermal_print_logo, thermal_cut, thermal_feed_lines
+         FROM agent_settings WHERE id = 1",
+        [],
+        |row| {
+            Ok(ThermalPrinterSettings {
+                target: row.get::<_, Option<String>>(0)?.filter(|value| !value.trim().is_empty()),
+                paper_width_mm: row.get::<_, Option<i64>>(1)?.unwrap_or(80),
+                code_page: row.get::<_, Option<String>>(2)?.unwrap_or_else(|| "cp852".to_string()),
+                code_page_table: row.get(3)?,
+                print_logo: row.get::<_, Option<i64>>(4)?.unwrap_or(1) != 0,
+                cut: row.get::<_, Option<i64>>(5)?.unwrap_or(1) != 0,
+                feed_lines: row.get::<_, Option<i64>>(6)?.unwrap_or(4),
+            })
+        },
+    )
+    .unwrap_or(ThermalPrinterSettings {
+        target: None,
+        paper_width_mm: 80,
+        code_page: "cp852".to_string(),
+        code_page_table: None,
+        print_logo: true,
+        cut: true,
+        feed_lines: 4,
+    })
+}
 
-    let port = wme_port
-        .and_then(|p| if p > 0 && p <= 65535 { Some(p as u16) } else { None })
-        .unwrap_or(8089);
+fn escpos_options(settings: &ThermalPrinterSettings) -> escpos::Options {
+    let code_page = escpos::CodePage::from_name(&settings.code_page);
+    escpos::Options {
+        paper_width_mm: if settings.paper_width_mm <= 58 { 58 } else { 80 },
+        code_page,
+        code_page_table: settings
+            .code_page_table
+            .map(|value| value.clamp(0, 255) as u8)
+            .unwrap_or_else(|| code_page.default_table()),
+        print_logo: settings.print_logo,
+        cut: settings.cut,
+        feed_lines: settings.feed_lines.clamp(0, 20) as u8,
+    }
+}
 
-    info!("Connecting to WME at {}:{}", host, port);
-    api_client::ApiClient::new(api_client::ApiConfig::from_settings(host, port))
-        .map_err(|e| format!("Failed to initialize API client: {}", e))
+/// Settings plus parsed target, or the error to show when the printer is not
+/// set up.
+fn thermal_printer(db: &State<'_, Database>) -> Result<(escpos::Options, escpos::Target), String> {
+    let settings = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        load_thermal_printer_settings(&conn)
+    };
+    let target = escpos::Target::parse(settings.target.as_deref().unwrap_or(""))?;
+    Ok((escpos_options(&settings), target))
 }
 
-#[tauri::command]
-pub fn get_agent_settings(db: State<'_, Database>) -> Result<AgentSettings, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+// Logo dithered for the paper width, decoded again only when the logo or the
+// width changes
+fn read_logo_raster(logo: &[u8], options: &escpos::Options) -> Option<escpos::Raster> {
+    static CACHE: Mutex<Option<(u64, usize, Option<escpos::Raster>)>> = Mutex::new(None);
 
-    let result = conn.query_row(
-        "SELECT agent_name, carnet_series, simbol_carnet_livr, simbol_gestiune_livrare, tip_contabil, cert_comanda_serie, cert_comanda_id_client, cod_carnet, cod_carnet_livr, cod_delegat, delegate_name, delegate_act, car_number, invoice_number_start, invoice_number_end, invoice_number_current, marca_agent, nume_casa, auto_sync_collections_enabled, auto_sync_collections_time, receipt_series, receipt_number_start, receipt_number_end, receipt_number_current, wme_host, wme_port FROM agent_settings WHERE id = 1",
-        [],
-        |row| {
-            let auto_sync_enabled: Option<i32> = row.get(18)?;
-            Ok(AgentSettings {
-                agent_name: row.get(0)?,
-                carnet_series: row.get(1)?,
-                simbol_carnet_livr: row.get(2)?,
-                simbol_gestiune_livrare: row.get(3)?,
-                tip_contabil: row.get(4)?,
-                cert_comanda_serie: row.get(5)?,
-                cert_comanda_id_client: row.get(6)?,
-                cod_carnet: row.get(7)?,
-                cod_carnet_livr: row.get(8)?,
-                cod_delegat: row.get(9)?,
-                delegate_name: row.get(10)?,
-                delegate_act: row.get(11)?,
-                car_number: row.get(12)?,
-                invoice_number_start: row.get(13)?,
-                invoice_number_end: row.get(14)?,
-                invoice_number_current: row.get(15)?,
-                marca_agent: row.get(16)?,
-                nume_casa: row.get(17)?,
-                auto_sync_collections_enabled: auto_sync_enabled.map(|v| v != 0),
-                auto_sync_collections_time: row.get(19)?,
-                receipt_series: row.get(20)?,
-                receipt_number_start: row.get(21)?,
-                receipt_number_end: row.get(22)?,
-                receipt_number_current: row.get(23)?,
-                wme_host: row.get(24)?,
-                wme_port: row.get::<_, Option<i64>>(25)?.map(|v| v as i32),
-            })
-        },
-    );
+    if !options.print_logo || logo.is_empty() {
+        return None;
+    }
 
-    match result {
-        Ok(settings) => Ok(settings),
-        Err(_) => Ok(AgentSettings {
-            agent_name: None,
-            carnet_series: None,
-            simbol_carnet_livr: None,
-            simbol_gestiune_livrare: None,
-            tip_contabil: Some("valoare".to_string()),
-            cert_comanda_serie: Some("CCAL".to_string()),
-            cert_comanda_id_client: Some("1602".to_string()),
-            cod_carnet: None,
-            cod_carnet_livr: None,
-            cod_delegat: None,
-            delegate_name: None,
-            delegate_act: None,
-            car_number: None,
-            invoice_number_start: Some(1),
-            invoice_number_end: Some(99999),
-            invoice_number_current: Some(1),
-            marca_agent: None,
-            nume_casa: None,
-            auto_sync_collections_enabled: Some(false),
-            auto_sync_collections_time: Some("23:00".to_string()),
-            receipt_series: None,
-            receipt_number_start: Some(1),
-            receipt_number_end: Some(99999),
-            receipt_number_current: Some(1),
-            wme_host: None,
-            wme_port: None,
-        }),
+    // Two thirds of the paper is enough for the logo
+    let max_width = options.dots() * 2 / 3;
+    let key = logo_fingerprint(logo);
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_key, cached_width, raster)) = cache.as_ref() {
+        if *cached_key == key && *cached_width == max_width {
+            return raster.clone();
+        }
     }
+
+    let raster = escpos::Raster::from_png(logo, max_width)
+        .map_err(|e| warn!("[ESCPOS] Could not load logo: {}", e))
+        .ok();
+    *cache = Some((key, max_width, raster.clone()));
+    raster
 }
 
 #[tauri::command]
-pub fn save_agent_settings(
-    db: State<'_, Database>,
-    agent_name: Option<String>,
-    carnet_series: Option<String>,
-    simbol_carnet_livr: Option<String>,
-    simbol_gestiune_livrare: Option<String>,
-    tip_contabil: Option<String>,
-    cert_comanda_serie: Option<String>,
-    cert_comanda_id_client: Option<String>,
-    cod_carnet: Option<String>,
-    cod_carnet_livr: Option<String>,
-    cod_delegat: Option<String>,
-    delegate_name: Option<String>,
-    delegate_act: Option<String>,
-    car_number: Option<String>,
-    invoice_number_start: Option<i64>,
-    invoice_number_end: Option<i64>,
-    invoice_number_current: Option<i64>,
-    marca_agent: Option<String>,
-    nume_casa: Option<String>,
-    auto_sync_collections_enabled: Option<bool>,
-    auto_sync_collections_time: Option<String>,
-    receipt_series: Option<String>,
-    receipt_number_start: Option<i64>,
-    receipt_number_end: Option<i64>,
-    receipt_number_current: Option<i64>,
-    wme_host: Option<String>,
-    wme_port: Option<i64>,
-) -> Result<AgentSettings, String> {
+pub fn get_thermal_printer_settings(db: State<'_, Database>) -> Result<ThermalPrinterSettings, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let now = chrono::Utc::now().to_rfc3339();
-
-    // Smart logic for invoice numbering:
-    // If invoice_number_start is provided and current is less than start, set current = start
-    let final_invoice_current = match (invoice_number_start, invoice_number_current) {
-        (Some(start), Some(current)) if current < start => {
-            info!("Auto-adjusting invoice_number_current from {} to {} (matching start)", current, start);
-            Some(start)
-        },
-        (Some(start), None) => {
-            info!("Initializing invoice_number_current to {} (start value)", start);
-            Some(start)
-        },
-        _ => invoice_number_current,
-    };
-
-    // Same logic for receipt numbering
-    let final_receipt_current = match (receipt_number_start, receipt_number_current) {
-        (Some(start), Some(current)) if current < start => {
-            info!("Auto-adjusting receipt_number_current from {} to {} (matching start)", current, start);
-            Some(start)
-        },
-        (Some(start), None) => {
-            info!("Initializing receipt_number_current to {} (start value)", start);
-            Some(start)
-        },
-        _ => receipt_number_current,
-    };
-
-    // Convert bool to i32 for SQLite
-    let auto_sync_enabled_int = auto_sync_collections_enabled.map(|v| if v { 1 } else { 0 });
-
-    let normalized_tip_contabil = tip_contabil
-        .as_ref()
-        .map(|value| value.trim().to_string())
-        .filter(|value| !value.is_empty())
-        .or(Some("valoare".to_string()));
-
-    let normalized_cert_comanda_serie = cert_comanda_serie
-        .as_ref()
-        .map(|value| value.trim().to_string())
-        .filter(|value| !value.is_empty())
-        .or(Some("CCAL".to_string()));
+    Ok(load_thermal_printer_settings(&conn))
+}
 
-    let normalized_cert_comanda_id_client = cert_comanda_id_client
+#[tauri::command]
+pub fn save_thermal_printer_settings(
+    db: State<'_, Database>,
+    settings: ThermalPrinterSettings,
+) -> Result<ThermalPrinterSettings, String> {
+    let target = settings
+        .target
         .as_ref()
         .map(|value| value.trim().to_string())
-        .filter(|value| !value.is_empty())
-        .or(Some("1602".to_string()));
+        .filter(|value| !value.is_empty());
+    if let Some(target) = &target {
+        escpos::Target::parse(target)?;
+    }
+    if settings.paper_width_mm != 58 && settings.paper_width_mm != 80 {
+        return Err("Lățimea hârtiei trebuie să fie 58 sau 80 mm.".to_string());
+    }
+    if let Some(table) = settings.code_page_table {
+        if !(0..=255).contains(&table) {
+            return Err("Numărul tabelei de caractere trebuie să fie între 0 și 255.".to_string());
+        }
+    }
+    if !(0..=20).contains(&settings.feed_lines) {
+        return Err("Avansul hârtiei trebuie să fie între 0 și 20 de rânduri.".to_string());
+    }
 
-    let normalized_wme_host = wme_host
-        .as_ref()
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty());
-    let normalized_wme_port = wme_port
-        .and_then(|p| if p > 0 && p <= 65535 { Some(p) } else { None })
-        .or(Some(8089));
+    let saved = ThermalPrinterSettings {
+        target,
+        code_page: escpos::CodePage::from_name(&settings.code_page).name().to_string(),
+        ..settings
+    };
 
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
     conn.execute(
-        "INSERT INTO agent_settings (id, agent_name, carnet_series, simbol_carnet_livr, simbol_gestiune_livrare, tip_contabil, cert_comanda_serie, cert_comanda_id_client, cod_carnet, cod_carnet_livr, cod_delegat, delegate_name, delegate_act, car_number, invoice_number_start, invoice_number_end, invoice_number_current, marca_agent, nume_casa, auto_sync_collections_enabled, auto_sync_collections_time, receipt_series, receipt_number_start, receipt_number_end, receipt_number_current, wme_host, wme_port, updated_at) VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28) \
-         ON CONFLICT(id) DO UPDATE SET agent_name = excluded.agent_name, carnet_series = excluded.carnet_series, simbol_carnet_livr = excluded.simbol_carnet_livr, simbol_gestiune_livrare = excluded.simbol_gestiune_livrare, tip_contabil = excluded.tip_contabil, cert_comanda_serie = excluded.cert_comanda_serie, cert_comanda_id_client = excluded.cert_comanda_id_client, cod_carnet = excluded.cod_carnet, cod_carnet_livr = excluded.cod_carnet_livr, cod_delegat = excluded.cod_delegat, delegate_name = excluded.delegate_name, delegate_act = excluded.delegate_act, car_number = excluded.car_number, invoice_number_start = excluded.invoice_number_start, invoice_number_end = excluded.invoice_number_end, invoice_number_current = excluded.invoice_number_current, marca_agent = excluded.marca_agent, nume_casa = excluded.nume_casa, auto_sync_collections_enabled = excluded.auto_sync_collections_enabled, auto_sync_collections_time = excluded.auto_sync_collections_time, receipt_series = excluded.receipt_series, receipt_number_start = excluded.receipt_number_start, receipt_number_end = excluded.receipt_number_end, receipt_number_current = excluded.receipt_number_current, wme_host = excluded.wme_host, wme_port = excluded.wme_port, updated_at = excluded.updated_at",
+        "INSERT INTO agent_settings (id, thermal_printer_target, thermal_paper_width, thermal_code_page, thermal_code_page_table, thermal_print_logo, thermal_cut, thermal_feed_lines, updated_at) \
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) \
+         ON CONFLICT(id) DO UPDATE SET thermal_printer_target = excluded.thermal_printer_target, thermal_paper_width = excluded.thermal_paper_width, thermal_code_page = excluded.thermal_code_page, thermal_code_page_table = excluded.thermal_code_page_table, thermal_print_logo = excluded.thermal_print_logo, thermal_cut = excluded.thermal_cut, thermal_feed_lines = excluded.thermal_feed_lines, updated_at = excluded.updated_at",
         params![
-            agent_name, carnet_series, simbol_carnet_livr, simbol_gestiune_livrare,
-            normalized_tip_contabil, normalized_cert_comanda_serie, normalized_cert_comanda_id_client,
-            cod_carnet, cod_carnet_livr, cod_delegat, delegate_name, delegate_act, car_number,
-            invoice_number_start, invoice_number_end, final_invoice_current, marca_agent, nume_casa,
-            auto_sync_enabled_int, auto_sync_collections_time, receipt_series, receipt_number_start,
-            receipt_number_end, final_receipt_current, normalized_wme_host, normalized_wme_port,
-            now
+            saved.target,
+            saved.paper_width_mm,
+            saved.code_page,
+            saved.code_page_table,
+            saved.print_logo as i64,
+            saved.cut as i64,
+            saved.feed_lines,
+            Utc::now().to_rfc3339()
         ],
     )
     .map_err(|e| e.to_string())?;
 
-    Ok(AgentSettings {
-        agent_name,
-        carnet_series,
-        simbol_carnet_livr,
-        simbol_gestiune_livrare,
-        tip_contabil: normalized_tip_contabil,
-        cert_comanda_serie: normalized_cert_comanda_serie,
-        cert_comanda_id_client: normalized_cert_comanda_id_client,
-        cod_carnet,
-        cod_carnet_livr,
-        cod_delegat,
-        delegate_name,
-        delegate_act,
-        car_number,
-        invoice_number_start: invoice_number_start.map(|v| v as i32),
-        invoice_number_end: invoice_number_end.map(|v| v as i32),
-        invoice_number_current: final_invoice_current.map(|v| v as i32),
-        marca_agent,
-        nume_casa,
-        auto_sync_collections_enabled,
-        auto_sync_collections_time,
-        receipt_series,
-        receipt_number_start: receipt_number_start.map(|v| v as i32),
-        receipt_number_end: receipt_number_end.map(|v| v as i32),
-        receipt_number_current: final_receipt_current.map(|v| v as i32),
-        wme_host: normalized_wme_host,
-        wme_port: normalized_wme_port.map(|v| v as i32),
-    })
+    info!("[ESCPOS] Thermal printer settings saved: {:?}", saved);
+    Ok(saved)
 }
 
-// ==================== DEBUG COMMANDS ====================
-
 #[tauri::command]
-pub fn debug_db_counts(db: State<'_, Database>) -> Result<String, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub async fn test_thermal_printer(db: State<'_, Database>) -> Result<(), String> {
+    let (options, target) = thermal_printer(&db)?;
+    let issuer = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        company::issuer(&conn)
+    };
 
-    let partners_count: i64 = conn
-        .query_row("SELECT COUNT(*) FROM partners", [], |row| row.get(0))
-        .unwrap_or(0);
+    let mut ticket = escpos::Ticket::new(&options);
+    if let Some(logo) = read_logo_raster(&issuer.logo, &options) {
+        ticket.raster(&logo);
+    }
+    ticket.align(pdf::Align::Center);
+    ticket.bold(true);
+    ticket.text("PAGINĂ DE TEST");
+    ticket.bold(false);
+    ticket.align(pdf::Align::Left);
+    ticket.rule('-');
+    ticket.text("Diacritice: ă â î ș ț Ă Â Î Ș Ț");
+    ticket.row(
+        &format!("Hârtie {} mm", options.paper_width_mm),
+        &format!("{} caractere", options.line_chars()),
+    );
+    ticket.row(
+        &format!("Cod pagină {}", options.code_page.name()),
+        &format!("ESC t {}", options.code_page_table),
+    );
+    ticket.rule('=');
 
-    let products_count: i64 = conn
-        .query_row("SELECT COUNT(*) FROM products", [], |row| row.get(0))
-        .unwrap_or(0);
+    escpos::send(&target, &ticket.finish())
+}
 
-    let partners_list: Vec<(String, String, Option<String>)> = conn
-        .prepare("SELECT id, name, inactiv FROM partners ORDER BY name")
-        .map_err(|e| e.to_string())?
-        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+/// Paper width and thermal settings for a document going to an ESC/POS
+/// printer. The target (the profile's device, or the one in the thermal
+/// printer settings) is stored in the destination, so the print job knows it.
+fn escpos_destination(
+    conn: &rusqlite::Connection,
+    destination: &mut printing::Destination,
+) -> Result<escpos::Options, String> {
+    let settings = load_thermal_printer_settings(conn);
+    let mut options = escpos_options(&settings);
+    if let Some(width) = destination.paper_width_mm() {
+        options.paper_width_mm = width;
+    }
+    let target = destination.printer.as_deref().or(settings.target.as_deref()).unwrap_or("");
+    let target = escpos::Target::parse(target)?;
 
-    let products_list: Vec<(String, String)> = conn
-        .prepare("SELECT id, name FROM products ORDER BY name")
-        .map_err(|e| e.to_string())?
-        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+    destination.printer = Some(target.to_string());
+    destination.paper_size = Some(format!("{}mm", options.paper_width_mm));
+    Ok(options)
+}
 
-    let mut result = format!("Database counts:\nPartners: {}\nProducts: {}\n\n", partners_count, products_count);
-    result.push_str("Partners:\n");
-    for (id, name, inactiv) in partners_list {
-        result.push_str(&format!("  - {} | {} | inactiv: {:?}\n", id, name, inactiv));
-    }
-    result.push_str("\nProducts:\n");
-    for (id, name) in products_list {
-        result.push_str(&format!("  - {} | {}\n", id, name));
-    }
+/// The printer from the thermal printer settings, for the ESC/POS commands
+fn thermal_destination(
+    conn: &rusqlite::Connection,
+    doc_type: &'static str,
+) -> Result<(printing::Destination, escpos::Options), String> {
+    let mut destination = printing::Destination {
+        doc_type,
+        profile_name: None,
+        printer: None,
+        backend: printing::Backend::EscPos,
+        paper_size: None,
+        duplex: false,
+        copies: 1,
+    };
+    let options = escpos_destination(conn, &mut destination)?;
+    Ok((destination, options))
+}
 
-    Ok(result)
+fn receipt_escpos(
+    receipt: &ReceiptPrintData,
+    issuer: &company::Issuer,
+    code: &codes::DocumentCode,
+    options: &escpos::Options,
+) -> Vec<u8> {
+    let logo = read_logo_raster(&issuer.logo, options);
+    print_receipt::generate_receipt_escpos(
+        &receipt.collection,
+        &issuer.profile,
+        logo.as_ref(),
+        code,
+        options,
+        receipt.details(),
+    )
+}
+
+fn invoice_escpos(
+    data: &InvoicePrintData,
+    issuer: &company::Issuer,
+    code: &codes::DocumentCode,
+    options: &escpos::Options,
+    duplicate: bool,
+) -> Vec<u8> {
+    let logo = read_logo_raster(&issuer.logo, options);
+    print_invoice::generate_invoice_escpos(
+        &data.invoice,
+        &data.items,
+        &issuer.profile,
+        logo.as_ref(),
+        code,
+        options,
+        data.details(duplicate),
+    )
 }
 
 #[tauri::command]
-pub fn debug_partner_payment_terms(db: State<'_, Database>, partner_id: String) -> Result<String, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub async fn print_collection_escpos(db: State<'_, Database>, collection_id: String) -> Result<(), String> {
+    let (destination, print_file) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let (destination, options) = thermal_destination(&conn, printing::RECEIPT)?;
+        let receipt = load_receipt_print_data(&conn, &collection_id)?;
+        let issuer = company::issuer(&conn);
+        let code = receipt_code(
+            &conn,
+            &receipt.collection,
+            &receipt.doc_series,
+            &receipt.doc_number,
+            &receipt.issue_date,
+            &issuer,
+            &collection_id,
+        );
+        let bytes = receipt_escpos(&receipt, &issuer, &code, &options);
+        (destination, write_escpos_file("receipts", &format!("chitanta_{}", collection_id), &bytes)?)
+    };
 
-    let result: Result<(String, Option<String>, Option<String>, Option<String>), _> = conn.query_row(
-        "SELECT name, cif, reg_com, scadenta_la_vanzare FROM partners WHERE id = ?1",
-        [&partner_id],
-        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
-    );
+    print_jobs::submit(&db, &destination, &collection_id, &print_file)?;
+    Ok(())
+}
 
-    match result {
-        Ok((name, cif, reg_com, scadenta)) => {
-            let mut output = format!("🔍 Partner Debug Info\n\n");
-            output.push_str(&format!("ID: {}\n", partner_id));
-            output.push_str(&format!("Name: {}\n", name));
-            output.push_str(&format!("CIF: {}\n", cif.unwrap_or("N/A".to_string())));
-            output.push_str(&format!("Reg.Com: {}\n", reg_com.unwrap_or("N/A".to_string())));
-            output.push_str(&format!("\n📅 Payment Terms (scadenta_la_vanzare):\n"));
-            output.push_str(&format!("  Raw value: {:?}\n", scadenta));
+#[tauri::command]
+pub async fn print_invoice_escpos(db: State<'_, Database>, invoice_id: String) -> Result<(), String> {
+    let (destination, print_file) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let (mut destination, _) = thermal_destination(&conn, printing::INVOICE)?;
+        let (_, print_file) = write_invoice_print_files(&conn, &invoice_id, &mut destination)?;
+        (destination, print_file)
+    };
 
-            if let Some(s) = &scadenta {
-                match s.trim().parse::<i64>() {
-                    Ok(days) => output.push_str(&format!("  Parsed: {} days ✅\n", days)),
-                    Err(e) => output.push_str(&format!("  Parse ERROR: {} ❌\n  String: '{}'\n", e, s)),
-                }
-            } else {
-                output.push_str("  Value: NULL (will use default 10 days) ⚠️\n");
-            }
+    print_jobs::submit(&db, &destination, &invoice_id, &print_file)?;
+    Ok(())
+}
 
-            Ok(output)
-        }
-        Err(e) => Err(format!("Partner not found: {}", e))
-    }
+// ESC/POS bytes kept for the print job, in the app data folder of the document
+fn write_escpos_file(folder: &str, name: &str, bytes: &[u8]) -> Result<String, String> {
+    let dir = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join(folder);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {} directory: {}", folder, e))?;
+
+    let path = dir.join(format!("{}.escpos", name));
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write ESC/POS file: {}", e))?;
+    Ok(path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-pub async fn update_all_partners_payment_terms(
-    db: State<'_, Database>,
-    new_days: String,
-) -> Result<String, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub async fn print_collections_report_escpos(db: State<'_, Database>, date: Option<String>) -> Result<(), String> {
+    let date_str = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let (destination, options) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        thermal_destination(&conn, printing::REPORT)?
+    };
 
-    info!("🔄 Updating all partners payment terms to {} days", new_days);
+    let (collections, issuer) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, id_partener, partner_name, numar_factura, serie_factura, cod_document, valoare,
+                        data_incasare, status, synced_at, error_message, created_at, receipt_series, receipt_number,
+                        payment_method, payment_reference, payment_due_date, bank_account
+                 FROM collections
+                 WHERE data_incasare LIKE ?1 AND status <> 'cancelled'
+                 ORDER BY created_at ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([format!("{}%", date_str)], |row| {
+                Ok(Collection {
+                    id: row.get(0)?,
+                    id_partener: row.get(1)?,
+                    partner_name: row.get(2)?,
+                    numar_factura: row.get(3)?,
+                    serie_factura: row.get(4)?,
+                    cod_document: row.get(5)?,
+                    valoare: row.get(6)?,
+                    data_incasare: row.get(7)?,
+                    status: CollectionStatus::from(row.get::<_, String>(8)?),
+                    synced_at: row.get(9)?,
+                    error_message: row.get(10)?,
+                    created_at: row.get(11)?,
+                    receipt_series: row.get(12)?,
+                    receipt_number: row.get(13)?,
+                    payment: payment_methods::from_row(row, 14)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        let collections = rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+        (collections, company::issuer(&conn))
+    };
 
-    let updated = conn.execute(
-        "UPDATE partners SET scadenta_la_vanzare = ?1",
-        [&new_days],
-    ).map_err(|e| e.to_string())?;
+    let total: f64 = collections.iter().map(|c| c.valoare).sum();
+    info!(
+        "[ESCPOS] Collections report {}: {} rows, total {:.2}",
+        date_str,
+        collections.len(),
+        total
+    );
 
-    info!("✅ Updated {} partners with new payment term: {} days", updated, new_days);
+    let bytes = print_daily_report::generate_collections_report_escpos(
+        &collections,
+        &date_str,
+        total,
+        &issuer.profile,
+        read_logo_raster(&issuer.logo, &options).as_ref(),
+        &options,
+    );
+    let print_file = write_escpos_file("reports", &format!("incasari_{}", date_str), &bytes)?;
+    print_jobs::submit(&db, &destination, &date_str, &print_file)?;
+    Ok(())
+}
 
-    Ok(format!("Successfully updated {} partners to {} days payment term", updated, new_days))
+// ==================== PRINTER PROFILE COMMANDS ====================
+
+#[tauri::command]
+pub fn get_printer_profiles(db: State<'_, Database>) -> Result<Vec<PrinterProfile>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    printing::list_profiles(&conn).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn open_external_link(url: String) -> Result<(), String> {
-    if !url.starts_with("http://") && !url.starts_with("https://") {
-        return Err("Invalid URL protocol".to_string());
+pub fn save_printer_profile(db: State<'_, Database>, profile: PrinterProfile) -> Result<PrinterProfile, String> {
+    let name = profile.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Denumirea profilului de imprimantă este obligatorie.".to_string());
+    }
+    let backend = printing::Backend::parse(&profile.backend)?;
+    let printer = profile
+        .printer
+        .as_ref()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    if backend == printing::Backend::EscPos {
+        // Without its own device the profile uses the thermal printer settings
+        if let Some(target) = &printer {
+            escpos::Target::parse(target)?;
+        }
+    }
+    if !(0..=99).contains(&profile.copies) {
+        return Err("Numărul de exemplare trebuie să fie între 0 și 99.".to_string());
     }
-    open::that(url).map_err(|e| e.to_string())
-}
 
-#[tauri::command]
-pub fn save_report_html(report_name: String, html_content: String) -> Result<String, String> {
-    let safe_name = report_name
-        .chars()
-        .map(|ch| {
-            if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' {
-                ch
-            } else {
-                '_'
+    let saved = PrinterProfile {
+        name,
+        printer,
+        backend: backend.name().to_string(),
+        paper_size: profile
+            .paper_size
+            .as_ref()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty()),
+        ..profile
+    };
+    if backend == printing::Backend::EscPos {
+        if let Some(paper) = &saved.paper_size {
+            if printing::thermal_paper_width(paper).is_none() {
+                return Err("Pentru imprimanta termică hârtia trebuie să fie 58mm sau 80mm.".to_string());
             }
-        })
-        .collect::<String>();
-
-    let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
-
-    let reports_dir = dirs::config_dir()
-        .ok_or("Could not find app data directory")?
-        .join("facturi.softconsulting.com")
-        .join("reports");
-
-    std::fs::create_dir_all(&reports_dir)
-        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
-
-    let file_path = reports_dir.join(format!("{}_{}.html", safe_name, timestamp));
+        }
+    }
 
-    std::fs::write(&file_path, html_content)
-        .map_err(|e| format!("Failed to save report HTML: {}", e))?;
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    if let Some(id) = saved.id {
+        if printing::load_profile(&conn, id).map_err(|e| e.to_string())?.is_none() {
+            return Err(format!("Profilul de imprimantă {} nu există.", id));
+        }
+    }
+    let profile_id = printing::save_profile(&conn, &saved).map_err(|e| e.to_string())?;
+    info!("[PRINT] Saved printer profile {} ({}, {})", profile_id, saved.name, saved.backend);
+    printing::load_profile(&conn, profile_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Profilul de imprimantă {} nu există.", profile_id))
+}
 
-    Ok(file_path.to_string_lossy().to_string())
+/// Documents routed to the profile go back to the default printer.
+#[tauri::command]
+pub fn delete_printer_profile(db: State<'_, Database>, profile_id: i64) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    printing::delete_profile(&conn, profile_id).map_err(|e| e.to_string())?;
+    info!("[PRINT] Deleted printer profile {}", profile_id);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn print_report_html(
-    report_name: String,
-    html_content: String,
-    printer_name: Option<String>,
-) -> Result<String, String> {
-    let saved_path = save_report_html(report_name, html_content)?;
-    let html_path_str = saved_path.clone();
+pub fn get_print_routes(db: State<'_, Database>) -> Result<Vec<PrintRoute>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    printing::routes(&conn).map_err(|e| e.to_string())
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        let reports_dir = std::path::Path::new(&saved_path)
-            .parent()
-            .ok_or("Invalid report file path")?;
-
-        let stem = std::path::Path::new(&saved_path)
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or("Invalid report file name")?;
-
-        let pdf_path = reports_dir.join(format!("{}.pdf", stem));
-        let pdf_path_str = pdf_path.to_string_lossy().to_string();
-
-        let mut print_file = html_path_str.clone();
-
-        let edge_paths = vec![
-            "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe",
-            "C:\\Program Files\\Microsoft\\Edge\\Application\\msedge.exe",
-        ];
-
-        for edge_path in edge_paths {
-            if std::path::Path::new(edge_path).exists() {
-                let file_url = format!("file:///{}", html_path_str.replace('\\', "/"));
-                if let Ok(result) = std::process::Command::new(edge_path)
-                    .args(&[
-                        "--headless",
-                        "--disable-gpu",
-                        "--no-sandbox",
-                        "--disable-dev-shm-usage",
-                        &format!("--print-to-pdf={}", pdf_path_str),
-                        &file_url,
-                    ])
-                    .output()
-                {
-                    info!("Report Edge command executed. Status: {}", result.status);
-                    if wait_for_file_ready(&pdf_path_str, 5000, 300) {
-                        print_file = pdf_path_str.clone();
-                        break;
-                    }
-                }
+/// Saves the given routes (the others are kept) and returns all of them.
+#[tauri::command]
+pub fn save_print_routes(db: State<'_, Database>, routes: Vec<PrintRoute>) -> Result<Vec<PrintRoute>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    for route in &routes {
+        printing::doc_type(&route.doc_type)?;
+        if let Some(copies) = route.copies {
+            if !(0..=99).contains(&copies) {
+                return Err("Numărul de exemplare trebuie să fie între 0 și 99.".to_string());
             }
         }
-
-        let printer = printer_name.unwrap_or_default();
-        let user_profile = std::env::var("USERPROFILE").unwrap_or_default();
-        let bundled_path = std::env::current_exe()
-            .ok()
-            .and_then(|exe| exe.parent().map(|p| p.join("resources").join("SumatraPDF.exe")));
-
-        let mut sumatra_paths = vec![
-            format!(r"{}\AppData\Local\SumatraPDF\SumatraPDF.exe", user_profile),
-            r"C:\Program Files\SumatraPDF\SumatraPDF.exe".to_string(),
-            r"C:\Program Files (x86)\SumatraPDF\SumatraPDF.exe".to_string(),
-        ];
-
-        if let Some(bundled) = bundled_path {
-            sumatra_paths.insert(0, bundled.to_string_lossy().to_string());
-        }
-
-        let mut sumatra_exe = None;
-        for path in &sumatra_paths {
-            if std::path::Path::new(path).exists() {
-                sumatra_exe = Some(path.to_string());
-                break;
+        if let Some(profile_id) = route.profile_id {
+            if printing::load_profile(&conn, profile_id).map_err(|e| e.to_string())?.is_none() {
+                return Err(format!("Profilul de imprimantă {} nu există.", profile_id));
             }
         }
+    }
 
-        if sumatra_exe.is_none() {
-            let app_data_dir = dirs::data_dir()
-                .ok_or("Could not get app data directory")?
-                .join("facturi.softconsulting.com");
-            let sumatra_portable = app_data_dir.join("tools").join("SumatraPDF.exe");
-            if sumatra_portable.exists() {
-                sumatra_exe = Some(sumatra_portable.to_string_lossy().to_string());
-            }
-        }
+    for route in &routes {
+        printing::save_route(&conn, route).map_err(|e| e.to_string())?;
+        info!("[PRINT] Route {} -> profile {:?}, copies {:?}", route.doc_type, route.profile_id, route.copies);
+    }
+    printing::routes(&conn).map_err(|e| e.to_string())
+}
 
-        if let Some(sumatra_path) = sumatra_exe {
-            let mut command = std::process::Command::new(&sumatra_path);
-            if printer.trim().is_empty() {
-                command.arg("-print-to-default");
-            } else {
-                command.arg("-print-to").arg(&printer);
-            }
+// ==================== PRINT JOB COMMANDS ====================
 
-            command
-                .arg("-print-settings")
-                .arg("noscale")
-                .arg(&print_file)
-                .arg("-silent")
-                .arg("-exit-when-done")
-                .arg("-exit-on-print")
-                .spawn()
-                .map_err(|e| format!("Failed to print report: {}", e))?;
-        } else {
-            return Err("SumatraPDF not found. Instalează SumatraPDF sau configurează calea aplicației de printare.".to_string());
+/// Newest first; `status` is "queued", "printing", "printed" or "failed".
+#[tauri::command]
+pub fn get_print_jobs(
+    db: State<'_, Database>,
+    status: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<PrintJob>, String> {
+    if let Some(status) = &status {
+        if ![print_jobs::QUEUED, print_jobs::PRINTING, print_jobs::PRINTED, print_jobs::FAILED].contains(&status.as_str()) {
+            return Err(format!("Stare necunoscută a lucrării de printare: {}", status));
         }
     }
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    print_jobs::list(&conn, status.as_deref(), limit.unwrap_or(100).clamp(1, 1000)).map_err(|e| e.to_string())
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        let mut cmd = std::process::Command::new("lp");
-        if let Some(printer) = printer_name {
-            if !printer.trim().is_empty() {
-                cmd.arg("-d").arg(printer);
-            }
-        }
-        cmd.arg(&html_path_str)
-            .spawn()
-            .map_err(|e| format!("Failed to print report: {}", e))?;
+/// Prints a job again. A job that did not print is retried as it is; a
+/// printed one gets a new job on the same printer, with invoices rendered
+/// again so they carry "DUPLICAT". `copies` overrides the copies of the new job.
+#[tauri::command]
+pub async fn reprint_job(
+    db: State<'_, Database>,
+    job_id: i64,
+    copies: Option<i64>,
+) -> Result<PrintJob, String> {
+    let job = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        print_jobs::load(&conn, job_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Lucrarea de printare {} nu există.", job_id))?
+    };
+    if job.status != print_jobs::PRINTED {
+        return print_jobs::run(&db, job_id);
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        let mut cmd = std::process::Command::new("lp");
-        if let Some(printer) = printer_name {
-            if !printer.trim().is_empty() {
-                cmd.arg("-d").arg(printer);
-            }
+    let mut destination = print_jobs::destination(&job)?;
+    if let Some(copies) = copies {
+        if !(1..=99).contains(&copies) {
+            return Err("Numărul de exemplare trebuie să fie între 1 și 99.".to_string());
         }
-        cmd.arg(&html_path_str)
-            .spawn()
-            .map_err(|e| format!("Failed to print report: {}", e))?;
+        destination.copies = copies as u32;
     }
 
-    Ok(saved_path)
-}
+    let new_job_id = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let print_file = if destination.doc_type == printing::INVOICE {
+            write_invoice_print_files(&conn, &job.document_id, &mut destination)?.1
+        } else if std::path::Path::new(&job.file_path).exists() {
+            job.file_path.clone()
+        } else {
+            return Err(format!("Fișierul {} nu mai există. Printați documentul din nou.", job.file_path));
+        };
+        print_jobs::enqueue(&conn, &destination, &job.document_id, &print_file, Some(job.id))
+            .map_err(|e| e.to_string())?
+    };
 
+    info!("[PRINT] Reprinting job {} as job {}", job_id, new_job_id);
+    print_jobs::run(&db, new_job_id)
+}
 
-// ==================== COLLECTION COMMANDS ====================
+// ==================== DOCUMENT TEMPLATE COMMANDS ====================
 
+/// HTML of a document rendered with the current templates, without the
+/// auto-print script. `id` is the invoice id (invoice, quality_certificate),
+/// the collection or receipt group id (receipt) or the date YYYY-MM-DD
+/// (daily_report). Errors in the user's templates are returned, not hidden.
 #[tauri::command]
-pub async fn sync_client_balances(
-    _app: tauri::AppHandle,
+pub async fn render_document_preview(
     db: State<'_, Database>,
+    doc_type: String,
+    id: String,
 ) -> Result<String, String> {
-    let settings = get_agent_settings(db.clone())?;
-    let marca_agent = settings
-        .marca_agent
-        .and_then(|value| {
-            let trimmed = value.trim().to_string();
-            if trimmed.is_empty() {
-                None
+    let doc_type = templates::DocType::parse(&doc_type)?;
+
+    match doc_type {
+        templates::DocType::Invoice => {
+            let (data, issuer, code) = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                let data = load_invoice_print_data(&conn, &id)?;
+                let issuer = company::invoice_issuer(&conn, &id);
+                let code = invoice_code(&conn, &data, &issuer, codes::Kind::Invoice);
+                (data, issuer, code)
+            };
+            let mut images = template_images(&issuer);
+            code.add_to(&mut images);
+            let mut ctx = print_invoice::invoice_template(
+                &data.invoice,
+                &data.items,
+                &issuer.profile,
+                &images,
+                data.details(false),
+            );
+            ctx.preview = true;
+            let html = templates::render_preview(doc_type, &issuer.profile, &ctx)?;
+            Ok(if data.invoice.status == InvoiceStatus::Cancelled {
+                print_invoice::mark_cancelled(html)
             } else {
-                Some(trimmed)
-            }
-        });
-
-    if let Some(marca) = &marca_agent {
-        info!("Sync client balances with MarcaAgent filter: {}", marca);
-    } else {
-        info!("MarcaAgent not set; syncing client balances without filter");
+                html
+            })
+        }
+        templates::DocType::Receipt => {
+            let (receipt, issuer, code) = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                let receipt = load_receipt_print_data(&conn, &id)?;
+                let issuer = company::issuer(&conn);
+                let code = receipt_code(
+                    &conn,
+                    &receipt.collection,
+                    &receipt.doc_series,
+                    &receipt.doc_number,
+                    &receipt.issue_date,
+                    &issuer,
+                    &id,
+                );
+                (receipt, issuer, code)
+            };
+            let mut images = template_images(&issuer);
+            code.add_to(&mut images);
+            let mut ctx = print_receipt::receipt_template(
+                &receipt.collection,
+                &issuer.profile,
+                &images,
+                receipt.details(),
+            );
+            ctx.preview = true;
+            let html = templates::render_preview(doc_type, &issuer.profile, &ctx)?;
+            Ok(if receipt.collection.status == CollectionStatus::Cancelled {
+                print_invoice::mark_cancelled(html)
+            } else {
+                html
+            })
+        }
+        templates::DocType::DailyReport => {
+            let ((invoices, total_sales), issuer) = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                (load_daily_report_invoices(&conn, &id)?, company::issuer(&conn))
+            };
+            let images = template_images(&issuer);
+            let mut ctx =
+                print_daily_report::daily_report_template(&invoices, &id, total_sales, &issuer.profile, &images);
+            ctx.preview = true;
+            templates::render_preview(doc_type, &issuer.profile, &ctx)
+        }
+        templates::DocType::QualityCertificate => {
+            let (car_number, issuer, code) = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                let data = load_invoice_print_data(&conn, &id)?;
+                let issuer = company::invoice_issuer(&conn, &id);
+                let code = invoice_code(&conn, &data, &issuer, codes::Kind::QualityCertificate);
+                (data.car_number, issuer, code)
+            };
+            let context =
+                build_quality_certificate_context(&db, &id, car_number.as_deref().unwrap_or_default()).await?;
+            let mut ctx = quality_certificate_template(&context, &issuer, &code);
+            ctx.preview = true;
+            templates::render_preview(doc_type, &issuer.profile, &ctx)
+        }
+        templates::DocType::CashRegister => {
+            let (page, issuer) = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                let casa = cash_register::current_casa(&conn);
+                (cash_register::day(&conn, &casa, &id)?, company::issuer(&conn))
+            };
+            let images = template_images(&issuer);
+            let mut ctx = print_cash_register::cash_register_template(&page, &issuer.profile, &images);
+            ctx.preview = true;
+            templates::render_preview(doc_type, &issuer.profile, &ctx)
+        }
+        templates::DocType::PartnerStatement => {
+            // `id` is the partner; the preview covers the current year
+            let today = Local::now().date_naive();
+            let (statement, issuer) = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                let from = format!("{}-01-01", today.year());
+                let to = today.format("%Y-%m-%d").to_string();
+                (partner_statement::build(&conn, &id, &from, &to)?, company::issuer(&conn))
+            };
+            let images = template_images(&issuer);
+            let mut ctx = print_partner_statement::partner_statement_template(&statement, &issuer.profile, &images);
+            ctx.preview = true;
+            templates::render_preview(doc_type, &issuer.profile, &ctx)
+        }
+        templates::DocType::BalanceConfirmation => {
+            let (confirmation, issuer) = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                (balance_confirmations::load(&conn, &id)?, company::issuer(&conn))
+            };
+            let images = template_images(&issuer);
+            let mut ctx =
+                print_balance_confirmation::balance_confirmation_template(&confirmation, &issuer.profile, &images);
+            ctx.preview = true;
+            templates::render_preview(doc_type, &issuer.profile, &ctx)
+        }
+        templates::DocType::AgingReport => {
+            // `id` is the date of the report
+            let report = aging::build(&get_client_balances(db.clone(), None)?, &id, aging::BY_PARTNER)?;
+            let issuer = {
+                let conn = db.conn.lock().map_err(|e| e.to_string())?;
+                company::issuer(&conn)
+            };
+            let images = template_images(&issuer);
+            let mut ctx = print_aging_report::aging_report_template(&report, &issuer.profile, &images);
+            ctx.preview = true;
+            templates::render_preview(doc_type, &issuer.profile, &ctx)
+        }
     }
+}
 
-    let partner_ids = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
-        let mut stmt = conn
-            .prepare("SELECT id FROM partners WHERE simbol_clasa = 'AGENTI' OR clasa = 'AGENTI'")
-            .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn get_document_templates() -> Result<Vec<DocumentTemplateInfo>, String> {
+    Ok(templates::DocType::ALL.into_iter().map(templates::info).collect())
+}
 
-        let rows = stmt
-            .query_map([], |row| row.get::<_, String>(0))
-            .map_err(|e| e.to_string())?;
+/// Copies the bundled template of a document into the templates folder so it
+/// can be edited. Files that already exist are left alone.
+#[tauri::command]
+pub fn create_document_template_override(doc_type: String) -> Result<DocumentTemplateInfo, String> {
+    let doc_type = templates::DocType::parse(&doc_type)?;
+    let info = templates::create_override(doc_type)?;
+    info!("[TEMPLATES] Template override for {} at {:?}", doc_type.name(), info.path);
+    Ok(info)
+}
 
-        let mut ids = Vec::new();
-        for row in rows {
-            ids.push(row.map_err(|e| e.to_string())?);
-        }
-        ids
-    };
+// ==================== DOCUMENT CODE COMMANDS ====================
 
-    if partner_ids.is_empty() {
-        return Err("Nu există parteneri locali. Fă mai întâi sincronizarea partenerilor.".to_string());
-    }
+#[tauri::command]
+pub fn get_print_barcode(db: State<'_, Database>) -> Result<bool, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    Ok(print_barcode_enabled(&conn))
+}
 
-    let partner_set: HashSet<String> = partner_ids
-        .into_iter()
-        .map(|id| id.trim().to_string())
-        .filter(|id| !id.is_empty())
-        .collect();
+/// Turns the Code128 barcode under the QR code on or off for the documents
+/// printed from now on.
+#[tauri::command]
+pub fn set_print_barcode(db: State<'_, Database>, enabled: bool) -> Result<bool, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO agent_settings (id, print_barcode, updated_at) VALUES (1, ?1, ?2) \
+         ON CONFLICT(id) DO UPDATE SET print_barcode = excluded.print_barcode, updated_at = excluded.updated_at",
+        params![enabled as i64, Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(enabled)
+}
 
-    // Use ApiClient to fetch balances
-    let api = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
-        get_wme_api_client(&conn)?
+/// Finds the invoice, receipt or quality certificate a scanned QR code or
+/// barcode was printed on. None when the code is not one of ours (a product
+/// or partner code), so the scanner can fall back to searching for it.
+#[tauri::command]
+pub fn lookup_document_by_code(db: State<'_, Database>, code: String) -> Result<Option<ScannedDocument>, String> {
+    let Some(scanned) = codes::parse(&code) else {
+        return Ok(None);
     };
+    info!("[CODES] Looking up scanned {:?} {}/{}", scanned.kind, scanned.series, scanned.number);
 
-    // Fetch all balances from WME for the configured agent, then keep only local partners
-    let solduri = api
-        .get_all_solduri(marca_agent)
-        .await
-        .map_err(|e: String| e)?;
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let found = codes::find(&conn, &scanned);
+
+    match found {
+        Ok(document) => Ok(Some(document)),
+        // A barcode is only a letter, series and number, which a partner or
+        // product code can look like too
+        Err(rusqlite::Error::QueryReturnedNoRows) if scanned.id.is_none() => Ok(None),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Err(format!(
+            "Documentul {} {} nu a fost găsit în baza de date locală.",
+            scanned.series, scanned.number
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    let total_solduri_fetched = solduri.len();
+// ==================== COMPANY PROFILE COMMANDS ====================
 
-    let solduri: Vec<api_client::SoldInfo> = solduri
-        .into_iter()
-        .filter(|s| {
-            if let Some(id_partener) = &s.id_partener {
-                partner_set.contains(id_partener.trim())
-            } else {
-                false
-            }
-        })
-        .filter(|s| api_client::parse_f64(&s.rest) > 0.0)
-        .collect();
+fn company_image_kind(kind: &str) -> Result<&'static str, String> {
+    match kind {
+        company::LOGO => Ok(company::LOGO),
+        company::STAMP => Ok(company::STAMP),
+        _ => Err(format!("Tip de imagine necunoscut: {}", kind)),
+    }
+}
 
-    info!(
-        "[SOLDURI] fetched={}, local_partners={}, kept_after_filters={}",
-        total_solduri_fetched,
-        partner_set.len(),
-        solduri.len()
-    );
+#[tauri::command]
+pub fn get_company_profiles(db: State<'_, Database>) -> Result<Vec<CompanyProfile>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    company::list(&conn).map_err(|e| e.to_string())
+}
 
+#[tauri::command]
+pub fn get_active_company_profile(db: State<'_, Database>) -> Result<CompanyProfile, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    Ok(company::issuer(&conn).profile)
+}
 
-    // Transaction to replace balances
-    conn.execute("DELETE FROM client_balances", []).map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn save_company_profile(db: State<'_, Database>, profile: CompanyProfile) -> Result<CompanyProfile, String> {
+    if profile.name.trim().is_empty() {
+        return Err("Denumirea firmei este obligatorie.".to_string());
+    }
+    if profile.cif.trim().is_empty() {
+        return Err("CIF-ul firmei este obligatoriu.".to_string());
+    }
+    for account in &profile.bank_accounts {
+        let iban = account.iban.split_whitespace().collect::<String>();
+        if iban.len() < 15 || iban.len() > 34 || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("IBAN invalid: {}", account.iban));
+        }
+    }
 
-    let mut stmt = conn.prepare(
-        "INSERT INTO client_balances (
-            id_partener, cod_fiscal, denumire, tip_document, cod_document,
-            serie, numar, data, valoare, rest, termen, moneda,
-            sediu, id_sediu, curs, observatii, cod_obligatie, marca_agent, synced_at
-        ) VALUES (
-            ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19
-        )"
-    ).map_err(|e| e.to_string())?;
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let company_id = company::save(&conn, &profile).map_err(|e| e.to_string())?;
+    info!("[COMPANY] Saved company profile {} ({})", company_id, profile.name.trim());
+    company::load(&conn, company_id).map_err(|e| e.to_string())
+}
 
-    let now = Utc::now().to_rfc3339();
+#[tauri::command]
+pub fn select_company_profile(db: State<'_, Database>, company_id: i64) -> Result<CompanyProfile, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    // Fails when the profile does not exist
+    company::load(&conn, company_id).map_err(|e| e.to_string())?;
 
-    for s in solduri {
-        // Parse fields using helper or manually
-        // We use api_client::parse_f64 if available public, else local parse
-        // Assuming api_client::parse_f64 is public
-        let valoare = api_client::parse_f64(&s.valoare);
-        let rest = api_client::parse_f64(&s.rest);
-        let curs = api_client::parse_f64(&s.curs);
+    conn.execute(
+        "INSERT INTO agent_settings (id, company_profile_id, updated_at) VALUES (1, ?1, ?2) \
+         ON CONFLICT(id) DO UPDATE SET company_profile_id = excluded.company_profile_id, updated_at = excluded.updated_at",
+        params![company_id, Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
 
-        // Parse date for sorting/display usage if needed, but we store as string
-        // s.data is Option<String>
+    info!("[COMPANY] Selected company profile {}", company_id);
+    company::load(&conn, company_id).map_err(|e| e.to_string())
+}
 
-        let id_partener_normalized = s.id_partener.map(|v| v.trim().to_string());
+#[tauri::command]
+pub fn delete_company_profile(db: State<'_, Database>, company_id: i64) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    if company::active_id(&conn).map_err(|e| e.to_string())? == Some(company_id) {
+        return Err("Firma selectată nu poate fi ștearsă. Selectați mai întâi altă firmă.".to_string());
+    }
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM company_profiles", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if count <= 1 {
+        return Err("Trebuie să existe cel puțin o firmă emitentă.".to_string());
+    }
 
-        stmt.execute(params![
-            id_partener_normalized,
-            s.cod_fiscal,
-            s.denumire,
-            s.tip_document,
-            s.cod_document,
-            s.serie,
-            s.numar,
-            s.data,
-            valoare,
-            rest,
-            s.termen,
-            s.moneda,
-            s.sediu,
-            s.id_sediu,
-            curs,
-            s.observatii,
-            s.cod_obligatie,
-            s.marca_agent,
-            now
-        ]).map_err(|e| e.to_string())?;
-    }
-
-    Ok(format!("Synced client balances"))
+    // The connection does not enable foreign_keys, so the cascade cannot be relied on
+    conn.execute("DELETE FROM company_bank_accounts WHERE company_id = ?1", [company_id])
+        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM company_profiles WHERE id = ?1", [company_id])
+        .map_err(|e| e.to_string())?;
+    info!("[COMPANY] Deleted company profile {}", company_id);
+    Ok(())
 }
 
+/// Sets the logo or stamp (`kind` is "logo" or "stamp") from a base64 PNG,
+/// optionally as a data URL. `None` goes back to the bundled image.
 #[tauri::command]
-pub fn get_client_balances(
+pub fn set_company_image(
     db: State<'_, Database>,
-    partner_id: Option<String>,
-) -> Result<Vec<ClientBalance>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    // Combine synced balances from WME with local invoices from DB.
-    // Local collections still in-flight (pending/sending) are subtracted from remaining amount.
-    // An invoice disappears only when the local collected total reaches full amount.
-    let mut query = "SELECT
-        q.id, q.id_partener, q.cod_fiscal, q.denumire, q.tip_document, q.cod_document,
-        q.serie, q.numar, q.data, q.valoare, q.rest, q.termen, q.moneda,
-        q.sediu, q.id_sediu, q.curs, q.observatii, q.cod_obligatie, q.marca_agent, q.synced_at
-        FROM (
-            -- WME invoices (from client_balances), excluding ones we created locally
-            SELECT
-                cb.id, cb.id_partener, cb.cod_fiscal, cb.denumire, cb.tip_document, cb.cod_document,
-                cb.serie, cb.numar, cb.data, cb.valoare,
-                CASE
-                    WHEN COALESCE(cb.rest, 0) - (
-                        SELECT COALESCE(SUM(c.valoare), 0)
-                        FROM collections c
-                        WHERE c.id_partener = cb.id_partener
-                          AND COALESCE(c.serie_factura, '') = COALESCE(cb.serie, '')
-                          AND COALESCE(c.numar_factura, '') = COALESCE(cb.numar, '')
-                          AND COALESCE(c.cod_document, '') = COALESCE(cb.cod_document, '')
-                          AND c.status IN ('pending', 'sending', 'synced')
-                    ) > 0.01
-                        THEN COALESCE(cb.rest, 0) - (
-                            SELECT COALESCE(SUM(c.valoare), 0)
-                            FROM collections c
-                            WHERE c.id_partener = cb.id_partener
-                              AND COALESCE(c.serie_factura, '') = COALESCE(cb.serie, '')
-                              AND COALESCE(c.numar_factura, '') = COALESCE(cb.numar, '')
-                              AND COALESCE(c.cod_document, '') = COALESCE(cb.cod_document, '')
-                              AND c.status IN ('pending', 'sending', 'synced')
-                        )
-                    ELSE 0
-                END AS rest,
-                cb.termen, cb.moneda,
-                cb.sediu, cb.id_sediu, cb.curs, cb.observatii, cb.cod_obligatie, cb.marca_agent, cb.synced_at
-            FROM client_balances cb
-            -- Exclude invoices that exist locally — those are handled by the invoices branch
-            WHERE NOT EXISTS (
-                SELECT 1 FROM invoices i_local
-                WHERE i_local.partner_id = cb.id_partener
-                  AND i_local.invoice_number = CAST(COALESCE(cb.numar, '0') AS INTEGER)
-                  AND (
-                      COALESCE(i_local.invoice_series, '') = COALESCE(cb.serie, '')
-                      OR trim(COALESCE(i_local.invoice_series, '')) = ''
-                      OR trim(COALESCE(cb.serie, '')) = ''
-                  )
-            )
-            -- Exclude invoices hidden by the agent (phantom test invoices)
-            AND NOT EXISTS (
-                SELECT 1 FROM ignored_balances ib
-                WHERE ib.id_partener = cb.id_partener
-                  AND ib.cod_document = COALESCE(cb.cod_document, '')
-                  AND ib.serie = COALESCE(cb.serie, '')
-                  AND ib.numar = COALESCE(cb.numar, '')
-            )
-
-            UNION ALL
-
-            -- Local invoices (created in this app) — always use local collection data
-            SELECT
-                NULL AS id,
-                i.partner_id AS id_partener,
-                p.cif AS cod_fiscal,
-                p.name AS denumire,
-                'FACTURA' AS tip_document,
-                CAST(i.invoice_number AS TEXT) AS cod_document,
-                i.invoice_series AS serie,
-                CAST(i.invoice_number AS TEXT) AS numar,
-                strftime('%d/%m/%Y', replace(substr(i.created_at, 1, 19), 'T', ' ')) AS data,
-                (
-                    SELECT COALESCE(SUM(ii.total_price * (1.0 + COALESCE(CAST(p.procent_tva AS REAL), 0) / 100.0)), 0)
-                    FROM invoice_items ii
-                    JOIN products p ON p.id = ii.product_id
-                    WHERE ii.invoice_id = i.id
-                ) AS valoare,
-                CASE
-                    WHEN (
-                        SELECT COALESCE(SUM(ii.total_price * (1.0 + COALESCE(CAST(p.procent_tva AS REAL), 0) / 100.0)), 0)
-                        FROM invoice_items ii
-                        JOIN products p ON p.id = ii.product_id
-                        WHERE ii.invoice_id = i.id
-                    ) - COALESCE(c2.total_collected, 0) > 0.01
-                        THEN (
-                            SELECT COALESCE(SUM(ii.total_price * (1.0 + COALESCE(CAST(p.procent_tva AS REAL), 0) / 100.0)), 0)
-                            FROM invoice_items ii
-                            JOIN products p ON p.id = ii.product_id
-                            WHERE ii.invoice_id = i.id
-                        ) - COALESCE(c2.total_collected, 0)
-                    ELSE 0
-                END AS rest,
-                strftime(
-                    '%d/%m/%Y',
-                    datetime(
-                        replace(substr(i.created_at, 1, 19), 'T', ' '),
-                        '+' || COALESCE(NULLIF(trim(p.scadenta_la_vanzare), ''), '30') || ' days'
-                    )
-                ) AS termen,
-                'RON' AS moneda,
-                l.name AS sediu,
-                l.id_sediu AS id_sediu,
-                1.0 AS curs,
-                i.notes AS observatii,
-                NULL AS cod_obligatie,
-                (SELECT marca_agent FROM agent_settings WHERE id = 1) AS marca_agent,
-                i.created_at AS synced_at
-            FROM invoices i
-            JOIN partners p ON p.id = i.partner_id
-            JOIN locations l ON l.id = i.location_id
-            LEFT JOIN (
-                SELECT
-                    id_partener,
-                    COALESCE(numar_factura, '') AS numar_factura,
-                    COALESCE(serie_factura, '') AS serie_factura,
-                    SUM(valoare) AS total_collected
-                FROM collections
-                WHERE status IN ('pending', 'sending', 'synced')
-                GROUP BY id_partener, COALESCE(numar_factura, ''), COALESCE(serie_factura, '')
-            ) c2 ON (
-                c2.id_partener = i.partner_id AND
-                c2.numar_factura = CAST(i.invoice_number AS TEXT) AND
-                COALESCE(c2.serie_factura, '') = COALESCE(i.invoice_series, '')
-            )
-            WHERE i.status IN ('pending', 'sending', 'sent', 'failed')
-        ) q
-        WHERE COALESCE(q.rest, 0) > 0".to_string();
-
-    let mut params: Vec<String> = Vec::new();
-
-    if let Some(pid) = partner_id {
-        query.push_str(" AND TRIM(q.id_partener) = TRIM(?1)");
-        params.push(pid);
-    }
-
-    query.push_str(" ORDER BY CASE WHEN date(q.termen) < date('now', 'start of day') THEN 0 ELSE 1 END, date(q.termen) ASC");
-
-    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+    company_id: i64,
+    kind: String,
+    png_base64: Option<String>,
+) -> Result<(), String> {
+    use base64::{engine::general_purpose, Engine as _};
 
-    let balances = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
-        Ok(ClientBalance {
-            id: row.get(0)?,
-            id_partener: row.get(1)?,
-            cod_fiscal: row.get(2)?,
-            denumire: row.get(3)?,
-            tip_document: row.get(4)?,
-            cod_document: row.get(5)?,
-            serie: row.get(6)?,
-            numar: row.get(7)?,
-            data: row.get(8)?,
-            valoare: row.get(9)?,
-            rest: row.get(10)?,
-            termen: row.get(11)?,
-            moneda: row.get(12)?,
-            sediu: row.get(13)?,
-            id_sediu: row.get(14)?,
-            curs: row.get(15)?,
-            observatii: row.get(16)?,
-            cod_obligatie: row.get(17)?,
-            marca_agent: row.get(18)?,
-            synced_at: row.get(19)?,
-        })
-    }).map_err(|e| e.to_string())?;
+    let kind = company_image_kind(&kind)?;
+    let png = match png_base64.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        Some(data) => {
+            let data = data.rsplit(',').next().unwrap_or(data);
+            let bytes = general_purpose::STANDARD
+                .decode(data)
+                .map_err(|e| format!("Imagine invalidă: {}", e))?;
+            pdf::decode_png_rgb(&bytes, 64).map_err(|e| format!("Imaginea trebuie să fie PNG: {}", e))?;
+            Some(bytes)
+        }
+        None => None,
+    };
 
-    let mut result = Vec::new();
-    for b in balances {
-        result.push(b.map_err(|e| e.to_string())?);
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let updated = company::set_image(&conn, company_id, kind, png.as_deref()).map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Firma {} nu există.", company_id));
     }
-
-    Ok(result)
+    info!("[COMPANY] {} of company profile {} {}", kind, company_id, if png.is_some() { "updated" } else { "reset" });
+    Ok(())
 }
 
+/// The stored logo or stamp as a data URL, `None` when the bundled one is used.
 #[tauri::command]
-pub fn hide_client_balance(
-    db: State<'_, Database>,
-    id_partener: String,
-    cod_document: String,
-    serie: Option<String>,
-    numar: Option<String>,
-) -> Result<(), String> {
+pub fn get_company_image(db: State<'_, Database>, company_id: i64, kind: String) -> Result<Option<String>, String> {
+    let kind = company_image_kind(&kind)?;
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let now = Utc::now().to_rfc3339();
-    let serie_norm = serie.as_deref().unwrap_or("").trim().to_string();
-    let numar_norm = numar.as_deref().unwrap_or("").trim().to_string();
-
-    conn.execute(
-        "INSERT OR REPLACE INTO ignored_balances (id_partener, cod_document, serie, numar, hidden_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![id_partener.trim(), cod_document.trim(), serie_norm, numar_norm, now],
-    )
-    .map_err(|e| e.to_string())?;
-
-    info!("[IGNORED_BALANCES] Hidden balance for partner={} cod_document={} serie={} numar={}",
-        id_partener, cod_document, serie_norm, numar_norm);
-    Ok(())
+    let png = company::image(&conn, company_id, kind).map_err(|e| e.to_string())?;
+    Ok(png.and_then(|bytes| png_data_url(&bytes)))
 }
 
-#[tauri::command]
-pub fn record_collection(
-    db: State<'_, Database>,
-    collection: Collection,
-) -> Result<String, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let (receipt_series_opt, carnet_series_opt): (Option<String>, Option<String>) = conn
+// ==================== AGENT SETTINGS COMMANDS ====================
+
+// Helper: Build an ApiClient from DB settings, returning a descriptive error if host is not set
+fn get_wme_api_client(conn: &rusqlite::Connection) -> Result<api_client::ApiClient, String> {
+    let (wme_host, wme_port): (Option<String>, Option<i64>) = conn
         .query_row(
-            "SELECT receipt_series, carnet_series FROM agent_settings WHERE id = 1",
+            "SELECT wme_host, wme_port FROM agent_settings WHERE id = 1",
             [],
-            |row| Ok((row.get(0).ok(), row.get(1).ok()))
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
-        .unwrap_or_default();
+        .unwrap_or((None, None));
 
-    let receipt_series = receipt_series_opt
-        .clone()
-        .filter(|s| !s.trim().is_empty())
-        .or(carnet_series_opt)
-        .unwrap_or_else(|| "CH".to_string());
+    let host = wme_host
+        .as_deref()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| "Server WME neconfigurat. Mergi la Setări și completează adresa IP a serverului WME înainte de sincronizare.".to_string())?;
 
-    let receipt_number = generate_receipt_number(&conn)?;
+    let port = wme_port
+        .and_then(|p| if p > 0 && p <= 65535 { Some(p as u16) } else { None })
+        .unwrap_or(8089);
 
-    // Ensure ID is generated if not provided (though frontend should provide UUID)
-    let id = if collection.id.is_empty() {
-        Uuid::new_v4().to_string()
-    } else {
-        collection.id
-    };
+    info!("Connecting to WME at {}:{}", host, port);
+    api_client::ApiClient::new(api_client::ApiConfig::from_settings(host, port))
+        .map_err(|e| format!("Failed to initialize API client: {}", e))
+}
 
-    conn.execute(
-        "INSERT INTO collections (
-            id, receipt_group_id, receipt_series, receipt_number,
-            id_partener, partner_name, numar_factura, serie_factura,
-            cod_document, valoare, data_incasare, status, created_at
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-        params![
-            id.clone(),
-            id,
+#[tauri::command]
+pub fn get_agent_settings(db: State<'_, Database>) -> Result<AgentSettings, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    let result = conn.query_row(
+        "SELECT agent_name, carnet_series, simbol_carnet_livr, simbol_gestiune_livrare, tip_contabil, cert_comanda_serie, cert_comanda_id_client, cod_carnet, cod_carnet_livr, cod_delegat, delegate_name, delegate_act, car_number, invoice_number_start, invoice_number_end, invoice_number_current, marca_agent, nume_casa, auto_sync_collections_enabled, auto_sync_collections_time, receipt_series, receipt_number_start, receipt_number_end, receipt_number_current, wme_host, wme_port FROM agent_settings WHERE id = 1",
+        [],
+        |row| {
+            let auto_sync_enabled: Option<i32> = row.get(18)?;
+            Ok(AgentSettings {
+                agent_name: row.get(0)?,
+                carnet_series: row.get(1)?,
+                simbol_carnet_livr: row.get(2)?,
+                simbol_gestiune_livrare: row.get(3)?,
+                tip_contabil: row.get(4)?,
+                cert_comanda_serie: row.get(5)?,
+                cert_comanda_id_client: row.get(6)?,
+                cod_carnet: row.get(7)?,
+                cod_carnet_livr: row.get(8)?,
+                cod_delegat: row.get(9)?,
+                delegate_name: row.get(10)?,
+                delegate_act: row.get(11)?,
+                car_number: row.get(12)?,
+                invoice_number_start: row.get(13)?,
+                invoice_number_end: row.get(14)?,
+                invoice_number_current: row.get(15)?,
+                marca_agent: row.get(16)?,
+                nume_casa: row.get(17)?,
+                auto_sync_collections_enabled: auto_sync_enabled.map(|v| v != 0),
+                auto_sync_collections_time: row.get(19)?,
+                receipt_series: row.get(20)?,
+                receipt_number_start: row.get(21)?,
+                receipt_number_end: row.get(22)?,
+                receipt_number_current: row.get(23)?,
+                wme_host: row.get(24)?,
+                wme_port: row.get::<_, Option<i64>>(25)?.map(|v| v as i32),
+            })
+        },
+    );
+
+    match result {
+        Ok(settings) => Ok(settings),
+        Err(_) => Ok(AgentSettings {
+            agent_name: None,
+            carnet_series: None,
+            simbol_carnet_livr: None,
+            simbol_gestiune_livrare: None,
+            tip_contabil: Some("valoare".to_string()),
+            cert_comanda_serie: Some("CCAL".to_string()),
+            cert_comanda_id_client: Some("1602".to_string()),
+            cod_carnet: None,
+            cod_carnet_livr: None,
+            cod_delegat: None,
+            delegate_name: None,
+            delegate_act: None,
+            car_number: None,
+            invoice_number_start: Some(1),
+            invoice_number_end: Some(99999),
+            invoice_number_current: Some(1),
+            marca_agent: None,
+            nume_casa: None,
+            auto_sync_collections_enabled: Some(false),
+            auto_sync_collections_time: Some("23:00".to_string()),
+            receipt_series: None,
+            receipt_number_start: Some(1),
+            receipt_number_end: Some(99999),
+            receipt_number_current: Some(1),
+            wme_host: None,
+            wme_port: None,
+        }),
+    }
+}
+
+#[tauri::command]
+pub fn save_agent_settings(
+    db: State<'_, Database>,
+    agent_name: Option<String>,
+    carnet_series: Option<String>,
+    simbol_carnet_livr: Option<String>,
+    simbol_gestiune_livrare: Option<String>,
+    tip_contabil: Option<String>,
+    cert_comanda_serie: Option<String>,
+    cert_comanda_id_client: Option<String>,
+    cod_carnet: Option<String>,
+    cod_carnet_livr: Option<String>,
+    cod_delegat: Option<String>,
+    delegate_name: Option<String>,
+    delegate_act: Option<String>,
+    car_number: Option<String>,
+    invoice_number_start: Option<i64>,
+    invoice_number_end: Option<i64>,
+    invoice_number_current: Option<i64>,
+    marca_agent: Option<String>,
+    nume_casa: Option<String>,
+    auto_sync_collections_enabled: Option<bool>,
+    auto_sync_collections_time: Option<String>,
+    receipt_series: Option<String>,
+    receipt_number_start: Option<i64>,
+    receipt_number_end: Option<i64>,
+    receipt_number_current: Option<i64>,
+    wme_host: Option<String>,
+    wme_port: Option<i64>,
+) -> Result<AgentSettings, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    // Smart logic for invoice numbering:
+    // If invoice_number_start is provided and current is less than start, set current = start
+    let final_invoice_current = match (invoice_number_start, invoice_number_current) {
+        (Some(start), Some(current)) if current < start => {
+            info!("Auto-adjusting invoice_number_current from {} to {} (matching start)", current, start);
+            Some(start)
+        },
+        (Some(start), None) => {
+            info!("Initializing invoice_number_current to {} (start value)", start);
+            Some(start)
+        },
+        _ => invoice_number_current,
+    };
+
+    // Same logic for receipt numbering
+    let final_receipt_current = match (receipt_number_start, receipt_number_current) {
+        (Some(start), Some(current)) if current < start => {
+            info!("Auto-adjusting receipt_number_current from {} to {} (matching start)", current, start);
+            Some(start)
+        },
+        (Some(start), None) => {
+            info!("Initializing receipt_number_current to {} (start value)", start);
+            Some(start)
+        },
+        _ => receipt_number_current,
+    };
+
+    // Convert bool to i32 for SQLite
+    let auto_sync_enabled_int = auto_sync_collections_enabled.map(|v| if v { 1 } else { 0 });
+
+    let normalized_tip_contabil = tip_contabil
+        .as_ref()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .or(Some("valoare".to_string()));
+
+    let normalized_cert_comanda_serie = cert_comanda_serie
+        .as_ref()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .or(Some("CCAL".to_string()));
+
+    let normalized_cert_comanda_id_client = cert_comanda_id_client
+        .as_ref()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .or(Some("1602".to_string()));
+
+    let normalized_wme_host = wme_host
+        .as_ref()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    let normalized_wme_port = wme_port
+        .and_then(|p| if p > 0 && p <= 65535 { Some(p) } else { None })
+        .or(Some(8089));
+
+    conn.execute(
+        "INSERT INTO agent_settings (id, agent_name, carnet_series, simbol_carnet_livr, simbol_gestiune_livrare, tip_contabil, cert_comanda_serie, cert_comanda_id_client, cod_carnet, cod_carnet_livr, cod_delegat, delegate_name, delegate_act, car_number, invoice_number_start, invoice_number_end, invoice_number_current, marca_agent, nume_casa, auto_sync_collections_enabled, auto_sync_collections_time, receipt_series, receipt_number_start, receipt_number_end, receipt_number_current, wme_host, wme_port, updated_at) VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28) \
+         ON CONFLICT(id) DO UPDATE SET agent_name = excluded.agent_name, carnet_series = excluded.carnet_series, simbol_carnet_livr = excluded.simbol_carnet_livr, simbol_gestiune_livrare = excluded.simbol_gestiune_livrare, tip_contabil = excluded.tip_contabil, cert_comanda_serie = excluded.cert_comanda_serie, cert_comanda_id_client = excluded.cert_comanda_id_client, cod_carnet = excluded.cod_carnet, cod_carnet_livr = excluded.cod_carnet_livr, cod_delegat = excluded.cod_delegat, delegate_name = excluded.delegate_name, delegate_act = excluded.delegate_act, car_number = excluded.car_number, invoice_number_start = excluded.invoice_number_start, invoice_number_end = excluded.invoice_number_end, invoice_number_current = excluded.invoice_number_current, marca_agent = excluded.marca_agent, nume_casa = excluded.nume_casa, auto_sync_collections_enabled = excluded.auto_sync_collections_enabled, auto_sync_collections_time = excluded.auto_sync_collections_time, receipt_series = excluded.receipt_series, receipt_number_start = excluded.receipt_number_start, receipt_number_end = excluded.receipt_number_end, receipt_number_current = excluded.receipt_number_current, wme_host = excluded.wme_host, wme_port = excluded.wme_port, updated_at = excluded.updated_at",
+        params![
+            agent_name, carnet_series, simbol_carnet_livr, simbol_gestiune_livrare,
+            normalized_tip_contabil, normalized_cert_comanda_serie, normalized_cert_comanda_id_client,
+            cod_carnet, cod_carnet_livr, cod_delegat, delegate_name, delegate_act, car_number,
+            invoice_number_start, invoice_number_end, final_invoice_current, marca_agent, nume_casa,
+            auto_sync_enabled_int, auto_sync_collections_time, receipt_series, receipt_number_start,
+            receipt_number_end, final_receipt_current, normalized_wme_host, normalized_wme_port,
+            now
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // The numbers set here are the carnet in use from now on
+    if invoice_number_start.is_some() || final_invoice_current.is_some() {
+        let start = invoice_number_start.or(final_invoice_current).unwrap_or(1);
+        numbering::sync_from_settings(
+            &conn,
+            numbering::INVOICE,
+            carnet_series.as_deref().unwrap_or_default(),
+            start,
+            Some(invoice_number_end.unwrap_or(99999)),
+            final_invoice_current.unwrap_or(start),
+        )?;
+    }
+    if receipt_number_start.is_some() || final_receipt_current.is_some() {
+        let start = receipt_number_start.or(final_receipt_current).unwrap_or(1);
+        let series = receipt_series
+            .as_deref()
+            .filter(|series| !series.trim().is_empty())
+            .or(carnet_series.as_deref().filter(|series| !series.trim().is_empty()))
+            .unwrap_or("CH");
+        numbering::sync_from_settings(
+            &conn,
+            numbering::RECEIPT,
+            series,
+            start,
+            receipt_number_end,
+            final_receipt_current.unwrap_or(start),
+        )?;
+    }
+
+    Ok(AgentSettings {
+        agent_name,
+        carnet_series,
+        simbol_carnet_livr,
+        simbol_gestiune_livrare,
+        tip_contabil: normalized_tip_contabil,
+        cert_comanda_serie: normalized_cert_comanda_serie,
+        cert_comanda_id_client: normalized_cert_comanda_id_client,
+        cod_carnet,
+        cod_carnet_livr,
+        cod_delegat,
+        delegate_name,
+        delegate_act,
+        car_number,
+        invoice_number_start: invoice_number_start.map(|v| v as i32),
+        invoice_number_end: invoice_number_end.map(|v| v as i32),
+        invoice_number_current: final_invoice_current.map(|v| v as i32),
+        marca_agent,
+        nume_casa,
+        auto_sync_collections_enabled,
+        auto_sync_collections_time,
+        receipt_series,
+        receipt_number_start: receipt_number_start.map(|v| v as i32),
+        receipt_number_end: receipt_number_end.map(|v| v as i32),
+        receipt_number_current: final_receipt_current.map(|v| v as i32),
+        wme_host: normalized_wme_host,
+        wme_port: normalized_wme_port.map(|v| v as i32),
+    })
+}
+
+// ==================== DEBUG COMMANDS ====================
+
+#[tauri::command]
+pub fn debug_db_counts(db: State<'_, Database>) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    let partners_count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM partners", [], |row| row.get(0))
+        .unwrap_or(0);
+
+    let products_count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM products", [], |row| row.get(0))
+        .unwrap_or(0);
+
+    let partners_list: Vec<(String, String, Option<String>)> = conn
+        .prepare("SELECT id, name, inactiv FROM partners ORDER BY name")
+        .map_err(|e| e.to_string())?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let products_list: Vec<(String, String)> = conn
+        .prepare("SELECT id, name FROM products ORDER BY name")
+        .map_err(|e| e.to_string())?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut result = format!("Database counts:\nPartners: {}\nProducts: {}\n\n", partners_count, products_count);
+    result.push_str("Partners:\n");
+    for (id, name, inactiv) in partners_list {
+        result.push_str(&format!("  - {} | {} | inactiv: {:?}\n", id, name, inactiv));
+    }
+    result.push_str("\nProducts:\n");
+    for (id, name) in products_list {
+        result.push_str(&format!("  - {} | {}\n", id, name));
+    }
+
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn debug_partner_payment_terms(db: State<'_, Database>, partner_id: String) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    let result: Result<(String, Option<String>, Option<String>, Option<String>), _> = conn.query_row(
+        "SELECT name, cif, reg_com, scadenta_la_vanzare FROM partners WHERE id = ?1",
+        [&partner_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    );
+
+    match result {
+        Ok((name, cif, reg_com, scadenta)) => {
+            let mut output = format!("🔍 Partner Debug Info\n\n");
+            output.push_str(&format!("ID: {}\n", partner_id));
+            output.push_str(&format!("Name: {}\n", name));
+            output.push_str(&format!("CIF: {}\n", cif.unwrap_or("N/A".to_string())));
+            output.push_str(&format!("Reg.Com: {}\n", reg_com.unwrap_or("N/A".to_string())));
+            output.push_str(&format!("\n📅 Payment Terms (scadenta_la_vanzare):\n"));
+            output.push_str(&format!("  Raw value: {:?}\n", scadenta));
+
+            if let Some(s) = &scadenta {
+                match s.trim().parse::<i64>() {
+                    Ok(days) => output.push_str(&format!("  Parsed: {} days ✅\n", days)),
+                    Err(e) => output.push_str(&format!("  Parse ERROR: {} ❌\n  String: '{}'\n", e, s)),
+                }
+            } else {
+                output.push_str("  Value: NULL (will use default 10 days) ⚠️\n");
+            }
+
+            Ok(output)
+        }
+        Err(e) => Err(format!("Partner not found: {}", e))
+    }
+}
+
+#[tauri::command]
+pub async fn update_all_partners_payment_terms(
+    db: State<'_, Database>,
+    new_days: String,
+) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    info!("🔄 Updating all partners payment terms to {} days", new_days);
+
+    let updated = conn.execute(
+        "UPDATE partners SET scadenta_la_vanzare = ?1",
+        [&new_days],
+    ).map_err(|e| e.to_string())?;
+
+    info!("✅ Updated {} partners with new payment term: {} days", updated, new_days);
+
+    Ok(format!("Successfully updated {} partners to {} days payment term", updated, new_days))
+}
+
+#[tauri::command]
+pub async fn open_external_link(url: String) -> Result<(), String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("Invalid URL protocol".to_string());
+    }
+    open::that(url).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_report_html(report_name: String, html_content: String) -> Result<String, String> {
+    let safe_name = report_name
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
+
+    let reports_dir = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join("reports");
+
+    std::fs::create_dir_all(&reports_dir)
+        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
+
+    let file_path = reports_dir.join(format!("{}_{}.html", safe_name, timestamp));
+
+    std::fs::write(&file_path, html_content)
+        .map_err(|e| format!("Failed to save report HTML: {}", e))?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+/// Prints the day summary ("centralizator zi") of the reports page: the
+/// invoices of the period per partner and the receipts of the last day,
+/// rendered natively as PDF, or as ESC/POS for a thermal printer.
+#[tauri::command]
+pub fn print_sales_summary(
+    db: State<'_, Database>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    let report_date = end_date
+        .clone()
+        .or_else(|| start_date.clone())
+        .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let sales = get_sales_print_report(db.clone(), start_date, end_date, None)?;
+    let collections = get_daily_collections_report(db.clone(), Some(report_date.clone()))?;
+
+    let (destination, print_file) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let mut destination = printing::destination(&conn, printing::REPORT, printer_name.as_deref());
+        let file_name = format!("centralizator_zi_{}", report_date);
+        let print_file = if destination.backend == printing::Backend::EscPos {
+            let options = escpos_destination(&conn, &mut destination)?;
+            let bytes = print_sales_summary::generate_sales_summary_escpos(&sales, &collections, &report_date, &options);
+            write_escpos_file("reports", &file_name, &bytes)?
+        } else {
+            let reports_dir = dirs::config_dir()
+                .ok_or("Could not find app data directory")?
+                .join("facturi.softconsulting.com")
+                .join("reports");
+            std::fs::create_dir_all(&reports_dir)
+                .map_err(|e| format!("Failed to create reports directory: {}", e))?;
+            let pdf_path = reports_dir.join(format!("{}.pdf", file_name));
+            let bytes = print_sales_summary::generate_sales_summary_pdf(&sales, &collections, &report_date);
+            std::fs::write(&pdf_path, &bytes).map_err(|e| format!("Failed to write PDF file: {}", e))?;
+            pdf_path.to_string_lossy().to_string()
+        };
+        (destination, print_file)
+    };
+
+    info!("[REPORT] Day summary {} rendered to {}", report_date, print_file);
+    print_jobs::submit(&db, &destination, &report_date, &print_file)?;
+    Ok(print_file)
+}
+
+
+// ==================== COLLECTION COMMANDS ====================
+
+#[tauri::command]
+pub async fn sync_client_balances(
+    _app: tauri::AppHandle,
+    db: State<'_, Database>,
+) -> Result<String, String> {
+    let settings = get_agent_settings(db.clone())?;
+    let marca_agent = settings
+        .marca_agent
+        .and_then(|value| {
+            let trimmed = value.trim().to_string();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed)
+            }
+        });
+
+    if let Some(marca) = &marca_agent {
+        info!("Sync client balances with MarcaAgent filter: {}", marca);
+    } else {
+        info!("MarcaAgent not set; syncing client balances without filter");
+    }
+
+    let partner_ids = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id FROM partners WHERE simbol_clasa = 'AGENTI' OR clasa = 'AGENTI'")
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+
+        let mut ids = Vec::new();
+        for row in rows {
+            ids.push(row.map_err(|e| e.to_string())?);
+        }
+        ids
+    };
+
+    if partner_ids.is_empty() {
+        return Err("Nu există parteneri locali. Fă mai întâi sincronizarea partenerilor.".to_string());
+    }
+
+    let partner_set: HashSet<String> = partner_ids
+        .into_iter()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+
+    // Use ApiClient to fetch balances
+    let api = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        get_wme_api_client(&conn)?
+    };
+
+    // Fetch all balances from WME for the configured agent, then keep only local partners
+    let solduri = api
+        .get_all_solduri(marca_agent)
+        .await
+        .map_err(|e: String| e)?;
+
+    let total_solduri_fetched = solduri.len();
+
+    let solduri: Vec<api_client::SoldInfo> = solduri
+        .into_iter()
+        .filter(|s| {
+            if let Some(id_partener) = &s.id_partener {
+                partner_set.contains(id_partener.trim())
+            } else {
+                false
+            }
+        })
+        .filter(|s| api_client::parse_f64(&s.rest) > 0.0)
+        .collect();
+
+    info!(
+        "[SOLDURI] fetched={}, local_partners={}, kept_after_filters={}",
+        total_solduri_fetched,
+        partner_set.len(),
+        solduri.len()
+    );
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    // Transaction to replace balances
+    conn.execute("DELETE FROM client_balances", []).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn.prepare(
+        "INSERT INTO client_balances (
+            id_partener, cod_fiscal, denumire, tip_document, cod_document,
+            serie, numar, data, valoare, rest, termen, moneda,
+            sediu, id_sediu, curs, observatii, cod_obligatie, marca_agent, synced_at
+        ) VALUES (
+            ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19
+        )"
+    ).map_err(|e| e.to_string())?;
+
+    let now = Utc::now().to_rfc3339();
+
+    for s in solduri {
+        // Parse fields using helper or manually
+        // We use api_client::parse_f64 if available public, else local parse
+        // Assuming api_client::parse_f64 is public
+        let valoare = api_client::parse_f64(&s.valoare);
+        let rest = api_client::parse_f64(&s.rest);
+        let curs = api_client::parse_f64(&s.curs);
+
+        // Parse date for sorting/display usage if needed, but we store as string
+        // s.data is Option<String>
+
+        let id_partener_normalized = s.id_partener.map(|v| v.trim().to_string());
+
+        stmt.execute(params![
+            id_partener_normalized,
+            s.cod_fiscal,
+            s.denumire,
+            s.tip_document,
+            s.cod_document,
+            s.serie,
+            s.numar,
+            s.data,
+            valoare,
+            rest,
+            s.termen,
+            s.moneda,
+            s.sediu,
+            s.id_sediu,
+            curs,
+            s.observatii,
+            s.cod_obligatie,
+            s.marca_agent,
+            now
+        ]).map_err(|e| e.to_string())?;
+    }
+
+    Ok(format!("Synced client balances"))
+}
+
+#[tauri::command]
+pub fn get_client_balances(
+    db: State<'_, Database>,
+    partner_id: Option<String>,
+) -> Result<Vec<ClientBalance>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    // Combine synced balances from WME with local invoices from DB.
+    // Local collections still in-flight (pending/sending) are subtracted from remaining amount,
+    // as are advances set against the invoice (invoice_payments).
+    // An invoice disappears only when the local collected total reaches full amount.
+    let mut query = "SELECT
+        q.id, q.id_partener, q.cod_fiscal, q.denumire, q.tip_document, q.cod_document,
+        q.serie, q.numar, q.data, q.valoare, q.rest, q.termen, q.moneda,
+        q.sediu, q.id_sediu, q.curs, q.observatii, q.cod_obligatie, q.marca_agent, q.synced_at
+        FROM (
+            -- WME invoices (from client_balances), excluding ones we created locally
+            SELECT
+                cb.id, cb.id_partener, cb.cod_fiscal, cb.denumire, cb.tip_document, cb.cod_document,
+                cb.serie, cb.numar, cb.data, cb.valoare,
+                CASE
+                    WHEN COALESCE(cb.rest, 0) - (
+                        SELECT COALESCE(SUM(c.valoare), 0)
+                        FROM invoice_payments c
+                        WHERE c.id_partener = cb.id_partener
+                          AND COALESCE(c.serie_factura, '') = COALESCE(cb.serie, '')
+                          AND COALESCE(c.numar_factura, '') = COALESCE(cb.numar, '')
+                          AND COALESCE(c.cod_document, '') = COALESCE(cb.cod_document, '')
+                          AND c.status IN ('pending', 'sending', 'synced')
+                    ) > 0.01
+                        THEN COALESCE(cb.rest, 0) - (
+                            SELECT COALESCE(SUM(c.valoare), 0)
+                            FROM invoice_payments c
+                            WHERE c.id_partener = cb.id_partener
+                              AND COALESCE(c.serie_factura, '') = COALESCE(cb.serie, '')
+                              AND COALESCE(c.numar_factura, '') = COALESCE(cb.numar, '')
+                              AND COALESCE(c.cod_document, '') = COALESCE(cb.cod_document, '')
+                              AND c.status IN ('pending', 'sending', 'synced')
+                        )
+                    ELSE 0
+                END AS rest,
+                cb.termen, cb.moneda,
+                cb.sediu, cb.id_sediu, cb.curs, cb.observatii, cb.cod_obligatie, cb.marca_agent, cb.synced_at
+            FROM client_balances cb
+            -- Exclude invoices that exist locally — those are handled by the invoices branch
+            WHERE NOT EXISTS (
+                SELECT 1 FROM invoices i_local
+                WHERE i_local.partner_id = cb.id_partener
+                  AND i_local.invoice_number = CAST(COALESCE(cb.numar, '0') AS INTEGER)
+                  AND (
+                      COALESCE(i_local.invoice_series, '') = COALESCE(cb.serie, '')
+                      OR trim(COALESCE(i_local.invoice_series, '')) = ''
+                      OR trim(COALESCE(cb.serie, '')) = ''
+                  )
+            )
+            -- Exclude invoices hidden by the agent (phantom test invoices)
+            AND NOT EXISTS (
+                SELECT 1 FROM ignored_balances ib
+                WHERE ib.id_partener = cb.id_partener
+                  AND ib.cod_document = COALESCE(cb.cod_document, '')
+                  AND ib.serie = COALESCE(cb.serie, '')
+                  AND ib.numar = COALESCE(cb.numar, '')
+            )
+
+            UNION ALL
+
+            -- Local invoices (created in this app) — always use local collection data
+            SELECT
+                NULL AS id,
+                i.partner_id AS id_partener,
+                p.cif AS cod_fiscal,
+                p.name AS denumire,
+                'FACTURA' AS tip_document,
+                CAST(i.invoice_number AS TEXT) AS cod_document,
+                i.invoice_series AS serie,
+                CAST(i.invoice_number AS TEXT) AS numar,
+                strftime('%d/%m/%Y', replace(substr(i.created_at, 1, 19), 'T', ' ')) AS data,
+                (
+                    SELECT COALESCE(SUM(ii.total_price * (1.0 + COALESCE(CAST(p.procent_tva AS REAL), 0) / 100.0)), 0)
+                    FROM invoice_items ii
+                    JOIN products p ON p.id = ii.product_id
+                    WHERE ii.invoice_id = i.id
+                ) AS valoare,
+                CASE
+                    WHEN (
+                        SELECT COALESCE(SUM(ii.total_price * (1.0 + COALESCE(CAST(p.procent_tva AS REAL), 0) / 100.0)), 0)
+                        FROM invoice_items ii
+                        JOIN products p ON p.id = ii.product_id
+                        WHERE ii.invoice_id = i.id
+                    ) - COALESCE(c2.total_collected, 0) > 0.01
+                        THEN (
+                            SELECT COALESCE(SUM(ii.total_price * (1.0 + COALESCE(CAST(p.procent_tva AS REAL), 0) / 100.0)), 0)
+                            FROM invoice_items ii
+                            JOIN products p ON p.id = ii.product_id
+                            WHERE ii.invoice_id = i.id
+                        ) - COALESCE(c2.total_collected, 0)
+                    ELSE 0
+                END AS rest,
+                strftime(
+                    '%d/%m/%Y',
+                    datetime(
+                        replace(substr(i.created_at, 1, 19), 'T', ' '),
+                        '+' || COALESCE(NULLIF(trim(p.scadenta_la_vanzare), ''), '30') || ' days'
+                    )
+                ) AS termen,
+                'RON' AS moneda,
+                l.name AS sediu,
+                l.id_sediu AS id_sediu,
+                1.0 AS curs,
+                i.notes AS observatii,
+                NULL AS cod_obligatie,
+                (SELECT marca_agent FROM agent_settings WHERE id = 1) AS marca_agent,
+                i.created_at AS synced_at
+            FROM invoices i
+            JOIN partners p ON p.id = i.partner_id
+            JOIN locations l ON l.id = i.location_id
+            LEFT JOIN (
+                SELECT
+                    id_partener,
+                    COALESCE(numar_factura, '') AS numar_factura,
+                    COALESCE(serie_factura, '') AS serie_factura,
+                    SUM(valoare) AS total_collected
+                FROM invoice_payments
+                WHERE status IN ('pending', 'sending', 'synced')
+                GROUP BY id_partener, COALESCE(numar_factura, ''), COALESCE(serie_factura, '')
+            ) c2 ON (
+                c2.id_partener = i.partner_id AND
+                c2.numar_factura = CAST(i.invoice_number AS TEXT) AND
+                COALESCE(c2.serie_factura, '') = COALESCE(i.invoice_series, '')
+            )
+            WHERE i.status IN ('pending', 'sending', 'sent', 'failed')
+        ) q
+        WHERE COALESCE(q.rest, 0) > 0".to_string();
+
+    let mut params: Vec<String> = Vec::new();
+
+    if let Some(pid) = partner_id {
+        query.push_str(" AND TRIM(q.id_partener) = TRIM(?1)");
+        params.push(pid);
+    }
+
+    query.push_str(" ORDER BY CASE WHEN date(q.termen) < date('now', 'start of day') THEN 0 ELSE 1 END, date(q.termen) ASC");
+
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+
+    let balances = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok(ClientBalance {
+            id: row.get(0)?,
+            id_partener: row.get(1)?,
+            cod_fiscal: row.get(2)?,
+            denumire: row.get(3)?,
+            tip_document: row.get(4)?,
+            cod_document: row.get(5)?,
+            serie: row.get(6)?,
+            numar: row.get(7)?,
+            data: row.get(8)?,
+            valoare: row.get(9)?,
+            rest: row.get(10)?,
+            termen: row.get(11)?,
+            moneda: row.get(12)?,
+            sediu: row.get(13)?,
+            id_sediu: row.get(14)?,
+            curs: row.get(15)?,
+            observatii: row.get(16)?,
+            cod_obligatie: row.get(17)?,
+            marca_agent: row.get(18)?,
+            synced_at: row.get(19)?,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for b in balances {
+        result.push(b.map_err(|e| e.to_string())?);
+    }
+
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn hide_client_balance(
+    db: State<'_, Database>,
+    id_partener: String,
+    cod_document: String,
+    serie: Option<String>,
+    numar: Option<String>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let now = Utc::now().to_rfc3339();
+    let serie_norm = serie.as_deref().unwrap_or("").trim().to_string();
+    let numar_norm = numar.as_deref().unwrap_or("").trim().to_string();
+
+    conn.execute(
+        "INSERT OR REPLACE INTO ignored_balances (id_partener, cod_document, serie, numar, hidden_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id_partener.trim(), cod_document.trim(), serie_norm, numar_norm, now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    info!("[IGNORED_BALANCES] Hidden balance for partner={} cod_document={} serie={} numar={}",
+        id_partener, cod_document, serie_norm, numar_norm);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_cash_limit_rules(db: State<'_, Database>) -> Result<Vec<CashLimitRule>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    cash_limits::rules(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_cash_limit_rule(db: State<'_, Database>, rule: CashLimitRule) -> Result<Vec<CashLimitRule>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    cash_limits::save_rule(&conn, &rule)?;
+    cash_limits::rules(&conn).map_err(|e| e.to_string())
+}
+
+/// Carnets of invoice and receipt numbers, in the order they are used.
+#[tauri::command]
+pub fn get_number_ranges(
+    db: State<'_, Database>,
+    document_type: Option<String>,
+) -> Result<Vec<NumberRange>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    numbering::ranges(&conn, document_type.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Adds a carnet (id 0), queued after the existing ones, or changes one.
+#[tauri::command]
+pub fn save_number_range(db: State<'_, Database>, range: NumberRange) -> Result<Vec<NumberRange>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    numbering::save_range(&conn, &range)?;
+    numbering::ranges(&conn, Some(&range.document_type)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_number_registry(
+    db: State<'_, Database>,
+    document_type: Option<String>,
+    series: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<NumberRegistryEntry>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    numbering::registry(&conn, document_type.as_deref(), series.as_deref(), limit.unwrap_or(200))
+        .map_err(|e| e.to_string())
+}
+
+/// Carnets running out, to be shown after a document is issued.
+#[tauri::command]
+pub fn get_numbering_warnings(db: State<'_, Database>) -> Result<Vec<String>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    numbering::warnings(&conn).map_err(|e| e.to_string())
+}
+
+/// Caps a receipt of today would exceed, so the screen can warn before it is
+/// recorded. Recording checks again and refuses the blocking ones.
+#[tauri::command]
+pub fn check_cash_limits(
+    db: State<'_, Database>,
+    partner_id: String,
+    allocations: Vec<CollectionAllocationRequest>,
+) -> Result<Vec<CashLimitCheck>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let now = Utc::now().to_rfc3339();
+    cash_limits::check(&conn, partner_id.trim(), &cash_register::day_of(&now), &allocations)
+}
+
+// Helper: cash collections must fall on an open cash register day and stay
+// within the cash caps; the other payment methods are not checked.
+fn check_cash_collection(
+    conn: &rusqlite::Connection,
+    payment: &PaymentDetails,
+    casa: &str,
+    partner_id: &str,
+    day: &str,
+    allocations: &[CollectionAllocationRequest],
+) -> Result<Vec<CashLimitCheck>, String> {
+    if !payment_methods::is_cash(&payment.method) {
+        return Ok(Vec::new());
+    }
+    cash_register::ensure_open(conn, casa, day)?;
+    cash_limits::enforce(conn, partner_id, day, allocations)
+}
+
+// Helper: series and number of a new collection. Cash takes the next receipt
+// from the carnet; the other methods keep the number of their own document.
+fn collection_number(
+    conn: &rusqlite::Connection,
+    payment: &PaymentDetails,
+    document_id: &str,
+) -> Result<(Option<String>, String), String> {
+    if payment_methods::is_cash(&payment.method) {
+        let receipt = numbering::allocate(conn, numbering::RECEIPT, document_id)?;
+        Ok((Some(receipt.series), receipt.number.to_string()))
+    } else {
+        Ok((None, payment.reference.clone().unwrap_or_default()))
+    }
+}
+
+#[tauri::command]
+pub fn record_collection(
+    db: State<'_, Database>,
+    collection: Collection,
+) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    let payment = payment_methods::validate(&conn, Some(&collection.payment))?;
+    let casa = cash_register::current_casa(&conn);
+    let cash_warnings = check_cash_collection(
+        &conn,
+        &payment,
+        &casa,
+        &collection.id_partener,
+        &cash_register::day_of(&collection.data_incasare),
+        &[CollectionAllocationRequest {
+            serie_factura: collection.serie_factura.clone(),
+            numar_factura: collection.numar_factura.clone(),
+            cod_document: collection.cod_document.clone(),
+            valoare: collection.valoare,
+        }],
+    )?;
+
+    // Ensure ID is generated if not provided (though frontend should provide UUID)
+    let id = if collection.id.is_empty() {
+        Uuid::new_v4().to_string()
+    } else {
+        collection.id
+    };
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let (receipt_series, receipt_number) = match collection_number(&conn, &payment, &id) {
+        Ok(number) => number,
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = conn.execute(
+        "INSERT INTO collections (
+            id, receipt_group_id, receipt_series, receipt_number,
+            id_partener, partner_name, numar_factura, serie_factura,
+            cod_document, valoare, data_incasare, status, created_at, nume_casa,
+            payment_method, payment_reference, payment_due_date, bank_account
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+        params![
+            id.clone(),
+            id,
+            receipt_series,
+            receipt_number,
+            collection.id_partener,
+            collection.partner_name,
+            collection.numar_factura,
+            collection.serie_factura,
+            collection.cod_document,
+            collection.valoare,
+            collection.data_incasare,
+            "pending",
+            Utc::now().to_rfc3339(),
+            Some(&casa).filter(|_| payment_methods::is_cash(&payment.method)),
+            payment.method,
+            payment.reference,
+            payment.due_date,
+            payment.bank_account
+        ]
+    ) {
+        let _ = conn.execute("ROLLBACK", []);
+        return Err(e.to_string());
+    }
+
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+
+    document_events::record_collection_group(&conn, &id, document_events::CREATED, None);
+    record_cash_limit_warnings(&conn, &id, &cash_warnings);
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn record_collection_group(
+    db: State<'_, Database>,
+    request: CreateCollectionGroupRequest,
+) -> Result<String, String> {
+    let partner_id = request.id_partener.trim().to_string();
+    if partner_id.is_empty() {
+        return Err("Partener invalid pentru încasare".to_string());
+    }
+
+    let advance = request.advance.unwrap_or(0.0);
+    if advance < 0.0 {
+        return Err("Avansul nu poate fi negativ".to_string());
+    }
+    if request.allocations.is_empty() && advance <= 0.0 {
+        return Err("Selectează cel puțin o factură sau introdu un avans".to_string());
+    }
+
+    let current_balances = get_client_balances(db.clone(), Some(partner_id.clone()))?;
+    let mut remaining_map: HashMap<String, f64> = HashMap::new();
+    for balance in current_balances {
+        let key = build_invoice_key(
+            &balance.id_partener,
+            &balance.serie,
+            &balance.numar,
+            &balance.cod_document,
+        );
+        remaining_map.insert(key, balance.rest.unwrap_or(0.0));
+    }
+
+    for allocation in &request.allocations {
+        if allocation.valoare <= 0.0 {
+            return Err("Valoarea pe fiecare factură trebuie să fie mai mare decât 0".to_string());
+        }
+
+        let key = build_invoice_key(
+            &partner_id,
+            &allocation.serie_factura,
+            &allocation.numar_factura,
+            &allocation.cod_document,
+        );
+
+        let remaining = remaining_map
+            .get(&key)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "Factura {} {} nu mai are sold disponibil",
+                    allocation.serie_factura.clone().unwrap_or_default(),
+                    allocation.numar_factura.clone().unwrap_or_default()
+                )
+            })?;
+
+        if allocation.valoare - remaining > 0.0001 {
+            return Err(format!(
+                "Valoarea introdusă depășește soldul disponibil ({:.2}) pentru factura {} {}",
+                remaining,
+                allocation.serie_factura.clone().unwrap_or_default(),
+                allocation.numar_factura.clone().unwrap_or_default()
+            ));
+        }
+    }
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let payment = payment_methods::validate(&conn, request.payment.as_ref())?;
+
+    // The advance is one more row on the receipt, without an invoice
+    let mut rows: Vec<(&str, CollectionAllocationRequest)> = request
+        .allocations
+        .iter()
+        .map(|allocation| (advances::INVOICE, allocation.clone()))
+        .collect();
+    if advance > 0.0 {
+        rows.push((
+            advances::ADVANCE,
+            CollectionAllocationRequest {
+                serie_factura: None,
+                numar_factura: None,
+                cod_document: None,
+                valoare: advance,
+            },
+        ));
+    }
+    let cash: Vec<CollectionAllocationRequest> = rows.iter().map(|(_, allocation)| allocation.clone()).collect();
+
+    let now = Utc::now().to_rfc3339();
+    let casa = cash_register::current_casa(&conn);
+    let cash_warnings = check_cash_collection(&conn, &payment, &casa, &partner_id, &cash_register::day_of(&now), &cash)?;
+    let casa = Some(casa).filter(|_| payment_methods::is_cash(&payment.method));
+
+    let receipt_group_id = Uuid::new_v4().to_string();
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let (receipt_series, receipt_number) = match collection_number(&conn, &payment, &receipt_group_id) {
+        Ok(number) => number,
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(e);
+        }
+    };
+
+    for (transaction_type, allocation) in &rows {
+        let row_id = Uuid::new_v4().to_string();
+        if let Err(e) = conn.execute(
+            "INSERT INTO collections (
+                id, receipt_group_id, receipt_series, receipt_number,
+                id_partener, partner_name, numar_factura, serie_factura,
+                cod_document, valoare, data_incasare, status, created_at, transaction_type, nume_casa,
+                payment_method, payment_reference, payment_due_date, bank_account
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+            params![
+                row_id,
+                &receipt_group_id,
+                &receipt_series,
+                &receipt_number,
+                &partner_id,
+                &request.partner_name,
+                &allocation.numar_factura,
+                &allocation.serie_factura,
+                &allocation.cod_document,
+                allocation.valoare,
+                &now,
+                "pending",
+                &now,
+                transaction_type,
+                &casa,
+                &payment.method,
+                &payment.reference,
+                &payment.due_date,
+                &payment.bank_account
+            ],
+        ) {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(e.to_string());
+        }
+    }
+
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+
+    document_events::record_collection_group(&conn, &receipt_group_id, document_events::CREATED, None);
+    if advance > 0.0 {
+        info!("[ADVANCES] Receipt {} for partner {} carries an advance of {:.2}", receipt_group_id, partner_id, advance);
+    }
+    record_cash_limit_warnings(&conn, &receipt_group_id, &cash_warnings);
+
+    Ok(receipt_group_id)
+}
+
+/// Splits a lump-sum payment over the partner's open invoices following
+/// `strategy` (oldest_due, overdue_first, exact_match). Only a proposal
+/// unless `record` is set, in which case it is recorded as one receipt, any
+/// excess as an advance.
+#[tauri::command]
+pub fn allocate_payment(
+    db: State<'_, Database>,
+    partner_id: String,
+    amount: f64,
+    strategy: String,
+    record: Option<bool>,
+    payment: Option<PaymentDetails>,
+) -> Result<PaymentAllocation, String> {
+    let partner_id = partner_id.trim().to_string();
+    if partner_id.is_empty() {
+        return Err("Partener invalid pentru încasare".to_string());
+    }
+
+    let balances = get_client_balances(db.clone(), Some(partner_id.clone()))?;
+    let today = Local::now().date_naive();
+    let mut allocation = payment_allocation::propose(&balances, amount, &strategy, today)?;
+    info!(
+        "[COLLECTIONS] Payment of {:.2} for partner {} allocated {:.2} over {} invoice(s) ({})",
+        allocation.amount,
+        partner_id,
+        allocation.allocated,
+        allocation.lines.len(),
+        allocation.strategy
+    );
+
+    if !record.unwrap_or(false) {
+        return Ok(allocation);
+    }
+    let partner_name = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row("SELECT name FROM partners WHERE id = ?1", [&partner_id], |row| row.get::<_, String>(0))
+            .ok()
+    }
+    .or_else(|| balances.iter().find_map(|balance| balance.denumire.clone()));
+
+    let request = CreateCollectionGroupRequest {
+        id_partener: partner_id,
+        partner_name,
+        allocations: allocation
+            .lines
+            .iter()
+            .map(|line| CollectionAllocationRequest {
+                serie_factura: line.serie_factura.clone(),
+                numar_factura: line.numar_factura.clone(),
+                cod_document: line.cod_document.clone(),
+                valoare: line.valoare,
+            })
+            .collect(),
+        advance: Some(allocation.unallocated).filter(|advance| *advance > 0.0),
+        payment,
+    };
+    allocation.receipt_group_id = Some(record_collection_group(db, request)?);
+    Ok(allocation)
+}
+
+// ==================== ADVANCE COMMANDS ====================
+
+#[tauri::command]
+pub fn get_partner_advances(db: State<'_, Database>, partner_id: String) -> Result<Vec<PartnerAdvance>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    advances::open_advances(&conn, partner_id.trim()).map_err(|e| e.to_string())
+}
+
+/// Sets what is left of the partner's advances against their open invoices,
+/// oldest due first. Returns the applications recorded, none when there is
+/// nothing to set against.
+#[tauri::command]
+pub fn apply_partner_advances(db: State<'_, Database>, partner_id: String) -> Result<Vec<AdvanceApplication>, String> {
+    let partner_id = partner_id.trim().to_string();
+    let balances = get_client_balances(db.clone(), Some(partner_id.clone()))?;
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let open = advances::open_advances(&conn, &partner_id).map_err(|e| e.to_string())?;
+    let available: f64 = open.iter().map(|advance| advance.available).sum();
+    if open.is_empty() || balances.iter().all(|balance| balance.rest.unwrap_or(0.0) <= 0.005) {
+        return Ok(Vec::new());
+    }
+
+    let proposal = payment_allocation::propose(
+        &balances,
+        available,
+        payment_allocation::OLDEST_DUE,
+        Local::now().date_naive(),
+    )?;
+    let group_id = advances::apply(&conn, &partner_id, &open, &proposal.lines)?;
+    advances::applications(&conn, &group_id).map_err(|e| e.to_string())
+}
+
+/// Sends advances set against invoices to WME. Each advance is moved from the
+/// partner's account onto the invoices on a compensation receipt numbered when
+/// the applications were recorded (the advance receipt is already in WME): a
+/// transaction that brings no new money, with a negative advance line and the
+/// invoices it pays.
+/// Applications whose advance is not in WME yet wait for it.
+#[tauri::command]
+pub async fn send_advance_applications(
+    db: State<'_, Database>,
+    group_id: String,
+) -> Result<Vec<AdvanceApplication>, String> {
+    let settings: AgentSettings = get_agent_settings(db.clone())?;
+
+    let (pending, receipts, api) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let pending: Vec<AdvanceApplication> = advances::applications(&conn, &group_id)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|application| application.status == "pending")
+            .collect();
+        if pending.is_empty() {
+            return advances::applications(&conn, &group_id).map_err(|e| e.to_string());
+        }
+
+        // Advance id -> (compensation receipt series, number, status and receipt of the advance)
+        let mut receipts: Vec<(String, String, String, String, String)> = Vec::new();
+        for application in &pending {
+            if receipts.iter().any(|(id, ..)| id == &application.advance_id) {
+                continue;
+            }
+            let (status, advance_receipt) = conn
+                .query_row(
+                    "SELECT status, TRIM(COALESCE(receipt_series, '') || ' ' || COALESCE(receipt_number, ''))
+                     FROM collections WHERE id = ?1",
+                    [&application.advance_id],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                )
+                .map_err(|e| e.to_string())?;
+            receipts.push((
+                application.advance_id.clone(),
+                application.receipt_series.clone().unwrap_or_default(),
+                application.receipt_number.clone().unwrap_or_default(),
+                status,
+                advance_receipt,
+            ));
+        }
+
+        if receipts.iter().any(|(_, _, _, status, _)| status != "synced") {
+            conn.execute(
+                "UPDATE advance_applications SET error_message = ?1 WHERE group_id = ?2 AND status = 'pending'",
+                params!["Avansul nu a fost încă trimis în WME", group_id],
+            )
+            .map_err(|e| e.to_string())?;
+            info!("[ADVANCES] Group {} waits for its advance receipt to be synced", group_id);
+            return advances::applications(&conn, &group_id).map_err(|e| e.to_string());
+        }
+
+        // Each advance is compensated where it was paid: cash desk or bank
+        let bookings = receipts
+            .iter()
+            .map(|(advance_id, ..)| receipt_booking(&conn, &settings, advance_id))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        (pending, receipts.into_iter().zip(bookings).collect::<Vec<_>>(), get_wme_api_client(&conn)?)
+    };
+
+    let now = Local::now();
+    let doc_date = now.format("%d.%m.%Y").to_string();
+    let partner_id = pending[0].id_partener.clone();
+
+    let documente = receipts
+        .iter()
+        .map(|((advance_id, series, number, _, advance_receipt), booking)| {
+            let taken: Vec<&AdvanceApplication> =
+                pending.iter().filter(|application| &application.advance_id == advance_id).collect();
+            let mut distribuire_valoare = vec![api_client::DistribuireValoare {
+                reprezinta: "Avans".to_string(),
+                numar_factura: "".to_string(),
+                serie_factura: "".to_string(),
+                termen_factura: "".to_string(),
+                valoare: -taken.iter().map(|application| application.valoare).sum::<f64>(),
+            }];
+            distribuire_valoare.extend(taken.iter().map(|application| api_client::DistribuireValoare {
+                reprezinta: "Factura".to_string(),
+                numar_factura: application.numar_factura.clone().unwrap_or_default(),
+                serie_factura: application.serie_factura.clone().unwrap_or_default(),
+                termen_factura: "".to_string(),
+                valoare: application.valoare,
+            }));
+            booking.document(
+                &doc_date,
+                vec![api_client::CasaBancaTranzactie {
+                    tip_tranzactie: "Incasare".to_string(),
+                    diferenta_pe_avans: "DA".to_string(),
+                    tip_doc: booking.tip_doc(),
+                    serie_doc: series.clone(),
+                    nr_doc: number.clone(),
+                    obiect_tranzactie: "Client".to_string(),
+                    data: doc_date.clone(),
+                    curs: 1.0,
+                    id_partener: partner_id.clone(),
+                    valoare: 0.0,
+                    obs: format!("Avans compensat din {}", advance_receipt),
+                    anulat: "NU".to_string(),
+                    distribuire_valoare,
+                }],
+            )
+        })
+        .collect();
+
+    let request = api_client::CasaBancaRequest {
+        an_lucru: now.year(),
+        luna_lucru: now.month() as i32,
+        cod_subunitate: None,
+        documente,
+    };
+
+    let result = api.send_collections_to_wme(request).await;
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    match result {
+        Ok(response) if response.result.as_deref().unwrap_or("").eq_ignore_ascii_case("ok") || response.error_list.is_empty() => {
+            conn.execute(
+                "UPDATE advance_applications SET status = 'synced', synced_at = ?1, error_message = NULL
+                 WHERE group_id = ?2 AND status = 'pending'",
+                params![Utc::now().to_rfc3339(), group_id],
+            )
+            .map_err(|e| e.to_string())?;
+            info!("[ADVANCES] Group {} sent to WME", group_id);
+        }
+        Ok(response) => {
+            let message = format!("API Error: {}; {:?}", response.result.unwrap_or_default(), response.error_list);
+            warn!("[ADVANCES] Group {} refused by WME: {}", group_id, message);
+            conn.execute(
+                "UPDATE advance_applications SET error_message = ?1 WHERE group_id = ?2 AND status = 'pending'",
+                params![message, group_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Err(e) => {
+            warn!("[ADVANCES] Sending group {} failed: {}", group_id, e);
+            conn.execute(
+                "UPDATE advance_applications SET error_message = ?1 WHERE group_id = ?2 AND status = 'pending'",
+                params![e, group_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    advances::applications(&conn, &group_id).map_err(|e| e.to_string())
+}
+
+// Helper: caps set to warn that a receipt went over stay in its history
+fn record_cash_limit_warnings(conn: &rusqlite::Connection, group_id: &str, warnings: &[CashLimitCheck]) {
+    for warning in warnings {
+        document_events::record_collection_group(
+            conn,
+            group_id,
+            document_events::CASH_LIMIT_WARNING,
+            Some(&warning.message),
+        );
+    }
+}
+
+// Helper: cancelled invoices keep their number but can no longer be collected
+fn invoice_is_cancelled(conn: &rusqlite::Connection, invoice_id: &str) -> bool {
+    conn.query_row(
+        "SELECT status FROM invoices WHERE id = ?1",
+        [invoice_id],
+        |row| row.get::<_, String>(0),
+    )
+    .map(|status| status == "cancelled")
+    .unwrap_or(false)
+}
+
+#[tauri::command]
+pub fn record_collection_from_invoice(
+    db: State<'_, Database>,
+    invoice_id: String,
+    paid_amount: f64,
+    payment: Option<PaymentDetails>,
+) -> Result<String, String> {
+    if paid_amount <= 0.0 {
+        return Err("Suma încasată trebuie să fie mai mare decât 0".to_string());
+    }
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let payment = payment_methods::validate(&conn, payment.as_ref())?;
+
+    if invoice_is_cancelled(&conn, &invoice_id) {
+        return Err("Factura este anulată și nu mai poate fi încasată.".to_string());
+    }
+
+    let (partner_id, partner_name, invoice_number, _total_amount, invoice_series, carnet_series): (
+        String,
+        String,
+        i64,
+        f64,
+        Option<String>,
+        Option<String>,
+    ) = conn
+        .query_row(
+            r#"
+            SELECT i.partner_id, p.name, i.invoice_number, i.total_amount,
+                   i.invoice_series, (SELECT carnet_series FROM agent_settings WHERE id = 1)
+            FROM invoices i
+            JOIN partners p ON p.id = i.partner_id
+            WHERE i.id = ?1
+            "#,
+            [&invoice_id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            },
+        )
+        .map_err(|e| format!("Factura nu a fost găsită: {}", e))?;
+
+    // Calculate Gross Total (Total with VAT)
+    let mut stmt_items = conn
+        .prepare(
+            "SELECT ii.total_price, p.procent_tva \
+             FROM invoice_items ii \
+             JOIN products p ON ii.product_id = p.id \
+             WHERE ii.invoice_id = ?1",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let gross_total: f64 = stmt_items
+        .query_map([&invoice_id], |row| {
+            let price: f64 = row.get(0)?;
+            let tva_str: Option<String> = row.get(1)?;
+            let tva_percent = tva_str
+                .and_then(|s| s.parse::<f64>().ok())
+                .unwrap_or(0.0);
+
+            let vat_amount = price * (tva_percent / 100.0);
+            Ok(price + vat_amount)
+        })
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .sum();
+
+    // Drop the statement to release the borrow on conn
+    drop(stmt_items);
+
+    // Allow a small epsilon for floating point comparison
+    const EPSILON: f64 = 0.01;
+
+    // Use gross_total for validation instead of total_amount (which is net)
+    if paid_amount > (gross_total + EPSILON) {
+        return Err(format!(
+            "Suma încasată ({:.2}) nu poate depăși totalul facturii cu TVA ({:.2})",
+            paid_amount,
+            gross_total
+        ));
+    }
+
+    let invoice_number_str = invoice_number.to_string();
+    let series = invoice_carnet_series(invoice_series.as_deref(), Some(carnet_series.as_deref().unwrap_or("FACTURA")));
+    let now = Utc::now().to_rfc3339();
+    let casa = cash_register::current_casa(&conn);
+    let cash_warnings = check_cash_collection(
+        &conn,
+        &payment,
+        &casa,
+        &partner_id,
+        &cash_register::day_of(&now),
+        &[CollectionAllocationRequest {
+            serie_factura: Some(series.clone()),
+            numar_factura: Some(invoice_number_str.clone()),
+            cod_document: Some(invoice_number_str.clone()),
+            valoare: paid_amount,
+        }],
+    )?;
+
+    let collected_total: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE
+             id_partener = ?1 AND numar_factura = ?2 AND cod_document = ?3 AND
+             status IN ('pending', 'sending', 'synced')",
+            params![&partner_id, &invoice_number_str, &invoice_number_str],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    // Calculate remaining based on Gross Total
+    let remaining = (gross_total - collected_total).max(0.0);
+
+    if remaining <= EPSILON {
+        return Err("Factura este deja încasată integral".to_string());
+    }
+
+    if paid_amount > (remaining + EPSILON) {
+        return Err(format!(
+            "Suma încasată ({:.2}) depășește restul disponibil ({:.2})",
+            paid_amount,
+            remaining
+        ));
+    }
+
+    let collection_id = Uuid::new_v4().to_string();
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+        .map_err(|e| e.to_string())?;
+
+    let (receipt_series, receipt_number) = match collection_number(&conn, &payment, &collection_id) {
+        Ok(number) => number,
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = conn.execute(
+        "INSERT INTO collections (
+            id, receipt_group_id, receipt_series, receipt_number,
+            id_partener, partner_name, numar_factura, serie_factura,
+            cod_document, valoare, data_incasare, status, created_at, nume_casa,
+            payment_method, payment_reference, payment_due_date, bank_account
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+        params![
+            &collection_id,
+            &collection_id,
             receipt_series,
             receipt_number,
-            collection.id_partener,
-            collection.partner_name,
-            collection.numar_factura,
-            collection.serie_factura,
-            collection.cod_document,
-            collection.valoare,
-            collection.data_incasare,
+            &partner_id,
+            &partner_name,
+            &invoice_number_str,
+            &series,
+            &invoice_number_str,
+            paid_amount,
+            &now,
             "pending",
-            Utc::now().to_rfc3339()
-        ]
-    ).map_err(|e| e.to_string())?;
+            &now,
+            Some(&casa).filter(|_| payment_methods::is_cash(&payment.method)),
+            &payment.method,
+            &payment.reference,
+            &payment.due_date,
+            &payment.bank_account,
+        ],
+    ) {
+        let _ = conn.execute("ROLLBACK", []);
+        return Err(e.to_string());
+    }
+
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+
+    document_events::record_collection_group(&conn, &collection_id, document_events::CREATED, None);
+    record_cash_limit_warnings(&conn, &collection_id, &cash_warnings);
+
+    Ok(collection_id)
+}
+
+#[tauri::command]
+pub fn get_invoice_remaining_for_collection(
+    db: State<'_, Database>,
+    invoice_id: String,
+) -> Result<f64, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    if invoice_is_cancelled(&conn, &invoice_id) {
+        return Ok(0.0);
+    }
+
+    let (partner_id, invoice_number, total_amount): (String, i64, f64) = conn
+        .query_row(
+            r#"
+            SELECT i.partner_id, i.invoice_number, i.total_amount
+            FROM invoices i
+            WHERE i.id = ?1
+            "#,
+            [&invoice_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("Factura nu a fost găsită: {}", e))?;
+
+    let invoice_number_str = invoice_number.to_string();
+
+    let collected_total: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE
+             id_partener = ?1 AND numar_factura = ?2 AND cod_document = ?3 AND
+             status IN ('pending', 'sending', 'synced')",
+            params![&partner_id, &invoice_number_str, &invoice_number_str],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok((total_amount - collected_total).max(0.0))
+}
+
+#[tauri::command]
+pub fn get_collections(
+    db: State<'_, Database>,
+    status_filter: Option<String>,
+) -> Result<Vec<Collection>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    let query = r#"
+        SELECT
+            COALESCE(receipt_group_id, id) AS group_id,
+            id_partener,
+            MAX(partner_name) AS partner_name,
+            MAX(numar_factura) AS first_numar_factura,
+            MAX(serie_factura) AS first_serie_factura,
+            MAX(cod_document) AS first_cod_document,
+            SUM(valoare) AS total_valoare,
+            MAX(data_incasare) AS data_incasare,
+            SUM(CASE WHEN status = 'sending' THEN 1 ELSE 0 END) AS cnt_sending,
+            SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS cnt_failed,
+            SUM(CASE WHEN status = 'pending' THEN 1 ELSE 0 END) AS cnt_pending,
+            MAX(synced_at) AS synced_at,
+            MAX(error_message) AS error_message,
+            MAX(created_at) AS created_at,
+            MAX(receipt_series) AS receipt_series,
+            MAX(receipt_number) AS receipt_number,
+            COUNT(*) AS invoice_count,
+            SUM(CASE WHEN status = 'cancelled' THEN 1 ELSE 0 END) AS cnt_cancelled,
+            MAX(payment_method) AS payment_method,
+            MAX(payment_reference) AS payment_reference,
+            MAX(payment_due_date) AS payment_due_date,
+            MAX(bank_account) AS bank_account
+        FROM collections
+        GROUP BY COALESCE(receipt_group_id, id), id_partener
+        ORDER BY MAX(created_at) DESC
+    "#;
+
+    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            let cnt_sending: i64 = row.get(8)?;
+            let cnt_failed: i64 = row.get(9)?;
+            let cnt_pending: i64 = row.get(10)?;
+            let invoice_count: i64 = row.get(16)?;
+            let cnt_cancelled: i64 = row.get(17)?;
+
+            let status = if cnt_cancelled == invoice_count {
+                CollectionStatus::Cancelled
+            } else if cnt_sending > 0 {
+                CollectionStatus::Sending
+            } else if cnt_failed > 0 {
+                CollectionStatus::Failed
+            } else if cnt_pending > 0 {
+                CollectionStatus::Pending
+            } else {
+                CollectionStatus::Synced
+            };
+
+            let first_numar_factura: Option<String> = row.get(3)?;
+            let first_serie_factura: Option<String> = row.get(4)?;
+            let first_cod_document: Option<String> = row.get(5)?;
+            let receipt_series: Option<String> = row.get(14)?;
+            let receipt_number: Option<String> = row.get(15)?;
+
+            let numar_factura = if invoice_count > 1 {
+                Some(format!("{} facturi", invoice_count))
+            } else {
+                first_numar_factura
+            };
+
+            let serie_factura = if invoice_count > 1 {
+                receipt_series.clone()
+            } else {
+                first_serie_factura
+            };
+
+            Ok(Collection {
+                id: row.get(0)?,
+                id_partener: row.get(1)?,
+                partner_name: row.get(2)?,
+                numar_factura,
+                serie_factura,
+                cod_document: receipt_number.clone().or(first_cod_document),
+                valoare: row.get(6)?,
+                data_incasare: row.get(7)?,
+                status,
+                synced_at: row.get(11)?,
+                error_message: row.get(12)?,
+                created_at: row.get(13)?,
+                receipt_series,
+                receipt_number,
+                payment: payment_methods::from_row(row, 18)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        let collection = row.map_err(|e| e.to_string())?;
+        if let Some(filter) = &status_filter {
+            if collection.status.to_string() != *filter {
+                continue;
+            }
+        }
+        result.push(collection);
+    }
+
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn sync_collections(
+    db: State<'_, Database>,
+) -> Result<SyncStatus, String> {
+    use std::sync::atomic::Ordering;
+
+    // Prevent concurrent runs
+    if db.is_syncing_collections.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+        return get_sync_status(db);
+    }
+
+    struct LockGuard<'a>(&'a std::sync::atomic::AtomicBool);
+    impl Drop for LockGuard<'_> {
+        fn drop(&mut self) {
+            self.0.store(false, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+    let _guard = LockGuard(&db.is_syncing_collections);
+
+    // Only retry pending — failed must be retried manually
+    let pending_collections = get_collections(db.clone(), Some("pending".to_string()))?;
+
+    if pending_collections.is_empty() {
+        return get_sync_status(db.clone());
+    }
+
+    info!("[SYNC] Trimitere {} chitante in asteptare...", pending_collections.len());
+
+    let mut sent = 0;
+    let mut errors = 0;
+
+    for collection in pending_collections {
+        match send_collection(db.clone(), collection.id.clone()).await {
+            Ok(_) => sent += 1,
+            Err(_) => errors += 1,
+        }
+    }
+
+    if sent > 0 || errors > 0 {
+        info!("[SYNC] Chitante: {} trimise, {} esuate", sent, errors);
+    }
+
+    get_sync_status(db.clone())
+}
+
+#[tauri::command]
+pub async fn send_collection(
+    db: State<'_, Database>,
+    collection_id: String,
+) -> Result<Collection, String> {
+    use chrono::Datelike;
+    info!("[CHITANTE][SEND] Start send_collection for id/group={}", collection_id);
+
+    let settings: AgentSettings = get_agent_settings(db.clone())?;
+
+    let rows = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT
+                    id,
+                    COALESCE(receipt_group_id, id) as receipt_group_id,
+                    receipt_series,
+                    receipt_number,
+                    id_partener,
+                    partner_name,
+                    numar_factura,
+                    serie_factura,
+                    cod_document,
+                    valoare,
+                    data_incasare,
+                    status,
+                    synced_at,
+                    error_message,
+                    created_at,
+                    transaction_type
+                 FROM collections
+                 WHERE COALESCE(receipt_group_id, id) = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mapped = stmt
+            .query_map([&collection_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                    row.get::<_, f64>(9)?,
+                    row.get::<_, String>(10)?,
+                    row.get::<_, String>(11)?,
+                    row.get::<_, Option<String>>(12)?,
+                    row.get::<_, Option<String>>(13)?,
+                    row.get::<_, String>(14)?,
+                    row.get::<_, String>(15)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut result = Vec::new();
+        for row in mapped {
+            result.push(row.map_err(|e| e.to_string())?);
+        }
+        result
+    };
+
+    if rows.is_empty() {
+        return Err("Chitanța nu a fost găsită".to_string());
+    }
+
+    let booking = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        receipt_booking(&conn, &settings, &collection_id)?
+    };
+    let cash = payment_methods::is_cash(&booking.payment.method);
+
+    let receipt_group_id = rows[0].1.clone();
+    // Non-cash collections carry the number of their own document, no series
+    let receipt_series = if cash {
+        rows[0]
+            .2
+            .clone()
+            .unwrap_or_else(|| settings.carnet_series.clone().unwrap_or_else(|| "CH".to_string()))
+    } else {
+        String::new()
+    };
+    let receipt_number = rows[0]
+        .3
+        .clone()
+        .or_else(|| rows[0].8.clone())
+        .unwrap_or_else(|| chrono::Local::now().format("%Y%m%d%H%M%S").to_string());
+    let partner_id = rows[0].4.clone();
+    let partner_name = rows[0].5.clone();
+    let doc_date_source = rows[0].10.clone();
+
+    let total_value: f64 = rows.iter().map(|r| r.9).sum();
+    let invoice_count = rows.len();
+
+    let collection_for_print = Collection {
+        id: receipt_group_id.clone(),
+        id_partener: partner_id.clone(),
+        partner_name: partner_name.clone(),
+        numar_factura: if invoice_count > 1 {
+            Some(format!("{} facturi", invoice_count))
+        } else {
+            rows[0].6.clone()
+        },
+        serie_factura: if invoice_count > 1 {
+            rows[0].2.clone()
+        } else {
+            rows[0].7.clone()
+        },
+        cod_document: Some(receipt_number.clone()),
+        valoare: total_value,
+        data_incasare: doc_date_source.clone(),
+        status: CollectionStatus::Pending,
+        synced_at: None,
+        error_message: None,
+        created_at: rows[0].14.clone(),
+        receipt_series: Some(receipt_series.clone()),
+        receipt_number: Some(receipt_number.clone()),
+        payment: booking.payment.clone(),
+    };
+
+    let now = Utc::now();
+    let issue_date_for_print = if let Ok(d) = chrono::NaiveDateTime::parse_from_str(&doc_date_source, "%Y-%m-%dT%H:%M:%S%.fZ") {
+        d.format("%d.%m.%Y").to_string()
+    } else {
+        now.format("%d.%m.%Y").to_string()
+    };
+
+    // Only a cash collection has a receipt to keep
+    let saved_receipt = if cash {
+        let (partner_cui, partner_reg_com, partner_address, partner_localitate, partner_judet, issuer, code) = {
+            let conn = db.conn.lock().map_err(|e| e.to_string())?;
+            let (cui, reg_com, address, localitate, judet) = get_partner_receipt_info(&conn, &partner_id);
+            let issuer = company::issuer(&conn);
+            let code = receipt_code(
+                &conn,
+                &collection_for_print,
+                &receipt_series,
+                &receipt_number,
+                &issue_date_for_print,
+                &issuer,
+                &receipt_group_id,
+            );
+            (cui, reg_com, address, localitate, judet, issuer, code)
+        };
+
+        let (saved_html_path, _) = save_receipt_html_file(
+            &collection_for_print,
+            &issuer,
+            &code,
+            print_receipt::ReceiptDetails {
+                series: &receipt_series,
+                number: &receipt_number,
+                issue_date: &issue_date_for_print,
+                agent: settings.agent_name.as_deref(),
+                partner_address: partner_address.as_deref(),
+                partner_localitate: partner_localitate.as_deref(),
+                partner_judet: partner_judet.as_deref(),
+                partner_cui: partner_cui.as_deref(),
+                partner_reg_com: partner_reg_com.as_deref(),
+            },
+            &booking.nume_casa,
+            &receipt_group_id,
+        )?;
+
+        info!(
+            "[CHITANTE][SEND] Receipt snapshot saved before API call: {}",
+            saved_html_path
+        );
+        format!(". Chitanță salvată: {}", saved_html_path)
+    } else {
+        String::new()
+    };
+
+    let lines: Vec<ReceiptLine> = rows
+        .iter()
+        .map(|r| ReceiptLine {
+            numar_factura: r.6.clone(),
+            serie_factura: r.7.clone(),
+            valoare: r.9,
+            transaction_type: r.15.clone(),
+        })
+        .collect();
+
+    info!(
+        "[CHITANTE][SEND] Loaded group {} partner={:?} allocations={} total={} marca_agent={:?} nume_casa={:?} payment={}",
+        receipt_group_id,
+        partner_name,
+        rows.len(),
+        total_value,
+        booking.agent,
+        booking.nume_casa,
+        booking.payment.method
+    );
+
+    {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+        // Check if we can transition to sending state
+        // Only allow if not already sending, synced or cancelled
+        // This prevents race conditions when multiple sync triggers happen
+        let affected = conn.execute(
+            "UPDATE collections SET status = 'sending' WHERE COALESCE(receipt_group_id, id) = ?1 AND status NOT IN ('sending', 'synced', 'cancelled')",
+            [&receipt_group_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+        if affected == 0 {
+            info!("[CHITANTE][SEND] Collection group {} is already sending, synced or cancelled. Skipping send.", receipt_group_id);
+            return Ok(collection_for_print);
+        }
+    }
+
+    let api = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        get_wme_api_client(&conn)?
+    };
+    let request = receipt_casa_banca_request(
+        &booking,
+        &partner_id,
+        (&receipt_series, &receipt_number),
+        &issue_date_for_print,
+        (now.year(), now.month() as i32),
+        &lines,
+        "NU",
+    );
+
+    if let Ok(payload) = serde_json::to_string_pretty(&request) {
+        info!("[CHITANTE][SEND] CasaBanca payload for group {}:\n{}", receipt_group_id, payload);
+    }
+
+    let now_str = Utc::now().to_rfc3339();
+
+    // DUPLICATE PREVENTION: Check if invoice is already paid in WME before sending receipt
+    // This handles the case where a previous attempt succeeded on server but failed to return OK to client
+    let _start_check = std::time::Instant::now();
+    // ApiClient already created above
+
+    // We need to check the balance for the partner to see if the invoice is still unpaid
+    // Filters for get_solduri_clienti
+    let check_filter = api_client::SolduriFilterRequest {
+        id_partener: Some(partner_id.clone()),
+        marca_agent: None,
+        paginare: None,
+    };
+
+    let mut already_paid = false;
+
+    // Only perform this check if we are retrying (status was pending/failed) or just always to be safe?
+    // Always checking is safer but adds latency. Given this is "Send" action initiated by user or sync, latency is acceptable for safety.
+    info!("[CHITANTE][SEND] Checking WME balance for partner {} to prevent duplicates...", partner_id);
+
+    match api.get_solduri_clienti(check_filter).await {
+        Ok(response) => {
+            // Check if our invoices exist in the balance list with remaining amount
+            // If they are missing or have rest=0, they are paid.
+
+            // Collect all invoice numbers from this receipt group
+            let invoice_numbers: std::collections::HashSet<String> = rows.iter()
+                .filter_map(|r| r.6.clone()) // numar_factura
+                .collect();
+
+            if !invoice_numbers.is_empty() {
+                // Find these invoices in the response
+                let mut found_unpaid_amount = 0.0;
+                let mut found_invoices_count = 0;
+
+                for sold in &response.info_solduri {
+                    let sold_numar = sold.numar.as_deref().unwrap_or_default().trim();
+                    if invoice_numbers.contains(sold_numar) {
+                        let rest = api_client::parse_f64(&sold.rest);
+                        if rest > 0.01 {
+                            found_unpaid_amount += rest;
+                            found_invoices_count += 1;
+                        }
+                    }
+                }
+
+                info!("[CHITANTE][SEND] Balance check: Found {} matching unpaid invoices with total rest {:.2}. Receipt total: {:.2}",
+                    found_invoices_count, found_unpaid_amount, total_value);
+
+                // IMPORTANT: Only mark as duplicate if we FOUND matching invoices in WME AND they appear already paid.
+                // If found_invoices_count == 0, the invoice might be a LOCAL invoice (not in WME solduri yet),
+                // so we MUST NOT assume it's paid — we should proceed with sending.
+                if found_invoices_count > 0 && found_unpaid_amount < (total_value - 0.5) {
+                    warn!("[CHITANTE][SEND] DETECTED DUPLICATE! WME Rest ({:.2}) < Receipt Value ({:.2}). Marking as synced.", found_unpaid_amount, total_value);
+                    already_paid = true;
+                } else if found_invoices_count == 0 {
+                    info!("[CHITANTE][SEND] No matching invoices found in WME solduri — likely a local invoice. Proceeding with send.");
+                }
+
+
+            } else {
+                 info!("[CHITANTE][SEND] No specific invoice numbers to check (advance payment?). Proceeding with send.");
+            }
+        },
+        Err(e) => {
+            warn!("[CHITANTE][SEND] Failed to check balance before sending: {}. Proceeding with send to be safe.", e);
+            // If check fails (e.g. timeout), we proceed with send.
+            // Worst case: duplicate.
+            // Better than blocking payment if check API is down.
+        }
+    }
+
+    if already_paid {
+         // Skip sending to API, just mark as synced
+         let conn = db.conn.lock().map_err(|e| e.to_string())?;
+         conn.execute(
+            "UPDATE collections SET status = 'synced', synced_at = ?1, error_message = NULL WHERE COALESCE(receipt_group_id, id) = ?2",
+            params![now_str, receipt_group_id],
+        )
+        .map_err(|e| e.to_string())?;
 
-    Ok(id)
-}
+        // Return updated collection
+        drop(conn); // Drop lock
+        let grouped = get_collections(db.clone(), None)?;
+        let updated = grouped
+            .into_iter()
+            .find(|c| c.id == receipt_group_id)
+            .ok_or_else(|| "Nu s-a putut încărca chitanța actualizată".to_string())?;
 
-#[tauri::command]
-pub fn record_collection_group(
-    db: State<'_, Database>,
-    request: CreateCollectionGroupRequest,
-) -> Result<String, String> {
-    let partner_id = request.id_partener.trim().to_string();
-    if partner_id.is_empty() {
-        return Err("Partener invalid pentru încasare".to_string());
+        return Ok(updated);
     }
 
-    if request.allocations.is_empty() {
-        return Err("Selectează cel puțin o factură".to_string());
-    }
+    // Actually send the request (previously missing variable 'request' is now defined above)
+    let api_result = api.send_collections_to_wme(request).await;
 
-    let current_balances = get_client_balances(db.clone(), Some(partner_id.clone()))?;
-    let mut remaining_map: HashMap<String, f64> = HashMap::new();
-    for balance in current_balances {
-        let key = build_invoice_key(
-            &balance.id_partener,
-            &balance.serie,
-            &balance.numar,
-            &balance.cod_document,
-        );
-        remaining_map.insert(key, balance.rest.unwrap_or(0.0));
-    }
+    match api_result {
+        Ok(response) => {
+            info!("[CHITANTE][SEND] CasaBanca response for group {} result={:?} errors={:?}", receipt_group_id, response.result, response.error_list);
+            let conn = db.conn.lock().map_err(|e| e.to_string())?;
+            let err_list = response.result.unwrap_or("".to_string());
 
-    for allocation in &request.allocations {
-        if allocation.valoare <= 0.0 {
-            return Err("Valoarea pe fiecare factură trebuie să fie mai mare decât 0".to_string());
+            if err_list.to_lowercase() == "ok" || response.error_list.is_empty() {
+                conn.execute(
+                    "UPDATE collections SET status = 'synced', synced_at = ?1, error_message = NULL WHERE COALESCE(receipt_group_id, id) = ?2",
+                    params![now_str, receipt_group_id],
+                )
+                .map_err(|e| e.to_string())?;
+            } else {
+                let err_msg = format!(
+                    "API Error: {}; {:?}{}",
+                    err_list,
+                    response.error_list,
+                    saved_receipt
+                );
+                conn.execute(
+                    "UPDATE collections SET status = 'failed', error_message = ?1 WHERE COALESCE(receipt_group_id, id) = ?2",
+                    params![err_msg, receipt_group_id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        Err(e) => {
+            info!("[CHITANTE][SEND] CasaBanca call failed for group {} error={}", receipt_group_id, e);
+            let conn = db.conn.lock().map_err(|err| err.to_string())?;
+            let err_msg = format!("{}{}", e, saved_receipt);
+            conn.execute(
+                "UPDATE collections SET status = 'pending', error_message = ?1 WHERE COALESCE(receipt_group_id, id) = ?2",
+                params![err_msg, receipt_group_id],
+            )
+            .map_err(|err| err.to_string())?;
         }
+    }
 
-        let key = build_invoice_key(
-            &partner_id,
-            &allocation.serie_factura,
-            &allocation.numar_factura,
-            &allocation.cod_document,
-        );
+    let grouped = get_collections(db.clone(), None)?;
+    let updated = grouped
+        .into_iter()
+        .find(|c| c.id == receipt_group_id)
+        .ok_or_else(|| "Nu s-a putut încărca chitanța actualizată".to_string())?;
 
-        let remaining = remaining_map
-            .get(&key)
-            .copied()
-            .ok_or_else(|| {
-                format!(
-                    "Factura {} {} nu mai are sold disponibil",
-                    allocation.serie_factura.clone().unwrap_or_default(),
-                    allocation.numar_factura.clone().unwrap_or_default()
-                )
-            })?;
+    info!(
+        "[CHITANTE][SEND] Finished send_collection group={} final_status={} error={:?}",
+        updated.id,
+        updated.status.to_string(),
+        updated.error_message
+    );
 
-        if allocation.valoare - remaining > 0.0001 {
-            return Err(format!(
-                "Valoarea introdusă depășește soldul disponibil ({:.2}) pentru factura {} {}",
-                remaining,
-                allocation.serie_factura.clone().unwrap_or_default(),
-                allocation.numar_factura.clone().unwrap_or_default()
-            ));
+    Ok(updated)
+}
+
+/// A receipt row as sent to WME.
+struct ReceiptLine {
+    numar_factura: Option<String>,
+    serie_factura: Option<String>,
+    valoare: f64,
+    transaction_type: String,
+}
+
+// Where a collection is booked in WME: the cash desk it was collected into,
+// or the bank account of a non-cash payment.
+struct ReceiptBooking {
+    agent: String,
+    nume_casa: String,
+    payment: PaymentDetails,
+}
+
+impl ReceiptBooking {
+    fn document(&self, doc_date: &str, tranzactii: Vec<api_client::CasaBancaTranzactie>) -> api_client::CasaBancaDocument {
+        let cash = payment_methods::is_cash(&self.payment.method);
+        api_client::CasaBancaDocument {
+            sursa: payment_methods::wme_source(&self.payment.method).to_string(),
+            nume_casa: if cash { self.nume_casa.clone() } else { "".to_string() },
+            numar_cont: if cash { "".to_string() } else { self.payment.bank_account.clone().unwrap_or_default() },
+            data: doc_date.to_string(),
+            agent: self.agent.clone(),
+            moneda: "RON".to_string(),
+            document_cumulativ: "".to_string(),
+            tranzactii,
         }
     }
 
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    fn tip_doc(&self) -> String {
+        payment_methods::wme_document_type(&self.payment.method).to_string()
+    }
+
+    // Due date of a promissory note or cheque, for the accountant
+    fn obs(&self) -> String {
+        self.payment
+            .due_date
+            .as_deref()
+            .map(|due_date| format!("Scadență {}", due_date))
+            .unwrap_or_default()
+    }
+}
 
-    let (receipt_series_opt, carnet_series_opt): (Option<String>, Option<String>) = conn
+// Helper: booking of the collection (row id or receipt group). Cash goes to
+// the cash desk it was recorded in, older rows to the one in the settings.
+fn receipt_booking(
+    conn: &rusqlite::Connection,
+    settings: &AgentSettings,
+    collection_id: &str,
+) -> Result<ReceiptBooking, String> {
+    let nume_casa: Option<String> = conn
         .query_row(
-            "SELECT receipt_series, carnet_series FROM agent_settings WHERE id = 1",
-            [],
-            |row| Ok((row.get(0).ok(), row.get(1).ok()))
+            "SELECT nume_casa FROM collections WHERE COALESCE(receipt_group_id, id) = ?1 OR id = ?1 LIMIT 1",
+            [collection_id],
+            |row| row.get(0),
         )
-        .unwrap_or_default();
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten();
+    Ok(ReceiptBooking {
+        agent: settings.marca_agent.clone().unwrap_or_default(),
+        nume_casa: nume_casa
+            .or_else(|| settings.nume_casa.clone())
+            .unwrap_or_else(|| cash_register::DEFAULT_CASA.to_string()),
+        payment: payment_methods::of_collection(conn, collection_id).map_err(|e| e.to_string())?,
+    })
+}
 
-    let receipt_series = receipt_series_opt
-        .clone()
-        .filter(|s| !s.trim().is_empty())
-        .or(carnet_series_opt)
-        .unwrap_or_else(|| "CH".to_string());
+// Helper: CasaBanca request for a receipt. `anulat` is "DA" to reverse a
+// receipt already booked in WME, sent again with the same series and number.
+fn receipt_casa_banca_request(
+    booking: &ReceiptBooking,
+    partner_id: &str,
+    (receipt_series, receipt_number): (&str, &str),
+    doc_date: &str,
+    (an_lucru, luna_lucru): (i32, i32),
+    lines: &[ReceiptLine],
+    anulat: &str,
+) -> api_client::CasaBancaRequest {
+    // The advance is what the receipt brings over its invoices; WME books it
+    // on the partner's account from DiferentaPeAvans
+    let has_advance = lines.iter().any(|line| line.transaction_type == advances::ADVANCE);
+    let distribuire_valoare = lines
+        .iter()
+        .filter(|line| line.transaction_type != advances::ADVANCE)
+        .map(|line| api_client::DistribuireValoare {
+            reprezinta: "Factura".to_string(),
+            numar_factura: line.numar_factura.clone().unwrap_or_default(),
+            serie_factura: line.serie_factura.clone().unwrap_or_default(),
+            termen_factura: "".to_string(),
+            valoare: line.valoare,
+        })
+        .collect();
 
-    let receipt_number = generate_receipt_number(&conn)?;
-    let receipt_group_id = Uuid::new_v4().to_string();
-    let now = Utc::now().to_rfc3339();
+    api_client::CasaBancaRequest {
+        an_lucru,
+        luna_lucru,
+        cod_subunitate: None,
+        documente: vec![booking.document(
+            doc_date,
+            vec![api_client::CasaBancaTranzactie {
+                tip_tranzactie: "Incasare".to_string(),
+                diferenta_pe_avans: if has_advance { "DA".to_string() } else { "".to_string() },
+                tip_doc: booking.tip_doc(),
+                serie_doc: receipt_series.to_string(),
+                nr_doc: receipt_number.to_string(),
+                obiect_tranzactie: "Client".to_string(),
+                data: doc_date.to_string(),
+                curs: 1.0,
+                id_partener: partner_id.to_string(),
+                valoare: lines.iter().map(|line| line.valoare).sum(),
+                obs: booking.obs(),
+                anulat: anulat.to_string(),
+                distribuire_valoare,
+            }],
+        )],
+    }
+}
 
-    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
-        .map_err(|e| e.to_string())?;
+/// Cancels a receipt whether or not it reached WME: the number stays used,
+/// every row of the group is cancelled and the receipt is printed again as
+/// "ANULAT". A receipt already in WME is sent again with `Anulat = "DA"` to
+/// reverse it; calling this again retries a reversal that did not get there.
+#[tauri::command]
+pub async fn cancel_receipt(
+    db: State<'_, Database>,
+    receipt_group_id: String,
+    reason: String,
+) -> Result<Collection, String> {
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err("Motivul anulării este obligatoriu".to_string());
+    }
+    let settings: AgentSettings = get_agent_settings(db.clone())?;
 
-    for allocation in &request.allocations {
-        let row_id = Uuid::new_v4().to_string();
-        if let Err(e) = conn.execute(
-            "INSERT INTO collections (
-                id, receipt_group_id, receipt_series, receipt_number,
-                id_partener, partner_name, numar_factura, serie_factura,
-                cod_document, valoare, data_incasare, status, created_at
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-            params![
-                row_id,
-                &receipt_group_id,
-                &receipt_series,
-                &receipt_number,
-                &partner_id,
-                &request.partner_name,
-                &allocation.numar_factura,
-                &allocation.serie_factura,
-                &allocation.cod_document,
-                allocation.valoare,
-                &now,
-                "pending",
-                &now
-            ],
-        ) {
-            let _ = conn.execute("ROLLBACK", []);
-            return Err(e.to_string());
+    let (group_id, reversal, cash) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let group_id: String = conn
+            .query_row(
+                "SELECT COALESCE(receipt_group_id, id) FROM collections WHERE id = ?1 OR receipt_group_id = ?1 LIMIT 1",
+                [&receipt_group_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| "Chitanța nu a fost găsită".to_string())?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT status, synced_at, cancel_sent_at, COALESCE(receipt_series, ''), COALESCE(receipt_number, ''),
+                        id_partener, data_incasare, numar_factura, serie_factura, valoare, transaction_type
+                 FROM collections WHERE COALESCE(receipt_group_id, id) = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([&group_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                    ReceiptLine {
+                        numar_factura: row.get(7)?,
+                        serie_factura: row.get(8)?,
+                        valoare: row.get(9)?,
+                        transaction_type: row.get(10)?,
+                    },
+                ))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        drop(stmt);
+
+        if rows.iter().any(|r| r.0 == "sending") {
+            return Err("Chitanța se trimite acum în WME. Încearcă din nou după ce trimiterea se încheie.".to_string());
+        }
+        let in_wme = rows.iter().any(|r| r.1.is_some());
+        let reversed = rows.iter().any(|r| r.2.is_some());
+        let cancelled = rows.iter().all(|r| r.0 == "cancelled");
+        if cancelled && (!in_wme || reversed) {
+            return Err("Chitanța este deja anulată.".to_string());
+        }
+
+        let compensated: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM advance_applications
+                 WHERE status = 'synced' AND advance_id IN (
+                     SELECT id FROM collections WHERE COALESCE(receipt_group_id, id) = ?1
+                 )",
+                [&group_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if compensated > 0 {
+            return Err("Avansul de pe chitanță a fost deja compensat pe facturi în WME; chitanța nu mai poate fi anulată.".to_string());
+        }
+
+        if !cancelled {
+            // A reversal retry only touches WME, the register page stays as it is
+            cash_register::ensure_receipt_open(&conn, &group_id)?;
+            conn.execute(
+                "UPDATE collections SET status = 'cancelled', cancelled_at = ?1, cancel_reason = ?2, error_message = ?3
+                 WHERE COALESCE(receipt_group_id, id) = ?4 AND status <> 'cancelled'",
+                params![Utc::now().to_rfc3339(), &reason, format!("Anulată: {}", reason), &group_id],
+            )
+            .map_err(|e| e.to_string())?;
+            conn.execute(
+                "UPDATE advance_applications SET status = 'cancelled', error_message = ?1
+                 WHERE status = 'pending' AND advance_id IN (
+                     SELECT id FROM collections WHERE COALESCE(receipt_group_id, id) = ?2
+                 )",
+                params![format!("Avans anulat: {}", reason), &group_id],
+            )
+            .map_err(|e| e.to_string())?;
+            info!("[CHITANTE][CANCEL] Receipt {} cancelled: {}", group_id, reason);
+        }
+
+        // Reversed as it was booked: same series, number and date
+        let booking = receipt_booking(&conn, &settings, &group_id)?;
+        let cash = payment_methods::is_cash(&booking.payment.method);
+        let reversal = in_wme.then(|| {
+            let (_, _, _, series, number, partner_id, data_incasare, _) = &rows[0];
+            let date = chrono::DateTime::parse_from_rfc3339(data_incasare)
+                .map(|date| date.with_timezone(&Local).date_naive())
+                .unwrap_or_else(|_| Local::now().date_naive());
+            let lines: Vec<ReceiptLine> = rows
+                .iter()
+                .map(|r| ReceiptLine {
+                    numar_factura: r.7.numar_factura.clone(),
+                    serie_factura: r.7.serie_factura.clone(),
+                    valoare: r.7.valoare,
+                    transaction_type: r.7.transaction_type.clone(),
+                })
+                .collect();
+            receipt_casa_banca_request(
+                &booking,
+                partner_id,
+                (series, number),
+                &date.format("%d.%m.%Y").to_string(),
+                (date.year(), date.month() as i32),
+                &lines,
+                "DA",
+            )
+        });
+        (group_id, reversal, cash)
+    };
+
+    if let Some(request) = reversal {
+        let api = {
+            let conn = db.conn.lock().map_err(|e| e.to_string())?;
+            get_wme_api_client(&conn)
+        };
+        let result = match api {
+            Ok(api) => match api.send_collections_to_wme(request).await {
+                Ok(response)
+                    if response.result.as_deref().unwrap_or("").eq_ignore_ascii_case("ok")
+                        || response.error_list.is_empty() =>
+                {
+                    Ok(())
+                }
+                Ok(response) => Err(format!(
+                    "API Error: {}; {:?}",
+                    response.result.unwrap_or_default(),
+                    response.error_list
+                )),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        match result {
+            Ok(()) => {
+                conn.execute(
+                    "UPDATE collections SET cancel_sent_at = ?1, error_message = ?2 WHERE COALESCE(receipt_group_id, id) = ?3",
+                    params![Utc::now().to_rfc3339(), format!("Anulată în WME: {}", reason), &group_id],
+                )
+                .map_err(|e| e.to_string())?;
+                document_events::record_collection_group(&conn, &group_id, document_events::WME_REVERSED, Some(&reason));
+                info!("[CHITANTE][CANCEL] Reversal of receipt {} sent to WME", group_id);
+            }
+            Err(e) => {
+                warn!("[CHITANTE][CANCEL] Failed to send reversal of receipt {} to WME: {}", group_id, e);
+                conn.execute(
+                    "UPDATE collections SET error_message = ?1 WHERE COALESCE(receipt_group_id, id) = ?2",
+                    params![format!("Anulată local, netrimisă în WME: {}", e), &group_id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
         }
     }
 
-    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+    if cash {
+        if let Err(e) = print_collection_to_html(db.clone(), group_id.clone(), None).await {
+            warn!("[CHITANTE][CANCEL] Cancelled receipt {} could not be reprinted: {}", group_id, e);
+        }
+    }
 
-    Ok(receipt_group_id)
+    get_collections(db, None)?
+        .into_iter()
+        .find(|collection| collection.id == group_id)
+        .ok_or_else(|| "Nu s-a putut încărca chitanța actualizată".to_string())
+}
+
+/// Restores a cancelled receipt to pending so it is synced again.
+#[tauri::command]
+pub fn restore_collection(db: State<'_, Database>, collection_id: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    cash_register::ensure_receipt_open(&conn, &collection_id)?;
+
+    let affected = conn
+        .execute(
+            "UPDATE collections SET status = 'pending', cancelled_at = NULL, cancel_reason = NULL, error_message = NULL \
+             WHERE (COALESCE(receipt_group_id, id) = ?1 OR id = ?1) AND status = 'cancelled' AND synced_at IS NULL",
+            [&collection_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    if affected == 0 {
+        return Err("Doar chitanțele anulate care nu au ajuns în WME pot fi restaurate.".to_string());
+    }
+
+    info!("[CHITANTE][CANCEL] Collection {} restored", collection_id);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn record_collection_from_invoice(
+pub fn get_sales_report(
     db: State<'_, Database>,
-    invoice_id: String,
-    paid_amount: f64,
-) -> Result<String, String> {
-    if paid_amount <= 0.0 {
-        return Err("Suma încasată trebuie să fie mai mare decât 0".to_string());
-    }
-
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<Vec<SalesReportItem>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
-    let (partner_id, partner_name, invoice_number, _total_amount, carnet_series): (String, String, i64, f64, Option<String>) = conn
-        .query_row(
-            r#"
-            SELECT i.partner_id, p.name, i.invoice_number, i.total_amount,
-                   (SELECT carnet_series FROM agent_settings WHERE id = 1)
-            FROM invoices i
-            JOIN partners p ON p.id = i.partner_id
-            WHERE i.id = ?1
-            "#,
-            [&invoice_id],
-            |row| {
-                Ok((
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    row.get(4)?,
-                ))
-            },
-        )
-        .map_err(|e| format!("Factura nu a fost găsită: {}", e))?;
+    let include_archived = include_archived.unwrap_or(false);
+    let invoices_source = archive::report_source(&conn, "invoices", include_archived).map_err(|e| e.to_string())?;
+    let invoice_items_source = archive::report_source(&conn, "invoice_items", include_archived).map_err(|e| e.to_string())?;
 
-    // Calculate Gross Total (Total with VAT)
-    let mut stmt_items = conn
-        .prepare(
-            "SELECT ii.total_price, p.procent_tva \
-             FROM invoice_items ii \
-             JOIN products p ON ii.product_id = p.id \
-             WHERE ii.invoice_id = ?1",
-        )
-        .map_err(|e| e.to_string())?;
+    let mut query = format!(
+        "SELECT
+        p.name as partner_name,
+        COUNT(*) as count,
+        SUM(i.total_amount) as total,
+        COALESCE(SUM(inv_qty.total_quantity), 0) as total_quantity
+        FROM {invoices_source} i
+        JOIN partners p ON p.id = i.partner_id
+        LEFT JOIN (
+            SELECT invoice_id, SUM(quantity) as total_quantity
+            FROM {invoice_items_source}
+            GROUP BY invoice_id
+        ) inv_qty ON inv_qty.invoice_id = i.id
+        WHERE i.status <> 'cancelled'"
+    );
 
-    let gross_total: f64 = stmt_items
-        .query_map([&invoice_id], |row| {
-            let price: f64 = row.get(0)?;
-            let tva_str: Option<String> = row.get(1)?;
-            let tva_percent = tva_str
-                .and_then(|s| s.parse::<f64>().ok())
-                .unwrap_or(0.0);
+    let mut params: Vec<String> = Vec::new();
 
-            let vat_amount = price * (tva_percent / 100.0);
-            Ok(price + vat_amount)
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .sum();
+    if let Some(start) = start_date {
+        query.push_str(" AND i.created_at >= ?");
+        params.push(format!("{}T00:00:00", start));
+    }
 
-    // Drop the statement to release the borrow on conn
-    drop(stmt_items);
+    if let Some(end) = end_date {
+        query.push_str(" AND i.created_at <= ?");
+        params.push(format!("{}T23:59:59", end));
+    }
 
-    // Allow a small epsilon for floating point comparison
-    const EPSILON: f64 = 0.01;
+    query.push_str(" GROUP BY p.name ORDER BY total DESC");
 
-    // Use gross_total for validation instead of total_amount (which is net)
-    if paid_amount > (gross_total + EPSILON) {
-        return Err(format!(
-            "Suma încasată ({:.2}) nu poate depăși totalul facturii cu TVA ({:.2})",
-            paid_amount,
-            gross_total
-        ));
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+
+    // Note: This is simplified. We might need better VAT calculation.
+    let items = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        let total: f64 = row.get(2)?;
+        let total_quantity: f64 = row.get(3)?;
+        Ok(SalesReportItem {
+            partner_name: row.get(0)?,
+            invoice_count: row.get(1)?,
+            total_amount: total,
+            total_vat: total * 0.19, // Approximation
+            total_quantity,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for i in items {
+        result.push(i.map_err(|e| e.to_string())?);
     }
 
-    let invoice_number_str = invoice_number.to_string();
-    let receipt_series = get_receipt_series(&conn)?;
-    let series = carnet_series.unwrap_or_else(|| "FACTURA".to_string());
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn get_sales_print_report(
+    db: State<'_, Database>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<Vec<SalesPrintItem>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
-    let receipt_number = generate_receipt_number(&conn)?;
+    let include_archived = include_archived.unwrap_or(false);
+    let invoices_source = archive::report_source(&conn, "invoices", include_archived).map_err(|e| e.to_string())?;
+    let invoice_items_source = archive::report_source(&conn, "invoice_items", include_archived).map_err(|e| e.to_string())?;
+    let collections_source = archive::report_source(&conn, "collections", include_archived).map_err(|e| e.to_string())?;
 
-    let collected_total: f64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE
-             id_partener = ?1 AND numar_factura = ?2 AND cod_document = ?3 AND
-             status IN ('pending', 'sending', 'synced')",
-            params![&partner_id, &invoice_number_str, &invoice_number_str],
-            |row| row.get(0),
+    let mut query = format!(
+        "WITH invoice_data AS (
+        SELECT
+            COALESCE(NULLIF(TRIM(p.cod_extern), ''), p.name, 'Partener') AS partner_name,
+            i.created_at,
+            COALESCE(inv_qty.total_quantity, 0) AS total_quantity,
+            COALESCE(inv_totals.total_without_vat, i.total_amount) AS total_without_vat,
+            COALESCE(inv_totals.total_with_vat, i.total_amount * 1.19) AS total_with_vat,
+            COALESCE(col.total_collected, 0) AS collected_amount
+        FROM {invoices_source} i
+        JOIN partners p ON p.id = i.partner_id
+        LEFT JOIN (
+            SELECT invoice_id, SUM(quantity) AS total_quantity
+            FROM {invoice_items_source}
+            GROUP BY invoice_id
+        ) inv_qty ON inv_qty.invoice_id = i.id
+        LEFT JOIN (
+            SELECT
+                ii.invoice_id,
+                SUM(ii.total_price) AS total_without_vat,
+                SUM(
+                    ii.total_price * (
+                        1 + (COALESCE(CAST(pr.procent_tva AS REAL), 19) / 100.0)
+                    )
+                ) AS total_with_vat
+            FROM {invoice_items_source} ii
+            LEFT JOIN products pr ON pr.id = ii.product_id
+            GROUP BY ii.invoice_id
+        ) inv_totals ON inv_totals.invoice_id = i.id
+        LEFT JOIN (
+            SELECT
+                id_partener,
+                COALESCE(numar_factura, '') AS numar_factura,
+                COALESCE(cod_document, '') AS cod_document,
+                SUM(valoare) AS total_collected
+            FROM {collections_source}
+            WHERE status IN ('pending', 'sending', 'synced')
+            GROUP BY id_partener, COALESCE(numar_factura, ''), COALESCE(cod_document, '')
+        ) col ON (
+            col.id_partener = i.partner_id
+            AND (
+                col.numar_factura = CAST(i.invoice_number AS TEXT)
+                OR col.cod_document = CAST(i.invoice_number AS TEXT)
+            )
         )
-        .map_err(|e| e.to_string())?;
+        WHERE i.status <> 'cancelled'"
+    );
 
-    // Calculate remaining based on Gross Total
-    let remaining = (gross_total - collected_total).max(0.0);
+    let mut params: Vec<String> = Vec::new();
 
-    if remaining <= EPSILON {
-        return Err("Factura este deja încasată integral".to_string());
+    if let Some(start) = start_date {
+        query.push_str(" AND i.created_at >= ?");
+        params.push(format!("{}T00:00:00", start));
     }
 
-    if paid_amount > (remaining + EPSILON) {
-        return Err(format!(
-            "Suma încasată ({:.2}) depășește restul disponibil ({:.2})",
-            paid_amount,
-            remaining
-        ));
+    if let Some(end) = end_date {
+        query.push_str(" AND i.created_at <= ?");
+        params.push(format!("{}T23:59:59", end));
     }
 
-    let collection_id = Uuid::new_v4().to_string();
-
-    conn.execute(
-        "INSERT INTO collections (
-            id, receipt_group_id, receipt_series, receipt_number,
-            id_partener, partner_name, numar_factura, serie_factura,
-            cod_document, valoare, data_incasare, status, created_at
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-        params![
-            &collection_id,
-            &collection_id,
-            receipt_series,
-            receipt_number,
-            &partner_id,
-            &partner_name,
-            &invoice_number_str,
-            &series,
-            &invoice_number_str,
-            paid_amount,
-            Utc::now().to_rfc3339(),
-            "pending",
-            Utc::now().to_rfc3339(),
-        ],
+    query.push_str(
+        "
+    ),
+    partner_data AS (
+        SELECT
+            partner_name,
+            CASE
+                WHEN collected_amount >= total_without_vat THEN 'paid'
+                ELSE 'unpaid'
+            END AS payment_section,
+            COUNT(*) AS invoice_count,
+            SUM(total_quantity) AS total_quantity,
+            SUM(total_without_vat) AS total_without_vat,
+            SUM(total_with_vat - total_without_vat) AS total_vat,
+            SUM(total_with_vat) AS total_with_vat
+        FROM invoice_data
+        GROUP BY partner_name, payment_section
     )
-    .map_err(|e| e.to_string())?;
+    SELECT
+        partner_name,
+        invoice_count,
+        total_quantity,
+        ROUND(total_quantity / 30.0, 2) AS total_cofrage,
+        total_without_vat,
+        total_vat,
+        total_with_vat,
+        payment_section
+    FROM partner_data
+    WHERE 1 = 1"
+    );
 
-    Ok(collection_id)
+    query.push_str(" ORDER BY CASE payment_section WHEN 'unpaid' THEN 1 ELSE 2 END, total_with_vat DESC");
+
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+
+    let items = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok(SalesPrintItem {
+                partner_name: row.get(0)?,
+                invoice_count: row.get(1)?,
+                total_quantity: row.get(2)?,
+                total_cofrage: row.get(3)?,
+                total_without_vat: row.get(4)?,
+                total_vat: row.get(5)?,
+                total_with_vat: row.get(6)?,
+                payment_section: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for item in items {
+        result.push(item.map_err(|e| e.to_string())?);
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
-pub fn get_invoice_remaining_for_collection(
+pub fn get_sales_products_report(
     db: State<'_, Database>,
-    invoice_id: String,
-) -> Result<f64, String> {
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<Vec<SalesProductReportItem>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
-    let (partner_id, invoice_number, total_amount): (String, i64, f64) = conn
-        .query_row(
-            r#"
-            SELECT i.partner_id, i.invoice_number, i.total_amount
-            FROM invoices i
-            WHERE i.id = ?1
-            "#,
-            [&invoice_id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
-        )
-        .map_err(|e| format!("Factura nu a fost găsită: {}", e))?;
+    let include_archived = include_archived.unwrap_or(false);
+    let invoices_source = archive::report_source(&conn, "invoices", include_archived).map_err(|e| e.to_string())?;
+    let invoice_items_source = archive::report_source(&conn, "invoice_items", include_archived).map_err(|e| e.to_string())?;
 
-    let invoice_number_str = invoice_number.to_string();
+    let mut query = format!(
+        "SELECT
+        ii.product_id,
+        pr.name AS product_name,
+        pr.class AS product_class,
+        p.name AS partner_name,
+        COALESCE(i.invoice_number, 0) AS invoice_number,
+        COALESCE(NULLIF(TRIM(i.invoice_series), ''), (SELECT carnet_series FROM agent_settings WHERE id = 1), 'FACTURA') AS invoice_series,
+        ii.quantity AS total_quantity,
+        ROUND(ii.quantity / 30.0, 2) AS total_cofrage,
+        ii.total_price AS total_without_vat,
+        ii.total_price * (1 + (COALESCE(CAST(pr.procent_tva AS REAL), 19) / 100.0)) AS total_with_vat,
+        i.created_at
+    FROM {invoice_items_source} ii
+    JOIN {invoices_source} i ON i.id = ii.invoice_id
+    JOIN partners p ON p.id = i.partner_id
+    LEFT JOIN products pr ON pr.id = ii.product_id
+    WHERE i.status <> 'cancelled'"
+    );
 
-    let collected_total: f64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE
-             id_partener = ?1 AND numar_factura = ?2 AND cod_document = ?3 AND
-             status IN ('pending', 'sending', 'synced')",
-            params![&partner_id, &invoice_number_str, &invoice_number_str],
-            |row| row.get(0),
-        )
+    let mut params: Vec<String> = Vec::new();
+
+    if let Some(start) = start_date {
+        query.push_str(" AND i.created_at >= ?");
+        params.push(format!("{}T00:00:00", start));
+    }
+
+    if let Some(end) = end_date {
+        query.push_str(" AND i.created_at <= ?");
+        params.push(format!("{}T23:59:59", end));
+    }
+
+    query.push_str(" ORDER BY COALESCE(pr.class, ''), pr.name, i.created_at DESC");
+
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+
+    let items = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok(SalesProductReportItem {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                product_class: row.get(2)?,
+                partner_name: row.get(3)?,
+                invoice_number: row.get(4)?,
+                invoice_series: row.get(5)?,
+                total_quantity: row.get(6)?,
+                total_cofrage: row.get(7)?,
+                total_without_vat: row.get(8)?,
+                total_with_vat: row.get(9)?,
+                created_at: row.get(10)?,
+            })
+        })
         .map_err(|e| e.to_string())?;
 
-    Ok((total_amount - collected_total).max(0.0))
+    let mut result = Vec::new();
+    for item in items {
+        result.push(item.map_err(|e| e.to_string())?);
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
-pub fn get_collections(
+pub fn get_collections_report(
     db: State<'_, Database>,
-    status_filter: Option<String>,
-) -> Result<Vec<Collection>, String> {
+    start_date: Option<String>,
+    end_date: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<Vec<CollectionsReportItem>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
-    let query = r#"
-        SELECT
-            COALESCE(receipt_group_id, id) AS group_id,
-            id_partener,
-            MAX(partner_name) AS partner_name,
-            MAX(numar_factura) AS first_numar_factura,
-            MAX(serie_factura) AS first_serie_factura,
-            MAX(cod_document) AS first_cod_document,
-            SUM(valoare) AS total_valoare,
-            MAX(data_incasare) AS data_incasare,
-            SUM(CASE WHEN status = 'sending' THEN 1 ELSE 0 END) AS cnt_sending,
-            SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS cnt_failed,
-            SUM(CASE WHEN status = 'pending' THEN 1 ELSE 0 END) AS cnt_pending,
-            MAX(synced_at) AS synced_at,
-            MAX(error_message) AS error_message,
-            MAX(created_at) AS created_at,
-            MAX(receipt_series) AS receipt_series,
-            MAX(receipt_number) AS receipt_number,
-            COUNT(*) AS invoice_count
-        FROM collections
-        GROUP BY COALESCE(receipt_group_id, id), id_partener
-        ORDER BY MAX(created_at) DESC
-    "#;
+    let include_archived = include_archived.unwrap_or(false);
+    let collections_source = archive::report_source(&conn, "collections", include_archived).map_err(|e| e.to_string())?;
 
-    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+    let mut query = format!(
+        "SELECT
+        partner_name,
+        COUNT(*) as count,
+        SUM(valoare) as total,
+        status
+        FROM {collections_source}"
+    );
 
-    let rows = stmt
-        .query_map([], |row| {
-            let cnt_sending: i64 = row.get(8)?;
-            let cnt_failed: i64 = row.get(9)?;
-            let cnt_pending: i64 = row.get(10)?;
+    let mut params: Vec<String> = Vec::new();
 
-            let status = if cnt_sending > 0 {
-                CollectionStatus::Sending
-            } else if cnt_failed > 0 {
-                CollectionStatus::Failed
-            } else if cnt_pending > 0 {
-                CollectionStatus::Pending
-            } else {
-                CollectionStatus::Synced
-            };
+    let mut where_started = false;
 
-            let invoice_count: i64 = row.get(16)?;
-            let first_numar_factura: Option<String> = row.get(3)?;
-            let first_serie_factura: Option<String> = row.get(4)?;
-            let first_cod_document: Option<String> = row.get(5)?;
-            let receipt_series: Option<String> = row.get(14)?;
-            let receipt_number: Option<String> = row.get(15)?;
+    if let Some(start) = start_date {
+        query.push_str(" WHERE data_incasare >= ?");
+        params.push(format!("{}T00:00:00", start));
+        where_started = true;
+    }
 
-            let numar_factura = if invoice_count > 1 {
-                Some(format!("{} facturi", invoice_count))
-            } else {
-                first_numar_factura
-            };
+    if let Some(end) = end_date {
+        if where_started {
+            query.push_str(" AND data_incasare <= ?");
+        } else {
+            query.push_str(" WHERE data_incasare <= ?");
+        }
+        params.push(format!("{}T23:59:59", end));
+    }
 
-            let serie_factura = if invoice_count > 1 {
-                receipt_series.clone()
-            } else {
-                first_serie_factura
-            };
+    query.push_str(" GROUP BY partner_name, status ORDER BY total DESC");
 
-            Ok(Collection {
-                id: row.get(0)?,
-                id_partener: row.get(1)?,
-                partner_name: row.get(2)?,
-                numar_factura,
-                serie_factura,
-                cod_document: receipt_number.clone().or(first_cod_document),
-                valoare: row.get(6)?,
-                data_incasare: row.get(7)?,
-                status,
-                synced_at: row.get(11)?,
-                error_message: row.get(12)?,
-                created_at: row.get(13)?,
-                receipt_series,
-                receipt_number,
-            })
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+
+    let items = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok(CollectionsReportItem {
+            partner_name: row.get(0)?,
+            collection_count: row.get(1)?,
+            total_amount: row.get(2)?,
+            status: row.get(3)?,
         })
-        .map_err(|e| e.to_string())?;
+    }).map_err(|e| e.to_string())?;
 
     let mut result = Vec::new();
-    for row in rows {
-        let collection = row.map_err(|e| e.to_string())?;
-        if let Some(filter) = &status_filter {
-            if collection.status.to_string() != *filter {
-                continue;
-            }
-        }
-        result.push(collection);
+    for i in items {
+        result.push(i.map_err(|e| e.to_string())?);
     }
 
     Ok(result)
 }
 
 #[tauri::command]
-pub async fn sync_collections(
+pub fn get_daily_collections_report(
     db: State<'_, Database>,
-) -> Result<SyncStatus, String> {
-    use std::sync::atomic::Ordering;
+    date: Option<String>,
+) -> Result<DailyCollectionsReport, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
-    // Prevent concurrent runs
-    if db.is_syncing_collections.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
-        return get_sync_status(db);
-    }
+    let target_date = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let previous_date = chrono::NaiveDate::parse_from_str(&target_date, "%Y-%m-%d")
+        .map_err(|e| format!("Dată invalidă: {}", e))?
+        .pred_opt()
+        .ok_or_else(|| "Nu s-a putut calcula ziua precedentă".to_string())?
+        .format("%Y-%m-%d")
+        .to_string();
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT
+                COALESCE(NULLIF(TRIM(p.cod_extern), ''), c.partner_name, p.name, 'Partener') AS partner_name,
+                SUM(
+                    CASE
+                        WHEN i.id IS NOT NULL AND substr(i.created_at, 1, 10) = ?1 THEN c.valoare
+                        ELSE 0
+                    END
+                ) AS amount_from_today_sales,
+                SUM(
+                    CASE
+                        WHEN i.id IS NULL OR substr(i.created_at, 1, 10) <> ?1 THEN c.valoare
+                        ELSE 0
+                    END
+                ) AS amount_from_previous_debt,
+                SUM(c.valoare) AS total_amount
+            FROM collections c
+            LEFT JOIN partners p ON p.id = c.id_partener
+            LEFT JOIN invoices i ON i.partner_id = c.id_partener
+                AND (
+                    CAST(i.invoice_number AS TEXT) = COALESCE(c.numar_factura, '')
+                    OR CAST(i.invoice_number AS TEXT) = COALESCE(c.cod_document, '')
+                )
+            WHERE substr(c.data_incasare, 1, 10) = ?1
+              AND c.status IN ('pending', 'sending', 'synced')
+                        GROUP BY COALESCE(NULLIF(TRIM(p.cod_extern), ''), c.partner_name, p.name, 'Partener')
+            ORDER BY total_amount DESC
+            "#,
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([&target_date], |row| {
+            Ok(DailyCollectionsPartnerItem {
+                partner_name: row.get(0)?,
+                amount_from_today_sales: row.get(1)?,
+                amount_from_previous_debt: row.get(2)?,
+                total_amount: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
 
-    struct LockGuard<'a>(&'a std::sync::atomic::AtomicBool);
-    impl Drop for LockGuard<'_> {
-        fn drop(&mut self) {
-            self.0.store(false, std::sync::atomic::Ordering::SeqCst);
-        }
+    let mut items = Vec::new();
+    for row in rows {
+        items.push(row.map_err(|e| e.to_string())?);
     }
-    let _guard = LockGuard(&db.is_syncing_collections);
-
-    // Only retry pending — failed must be retried manually
-    let pending_collections = get_collections(db.clone(), Some("pending".to_string()))?;
 
-    if pending_collections.is_empty() {
-        return get_sync_status(db.clone());
-    }
+    let current_day_collections_total: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
+            [&target_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
 
-    info!("[SYNC] Trimitere {} chitante in asteptare...", pending_collections.len());
+    let previous_day_collections_total: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
+            [&previous_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
 
-    let mut sent = 0;
-    let mut errors = 0;
+    let current_day_receipts_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(DISTINCT COALESCE(receipt_group_id, id)) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
+            [&target_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
 
-    for collection in pending_collections {
-        match send_collection(db.clone(), collection.id.clone()).await {
-            Ok(_) => sent += 1,
-            Err(_) => errors += 1,
-        }
-    }
+    let previous_day_receipts_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(DISTINCT COALESCE(receipt_group_id, id)) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
+            [&previous_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
 
-    if sent > 0 || errors > 0 {
-        info!("[SYNC] Chitante: {} trimise, {} esuate", sent, errors);
-    }
+    let (receipts_today_invoices_count, receipts_previous_debt_count): (i64, i64) = conn
+        .query_row(
+            r#"
+            SELECT
+                COUNT(DISTINCT CASE
+                    WHEN i.id IS NOT NULL AND substr(i.created_at, 1, 10) = ?1
+                    THEN COALESCE(c.receipt_group_id, c.id)
+                END) AS receipts_today_invoices_count,
+                COUNT(DISTINCT CASE
+                    WHEN i.id IS NULL OR substr(i.created_at, 1, 10) <> ?1
+                    THEN COALESCE(c.receipt_group_id, c.id)
+                END) AS receipts_previous_debt_count
+            FROM collections c
+            LEFT JOIN invoices i ON i.partner_id = c.id_partener
+                AND (
+                    CAST(i.invoice_number AS TEXT) = COALESCE(c.numar_factura, '')
+                    OR CAST(i.invoice_number AS TEXT) = COALESCE(c.cod_document, '')
+                )
+            WHERE substr(c.data_incasare, 1, 10) = ?1
+              AND c.status IN ('pending', 'sending', 'synced')
+            "#,
+            [&target_date],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
 
-    get_sync_status(db.clone())
+    Ok(DailyCollectionsReport {
+        items,
+        receipts_today_invoices_count,
+        receipts_previous_debt_count,
+        current_day_receipts_count,
+        previous_day_receipts_count,
+        current_day_collections_total,
+        previous_day_collections_total,
+        total_day_collections: current_day_collections_total,
+    })
 }
 
-#[tauri::command]
-pub async fn send_collection(
-    db: State<'_, Database>,
-    collection_id: String,
-) -> Result<Collection, String> {
-    use chrono::Datelike;
-    info!("[CHITANTE][SEND] Start send_collection for id/group={}", collection_id);
-
-    let settings: AgentSettings = get_agent_settings(db.clone())?;
+// Invoices of a day (YYYY-MM-DD) for the daily sales report, with their total
+fn load_daily_report_invoices(conn: &rusqlite::Connection, date_str: &str) -> Result<(Vec<Invoice>, f64), String> {
+    // Fetch invoices for this date
+    // We assume created_at is ISO string, so we match YYYY-MM-DD%
+    let param_date = format!("{}%", date_str);
 
-    let rows = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
-        let mut stmt = conn
-            .prepare(
-                "SELECT
-                    id,
-                    COALESCE(receipt_group_id, id) as receipt_group_id,
-                    receipt_series,
-                    receipt_number,
-                    id_partener,
-                    partner_name,
-                    numar_factura,
-                    serie_factura,
-                    cod_document,
-                    valoare,
-                    data_incasare,
-                    status,
-                    synced_at,
-                    error_message,
-                    created_at
-                 FROM collections
-                 WHERE COALESCE(receipt_group_id, id) = ?1",
-            )
-            .map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        r#"SELECT
+                i.id, i.partner_id, p.name, p.cif, p.reg_com, i.location_id, l.name, l.address,
+                i.status, i.total_amount, i.notes, i.created_at, i.sent_at, i.error_message,
+                (SELECT COUNT(*) FROM invoice_items WHERE invoice_id = i.id),
+                p.scadenta_la_vanzare
+            FROM invoices i
+            JOIN partners p ON i.partner_id = p.id
+            JOIN locations l ON i.location_id = l.id
+            WHERE i.created_at LIKE ?1 AND i.status <> 'cancelled'
+            ORDER BY i.created_at ASC"#
+    ).map_err(|e| e.to_string())?;
 
-        let mapped = stmt
-            .query_map([&collection_id], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,
-                    row.get::<_, String>(1)?,
-                    row.get::<_, Option<String>>(2)?,
-                    row.get::<_, Option<String>>(3)?,
-                    row.get::<_, String>(4)?,
-                    row.get::<_, Option<String>>(5)?,
-                    row.get::<_, Option<String>>(6)?,
-                    row.get::<_, Option<String>>(7)?,
-                    row.get::<_, Option<String>>(8)?,
-                    row.get::<_, f64>(9)?,
-                    row.get::<_, String>(10)?,
-                    row.get::<_, String>(11)?,
-                    row.get::<_, Option<String>>(12)?,
-                    row.get::<_, Option<String>>(13)?,
-                    row.get::<_, String>(14)?,
-                ))
-            })
-            .map_err(|e| e.to_string())?;
+    let invoices_iter = stmt.query_map([&param_date], |row| {
+        Ok(Invoice {
+            id: row.get(0)?,
+            partner_id: row.get(1)?,
+            partner_name: row.get(2)?,
+            partner_cif: row.get(3)?,
+            partner_reg_com: row.get(4)?,
+            location_id: row.get(5)?,
+            location_name: row.get(6)?,
+            location_address: row.get(7)?,
+            status: InvoiceStatus::from(row.get::<_, String>(8)?),
+            total_amount: row.get(9)?,
+            notes: row.get(10)?,
+            created_at: row.get(11)?,
+            sent_at: row.get(12)?,
+            error_message: row.get(13)?,
+            item_count: row.get(14)?,
+            partner_payment_term: row.get(15)?,
+            invoice_number: 0,
+            invoice_series: None,
+        })
+    }).map_err(|e| e.to_string())?;
 
-        let mut result = Vec::new();
-        for row in mapped {
-            result.push(row.map_err(|e| e.to_string())?);
-        }
-        result
-    };
+    let mut invoices = Vec::new();
+    let mut total_sales: f64 = 0.0;
 
-    if rows.is_empty() {
-        return Err("Chitanța nu a fost găsită".to_string());
+    for invoice in invoices_iter {
+        let inv = invoice.map_err(|e| e.to_string())?;
+        total_sales += inv.total_amount;
+        invoices.push(inv);
     }
 
-    let receipt_group_id = rows[0].1.clone();
-    let receipt_series = rows[0]
-        .2
-        .clone()
-        .unwrap_or_else(|| settings.carnet_series.clone().unwrap_or_else(|| "CH".to_string()));
-    let receipt_number = rows[0]
-        .3
-        .clone()
-        .or_else(|| rows[0].8.clone())
-        .unwrap_or_else(|| chrono::Local::now().format("%Y%m%d%H%M%S").to_string());
-    let partner_id = rows[0].4.clone();
-    let partner_name = rows[0].5.clone();
-    let doc_date_source = rows[0].10.clone();
-
-    let total_value: f64 = rows.iter().map(|r| r.9).sum();
-    let invoice_count = rows.len();
-
-    let collection_for_print = Collection {
-        id: receipt_group_id.clone(),
-        id_partener: partner_id.clone(),
-        partner_name: partner_name.clone(),
-        numar_factura: if invoice_count > 1 {
-            Some(format!("{} facturi", invoice_count))
-        } else {
-            rows[0].6.clone()
-        },
-        serie_factura: if invoice_count > 1 {
-            rows[0].2.clone()
-        } else {
-            rows[0].7.clone()
-        },
-        cod_document: Some(receipt_number.clone()),
-        valoare: total_value,
-        data_incasare: doc_date_source.clone(),
-        status: CollectionStatus::Pending,
-        synced_at: None,
-        error_message: None,
-        created_at: rows[0].14.clone(),
-        receipt_series: Some(receipt_series.clone()),
-        receipt_number: Some(receipt_number.clone()),
-    };
-
-    let now = Utc::now();
-    let issue_date_for_print = if let Ok(d) = chrono::NaiveDateTime::parse_from_str(&doc_date_source, "%Y-%m-%dT%H:%M:%S%.fZ") {
-        d.format("%d.%m.%Y").to_string()
-    } else {
-        now.format("%d.%m.%Y").to_string()
-    };
+    Ok((invoices, total_sales))
+}
 
-    let (partner_cui, partner_reg_com, partner_address, partner_localitate, partner_judet) = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
-        get_partner_receipt_info(&conn, &partner_id)
-    };
+#[tauri::command]
+pub fn print_daily_report(
+    db: State<'_, Database>,
+    date: Option<String>,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
-    let (saved_html_path, _) = save_receipt_html_file(
-        &collection_for_print,
-        &receipt_series,
-        &receipt_number,
-        &issue_date_for_print,
-        settings.agent_name.as_deref(),
-        settings.nume_casa.as_deref().unwrap_or("CASA LEI"),
-        partner_address.as_deref(),
-        partner_localitate.as_deref(),
-        partner_judet.as_deref(),
-        partner_cui.as_deref(),
-        partner_reg_com.as_deref(),
-        &receipt_group_id,
-    )?;
+    // Determine date to filter (YYYY-MM-DD)
+    let date_str = date.unwrap_or_else(|| {
+        Local::now().format("%Y-%m-%d").to_string()
+    });
 
-    info!(
-        "[CHITANTE][SEND] Receipt snapshot saved before API call: {}",
-        saved_html_path
-    );
+    info!("Generating daily sales report for date: {}", date_str);
 
-    let distribuire_valoare: Vec<api_client::DistribuireValoare> = rows
-        .iter()
-        .map(|r| api_client::DistribuireValoare {
-            reprezinta: "Factura".to_string(),
-            numar_factura: r.6.clone().unwrap_or_default(),
-            serie_factura: r.7.clone().unwrap_or_default(),
-            termen_factura: "".to_string(),
-            valoare: r.9,
-        })
-        .collect();
+    let (invoices, total_sales) = load_daily_report_invoices(&conn, &date_str)?;
 
-    info!(
-        "[CHITANTE][SEND] Loaded group {} partner={:?} allocations={} total={} marca_agent={:?} nume_casa={:?}",
-        receipt_group_id,
-        partner_name,
-        rows.len(),
-        total_value,
-        settings.marca_agent,
-        settings.nume_casa
+    // Generate HTML
+    let issuer = company::issuer(&conn);
+    let images = template_images(&issuer);
+    let html = print_daily_report::generate_daily_report_html(
+        &invoices,
+        &date_str,
+        total_sales,
+        &issuer.profile,
+        &images,
     );
 
-    {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    // Save to reports folder
+    let app_data_dir = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join("reports");
 
-        // Check if we can transition to sending state
-        // Only allow if not already sending or synced
-        // This prevents race conditions when multiple sync triggers happen
-        let affected = conn.execute(
-            "UPDATE collections SET status = 'sending' WHERE COALESCE(receipt_group_id, id) = ?1 AND status != 'sending' AND status != 'synced'",
-            [&receipt_group_id],
-        )
-        .map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
 
-        if affected == 0 {
-            info!("[CHITANTE][SEND] Collection group {} is already sending or synced. Skipping duplicate send.", receipt_group_id);
-            return Ok(collection_for_print);
-        }
-    }
+    let file_name = format!("raport_{}", date_str);
+    let html_file_path = app_data_dir.join(format!("{}.html", file_name));
+    let pdf_file_path = app_data_dir.join(format!("{}.pdf", file_name));
 
-    let api = {
-        let conn = db.conn.lock().map_err(|e| e.to_string())?;
-        get_wme_api_client(&conn)?
-    };
-    let an_lucru = now.year();
-    let luna_lucru = now.month() as i32;
+    std::fs::write(&html_file_path, &html)
+        .map_err(|e| format!("Failed to write HTML file: {}", e))?;
 
-    let doc_date = issue_date_for_print.clone();
+    let pdf_bytes = print_daily_report::generate_daily_report_pdf(
+        &invoices,
+        &date_str,
+        total_sales,
+        &issuer.profile,
+        read_logo_image(&issuer.logo).as_ref(),
+    );
+    std::fs::write(&pdf_file_path, &pdf_bytes)
+        .map_err(|e| format!("Failed to write PDF file: {}", e))?;
 
-    let doc = api_client::CasaBancaDocument {
-        sursa: "CASA".to_string(),
-        nume_casa: settings.nume_casa.clone().unwrap_or("CASA LEI".to_string()),
-        numar_cont: "".to_string(),
-        data: doc_date.clone(),
-        agent: settings.marca_agent.clone().unwrap_or_default(),
-        moneda: "RON".to_string(),
-        document_cumulativ: "".to_string(),
-        tranzactii: vec![api_client::CasaBancaTranzactie {
-            tip_tranzactie: "Incasare".to_string(),
-            diferenta_pe_avans: "".to_string(),
-            tip_doc: "Chitanta".to_string(),
-            serie_doc: receipt_series,
-            nr_doc: receipt_number,
-            obiect_tranzactie: "Client".to_string(),
-            data: doc_date,
-            curs: 1.0,
-            id_partener: partner_id.clone(),
-            valoare: total_value,
-            obs: "".to_string(),
-            anulat: "NU".to_string(),
-            distribuire_valoare,
-        }],
-    };
+    let html_path_str = html_file_path.to_string_lossy().to_string();
+    let pdf_path_str = pdf_file_path.to_string_lossy().to_string();
 
-    let request = api_client::CasaBancaRequest {
-        an_lucru,
-        luna_lucru,
-        cod_subunitate: None,
-        documente: vec![doc],
+    info!("Generated report HTML at: {}", html_path_str);
+    info!("Generated report PDF at: {} ({} bytes)", pdf_path_str, pdf_bytes.len());
+
+    let destination = printing::destination(&conn, printing::DAILY_REPORT, printer_name.as_deref());
+    drop(conn);
+    let printed = if destination.backend == printing::Backend::EscPos {
+        Err("raportul de vânzări nu are format pentru imprimanta termică".to_string())
+    } else {
+        print_jobs::submit(&db, &destination, &date_str, &pdf_path_str).map(|_| ())
     };
 
-    if let Ok(payload) = serde_json::to_string_pretty(&request) {
-        info!("[CHITANTE][SEND] CasaBanca payload for group {}:\n{}", receipt_group_id, payload);
+    match printed {
+        Ok(()) => {
+            info!("✓ Report printed successfully");
+            Ok(format!("Report printed successfully. File saved at: {}", pdf_path_str))
+        }
+        Err(e) => {
+            let msg = format!(
+                "Could not print report ({}). The printer may be offline or disconnected. PDF saved at: {}",
+                e, pdf_path_str
+            );
+            warn!("{}", msg);
+            Ok(msg)
+        }
     }
+}
 
-    let now_str = Utc::now().to_rfc3339();
-
-    // DUPLICATE PREVENTION: Check if invoice is already paid in WME before sending receipt
-    // This handles the case where a previous attempt succeeded on server but failed to return OK to client
-    let _start_check = std::time::Instant::now();
-    // ApiClient already created above
-
-    // We need to check the balance for the partner to see if the invoice is still unpaid
-    // Filters for get_solduri_clienti
-    let check_filter = api_client::SolduriFilterRequest {
-        id_partener: Some(partner_id.clone()),
-        marca_agent: None,
-        paginare: None,
-    };
+// ==================== CASH REGISTER COMMANDS ====================
 
-    let mut already_paid = false;
+/// Register page of the current cash desk for `date` (today by default).
+#[tauri::command]
+pub fn get_cash_register(db: State<'_, Database>, date: Option<String>) -> Result<CashRegisterDay, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let date = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let casa = cash_register::current_casa(&conn);
+    cash_register::day(&conn, &casa, &date)
+}
 
-    // Only perform this check if we are retrying (status was pending/failed) or just always to be safe?
-    // Always checking is safer but adds latency. Given this is "Send" action initiated by user or sync, latency is acceptable for safety.
-    info!("[CHITANTE][SEND] Checking WME balance for partner {} to prevent duplicates...", partner_id);
+/// First day of the register of the current cash desk and the cash counted in
+/// the drawer that morning. Returns the page of that day.
+#[tauri::command]
+pub fn set_cash_register_start(
+    db: State<'_, Database>,
+    date: String,
+    opening_balance: f64,
+) -> Result<CashRegisterDay, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let casa = cash_register::current_casa(&conn);
+    cash_register::set_start(&conn, &casa, &date, opening_balance)?;
+    cash_register::day(&conn, &casa, &date)
+}
 
-    match api.get_solduri_clienti(check_filter).await {
-        Ok(response) => {
-            // Check if our invoices exist in the balance list with remaining amount
-            // If they are missing or have rest=0, they are paid.
+/// Cash handed over, deposited or spent from the drawer. Returns the id.
+#[tauri::command]
+pub fn add_cash_movement(db: State<'_, Database>, movement: CashMovement) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    cash_register::add_movement(&conn, &movement)
+}
 
-            // Collect all invoice numbers from this receipt group
-            let invoice_numbers: std::collections::HashSet<String> = rows.iter()
-                .filter_map(|r| r.6.clone()) // numar_factura
-                .collect();
+#[tauri::command]
+pub fn delete_cash_movement(db: State<'_, Database>, id: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    cash_register::delete_movement(&conn, &id).map(|_| ())
+}
 
-            if !invoice_numbers.is_empty() {
-                // Find these invoices in the response
-                let mut found_unpaid_amount = 0.0;
-                let mut found_invoices_count = 0;
+// Helper: writes the register page as HTML and PDF to the reports folder and
+// prints the PDF. Returns the PDF path and the print error, if any.
+fn print_cash_register_page(
+    db: &Database,
+    page: &CashRegisterDay,
+    printer_name: Option<&str>,
+) -> Result<(String, Option<String>), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let issuer = company::issuer(&conn);
+    let images = template_images(&issuer);
+    let html = print_cash_register::generate_cash_register_html(page, &issuer.profile, &images);
 
-                for sold in &response.info_solduri {
-                    let sold_numar = sold.numar.as_deref().unwrap_or_default().trim();
-                    if invoice_numbers.contains(sold_numar) {
-                        let rest = api_client::parse_f64(&sold.rest);
-                        if rest > 0.01 {
-                            found_unpaid_amount += rest;
-                            found_invoices_count += 1;
-                        }
-                    }
-                }
+    let app_data_dir = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join("reports");
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
 
-                info!("[CHITANTE][SEND] Balance check: Found {} matching unpaid invoices with total rest {:.2}. Receipt total: {:.2}",
-                    found_invoices_count, found_unpaid_amount, total_value);
+    let casa_slug: String = page
+        .nume_casa
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let file_name = format!("registru_casa_{}_{}", casa_slug, page.day);
+    let html_file_path = app_data_dir.join(format!("{}.html", file_name));
+    let pdf_file_path = app_data_dir.join(format!("{}.pdf", file_name));
+    std::fs::write(&html_file_path, &html)
+        .map_err(|e| format!("Failed to write HTML file: {}", e))?;
+    let pdf_bytes =
+        print_cash_register::generate_cash_register_pdf(page, &issuer.profile, read_logo_image(&issuer.logo).as_ref());
+    std::fs::write(&pdf_file_path, &pdf_bytes)
+        .map_err(|e| format!("Failed to write PDF file: {}", e))?;
+    let pdf_path_str = pdf_file_path.to_string_lossy().to_string();
+    info!("[CASH_REGISTER] Register page saved at: {} ({} bytes)", pdf_path_str, pdf_bytes.len());
 
-                // IMPORTANT: Only mark as duplicate if we FOUND matching invoices in WME AND they appear already paid.
-                // If found_invoices_count == 0, the invoice might be a LOCAL invoice (not in WME solduri yet),
-                // so we MUST NOT assume it's paid — we should proceed with sending.
-                if found_invoices_count > 0 && found_unpaid_amount < (total_value - 0.5) {
-                    warn!("[CHITANTE][SEND] DETECTED DUPLICATE! WME Rest ({:.2}) < Receipt Value ({:.2}). Marking as synced.", found_unpaid_amount, total_value);
-                    already_paid = true;
-                } else if found_invoices_count == 0 {
-                    info!("[CHITANTE][SEND] No matching invoices found in WME solduri — likely a local invoice. Proceeding with send.");
-                }
+    if page.closed_at.is_some() {
+        cash_register::set_document_path(&conn, &page.nume_casa, &page.day, &pdf_path_str)
+            .map_err(|e| e.to_string())?;
+    }
 
+    let destination = printing::destination(&conn, printing::REPORT, printer_name);
+    drop(conn);
+    let printed = if destination.backend == printing::Backend::EscPos {
+        Err("registrul de casă nu are format pentru imprimanta termică".to_string())
+    } else {
+        print_jobs::submit(db, &destination, &format!("{}_{}", page.nume_casa, page.day), &pdf_path_str).map(|_| ())
+    };
+    if let Err(e) = &printed {
+        warn!("[CASH_REGISTER] Could not print register page {}: {}", pdf_path_str, e);
+    }
+    Ok((pdf_path_str, printed.err()))
+}
 
-            } else {
-                 info!("[CHITANTE][SEND] No specific invoice numbers to check (advance payment?). Proceeding with send.");
+/// Closes the register page of `date` (today by default) for the current cash
+/// desk and prints it. The page stays closed even if printing fails.
+#[tauri::command]
+pub fn close_cash_day(
+    db: State<'_, Database>,
+    date: Option<String>,
+    printer_name: Option<String>,
+) -> Result<CashRegisterDay, String> {
+    let date = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let mut page = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let casa = cash_register::current_casa(&conn);
+        conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+            .map_err(|e| e.to_string())?;
+        match cash_register::close(&conn, &casa, &date) {
+            Ok(page) => {
+                conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+                page
+            }
+            Err(e) => {
+                let _ = conn.execute("ROLLBACK", []);
+                return Err(e);
             }
-        },
-        Err(e) => {
-            warn!("[CHITANTE][SEND] Failed to check balance before sending: {}. Proceeding with send to be safe.", e);
-            // If check fails (e.g. timeout), we proceed with send.
-            // Worst case: duplicate.
-            // Better than blocking payment if check API is down.
         }
-    }
-
-    if already_paid {
-         // Skip sending to API, just mark as synced
-         let conn = db.conn.lock().map_err(|e| e.to_string())?;
-         conn.execute(
-            "UPDATE collections SET status = 'synced', synced_at = ?1, error_message = NULL WHERE COALESCE(receipt_group_id, id) = ?2",
-            params![now_str, receipt_group_id],
-        )
-        .map_err(|e| e.to_string())?;
-
-        // Return updated collection
-        drop(conn); // Drop lock
-        let grouped = get_collections(db.clone(), None)?;
-        let updated = grouped
-            .into_iter()
-            .find(|c| c.id == receipt_group_id)
-            .ok_or_else(|| "Nu s-a putut încărca chitanța actualizată".to_string())?;
+    };
 
-        return Ok(updated);
-    }
+    let (pdf_path, _) = print_cash_register_page(&db, &page, printer_name.as_deref())?;
+    page.document_path = Some(pdf_path);
+    Ok(page)
+}
 
-    // Actually send the request (previously missing variable 'request' is now defined above)
-    let api_result = api.send_collections_to_wme(request).await;
+/// Prints the register page of `date` again; a day still open is printed as
+/// a provisional page.
+#[tauri::command]
+pub fn print_cash_register(
+    db: State<'_, Database>,
+    date: Option<String>,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    let page = get_cash_register(db.clone(), date)?;
+    let (pdf_path, print_error) = print_cash_register_page(&db, &page, printer_name.as_deref())?;
+    Ok(match print_error {
+        None => format!("Registrul de casă a fost trimis la imprimantă. Fișier salvat: {}", pdf_path),
+        Some(e) => format!("Registrul de casă nu a putut fi printat ({}). PDF salvat: {}", e, pdf_path),
+    })
+}
 
-    match api_result {
-        Ok(response) => {
-            info!("[CHITANTE][SEND] CasaBanca response for group {} result={:?} errors={:?}", receipt_group_id, response.result, response.error_list);
-            let conn = db.conn.lock().map_err(|e| e.to_string())?;
-            let err_list = response.result.unwrap_or("".to_string());
+// ==================== PARTNER STATEMENT COMMANDS ====================
 
-            if err_list.to_lowercase() == "ok" || response.error_list.is_empty() {
-                conn.execute(
-                    "UPDATE collections SET status = 'synced', synced_at = ?1, error_message = NULL WHERE COALESCE(receipt_group_id, id) = ?2",
-                    params![now_str, receipt_group_id],
-                )
-                .map_err(|e| e.to_string())?;
-            } else {
-                let err_msg = format!(
-                    "API Error: {}; {:?}. Chitanță salvată: {}",
-                    err_list,
-                    response.error_list,
-                    saved_html_path
-                );
-                conn.execute(
-                    "UPDATE collections SET status = 'failed', error_message = ?1 WHERE COALESCE(receipt_group_id, id) = ?2",
-                    params![err_msg, receipt_group_id],
-                )
-                .map_err(|e| e.to_string())?;
-            }
-        }
-        Err(e) => {
-            info!("[CHITANTE][SEND] CasaBanca call failed for group {} error={}", receipt_group_id, e);
-            let conn = db.conn.lock().map_err(|err| err.to_string())?;
-            let err_msg = format!("{}. Chitanță salvată: {}", e, saved_html_path);
-            conn.execute(
-                "UPDATE collections SET status = 'pending', error_message = ?1 WHERE COALESCE(receipt_group_id, id) = ?2",
-                params![err_msg, receipt_group_id],
-            )
-            .map_err(|err| err.to_string())?;
-        }
-    }
+// Helper: builds the statement and writes it as HTML and PDF to the reports
+// folder. The PDF path is set on the statement.
+fn save_partner_statement(
+    db: &Database,
+    partner_id: &str,
+    from: &str,
+    to: &str,
+) -> Result<PartnerStatement, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let mut statement = partner_statement::build(&conn, partner_id, from, to)?;
+    let issuer = company::issuer(&conn);
+    drop(conn);
+    let images = template_images(&issuer);
+    let html = print_partner_statement::generate_partner_statement_html(&statement, &issuer.profile, &images);
 
-    let grouped = get_collections(db.clone(), None)?;
-    let updated = grouped
-        .into_iter()
-        .find(|c| c.id == receipt_group_id)
-        .ok_or_else(|| "Nu s-a putut încărca chitanța actualizată".to_string())?;
+    let app_data_dir = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join("reports");
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
 
+    let partner_slug: String = statement
+        .partner_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let file_name = format!("fisa_cont_{}_{}_{}", partner_slug, from, to);
+    let html_file_path = app_data_dir.join(format!("{}.html", file_name));
+    let pdf_file_path = app_data_dir.join(format!("{}.pdf", file_name));
+    std::fs::write(&html_file_path, &html)
+        .map_err(|e| format!("Failed to write HTML file: {}", e))?;
+    let pdf_bytes = print_partner_statement::generate_partner_statement_pdf(
+        &statement,
+        &issuer.profile,
+        read_logo_image(&issuer.logo).as_ref(),
+    );
+    std::fs::write(&pdf_file_path, &pdf_bytes)
+        .map_err(|e| format!("Failed to write PDF file: {}", e))?;
+    let pdf_path_str = pdf_file_path.to_string_lossy().to_string();
     info!(
-        "[CHITANTE][SEND] Finished send_collection group={} final_status={} error={:?}",
-        updated.id,
-        updated.status.to_string(),
-        updated.error_message
+        "[STATEMENT] Statement for {} ({} - {}) saved at: {} ({} lines, balance {:.2})",
+        partner_id,
+        from,
+        to,
+        pdf_path_str,
+        statement.lines.len(),
+        statement.closing_balance
     );
 
-    Ok(updated)
+    statement.document_path = Some(pdf_path_str);
+    Ok(statement)
 }
 
+/// Fișă de cont of a partner for `from` - `to` (YYYY-MM-DD), saved as HTML
+/// and PDF.
 #[tauri::command]
-pub fn delete_collection(db: State<'_, Database>, collection_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "DELETE FROM collections WHERE COALESCE(receipt_group_id, id) = ?1 OR id = ?1",
-        [&collection_id],
-    )
-        .map_err(|e| e.to_string())?;
-
-    info!("Deleted collection {}", collection_id);
-    Ok(())
+pub fn generate_partner_statement(
+    db: State<'_, Database>,
+    partner_id: String,
+    from: String,
+    to: String,
+) -> Result<PartnerStatement, String> {
+    save_partner_statement(&db, &partner_id, &from, &to)
 }
 
+/// Generates the statement and prints it for the customer.
 #[tauri::command]
-pub fn get_sales_report(
+pub fn print_partner_statement(
     db: State<'_, Database>,
-    start_date: Option<String>,
-    end_date: Option<String>,
-) -> Result<Vec<SalesReportItem>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    let mut query = "SELECT
-        p.name as partner_name,
-        COUNT(*) as count,
-        SUM(i.total_amount) as total,
-        COALESCE(SUM(inv_qty.total_quantity), 0) as total_quantity
-        FROM invoices i
-        JOIN partners p ON p.id = i.partner_id
-        LEFT JOIN (
-            SELECT invoice_id, SUM(quantity) as total_quantity
-            FROM invoice_items
-            GROUP BY invoice_id
-        ) inv_qty ON inv_qty.invoice_id = i.id
-        WHERE 1 = 1".to_string();
-
-    let mut params: Vec<String> = Vec::new();
-
-    if let Some(start) = start_date {
-        query.push_str(" AND i.created_at >= ?");
-        params.push(format!("{}T00:00:00", start));
-    }
+    partner_id: String,
+    from: String,
+    to: String,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    let statement = save_partner_statement(&db, &partner_id, &from, &to)?;
+    let pdf_path = statement.document_path.unwrap_or_default();
 
-    if let Some(end) = end_date {
-        query.push_str(" AND i.created_at <= ?");
-        params.push(format!("{}T23:59:59", end));
-    }
+    let destination = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        printing::destination(&conn, printing::REPORT, printer_name.as_deref())
+    };
+    let printed = if destination.backend == printing::Backend::EscPos {
+        Err("fișa de cont nu are format pentru imprimanta termică".to_string())
+    } else {
+        print_jobs::submit(&db, &destination, &format!("fisa_cont_{}", partner_id), &pdf_path).map(|_| ())
+    };
+    Ok(match printed {
+        Ok(()) => format!("Fișa de cont a fost trimisă la imprimantă. Fișier salvat: {}", pdf_path),
+        Err(e) => {
+            warn!("[STATEMENT] Could not print statement {}: {}", pdf_path, e);
+            format!("Fișa de cont nu a putut fi printată ({}). PDF salvat: {}", e, pdf_path)
+        }
+    })
+}
 
-    query.push_str(" GROUP BY p.name ORDER BY total DESC");
+// ==================== BALANCE CONFIRMATION COMMANDS ====================
 
-    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+// Helper: writes the letter as HTML and PDF to the reports folder and keeps
+// the PDF path on it.
+fn save_balance_confirmation(db: &Database, confirmation: &mut BalanceConfirmation) -> Result<(), String> {
+    let issuer = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        company::issuer(&conn)
+    };
+    let images = template_images(&issuer);
+    let html = print_balance_confirmation::generate_balance_confirmation_html(confirmation, &issuer.profile, &images);
 
-    // Note: This is simplified. We might need better VAT calculation.
-    let items = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
-        let total: f64 = row.get(2)?;
-        let total_quantity: f64 = row.get(3)?;
-        Ok(SalesReportItem {
-            partner_name: row.get(0)?,
-            invoice_count: row.get(1)?,
-            total_amount: total,
-            total_vat: total * 0.19, // Approximation
-            total_quantity,
-        })
-    }).map_err(|e| e.to_string())?;
+    let app_data_dir = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join("reports")
+        .join(format!("confirmari_sold_{}", confirmation.reference_date));
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
 
-    let mut result = Vec::new();
-    for i in items {
-        result.push(i.map_err(|e| e.to_string())?);
-    }
+    let partner_slug: String = confirmation
+        .partner_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let file_name = format!("confirmare_sold_{}", partner_slug);
+    let html_file_path = app_data_dir.join(format!("{}.html", file_name));
+    let pdf_file_path = app_data_dir.join(format!("{}.pdf", file_name));
+    std::fs::write(&html_file_path, &html)
+        .map_err(|e| format!("Failed to write HTML file: {}", e))?;
+    let pdf_bytes = print_balance_confirmation::generate_balance_confirmation_pdf(
+        confirmation,
+        &issuer.profile,
+        read_logo_image(&issuer.logo).as_ref(),
+    );
+    std::fs::write(&pdf_file_path, &pdf_bytes)
+        .map_err(|e| format!("Failed to write PDF file: {}", e))?;
+    let pdf_path_str = pdf_file_path.to_string_lossy().to_string();
 
-    Ok(result)
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    balance_confirmations::set_document_path(&conn, &confirmation.id, &pdf_path_str)
+        .map_err(|e| e.to_string())?;
+    confirmation.document_path = Some(pdf_path_str);
+    Ok(())
 }
 
+/// Balance confirmation letters for `reference_date` (YYYY-MM-DD): for one
+/// partner, or for every partner of the agent with documents in the WME
+/// balances. Letters already returned signed are left as they are.
 #[tauri::command]
-pub fn get_sales_print_report(
+pub fn generate_balance_confirmations(
     db: State<'_, Database>,
-    start_date: Option<String>,
-    end_date: Option<String>,
-) -> Result<Vec<SalesPrintItem>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    let mut query = "WITH invoice_data AS (
-        SELECT
-            COALESCE(NULLIF(TRIM(p.cod_extern), ''), p.name, 'Partener') AS partner_name,
-            i.created_at,
-            COALESCE(inv_qty.total_quantity, 0) AS total_quantity,
-            COALESCE(inv_totals.total_without_vat, i.total_amount) AS total_without_vat,
-            COALESCE(inv_totals.total_with_vat, i.total_amount * 1.19) AS total_with_vat,
-            COALESCE(col.total_collected, 0) AS collected_amount
-        FROM invoices i
-        JOIN partners p ON p.id = i.partner_id
-        LEFT JOIN (
-            SELECT invoice_id, SUM(quantity) AS total_quantity
-            FROM invoice_items
-            GROUP BY invoice_id
-        ) inv_qty ON inv_qty.invoice_id = i.id
-        LEFT JOIN (
-            SELECT
-                ii.invoice_id,
-                SUM(ii.total_price) AS total_without_vat,
-                SUM(
-                    ii.total_price * (
-                        1 + (COALESCE(CAST(pr.procent_tva AS REAL), 19) / 100.0)
-                    )
-                ) AS total_with_vat
-            FROM invoice_items ii
-            LEFT JOIN products pr ON pr.id = ii.product_id
-            GROUP BY ii.invoice_id
-        ) inv_totals ON inv_totals.invoice_id = i.id
-        LEFT JOIN (
-            SELECT
-                id_partener,
-                COALESCE(numar_factura, '') AS numar_factura,
-                COALESCE(cod_document, '') AS cod_document,
-                SUM(valoare) AS total_collected
-            FROM collections
-            WHERE status IN ('pending', 'sending', 'synced')
-            GROUP BY id_partener, COALESCE(numar_factura, ''), COALESCE(cod_document, '')
-        ) col ON (
-            col.id_partener = i.partner_id
-            AND (
-                col.numar_factura = CAST(i.invoice_number AS TEXT)
-                OR col.cod_document = CAST(i.invoice_number AS TEXT)
-            )
-        )
-        WHERE 1 = 1"
-        .to_string();
-
-    let mut params: Vec<String> = Vec::new();
+    reference_date: String,
+    partner_id: Option<String>,
+) -> Result<Vec<BalanceConfirmation>, String> {
+    let mut confirmations = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let partners = match partner_id {
+            Some(partner_id) => vec![partner_id],
+            None => {
+                let marca_agent: Option<String> = conn
+                    .query_row("SELECT marca_agent FROM agent_settings WHERE id = 1", [], |row| row.get(0))
+                    .optional()
+                    .map_err(|e| e.to_string())?
+                    .flatten()
+                    .filter(|marca: &String| !marca.trim().is_empty());
+                let mut partners = Vec::new();
+                for partner_id in balance_confirmations::agent_partners(&conn, marca_agent.as_deref())
+                    .map_err(|e| e.to_string())?
+                {
+                    let status = balance_confirmations::return_status(&conn, &partner_id, &reference_date)
+                        .map_err(|e| e.to_string())?;
+                    if status.map_or(true, |status| status == balance_confirmations::SENT) {
+                        partners.push(partner_id);
+                    }
+                }
+                partners
+            }
+        };
 
-    if let Some(start) = start_date {
-        query.push_str(" AND i.created_at >= ?");
-        params.push(format!("{}T00:00:00", start));
-    }
+        conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
+            .map_err(|e| e.to_string())?;
+        let mut confirmations = Vec::new();
+        for partner_id in &partners {
+            match balance_confirmations::generate(&conn, partner_id, &reference_date) {
+                Ok(confirmation) => confirmations.push(confirmation),
+                Err(e) => {
+                    let _ = conn.execute("ROLLBACK", []);
+                    return Err(e);
+                }
+            }
+        }
+        conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+        confirmations
+    };
 
-    if let Some(end) = end_date {
-        query.push_str(" AND i.created_at <= ?");
-        params.push(format!("{}T23:59:59", end));
+    for confirmation in confirmations.iter_mut() {
+        save_balance_confirmation(&db, confirmation)?;
     }
-
-    query.push_str(
-        "
-    ),
-    partner_data AS (
-        SELECT
-            partner_name,
-            CASE
-                WHEN collected_amount >= total_without_vat THEN 'paid'
-                ELSE 'unpaid'
-            END AS payment_section,
-            COUNT(*) AS invoice_count,
-            SUM(total_quantity) AS total_quantity,
-            SUM(total_without_vat) AS total_without_vat,
-            SUM(total_with_vat - total_without_vat) AS total_vat,
-            SUM(total_with_vat) AS total_with_vat
-        FROM invoice_data
-        GROUP BY partner_name, payment_section
-    )
-    SELECT
-        partner_name,
-        invoice_count,
-        total_quantity,
-        ROUND(total_quantity / 30.0, 2) AS total_cofrage,
-        total_without_vat,
-        total_vat,
-        total_with_vat,
-        payment_section
-    FROM partner_data
-    WHERE 1 = 1"
+    info!(
+        "[BALANCE_CONFIRMATION] {} letters generated for {}",
+        confirmations.len(),
+        reference_date
     );
-
-    query.push_str(" ORDER BY CASE payment_section WHEN 'unpaid' THEN 1 ELSE 2 END, total_with_vat DESC");
-
-    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
-
-    let items = stmt
-        .query_map(rusqlite::params_from_iter(params.iter()), |row| {
-            Ok(SalesPrintItem {
-                partner_name: row.get(0)?,
-                invoice_count: row.get(1)?,
-                total_quantity: row.get(2)?,
-                total_cofrage: row.get(3)?,
-                total_without_vat: row.get(4)?,
-                total_vat: row.get(5)?,
-                total_with_vat: row.get(6)?,
-                payment_section: row.get(7)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    let mut result = Vec::new();
-    for item in items {
-        result.push(item.map_err(|e| e.to_string())?);
-    }
-
-    Ok(result)
+    Ok(confirmations)
 }
 
 #[tauri::command]
-pub fn get_sales_products_report(
+pub fn get_balance_confirmations(
     db: State<'_, Database>,
-    start_date: Option<String>,
-    end_date: Option<String>,
-) -> Result<Vec<SalesProductReportItem>, String> {
+    reference_date: String,
+) -> Result<Vec<BalanceConfirmation>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    balance_confirmations::list(&conn, &reference_date)
+}
 
-    let mut query = "SELECT
-        ii.product_id,
-        pr.name AS product_name,
-        pr.class AS product_class,
-        p.name AS partner_name,
-        COALESCE(i.invoice_number, 0) AS invoice_number,
-        COALESCE((SELECT carnet_series FROM agent_settings WHERE id = 1), 'FACTURA') AS invoice_series,
-        ii.quantity AS total_quantity,
-        ROUND(ii.quantity / 30.0, 2) AS total_cofrage,
-        ii.total_price AS total_without_vat,
-        ii.total_price * (1 + (COALESCE(CAST(pr.procent_tva AS REAL), 19) / 100.0)) AS total_with_vat,
-        i.created_at
-    FROM invoice_items ii
-    JOIN invoices i ON i.id = ii.invoice_id
-    JOIN partners p ON p.id = i.partner_id
-    LEFT JOIN products pr ON pr.id = ii.product_id
-    WHERE 1 = 1"
-        .to_string();
-
-    let mut params: Vec<String> = Vec::new();
-
-    if let Some(start) = start_date {
-        query.push_str(" AND i.created_at >= ?");
-        params.push(format!("{}T00:00:00", start));
+/// Prints the letters of `reference_date` still waiting for the signed copy,
+/// or the letter of one partner.
+#[tauri::command]
+pub fn print_balance_confirmations(
+    db: State<'_, Database>,
+    reference_date: String,
+    partner_id: Option<String>,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    let (letters, destination) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let letters: Vec<BalanceConfirmation> = balance_confirmations::list(&conn, &reference_date)?
+            .into_iter()
+            .filter(|letter| match &partner_id {
+                Some(partner_id) => &letter.partner_id == partner_id,
+                None => letter.return_status == balance_confirmations::SENT,
+            })
+            .collect();
+        (letters, printing::destination(&conn, printing::REPORT, printer_name.as_deref()))
+    };
+    if letters.is_empty() {
+        return Err(format!("Nu există confirmări de sold de printat pentru {}", reference_date));
     }
-
-    if let Some(end) = end_date {
-        query.push_str(" AND i.created_at <= ?");
-        params.push(format!("{}T23:59:59", end));
+    if destination.backend == printing::Backend::EscPos {
+        return Err("Confirmarea de sold nu are format pentru imprimanta termică".to_string());
     }
 
-    query.push_str(" ORDER BY COALESCE(pr.class, ''), pr.name, i.created_at DESC");
+    let mut failed = 0;
+    for mut letter in letters.iter().cloned() {
+        let missing = letter
+            .document_path
+            .as_ref()
+            .map_or(true, |path| !std::path::Path::new(path).exists());
+        if missing {
+            save_balance_confirmation(&db, &mut letter)?;
+        }
+        let pdf_path = letter.document_path.clone().unwrap_or_default();
+        if let Err(e) = print_jobs::submit(&db, &destination, &format!("confirmare_sold_{}", letter.id), &pdf_path) {
+            warn!("[BALANCE_CONFIRMATION] Could not print letter {}: {}", pdf_path, e);
+            failed += 1;
+        }
+    }
+    Ok(if failed == 0 {
+        format!("{} confirmări de sold trimise la imprimantă.", letters.len())
+    } else {
+        format!(
+            "{} din {} confirmări de sold nu au putut fi printate; PDF-urile sunt în folderul de rapoarte.",
+            failed,
+            letters.len()
+        )
+    })
+}
 
-    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+/// Records the signed copy coming back: `confirmed`, `disputed` (with the
+/// balance the client states) or `sent` to undo.
+#[tauri::command]
+pub fn record_balance_confirmation_return(
+    db: State<'_, Database>,
+    id: String,
+    status: String,
+    partner_balance: Option<f64>,
+    notes: Option<String>,
+) -> Result<BalanceConfirmation, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    balance_confirmations::record_return(&conn, &id, &status, partner_balance, notes.as_deref())
+}
 
-    let items = stmt
-        .query_map(rusqlite::params_from_iter(params.iter()), |row| {
-            Ok(SalesProductReportItem {
-                product_id: row.get(0)?,
-                product_name: row.get(1)?,
-                product_class: row.get(2)?,
-                partner_name: row.get(3)?,
-                invoice_number: row.get(4)?,
-                invoice_series: row.get(5)?,
-                total_quantity: row.get(6)?,
-                total_cofrage: row.get(7)?,
-                total_without_vat: row.get(8)?,
-                total_with_vat: row.get(9)?,
-                created_at: row.get(10)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+// ==================== AGING REPORT COMMANDS ====================
 
-    let mut result = Vec::new();
-    for item in items {
-        result.push(item.map_err(|e| e.to_string())?);
-    }
+// Helper: report on `as_of` (today by default) grouped by partner unless
+// told otherwise.
+fn build_aging_report(
+    db: &State<'_, Database>,
+    as_of: Option<String>,
+    group_by: Option<String>,
+) -> Result<AgingReport, String> {
+    let as_of = as_of.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let group_by = group_by.unwrap_or_else(|| aging::BY_PARTNER.to_string());
+    let balances = get_client_balances(db.clone(), None)?;
+    aging::build(&balances, &as_of, &group_by)
+}
 
-    Ok(result)
+/// Open balances by partner, location or agent, split into current, 1-30,
+/// 31-60, 61-90 and over 90 days overdue.
+#[tauri::command]
+pub fn get_aging_report(
+    db: State<'_, Database>,
+    as_of: Option<String>,
+    group_by: Option<String>,
+) -> Result<AgingReport, String> {
+    build_aging_report(&db, as_of, group_by)
 }
 
 #[tauri::command]
-pub fn get_collections_report(
+pub fn print_aging_report(
     db: State<'_, Database>,
-    start_date: Option<String>,
-    end_date: Option<String>,
-) -> Result<Vec<CollectionsReportItem>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    as_of: Option<String>,
+    group_by: Option<String>,
+    printer_name: Option<String>,
+) -> Result<String, String> {
+    let report = build_aging_report(&db, as_of, group_by)?;
+    let (issuer, destination) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        (
+            company::issuer(&conn),
+            printing::destination(&conn, printing::REPORT, printer_name.as_deref()),
+        )
+    };
+    let images = template_images(&issuer);
+    let html = print_aging_report::generate_aging_report_html(&report, &issuer.profile, &images);
 
-    let mut query = "SELECT
-        partner_name,
-        COUNT(*) as count,
-        SUM(valoare) as total,
-        status
-        FROM collections".to_string();
+    let app_data_dir = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join("reports");
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
 
-    let mut params: Vec<String> = Vec::new();
+    let file_name = format!("vechime_creante_{}_{}", report.group_by, report.as_of);
+    let html_file_path = app_data_dir.join(format!("{}.html", file_name));
+    let pdf_file_path = app_data_dir.join(format!("{}.pdf", file_name));
+    std::fs::write(&html_file_path, &html)
+        .map_err(|e| format!("Failed to write HTML file: {}", e))?;
+    let pdf_bytes =
+        print_aging_report::generate_aging_report_pdf(&report, &issuer.profile, read_logo_image(&issuer.logo).as_ref());
+    std::fs::write(&pdf_file_path, &pdf_bytes)
+        .map_err(|e| format!("Failed to write PDF file: {}", e))?;
+    let pdf_path = pdf_file_path.to_string_lossy().to_string();
+    info!("[AGING] Report saved at: {} ({} rows)", pdf_path, report.rows.len());
 
-    let mut where_started = false;
+    let printed = if destination.backend == printing::Backend::EscPos {
+        Err("raportul de vechime nu are format pentru imprimanta termică".to_string())
+    } else {
+        print_jobs::submit(&db, &destination, &file_name, &pdf_path).map(|_| ())
+    };
+    Ok(match printed {
+        Ok(()) => format!("Raportul a fost trimis la imprimantă. Fișier salvat: {}", pdf_path),
+        Err(e) => {
+            warn!("[AGING] Could not print report {}: {}", pdf_path, e);
+            format!("Raportul nu a putut fi printat ({}). PDF salvat: {}", e, pdf_path)
+        }
+    })
+}
 
-    if let Some(start) = start_date {
-        query.push_str(" WHERE data_incasare >= ?");
-        params.push(format!("{}T00:00:00", start));
-        where_started = true;
-    }
+/// Writes the report as CSV. `path` is a file or a folder, the reports folder
+/// by default. Returns the file path.
+#[tauri::command]
+pub fn export_aging_report(
+    db: State<'_, Database>,
+    as_of: Option<String>,
+    group_by: Option<String>,
+    path: Option<String>,
+) -> Result<String, String> {
+    let report = build_aging_report(&db, as_of, group_by)?;
 
-    if let Some(end) = end_date {
-        if where_started {
-            query.push_str(" AND data_incasare <= ?");
-        } else {
-            query.push_str(" WHERE data_incasare <= ?");
+    let mut file_path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let reports_dir = dirs::config_dir()
+                .ok_or("Could not find app data directory")?
+                .join("facturi.softconsulting.com")
+                .join("reports");
+            std::fs::create_dir_all(&reports_dir)
+                .map_err(|e| format!("Failed to create reports directory: {}", e))?;
+            reports_dir
         }
-        params.push(format!("{}T23:59:59", end));
+    };
+    if file_path.is_dir() {
+        file_path.push(format!("vechime_creante_{}_{}.csv", report.group_by, report.as_of));
     }
+    std::fs::write(&file_path, aging::to_csv(&report))
+        .map_err(|e| format!("Nu s-a putut scrie fișierul CSV: {}", e))?;
 
-    query.push_str(" GROUP BY partner_name, status ORDER BY total DESC");
+    info!("[AGING] Report exported to {}", file_path.display());
+    Ok(file_path.to_string_lossy().to_string())
+}
 
-    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+// ==================== MAINTENANCE COMMANDS ====================
 
-    let items = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
-        Ok(CollectionsReportItem {
-            partner_name: row.get(0)?,
-            collection_count: row.get(1)?,
-            total_amount: row.get(2)?,
-            status: row.get(3)?,
-        })
-    }).map_err(|e| e.to_string())?;
+fn build_archive_status(conn: &rusqlite::Connection) -> Result<ArchiveStatus, String> {
+    let archived_invoices: i64 = conn
+        .query_row("SELECT COUNT(*) FROM archive.invoices", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let archived_collections: i64 = conn
+        .query_row("SELECT COUNT(*) FROM archive.collections", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
 
-    let mut result = Vec::new();
-    for i in items {
-        result.push(i.map_err(|e| e.to_string())?);
-    }
+    Ok(ArchiveStatus {
+        retention_months: archive::retention_months(conn),
+        archived_invoices,
+        archived_collections,
+        database_size_bytes: archive::schema_size(conn, "main"),
+        archive_size_bytes: archive::schema_size(conn, "archive"),
+    })
+}
 
-    Ok(result)
+#[tauri::command]
+pub fn get_archive_status(db: State<'_, Database>) -> Result<ArchiveStatus, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    build_archive_status(&conn)
 }
 
 #[tauri::command]
-pub fn get_daily_collections_report(
-    db: State<'_, Database>,
-    date: Option<String>,
-) -> Result<DailyCollectionsReport, String> {
+pub fn set_retention_months(db: State<'_, Database>, months: i64) -> Result<ArchiveStatus, String> {
+    if months < archive::MIN_RETENTION_MONTHS {
+        return Err(format!(
+            "Perioada de păstrare trebuie să fie de cel puțin {} luni.",
+            archive::MIN_RETENTION_MONTHS
+        ));
+    }
+
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO agent_settings (id, retention_months, updated_at) VALUES (1, ?1, ?2) \
+         ON CONFLICT(id) DO UPDATE SET retention_months = excluded.retention_months, updated_at = excluded.updated_at",
+        params![months, Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
 
-    let target_date = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
-    let previous_date = chrono::NaiveDate::parse_from_str(&target_date, "%Y-%m-%d")
-        .map_err(|e| format!("Dată invalidă: {}", e))?
-        .pred_opt()
-        .ok_or_else(|| "Nu s-a putut calcula ziua precedentă".to_string())?
-        .format("%Y-%m-%d")
-        .to_string();
+    info!("[ARCHIVE] Retention set to {} months", months);
+    build_archive_status(&conn)
+}
 
-    let mut stmt = conn
-        .prepare(
-            r#"
-            SELECT
-                COALESCE(NULLIF(TRIM(p.cod_extern), ''), c.partner_name, p.name, 'Partener') AS partner_name,
-                SUM(
-                    CASE
-                        WHEN i.id IS NOT NULL AND substr(i.created_at, 1, 10) = ?1 THEN c.valoare
-                        ELSE 0
-                    END
-                ) AS amount_from_today_sales,
-                SUM(
-                    CASE
-                        WHEN i.id IS NULL OR substr(i.created_at, 1, 10) <> ?1 THEN c.valoare
-                        ELSE 0
-                    END
-                ) AS amount_from_previous_debt,
-                SUM(c.valoare) AS total_amount
-            FROM collections c
-            LEFT JOIN partners p ON p.id = c.id_partener
-            LEFT JOIN invoices i ON i.partner_id = c.id_partener
-                AND (
-                    CAST(i.invoice_number AS TEXT) = COALESCE(c.numar_factura, '')
-                    OR CAST(i.invoice_number AS TEXT) = COALESCE(c.cod_document, '')
-                )
-            WHERE substr(c.data_incasare, 1, 10) = ?1
-              AND c.status IN ('pending', 'sending', 'synced')
-                        GROUP BY COALESCE(NULLIF(TRIM(p.cod_extern), ''), c.partner_name, p.name, 'Partener')
-            ORDER BY total_amount DESC
-            "#,
-        )
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn archive_old_documents(db: State<'_, Database>) -> Result<ArchiveResult, String> {
+    if db.is_sending_invoices.load(std::sync::atomic::Ordering::SeqCst)
+        || db.is_syncing_collections.load(std::sync::atomic::Ordering::SeqCst)
+    {
+        return Err("Arhivarea nu poate rula în timpul trimiterii documentelor. Încearcă din nou după sincronizare.".to_string());
+    }
 
-    let rows = stmt
-        .query_map([&target_date], |row| {
-            Ok(DailyCollectionsPartnerItem {
-                partner_name: row.get(0)?,
-                amount_from_today_sales: row.get(1)?,
-                amount_from_previous_debt: row.get(2)?,
-                total_amount: row.get(3)?,
-            })
-        })
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let months = archive::retention_months(&conn);
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
         .map_err(|e| e.to_string())?;
 
-    let mut items = Vec::new();
-    for row in rows {
-        items.push(row.map_err(|e| e.to_string())?);
+    match archive::archive_documents(&conn, months) {
+        Ok(result) => {
+            conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", []);
+            warn!("[ARCHIVE] Failed, nothing was moved: {}", e);
+            Err(format!("Arhivarea a eșuat: {}", e))
+        }
     }
+}
 
-    let current_day_collections_total: f64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
-            [&target_date],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn vacuum_database(db: State<'_, Database>) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
 
-    let previous_day_collections_total: f64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(valoare), 0) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
-            [&previous_date],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+    let main_before = archive::schema_size(&conn, "main");
+    let archive_before = archive::schema_size(&conn, "archive");
 
-    let current_day_receipts_count: i64 = conn
-        .query_row(
-            "SELECT COUNT(DISTINCT COALESCE(receipt_group_id, id)) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
-            [&target_date],
-            |row| row.get(0),
-        )
+    conn.execute_batch("VACUUM main; VACUUM archive;")
         .map_err(|e| e.to_string())?;
 
-    let previous_day_receipts_count: i64 = conn
-        .query_row(
-            "SELECT COUNT(DISTINCT COALESCE(receipt_group_id, id)) FROM collections WHERE substr(data_incasare, 1, 10) = ?1 AND status IN ('pending', 'sending', 'synced')",
-            [&previous_date],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+    let main_after = archive::schema_size(&conn, "main");
+    let archive_after = archive::schema_size(&conn, "archive");
 
-    let (receipts_today_invoices_count, receipts_previous_debt_count): (i64, i64) = conn
-        .query_row(
-            r#"
-            SELECT
-                COUNT(DISTINCT CASE
-                    WHEN i.id IS NOT NULL AND substr(i.created_at, 1, 10) = ?1
-                    THEN COALESCE(c.receipt_group_id, c.id)
-                END) AS receipts_today_invoices_count,
-                COUNT(DISTINCT CASE
-                    WHEN i.id IS NULL OR substr(i.created_at, 1, 10) <> ?1
-                    THEN COALESCE(c.receipt_group_id, c.id)
-                END) AS receipts_previous_debt_count
-            FROM collections c
-            LEFT JOIN invoices i ON i.partner_id = c.id_partener
-                AND (
-                    CAST(i.invoice_number AS TEXT) = COALESCE(c.numar_factura, '')
-                    OR CAST(i.invoice_number AS TEXT) = COALESCE(c.cod_document, '')
-                )
-            WHERE substr(c.data_incasare, 1, 10) = ?1
-              AND c.status IN ('pending', 'sending', 'synced')
-            "#,
-            [&target_date],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
+    info!(
+        "[VACUUM] facturi.db {} -> {} bytes, archive.db {} -> {} bytes",
+        main_before, main_after, archive_before, archive_after
+    );
+
+    Ok(format!(
+        "Baza de date a fost compactată: {:.1} MB -> {:.1} MB (arhivă: {:.1} MB -> {:.1} MB)",
+        main_before as f64 / 1_048_576.0,
+        main_after as f64 / 1_048_576.0,
+        archive_before as f64 / 1_048_576.0,
+        archive_after as f64 / 1_048_576.0
+    ))
+}
+
+#[tauri::command]
+pub fn run_integrity_check(db: State<'_, Database>) -> Result<IntegrityReport, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    integrity::run_checks(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn repair_integrity_issues(db: State<'_, Database>, issue_codes: Vec<String>) -> Result<IntegrityReport, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+
+    conn.execute("BEGIN IMMEDIATE TRANSACTION", [])
         .map_err(|e| e.to_string())?;
 
-    Ok(DailyCollectionsReport {
-        items,
-        receipts_today_invoices_count,
-        receipts_previous_debt_count,
-        current_day_receipts_count,
-        previous_day_receipts_count,
-        current_day_collections_total,
-        previous_day_collections_total,
-        total_day_collections: current_day_collections_total,
-    })
+    let applied = match integrity::repair(&conn, &issue_codes) {
+        Ok(applied) => applied,
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(format!("Repararea a eșuat, nu s-a modificat nimic: {}", e));
+        }
+    };
+
+    conn.execute("COMMIT", []).map_err(|e| e.to_string())?;
+
+    let mut report = integrity::run_checks(&conn).map_err(|e| e.to_string())?;
+    report.applied_fixes = applied;
+    Ok(report)
 }
 
+/// Records the reason a number reported as an unexplained gap was skipped.
 #[tauri::command]
-pub fn print_daily_report(
+pub fn explain_number_gap(
     db: State<'_, Database>,
-    date: Option<String>,
-    printer_name: Option<String>,
-) -> Result<String, String> {
+    document_type: String,
+    series: String,
+    number: i64,
+    reason: String,
+) -> Result<IntegrityReport, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let applied = integrity::explain_gap(&conn, &document_type, &series, number, &reason)?;
 
-    // Determine date to filter (YYYY-MM-DD)
-    let date_str = date.unwrap_or_else(|| {
-        Local::now().format("%Y-%m-%d").to_string()
-    });
+    let mut report = integrity::run_checks(&conn).map_err(|e| e.to_string())?;
+    report.applied_fixes = vec![applied];
+    Ok(report)
+}
 
-    info!("Generating daily sales report for date: {}", date_str);
+// ==================== AUDIT COMMANDS ====================
 
-    // Fetch invoices for this date
-    // We assume created_at is ISO string, so we match YYYY-MM-DD%
-    let param_date = format!("{}%", date_str);
+#[tauri::command]
+pub fn get_document_history(
+    db: State<'_, Database>,
+    document_type: String,
+    document_id: String,
+) -> Result<Vec<DocumentEvent>, String> {
+    if document_type != document_events::INVOICE && document_type != document_events::COLLECTION {
+        return Err(format!("Tip de document necunoscut: {}", document_type));
+    }
 
-    let mut stmt = conn.prepare(
-        r#"SELECT
-                i.id, i.partner_id, p.name, p.cif, p.reg_com, i.location_id, l.name, l.address,
-                i.status, i.total_amount, i.notes, i.created_at, i.sent_at, i.error_message,
-                (SELECT COUNT(*) FROM invoice_items WHERE invoice_id = i.id),
-                p.scadenta_la_vanzare
-            FROM invoices i
-            JOIN partners p ON i.partner_id = p.id
-            JOIN locations l ON i.location_id = l.id
-            WHERE i.created_at LIKE ?1
-            ORDER BY i.created_at ASC"#
-    ).map_err(|e| e.to_string())?;
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    document_events::history(&conn, &document_type, &document_id).map_err(|e| e.to_string())
+}
 
-    let invoices_iter = stmt.query_map([&param_date], |row| {
-        Ok(Invoice {
-            id: row.get(0)?,
-            partner_id: row.get(1)?,
-            partner_name: row.get(2)?,
-            partner_cif: row.get(3)?,
-            partner_reg_com: row.get(4)?,
-            location_id: row.get(5)?,
-            location_name: row.get(6)?,
-            location_address: row.get(7)?,
-            status: InvoiceStatus::from(row.get::<_, String>(8)?),
-            total_amount: row.get(9)?,
-            notes: row.get(10)?,
-            created_at: row.get(11)?,
-            sent_at: row.get(12)?,
-            error_message: row.get(13)?,
-            item_count: row.get(14)?,
-            partner_payment_term: row.get(15)?,
-            invoice_number: 0,
-            invoice_series: None,
-        })
-    }).map_err(|e| e.to_string())?;
+// ==================== E-FACTURA COMMANDS ====================
 
-    let mut invoices = Vec::new();
-    let mut total_sales: f64 = 0.0;
+// (VAT id with country prefix, CUI) from a CIF as typed: "RO 5379259" is a
+// VAT payer, "5379259" is not
+fn ubl_tax_ids(cif: &str) -> (Option<String>, Option<String>) {
+    let cif: String = cif.split_whitespace().collect::<String>().to_uppercase();
+    if cif.is_empty() {
+        return (None, None);
+    }
+    match cif.strip_prefix("RO") {
+        Some(cui) => (Some(cif.clone()), Some(cui.to_string())),
+        None => (None, Some(cif)),
+    }
+}
 
-    for invoice in invoices_iter {
-        let inv = invoice.map_err(|e| e.to_string())?;
-        total_sales += inv.total_amount;
-        invoices.push(inv);
+fn ubl_country(tara: Option<&str>) -> String {
+    let tara = tara.map(str::trim).unwrap_or_default().to_uppercase();
+    match tara.as_str() {
+        "" | "RO" | "ROU" | "ROMANIA" | "ROMÂNIA" => "RO".to_string(),
+        _ => tara,
     }
+}
 
-    // Generate HTML
-    let logo_base64 = read_logo_to_base64();
-    let html = print_daily_report::generate_daily_report_html(
-        &invoices,
-        &date_str,
-        total_sales,
-        logo_base64.as_deref(),
-    );
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
 
-    // Save to reports folder
-    let app_data_dir = dirs::config_dir()
-        .ok_or("Could not find app data directory")?
-        .join("facturi.softconsulting.com")
-        .join("reports");
+/// The invoice as an e-Factura document: seller from the issuing company
+/// profile, buyer from the partner and the delivery location.
+fn load_ubl_document(conn: &rusqlite::Connection, invoice_id: &str) -> Result<ubl::Document, String> {
+    let data = load_invoice_print_data(conn, invoice_id)?;
+    if matches!(data.invoice.status, InvoiceStatus::Cancelled) {
+        return Err("Factura este anulată și nu poate fi transmisă în e-Factura.".to_string());
+    }
+    let issuer = company::invoice_issuer(conn, invoice_id);
+    let profile = &issuer.profile;
 
-    std::fs::create_dir_all(&app_data_dir)
-        .map_err(|e| format!("Failed to create reports directory: {}", e))?;
+    let (strada, numar, address, localitate, judet, tara, sector, cod_postal, telefon, email, moneda) = conn
+        .query_row(
+            "SELECT l.strada, l.numar, l.address, l.localitate, l.judet, l.tara, l.sector, l.cod_postal,
+                    l.telefon, l.email, p.moneda
+             FROM invoices i
+             JOIN partners p ON i.partner_id = p.id
+             JOIN locations l ON i.location_id = l.id
+             WHERE i.id = ?1",
+            [invoice_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                    row.get::<_, Option<String>>(9)?,
+                    row.get::<_, Option<String>>(10)?,
+                ))
+            },
+        )
+        .map_err(|e| format!("Invoice not found: {}", e))?;
 
-    let file_name = format!("raport_{}", date_str);
-    let html_file_path = app_data_dir.join(format!("{}.html", file_name));
-    let pdf_file_path = app_data_dir.join(format!("{}.pdf", file_name));
+    let street = match (non_empty(strada), non_empty(numar)) {
+        (Some(strada), Some(numar)) => format!("{} nr. {}", strada, numar),
+        (Some(strada), None) => strada,
+        _ => non_empty(address).unwrap_or_default(),
+    };
+    // Bucharest addresses keep the sector in its own column
+    let mut city = non_empty(localitate).unwrap_or_default();
+    if let Some(sector) = non_empty(sector) {
+        if !city.to_uppercase().contains("SECTOR") {
+            city = format!("{} Sector {}", city, sector.trim_start_matches(|c: char| !c.is_ascii_digit()));
+        }
+    }
 
-    std::fs::write(&html_file_path, &html)
-        .map_err(|e| format!("Failed to write HTML file: {}", e))?;
+    let (buyer_vat_id, buyer_legal_id) = ubl_tax_ids(data.invoice.partner_cif.as_deref().unwrap_or_default());
+    let buyer = ubl::Party {
+        name: data.invoice.partner_name.clone(),
+        vat_id: buyer_vat_id,
+        legal_id: buyer_legal_id,
+        legal_form: None,
+        street,
+        city,
+        postal_zone: non_empty(cod_postal),
+        county: judet.unwrap_or_default(),
+        country: ubl_country(tara.as_deref()),
+        phone: non_empty(telefon),
+        email: non_empty(email),
+    };
 
-    let html_path_str = html_file_path.to_string_lossy().to_string();
-    let pdf_path_str = pdf_file_path.to_string_lossy().to_string();
+    let (seller_vat_id, seller_legal_id) = ubl_tax_ids(&profile.cif);
+    let seller = ubl::Party {
+        name: profile.name.clone(),
+        vat_id: seller_vat_id,
+        legal_id: seller_legal_id,
+        legal_form: non_empty(Some(
+            [
+                profile.reg_com.trim().to_string(),
+                non_empty(Some(profile.capital.clone()))
+                    .map(|capital| format!("Capital social: {}", capital))
+                    .unwrap_or_default(),
+            ]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", "),
+        )),
+        street: profile.address.clone(),
+        city: profile.localitate.clone(),
+        postal_zone: non_empty(Some(profile.cod_postal.clone())),
+        county: profile.judet.clone(),
+        country: "RO".to_string(),
+        phone: non_empty(profile.phone.clone()),
+        email: non_empty(profile.email.clone()),
+    };
 
-    info!("Generated report HTML at: {}", html_path_str);
+    let issue_date = chrono::DateTime::parse_from_rfc3339(&data.invoice.created_at)
+        .map_err(|e| format!("Failed to parse invoice date: {}", e))?
+        .date_naive();
 
-    // Convert HTML to PDF using Edge (headless)
-    #[cfg(target_os = "windows")]
-    {
-        let mut pdf_generated = false;
-        let mut print_file = html_path_str.clone();
-
-        // Try Edge first
-        let edge_paths = vec![
-            "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe",
-            "C:\\Program Files\\Microsoft\\Edge\\Application\\msedge.exe",
-        ];
-
-        for edge_path in edge_paths {
-            if std::path::Path::new(edge_path).exists() {
-                let file_url = format!("file:///{}", html_path_str.replace('\\', "/"));
-
-                let output = std::process::Command::new(edge_path)
-                    .args(&[
-                        "--headless",
-                        "--disable-gpu",
-                        "--no-sandbox",
-                        "--disable-dev-shm-usage",
-                        &format!("--print-to-pdf={}", pdf_path_str),
-                        &file_url,
-                    ])
-                    .output();
-
-                match output {
-                    Ok(result) => {
-                        info!("Edge command executed. Status: {}", result.status);
-                        // Give Edge time to write
-                        let mut waited = 0;
-                        while waited < 5000 {
-                            if wait_for_file_ready(&pdf_path_str, 1000, 300) {
-                                pdf_generated = true;
-                                print_file = pdf_path_str.clone();
-                                info!("PDF generated successfully at: {}", pdf_path_str);
-                                break;
-                            }
-                            std::thread::sleep(std::time::Duration::from_millis(100));
-                            waited += 100;
-                        }
-                        if pdf_generated {
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        info!("Failed to use Edge: {}", e);
-                    }
-                }
-            }
-        }
+    let lines = data
+        .items
+        .iter()
+        .map(|item| ubl::Line {
+            name: item.product_name.clone(),
+            seller_item_id: Some(item.product_id.clone()),
+            quantity: item.quantity,
+            unit: item.unit_of_measure.clone(),
+            unit_price: item.unit_price,
+            net_amount: item.total_price,
+            // Same default as the printed invoice
+            vat_percent: item.tva_percent.unwrap_or(19.0),
+            // The products synced from WME carry no reverse charge flag
+            reverse_charge: false,
+        })
+        .collect();
 
-        if !pdf_generated {
-            info!("PDF generation failed, will print HTML directly");
-        }
+    Ok(ubl::Document {
+        number: format!("{}{}", data.carnet_series.trim(), data.invoice_number),
+        issue_date,
+        due_date: issue_date + chrono::Duration::days(data.payment_days),
+        currency: non_empty(moneda).map(|m| m.to_uppercase()).unwrap_or_else(|| "RON".to_string()),
+        note: non_empty(data.invoice.notes.clone()),
+        vat_on_collection: profile.vat_on_collection,
+        account: profile.accounts_for_print().first().map(|account| ubl::PaymentAccount {
+            iban: account.iban.clone(),
+            bank_name: non_empty(Some(account.bank_name.clone())),
+        }),
+        payment_terms: Some(format!("Termen de plată: {} zile", data.payment_days)),
+        seller,
+        buyer,
+        lines,
+    })
+}
 
-        // Print using SumatraPDF
-        let printer = printer_name.unwrap_or_else(|| String::from(""));
-
-        // Check if a default printer is available when no specific printer is given
-        if printer.is_empty() {
-            #[cfg(target_os = "windows")]
-            {
-                // Try to detect default printer using PowerShell
-                if let Ok(output) = std::process::Command::new("powershell")
-                    .args(&["-NoProfile", "-Command", "Get-CimInstance -Class Win32_Printer | Where-Object { $_.Default -eq $true } | Select-Object -ExpandProperty Name"])
-                    .output()
-                {
-                    let default_printer = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                    if !default_printer.is_empty() {
-                        info!("Default printer detected: {}", default_printer);
-                    } else {
-                        warn!("⚠ No default printer configured in Windows. Printing may fail.");
-                    }
-                }
-            }
-        }
+/// UBL XML of an invoice, or an error listing every rule it breaks.
+fn invoice_ubl_xml(conn: &rusqlite::Connection, invoice_id: &str) -> Result<(ubl::Document, String), String> {
+    let document = load_ubl_document(conn, invoice_id)?;
+    let xml = ubl::to_xml(&document).map_err(|violations| {
+        let rules: Vec<String> = violations
+            .iter()
+            .map(|violation| format!("[{}] {}", violation.rule, violation.message))
+            .collect();
+        format!("Factura nu respectă regulile e-Factura:\n{}", rules.join("\n"))
+    })?;
+    Ok((document, xml))
+}
 
-        let user_profile = std::env::var("USERPROFILE").unwrap_or_default();
-        let bundled_path = std::env::current_exe()
-            .ok()
-            .and_then(|exe| exe.parent().map(|p| p.join("resources").join("SumatraPDF.exe")));
+/// e-Factura rules the invoice breaks, as far as `ubl::validate` knows them.
+/// A pre-check only: ANAF's validator decides on upload.
+#[tauri::command]
+pub fn validate_invoice_ubl(db: State<'_, Database>, invoice_id: String) -> Result<Vec<UblRuleViolation>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let document = load_ubl_document(&conn, &invoice_id)?;
+    Ok(ubl::validate(&document))
+}
+
+/// Writes the invoice as CIUS-RO UBL XML. `path` is a file or a folder; in a
+/// folder the file is named after the invoice number. Returns the file path.
+#[tauri::command]
+pub fn export_invoice_ubl(db: State<'_, Database>, invoice_id: String, path: String) -> Result<String, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let (document, xml) = invoice_ubl_xml(&conn, &invoice_id)?;
+    drop(conn);
+
+    let mut file_path = PathBuf::from(&path);
+    if file_path.is_dir() {
+        file_path.push(format!("factura_{}.xml", document.number));
+    }
+    std::fs::write(&file_path, xml).map_err(|e| format!("Nu s-a putut scrie fișierul XML: {}", e))?;
+
+    info!("[UBL] Invoice {} exported to {}", document.number, file_path.display());
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+fn efactura_folder() -> Result<PathBuf, String> {
+    let folder = dirs::config_dir()
+        .ok_or("Could not find app data directory")?
+        .join("facturi.softconsulting.com")
+        .join("efactura");
+    std::fs::create_dir_all(&folder).map_err(|e| format!("Failed to create e-Factura directory: {}", e))?;
+    Ok(folder)
+}
+
+fn efactura_state_label(state: &str) -> &'static str {
+    match state {
+        efactura::SENDING => "în curs de transmitere",
+        efactura::UPLOADED => "încărcată",
+        efactura::PROCESSING => "în prelucrare la ANAF",
+        efactura::ACCEPTED => "acceptată",
+        efactura::REJECTED => "respinsă",
+        _ => "invalidă",
+    }
+}
 
-        let mut sumatra_paths = vec![
-            format!(r"{}\AppData\Local\SumatraPDF\SumatraPDF.exe", user_profile),
-            r"C:\Program Files\SumatraPDF\SumatraPDF.exe".to_string(),
-            r"C:\Program Files (x86)\SumatraPDF\SumatraPDF.exe".to_string(),
-        ];
+/// Client for the configured environment; an expired access token is
+/// refreshed (and saved) first.
+async fn efactura_client(db: &Database) -> Result<(efactura::Client, String), String> {
+    let settings = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        efactura::load_settings(&conn).map_err(|e| e.to_string())?
+    };
 
-        if let Some(p) = bundled_path {
-            sumatra_paths.insert(0, p.to_string_lossy().to_string());
+    let mut token = settings.access_token.clone();
+    if settings.environment != efactura::ENV_LOCAL {
+        if settings.token_expired() || (token.is_none() && settings.refresh_token.is_some()) {
+            let tokens = efactura::refresh_tokens(&settings).await?;
+            let conn = db.conn.lock().map_err(|e| e.to_string())?;
+            efactura::save_tokens(&conn, &tokens)?;
+            token = Some(tokens.access_token);
+        }
+        if token.is_none() {
+            return Err("Autentificarea SPV lipsește. Completează tokenul e-Factura în Setări.".to_string());
         }
+    }
 
-        let mut printed = false;
+    let base_url = efactura::base_url(&settings)?;
+    let client = efactura::Client::new(&base_url, std::sync::Arc::new(efactura::StaticToken(token)))?;
+    Ok((client, settings.environment))
+}
 
-        for sumatra_path in sumatra_paths {
-            if std::path::Path::new(&sumatra_path).exists() {
-                info!("Found SumatraPDF at: {}", sumatra_path);
+// Polls ANAF for an upload and downloads the answer once it is processed
+async fn check_efactura_submission(
+    db: &Database,
+    client: &efactura::Client,
+    submission: EfacturaSubmission,
+) -> Result<EfacturaSubmission, String> {
+    let index = submission
+        .upload_index
+        .clone()
+        .ok_or_else(|| "Factura nu are index de încărcare.".to_string())?;
 
-                let mut args = vec![
-                    "-print-to-default".to_string(),
-                    "-silent".to_string(),
-                ];
+    let (state, download_id, mut errors) = match client.status(&index).await? {
+        efactura::MessageState::Processing => (efactura::PROCESSING, None, Vec::new()),
+        efactura::MessageState::Accepted { download_id } => (efactura::ACCEPTED, Some(download_id), Vec::new()),
+        efactura::MessageState::Rejected { download_id, errors } => (efactura::REJECTED, download_id, errors),
+        efactura::MessageState::Invalid(errors) => (efactura::INVALID, None, errors),
+    };
 
-                if !printer.is_empty() {
-                    args = vec![
-                        "-print-to".to_string(),
-                        printer.clone(),
-                        "-silent".to_string(),
-                    ];
-                }
+    let mut response_path = None;
+    if let Some(download_id) = &download_id {
+        let zip = client.download(download_id).await?;
+        if state == efactura::REJECTED && errors.is_empty() {
+            errors = efactura::rejection_errors(&zip).unwrap_or_else(|e| vec![e]);
+        }
+        let path = efactura_folder()?.join(format!("raspuns_{}_{}.zip", submission.invoice_number, index));
+        std::fs::write(&path, &zip).map_err(|e| format!("Nu s-a putut salva răspunsul ANAF: {}", e))?;
+        response_path = Some(path.to_string_lossy().to_string());
+    }
 
-                args.push(print_file.clone());
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    efactura::update(&conn, submission.id, state, download_id.as_deref(), &errors, response_path.as_deref())
+        .map_err(|e| e.to_string())?;
+    match state {
+        efactura::ACCEPTED => {
+            let message = format!("index {}", index);
+            document_events::record_invoice(&conn, &submission.invoice_id, document_events::EFACTURA_ACCEPTED, Some(&message));
+        }
+        efactura::REJECTED | efactura::INVALID => {
+            let message = format!("index {}: {}", index, errors.join("; "));
+            document_events::record_invoice(&conn, &submission.invoice_id, document_events::EFACTURA_REJECTED, Some(&message));
+        }
+        _ => {}
+    }
+    info!("[EFACTURA] Invoice {} (index {}): {}", submission.invoice_number, index, state);
 
-                // Log the full command for debugging
-                info!("Executing print command with args: {:?}", args);
+    efactura::load(&conn, submission.id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Transmiterea {} nu există.", submission.id))
+}
 
-                let output = std::process::Command::new(&sumatra_path)
-                    .args(&args)
-                    .output();
+#[tauri::command]
+pub fn get_efactura_settings(db: State<'_, Database>) -> Result<EfacturaSettings, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let settings = efactura::load_settings(&conn).map_err(|e| e.to_string())?;
+    Ok(efactura::public_settings(&settings))
+}
 
-                match output {
-                    Ok(result) => {
-                        info!("Print command executed. Status: {}", result.status);
-                        let stdout = String::from_utf8_lossy(&result.stdout);
-                        let stderr = String::from_utf8_lossy(&result.stderr);
+#[tauri::command]
+pub fn save_efactura_settings(db: State<'_, Database>, settings: EfacturaSettings) -> Result<EfacturaSettings, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    efactura::save_settings(&conn, &settings)?;
+    let settings = efactura::load_settings(&conn).map_err(|e| e.to_string())?;
+    info!("[EFACTURA] Settings saved, environment {}", settings.environment);
+    Ok(efactura::public_settings(&settings))
+}
 
-                        if !stdout.is_empty() {
-                            info!("Print stdout: {}", stdout);
-                        }
-                        if !stderr.is_empty() {
-                            info!("Print stderr: {}", stderr);
-                        }
+/// Uploads an invoice to e-Factura. An invoice already uploaded, or being
+/// uploaded, is refused unless its last upload was rejected.
+#[tauri::command]
+pub async fn submit_invoice_efactura(db: State<'_, Database>, invoice_id: String) -> Result<EfacturaSubmission, String> {
+    let (client, environment) = efactura_client(&db).await?;
 
-                        if result.status.success() {
-                            printed = true;
-                            info!("✓ Document sent to printer successfully");
-                            break;
-                        } else {
-                            warn!("✗ Print failed with exit code: {:?}", result.status.code());
-
-                            // Check for specific printer initialization errors
-                            if stdout.contains("CreateDCW") && stdout.contains("failed") {
-                                warn!("Printer driver error detected. The printer may be offline, disconnected, or have driver issues.");
-                                if let Some(printer_name_match) = stdout.lines()
-                                    .find(|line| line.contains("printer:"))
-                                    .and_then(|line| line.split("printer: '").nth(1))
-                                    .and_then(|s| s.split('\'').next())
-                                {
-                                    warn!("Printer: {} - Please check if it's powered on and connected.", printer_name_match);
-                                }
-                            } else if printer.is_empty() {
-                                warn!("Hint: No printer specified. Ensure a default printer is set in Windows.");
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Failed to execute print command: {}", e);
-                    }
-                }
+    // Checked and claimed under one lock: a second click waits here, then sees the claim
+    let (document, xml, xml_path, submission_id) = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        if let Some(existing) = efactura::blocking_submission(&conn, &invoice_id).map_err(|e| e.to_string())? {
+            if existing.state == efactura::SENDING {
+                return Err(format!("Factura {} se transmite deja în e-Factura.", existing.invoice_number));
             }
+            return Err(format!(
+                "Factura {} a fost deja transmisă în e-Factura (index {}, {}).",
+                existing.invoice_number,
+                existing.upload_index.unwrap_or_default(),
+                efactura_state_label(&existing.state)
+            ));
         }
+        let (document, xml) = invoice_ubl_xml(&conn, &invoice_id)?;
+        let xml_path = efactura_folder()?.join(format!(
+            "factura_{}_{}.xml",
+            document.number,
+            Utc::now().format("%Y%m%d%H%M%S")
+        ));
+        let submission_id = efactura::claim(&conn, &invoice_id, &document.number, &environment, &xml_path.to_string_lossy())
+            .map_err(|e| e.to_string())?;
+        (document, xml, xml_path, submission_id)
+    };
 
-        if printed {
-            info!("✓ Report printed successfully");
-            Ok(format!("Report printed successfully. File saved at: {}", print_file))
-        } else {
-            let msg = format!(
-                "Could not print report. The printer may be offline or disconnected. PDF saved at: {}",
-                print_file
-            );
-            warn!("{}", msg);
-            Ok(msg)
+    let upload = match efactura_upload(&document, &xml, &xml_path, &client).await {
+        Ok(upload) => upload,
+        Err(e) => {
+            let conn = db.conn.lock().map_err(|e| e.to_string())?;
+            efactura::release(&conn, submission_id).map_err(|e| e.to_string())?;
+            return Err(e);
+        }
+    };
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    efactura::record_upload(&conn, submission_id, &upload).map_err(|e| e.to_string())?;
+    match &upload {
+        efactura::UploadResponse::Indexed(index) => {
+            let message = format!("index {} ({})", index, environment);
+            document_events::record_invoice(&conn, &invoice_id, document_events::EFACTURA_UPLOADED, Some(&message));
+            info!("[EFACTURA] Invoice {} uploaded, index {}", document.number, index);
+        }
+        efactura::UploadResponse::Refused(errors) => {
+            document_events::record_invoice(&conn, &invoice_id, document_events::EFACTURA_REJECTED, Some(&errors.join("; ")));
+            warn!("[EFACTURA] Upload of invoice {} refused: {}", document.number, errors.join("; "));
         }
     }
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        Ok("Printing is only supported on Windows".to_string())
+    efactura::load(&conn, submission_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Transmiterea {} nu există.", submission_id))
+}
+
+// Saves the XML sent, then uploads it
+async fn efactura_upload(
+    document: &ubl::Document,
+    xml: &str,
+    xml_path: &std::path::Path,
+    client: &efactura::Client,
+) -> Result<efactura::UploadResponse, String> {
+    let cif = document
+        .seller
+        .legal_id
+        .clone()
+        .ok_or_else(|| "Compania emitentă nu are CIF.".to_string())?;
+    std::fs::write(xml_path, xml).map_err(|e| format!("Nu s-a putut scrie fișierul XML: {}", e))?;
+    client.upload(xml, &cif).await
+}
+
+/// Asks ANAF for the state of an upload; a processed one gets its answer
+/// downloaded.
+#[tauri::command]
+pub async fn check_efactura_status(db: State<'_, Database>, submission_id: i64) -> Result<EfacturaSubmission, String> {
+    let submission = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        efactura::load(&conn, submission_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Transmiterea {} nu există.", submission_id))?
+    };
+    if submission.state != efactura::UPLOADED && submission.state != efactura::PROCESSING {
+        return Ok(submission);
+    }
+
+    let (client, environment) = efactura_client(&db).await?;
+    if environment != submission.environment {
+        return Err(format!(
+            "Factura a fost transmisă în mediul e-Factura „{}”, iar în Setări este ales „{}”.",
+            submission.environment, environment
+        ));
+    }
+    check_efactura_submission(&db, &client, submission).await
+}
+
+/// Checks every upload of the current environment ANAF has not finished
+/// processing; returns the ones checked.
+#[tauri::command]
+pub async fn poll_efactura_submissions(db: State<'_, Database>) -> Result<Vec<EfacturaSubmission>, String> {
+    let pending = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        efactura::pending(&conn).map_err(|e| e.to_string())?
+    };
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (client, environment) = efactura_client(&db).await?;
+    let mut checked = Vec::new();
+    for submission in pending.into_iter().filter(|submission| submission.environment == environment) {
+        let number = submission.invoice_number.clone();
+        match check_efactura_submission(&db, &client, submission).await {
+            Ok(submission) => checked.push(submission),
+            Err(e) => warn!("[EFACTURA] Could not check invoice {}: {}", number, e),
+        }
     }
+    Ok(checked)
+}
+
+#[tauri::command]
+pub fn get_efactura_submissions(db: State<'_, Database>, invoice_id: String) -> Result<Vec<EfacturaSubmission>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    efactura::for_invoice(&conn, &invoice_id).map_err(|e| e.to_string())
 }
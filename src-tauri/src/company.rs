@@ -0,0 +1,465 @@
+use chrono::Utc;
+use log::warn;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::models::{CompanyBankAccount, CompanyProfile};
+
+// Issuing company profiles.
+//
+// The company printed as "furnizor" used to be compiled in; it now lives in
+// company_profiles so the same build serves the sister companies and a new
+// bank account is just an edit. Each installation belongs to one agent, who
+// selects the profile in agent_settings.company_profile_id; without a valid
+// selection the oldest profile is used. Logo and stamp are stored as PNG
+// blobs, and a profile without them prints the images bundled with the app.
+//
+// An invoice records the profile that issued it, with a copy of its details
+// as they were, so reprints and e-Factura exports keep the original supplier
+// after the profile is edited or another one is selected.
+
+pub const DEFAULT_LOGO: &[u8] = include_bytes!("../../public/logo.png");
+pub const DEFAULT_STAMP: &[u8] = include_bytes!("../../public/STAMPILA.png");
+
+pub const LOGO: &str = "logo";
+pub const STAMP: &str = "stamp";
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS company_profiles (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        cif TEXT NOT NULL,
+        reg_com TEXT NOT NULL DEFAULT '',
+        address TEXT NOT NULL DEFAULT '',
+        localitate TEXT NOT NULL DEFAULT '',
+        judet TEXT NOT NULL DEFAULT '',
+        cod_postal TEXT NOT NULL DEFAULT '',
+        capital TEXT NOT NULL DEFAULT '',
+        phone TEXT,
+        email TEXT,
+        vat_on_collection INTEGER NOT NULL DEFAULT 0,
+        logo BLOB,
+        stamp BLOB,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS company_bank_accounts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        company_id INTEGER NOT NULL REFERENCES company_profiles(id) ON DELETE CASCADE,
+        bank_name TEXT NOT NULL,
+        iban TEXT NOT NULL,
+        currency TEXT NOT NULL DEFAULT 'RON',
+        is_primary INTEGER NOT NULL DEFAULT 0,
+        sort_order INTEGER NOT NULL DEFAULT 0
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_company_bank_accounts_company ON company_bank_accounts(company_id);
+"#;
+
+/// The company the app shipped with. Seeds the first profile and is printed
+/// if the profiles table cannot be read.
+pub fn builtin() -> CompanyProfile {
+    CompanyProfile {
+        id: None,
+        name: "KARIN SRL".to_string(),
+        cif: "RO5379259".to_string(),
+        reg_com: "J24/380/1994".to_string(),
+        address: "Str. Nicolae Balcescu 43".to_string(),
+        localitate: "Seini".to_string(),
+        judet: "Maramures".to_string(),
+        cod_postal: "435500".to_string(),
+        capital: "200020 RON".to_string(),
+        phone: Some("0753068450".to_string()),
+        email: Some("nasesem@yahoo.com".to_string()),
+        vat_on_collection: false,
+        bank_accounts: vec![CompanyBankAccount {
+            id: None,
+            bank_name: "Banca Transilvania".to_string(),
+            iban: "RO03BTRL02501202L70970XX".to_string(),
+            currency: "RON".to_string(),
+            is_primary: true,
+        }],
+        has_logo: false,
+        has_stamp: false,
+        is_active: true,
+    }
+}
+
+impl CompanyProfile {
+    /// "Seini, Jud. Maramures"
+    pub fn localitate_line(&self) -> String {
+        if self.judet.trim().is_empty() {
+            self.localitate.clone()
+        } else {
+            format!("{}, Jud. {}", self.localitate, self.judet)
+        }
+    }
+
+    /// Accounts in print order: the primary one first.
+    pub fn accounts_for_print(&self) -> Vec<&CompanyBankAccount> {
+        let mut accounts: Vec<&CompanyBankAccount> = self.bank_accounts.iter().collect();
+        accounts.sort_by_key(|account| !account.is_primary);
+        accounts
+    }
+}
+
+/// Profile to print plus its images (the bundled ones when it has none).
+pub struct Issuer {
+    pub profile: CompanyProfile,
+    pub logo: Vec<u8>,
+    pub stamp: Vec<u8>,
+}
+
+/// Creates the first profile from `builtin()` when there is none yet and
+/// selects it. Used by the migration.
+pub fn seed(conn: &Connection) -> rusqlite::Result<()> {
+    let existing: i64 = conn.query_row("SELECT COUNT(*) FROM company_profiles", [], |row| row.get(0))?;
+    if existing > 0 {
+        return Ok(());
+    }
+
+    let id = save(conn, &builtin())?;
+    conn.execute(
+        "UPDATE agent_settings SET company_profile_id = ?1 WHERE id = 1 AND company_profile_id IS NULL",
+        [id],
+    )?;
+    Ok(())
+}
+
+pub fn active_id(conn: &Connection) -> rusqlite::Result<Option<i64>> {
+    let selected: Option<i64> = conn
+        .query_row(
+            "SELECT s.company_profile_id FROM agent_settings s
+             JOIN company_profiles c ON c.id = s.company_profile_id
+             WHERE s.id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    match selected {
+        Some(id) => Ok(Some(id)),
+        None => conn.query_row("SELECT MIN(id) FROM company_profiles", [], |row| row.get(0)),
+    }
+}
+
+fn load_accounts(conn: &Connection, company_id: i64) -> rusqlite::Result<Vec<CompanyBankAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, bank_name, iban, currency, is_primary FROM company_bank_accounts
+         WHERE company_id = ?1 ORDER BY sort_order, id",
+    )?;
+    let accounts = stmt
+        .query_map([company_id], |row| {
+            Ok(CompanyBankAccount {
+                id: row.get(0)?,
+                bank_name: row.get(1)?,
+                iban: row.get(2)?,
+                currency: row.get(3)?,
+                is_primary: row.get::<_, i64>(4)? != 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(accounts)
+}
+
+pub fn load(conn: &Connection, company_id: i64) -> rusqlite::Result<CompanyProfile> {
+    let active = active_id(conn)?;
+    let mut profile = conn.query_row(
+        "SELECT id, name, cif, reg_com, address, localitate, judet, cod_postal, capital, phone, email,
+                vat_on_collection, logo IS NOT NULL, stamp IS NOT NULL
+         FROM company_profiles WHERE id = ?1",
+        [company_id],
+        |row| {
+            Ok(CompanyProfile {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                cif: row.get(2)?,
+                reg_com: row.get(3)?,
+                address: row.get(4)?,
+                localitate: row.get(5)?,
+                judet: row.get(6)?,
+                cod_postal: row.get(7)?,
+                capital: row.get(8)?,
+                phone: row.get(9)?,
+                email: row.get(10)?,
+                vat_on_collection: row.get::<_, i64>(11)? != 0,
+                bank_accounts: Vec::new(),
+                has_logo: row.get(12)?,
+                has_stamp: row.get(13)?,
+                is_active: false,
+            })
+        },
+    )?;
+    profile.bank_accounts = load_accounts(conn, company_id)?;
+    profile.is_active = active == Some(company_id);
+    Ok(profile)
+}
+
+pub fn list(conn: &Connection) -> rusqlite::Result<Vec<CompanyProfile>> {
+    let ids = conn
+        .prepare("SELECT id FROM company_profiles ORDER BY name, id")?
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    ids.into_iter().map(|id| load(conn, id)).collect()
+}
+
+/// Inserts or updates a profile (by `id`) and replaces its bank accounts.
+/// Images are not touched; see `set_image`.
+pub fn save(conn: &Connection, profile: &CompanyProfile) -> rusqlite::Result<i64> {
+    let now = Utc::now().to_rfc3339();
+    let optional = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let phone = optional(&profile.phone);
+    let email = optional(&profile.email);
+
+    let company_id = match profile.id {
+        Some(id) => {
+            let updated = conn.execute(
+                "UPDATE company_profiles SET name = ?1, cif = ?2, reg_com = ?3, address = ?4, localitate = ?5,
+                        judet = ?6, cod_postal = ?7, capital = ?8, phone = ?9, email = ?10,
+                        vat_on_collection = ?11, updated_at = ?12
+                 WHERE id = ?13",
+                params![
+                    profile.name.trim(),
+                    profile.cif.trim(),
+                    profile.reg_com.trim(),
+                    profile.address.trim(),
+                    profile.localitate.trim(),
+                    profile.judet.trim(),
+                    profile.cod_postal.trim(),
+                    profile.capital.trim(),
+                    phone,
+                    email,
+                    profile.vat_on_collection as i64,
+                    now,
+                    id
+                ],
+            )?;
+            if updated == 0 {
+                return Err(rusqlite::Error::QueryReturnedNoRows);
+            }
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO company_profiles (name, cif, reg_com, address, localitate, judet, cod_postal, capital,
+                        phone, email, vat_on_collection, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)",
+                params![
+                    profile.name.trim(),
+                    profile.cif.trim(),
+                    profile.reg_com.trim(),
+                    profile.address.trim(),
+                    profile.localitate.trim(),
+                    profile.judet.trim(),
+                    profile.cod_postal.trim(),
+                    profile.capital.trim(),
+                    phone,
+                    email,
+                    profile.vat_on_collection as i64,
+                    now
+                ],
+            )?;
+            conn.last_insert_rowid()
+        }
+    };
+
+    conn.execute("DELETE FROM company_bank_accounts WHERE company_id = ?1", [company_id])?;
+    // Exactly one primary account: the flagged one, or else the first
+    let primary = profile
+        .bank_accounts
+        .iter()
+        .position(|account| account.is_primary)
+        .unwrap_or(0);
+    for (idx, account) in profile.bank_accounts.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO company_bank_accounts (company_id, bank_name, iban, currency, is_primary, sort_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                company_id,
+                account.bank_name.trim(),
+                account.iban.split_whitespace().collect::<String>().to_uppercase(),
+                Some(account.currency.trim()).filter(|v| !v.is_empty()).unwrap_or("RON"),
+                (idx == primary) as i64,
+                idx as i64,
+            ],
+        )?;
+    }
+
+    Ok(company_id)
+}
+
+/// Stores (or with `None` clears) the logo or stamp of a profile.
+pub fn set_image(conn: &Connection, company_id: i64, kind: &str, png: Option<&[u8]>) -> rusqlite::Result<usize> {
+    let column = if kind == STAMP { "stamp" } else { "logo" };
+    conn.execute(
+        &format!("UPDATE company_profiles SET {} = ?1, updated_at = ?2 WHERE id = ?3", column),
+        params![png, Utc::now().to_rfc3339(), company_id],
+    )
+}
+
+pub fn image(conn: &Connection, company_id: i64, kind: &str) -> rusqlite::Result<Option<Vec<u8>>> {
+    let column = if kind == STAMP { "stamp" } else { "logo" };
+    conn.query_row(
+        &format!("SELECT {} FROM company_profiles WHERE id = ?1", column),
+        [company_id],
+        |row| row.get(0),
+    )
+}
+
+/// The selected profile with the images to print. Never fails: printing
+/// falls back to the built-in company rather than refusing to print.
+pub fn issuer(conn: &Connection) -> Issuer {
+    let loaded = active_id(conn).and_then(|id| match id {
+        Some(id) => Ok(Some((
+            load(conn, id)?,
+            image(conn, id, LOGO)?,
+            image(conn, id, STAMP)?,
+        ))),
+        None => Ok(None),
+    });
+
+    match loaded {
+        Ok(Some((profile, logo, stamp))) => Issuer {
+            profile,
+            logo: logo.unwrap_or_else(|| DEFAULT_LOGO.to_vec()),
+            stamp: stamp.unwrap_or_else(|| DEFAULT_STAMP.to_vec()),
+        },
+        Ok(None) => fallback_issuer(),
+        Err(e) => {
+            warn!("[COMPANY] Could not load the company profile, using the built-in one: {}", e);
+            fallback_issuer()
+        }
+    }
+}
+
+/// Records the selected profile as the issuer of `invoice_id`, with a copy
+/// of its details. Called when the invoice is created.
+pub fn record_invoice_issuer(conn: &Connection, invoice_id: &str) -> rusqlite::Result<()> {
+    let Some(company_id) = active_id(conn)? else {
+        return Ok(());
+    };
+    let profile = load(conn, company_id)?;
+    let snapshot = serde_json::to_string(&profile).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "UPDATE invoices SET company_profile_id = ?1, issuer = ?2 WHERE id = ?3",
+        params![company_id, snapshot, invoice_id],
+    )?;
+    Ok(())
+}
+
+/// The company that issued `invoice_id`, as it was then, with the current
+/// images of its profile. Invoices issued before this was recorded get the
+/// selected profile.
+pub fn invoice_issuer(conn: &Connection, invoice_id: &str) -> Issuer {
+    let recorded = conn
+        .query_row(
+            "SELECT company_profile_id, issuer FROM invoices WHERE id = ?1",
+            [invoice_id],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional();
+    let (company_id, snapshot) = match recorded {
+        Ok(Some((company_id, Some(snapshot)))) => (company_id, snapshot),
+        Ok(_) => return issuer(conn),
+        Err(e) => {
+            warn!("[COMPANY] Could not read the issuer of invoice {}: {}", invoice_id, e);
+            return issuer(conn);
+        }
+    };
+    let profile: CompanyProfile = match serde_json::from_str(&snapshot) {
+        Ok(profile) => profile,
+        Err(e) => {
+            warn!("[COMPANY] Issuer of invoice {} cannot be read, using the selected profile: {}", invoice_id, e);
+            return issuer(conn);
+        }
+    };
+
+    // A deleted profile prints the bundled images
+    let stored_image = |kind: &str| company_id.and_then(|id| image(conn, id, kind).ok().flatten());
+    Issuer {
+        profile,
+        logo: stored_image(LOGO).unwrap_or_else(|| DEFAULT_LOGO.to_vec()),
+        stamp: stored_image(STAMP).unwrap_or_else(|| DEFAULT_STAMP.to_vec()),
+    }
+}
+
+fn fallback_issuer() -> Issuer {
+    Issuer {
+        profile: builtin(),
+        logo: DEFAULT_LOGO.to_vec(),
+        stamp: DEFAULT_STAMP.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoices_db() -> Connection {
+        let conn = crate::database::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO agent_settings (id) VALUES (1) ON CONFLICT(id) DO NOTHING;
+             INSERT INTO partners (id, name, created_at, updated_at) VALUES ('P001', 'Client SRL', '2026-01-01', '2026-01-01');
+             INSERT INTO locations (id, partner_id, name) VALUES ('L001', 'P001', 'Magazin');",
+        )
+        .unwrap();
+        seed(&conn).unwrap();
+        conn
+    }
+
+    fn save_invoice(conn: &Connection, id: &str, number: i64) {
+        conn.execute(
+            "INSERT INTO invoices (id, invoice_number, invoice_series, partner_id, location_id, created_at)
+             VALUES (?1, ?2, 'KRN', 'P001', 'L001', '2026-03-02')",
+            params![id, number],
+        )
+        .unwrap();
+    }
+
+    fn select(conn: &Connection, company_id: i64) {
+        conn.execute("UPDATE agent_settings SET company_profile_id = ?1 WHERE id = 1", [company_id])
+            .unwrap();
+    }
+
+    #[test]
+    fn invoice_keeps_the_issuer_it_was_created_with() {
+        let conn = invoices_db();
+        save_invoice(&conn, "inv-1", 1);
+        record_invoice_issuer(&conn, "inv-1").unwrap();
+
+        // The profile is edited, then another company is selected
+        let first = active_id(&conn).unwrap().unwrap();
+        let mut edited = load(&conn, first).unwrap();
+        edited.name = "KARIN DISTRIBUTIE SRL".to_string();
+        edited.bank_accounts[0].iban = "RO49AAAA1B31007593840000".to_string();
+        save(&conn, &edited).unwrap();
+        let sister = CompanyProfile {
+            id: None,
+            name: "KARIN AGRO SRL".to_string(),
+            cif: "RO12345678".to_string(),
+            ..builtin()
+        };
+        select(&conn, save(&conn, &sister).unwrap());
+
+        let issued = invoice_issuer(&conn, "inv-1");
+        assert_eq!(issued.profile.name, "KARIN SRL");
+        assert_eq!(issued.profile.cif, "RO5379259");
+        assert_eq!(issued.profile.bank_accounts[0].iban, "RO03BTRL02501202L70970XX");
+        assert_eq!(issuer(&conn).profile.name, "KARIN AGRO SRL");
+    }
+
+    #[test]
+    fn invoice_without_a_recorded_issuer_gets_the_selected_profile() {
+        let conn = invoices_db();
+        save_invoice(&conn, "old", 1);
+
+        assert_eq!(invoice_issuer(&conn, "old").profile.name, "KARIN SRL");
+        assert_eq!(invoice_issuer(&conn, "old").logo, DEFAULT_LOGO);
+    }
+}
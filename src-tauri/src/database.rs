@@ -20,6 +20,7 @@ impl Database {
         info!("Opening database at: {:?}", db_path);
 
         let conn = Connection::open(db_path)?;
+        crate::cash_register::register_functions(&conn)?;
 
         // Run migrations
         conn.execute_batch(SCHEMA)?;
@@ -27,6 +28,9 @@ impl Database {
         // Run migrations for new columns
         run_migrations(&conn)?;
 
+        // Old sent/synced documents live in archive.db, attached as `archive`
+        crate::archive::attach(&conn, &app_data_dir.join("archive.db"))?;
+
         // Reset any records stuck in 'sending' state from a previous crash
         let invoices_reset = conn.execute(
             "UPDATE invoices SET status = 'pending' WHERE status = 'sending'", []
@@ -63,6 +67,8 @@ impl Database {
         conn.execute("DELETE FROM partners", [])?;
         conn.execute("DELETE FROM products", [])?;
         conn.execute("DELETE FROM sync_metadata", [])?;
+        conn.execute("DELETE FROM partner_search", [])?;
+        conn.execute("DELETE FROM product_search", [])?;
         
         // Re-enable foreign key constraints
         conn.execute("PRAGMA foreign_keys = ON", [])?;
@@ -269,7 +275,7 @@ const SCHEMA: &str = r#"
 
     CREATE TABLE IF NOT EXISTS invoices (
         id TEXT PRIMARY KEY,
-        invoice_number INTEGER UNIQUE,
+        invoice_number INTEGER,
         invoice_series TEXT,
         partner_id TEXT NOT NULL,
         location_id TEXT NOT NULL,
@@ -280,7 +286,8 @@ const SCHEMA: &str = r#"
         sent_at TEXT,
         error_message TEXT,
         FOREIGN KEY (partner_id) REFERENCES partners(id),
-        FOREIGN KEY (location_id) REFERENCES locations(id)
+        FOREIGN KEY (location_id) REFERENCES locations(id),
+        UNIQUE (invoice_series, invoice_number)
     );
 
     CREATE TABLE IF NOT EXISTS invoice_items (
@@ -700,6 +707,265 @@ fn run_migrations(conn: &rusqlite::Connection) -> Result<()> {
         info!("Migration 21 completed");
     }
 
+    // Migration 22: Full-text search index for partners and products (v1.0.6)
+    if current_version < 22 {
+        info!("Applying migration 22: Add partner/product full-text search index");
+        conn.execute_batch(crate::search_index::SCHEMA)?;
+        crate::search_index::rebuild_all(conn)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (22, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 22 completed");
+    }
+
+    // Migration 23: Retention policy for archiving old documents (v1.0.6)
+    if current_version < 23 {
+        info!("Applying migration 23: Add retention_months to agent_settings");
+        let _ = conn.execute("ALTER TABLE agent_settings ADD COLUMN retention_months INTEGER DEFAULT 12;", []).ok();
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (23, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 23 completed");
+    }
+
+    // Migration 24: Append-only audit trail for invoices and collections (v1.0.6)
+    if current_version < 24 {
+        info!("Applying migration 24: Add document_events audit trail");
+        conn.execute_batch(crate::document_events::SCHEMA)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (24, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 24 completed");
+    }
+
+    // Migration 25: Cancellation instead of deletion for invoices and receipts (v1.0.6)
+    if current_version < 25 {
+        info!("Applying migration 25: Add cancellation columns to invoices and collections");
+        let cancel_columns = vec![
+            "ALTER TABLE invoices ADD COLUMN cancelled_at TEXT;",
+            "ALTER TABLE invoices ADD COLUMN cancel_reason TEXT;",
+            "ALTER TABLE invoices ADD COLUMN cancel_sent_at TEXT;",
+            "ALTER TABLE collections ADD COLUMN cancelled_at TEXT;",
+            "ALTER TABLE collections ADD COLUMN cancel_reason TEXT;",
+        ];
+        for sql in cancel_columns {
+            let _ = conn.execute(sql, []).ok();
+        }
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (25, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 25 completed");
+    }
+
+    // Migration 26: Thermal (ESC/POS) printer settings (v1.0.6)
+    if current_version < 26 {
+        info!("Applying migration 26: Add thermal printer settings to agent_settings");
+        let thermal_columns = vec![
+            "ALTER TABLE agent_settings ADD COLUMN thermal_printer_target TEXT;",
+            "ALTER TABLE agent_settings ADD COLUMN thermal_paper_width INTEGER DEFAULT 80;",
+            "ALTER TABLE agent_settings ADD COLUMN thermal_code_page TEXT DEFAULT 'cp852';",
+            "ALTER TABLE agent_settings ADD COLUMN thermal_code_page_table INTEGER;",
+            "ALTER TABLE agent_settings ADD COLUMN thermal_print_logo INTEGER DEFAULT 1;",
+            "ALTER TABLE agent_settings ADD COLUMN thermal_cut INTEGER DEFAULT 1;",
+            "ALTER TABLE agent_settings ADD COLUMN thermal_feed_lines INTEGER DEFAULT 4;",
+        ];
+        for sql in thermal_columns {
+            let _ = conn.execute(sql, []).ok();
+        }
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (26, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 26 completed");
+    }
+
+    // Migration 27: Issuing company profiles instead of the compiled-in company (v1.0.6)
+    if current_version < 27 {
+        info!("Applying migration 27: Add company profiles");
+        conn.execute_batch(crate::company::SCHEMA)?;
+        let _ = conn.execute("ALTER TABLE agent_settings ADD COLUMN company_profile_id INTEGER;", []).ok();
+        crate::company::seed(conn)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (27, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 27 completed");
+    }
+
+    // Migration 28: Printer profiles and per-document routes (v1.0.6)
+    if current_version < 28 {
+        info!("Applying migration 28: Add printer profiles and print routes");
+        conn.execute_batch(crate::printing::SCHEMA)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (28, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 28 completed");
+    }
+
+    // Migration 29: Print job queue and invoice print counter (v1.0.6)
+    if current_version < 29 {
+        info!("Applying migration 29: Add print jobs and invoice print counter");
+        conn.execute_batch(crate::print_jobs::SCHEMA)?;
+        let _ = conn.execute("ALTER TABLE invoices ADD COLUMN printed_at TEXT;", []).ok();
+        let _ = conn.execute("ALTER TABLE invoices ADD COLUMN print_count INTEGER NOT NULL DEFAULT 0;", []).ok();
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (29, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 29 completed");
+    }
+
+    // Migration 30: e-Factura settings and uploads (v1.0.6)
+    if current_version < 30 {
+        info!("Applying migration 30: Add e-Factura settings and submissions");
+        conn.execute_batch(crate::efactura::SCHEMA)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (30, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 30 completed");
+    }
+
+    // Migration 31: Optional Code128 barcode on printed documents (v1.0.6)
+    if current_version < 31 {
+        info!("Applying migration 31: Add print_barcode to agent_settings");
+        let _ = conn.execute("ALTER TABLE agent_settings ADD COLUMN print_barcode INTEGER DEFAULT 0;", []).ok();
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (31, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 31 completed");
+    }
+
+    // Migration 32: Cash collection limits (v1.0.6)
+    if current_version < 32 {
+        info!("Applying migration 32: Add cash limit rules");
+        conn.execute_batch(crate::cash_limits::SCHEMA)?;
+        crate::cash_limits::seed(conn)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (32, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 32 completed");
+    }
+
+    // Migration 33: Advance payments (v1.0.6)
+    if current_version < 33 {
+        info!("Applying migration 33: Add advance payments");
+        let _ = conn.execute("ALTER TABLE collections ADD COLUMN transaction_type TEXT NOT NULL DEFAULT 'invoice';", []).ok();
+        conn.execute_batch(crate::advances::SCHEMA)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (33, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 33 completed");
+    }
+
+    // Migration 34: Receipt cancellations sent to WME (v1.0.6)
+    if current_version < 34 {
+        info!("Applying migration 34: Track receipt cancellations sent to WME");
+        let _ = conn.execute("ALTER TABLE collections ADD COLUMN cancel_sent_at TEXT;", []).ok();
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (34, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 34 completed");
+    }
+
+    // Migration 35: Document number carnets and registry (v1.0.6)
+    if current_version < 35 {
+        info!("Applying migration 35: Add number carnets and number registry");
+        conn.execute_batch(crate::numbering::SCHEMA)?;
+        crate::numbering::seed_from_settings(conn)?;
+        crate::numbering::backfill(conn)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (35, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 35 completed");
+    }
+
+    // Migration 36: Daily cash register per cash desk (v1.0.6)
+    if current_version < 36 {
+        info!("Applying migration 36: Add cash register movements and closed days");
+        conn.execute_batch(crate::cash_register::SCHEMA)?;
+        let _ = conn.execute("ALTER TABLE collections ADD COLUMN nume_casa TEXT;", []).ok();
+        let casa = crate::cash_register::current_casa(conn);
+        conn.execute("UPDATE collections SET nume_casa = ?1 WHERE nume_casa IS NULL", [&casa])?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (36, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 36 completed");
+    }
+
+    // Migration 37: Payment method of collections (v1.0.6)
+    if current_version < 37 {
+        info!("Applying migration 37: Add payment method fields to collections");
+        for statement in [
+            "ALTER TABLE collections ADD COLUMN payment_method TEXT NOT NULL DEFAULT 'cash';",
+            "ALTER TABLE collections ADD COLUMN payment_reference TEXT;",
+            "ALTER TABLE collections ADD COLUMN payment_due_date TEXT;",
+            "ALTER TABLE collections ADD COLUMN bank_account TEXT;",
+        ] {
+            let _ = conn.execute(statement, []).ok();
+        }
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (37, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 37 completed");
+    }
+
+    // Migration 38: Balance confirmation letters (v1.0.6)
+    if current_version < 38 {
+        info!("Applying migration 38: Add balance confirmations");
+        conn.execute_batch(crate::balance_confirmations::SCHEMA)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (38, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 38 completed");
+    }
+
+    // Migration 39: e-Factura secrets in the OS credential store (v1.0.6)
+    if current_version < 39 {
+        info!("Applying migration 39: Move e-Factura secrets to the credential store");
+        crate::efactura::move_secrets_to_keychain(conn)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (39, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 39 completed");
+    }
+
+    // Migration 40: Start day and opening balance of the cash registers (v1.0.6)
+    if current_version < 40 {
+        info!("Applying migration 40: Add cash register starts");
+        conn.execute_batch(crate::cash_register::SCHEMA)?;
+        crate::cash_register::seed_starts(conn)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (40, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 40 completed");
+    }
+
+    // Migration 41: Compensation receipts of the advances set against invoices (v1.0.6)
+    if current_version < 41 {
+        info!("Applying migration 41: Add compensation receipt numbers to advance applications");
+        let _ = conn.execute("ALTER TABLE advance_applications ADD COLUMN receipt_series TEXT;", []).ok();
+        let _ = conn.execute("ALTER TABLE advance_applications ADD COLUMN receipt_number TEXT;", []).ok();
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (41, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 41 completed");
+    }
+
+    // Migration 42: Invoice numbers unique per series, not across all series (v1.0.6)
+    if current_version < 42 {
+        info!("Applying migration 42: Make invoice numbers unique per series");
+        // SQLite cannot change a constraint, so the table is rebuilt. Its
+        // indexes and audit triggers go with the old table and are created again.
+        conn.execute("PRAGMA foreign_keys = OFF", [])?;
+        let rebuilt = conn.execute_batch(r#"
+            BEGIN;
+            CREATE TABLE invoices_new (
+                id TEXT PRIMARY KEY,
+                invoice_number INTEGER,
+                invoice_series TEXT,
+                partner_id TEXT NOT NULL,
+                location_id TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                total_amount REAL NOT NULL DEFAULT 0,
+                notes TEXT,
+                created_at TEXT NOT NULL,
+                sent_at TEXT,
+                error_message TEXT,
+                cancelled_at TEXT,
+                cancel_reason TEXT,
+                cancel_sent_at TEXT,
+                printed_at TEXT,
+                print_count INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (partner_id) REFERENCES partners(id),
+                FOREIGN KEY (location_id) REFERENCES locations(id),
+                UNIQUE (invoice_series, invoice_number)
+            );
+            INSERT INTO invoices_new (id, invoice_number, invoice_series, partner_id, location_id, status, total_amount,
+                notes, created_at, sent_at, error_message, cancelled_at, cancel_reason, cancel_sent_at, printed_at, print_count)
+            SELECT id, invoice_number, invoice_series, partner_id, location_id, status, total_amount,
+                notes, created_at, sent_at, error_message, cancelled_at, cancel_reason, cancel_sent_at, printed_at, print_count
+            FROM invoices;
+            DROP TABLE invoices;
+            ALTER TABLE invoices_new RENAME TO invoices;
+            CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
+            CREATE INDEX IF NOT EXISTS idx_invoices_partner ON invoices(partner_id);
+            COMMIT;
+        "#);
+        if let Err(e) = rebuilt {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(e);
+        }
+        conn.execute_batch(crate::document_events::SCHEMA)?;
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (42, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 42 completed");
+    }
+
+    // Migration 43: Issuing company recorded on each invoice (v1.0.6)
+    if current_version < 43 {
+        info!("Applying migration 43: Record the issuing company of invoices");
+        let _ = conn.execute("ALTER TABLE invoices ADD COLUMN company_profile_id INTEGER;", []).ok();
+        let _ = conn.execute("ALTER TABLE invoices ADD COLUMN issuer TEXT;", []).ok();
+        conn.execute("INSERT INTO db_migrations (version, applied_at) VALUES (43, ?1)", [&Utc::now().to_rfc3339()])?;
+        info!("Migration 43 completed");
+    }
+
     info!("All migrations completed successfully");
     Ok(())
 }
@@ -713,3 +979,15 @@ pub fn init_database(app: &AppHandle) -> Result<Database, Box<dyn std::error::Er
     let db = Database::new(app_data_dir)?;
     Ok(db)
 }
+
+/// The app's schema with every migration applied, in memory, with an empty
+/// archive attached: for tests that run the app's own queries.
+#[cfg(test)]
+pub fn open_in_memory() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    crate::cash_register::register_functions(&conn).unwrap();
+    conn.execute_batch(SCHEMA).unwrap();
+    run_migrations(&conn).unwrap();
+    crate::archive::attach(&conn, std::path::Path::new(":memory:")).unwrap();
+    conn
+}
@@ -0,0 +1,217 @@
+use log::warn;
+use rusqlite::{params, Connection};
+
+use crate::models::DocumentEvent;
+
+// Append-only audit trail for invoices and collections.
+//
+// Status changes are recorded by triggers, so every `UPDATE ... SET status`
+// in commands.rs is covered without touching it (including cancellation).
+// Creation and printing are recorded explicitly by the commands through
+// `record_*`. Rows are never updated or deleted: when a client disputes an
+// invoice we must be able to show when it was printed and sent.
+
+pub const INVOICE: &str = "invoice";
+pub const COLLECTION: &str = "collection";
+
+pub const CREATED: &str = "created";
+pub const PRINTED: &str = "printed";
+pub const EFACTURA_UPLOADED: &str = "efactura_uploaded";
+pub const EFACTURA_ACCEPTED: &str = "efactura_accepted";
+pub const EFACTURA_REJECTED: &str = "efactura_rejected";
+pub const CASH_LIMIT_WARNING: &str = "cash_limit_warning";
+pub const WME_REVERSED: &str = "wme_reversed";
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS document_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        document_type TEXT NOT NULL,
+        document_id TEXT NOT NULL,
+        document_group_id TEXT,
+        document_number TEXT,
+        event_type TEXT NOT NULL,
+        previous_status TEXT,
+        new_status TEXT,
+        agent TEXT,
+        message TEXT,
+        created_at TEXT NOT NULL
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_document_events_document ON document_events(document_type, document_id);
+    CREATE INDEX IF NOT EXISTS idx_document_events_group ON document_events(document_type, document_group_id);
+
+    CREATE TRIGGER IF NOT EXISTS trg_document_events_no_update
+    BEFORE UPDATE ON document_events
+    BEGIN
+        SELECT RAISE(ABORT, 'document_events is append-only');
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS trg_document_events_no_delete
+    BEFORE DELETE ON document_events
+    BEGIN
+        SELECT RAISE(ABORT, 'document_events is append-only');
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS trg_invoices_status_event
+    AFTER UPDATE OF status, error_message ON invoices
+    WHEN OLD.status IS NOT NEW.status OR OLD.error_message IS NOT NEW.error_message
+    BEGIN
+        INSERT INTO document_events (
+            document_type, document_id, document_group_id, document_number, event_type,
+            previous_status, new_status, agent, message, created_at
+        ) VALUES (
+            'invoice', NEW.id, NEW.id,
+            TRIM(COALESCE(NEW.invoice_series, '') || ' ' || COALESCE(NEW.invoice_number, '')),
+            'status_changed', OLD.status, NEW.status,
+            (SELECT agent_name FROM agent_settings WHERE id = 1),
+            NEW.error_message,
+            strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+        );
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS trg_invoices_edit_event
+    AFTER UPDATE OF partner_id, location_id, total_amount, notes ON invoices
+    WHEN OLD.partner_id IS NOT NEW.partner_id
+      OR OLD.location_id IS NOT NEW.location_id
+      OR OLD.total_amount IS NOT NEW.total_amount
+      OR OLD.notes IS NOT NEW.notes
+    BEGIN
+        INSERT INTO document_events (
+            document_type, document_id, document_group_id, document_number, event_type,
+            previous_status, new_status, agent, message, created_at
+        ) VALUES (
+            'invoice', NEW.id, NEW.id,
+            TRIM(COALESCE(NEW.invoice_series, '') || ' ' || COALESCE(NEW.invoice_number, '')),
+            'edited', OLD.status, NEW.status,
+            (SELECT agent_name FROM agent_settings WHERE id = 1),
+            printf('total %.2f -> %.2f', OLD.total_amount, NEW.total_amount),
+            strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+        );
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS trg_collections_status_event
+    AFTER UPDATE OF status, error_message ON collections
+    WHEN OLD.status IS NOT NEW.status OR OLD.error_message IS NOT NEW.error_message
+    BEGIN
+        INSERT INTO document_events (
+            document_type, document_id, document_group_id, document_number, event_type,
+            previous_status, new_status, agent, message, created_at
+        ) VALUES (
+            'collection', NEW.id, COALESCE(NEW.receipt_group_id, NEW.id),
+            TRIM(COALESCE(NEW.receipt_series, '') || ' ' || COALESCE(NEW.receipt_number, '')),
+            'status_changed', OLD.status, NEW.status,
+            (SELECT agent_name FROM agent_settings WHERE id = 1),
+            NEW.error_message,
+            strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+        );
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS trg_collections_edit_event
+    AFTER UPDATE OF valoare, numar_factura, serie_factura, id_partener ON collections
+    WHEN OLD.valoare IS NOT NEW.valoare
+      OR OLD.numar_factura IS NOT NEW.numar_factura
+      OR OLD.serie_factura IS NOT NEW.serie_factura
+      OR OLD.id_partener IS NOT NEW.id_partener
+    BEGIN
+        INSERT INTO document_events (
+            document_type, document_id, document_group_id, document_number, event_type,
+            previous_status, new_status, agent, message, created_at
+        ) VALUES (
+            'collection', NEW.id, COALESCE(NEW.receipt_group_id, NEW.id),
+            TRIM(COALESCE(NEW.receipt_series, '') || ' ' || COALESCE(NEW.receipt_number, '')),
+            'edited', OLD.status, NEW.status,
+            (SELECT agent_name FROM agent_settings WHERE id = 1),
+            printf('valoare %.2f -> %.2f', OLD.valoare, NEW.valoare),
+            strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+        );
+    END;
+"#;
+
+/// Previous/new status columns for an explicit event, as SQL over the
+/// document's current `status`.
+fn status_columns(event_type: &str) -> (&'static str, &'static str) {
+    match event_type {
+        CREATED => ("NULL", "status"),
+        _ => ("status", "status"),
+    }
+}
+
+/// Records an event for an invoice, taking number and status from the row.
+/// Failures are logged, never returned: the audit trail must not block
+/// issuing or printing documents.
+pub fn record_invoice(conn: &Connection, invoice_id: &str, event_type: &str, message: Option<&str>) {
+    let (previous_status, new_status) = status_columns(event_type);
+    let sql = format!(
+        "INSERT INTO document_events (
+            document_type, document_id, document_group_id, document_number, event_type,
+            previous_status, new_status, agent, message, created_at
+         )
+         SELECT 'invoice', id, id,
+                TRIM(COALESCE(invoice_series, '') || ' ' || COALESCE(invoice_number, '')),
+                ?2, {}, {},
+                (SELECT agent_name FROM agent_settings WHERE id = 1),
+                ?3, strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+         FROM invoices WHERE id = ?1",
+        previous_status, new_status
+    );
+
+    if let Err(e) = conn.execute(&sql, params![invoice_id, event_type, message]) {
+        warn!("[AUDIT] Failed to record '{}' for invoice {}: {}", event_type, invoice_id, e);
+    }
+}
+
+/// Records an event for every row of a receipt (`group_id` may also be the id
+/// of a single collection row).
+pub fn record_collection_group(conn: &Connection, group_id: &str, event_type: &str, message: Option<&str>) {
+    let (previous_status, new_status) = status_columns(event_type);
+    let sql = format!(
+        "INSERT INTO document_events (
+            document_type, document_id, document_group_id, document_number, event_type,
+            previous_status, new_status, agent, message, created_at
+         )
+         SELECT 'collection', id, COALESCE(receipt_group_id, id),
+                TRIM(COALESCE(receipt_series, '') || ' ' || COALESCE(receipt_number, '')),
+                ?2, {}, {},
+                (SELECT agent_name FROM agent_settings WHERE id = 1),
+                ?3, strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+         FROM collections WHERE COALESCE(receipt_group_id, id) = ?1 OR id = ?1",
+        previous_status, new_status
+    );
+
+    if let Err(e) = conn.execute(&sql, params![group_id, event_type, message]) {
+        warn!("[AUDIT] Failed to record '{}' for collection {}: {}", event_type, group_id, e);
+    }
+}
+
+/// Full history of a document, in the order the events were recorded. For collections `document_id`
+/// may be either a row id or a receipt group id.
+pub fn history(conn: &Connection, document_type: &str, document_id: &str) -> rusqlite::Result<Vec<DocumentEvent>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, document_type, document_id, document_group_id, document_number, event_type,
+                previous_status, new_status, agent, message, created_at
+         FROM document_events
+         WHERE document_type = ?1 AND (document_id = ?2 OR document_group_id = ?2)
+         ORDER BY id",
+    )?;
+
+    let events = stmt
+        .query_map(params![document_type, document_id], |row| {
+            Ok(DocumentEvent {
+                id: row.get(0)?,
+                document_type: row.get(1)?,
+                document_id: row.get(2)?,
+                document_group_id: row.get(3)?,
+                document_number: row.get(4)?,
+                event_type: row.get(5)?,
+                previous_status: row.get(6)?,
+                new_status: row.get(7)?,
+                agent: row.get(8)?,
+                message: row.get(9)?,
+                created_at: row.get(10)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(events)
+}
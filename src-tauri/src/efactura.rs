@@ -0,0 +1,964 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::models::{EfacturaSettings, EfacturaSubmission};
+
+// ANAF e-Factura (SPV) client.
+//
+// An invoice is uploaded as CIUS-RO XML (see ubl.rs) and ANAF answers with an
+// upload index. The index is polled until the message is processed; the
+// answer is then downloaded as a ZIP: the invoice with ANAF's signature when
+// it was accepted, the list of errors when it was not. Every upload is a row
+// in efactura_submissions, so the office can show a customer that an invoice
+// was accepted in SPV.
+//
+// The API address comes from the settings: ANAF's test or production
+// environment. Development builds (feature `efactura-stand-in`) also have a
+// local stand-in (efactura_stand_in.rs) that answers like ANAF without
+// sending anything. How the bearer token is obtained is behind
+// `TokenProvider`.
+//
+// The OAuth client secret and the tokens are kept in the operating system's
+// credential store (Windows Credential Manager, macOS Keychain, Secret
+// Service on Linux), never in the database.
+
+pub const TEST_URL: &str = "https://api.anaf.ro/test/FCTEL/rest";
+pub const PROD_URL: &str = "https://api.anaf.ro/prod/FCTEL/rest";
+pub const TOKEN_URL: &str = "https://logincert.anaf.ro/anaf-oauth2/v1/token";
+
+pub const ENV_TEST: &str = "test";
+pub const ENV_PROD: &str = "prod";
+pub const ENV_LOCAL: &str = "local";
+
+/// Environments this build can send to.
+#[cfg(any(test, feature = "efactura-stand-in"))]
+pub const ENVIRONMENTS: &[&str] = &[ENV_TEST, ENV_PROD, ENV_LOCAL];
+#[cfg(not(any(test, feature = "efactura-stand-in")))]
+pub const ENVIRONMENTS: &[&str] = &[ENV_TEST, ENV_PROD];
+
+/// Claimed for upload, ANAF has not answered yet
+pub const SENDING: &str = "sending";
+/// Uploaded, not checked yet
+pub const UPLOADED: &str = "uploaded";
+/// ANAF is still processing it ("in prelucrare")
+pub const PROCESSING: &str = "processing";
+/// Validated and signed by ANAF ("ok")
+pub const ACCEPTED: &str = "accepted";
+/// Processed and refused by ANAF ("nok")
+pub const REJECTED: &str = "rejected";
+/// Refused at upload, or XML ANAF could not read
+pub const INVALID: &str = "invalid";
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS efactura_settings (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        environment TEXT NOT NULL DEFAULT 'test',
+        base_url TEXT,
+        client_id TEXT,
+        token_expires_at TEXT,
+        updated_at TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS efactura_submissions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        invoice_id TEXT NOT NULL,
+        invoice_number TEXT NOT NULL,
+        environment TEXT NOT NULL,
+        upload_index TEXT,
+        state TEXT NOT NULL,
+        download_id TEXT,
+        errors TEXT,
+        xml_path TEXT NOT NULL,
+        response_path TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        checked_at TEXT
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_efactura_submissions_invoice ON efactura_submissions(invoice_id);
+    CREATE INDEX IF NOT EXISTS idx_efactura_submissions_state ON efactura_submissions(state);
+"#;
+
+// ==================== SECRETS ====================
+
+#[cfg(not(test))]
+const KEYCHAIN_SERVICE: &str = "facturi.softconsulting.com/efactura";
+
+const CLIENT_SECRET: &str = "client_secret";
+const ACCESS_TOKEN: &str = "access_token";
+const REFRESH_TOKEN: &str = "refresh_token";
+
+#[cfg(not(test))]
+fn keychain_entry(name: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, name).map_err(|e| format!("Seiful de parole al sistemului nu este disponibil: {}", e))
+}
+
+#[cfg(not(test))]
+fn read_secret(name: &str) -> Result<Option<String>, String> {
+    match keychain_entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Nu s-a putut citi {} din seiful de parole: {}", name, e)),
+    }
+}
+
+/// Stores a secret; None removes it.
+#[cfg(not(test))]
+fn write_secret(name: &str, value: Option<&str>) -> Result<(), String> {
+    let entry = keychain_entry(name)?;
+    let result = match value {
+        Some(value) => entry.set_password(value),
+        None => match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            result => result,
+        },
+    };
+    result.map_err(|e| format!("Nu s-a putut salva {} în seiful de parole: {}", name, e))
+}
+
+// Tests keep the secrets in memory instead of the developer's keychain
+#[cfg(test)]
+thread_local! {
+    static SECRETS: std::cell::RefCell<std::collections::HashMap<String, String>> = Default::default();
+}
+
+#[cfg(test)]
+fn read_secret(name: &str) -> Result<Option<String>, String> {
+    Ok(SECRETS.with(|secrets| secrets.borrow().get(name).cloned()))
+}
+
+#[cfg(test)]
+fn write_secret(name: &str, value: Option<&str>) -> Result<(), String> {
+    SECRETS.with(|secrets| match value {
+        Some(value) => secrets.borrow_mut().insert(name.to_string(), value.to_string()),
+        None => secrets.borrow_mut().remove(name),
+    });
+    Ok(())
+}
+
+/// A secret for the settings; one the store cannot give is treated as not
+/// set, so the settings still open and it can be entered again.
+fn stored_secret(name: &str) -> Option<String> {
+    read_secret(name).unwrap_or_else(|e| {
+        warn!("[EFACTURA] {}", e);
+        None
+    })
+}
+
+/// Moves secrets saved in efactura_settings by earlier versions to the
+/// credential store and drops their columns.
+pub fn move_secrets_to_keychain(conn: &Connection) -> rusqlite::Result<()> {
+    for name in [CLIENT_SECRET, ACCESS_TOKEN, REFRESH_TOKEN] {
+        let has_column = conn
+            .prepare("SELECT 1 FROM pragma_table_info('efactura_settings') WHERE name = ?1")?
+            .exists([name])?;
+        if !has_column {
+            continue;
+        }
+
+        let value: Option<String> = conn
+            .query_row(&format!("SELECT {} FROM efactura_settings WHERE id = 1", name), [], |row| row.get(0))
+            .optional()?
+            .flatten();
+        if let Some(value) = value {
+            // A secret the store refuses is dropped: it is entered again in the settings
+            if let Err(e) = write_secret(name, Some(&value)) {
+                warn!("[EFACTURA] {}", e);
+            }
+        }
+        conn.execute(&format!("ALTER TABLE efactura_settings DROP COLUMN {}", name), [])?;
+    }
+    Ok(())
+}
+
+// ==================== SETTINGS ====================
+
+/// Settings with the secrets read from the credential store.
+/// `EfacturaSettings` is what the UI sees.
+pub struct StoredSettings {
+    pub environment: String,
+    pub base_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
+    pub token_expires_at: Option<String>,
+}
+
+impl StoredSettings {
+    /// The access token needs refreshing: it expires within 5 minutes.
+    pub fn token_expired(&self) -> bool {
+        self.token_expires_at
+            .as_deref()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| at.with_timezone(&Utc) - Utc::now() < Duration::minutes(5))
+            .unwrap_or(false)
+    }
+}
+
+pub fn load_settings(conn: &Connection) -> rusqlite::Result<StoredSettings> {
+    let settings = conn
+        .query_row(
+            "SELECT environment, base_url, client_id, token_expires_at FROM efactura_settings WHERE id = 1",
+            [],
+            |row| {
+                Ok(StoredSettings {
+                    environment: row.get(0)?,
+                    base_url: row.get(1)?,
+                    client_id: row.get(2)?,
+                    client_secret: stored_secret(CLIENT_SECRET),
+                    access_token: stored_secret(ACCESS_TOKEN),
+                    refresh_token: stored_secret(REFRESH_TOKEN),
+                    token_expires_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+
+    Ok(settings.unwrap_or_else(|| StoredSettings {
+        environment: ENV_TEST.to_string(),
+        base_url: None,
+        client_id: None,
+        client_secret: stored_secret(CLIENT_SECRET),
+        access_token: stored_secret(ACCESS_TOKEN),
+        refresh_token: stored_secret(REFRESH_TOKEN),
+        token_expires_at: None,
+    }))
+}
+
+/// Settings for the UI: the secrets are only reported as present.
+pub fn public_settings(settings: &StoredSettings) -> EfacturaSettings {
+    EfacturaSettings {
+        environment: settings.environment.clone(),
+        base_url: settings.base_url.clone(),
+        client_id: settings.client_id.clone(),
+        client_secret: None,
+        access_token: None,
+        refresh_token: None,
+        token_expires_at: settings.token_expires_at.clone(),
+        has_client_secret: settings.client_secret.is_some(),
+        has_access_token: settings.access_token.is_some(),
+        has_refresh_token: settings.refresh_token.is_some(),
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// Saves the settings. A secret left empty keeps the stored one.
+pub fn save_settings(conn: &Connection, settings: &EfacturaSettings) -> Result<(), String> {
+    if !ENVIRONMENTS.contains(&settings.environment.as_str()) {
+        return Err(format!("Mediu e-Factura necunoscut: {}", settings.environment));
+    }
+
+    for (name, value) in [
+        (CLIENT_SECRET, &settings.client_secret),
+        (ACCESS_TOKEN, &settings.access_token),
+        (REFRESH_TOKEN, &settings.refresh_token),
+    ] {
+        if let Some(value) = non_empty(value) {
+            write_secret(name, Some(&value))?;
+        }
+    }
+
+    let stored = load_settings(conn).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO efactura_settings (id, environment, base_url, client_id, token_expires_at, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5)",
+        params![
+            settings.environment,
+            non_empty(&settings.base_url),
+            non_empty(&settings.client_id),
+            non_empty(&settings.token_expires_at).or(stored.token_expires_at),
+            Utc::now().to_rfc3339()
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn save_tokens(conn: &Connection, tokens: &OAuthTokens) -> Result<(), String> {
+    write_secret(ACCESS_TOKEN, Some(&tokens.access_token))?;
+    if let Some(refresh_token) = &tokens.refresh_token {
+        write_secret(REFRESH_TOKEN, Some(refresh_token))?;
+    }
+
+    let expires_at = tokens
+        .expires_in
+        .map(|seconds| (Utc::now() + Duration::seconds(seconds)).to_rfc3339());
+    conn.execute(
+        "UPDATE efactura_settings SET token_expires_at = ?1, updated_at = ?2 WHERE id = 1",
+        params![expires_at, Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// ==================== TOKENS ====================
+
+/// Source of the OAuth bearer token sent with every request. None sends no
+/// Authorization header (the local stand-in does not check it).
+pub trait TokenProvider: Send + Sync {
+    fn access_token(&self) -> Result<Option<String>, String>;
+}
+
+/// A token obtained beforehand: from the settings, or a fixed one.
+pub struct StaticToken(pub Option<String>);
+
+impl TokenProvider for StaticToken {
+    fn access_token(&self) -> Result<Option<String>, String> {
+        Ok(self.0.clone())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Seconds
+    pub expires_in: Option<i64>,
+}
+
+/// New access token from ANAF's OAuth server for a stored refresh token.
+pub async fn refresh_tokens(settings: &StoredSettings) -> Result<OAuthTokens, String> {
+    let (Some(client_id), Some(client_secret), Some(refresh_token)) =
+        (&settings.client_id, &settings.client_secret, &settings.refresh_token)
+    else {
+        return Err("Tokenul e-Factura a expirat. Completează din nou autentificarea SPV în Setări.".to_string());
+    };
+
+    let response = reqwest::Client::new()
+        .post(TOKEN_URL)
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("Reînnoirea tokenului e-Factura a eșuat: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Reînnoirea tokenului e-Factura a eșuat: HTTP {}", response.status()));
+    }
+
+    info!("[EFACTURA] Access token refreshed");
+    response.json::<OAuthTokens>().await.map_err(|e| e.to_string())
+}
+
+// ==================== CLIENT ====================
+
+pub enum UploadResponse {
+    /// Accepted for processing, with its upload index
+    Indexed(String),
+    /// Refused at upload
+    Refused(Vec<String>),
+}
+
+pub enum MessageState {
+    Processing,
+    Accepted { download_id: String },
+    Rejected { download_id: Option<String>, errors: Vec<String> },
+    /// "XML cu erori nepreluat de sistem"
+    Invalid(Vec<String>),
+}
+
+pub struct Client {
+    base_url: String,
+    tokens: Arc<dyn TokenProvider>,
+    http: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(base_url: &str, tokens: Arc<dyn TokenProvider>) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(60))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            tokens,
+            http,
+        })
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder, String> {
+        Ok(match self.tokens.access_token()? {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+        let response = self
+            .authorized(request)?
+            .send()
+            .await
+            .map_err(|e| format!("Serverul e-Factura nu răspunde: {}", e))?;
+        match response.status().as_u16() {
+            200..=299 => Ok(response),
+            401 | 403 => Err("ANAF a refuzat autentificarea. Verifică tokenul SPV în Setări.".to_string()),
+            status => Err(format!("Serverul e-Factura a răspuns cu HTTP {}", status)),
+        }
+    }
+
+    /// Uploads an invoice issued by `cif` (CUI without "RO").
+    pub async fn upload(&self, xml: &str, cif: &str) -> Result<UploadResponse, String> {
+        let request = self
+            .http
+            .post(format!("{}/upload", self.base_url))
+            .query(&[("standard", "UBL"), ("cif", cif)])
+            .header(reqwest::header::CONTENT_TYPE, "text/plain")
+            .body(xml.to_string());
+        let body = self.send(request).await?.text().await.map_err(|e| e.to_string())?;
+
+        match (attribute(&body, "ExecutionStatus").as_deref(), attribute(&body, "index_incarcare")) {
+            (Some("0"), Some(index)) => Ok(UploadResponse::Indexed(index)),
+            _ => Ok(UploadResponse::Refused(error_messages(&body))),
+        }
+    }
+
+    pub async fn status(&self, upload_index: &str) -> Result<MessageState, String> {
+        let request = self
+            .http
+            .get(format!("{}/stareMesaj", self.base_url))
+            .query(&[("id_incarcare", upload_index)]);
+        let body = self.send(request).await?.text().await.map_err(|e| e.to_string())?;
+
+        let download_id = attribute(&body, "id_descarcare");
+        match attribute(&body, "stare").as_deref() {
+            Some("ok") => download_id
+                .map(|download_id| MessageState::Accepted { download_id })
+                .ok_or_else(|| "Răspunsul ANAF nu conține id-ul de descărcare.".to_string()),
+            Some("nok") => Ok(MessageState::Rejected {
+                download_id,
+                errors: error_messages(&body),
+            }),
+            Some("in prelucrare") => Ok(MessageState::Processing),
+            Some(_) => Ok(MessageState::Invalid(error_messages(&body))),
+            None => Err(error_messages(&body).join("; ")),
+        }
+    }
+
+    /// The ZIP with ANAF's answer: the signed invoice, or the errors.
+    pub async fn download(&self, download_id: &str) -> Result<Vec<u8>, String> {
+        let request = self
+            .http
+            .get(format!("{}/descarcare", self.base_url))
+            .query(&[("id", download_id)]);
+        let bytes = self.send(request).await?.bytes().await.map_err(|e| e.to_string())?;
+        if !bytes.starts_with(b"PK") {
+            return Err(error_messages(&String::from_utf8_lossy(&bytes)).join("; "));
+        }
+        Ok(bytes.to_vec())
+    }
+}
+
+/// API address for the settings; the local environment starts the stand-in.
+pub fn base_url(settings: &StoredSettings) -> Result<String, String> {
+    if let Some(url) = non_empty(&settings.base_url) {
+        return Ok(url);
+    }
+    match settings.environment.as_str() {
+        ENV_PROD => Ok(PROD_URL.to_string()),
+        #[cfg(any(test, feature = "efactura-stand-in"))]
+        ENV_LOCAL => crate::efactura_stand_in::start(),
+        #[cfg(not(any(test, feature = "efactura-stand-in")))]
+        ENV_LOCAL => Err("Serverul e-Factura local există doar în versiunea de dezvoltare. Alege mediul de test sau producție în Setări.".to_string()),
+        _ => Ok(TEST_URL.to_string()),
+    }
+}
+
+// ==================== RESPONSES ====================
+
+fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Value of the first `name="..."` attribute in an ANAF response.
+fn attribute(xml: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", name);
+    let start = xml.find(&pattern)? + pattern.len();
+    let end = xml[start..].find('"')? + start;
+    Some(unescape(&xml[start..end]))
+}
+
+/// Every errorMessage="..." in an ANAF response or error file.
+pub fn error_messages(xml: &str) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("errorMessage=\"") {
+        rest = &rest[start + "errorMessage=\"".len()..];
+        let Some(end) = rest.find('"') else { break };
+        messages.push(unescape(&rest[..end]));
+        rest = &rest[end..];
+    }
+    if messages.is_empty() && !xml.trim().is_empty() && !xml.contains('<') {
+        messages.push(xml.trim().to_string());
+    }
+    messages
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<usize> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as usize)
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<usize> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?) as usize)
+}
+
+/// Files in a ZIP (stored or deflated), read through its central directory.
+pub fn zip_entries(zip: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let invalid = || "Arhiva primită de la ANAF nu este un ZIP valid.".to_string();
+
+    let end = (0..zip.len().saturating_sub(21))
+        .rev()
+        .find(|&at| zip[at..].starts_with(&[0x50, 0x4b, 0x05, 0x06]))
+        .ok_or_else(invalid)?;
+    let count = u16_at(zip, end + 10).ok_or_else(invalid)?;
+    let mut at = u32_at(zip, end + 16).ok_or_else(invalid)?;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        if !zip.get(at..).is_some_and(|rest| rest.starts_with(&[0x50, 0x4b, 0x01, 0x02])) {
+            return Err(invalid());
+        }
+        let method = u16_at(zip, at + 10).ok_or_else(invalid)?;
+        let compressed_size = u32_at(zip, at + 20).ok_or_else(invalid)?;
+        let name_len = u16_at(zip, at + 28).ok_or_else(invalid)?;
+        let extra_len = u16_at(zip, at + 30).ok_or_else(invalid)?;
+        let comment_len = u16_at(zip, at + 32).ok_or_else(invalid)?;
+        let local = u32_at(zip, at + 42).ok_or_else(invalid)?;
+        let name = String::from_utf8_lossy(zip.get(at + 46..at + 46 + name_len).ok_or_else(invalid)?).to_string();
+        at += 46 + name_len + extra_len + comment_len;
+
+        let data_start = local + 30 + u16_at(zip, local + 26).ok_or_else(invalid)? + u16_at(zip, local + 28).ok_or_else(invalid)?;
+        let data = zip.get(data_start..data_start + compressed_size).ok_or_else(invalid)?;
+        let content = match method {
+            0 => data.to_vec(),
+            8 => {
+                let mut content = Vec::new();
+                flate2::read::DeflateDecoder::new(data)
+                    .read_to_end(&mut content)
+                    .map_err(|_| invalid())?;
+                content
+            }
+            _ => return Err(invalid()),
+        };
+        entries.push((name, content));
+    }
+    Ok(entries)
+}
+
+/// Errors listed in the answer ZIP of a refused invoice (the file that is not
+/// ANAF's signature).
+pub fn rejection_errors(zip: &[u8]) -> Result<Vec<String>, String> {
+    Ok(zip_entries(zip)?
+        .iter()
+        .filter(|(name, _)| !name.starts_with("semnatura"))
+        .flat_map(|(_, content)| error_messages(&String::from_utf8_lossy(content)))
+        .collect())
+}
+
+// ==================== SUBMISSIONS ====================
+
+const COLUMNS: &str = "id, invoice_id, invoice_number, environment, upload_index, state, download_id, errors,
+                       xml_path, response_path, created_at, updated_at, checked_at";
+
+fn read_submission(row: &rusqlite::Row) -> rusqlite::Result<EfacturaSubmission> {
+    let errors: Option<String> = row.get(7)?;
+    Ok(EfacturaSubmission {
+        id: row.get(0)?,
+        invoice_id: row.get(1)?,
+        invoice_number: row.get(2)?,
+        environment: row.get(3)?,
+        upload_index: row.get(4)?,
+        state: row.get(5)?,
+        download_id: row.get(6)?,
+        errors: errors
+            .map(|errors| errors.lines().map(str::to_string).collect())
+            .unwrap_or_default(),
+        xml_path: row.get(8)?,
+        response_path: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        checked_at: row.get(12)?,
+    })
+}
+
+pub fn load(conn: &Connection, submission_id: i64) -> rusqlite::Result<Option<EfacturaSubmission>> {
+    conn.query_row(
+        &format!("SELECT {} FROM efactura_submissions WHERE id = ?1", COLUMNS),
+        [submission_id],
+        read_submission,
+    )
+    .optional()
+}
+
+/// Uploads of an invoice, newest first.
+pub fn for_invoice(conn: &Connection, invoice_id: &str) -> rusqlite::Result<Vec<EfacturaSubmission>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM efactura_submissions WHERE invoice_id = ?1 ORDER BY id DESC",
+        COLUMNS
+    ))?;
+    let submissions = stmt
+        .query_map([invoice_id], read_submission)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(submissions)
+}
+
+/// Uploads ANAF has not finished processing.
+pub fn pending(conn: &Connection) -> rusqlite::Result<Vec<EfacturaSubmission>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM efactura_submissions WHERE state IN (?1, ?2) ORDER BY id",
+        COLUMNS
+    ))?;
+    let submissions = stmt
+        .query_map(params![UPLOADED, PROCESSING], read_submission)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(submissions)
+}
+
+/// An upload still `sending` after this long was cut short (the app closed
+/// mid-upload; the client gives up after 60 s) and no longer blocks the invoice.
+const SENDING_STALE_MINUTES: i64 = 5;
+
+/// An invoice is uploaded again only when the previous upload failed:
+/// pending or accepted ones would become a duplicate in SPV.
+pub fn blocking_submission(conn: &Connection, invoice_id: &str) -> rusqlite::Result<Option<EfacturaSubmission>> {
+    let stale = (Utc::now() - Duration::minutes(SENDING_STALE_MINUTES)).to_rfc3339();
+    Ok(for_invoice(conn, invoice_id)?.into_iter().find(|submission| {
+        [UPLOADED, PROCESSING, ACCEPTED].contains(&submission.state.as_str())
+            || (submission.state == SENDING && submission.updated_at > stale)
+    }))
+}
+
+/// Records that `invoice_id` is being uploaded, before the upload starts:
+/// taken under the same lock as `blocking_submission`, it keeps a second
+/// click or window from uploading the invoice again meanwhile.
+pub fn claim(
+    conn: &Connection,
+    invoice_id: &str,
+    invoice_number: &str,
+    environment: &str,
+    xml_path: &str,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO efactura_submissions (invoice_id, invoice_number, environment, state, xml_path, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+        params![invoice_id, invoice_number, environment, SENDING, xml_path, Utc::now().to_rfc3339()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Records ANAF's answer to the upload claimed as `submission_id`.
+pub fn record_upload(conn: &Connection, submission_id: i64, upload: &UploadResponse) -> rusqlite::Result<()> {
+    let (index, state, errors) = match upload {
+        UploadResponse::Indexed(index) => (Some(index.as_str()), UPLOADED, None),
+        UploadResponse::Refused(errors) => (None, INVALID, Some(errors.join("\n"))),
+    };
+    conn.execute(
+        "UPDATE efactura_submissions SET upload_index = ?1, state = ?2, errors = ?3, updated_at = ?4 WHERE id = ?5",
+        params![index, state, errors, Utc::now().to_rfc3339(), submission_id],
+    )?;
+    Ok(())
+}
+
+/// Drops a claimed upload that never reached ANAF, so it can be retried.
+pub fn release(conn: &Connection, submission_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM efactura_submissions WHERE id = ?1 AND state = ?2",
+        params![submission_id, SENDING],
+    )?;
+    Ok(())
+}
+
+pub fn update(
+    conn: &Connection,
+    submission_id: i64,
+    state: &str,
+    download_id: Option<&str>,
+    errors: &[String],
+    response_path: Option<&str>,
+) -> rusqlite::Result<()> {
+    let now = Utc::now().to_rfc3339();
+    let errors = if errors.is_empty() { None } else { Some(errors.join("\n")) };
+    conn.execute(
+        "UPDATE efactura_submissions
+         SET state = ?1, download_id = COALESCE(?2, download_id), errors = COALESCE(?3, errors),
+             response_path = COALESCE(?4, response_path), checked_at = ?5, updated_at = ?5
+         WHERE id = ?6",
+        params![state, download_id, errors, response_path, now, submission_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const SELLER: &str = "5379259";
+
+    fn invoice_xml(seller: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Invoice xmlns="urn:oasis:names:specification:ubl:schema:xsd:Invoice-2">
+  <cbc:CustomizationID>{}</cbc:CustomizationID>
+  <cbc:ID>KRN42</cbc:ID>
+  <cac:AccountingSupplierParty><cac:Party><cac:PartyTaxScheme><cbc:CompanyID>RO{}</cbc:CompanyID></cac:PartyTaxScheme></cac:Party></cac:AccountingSupplierParty>
+</Invoice>"#,
+            crate::ubl::CUSTOMIZATION_ID,
+            seller
+        )
+    }
+
+    fn client() -> Client {
+        let url = crate::efactura_stand_in::start().unwrap();
+        Client::new(&url, Arc::new(StaticToken(None))).unwrap()
+    }
+
+    async fn upload_index(client: &Client, xml: &str) -> String {
+        match client.upload(xml, SELLER).await.unwrap() {
+            UploadResponse::Indexed(index) => index,
+            UploadResponse::Refused(errors) => panic!("refused: {:?}", errors),
+        }
+    }
+
+    #[tokio::test]
+    async fn accepted_invoice_is_polled_then_downloaded_with_its_signature() {
+        let client = client();
+        let xml = invoice_xml(SELLER);
+        let index = upload_index(&client, &xml).await;
+
+        assert!(matches!(client.status(&index).await.unwrap(), MessageState::Processing));
+        let download_id = match client.status(&index).await.unwrap() {
+            MessageState::Accepted { download_id } => download_id,
+            _ => panic!("not accepted"),
+        };
+        assert_ne!(download_id, index);
+
+        let zip = client.download(&download_id).await.unwrap();
+        let entries = zip_entries(&zip).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, format!("{}.xml", index));
+        assert_eq!(entries[0].1, xml.as_bytes());
+        assert_eq!(entries[1].0, format!("semnatura_{}.xml", index));
+        assert!(rejection_errors(&zip).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn invoice_of_another_seller_is_rejected_with_the_errors_in_the_zip() {
+        let client = client();
+        let index = upload_index(&client, &invoice_xml("99999999")).await;
+
+        assert!(matches!(client.status(&index).await.unwrap(), MessageState::Processing));
+        let download_id = match client.status(&index).await.unwrap() {
+            MessageState::Rejected { download_id, .. } => download_id.unwrap(),
+            _ => panic!("not rejected"),
+        };
+
+        let errors = rejection_errors(&client.download(&download_id).await.unwrap()).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("CIF-ul 5379259"), "{}", errors[0]);
+    }
+
+    #[tokio::test]
+    async fn file_that_is_not_an_invoice_is_refused_at_upload() {
+        match client().upload("<CreditNote/>", SELLER).await.unwrap() {
+            UploadResponse::Refused(errors) => assert_eq!(errors, ["Fisierul transmis nu este o factura CIUS-RO valida."]),
+            UploadResponse::Indexed(index) => panic!("indexed as {}", index),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_index_and_download_id_are_errors() {
+        let client = client();
+        let error = client.status("1").await.err().unwrap();
+        assert_eq!(error, "Nu exista factura cu id_incarcare= 1");
+
+        let error = client.download("1").await.unwrap_err();
+        assert!(error.contains("Nu exista mesaj pentru id-ul cerut"), "{}", error);
+    }
+
+    #[test]
+    fn response_attributes_and_errors_are_unescaped() {
+        let xml = r#"<header ExecutionStatus="0" index_incarcare="5000123"><Errors errorMessage="E: &quot;BT-1&quot; &lt;lipsa&gt; &amp; altele"/><Errors errorMessage="al doilea"/></header>"#;
+        assert_eq!(attribute(xml, "index_incarcare").as_deref(), Some("5000123"));
+        assert_eq!(attribute(xml, "stare"), None);
+        assert_eq!(error_messages(xml), ["E: \"BT-1\" <lipsa> & altele", "al doilea"]);
+        assert_eq!(error_messages("Limita de apeluri depasita"), ["Limita de apeluri depasita"]);
+    }
+
+    /// One-entry ZIP with a deflated file, as ANAF sends them.
+    fn deflated_zip(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content).unwrap();
+        let data = encoder.finish().unwrap();
+        let mut crc = flate2::Crc::new();
+        crc.update(content);
+
+        let fields = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&[20, 0, 0, 0, 8, 0, 0, 0, 0x21, 0]);
+            out.extend_from_slice(&crc.sum().to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        let mut zip = vec![0x50, 0x4b, 0x03, 0x04];
+        fields(&mut zip);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(&data);
+
+        let directory_offset = zip.len() as u32;
+        let mut directory = vec![0x50, 0x4b, 0x01, 0x02, 20, 0];
+        fields(&mut directory);
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(&directory);
+
+        zip.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0, 1, 0, 1, 0]);
+        zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&directory_offset.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+        zip
+    }
+
+    #[test]
+    fn deflated_zip_entries_are_inflated() {
+        let content = r#"<header><Error errorMessage="E: BR-RO-100 lipsa judet"/></header>"#.repeat(20);
+        let zip = deflated_zip("4000001.xml", content.as_bytes());
+        let entries = zip_entries(&zip).unwrap();
+        assert_eq!(entries, [("4000001.xml".to_string(), content.into_bytes())]);
+        assert_eq!(rejection_errors(&zip).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn broken_zip_is_an_error() {
+        let zip = deflated_zip("a.xml", b"<a/>");
+        assert!(zip_entries(b"{\"eroare\":\"x\"}").is_err());
+        assert!(zip_entries(&zip[..zip.len() - 30]).is_err());
+        let mut bad_offset = zip.clone();
+        let at = bad_offset.len() - 6;
+        bad_offset[at..at + 4].copy_from_slice(&9999u32.to_le_bytes());
+        assert!(zip_entries(&bad_offset).is_err());
+    }
+
+    fn settings_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    fn settings(client_secret: Option<&str>) -> EfacturaSettings {
+        EfacturaSettings {
+            environment: ENV_TEST.to_string(),
+            base_url: None,
+            client_id: Some("client".to_string()),
+            client_secret: client_secret.map(str::to_string),
+            access_token: None,
+            refresh_token: Some("refresh".to_string()),
+            token_expires_at: None,
+            has_client_secret: false,
+            has_access_token: false,
+            has_refresh_token: false,
+        }
+    }
+
+    #[test]
+    fn secrets_go_to_the_credential_store_not_the_database() {
+        let conn = settings_db();
+        save_settings(&conn, &settings(Some("s3cret"))).unwrap();
+
+        let row: Vec<Option<String>> = conn
+            .query_row("SELECT * FROM efactura_settings", [], |row| {
+                (1..row.as_ref().column_count()).map(|i| row.get(i)).collect()
+            })
+            .unwrap();
+        assert!(!row.iter().flatten().any(|value| value == "s3cret" || value == "refresh"), "{:?}", row);
+
+        let stored = load_settings(&conn).unwrap();
+        assert_eq!(stored.client_secret.as_deref(), Some("s3cret"));
+        assert_eq!(stored.refresh_token.as_deref(), Some("refresh"));
+        assert!(public_settings(&stored).has_client_secret);
+        assert_eq!(public_settings(&stored).client_secret, None);
+
+        // Left empty, the secret keeps its value
+        save_settings(&conn, &settings(None)).unwrap();
+        assert_eq!(load_settings(&conn).unwrap().client_secret.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn secrets_of_earlier_versions_move_out_of_the_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE efactura_settings (id INTEGER PRIMARY KEY, environment TEXT NOT NULL DEFAULT 'test', base_url TEXT,
+                 client_id TEXT, client_secret TEXT, access_token TEXT, refresh_token TEXT, token_expires_at TEXT, updated_at TEXT);
+             INSERT INTO efactura_settings (id, client_id, client_secret, access_token) VALUES (1, 'client', 'old-secret', 'old-token');",
+        )
+        .unwrap();
+
+        move_secrets_to_keychain(&conn).unwrap();
+        let columns: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM pragma_table_info('efactura_settings') WHERE name IN ('client_secret', 'access_token', 'refresh_token')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(columns, 0);
+
+        let stored = load_settings(&conn).unwrap();
+        assert_eq!(stored.client_id.as_deref(), Some("client"));
+        assert_eq!(stored.client_secret.as_deref(), Some("old-secret"));
+        assert_eq!(stored.access_token.as_deref(), Some("old-token"));
+        assert_eq!(stored.refresh_token, None);
+    }
+
+    #[test]
+    fn invoice_being_uploaded_cannot_be_uploaded_again() {
+        let conn = settings_db();
+        let claimed = claim(&conn, "inv-1", "KRN42", ENV_TEST, "factura_KRN42.xml").unwrap();
+        assert_eq!(blocking_submission(&conn, "inv-1").unwrap().unwrap().state, SENDING);
+
+        record_upload(&conn, claimed, &UploadResponse::Indexed("5000123".to_string())).unwrap();
+        let uploaded = blocking_submission(&conn, "inv-1").unwrap().unwrap();
+        assert_eq!((uploaded.state.as_str(), uploaded.upload_index.as_deref()), (UPLOADED, Some("5000123")));
+        assert_eq!(for_invoice(&conn, "inv-1").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn upload_without_an_answer_can_be_retried() {
+        let conn = settings_db();
+        let claimed = claim(&conn, "inv-1", "KRN42", ENV_TEST, "factura_KRN42.xml").unwrap();
+        release(&conn, claimed).unwrap();
+        assert!(blocking_submission(&conn, "inv-1").unwrap().is_none());
+
+        let refused = claim(&conn, "inv-1", "KRN42", ENV_TEST, "factura_KRN42.xml").unwrap();
+        record_upload(&conn, refused, &UploadResponse::Refused(vec!["XML invalid".to_string()])).unwrap();
+        assert!(blocking_submission(&conn, "inv-1").unwrap().is_none());
+        assert_eq!(load(&conn, refused).unwrap().unwrap().errors, ["XML invalid"]);
+
+        // Left `sending` by an upload cut short
+        let cut_short = claim(&conn, "inv-1", "KRN42", ENV_TEST, "factura_KRN42.xml").unwrap();
+        conn.execute(
+            "UPDATE efactura_submissions SET updated_at = ?1 WHERE id = ?2",
+            params![(Utc::now() - Duration::minutes(SENDING_STALE_MINUTES + 1)).to_rfc3339(), cut_short],
+        )
+        .unwrap();
+        assert!(blocking_submission(&conn, "inv-1").unwrap().is_none());
+    }
+}
@@ -0,0 +1,260 @@
+use log::{info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Mutex, OnceLock};
+
+// Local stand-in for the ANAF e-Factura API, used by the "local" environment.
+// Only compiled into tests and development builds (feature
+// `efactura-stand-in`), never into the released app.
+//
+// It answers upload, stareMesaj and descarcare like ANAF does, so the whole
+// flow can be tried (and the client exercised) without SPV credentials and
+// without sending a real invoice. Nothing leaves the machine and nothing is
+// kept after the app closes. Acceptance only checks what ANAF checks first:
+// the file is a CIUS-RO invoice and it was uploaded by its seller.
+
+struct Message {
+    cif: String,
+    xml: String,
+    /// Status requests so far; the first one answers "in prelucrare"
+    polls: u32,
+    errors: Vec<String>,
+}
+
+struct State {
+    next_index: u64,
+    messages: BTreeMap<String, Message>,
+}
+
+static ADDRESS: OnceLock<Result<String, String>> = OnceLock::new();
+static STATE: Mutex<State> = Mutex::new(State {
+    next_index: 5_000_001,
+    messages: BTreeMap::new(),
+});
+
+/// Starts the stand-in on a free local port (once) and returns its base URL.
+pub fn start() -> Result<String, String> {
+    ADDRESS
+        .get_or_init(|| {
+            let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+            let url = format!("http://{}", listener.local_addr().map_err(|e| e.to_string())?);
+            info!("[EFACTURA] Local stand-in listening on {}", url);
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle(stream) {
+                            warn!("[EFACTURA] Stand-in request failed: {}", e);
+                        }
+                    });
+                }
+            });
+            Ok(url)
+        })
+        .clone()
+}
+
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 8192];
+    let header_end = loop {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+        if let Some(at) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break at + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length").then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+    while request.len() < header_end + content_length {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let body = String::from_utf8_lossy(&request[header_end..]).to_string();
+
+    let target = head.lines().next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query: HashMap<&str, &str> = query.split('&').filter_map(|pair| pair.split_once('=')).collect();
+
+    let (content_type, response) = match path.rsplit('/').next().unwrap_or_default() {
+        "upload" => ("application/xml", upload(query.get("cif").copied().unwrap_or_default(), body).into_bytes()),
+        "stareMesaj" => ("application/xml", status(query.get("id_incarcare").copied().unwrap_or_default()).into_bytes()),
+        "descarcare" => match download(query.get("id").copied().unwrap_or_default()) {
+            Some(zip) => ("application/zip", zip),
+            None => ("application/json", br#"{"eroare":"Nu exista mesaj pentru id-ul cerut"}"#.to_vec()),
+        },
+        _ => ("text/plain", b"Not found".to_vec()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        content_type,
+        response.len()
+    )?;
+    stream.write_all(&response)?;
+    stream.flush()
+}
+
+fn timestamp() -> String {
+    chrono::Local::now().format("%Y%m%d%H%M").to_string()
+}
+
+fn upload(cif: &str, xml: String) -> String {
+    if !xml.contains("<Invoice") || !xml.contains(crate::ubl::CUSTOMIZATION_ID) {
+        return format!(
+            r#"<header xmlns="mfp:anaf:dgti:spv:respUploadFisier:v1" dateResponse="{}" ExecutionStatus="1"><Errors errorMessage="Fisierul transmis nu este o factura CIUS-RO valida."/></header>"#,
+            timestamp()
+        );
+    }
+
+    // ANAF accepts an invoice only from its seller
+    let seller = xml
+        .split("<cac:AccountingSupplierParty>")
+        .nth(1)
+        .and_then(|party| party.split("</cac:AccountingSupplierParty>").next())
+        .unwrap_or_default();
+    let mut errors = Vec::new();
+    if !seller.contains(&format!("{}</cbc:CompanyID>", cif)) {
+        errors.push(format!(
+            "E: validari globale  eroare: CIF-ul {} din cerere nu corespunde furnizorului din factura.",
+            cif
+        ));
+    }
+
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    let index = state.next_index.to_string();
+    state.next_index += 1;
+    state.messages.insert(
+        index.clone(),
+        Message {
+            cif: cif.to_string(),
+            xml,
+            polls: 0,
+            errors,
+        },
+    );
+
+    format!(
+        r#"<header xmlns="mfp:anaf:dgti:spv:respUploadFisier:v1" dateResponse="{}" ExecutionStatus="0" index_incarcare="{}"/>"#,
+        timestamp(),
+        index
+    )
+}
+
+fn status(index: &str) -> String {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(message) = state.messages.get_mut(index) else {
+        return format!(
+            r#"<header xmlns="mfp:anaf:dgti:efactura:stareMesajFactura:v1"><Errors errorMessage="Nu exista factura cu id_incarcare= {}"/></header>"#,
+            index
+        );
+    };
+
+    message.polls += 1;
+    let stare = match (message.polls, message.errors.is_empty()) {
+        (1, _) => return r#"<header xmlns="mfp:anaf:dgti:efactura:stareMesajFactura:v1" stare="in prelucrare"/>"#.to_string(),
+        (_, true) => "ok",
+        (_, false) => "nok",
+    };
+    format!(
+        r#"<header xmlns="mfp:anaf:dgti:efactura:stareMesajFactura:v1" stare="{}" id_descarcare="{}"/>"#,
+        stare,
+        download_id(index)
+    )
+}
+
+// Download ids are distinct from upload indexes, as in SPV
+fn download_id(index: &str) -> String {
+    format!("3{}", index)
+}
+
+fn download(id: &str) -> Option<Vec<u8>> {
+    let state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    let (index, message) = state
+        .messages
+        .iter()
+        .find(|(index, message)| download_id(index) == id && message.polls > 1)?;
+
+    let entries = if message.errors.is_empty() {
+        vec![
+            (format!("{}.xml", index), message.xml.clone().into_bytes()),
+            (
+                format!("semnatura_{}.xml", index),
+                b"<Signature>Semnatura simulata de serverul local</Signature>".to_vec(),
+            ),
+        ]
+    } else {
+        let errors: String = message
+            .errors
+            .iter()
+            .map(|error| format!("<Error errorMessage=\"{}\"/>", error))
+            .collect();
+        vec![(
+            format!("{}.xml", index),
+            format!(
+                r#"<header xmlns="mfp:anaf:dgti:efactura:stareMesajFactura:v1" Index_incarcare="{}" Cif_emitent="{}">{}</header>"#,
+                index, message.cif, errors
+            )
+            .into_bytes(),
+        )]
+    };
+    Some(zip(&entries))
+}
+
+/// ZIP with stored (uncompressed) entries.
+fn zip(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+
+    for (name, content) in entries {
+        let mut crc = flate2::Crc::new();
+        crc.update(content);
+        let offset = out.len() as u32;
+
+        // Version 2.0, no flags, stored, 1980-01-01 00:00
+        let common = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+            out.extend_from_slice(&crc.sum().to_le_bytes());
+            out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        out.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04]);
+        common(&mut out);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(content);
+
+        directory.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0]);
+        common(&mut directory);
+        // Comment, disk, attributes
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = out.len() as u32;
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&directory_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out
+}
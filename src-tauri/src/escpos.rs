@@ -0,0 +1,779 @@
+use log::info;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::codes::Bitmap;
+use crate::pdf::{decode_png_rgb, Align};
+use crate::search_index::fold_diacritics;
+
+// ESC/POS output for the agents' 58/80mm thermal printers (Bluetooth/USB).
+//
+// Documents are rendered by `Ticket` into a plain byte buffer: the same
+// document with the same options always gives the same bytes, so a layout can
+// be checked against a saved golden file. `send` then writes the buffer to
+// the printer, either a raw device, a serial port (Bluetooth SPP shows up as
+// one) or a network printer on TCP 9100.
+
+const ESC: u8 = 0x1B;
+const GS: u8 = 0x1D;
+const LF: u8 = 0x0A;
+
+pub const DEFAULT_TCP_PORT: u16 = 9100;
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Character table used for the Romanian diacritics. Printers differ in
+/// which `ESC t` number selects a table, so the number is configured
+/// separately (`Options::code_page_table`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodePage {
+    Cp852,
+    Cp1250,
+    /// No diacritics: letters are printed without accents.
+    Ascii,
+}
+
+impl CodePage {
+    pub fn from_name(name: &str) -> CodePage {
+        match name.trim().to_lowercase().as_str() {
+            "cp1250" | "wpc1250" | "1250" => CodePage::Cp1250,
+            "ascii" | "none" => CodePage::Ascii,
+            _ => CodePage::Cp852,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CodePage::Cp852 => "cp852",
+            CodePage::Cp1250 => "cp1250",
+            CodePage::Ascii => "ascii",
+        }
+    }
+
+    /// `ESC t` number of the table on Epson-compatible printers.
+    pub fn default_table(&self) -> u8 {
+        match self {
+            CodePage::Cp852 => 18,
+            CodePage::Cp1250 => 45,
+            CodePage::Ascii => 0,
+        }
+    }
+
+    fn encode(&self, ch: char) -> u8 {
+        if ch.is_ascii() {
+            return ch as u8;
+        }
+
+        // Neither table has the comma-below letters; the cedilla forms print
+        // the same on 203 dpi paper.
+        let byte = match (self, ch) {
+            (CodePage::Cp852, 'ă') => Some(0xC7),
+            (CodePage::Cp852, 'Ă') => Some(0xC6),
+            (CodePage::Cp852, 'â') => Some(0x83),
+            (CodePage::Cp852, 'Â') => Some(0xB6),
+            (CodePage::Cp852, 'î') => Some(0x8C),
+            (CodePage::Cp852, 'Î') => Some(0xD7),
+            (CodePage::Cp852, 'ș' | 'ş') => Some(0xAD),
+            (CodePage::Cp852, 'Ș' | 'Ş') => Some(0xB8),
+            (CodePage::Cp852, 'ț' | 'ţ') => Some(0xEE),
+            (CodePage::Cp852, 'Ț' | 'Ţ') => Some(0xDD),
+            (CodePage::Cp1250, 'ă') => Some(0xE3),
+            (CodePage::Cp1250, 'Ă') => Some(0xC3),
+            (CodePage::Cp1250, 'â') => Some(0xE2),
+            (CodePage::Cp1250, 'Â') => Some(0xC2),
+            (CodePage::Cp1250, 'î') => Some(0xEE),
+            (CodePage::Cp1250, 'Î') => Some(0xCE),
+            (CodePage::Cp1250, 'ș' | 'ş') => Some(0xBA),
+            (CodePage::Cp1250, 'Ș' | 'Ş') => Some(0xAA),
+            (CodePage::Cp1250, 'ț' | 'ţ') => Some(0xFE),
+            (CodePage::Cp1250, 'Ț' | 'Ţ') => Some(0xDE),
+            _ => None,
+        };
+
+        byte.unwrap_or_else(|| {
+            let folded = fold_diacritics(&ch.to_string()).chars().next().unwrap_or('?');
+            let folded = if ch.is_uppercase() { folded.to_ascii_uppercase() } else { folded };
+            if folded.is_ascii() { folded as u8 } else { b'?' }
+        })
+    }
+}
+
+/// Printer settings that change the generated bytes.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// 58 or 80 (mm).
+    pub paper_width_mm: u32,
+    pub code_page: CodePage,
+    pub code_page_table: u8,
+    pub print_logo: bool,
+    pub cut: bool,
+    /// Blank lines fed before the cut, so the last line clears the cutter.
+    pub feed_lines: u8,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            paper_width_mm: 80,
+            code_page: CodePage::Cp852,
+            code_page_table: CodePage::Cp852.default_table(),
+            print_logo: true,
+            cut: true,
+            feed_lines: 4,
+        }
+    }
+}
+
+impl Options {
+    /// Characters per line in font A.
+    pub fn line_chars(&self) -> usize {
+        if self.paper_width_mm <= 58 {
+            32
+        } else {
+            48
+        }
+    }
+
+    /// Printable width in dots at 203 dpi.
+    pub fn dots(&self) -> usize {
+        if self.paper_width_mm <= 58 {
+            384
+        } else {
+            576
+        }
+    }
+}
+
+/// 1-bit image for `GS v 0`, rows packed MSB first, 1 = black.
+#[derive(Debug, Clone)]
+pub struct Raster {
+    width_bytes: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Raster {
+    /// Decodes a PNG, scales it to at most `max_width_dots` wide and dithers
+    /// it (Floyd-Steinberg) to black and white.
+    pub fn from_png(bytes: &[u8], max_width_dots: usize) -> Result<Raster, String> {
+        let (src_w, src_h, rgb) = decode_png_rgb(bytes, (max_width_dots * 2) as u32)?;
+
+        let width = src_w.min(max_width_dots).max(1);
+        let height = ((src_h * width + src_w / 2) / src_w).max(1);
+
+        // Nearest-neighbour resample to the target size, as luminance
+        let mut gray: Vec<f32> = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sy = (y * src_h / height).min(src_h - 1);
+            for x in 0..width {
+                let sx = (x * src_w / width).min(src_w - 1);
+                let p = &rgb[(sy * src_w + sx) * 3..][..3];
+                gray.push(0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32);
+            }
+        }
+
+        let width_bytes = width.div_ceil(8);
+        let mut data = vec![0u8; width_bytes * height];
+        for y in 0..height {
+            for x in 0..width {
+                let old = gray[y * width + x];
+                let black = old < 128.0;
+                if black {
+                    data[y * width_bytes + x / 8] |= 0x80 >> (x % 8);
+                }
+
+                let error = old - if black { 0.0 } else { 255.0 };
+                let mut spread = |dx: isize, dy: usize, weight: f32| {
+                    let nx = x as isize + dx;
+                    if nx >= 0 && (nx as usize) < width && y + dy < height {
+                        gray[(y + dy) * width + nx as usize] += error * weight;
+                    }
+                };
+                spread(1, 0, 7.0 / 16.0);
+                spread(-1, 1, 3.0 / 16.0);
+                spread(0, 1, 5.0 / 16.0);
+                spread(1, 1, 1.0 / 16.0);
+            }
+        }
+
+        Ok(Raster {
+            width_bytes,
+            height,
+            data,
+        })
+    }
+
+    /// Black and white modules (QR code, barcode), each printed as a
+    /// `scale` x `scale` dot square.
+    pub fn from_bitmap(bitmap: &Bitmap, scale: usize) -> Raster {
+        let scale = scale.max(1);
+        let (width, height) = (bitmap.width * scale, bitmap.height * scale);
+        let width_bytes = width.div_ceil(8);
+        let mut data = vec![0u8; width_bytes * height];
+        for y in 0..height {
+            for x in 0..width {
+                if bitmap.dark[(y / scale) * bitmap.width + x / scale] {
+                    data[y * width_bytes + x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+
+        Raster {
+            width_bytes,
+            height,
+            data,
+        }
+    }
+}
+
+/// Builds one printed document.
+pub struct Ticket {
+    options: Options,
+    buf: Vec<u8>,
+    double: bool,
+}
+
+impl Ticket {
+    pub fn new(options: &Options) -> Self {
+        let mut buf = vec![ESC, b'@'];
+        if options.code_page != CodePage::Ascii {
+            buf.extend([ESC, b't', options.code_page_table]);
+        }
+
+        Ticket {
+            options: options.clone(),
+            buf,
+            double: false,
+        }
+    }
+
+    /// Characters per line with the current text size.
+    pub fn width(&self) -> usize {
+        if self.double {
+            self.options.line_chars() / 2
+        } else {
+            self.options.line_chars()
+        }
+    }
+
+    pub fn align(&mut self, align: Align) {
+        let n = match align {
+            Align::Left => 0,
+            Align::Center => 1,
+            Align::Right => 2,
+        };
+        self.buf.extend([ESC, b'a', n]);
+    }
+
+    pub fn bold(&mut self, on: bool) {
+        self.buf.extend([ESC, b'E', on as u8]);
+    }
+
+    pub fn underline(&mut self, on: bool) {
+        self.buf.extend([ESC, b'-', on as u8]);
+    }
+
+    /// Double width and height (half as many characters per line).
+    pub fn double(&mut self, on: bool) {
+        self.double = on;
+        self.buf.extend([GS, b'!', if on { 0x11 } else { 0x00 }]);
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let code_page = self.options.code_page;
+        self.buf.extend(text.chars().map(|ch| code_page.encode(ch)));
+    }
+
+    /// Prints text, wrapped at word boundaries; `\n` starts a new line.
+    pub fn text(&mut self, text: &str) {
+        for line in wrap(text, self.width()) {
+            self.push_text(&line);
+            self.buf.push(LF);
+        }
+    }
+
+    /// Label on the left, value on the right of the same line. A label too
+    /// long for the line is wrapped and the value goes on its last line.
+    pub fn row(&mut self, left: &str, right: &str) {
+        let width = self.width();
+        let right_len = right.chars().count();
+        let mut lines = wrap(left, width);
+        let last = lines.pop().unwrap_or_default();
+
+        for line in lines {
+            self.push_text(&line);
+            self.buf.push(LF);
+        }
+
+        let last_len = last.chars().count();
+        if last_len + 1 + right_len <= width {
+            self.push_text(&last);
+            self.push_text(&" ".repeat(width - last_len - right_len));
+            self.push_text(right);
+        } else {
+            self.push_text(&last);
+            self.buf.push(LF);
+            self.push_text(&format!("{:>width$}", right, width = width));
+        }
+        self.buf.push(LF);
+    }
+
+    /// One table line. A width of 0 takes the characters left over; cells
+    /// longer than their column are cut.
+    pub fn columns(&mut self, cells: &[(&str, usize, Align)]) {
+        let width = self.width();
+        let fixed: usize = cells.iter().map(|(_, w, _)| *w).sum();
+        let rest = width.saturating_sub(fixed);
+
+        let mut line = String::new();
+        for (text, cell_width, align) in cells {
+            let cell_width = if *cell_width == 0 { rest } else { *cell_width };
+            let text: String = text.chars().take(cell_width).collect();
+            let pad = cell_width - text.chars().count();
+            let (before, after) = match align {
+                Align::Left => (0, pad),
+                Align::Center => (pad / 2, pad - pad / 2),
+                Align::Right => (pad, 0),
+            };
+            line.push_str(&" ".repeat(before));
+            line.push_str(&text);
+            line.push_str(&" ".repeat(after));
+        }
+
+        self.push_text(line.trim_end());
+        self.buf.push(LF);
+    }
+
+    /// Full-width line of `ch` ('-', '=', '.').
+    pub fn rule(&mut self, ch: char) {
+        let line = ch.to_string().repeat(self.width());
+        self.push_text(&line);
+        self.buf.push(LF);
+    }
+
+    pub fn feed(&mut self, lines: u8) {
+        if lines > 0 {
+            self.buf.extend([ESC, b'd', lines]);
+        }
+    }
+
+    pub fn banner(&mut self, text: &str) {
+        self.align(Align::Center);
+        self.rule('*');
+        self.double(true);
+        self.bold(true);
+        self.text(text);
+        self.bold(false);
+        self.double(false);
+        self.rule('*');
+        self.align(Align::Left);
+    }
+
+    /// Printable width in dots.
+    pub fn dots(&self) -> usize {
+        self.options.dots()
+    }
+
+    /// Prints the image centred, if logos are enabled.
+    pub fn raster(&mut self, raster: &Raster) {
+        if !self.options.print_logo {
+            return;
+        }
+        self.code(raster);
+    }
+
+    /// Prints a QR code or barcode centred; unlike logos these are always
+    /// printed.
+    pub fn code(&mut self, raster: &Raster) {
+        self.align(Align::Center);
+        self.buf.extend([
+            GS,
+            b'v',
+            b'0',
+            0,
+            (raster.width_bytes & 0xFF) as u8,
+            (raster.width_bytes >> 8) as u8,
+            (raster.height & 0xFF) as u8,
+            (raster.height >> 8) as u8,
+        ]);
+        self.buf.extend(&raster.data);
+        self.align(Align::Left);
+    }
+
+    /// Feeds the paper past the cutter and cuts, if cutting is enabled.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.feed(self.options.feed_lines);
+        if self.options.cut {
+            // Partial cut
+            self.buf.extend([GS, b'V', 1]);
+        }
+        self.buf
+    }
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            // Words longer than a line are split hard
+            while word.len() > width {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                lines.push(word.drain(..width).collect());
+            }
+            let word: String = word.into_iter().collect();
+            if word.is_empty() {
+                continue;
+            }
+
+            let needed = if current.is_empty() { 0 } else { current.chars().count() + 1 };
+            if needed + word.chars().count() > width {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        lines.push(current);
+    }
+
+    lines
+}
+
+/// Where the bytes go. Written as `tcp:host[:port]`, `serial:PORT[@baud]`
+/// or `device:PATH` (a bare path is a device too).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Tcp { host: String, port: u16 },
+    Serial { port: String, baud: Option<u32> },
+    Device(String),
+}
+
+impl Target {
+    pub fn parse(value: &str) -> Result<Target, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("Imprimanta termică nu este configurată".to_string());
+        }
+
+        if let Some(rest) = value.strip_prefix("tcp:") {
+            let rest = rest.trim_start_matches("//");
+            let (host, port) = match rest.rsplit_once(':') {
+                Some((host, port)) => (
+                    host,
+                    port.parse::<u16>()
+                        .map_err(|_| format!("Port invalid pentru imprimanta termică: {}", port))?,
+                ),
+                None => (rest, DEFAULT_TCP_PORT),
+            };
+            if host.is_empty() {
+                return Err("Adresa imprimantei termice lipsește".to_string());
+            }
+            return Ok(Target::Tcp {
+                host: host.to_string(),
+                port,
+            });
+        }
+
+        if let Some(rest) = value.strip_prefix("serial:") {
+            let (port, baud) = match rest.split_once('@') {
+                Some((port, baud)) => (
+                    port,
+                    Some(baud.parse::<u32>()
+                        .map_err(|_| format!("Viteză invalidă pentru portul serial: {}", baud))?),
+                ),
+                None => (rest, None),
+            };
+            return Ok(Target::Serial {
+                port: port.trim().to_string(),
+                baud,
+            });
+        }
+
+        let path = value.strip_prefix("device:").unwrap_or(value);
+        Ok(Target::Device(path.trim().to_string()))
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Tcp { host, port } => write!(f, "tcp:{}:{}", host, port),
+            Target::Serial { port, baud: Some(baud) } => write!(f, "serial:{}@{}", port, baud),
+            Target::Serial { port, baud: None } => write!(f, "serial:{}", port),
+            Target::Device(path) => write!(f, "device:{}", path),
+        }
+    }
+}
+
+fn write_to_path(path: &str, bytes: &[u8]) -> Result<(), String> {
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("Nu am putut deschide {}: {}", path, e))?;
+    file.write_all(bytes)
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("Eroare la scrierea către {}: {}", path, e))
+}
+
+fn configure_serial(port: &str, baud: u32) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let output = std::process::Command::new("mode")
+        .arg(format!("{}:", port))
+        .arg(format!("BAUD={}", baud))
+        .args(["PARITY=N", "DATA=8", "STOP=1"])
+        .output();
+
+    #[cfg(target_os = "macos")]
+    let output = std::process::Command::new("stty")
+        .args(["-f", port, &baud.to_string(), "raw", "-echo"])
+        .output();
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let output = std::process::Command::new("stty")
+        .args(["-F", port, &baud.to_string(), "raw", "-echo"])
+        .output();
+
+    match output {
+        Ok(result) if result.status.success() => Ok(()),
+        Ok(result) => Err(format!(
+            "Nu am putut configura portul {}: {}",
+            port,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )),
+        Err(e) => Err(format!("Nu am putut configura portul {}: {}", port, e)),
+    }
+}
+
+/// Sends a rendered document to the printer.
+pub fn send(target: &Target, bytes: &[u8]) -> Result<(), String> {
+    match target {
+        Target::Tcp { host, port } => {
+            let address = (host.as_str(), *port)
+                .to_socket_addrs()
+                .map_err(|e| format!("Adresă invalidă {}:{}: {}", host, port, e))?
+                .next()
+                .ok_or_else(|| format!("Adresă invalidă {}:{}", host, port))?;
+            let mut stream = TcpStream::connect_timeout(&address, TCP_TIMEOUT)
+                .map_err(|e| format!("Imprimanta {}:{} nu răspunde: {}", host, port, e))?;
+            stream
+                .set_write_timeout(Some(TCP_TIMEOUT))
+                .map_err(|e| e.to_string())?;
+            stream
+                .write_all(bytes)
+                .and_then(|_| stream.flush())
+                .map_err(|e| format!("Eroare la trimiterea către {}:{}: {}", host, port, e))?;
+        }
+        Target::Serial { port, baud } => {
+            if let Some(baud) = baud {
+                configure_serial(port, *baud)?;
+            }
+            // COM10 and above are only reachable through the device namespace
+            #[cfg(target_os = "windows")]
+            let path = if port.starts_with(r"\\") { port.clone() } else { format!(r"\\.\{}", port) };
+            #[cfg(not(target_os = "windows"))]
+            let path = port.clone();
+            write_to_path(&path, bytes)?;
+        }
+        Target::Device(path) => write_to_path(path, bytes)?,
+    }
+
+    info!("[ESCPOS] Sent {} bytes to {}", bytes.len(), target);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codes::Kind;
+    use crate::company;
+    use crate::{print_daily_report, print_invoice, print_receipt, test_fixtures};
+    use std::path::PathBuf;
+
+    /// Compares with tests/fixtures/escpos/`name`. After a deliberate layout
+    /// change, check a printout and rewrite the files with UPDATE_GOLDEN=1.
+    fn assert_golden(name: &str, bytes: &[u8]) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/escpos").join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, bytes).unwrap();
+            return;
+        }
+        let expected = std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(bytes == expected.as_slice(), "{} differs from the golden file", name);
+    }
+
+    fn logo(options: &Options) -> Raster {
+        Raster::from_png(company::DEFAULT_LOGO, options.dots()).unwrap()
+    }
+
+    /// Bytes written between `ESC @ ESC t n` and the feed/cut.
+    fn body(options: &Options, draw: impl FnOnce(&mut Ticket)) -> Vec<u8> {
+        let mut ticket = Ticket::new(options);
+        let start = ticket.buf.len();
+        draw(&mut ticket);
+        ticket.buf.split_off(start)
+    }
+
+    #[test]
+    fn receipt_matches_golden_file() {
+        let options = Options::default();
+        let bytes = print_receipt::generate_receipt_escpos(
+            &test_fixtures::collection(),
+            &company::builtin(),
+            Some(&logo(&options)),
+            &test_fixtures::code(Kind::Receipt),
+            &options,
+            test_fixtures::receipt_details(),
+        );
+        assert_golden("receipt_80mm_cp852.bin", &bytes);
+    }
+
+    #[test]
+    fn compact_invoice_matches_golden_file() {
+        let options = Options {
+            paper_width_mm: 58,
+            code_page: CodePage::Cp1250,
+            code_page_table: CodePage::Cp1250.default_table(),
+            print_logo: false,
+            ..Options::default()
+        };
+        let bytes = print_invoice::generate_invoice_escpos(
+            &test_fixtures::invoice(),
+            &test_fixtures::invoice_items(),
+            &company::builtin(),
+            Some(&logo(&options)),
+            &test_fixtures::code(Kind::Invoice),
+            &options,
+            test_fixtures::invoice_details(),
+        );
+        assert_golden("invoice_58mm_cp1250.bin", &bytes);
+    }
+
+    #[test]
+    fn daily_report_matches_golden_file() {
+        let options = Options {
+            code_page: CodePage::Ascii,
+            code_page_table: CodePage::Ascii.default_table(),
+            cut: false,
+            feed_lines: 2,
+            ..Options::default()
+        };
+        let mut second = test_fixtures::collection();
+        second.partner_name = Some("Alimentara Bucovina".to_string());
+        second.valoare = 88.29;
+        second.receipt_number = Some("8".to_string());
+        let collections = [test_fixtures::collection(), second];
+
+        let bytes = print_daily_report::generate_collections_report_escpos(
+            &collections,
+            "02.03.2026",
+            242.99,
+            &company::builtin(),
+            Some(&logo(&options)),
+            &options,
+        );
+        assert_golden("daily_report_80mm_ascii.bin", &bytes);
+    }
+
+    #[test]
+    fn romanian_letters_use_the_selected_code_page() {
+        let text = "ăâîșțĂÂÎȘȚşţ";
+        let cases = [
+            (CodePage::Cp852, [0xC7, 0x83, 0x8C, 0xAD, 0xEE, 0xC6, 0xB6, 0xD7, 0xB8, 0xDD, 0xAD, 0xEE]),
+            (CodePage::Cp1250, [0xE3, 0xE2, 0xEE, 0xBA, 0xFE, 0xC3, 0xC2, 0xCE, 0xAA, 0xDE, 0xBA, 0xFE]),
+            (CodePage::Ascii, *b"aaistAAISTst"),
+        ];
+        for (code_page, expected) in cases {
+            let options = Options {
+                code_page,
+                code_page_table: code_page.default_table(),
+                ..Options::default()
+            };
+            let printed = body(&options, |ticket| ticket.text(text));
+            assert_eq!(printed[..12], expected, "{}", code_page.name());
+            assert_eq!(printed[12..], [LF]);
+        }
+    }
+
+    #[test]
+    fn ticket_selects_the_configured_table() {
+        let options = Options {
+            code_page_table: 7,
+            ..Options::default()
+        };
+        assert_eq!(Ticket::new(&options).buf, [ESC, b'@', ESC, b't', 7]);
+
+        let ascii = Options {
+            code_page: CodePage::Ascii,
+            ..Options::default()
+        };
+        assert_eq!(Ticket::new(&ascii).buf, [ESC, b'@']);
+    }
+
+    #[test]
+    fn finish_feeds_then_cuts() {
+        let options = Options::default();
+        let bytes = Ticket::new(&options).finish();
+        assert_eq!(bytes[5..], [ESC, b'd', 4, GS, b'V', 1]);
+
+        let no_cut = Options {
+            cut: false,
+            feed_lines: 0,
+            ..Options::default()
+        };
+        assert_eq!(Ticket::new(&no_cut).finish(), [ESC, b'@', ESC, b't', 18]);
+    }
+
+    #[test]
+    fn logo_is_a_centred_raster_within_the_paper() {
+        let options = Options {
+            paper_width_mm: 58,
+            ..Options::default()
+        };
+        let raster = logo(&options);
+        assert!(raster.width_bytes * 8 <= options.dots());
+        assert_eq!(raster.data.len(), raster.width_bytes * raster.height);
+
+        let printed = body(&options, |ticket| ticket.raster(&raster));
+        let header = [
+            ESC,
+            b'a',
+            1,
+            GS,
+            b'v',
+            b'0',
+            0,
+            raster.width_bytes as u8,
+            (raster.width_bytes >> 8) as u8,
+            (raster.height & 0xFF) as u8,
+            (raster.height >> 8) as u8,
+        ];
+        assert_eq!(printed[..11], header);
+        assert_eq!(printed[11..printed.len() - 3], raster.data);
+        assert_eq!(printed[printed.len() - 3..], [ESC, b'a', 0]);
+
+        let without_logo = Options {
+            print_logo: false,
+            ..options
+        };
+        assert!(body(&without_logo, |ticket| ticket.raster(&raster)).is_empty());
+    }
+
+    #[test]
+    fn bitmap_modules_are_scaled_and_packed_msb_first() {
+        let bitmap = Bitmap {
+            width: 3,
+            height: 1,
+            dark: vec![true, false, true],
+        };
+        let raster = Raster::from_bitmap(&bitmap, 3);
+        assert_eq!((raster.width_bytes, raster.height), (2, 3));
+        assert_eq!(raster.data, [0b1110_0011, 0b1000_0000].repeat(3));
+    }
+}
@@ -0,0 +1,669 @@
+use chrono::Utc;
+use log::{info, warn};
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+use crate::models::{IntegrityIssue, IntegrityReport, NumberRange};
+use crate::numbering;
+
+// Database integrity check and self-repair.
+//
+// Besides SQLite's own checks, this looks for inconsistent business data the
+// app can end up with after crashes, partial syncs or manual edits. Only
+// issues whose fix cannot lose a document are marked `fixable`; everything
+// else is reported so the agent can contact support.
+
+pub const SQLITE_INTEGRITY: &str = "sqlite_integrity";
+pub const FOREIGN_KEYS: &str = "foreign_keys";
+pub const ORPHAN_ROWS: &str = "orphan_rows";
+pub const INVOICES_WITHOUT_ITEMS: &str = "invoices_without_items";
+pub const ITEMS_MISSING_PRODUCTS: &str = "items_missing_products";
+pub const DUPLICATE_INVOICE_NUMBERS: &str = "duplicate_invoice_numbers";
+pub const MIXED_PARTNER_RECEIPTS: &str = "mixed_partner_receipts";
+pub const INVOICE_COUNTER_BEHIND: &str = "invoice_counter_behind";
+pub const RECEIPT_COUNTER_BEHIND: &str = "receipt_counter_behind";
+pub const NUMBER_GAPS: &str = "number_gaps";
+pub const SEARCH_INDEX_STALE: &str = "search_index_stale";
+
+/// Registry note of the numbers recorded by a repair
+const REPAIR_NOTE: &str = "Trecut în registru la verificarea bazei de date";
+
+/// Details listed per issue; the count always reflects every affected row.
+const MAX_DETAILS: usize = 20;
+
+/// Child tables whose rows are meaningless without their parent and can be
+/// deleted safely: (table, parent).
+const ORPHAN_TABLES: [(&str, &str); 4] = [
+    ("invoice_items", "invoices"),
+    ("invoice_certificate_cache", "invoices"),
+    ("offer_items", "offers"),
+    ("locations", "partners"),
+];
+
+fn issue(code: &str, severity: &str, message: String, details: Vec<String>, fixable: bool) -> IntegrityIssue {
+    let count = details.len() as i64;
+    IntegrityIssue {
+        code: code.to_string(),
+        severity: severity.to_string(),
+        message,
+        count,
+        details: details.into_iter().take(MAX_DETAILS).collect(),
+        fixable,
+    }
+}
+
+fn query_strings(conn: &Connection, sql: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn check_sqlite_integrity(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    let rows = query_strings(conn, "PRAGMA integrity_check(100)")?;
+    if rows.len() == 1 && rows[0] == "ok" {
+        return Ok(());
+    }
+
+    issues.push(issue(
+        SQLITE_INTEGRITY,
+        "error",
+        "Fișierul bazei de date este deteriorat. Faceți o copie a datelor și contactați suportul.".to_string(),
+        rows,
+        false,
+    ));
+    Ok(())
+}
+
+fn foreign_key_violations(conn: &Connection) -> rusqlite::Result<Vec<(String, i64, String)>> {
+    let mut stmt = conn.prepare("PRAGMA main.foreign_key_check")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get::<_, Option<i64>>(1)?.unwrap_or(0), row.get(2)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn is_orphan(table: &str, parent: &str) -> bool {
+    ORPHAN_TABLES.iter().any(|(t, p)| *t == table && *p == parent)
+}
+
+fn check_foreign_keys(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    let mut orphans = Vec::new();
+    let mut others = Vec::new();
+
+    for (table, rowid, parent) in foreign_key_violations(conn)? {
+        // Items pointing to products missing from the catalog have their own check
+        if table == "invoice_items" && parent == "products" {
+            continue;
+        }
+        let detail = format!("{} rowid {} -> {}", table, rowid, parent);
+        if is_orphan(&table, &parent) {
+            orphans.push(detail);
+        } else {
+            others.push(detail);
+        }
+    }
+
+    if !orphans.is_empty() {
+        issues.push(issue(
+            ORPHAN_ROWS,
+            "warning",
+            format!("{} înregistrări rămase fără documentul părinte (pot fi șterse).", orphans.len()),
+            orphans,
+            true,
+        ));
+    }
+
+    if !others.is_empty() {
+        issues.push(issue(
+            FOREIGN_KEYS,
+            "warning",
+            format!("{} documente fac referire la parteneri sau sedii care nu mai există local. Rulați o sincronizare completă.", others.len()),
+            others,
+            false,
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_invoices_without_items(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    let rows = query_strings(
+        conn,
+        "SELECT COALESCE(i.invoice_series, '') || ' ' || COALESCE(i.invoice_number, '?') || ' (' || i.status || ')'
+         FROM invoices i
+         WHERE NOT EXISTS (SELECT 1 FROM invoice_items ii WHERE ii.invoice_id = i.id)
+         ORDER BY i.created_at",
+    )?;
+
+    if !rows.is_empty() {
+        issues.push(issue(
+            INVOICES_WITHOUT_ITEMS,
+            "error",
+            format!("{} facturi nu au nicio linie de produs.", rows.len()),
+            rows,
+            false,
+        ));
+    }
+    Ok(())
+}
+
+fn check_items_missing_products(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    let rows = query_strings(
+        conn,
+        "SELECT ii.product_id || ' (' || COUNT(*) || ' linii)'
+         FROM invoice_items ii
+         LEFT JOIN products p ON p.id = ii.product_id
+         WHERE p.id IS NULL
+         GROUP BY ii.product_id
+         ORDER BY ii.product_id",
+    )?;
+
+    if !rows.is_empty() {
+        issues.push(issue(
+            ITEMS_MISSING_PRODUCTS,
+            "warning",
+            format!("{} produse folosite pe facturi lipsesc din nomenclator. Rulați o sincronizare completă.", rows.len()),
+            rows,
+            false,
+        ));
+    }
+    Ok(())
+}
+
+fn check_duplicate_invoice_numbers(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    let rows = query_strings(
+        conn,
+        "SELECT COALESCE(invoice_series, '') || ' ' || invoice_number || ' (x' || COUNT(*) || ')'
+         FROM (
+             SELECT invoice_series, invoice_number FROM main.invoices
+             UNION ALL
+             SELECT invoice_series, invoice_number FROM archive.invoices
+         )
+         WHERE invoice_number IS NOT NULL
+         GROUP BY COALESCE(invoice_series, ''), invoice_number
+         HAVING COUNT(*) > 1",
+    )?;
+
+    if !rows.is_empty() {
+        issues.push(issue(
+            DUPLICATE_INVOICE_NUMBERS,
+            "error",
+            format!("{} numere de factură sunt folosite de mai multe ori în aceeași serie.", rows.len()),
+            rows,
+            false,
+        ));
+    }
+    Ok(())
+}
+
+fn check_mixed_partner_receipts(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    let rows = query_strings(
+        conn,
+        "SELECT COALESCE(MAX(receipt_series), '') || ' ' || COALESCE(MAX(receipt_number), receipt_group_id)
+                || ' (' || COUNT(DISTINCT id_partener) || ' parteneri)'
+         FROM collections
+         WHERE receipt_group_id IS NOT NULL AND TRIM(receipt_group_id) <> ''
+         GROUP BY receipt_group_id
+         HAVING COUNT(DISTINCT id_partener) > 1",
+    )?;
+
+    if !rows.is_empty() {
+        issues.push(issue(
+            MIXED_PARTNER_RECEIPTS,
+            "error",
+            format!("{} chitanțe conțin încasări de la parteneri diferiți.", rows.len()),
+            rows,
+            false,
+        ));
+    }
+    Ok(())
+}
+
+/// A document number with the id of the document that carries it.
+type Issued = (i64, String);
+
+/// Numbers of the documents issued in a series, live and archived, with the
+/// document each belongs to (the receipt group for receipts, the application
+/// group for advance compensations).
+fn issued_numbers(conn: &Connection, document_type: &str, series: &str) -> rusqlite::Result<Vec<Issued>> {
+    let sql = if document_type == numbering::INVOICE {
+        "SELECT DISTINCT invoice_number, id FROM (
+             SELECT invoice_series, invoice_number, id FROM main.invoices
+             UNION ALL
+             SELECT invoice_series, invoice_number, id FROM archive.invoices
+         )
+         WHERE invoice_number IS NOT NULL AND TRIM(COALESCE(invoice_series, '')) = ?1
+         ORDER BY 1"
+    } else {
+        // Timestamp numbers, given while no receipt carnet was set, are left out
+        "SELECT DISTINCT CAST(receipt_number AS INTEGER), document_id FROM (
+             SELECT receipt_series, receipt_number, COALESCE(receipt_group_id, id) AS document_id FROM main.collections
+             UNION ALL
+             SELECT receipt_series, receipt_number, COALESCE(receipt_group_id, id) FROM archive.collections
+             UNION ALL
+             SELECT receipt_series, receipt_number, group_id FROM main.advance_applications
+         )
+         WHERE receipt_number != '' AND receipt_number NOT GLOB '*[^0-9]*' AND LENGTH(receipt_number) < 14
+             AND TRIM(COALESCE(receipt_series, '')) = ?1
+         ORDER BY 1"
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt
+        .query_map([series], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+/// Numbers of a series in number_registry, used or skipped.
+fn registered_numbers(conn: &Connection, document_type: &str, series: &str) -> rusqlite::Result<HashSet<i64>> {
+    let mut stmt = conn.prepare("SELECT number FROM number_registry WHERE document_type = ?1 AND series = ?2")?;
+    let numbers = stmt
+        .query_map([document_type, series], |row| row.get(0))?
+        .collect::<rusqlite::Result<HashSet<_>>>()?;
+    Ok(numbers)
+}
+
+/// Documents numbered from an active carnet, at or past its next number,
+/// that the registry does not know: the carnet would give their numbers
+/// again.
+fn unregistered_ahead(conn: &Connection, document_type: &str) -> rusqlite::Result<Vec<(NumberRange, Vec<Issued>)>> {
+    let mut found = Vec::new();
+    for range in numbering::ranges(conn, Some(document_type))? {
+        if range.status != numbering::ACTIVE {
+            continue;
+        }
+        let registered = registered_numbers(conn, document_type, &range.series)?;
+        let ahead: Vec<Issued> = issued_numbers(conn, document_type, &range.series)?
+            .into_iter()
+            .filter(|(number, _)| {
+                *number >= range.next_number
+                    && numbering::within(range.range_end, *number)
+                    && !registered.contains(number)
+            })
+            .collect();
+        if !ahead.is_empty() {
+            found.push((range, ahead));
+        }
+    }
+    Ok(found)
+}
+
+/// Numbers a carnet has already passed that have neither a document nor an
+/// entry in the registry.
+fn unexplained_gaps(conn: &Connection) -> rusqlite::Result<Vec<(NumberRange, Vec<i64>)>> {
+    let mut found = Vec::new();
+    for range in numbering::ranges(conn, None)? {
+        let last = range.range_end.map_or(range.next_number - 1, |end| end.min(range.next_number - 1));
+        if last < range.range_start {
+            continue;
+        }
+        let registered = registered_numbers(conn, &range.document_type, &range.series)?;
+        let issued: HashSet<i64> = issued_numbers(conn, &range.document_type, &range.series)?
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect();
+        let gaps: Vec<i64> = (range.range_start..=last)
+            .filter(|number| !registered.contains(number) && !issued.contains(number))
+            .collect();
+        if !gaps.is_empty() {
+            found.push((range, gaps));
+        }
+    }
+    Ok(found)
+}
+
+/// Consecutive numbers as (first, last) runs.
+fn runs(numbers: &[i64]) -> Vec<(i64, i64)> {
+    let mut runs: Vec<(i64, i64)> = Vec::new();
+    for &number in numbers {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == number => *last = number,
+            _ => runs.push((number, number)),
+        }
+    }
+    runs
+}
+
+fn series_label(series: &str, first: i64, last: i64) -> String {
+    let numbers = if first == last { first.to_string() } else { format!("{}-{}", first, last) };
+    if series.is_empty() {
+        numbers
+    } else {
+        format!("{} {}", series, numbers)
+    }
+}
+
+fn check_counters(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    for (document_type, code, label) in [
+        (numbering::INVOICE, INVOICE_COUNTER_BEHIND, "facturi"),
+        (numbering::RECEIPT, RECEIPT_COUNTER_BEHIND, "chitanțe"),
+    ] {
+        let found = unregistered_ahead(conn, document_type)?;
+        if found.is_empty() {
+            continue;
+        }
+        let details: Vec<String> = found
+            .iter()
+            .flat_map(|(range, ahead)| {
+                ahead.iter().map(move |(number, _)| {
+                    format!("{} (următorul număr din carnet: {})", series_label(&range.series, *number, *number), range.next_number)
+                })
+            })
+            .collect();
+        issues.push(issue(
+            code,
+            "error",
+            format!(
+                "{} numere de {} deja emise nu sunt în registrul de numere și ar fi date din nou.",
+                details.len(),
+                label
+            ),
+            details,
+            true,
+        ));
+    }
+
+    let gaps = unexplained_gaps(conn)?;
+    if !gaps.is_empty() {
+        let details: Vec<String> = gaps
+            .iter()
+            .flat_map(|(range, numbers)| {
+                runs(numbers).into_iter().map(move |(first, last)| {
+                    format!("{} {}", range.document_type, series_label(&range.series, first, last))
+                })
+            })
+            .collect();
+        let count: usize = gaps.iter().map(|(_, numbers)| numbers.len()).sum();
+        issues.push(issue(
+            NUMBER_GAPS,
+            "warning",
+            format!(
+                "{} numere sărite din carnete nu au document și nici mențiune în registru. Verificați dacă lipsesc documente \
+                 și treceți în registru motivul fiecărui număr sărit.",
+                count
+            ),
+            details,
+            // Each gap needs its own reason: see `explain_gap`
+            false,
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_search_index(conn: &Connection, issues: &mut Vec<IntegrityIssue>) -> rusqlite::Result<()> {
+    let (partners, indexed_partners, products, indexed_products): (i64, i64, i64, i64) = conn.query_row(
+        "SELECT
+            (SELECT COUNT(*) FROM partners),
+            (SELECT COUNT(*) FROM partner_search),
+            (SELECT COUNT(*) FROM products),
+            (SELECT COUNT(*) FROM product_search)",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+
+    if partners != indexed_partners || products != indexed_products {
+        issues.push(issue(
+            SEARCH_INDEX_STALE,
+            "warning",
+            "Indexul de căutare nu corespunde cu lista de parteneri/produse.".to_string(),
+            vec![format!(
+                "parteneri {} / indexați {}, produse {} / indexate {}",
+                partners, indexed_partners, products, indexed_products
+            )],
+            true,
+        ));
+    }
+    Ok(())
+}
+
+pub fn run_checks(conn: &Connection) -> rusqlite::Result<IntegrityReport> {
+    let mut issues = Vec::new();
+
+    check_sqlite_integrity(conn, &mut issues)?;
+    check_foreign_keys(conn, &mut issues)?;
+    check_invoices_without_items(conn, &mut issues)?;
+    check_items_missing_products(conn, &mut issues)?;
+    check_duplicate_invoice_numbers(conn, &mut issues)?;
+    check_mixed_partner_receipts(conn, &mut issues)?;
+    check_counters(conn, &mut issues)?;
+    check_search_index(conn, &mut issues)?;
+
+    for found in &issues {
+        warn!("[INTEGRITY] {} ({}): {}", found.code, found.count, found.message);
+    }
+    info!("[INTEGRITY] Check finished with {} issues", issues.len());
+
+    Ok(IntegrityReport {
+        checked_at: Utc::now().to_rfc3339(),
+        ok: issues.is_empty(),
+        issues,
+        applied_fixes: Vec::new(),
+    })
+}
+
+/// Applies the safe fix for each requested issue code and returns a line per
+/// applied fix. Unknown or non-fixable codes are ignored. Must run inside a
+/// transaction opened by the caller.
+pub fn repair(conn: &Connection, codes: &[String]) -> Result<Vec<String>, String> {
+    let mut applied = Vec::new();
+
+    for code in codes {
+        match code.as_str() {
+            ORPHAN_ROWS => {
+                let mut deleted = 0;
+                for (table, rowid, parent) in foreign_key_violations(conn).map_err(|e| e.to_string())? {
+                    if is_orphan(&table, &parent) {
+                        deleted += conn
+                            .execute(&format!("DELETE FROM main.{} WHERE rowid = ?1", table), [rowid])
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+                applied.push(format!("Șterse {} înregistrări orfane", deleted));
+            }
+            INVOICE_COUNTER_BEHIND | RECEIPT_COUNTER_BEHIND => {
+                let document_type = if code == INVOICE_COUNTER_BEHIND { numbering::INVOICE } else { numbering::RECEIPT };
+                for (range, ahead) in unregistered_ahead(conn, document_type).map_err(|e| e.to_string())? {
+                    for (number, document_id) in &ahead {
+                        numbering::register(conn, &range, *number, document_id, REPAIR_NOTE).map_err(|e| e.to_string())?;
+                    }
+                    let last = ahead.iter().map(|(number, _)| *number).max().unwrap_or(range.next_number - 1);
+                    let next_number = range.range_end.map_or(last + 1, |end| (last + 1).min(end + 1));
+                    numbering::save_range(conn, &NumberRange { next_number, ..range.clone() })?;
+                    applied.push(format!(
+                        "Carnet {}: {} numere trecute în registru, următorul număr {} -> {}",
+                        series_label(&range.series, range.range_start, range.range_end.unwrap_or(last)),
+                        ahead.len(),
+                        range.next_number,
+                        next_number
+                    ));
+                }
+            }
+            SEARCH_INDEX_STALE => {
+                crate::search_index::rebuild_all(conn).map_err(|e| e.to_string())?;
+                applied.push("Index de căutare reconstruit".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    for line in &applied {
+        info!("[INTEGRITY][REPAIR] {}", line);
+    }
+
+    Ok(applied)
+}
+
+/// Records why `number` of `series` was passed over without a document.
+/// Only a number reported in NUMBER_GAPS can be explained, one at a time.
+pub fn explain_gap(conn: &Connection, document_type: &str, series: &str, number: i64, reason: &str) -> Result<String, String> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(format!("Scrieți motivul pentru care numărul {} a fost sărit.", number));
+    }
+    let range = unexplained_gaps(conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|(range, numbers)| {
+            range.document_type == document_type && range.series == series.trim() && numbers.contains(&number)
+        })
+        .map(|(range, _)| range)
+        .ok_or_else(|| {
+            format!(
+                "Numărul {} nu este un număr sărit fără explicație.",
+                series_label(series.trim(), number, number)
+            )
+        })?;
+
+    numbering::skip(conn, &range, number, number + 1, reason).map_err(|e| e.to_string())?;
+    let line = format!("{} trecut în registru ca sărit: {}", series_label(&range.series, number, number), reason);
+    info!("[INTEGRITY] {} {}", document_type, line);
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::params;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "ATTACH DATABASE ':memory:' AS archive;
+             CREATE TABLE main.invoices (id TEXT PRIMARY KEY, invoice_series TEXT, invoice_number INTEGER);
+             CREATE TABLE archive.invoices (id TEXT PRIMARY KEY, invoice_series TEXT, invoice_number INTEGER);
+             CREATE TABLE main.collections (id TEXT PRIMARY KEY, receipt_series TEXT, receipt_number TEXT, receipt_group_id TEXT);
+             CREATE TABLE archive.collections (id TEXT PRIMARY KEY, receipt_series TEXT, receipt_number TEXT, receipt_group_id TEXT);
+             CREATE TABLE main.advance_applications (id TEXT PRIMARY KEY, group_id TEXT, receipt_series TEXT, receipt_number TEXT);
+             CREATE TABLE agent_settings (id INTEGER PRIMARY KEY, carnet_series TEXT,
+                 invoice_number_start INTEGER, invoice_number_end INTEGER, invoice_number_current INTEGER,
+                 receipt_series TEXT, receipt_number_start INTEGER, receipt_number_end INTEGER, receipt_number_current INTEGER);
+             INSERT INTO agent_settings (id, carnet_series, invoice_number_start, invoice_number_end, invoice_number_current)
+                 VALUES (1, 'KRN', 1, 100, 5);",
+        )
+        .unwrap();
+        conn.execute_batch(numbering::SCHEMA).unwrap();
+        numbering::seed_from_settings(&conn).unwrap();
+        conn
+    }
+
+    /// Invoice `number` saved through the carnet, as the app does.
+    fn issue_invoice(conn: &Connection, table: &str, number: i64, registered: bool) {
+        let id = format!("inv-{}", number);
+        conn.execute(
+            &format!("INSERT INTO {}.invoices (id, invoice_series, invoice_number) VALUES (?1, 'KRN', ?2)", table),
+            params![id, number],
+        )
+        .unwrap();
+        if registered {
+            let range = numbering::ranges(conn, Some(numbering::INVOICE)).unwrap().remove(0);
+            numbering::register(conn, &range, number, &id, "").unwrap();
+        }
+    }
+
+    fn counter_issues(conn: &Connection) -> Vec<IntegrityIssue> {
+        let mut issues = Vec::new();
+        check_counters(conn, &mut issues).unwrap();
+        issues
+    }
+
+    fn registry_status(conn: &Connection, number: i64) -> Option<(String, Option<String>)> {
+        conn.query_row(
+            "SELECT status, document_id FROM number_registry WHERE document_type = 'invoice' AND series = 'KRN' AND number = ?1",
+            [number],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .ok()
+    }
+
+    #[test]
+    fn numbered_carnet_without_gaps_is_clean() {
+        let conn = test_db();
+        for number in 1..5 {
+            issue_invoice(&conn, "main", number, true);
+        }
+        assert!(counter_issues(&conn).is_empty());
+    }
+
+    #[test]
+    fn unregistered_invoice_past_the_next_number_is_repaired_through_the_carnet() {
+        let conn = test_db();
+        for number in 1..5 {
+            issue_invoice(&conn, "main", number, true);
+        }
+        issue_invoice(&conn, "archive", 7, false);
+
+        let issues = counter_issues(&conn);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, INVOICE_COUNTER_BEHIND);
+        assert_eq!(issues[0].details, ["KRN 7 (următorul număr din carnet: 5)"]);
+        assert!(issues[0].fixable);
+
+        // The repair runs inside the transaction of the command
+        conn.execute_batch("BEGIN IMMEDIATE TRANSACTION").unwrap();
+        let applied = repair(&conn, &[INVOICE_COUNTER_BEHIND.to_string()]).unwrap();
+        conn.execute_batch("COMMIT").unwrap();
+        assert_eq!(applied, ["Carnet KRN 1-100: 1 numere trecute în registru, următorul număr 5 -> 8"]);
+
+        assert_eq!(registry_status(&conn, 7), Some((numbering::USED.to_string(), Some("inv-7".to_string()))));
+        assert_eq!(registry_status(&conn, 5).unwrap().0, numbering::SKIPPED);
+        assert_eq!(registry_status(&conn, 6).unwrap().0, numbering::SKIPPED);
+        let current: i64 = conn
+            .query_row("SELECT invoice_number_current FROM agent_settings WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(current, 8);
+        assert!(counter_issues(&conn).is_empty());
+        assert_eq!(numbering::allocate(&conn, numbering::INVOICE, "inv-8").unwrap().number, 8);
+    }
+
+    #[test]
+    fn settings_counter_is_not_what_the_check_reads() {
+        let conn = test_db();
+        for number in 1..5 {
+            issue_invoice(&conn, "main", number, true);
+        }
+        // Only the mirror in agent_settings is behind: allocate does not read it
+        conn.execute("UPDATE agent_settings SET invoice_number_current = 2 WHERE id = 1", []).unwrap();
+        assert!(counter_issues(&conn).is_empty());
+    }
+
+    #[test]
+    fn passed_numbers_without_document_or_skipped_entry_are_gaps() {
+        let conn = test_db();
+        for number in [1, 4] {
+            issue_invoice(&conn, "main", number, true);
+        }
+        // 2 was skipped on purpose, 3 has no trace
+        let range = numbering::ranges(&conn, Some(numbering::INVOICE)).unwrap().remove(0);
+        numbering::skip(&conn, &range, 2, 3, "Sărit").unwrap();
+
+        let issues = counter_issues(&conn);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, NUMBER_GAPS);
+        assert_eq!(issues[0].count, 1);
+        assert_eq!(issues[0].details, ["invoice KRN 3"]);
+
+        assert!(!issues[0].fixable);
+
+        // Not repaired in bulk: every number needs its reason
+        assert!(repair(&conn, &[NUMBER_GAPS.to_string()]).unwrap().is_empty());
+        assert_eq!(registry_status(&conn, 3), None);
+        assert!(explain_gap(&conn, numbering::INVOICE, "KRN", 3, "  ").is_err());
+        assert!(explain_gap(&conn, numbering::INVOICE, "KRN", 4, "Anulată").is_err());
+        assert!(explain_gap(&conn, numbering::INVOICE, "KRN", 2, "Anulată").is_err());
+
+        let line = explain_gap(&conn, numbering::INVOICE, "KRN", 3, "Formular deteriorat la imprimare").unwrap();
+        assert_eq!(line, "KRN 3 trecut în registru ca sărit: Formular deteriorat la imprimare");
+        assert_eq!(registry_status(&conn, 3), Some((numbering::SKIPPED.to_string(), None)));
+        assert!(counter_issues(&conn).is_empty());
+    }
+
+    #[test]
+    fn consecutive_gaps_are_listed_as_runs() {
+        assert_eq!(runs(&[3, 4, 5, 9, 11, 12]), [(3, 5), (9, 9), (11, 12)]);
+    }
+}
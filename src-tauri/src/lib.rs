@@ -8,7 +8,39 @@ mod models;
 mod print_invoice;
 mod print_receipt;
 mod print_daily_report;
+mod print_cash_register;
+mod print_partner_statement;
+mod print_quality_certificate;
+mod print_balance_confirmation;
+mod print_aging_report;
+mod print_sales_summary;
+mod search_index;
+mod templates;
+mod archive;
+mod integrity;
+mod company;
+mod document_events;
+mod pdf;
+mod escpos;
+mod printing;
+mod print_jobs;
+mod ubl;
+mod efactura;
+#[cfg(any(test, feature = "efactura-stand-in"))]
+mod efactura_stand_in;
+mod codes;
+mod cash_limits;
+mod advances;
+mod payment_allocation;
+mod payment_methods;
+mod partner_statement;
+mod balance_confirmations;
+mod aging;
+mod numbering;
+mod cash_register;
 mod api_client;
+#[cfg(test)]
+mod test_fixtures;
 
 #[cfg(not(debug_assertions))]
 mod updater;
@@ -34,6 +66,10 @@ pub fn run() {
                 .expect("Failed to initialize database");
             app.manage(db);
 
+            // Failed print jobs are retried in the background
+            let handle = app.handle().clone();
+            std::thread::spawn(move || print_jobs::retry_loop(handle));
+
             #[cfg(not(debug_assertions))]
             {
                 let handle = app.handle().clone();
@@ -70,7 +106,8 @@ pub fn run() {
             commands::preview_invoice_json,
             commands::send_all_pending_invoices,
             commands::cancel_invoice_sending,
-            commands::delete_invoice,
+            commands::cancel_invoice,
+            commands::restore_invoice,
             commands::print_invoice_to_html,
             commands::print_invoice_certificate,
             commands::preview_invoice_certificate,
@@ -82,6 +119,32 @@ pub fn run() {
             // Collection & Balance commands
             commands::sync_client_balances,
             commands::get_client_balances,
+            commands::get_cash_limit_rules,
+            commands::save_cash_limit_rule,
+            commands::get_number_ranges,
+            commands::save_number_range,
+            commands::get_number_registry,
+            commands::get_numbering_warnings,
+            commands::get_cash_register,
+            commands::set_cash_register_start,
+            commands::add_cash_movement,
+            commands::delete_cash_movement,
+            commands::close_cash_day,
+            commands::print_cash_register,
+            commands::generate_partner_statement,
+            commands::print_partner_statement,
+            commands::generate_balance_confirmations,
+            commands::get_balance_confirmations,
+            commands::print_balance_confirmations,
+            commands::record_balance_confirmation_return,
+            commands::get_aging_report,
+            commands::print_aging_report,
+            commands::export_aging_report,
+            commands::check_cash_limits,
+            commands::allocate_payment,
+            commands::get_partner_advances,
+            commands::apply_partner_advances,
+            commands::send_advance_applications,
             commands::record_collection,
             commands::record_collection_group,
             commands::record_collection_from_invoice,
@@ -89,14 +152,41 @@ pub fn run() {
             commands::get_collections,
             commands::sync_collections,
             commands::send_collection,
-            commands::delete_collection,
+            commands::cancel_receipt,
+            commands::restore_collection,
             commands::get_sales_report,
             commands::get_sales_print_report,
             commands::get_sales_products_report,
             commands::get_collections_report,
             commands::get_daily_collections_report,
             commands::print_daily_report,
-            commands::print_report_html,
+            commands::get_thermal_printer_settings,
+            commands::save_thermal_printer_settings,
+            commands::test_thermal_printer,
+            commands::print_collection_escpos,
+            commands::print_invoice_escpos,
+            commands::print_collections_report_escpos,
+            commands::get_printer_profiles,
+            commands::save_printer_profile,
+            commands::delete_printer_profile,
+            commands::get_print_routes,
+            commands::save_print_routes,
+            commands::get_print_jobs,
+            commands::reprint_job,
+            commands::render_document_preview,
+            commands::get_print_barcode,
+            commands::set_print_barcode,
+            commands::lookup_document_by_code,
+            commands::get_document_templates,
+            commands::create_document_template_override,
+            commands::get_company_profiles,
+            commands::get_active_company_profile,
+            commands::save_company_profile,
+            commands::select_company_profile,
+            commands::delete_company_profile,
+            commands::set_company_image,
+            commands::get_company_image,
+            commands::print_sales_summary,
             // API test commands
             commands::test_api_partners,
             commands::test_api_articles,
@@ -107,6 +197,24 @@ pub fn run() {
             commands::save_report_html,
             commands::open_external_link,
             commands::hide_client_balance,
+            // Maintenance commands
+            commands::get_archive_status,
+            commands::set_retention_months,
+            commands::archive_old_documents,
+            commands::vacuum_database,
+            commands::run_integrity_check,
+            commands::repair_integrity_issues,
+            commands::explain_number_gap,
+            // Audit commands
+            commands::get_document_history,
+            commands::validate_invoice_ubl,
+            commands::export_invoice_ubl,
+            commands::get_efactura_settings,
+            commands::save_efactura_settings,
+            commands::submit_invoice_efactura,
+            commands::check_efactura_status,
+            commands::poll_efactura_submissions,
+            commands::get_efactura_submissions,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
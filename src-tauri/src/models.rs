@@ -105,6 +105,7 @@ pub enum InvoiceStatus {
     Sending,
     Sent,
     Failed,
+    Cancelled,
 }
 
 impl ToString for InvoiceStatus {
@@ -114,6 +115,7 @@ impl ToString for InvoiceStatus {
             InvoiceStatus::Sending => "sending".to_string(),
             InvoiceStatus::Sent => "sent".to_string(),
             InvoiceStatus::Failed => "failed".to_string(),
+            InvoiceStatus::Cancelled => "cancelled".to_string(),
         }
     }
 }
@@ -125,6 +127,7 @@ impl From<String> for InvoiceStatus {
             "sending" => InvoiceStatus::Sending,
             "sent" => InvoiceStatus::Sent,
             "failed" => InvoiceStatus::Failed,
+            "cancelled" => InvoiceStatus::Cancelled,
             _ => InvoiceStatus::Pending,
         }
     }
@@ -254,6 +257,7 @@ pub enum CollectionStatus {
     Sending,
     Synced,
     Failed,
+    Cancelled,
 }
 
 impl ToString for CollectionStatus {
@@ -263,6 +267,7 @@ impl ToString for CollectionStatus {
             CollectionStatus::Sending => "sending".to_string(),
             CollectionStatus::Synced => "synced".to_string(),
             CollectionStatus::Failed => "failed".to_string(),
+            CollectionStatus::Cancelled => "cancelled".to_string(),
         }
     }
 }
@@ -274,6 +279,7 @@ impl From<String> for CollectionStatus {
             "sending" => CollectionStatus::Sending,
             "synced" => CollectionStatus::Synced,
             "failed" => CollectionStatus::Failed,
+            "cancelled" => CollectionStatus::Cancelled,
             _ => CollectionStatus::Pending,
         }
     }
@@ -295,6 +301,8 @@ pub struct Collection {
     pub created_at: String,
     pub receipt_series: Option<String>,
     pub receipt_number: Option<String>,
+    #[serde(default)]
+    pub payment: PaymentDetails,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -305,13 +313,181 @@ pub struct CollectionAllocationRequest {
     pub valoare: f64,
 }
 
+/// How a collection was paid, see payment_methods.rs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentDetails {
+    /// "cash", "card", "bank_transfer", "promissory_note" or "cheque"
+    pub method: String,
+    /// POS slip number, transfer reference, promissory note or cheque number
+    #[serde(default)]
+    pub reference: Option<String>,
+    /// Due date of a promissory note or cheque (YYYY-MM-DD)
+    #[serde(default)]
+    pub due_date: Option<String>,
+    /// Bank account (IBAN) the money goes to
+    #[serde(default)]
+    pub bank_account: Option<String>,
+}
+
+impl Default for PaymentDetails {
+    fn default() -> Self {
+        PaymentDetails {
+            method: "cash".to_string(),
+            reference: None,
+            due_date: None,
+            bank_account: None,
+        }
+    }
+}
+
+/// Cap on cash collections, see cash_limits.rs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashLimitRule {
+    #[serde(default)]
+    pub id: i64,
+    /// "legal_entity" or "individual"
+    pub partner_type: String,
+    /// "per_day" or "per_document"
+    pub scope: String,
+    pub limit_amount: f64,
+    /// "block", "warn" or "off"
+    pub action: String,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+/// A cash cap a receipt would exceed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashLimitCheck {
+    pub scope: String,
+    pub partner_type: String,
+    /// Invoice, for per_document caps
+    pub document: Option<String>,
+    pub limit_amount: f64,
+    pub already_collected: f64,
+    pub amount: f64,
+    /// The receipt cannot be recorded
+    pub blocking: bool,
+    pub message: String,
+}
+
+/// A carnet of document numbers, see numbering.rs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumberRange {
+    #[serde(default)]
+    pub id: i64,
+    /// "invoice" or "receipt"
+    pub document_type: String,
+    pub series: String,
+    pub range_start: i64,
+    /// None: no last number
+    pub range_end: Option<i64>,
+    /// Next number handed out from this carnet
+    pub next_number: i64,
+    /// Carnets are used in ascending order
+    #[serde(default)]
+    pub position: i64,
+    /// "active", "exhausted" or "disabled"
+    pub status: String,
+    #[serde(default)]
+    pub remaining: Option<i64>,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+/// A document number handed out, or skipped, by numbering.rs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumberRegistryEntry {
+    pub id: i64,
+    pub document_type: String,
+    pub series: String,
+    pub number: i64,
+    pub range_id: Option<i64>,
+    /// Invoice id or receipt group id; none for a skipped number
+    pub document_id: Option<String>,
+    /// "used" or "skipped"
+    pub status: String,
+    pub note: Option<String>,
+    pub allocated_at: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateCollectionGroupRequest {
     pub id_partener: String,
     pub partner_name: Option<String>,
     pub allocations: Vec<CollectionAllocationRequest>,
+    /// Paid on account, over what the invoices take
+    #[serde(default)]
+    pub advance: Option<f64>,
+    /// Cash when missing
+    #[serde(default)]
+    pub payment: Option<PaymentDetails>,
+}
+
+/// A payment on account with money left to set against invoices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartnerAdvance {
+    /// Collection row of the advance
+    pub id: String,
+    pub receipt_group_id: String,
+    pub receipt_series: Option<String>,
+    pub receipt_number: Option<String>,
+    pub data_incasare: String,
+    pub valoare: f64,
+    pub status: String,
+    pub applied: f64,
+    pub available: f64,
+}
+
+/// Part of an advance set against an invoice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdvanceApplication {
+    pub id: String,
+    pub group_id: String,
+    pub advance_id: String,
+    pub id_partener: String,
+    pub serie_factura: Option<String>,
+    pub numar_factura: Option<String>,
+    pub cod_document: Option<String>,
+    pub valoare: f64,
+    /// pending, synced or cancelled
+    pub status: String,
+    pub synced_at: Option<String>,
+    pub error_message: Option<String>,
+    pub created_at: String,
+    /// Receipt WME gets the compensation on, one per advance
+    pub receipt_series: Option<String>,
+    pub receipt_number: Option<String>,
 }
 
+/// A lump-sum payment split over a partner's open invoices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentAllocation {
+    /// oldest_due, overdue_first or exact_match
+    pub strategy: String,
+    pub amount: f64,
+    pub allocated: f64,
+    /// More than the partner owes; recorded as an advance
+    pub unallocated: f64,
+    pub open_balance: f64,
+    pub lines: Vec<PaymentAllocationLine>,
+    /// Set when the allocation was recorded
+    pub receipt_group_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentAllocationLine {
+    pub serie_factura: Option<String>,
+    pub numar_factura: Option<String>,
+    pub cod_document: Option<String>,
+    pub data: Option<String>,
+    pub termen: Option<String>,
+    /// Left to pay before this payment
+    pub rest: f64,
+    pub valoare: f64,
+    pub days_overdue: i64,
+    pub fully_paid: bool,
+}
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -377,3 +553,423 @@ pub struct DailyCollectionsReport {
     pub previous_day_collections_total: f64,
     pub total_day_collections: f64,
 }
+
+/// Cash taken out of the drawer, see cash_register.rs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashMovement {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub nume_casa: String,
+    /// YYYY-MM-DD
+    pub day: String,
+    /// "handover", "deposit" or "expense"
+    pub kind: String,
+    pub amount: f64,
+    pub description: String,
+    /// Supporting document (handover slip, bank slip, fuel receipt)
+    pub document: Option<String>,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+/// One line of a cash register page: a receipt in, or a movement out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashRegisterLine {
+    /// HH:MM
+    pub time: String,
+    /// "receipt", "handover", "deposit" or "expense"
+    pub kind: String,
+    pub document: String,
+    pub description: String,
+    pub receipt: f64,
+    pub payment: f64,
+}
+
+/// A day of the cash register of one cash desk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashRegisterDay {
+    pub nume_casa: String,
+    pub day: String,
+    /// Page of the register, given when the day is closed
+    pub page_number: Option<i64>,
+    pub opening_balance: f64,
+    pub lines: Vec<CashRegisterLine>,
+    pub movements: Vec<CashMovement>,
+    pub total_receipts: f64,
+    pub total_payments: f64,
+    pub closing_balance: f64,
+    pub closed_at: Option<String>,
+    /// PDF of the printed page
+    pub document_path: Option<String>,
+    /// First day of the register, when one was set
+    pub register_start: Option<String>,
+    /// The start and opening balance can change until a page is closed
+    pub start_editable: bool,
+}
+
+/// A line of a partner statement, in the partner's favour on `credit`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartnerStatementLine {
+    /// YYYY-MM-DD
+    pub date: String,
+    /// "invoice", "credit_note", "collection" or "prior_payment"
+    pub kind: String,
+    pub document: String,
+    pub description: String,
+    pub due_date: Option<String>,
+    pub debit: f64,
+    pub credit: f64,
+    /// Balance after this line
+    pub balance: f64,
+    /// Invoice still (partly) unpaid past its due date at the end of the period
+    pub days_overdue: Option<i64>,
+}
+
+/// Fișă de cont: what a partner was invoiced and paid over a period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartnerStatement {
+    pub partner_id: String,
+    pub partner_name: String,
+    pub partner_cif: Option<String>,
+    pub from: String,
+    pub to: String,
+    pub opening_balance: f64,
+    pub lines: Vec<PartnerStatementLine>,
+    pub total_debit: f64,
+    pub total_credit: f64,
+    pub closing_balance: f64,
+    /// Part of the closing balance that is past due
+    pub overdue_amount: f64,
+    pub generated_at: String,
+    /// PDF of the statement
+    pub document_path: Option<String>,
+}
+
+/// Open document listed on a balance confirmation, as WME sent it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceConfirmationDocument {
+    pub tip_document: String,
+    /// Series and number
+    pub document: String,
+    pub data: Option<String>,
+    pub termen: Option<String>,
+    pub valoare: f64,
+    pub rest: f64,
+}
+
+/// Confirmare de sold sent to a partner for a reference date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceConfirmation {
+    pub id: String,
+    pub partner_id: String,
+    pub partner_name: String,
+    pub partner_cif: Option<String>,
+    pub partner_reg_com: Option<String>,
+    pub partner_address: Option<String>,
+    /// YYYY-MM-DD
+    pub reference_date: String,
+    pub documents: Vec<BalanceConfirmationDocument>,
+    /// Sum of the rest of the documents
+    pub balance: f64,
+    pub generated_at: String,
+    /// PDF of the letter
+    pub document_path: Option<String>,
+    /// "sent", "confirmed" or "disputed"
+    pub return_status: String,
+    /// When the signed copy came back
+    pub returned_at: Option<String>,
+    /// Balance stated by the partner on a disputed letter
+    pub partner_balance: Option<f64>,
+    pub notes: Option<String>,
+}
+
+/// Open balance split by days overdue.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgingBuckets {
+    /// Not due yet, or without a due date
+    pub current: f64,
+    pub days_1_30: f64,
+    pub days_31_60: f64,
+    pub days_61_90: f64,
+    pub days_over_90: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgingRow {
+    /// Partner id, partner and location, or agent
+    pub key: String,
+    pub name: String,
+    /// None when grouped by agent
+    pub partner_id: Option<String>,
+    pub document_count: usize,
+    pub max_days_overdue: i64,
+    #[serde(flatten)]
+    pub buckets: AgingBuckets,
+}
+
+/// Vechimea creanțelor on a date, most overdue first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgingReport {
+    /// YYYY-MM-DD
+    pub as_of: String,
+    /// "partner", "location" or "agent"
+    pub group_by: String,
+    pub rows: Vec<AgingRow>,
+    pub totals: AgingBuckets,
+    pub generated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveResult {
+    pub cutoff_date: String,
+    pub invoices: usize,
+    pub invoice_items: usize,
+    pub certificates: usize,
+    pub collections: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveStatus {
+    pub retention_months: i64,
+    pub archived_invoices: i64,
+    pub archived_collections: i64,
+    pub database_size_bytes: i64,
+    pub archive_size_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    pub code: String,
+    pub severity: String,
+    pub message: String,
+    pub count: i64,
+    pub details: Vec<String>,
+    pub fixable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub checked_at: String,
+    pub ok: bool,
+    pub issues: Vec<IntegrityIssue>,
+    pub applied_fixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentEvent {
+    pub id: i64,
+    pub document_type: String,
+    pub document_id: String,
+    pub document_group_id: Option<String>,
+    pub document_number: Option<String>,
+    pub event_type: String,
+    pub previous_status: Option<String>,
+    pub new_status: Option<String>,
+    pub agent: Option<String>,
+    pub message: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalPrinterSettings {
+    /// `tcp:host[:port]`, `serial:PORT[@baud]` or a device path; None = not configured.
+    pub target: Option<String>,
+    pub paper_width_mm: i64,
+    pub code_page: String,
+    /// `ESC t` table number; None = the usual number for `code_page`.
+    pub code_page_table: Option<i64>,
+    pub print_logo: bool,
+    pub cut: bool,
+    pub feed_lines: i64,
+}
+
+/// A printer and how documents are printed on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrinterProfile {
+    pub id: Option<i64>,
+    pub name: String,
+    /// Printer name (system/SumatraPDF/lp) or ESC/POS target; None = default printer.
+    pub printer: Option<String>,
+    /// "system", "sumatra", "escpos" or "lp"
+    pub backend: String,
+    /// Paper name passed to the printer ("A4", "A5"); "58mm"/"80mm" for ESC/POS.
+    pub paper_size: Option<String>,
+    pub copies: i64,
+    pub duplex: bool,
+}
+
+/// Printer profile a document type is sent to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintRoute {
+    /// "invoice", "quality_certificate", "receipt", "daily_report" or "report"
+    pub doc_type: String,
+    /// None = default printer
+    pub profile_id: Option<i64>,
+    /// None = the profile's copies; 0 = do not print
+    pub copies: Option<i64>,
+}
+
+/// A document sent to a printer and the outcome of its last attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintJob {
+    pub id: i64,
+    pub doc_type: String,
+    /// Invoice or collection id; the date for reports
+    pub document_id: String,
+    /// Rendered file that is sent to the printer (PDF, HTML or ESC/POS bytes)
+    pub file_path: String,
+    pub backend: String,
+    pub printer: Option<String>,
+    pub profile_name: Option<String>,
+    pub paper_size: Option<String>,
+    pub duplex: bool,
+    pub copies: i64,
+    /// "queued", "printing", "printed" or "failed"
+    pub status: String,
+    pub attempts: i64,
+    pub error_message: Option<String>,
+    /// Job this one reprints
+    pub reprint_of: Option<i64>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub printed_at: Option<String>,
+}
+
+/// An EN 16931 / CIUS-RO rule an invoice breaks, by its schematron id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UblRuleViolation {
+    /// "BR-16", "BR-RO-100", ...
+    pub rule: String,
+    pub message: String,
+}
+
+/// ANAF e-Factura connection. Secrets are write-only: they are never sent
+/// back to the UI, and left empty they keep the stored value. They are kept
+/// in the operating system's credential store, see efactura.rs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EfacturaSettings {
+    /// "test", "prod" or "local" (stand-in on this computer, development
+    /// builds only)
+    pub environment: String,
+    /// Overrides the API address of the environment
+    pub base_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
+    pub token_expires_at: Option<String>,
+    #[serde(default)]
+    pub has_client_secret: bool,
+    #[serde(default)]
+    pub has_access_token: bool,
+    #[serde(default)]
+    pub has_refresh_token: bool,
+}
+
+/// Document found from a scanned QR code or barcode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScannedDocument {
+    /// "invoice", "receipt" or "quality_certificate"
+    pub document_type: String,
+    /// Invoice id; for receipts the id of their first collection
+    pub document_id: String,
+    pub series: String,
+    pub number: String,
+    pub partner_name: String,
+    pub date: String,
+    /// With VAT
+    pub total: f64,
+    pub status: String,
+}
+
+/// One upload of an invoice to e-Factura and what ANAF answered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EfacturaSubmission {
+    pub id: i64,
+    pub invoice_id: String,
+    pub invoice_number: String,
+    pub environment: String,
+    /// index_incarcare; None when the upload was refused
+    pub upload_index: Option<String>,
+    /// "sending", "uploaded", "processing", "accepted", "rejected" or "invalid"
+    pub state: String,
+    /// id_descarcare of the answer ZIP
+    pub download_id: Option<String>,
+    pub errors: Vec<String>,
+    pub xml_path: String,
+    /// Answer ZIP: signed invoice or the list of errors
+    pub response_path: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub checked_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanyBankAccount {
+    pub id: Option<i64>,
+    pub bank_name: String,
+    pub iban: String,
+    pub currency: String,
+    pub is_primary: bool,
+}
+
+/// Issuing company ("furnizor") printed on invoices, receipts and reports.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanyProfile {
+    pub id: Option<i64>,
+    pub name: String,
+    pub cif: String,
+    pub reg_com: String,
+    pub address: String,
+    pub localitate: String,
+    pub judet: String,
+    pub cod_postal: String,
+    pub capital: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    /// Applies "TVA la încasare"; the mention is printed on invoices.
+    pub vat_on_collection: bool,
+    pub bank_accounts: Vec<CompanyBankAccount>,
+    #[serde(default)]
+    pub has_logo: bool,
+    #[serde(default)]
+    pub has_stamp: bool,
+    #[serde(default)]
+    pub is_active: bool,
+}
+
+/// Where the user's override of a document template lives and whether it
+/// exists (see templates/README.md).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentTemplateInfo {
+    pub doc_type: String,
+    pub path: Option<String>,
+    pub overridden: bool,
+    pub footer_path: Option<String>,
+    pub footer_overridden: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityCertificateProductLine {
+    pub denumire: String,
+    pub lot: String,
+    pub data_productie: String,
+    pub data_expirare: String,
+}
+
+/// What the quality certificate of an invoice says, see
+/// print_quality_certificate.rs.
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityCertificateContext {
+    pub cert_date: String,
+    pub subtitle: String,
+    pub packed_date: String,
+    pub beneficiary: String,
+    pub invoice_display: String,
+    pub invoice_date: String,
+    pub car_number: String,
+    pub bon_analiza: String,
+    pub product_lines: Vec<QualityCertificateProductLine>,
+}
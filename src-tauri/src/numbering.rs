@@ -0,0 +1,913 @@
+use chrono::Utc;
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::models::{NumberRange, NumberRegistryEntry};
+
+// Series and numbers of the invoices and receipts issued on the device.
+//
+// Numbers come from carnets: a series with a range of numbers. A document type
+// may have several carnets; they are used in ascending `position`, and when
+// one runs out the next active one takes over. A number is taken inside the
+// transaction that inserts the document, so a document that fails to save
+// gives its number back and two documents never get the same one.
+//
+// Every number handed out goes to `number_registry` with the document it was
+// given to. A number passed over, because a carnet was moved forward by hand,
+// is recorded there as skipped, so every gap in a series can be explained.
+//
+// `agent_settings` keeps showing the carnet in use (series, start, end,
+// current): it is updated after each allocation, and a carnet changed there
+// becomes the one in use.
+
+pub const INVOICE: &str = "invoice";
+pub const RECEIPT: &str = "receipt";
+
+pub const ACTIVE: &str = "active";
+pub const EXHAUSTED: &str = "exhausted";
+pub const DISABLED: &str = "disabled";
+
+pub const USED: &str = "used";
+pub const SKIPPED: &str = "skipped";
+
+/// A carnet with this many numbers left, or fewer, is nearly exhausted
+pub const WARN_REMAINING: i64 = 10;
+
+/// Last invoice number when none was set, as before carnets existed
+const DEFAULT_INVOICE_END: i64 = 99999;
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS number_ranges (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        document_type TEXT NOT NULL,
+        series TEXT NOT NULL DEFAULT '',
+        range_start INTEGER NOT NULL,
+        range_end INTEGER,
+        next_number INTEGER NOT NULL,
+        position INTEGER NOT NULL DEFAULT 0,
+        status TEXT NOT NULL DEFAULT 'active',
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_number_ranges_type ON number_ranges(document_type, status, position);
+
+    CREATE TABLE IF NOT EXISTS number_registry (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        document_type TEXT NOT NULL,
+        series TEXT NOT NULL DEFAULT '',
+        number INTEGER NOT NULL,
+        range_id INTEGER,
+        document_id TEXT,
+        status TEXT NOT NULL DEFAULT 'used',
+        note TEXT,
+        allocated_at TEXT NOT NULL,
+        UNIQUE(document_type, series, number)
+    );
+    CREATE INDEX IF NOT EXISTS idx_number_registry_document ON number_registry(document_id);
+"#;
+
+/// Number given to a document.
+#[derive(Debug)]
+pub struct Allocated {
+    pub series: String,
+    pub number: i64,
+}
+
+fn label(document_type: &str) -> &'static str {
+    if document_type == INVOICE {
+        "facturi"
+    } else {
+        "chitanțe"
+    }
+}
+
+fn carnet_label(range: &NumberRange) -> String {
+    let numbers = match range.range_end {
+        Some(end) => format!("{}-{}", range.range_start, end),
+        None => format!("de la {}", range.range_start),
+    };
+    if range.series.is_empty() {
+        numbers
+    } else {
+        format!("{} {}", range.series, numbers)
+    }
+}
+
+const RANGE_COLUMNS: &str =
+    "id, document_type, series, range_start, range_end, next_number, position, status, created_at";
+
+fn map_range(row: &rusqlite::Row) -> rusqlite::Result<NumberRange> {
+    let range_end: Option<i64> = row.get(4)?;
+    let next_number: i64 = row.get(5)?;
+    Ok(NumberRange {
+        id: row.get(0)?,
+        document_type: row.get(1)?,
+        series: row.get(2)?,
+        range_start: row.get(3)?,
+        range_end,
+        next_number,
+        position: row.get(6)?,
+        status: row.get(7)?,
+        remaining: range_end.map(|end| (end - next_number + 1).max(0)),
+        created_at: row.get(8)?,
+    })
+}
+
+/// Carnets of one document type (all when none is given) in the order they are used.
+pub fn ranges(conn: &Connection, document_type: Option<&str>) -> rusqlite::Result<Vec<NumberRange>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM number_ranges WHERE ?1 IS NULL OR document_type = ?1
+         ORDER BY document_type, position, id",
+        RANGE_COLUMNS
+    ))?;
+    let ranges = stmt
+        .query_map([document_type], map_range)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(ranges)
+}
+
+fn active_ranges(conn: &Connection, document_type: &str) -> rusqlite::Result<Vec<NumberRange>> {
+    Ok(ranges(conn, Some(document_type))?
+        .into_iter()
+        .filter(|range| range.status == ACTIVE)
+        .collect())
+}
+
+fn has_ranges(conn: &Connection, document_type: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM number_ranges WHERE document_type = ?1)",
+        [document_type],
+        |row| row.get(0),
+    )
+}
+
+fn front_position(conn: &Connection, document_type: &str) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MIN(position), 1) - 1 FROM number_ranges WHERE document_type = ?1",
+        [document_type],
+        |row| row.get(0),
+    )
+}
+
+fn is_registered(conn: &Connection, document_type: &str, series: &str, number: i64) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM number_registry WHERE document_type = ?1 AND series = ?2 AND number = ?3)",
+        params![document_type, series, number],
+        |row| row.get(0),
+    )
+}
+
+pub(crate) fn within(range_end: Option<i64>, number: i64) -> bool {
+    range_end.map_or(true, |end| number <= end)
+}
+
+fn status_for(range_end: Option<i64>, next_number: i64) -> &'static str {
+    if within(range_end, next_number) {
+        ACTIVE
+    } else {
+        EXHAUSTED
+    }
+}
+
+fn insert_range(
+    conn: &Connection,
+    document_type: &str,
+    series: &str,
+    range_start: i64,
+    range_end: Option<i64>,
+    next_number: i64,
+    position: i64,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO number_ranges (document_type, series, range_start, range_end, next_number, position, status, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            document_type,
+            series,
+            range_start,
+            range_end,
+            next_number,
+            position,
+            status_for(range_end, next_number),
+            Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Records the numbers in `from..to` as skipped, leaving the ones already
+/// given to a document alone.
+pub fn skip(
+    conn: &Connection,
+    range: &NumberRange,
+    from: i64,
+    to: i64,
+    note: &str,
+) -> rusqlite::Result<usize> {
+    let now = Utc::now().to_rfc3339();
+    let mut skipped = 0;
+    for number in from.max(range.range_start)..to {
+        skipped += conn.execute(
+            "INSERT OR IGNORE INTO number_registry (document_type, series, number, range_id, document_id, status, note, allocated_at)
+             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6, ?7)",
+            params![range.document_type, range.series, number, range.id, SKIPPED, note, now],
+        )?;
+    }
+    if skipped > 0 {
+        info!(
+            "[NUMBERING] {} number(s) of carnet {} ({}) recorded as skipped",
+            skipped,
+            carnet_label(range),
+            range.document_type
+        );
+    }
+    Ok(skipped)
+}
+
+/// Records a number of `range` as given to `document_id`, for a document
+/// numbered without going through `allocate`.
+pub fn register(
+    conn: &Connection,
+    range: &NumberRange,
+    number: i64,
+    document_id: &str,
+    note: &str,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT OR IGNORE INTO number_registry (document_type, series, number, range_id, document_id, status, note, allocated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![range.document_type, range.series, number, range.id, document_id, USED, note, Utc::now().to_rfc3339()],
+    )
+}
+
+/// Receipt series before carnets: receipt series, else invoice carnet, else "CH"
+fn settings_receipt_series(conn: &Connection) -> String {
+    let (receipt_series, carnet_series): (Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT receipt_series, carnet_series FROM agent_settings WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap_or_default();
+    receipt_series
+        .filter(|series| !series.trim().is_empty())
+        .or(carnet_series.filter(|series| !series.trim().is_empty()))
+        .map(|series| series.trim().to_string())
+        .unwrap_or_else(|| "CH".to_string())
+}
+
+/// Creates the carnets described in `agent_settings` for the document types
+/// that have none yet. Receipts were numbered by timestamp while no receipt
+/// numbers were set, so they only get a carnet once numbers are set.
+pub fn seed_from_settings(conn: &Connection) -> rusqlite::Result<()> {
+    let (carnet_series, [invoice_start, invoice_end, invoice_current, receipt_start, receipt_end, receipt_current]): (
+        Option<String>,
+        [Option<i64>; 6],
+    ) = conn
+        .query_row(
+            "SELECT carnet_series, invoice_number_start, invoice_number_end, invoice_number_current,
+                    receipt_number_start, receipt_number_end, receipt_number_current
+             FROM agent_settings WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, [row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?])),
+        )
+        .optional()?
+        .unwrap_or_default();
+
+    if !has_ranges(conn, INVOICE)? {
+        let start = invoice_start.unwrap_or(1);
+        let next = invoice_current.unwrap_or(start);
+        let series = carnet_series.unwrap_or_default().trim().to_string();
+        insert_range(conn, INVOICE, &series, start.min(next), Some(invoice_end.unwrap_or(DEFAULT_INVOICE_END)), next, 0)?;
+        info!("[NUMBERING] Invoice carnet {} created from settings, next number {}", series, next);
+    }
+
+    if !has_ranges(conn, RECEIPT)? && (receipt_start.is_some() || receipt_current.is_some()) {
+        let start = receipt_start.or(receipt_current).unwrap_or(1);
+        let next = receipt_current.unwrap_or(start);
+        let series = settings_receipt_series(conn);
+        insert_range(conn, RECEIPT, &series, start.min(next), receipt_end, next, 0)?;
+        info!("[NUMBERING] Receipt carnet {} created from settings, next number {}", series, next);
+    }
+    Ok(())
+}
+
+/// Registers the numbers of the documents issued before the registry existed.
+/// Receipts numbered by timestamp had no carnet and are left out.
+pub fn backfill(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO number_registry (document_type, series, number, document_id, status, allocated_at)
+         SELECT 'invoice', TRIM(COALESCE(invoice_series, '')), invoice_number, id, 'used', created_at
+         FROM invoices WHERE invoice_number IS NOT NULL ORDER BY created_at",
+        [],
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO number_registry (document_type, series, number, document_id, status, allocated_at)
+         SELECT 'receipt', TRIM(COALESCE(receipt_series, '')), CAST(receipt_number AS INTEGER),
+                COALESCE(receipt_group_id, id), 'used', MIN(created_at)
+         FROM collections
+         WHERE receipt_number != '' AND receipt_number NOT GLOB '*[^0-9]*' AND length(receipt_number) < 14
+         GROUP BY COALESCE(receipt_group_id, id)
+         ORDER BY MIN(created_at)",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE number_registry SET range_id = (
+            SELECT r.id FROM number_ranges r
+            WHERE r.document_type = number_registry.document_type AND r.series = number_registry.series
+              AND number_registry.number >= r.range_start
+              AND number_registry.number <= COALESCE(r.range_end, number_registry.number)
+            ORDER BY r.position, r.id LIMIT 1)
+         WHERE range_id IS NULL",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Shows the carnet in use in `agent_settings`, where the settings screen
+/// and the older code read it.
+fn mirror_settings(conn: &Connection, document_type: &str) -> rusqlite::Result<()> {
+    let Some(range) = active_ranges(conn, document_type)?.into_iter().next() else {
+        return Ok(());
+    };
+    let sql = if document_type == INVOICE {
+        "INSERT INTO agent_settings (id, carnet_series, invoice_number_start, invoice_number_end, invoice_number_current)
+         VALUES (1, NULLIF(?1, ''), ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET carnet_series = COALESCE(excluded.carnet_series, carnet_series),
+            invoice_number_start = excluded.invoice_number_start, invoice_number_end = excluded.invoice_number_end,
+            invoice_number_current = excluded.invoice_number_current"
+    } else {
+        "INSERT INTO agent_settings (id, receipt_series, receipt_number_start, receipt_number_end, receipt_number_current)
+         VALUES (1, NULLIF(?1, ''), ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET receipt_series = COALESCE(excluded.receipt_series, receipt_series),
+            receipt_number_start = excluded.receipt_number_start, receipt_number_end = excluded.receipt_number_end,
+            receipt_number_current = excluded.receipt_number_current"
+    };
+    conn.execute(sql, params![range.series, range.range_start, range.range_end, range.next_number])?;
+    Ok(())
+}
+
+/// What to tell the user about the carnets of `document_type`, if anything.
+pub fn warning(conn: &Connection, document_type: &str) -> rusqlite::Result<Option<String>> {
+    let active = active_ranges(conn, document_type)?;
+    let Some(current) = active.first() else {
+        if has_ranges(conn, document_type)? {
+            return Ok(Some(format!(
+                "Toate carnetele de {} sunt epuizate. Adaugă un carnet nou în Setări.",
+                label(document_type)
+            )));
+        }
+        return Ok(None);
+    };
+    match current.remaining {
+        Some(remaining) if remaining <= WARN_REMAINING => Ok(Some(match active.get(1) {
+            Some(next) => format!(
+                "Carnetul de {} {} mai are {} numere; urmează carnetul {}.",
+                label(document_type),
+                carnet_label(current),
+                remaining,
+                carnet_label(next)
+            ),
+            None => format!(
+                "Carnetul de {} {} mai are {} numere și nu există alt carnet. Adaugă un carnet nou în Setări.",
+                label(document_type),
+                carnet_label(current),
+                remaining
+            ),
+        })),
+        _ => Ok(None),
+    }
+}
+
+pub fn warnings(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    for document_type in [INVOICE, RECEIPT] {
+        warnings.extend(warning(conn, document_type)?);
+    }
+    Ok(warnings)
+}
+
+/// Takes the next free number for `document_id` and registers it. Must run
+/// inside the transaction that inserts the document: if the insert fails,
+/// rolling back returns the number to the carnet.
+///
+/// Receipts without any carnet keep the timestamp numbers they had before
+/// carnets existed; those are not registered.
+pub fn allocate(conn: &Connection, document_type: &str, document_id: &str) -> Result<Allocated, String> {
+    if !has_ranges(conn, document_type).map_err(|e| e.to_string())? {
+        seed_from_settings(conn).map_err(|e| e.to_string())?;
+    }
+
+    loop {
+        let active = active_ranges(conn, document_type).map_err(|e| e.to_string())?;
+        let Some(range) = active.into_iter().next() else {
+            if document_type == RECEIPT && !has_ranges(conn, RECEIPT).map_err(|e| e.to_string())? {
+                return Ok(Allocated {
+                    series: settings_receipt_series(conn),
+                    number: chrono::Local::now()
+                        .format("%Y%m%d%H%M%S")
+                        .to_string()
+                        .parse()
+                        .map_err(|e: std::num::ParseIntError| e.to_string())?,
+                });
+            }
+            return Err(format!(
+                "Nu mai sunt numere disponibile pentru {}: toate carnetele sunt epuizate. Adaugă un carnet nou în Setări.",
+                label(document_type)
+            ));
+        };
+
+        // Numbers already registered (by hand, or in an overlapping carnet) are passed over
+        let mut number = range.next_number.max(range.range_start);
+        while within(range.range_end, number)
+            && is_registered(conn, document_type, &range.series, number).map_err(|e| e.to_string())?
+        {
+            number += 1;
+        }
+
+        if !within(range.range_end, number) {
+            conn.execute(
+                "UPDATE number_ranges SET next_number = ?1, status = ?2 WHERE id = ?3",
+                params![number, EXHAUSTED, range.id],
+            )
+            .map_err(|e| e.to_string())?;
+            warn!(
+                "[NUMBERING] Carnet {} ({}) is used up, moving to the next one",
+                carnet_label(&range),
+                document_type
+            );
+            continue;
+        }
+
+        conn.execute(
+            "INSERT INTO number_registry (document_type, series, number, range_id, document_id, status, allocated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![document_type, range.series, number, range.id, document_id, USED, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE number_ranges SET next_number = ?1, status = ?2 WHERE id = ?3",
+            params![number + 1, status_for(range.range_end, number + 1), range.id],
+        )
+        .map_err(|e| e.to_string())?;
+        mirror_settings(conn, document_type).map_err(|e| e.to_string())?;
+
+        if let Some(message) = warning(conn, document_type).map_err(|e| e.to_string())? {
+            warn!("[NUMBERING] {}", message);
+        }
+        info!(
+            "[NUMBERING] {} {} {} given to {}",
+            document_type, range.series, number, document_id
+        );
+        return Ok(Allocated { series: range.series, number });
+    }
+}
+
+fn validate(range: &NumberRange) -> Result<(), String> {
+    if ![INVOICE, RECEIPT].contains(&range.document_type.as_str()) {
+        return Err(format!("Tip de document necunoscut: {}", range.document_type));
+    }
+    if ![ACTIVE, EXHAUSTED, DISABLED].contains(&range.status.as_str()) {
+        return Err(format!("Stare de carnet necunoscută: {}", range.status));
+    }
+    if range.range_start < 1 {
+        return Err("Primul număr din carnet trebuie să fie cel puțin 1".to_string());
+    }
+    if let Some(end) = range.range_end {
+        if end < range.range_start {
+            return Err("Ultimul număr din carnet nu poate fi mai mic decât primul".to_string());
+        }
+    }
+    if range.next_number < range.range_start {
+        return Err("Numărul curent nu poate fi mai mic decât primul număr din carnet".to_string());
+    }
+    Ok(())
+}
+
+/// Refuses a carnet that shares numbers with another carnet of the same
+/// series: the same number would be given to two documents.
+fn check_clash(conn: &Connection, range: &NumberRange) -> Result<(), String> {
+    let clash = ranges(conn, Some(&range.document_type))
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|other| {
+            other.id != range.id
+                && other.series == range.series
+                && other.range_start <= range.range_end.unwrap_or(i64::MAX)
+                && range.range_start <= other.range_end.unwrap_or(i64::MAX)
+        });
+    match clash {
+        Some(other) => Err(format!(
+            "Carnetul {} se suprapune cu carnetul {}. Folosește altă serie sau alte numere.",
+            carnet_label(range),
+            carnet_label(&other)
+        )),
+        None => Ok(()),
+    }
+}
+
+fn load_range(conn: &Connection, id: i64) -> rusqlite::Result<Option<NumberRange>> {
+    conn.query_row(
+        &format!("SELECT {} FROM number_ranges WHERE id = ?1", RANGE_COLUMNS),
+        [id],
+        map_range,
+    )
+    .optional()
+}
+
+/// Updates `existing` to `range`; numbers the carnet jumps over are recorded
+/// as skipped.
+fn update_range(conn: &Connection, existing: &NumberRange, range: &NumberRange, note: &str) -> Result<(), String> {
+    let status = if range.status == DISABLED {
+        DISABLED
+    } else {
+        status_for(range.range_end, range.next_number)
+    };
+    conn.execute(
+        "UPDATE number_ranges SET series = ?1, range_start = ?2, range_end = ?3, next_number = ?4, position = ?5, status = ?6
+         WHERE id = ?7",
+        params![
+            range.series,
+            range.range_start,
+            range.range_end,
+            range.next_number,
+            range.position,
+            status,
+            existing.id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    if existing.series == range.series && range.next_number > existing.next_number {
+        let last = range.range_end.map_or(range.next_number, |end| range.next_number.min(end + 1));
+        skip(conn, range, existing.next_number, last, note).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn store_range(conn: &Connection, range: &mut NumberRange) -> Result<(), String> {
+    if range.id == 0 {
+        check_clash(conn, range)?;
+        let position: i64 = conn
+            .query_row(
+                "SELECT COALESCE(MAX(position), -1) + 1 FROM number_ranges WHERE document_type = ?1",
+                [&range.document_type],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let id = insert_range(
+            conn,
+            &range.document_type,
+            &range.series,
+            range.range_start,
+            range.range_end,
+            range.next_number,
+            position,
+        )
+        .map_err(|e| e.to_string())?;
+        if range.status == DISABLED {
+            conn.execute("UPDATE number_ranges SET status = ?1 WHERE id = ?2", params![DISABLED, id])
+                .map_err(|e| e.to_string())?;
+        }
+        range.id = id;
+    } else {
+        let existing = load_range(conn, range.id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Carnetul nu a fost găsit".to_string())?;
+        range.document_type = existing.document_type.clone();
+        check_clash(conn, range)?;
+        update_range(conn, &existing, range, "Sărit la modificarea carnetului")?;
+    }
+    mirror_settings(conn, &range.document_type).map_err(|e| e.to_string())
+}
+
+fn make_current(conn: &Connection, range: &mut NumberRange) -> Result<(), String> {
+    let active = active_ranges(conn, &range.document_type).map_err(|e| e.to_string())?;
+    let same: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM number_ranges WHERE document_type = ?1 AND series = ?2 AND range_start = ?3
+             ORDER BY position, id LIMIT 1",
+            params![range.document_type, range.series, range.range_start],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let unused_front: Option<i64> = match active.first() {
+        Some(front) => {
+            let used: bool = conn
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM number_registry WHERE range_id = ?1)",
+                    [front.id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            (!used).then_some(front.id)
+        }
+        None => None,
+    };
+
+    match same.or(unused_front) {
+        Some(id) => {
+            let existing = load_range(conn, id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| "Carnetul nu a fost găsit".to_string())?;
+            // Already the carnet in use: it keeps its place
+            range.position = match active.first() {
+                Some(front) if front.id == existing.id => existing.position,
+                _ => front_position(conn, &range.document_type).map_err(|e| e.to_string())?,
+            };
+            range.id = existing.id;
+            check_clash(conn, range)?;
+            update_range(conn, &existing, range, "Sărit la modificarea numărului curent din Setări")
+        }
+        None => {
+            check_clash(conn, range)?;
+            let position = front_position(conn, &range.document_type).map_err(|e| e.to_string())?;
+            insert_range(
+                conn,
+                &range.document_type,
+                &range.series,
+                range.range_start,
+                range.range_end,
+                range.next_number,
+                position,
+            )
+            .map_err(|e| e.to_string())?;
+            info!(
+                "[NUMBERING] New {} carnet {} from settings, next number {}",
+                range.document_type,
+                carnet_label(range),
+                range.next_number
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Runs `change` in a transaction of its own, or in a savepoint when the
+/// caller already opened one.
+fn in_transaction(conn: &Connection, change: impl FnOnce() -> Result<(), String>) -> Result<(), String> {
+    let (begin, rollback, commit) = if conn.is_autocommit() {
+        ("BEGIN IMMEDIATE TRANSACTION", "ROLLBACK", "COMMIT")
+    } else {
+        ("SAVEPOINT numbering", "ROLLBACK TO numbering; RELEASE numbering", "RELEASE numbering")
+    };
+
+    conn.execute_batch(begin).map_err(|e| e.to_string())?;
+    if let Err(e) = change() {
+        let _ = conn.execute_batch(rollback);
+        return Err(e);
+    }
+    conn.execute_batch(commit).map_err(|e| e.to_string())
+}
+
+/// Adds a carnet, after the existing ones, or changes one.
+pub fn save_range(conn: &Connection, range: &NumberRange) -> Result<(), String> {
+    let mut range = range.clone();
+    range.series = range.series.trim().to_string();
+    validate(&range)?;
+
+    in_transaction(conn, || store_range(conn, &mut range))?;
+
+    info!(
+        "[NUMBERING] Carnet {} ({}) saved, next number {} ({})",
+        carnet_label(&range),
+        range.document_type,
+        range.next_number,
+        range.status
+    );
+    Ok(())
+}
+
+/// Applies the carnet edited in the settings screen: it becomes the carnet
+/// in use. The carnet with the same series and first number is changed,
+/// else the carnet in use if nothing was numbered from it yet, else a new
+/// carnet is added in front.
+pub fn sync_from_settings(
+    conn: &Connection,
+    document_type: &str,
+    series: &str,
+    range_start: i64,
+    range_end: Option<i64>,
+    current: i64,
+) -> Result<(), String> {
+    let mut range = NumberRange {
+        id: 0,
+        document_type: document_type.to_string(),
+        series: series.trim().to_string(),
+        range_start: range_start.min(current),
+        range_end,
+        next_number: current,
+        position: 0,
+        status: ACTIVE.to_string(),
+        remaining: None,
+        created_at: String::new(),
+    };
+    validate(&range)?;
+
+    in_transaction(conn, || make_current(conn, &mut range))
+}
+
+/// Numbers handed out or skipped, latest first.
+pub fn registry(
+    conn: &Connection,
+    document_type: Option<&str>,
+    series: Option<&str>,
+    limit: i64,
+) -> rusqlite::Result<Vec<NumberRegistryEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, document_type, series, number, range_id, document_id, status, note, allocated_at
+         FROM number_registry
+         WHERE (?1 IS NULL OR document_type = ?1) AND (?2 IS NULL OR series = ?2)
+         ORDER BY document_type, series, number DESC
+         LIMIT ?3",
+    )?;
+    let entries = stmt
+        .query_map(params![document_type, series, limit], |row| {
+            Ok(NumberRegistryEntry {
+                id: row.get(0)?,
+                document_type: row.get(1)?,
+                series: row.get(2)?,
+                number: row.get(3)?,
+                range_id: row.get(4)?,
+                document_id: row.get(5)?,
+                status: row.get(6)?,
+                note: row.get(7)?,
+                allocated_at: row.get(8)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agent_settings (id INTEGER PRIMARY KEY, carnet_series TEXT,
+                 invoice_number_start INTEGER, invoice_number_end INTEGER, invoice_number_current INTEGER,
+                 receipt_series TEXT, receipt_number_start INTEGER, receipt_number_end INTEGER, receipt_number_current INTEGER);
+             INSERT INTO agent_settings (id) VALUES (1);",
+        )
+        .unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    fn carnet(series: &str, range_start: i64, range_end: Option<i64>, next_number: i64) -> NumberRange {
+        NumberRange {
+            id: 0,
+            document_type: INVOICE.to_string(),
+            series: series.to_string(),
+            range_start,
+            range_end,
+            next_number,
+            position: 0,
+            status: ACTIVE.to_string(),
+            remaining: None,
+            created_at: String::new(),
+        }
+    }
+
+    fn carnet_in_use(conn: &Connection) -> NumberRange {
+        active_ranges(conn, INVOICE).unwrap().remove(0)
+    }
+
+    fn allocated(conn: &Connection, document_id: &str) -> (String, i64) {
+        let allocated = allocate(conn, INVOICE, document_id).unwrap();
+        (allocated.series, allocated.number)
+    }
+
+    #[test]
+    fn rolled_back_allocation_gives_the_number_back() {
+        let conn = test_db();
+        save_range(&conn, &carnet("KRN", 1, Some(100), 1)).unwrap();
+
+        conn.execute_batch("BEGIN IMMEDIATE TRANSACTION").unwrap();
+        assert_eq!(allocated(&conn, "failed"), ("KRN".to_string(), 1));
+        conn.execute_batch("ROLLBACK").unwrap();
+
+        assert_eq!(allocated(&conn, "saved"), ("KRN".to_string(), 1));
+        let entries = registry(&conn, Some(INVOICE), None, 10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].document_id.as_deref(), Some("saved"));
+        assert_eq!(carnet_in_use(&conn).next_number, 2);
+    }
+
+    #[test]
+    fn next_carnet_takes_over_when_one_runs_out() {
+        let conn = test_db();
+        save_range(&conn, &carnet("KRN25", 1, Some(2), 1)).unwrap();
+        save_range(&conn, &carnet("KRN26", 1, Some(100), 1)).unwrap();
+
+        assert_eq!(allocated(&conn, "a"), ("KRN25".to_string(), 1));
+        assert_eq!(allocated(&conn, "b"), ("KRN25".to_string(), 2));
+        assert_eq!(allocated(&conn, "c"), ("KRN26".to_string(), 1));
+
+        let all = ranges(&conn, Some(INVOICE)).unwrap();
+        assert_eq!(all[0].status, EXHAUSTED);
+        assert_eq!(all[1].next_number, 2);
+        let (series, current): (String, i64) = conn
+            .query_row(
+                "SELECT carnet_series, invoice_number_current FROM agent_settings WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((series.as_str(), current), ("KRN26", 2));
+    }
+
+    #[test]
+    fn last_carnet_running_out_is_an_error() {
+        let conn = test_db();
+        save_range(&conn, &carnet("KRN", 1, Some(1), 1)).unwrap();
+
+        allocated(&conn, "a");
+        let error = allocate(&conn, INVOICE, "b").unwrap_err();
+        assert!(error.contains("toate carnetele sunt epuizate"), "{}", error);
+    }
+
+    #[test]
+    fn registered_numbers_are_passed_over() {
+        let conn = test_db();
+        save_range(&conn, &carnet("KRN", 1, Some(100), 1)).unwrap();
+        let range = carnet_in_use(&conn);
+        register(&conn, &range, 1, "by-hand-1", "Introdus manual").unwrap();
+        register(&conn, &range, 2, "by-hand-2", "Introdus manual").unwrap();
+        skip(&conn, &range, 4, 5, "Sărit").unwrap();
+
+        assert_eq!(allocated(&conn, "a").1, 3);
+        assert_eq!(allocated(&conn, "b").1, 5);
+    }
+
+    #[test]
+    fn warns_when_the_carnet_is_nearly_used_up() {
+        let conn = test_db();
+        save_range(&conn, &carnet("KRN", 1, Some(12), 1)).unwrap();
+
+        allocated(&conn, "a");
+        assert_eq!(warning(&conn, INVOICE).unwrap(), None);
+        allocated(&conn, "b");
+        let alone = warning(&conn, INVOICE).unwrap().unwrap();
+        assert!(alone.contains("mai are 10 numere și nu există alt carnet"), "{}", alone);
+
+        save_range(&conn, &carnet("KRN26", 1, Some(100), 1)).unwrap();
+        let followed = warning(&conn, INVOICE).unwrap().unwrap();
+        assert!(followed.contains("mai are 10 numere; urmează carnetul"), "{}", followed);
+        assert!(followed.contains("KRN26"), "{}", followed);
+    }
+
+    #[test]
+    fn saving_inside_an_open_transaction_uses_a_savepoint() {
+        let conn = test_db();
+        conn.execute_batch("BEGIN IMMEDIATE TRANSACTION").unwrap();
+
+        save_range(&conn, &carnet("KRN", 1, Some(100), 1)).unwrap();
+        assert!(save_range(&conn, &carnet("KRN", 50, Some(150), 50)).is_err());
+
+        // The failed save only undid its own changes
+        assert!(!conn.is_autocommit());
+        assert_eq!(ranges(&conn, Some(INVOICE)).unwrap().len(), 1);
+
+        conn.execute_batch("ROLLBACK").unwrap();
+        assert!(ranges(&conn, Some(INVOICE)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn carnets_of_a_series_may_not_share_numbers() {
+        let conn = test_db();
+        save_range(&conn, &carnet("KRN", 1, Some(100), 1)).unwrap();
+
+        let error = save_range(&conn, &carnet("KRN", 100, None, 100)).unwrap_err();
+        assert!(error.contains("se suprapune"), "{}", error);
+        allocated(&conn, "a");
+        assert!(sync_from_settings(&conn, INVOICE, "KRN", 50, Some(150), 120).is_err());
+
+        save_range(&conn, &carnet("KRN", 101, Some(200), 101)).unwrap();
+        save_range(&conn, &carnet("KRN26", 1, Some(100), 1)).unwrap();
+        assert_eq!(ranges(&conn, Some(INVOICE)).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn invoice_numbers_repeat_only_across_series() {
+        let conn = crate::database::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO partners (id, name, created_at, updated_at) VALUES ('P001', 'Client SRL', '2026-01-01', '2026-01-01');
+             INSERT INTO locations (id, partner_id, name) VALUES ('L001', 'P001', 'Magazin');",
+        )
+        .unwrap();
+        let save = |id: &str, series: &str| {
+            conn.execute(
+                "INSERT INTO invoices (id, invoice_number, invoice_series, partner_id, location_id, status, created_at)
+                 VALUES (?1, 1, ?2, 'P001', 'L001', 'pending', '2026-01-02')",
+                params![id, series],
+            )
+        };
+
+        save("a", "KRN25").unwrap();
+        save("b", "KRN26").unwrap();
+        assert!(save("c", "KRN26").is_err());
+    }
+}
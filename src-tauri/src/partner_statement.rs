@@ -0,0 +1,495 @@
+use chrono::{Local, NaiveDate};
+use log::warn;
+use rusqlite::{params, Connection};
+
+use crate::archive;
+use crate::models::{PartnerStatement, PartnerStatementLine};
+use crate::payment_allocation::parse_date;
+use crate::payment_methods;
+
+// Fișă de cont: the partner's invoices and payments over a period, in date
+// order with a running balance.
+//
+// Invoices come from WME (client_balances) and from this app. WME only sends
+// documents that are still open, with what is left to pay on them; whatever
+// was paid on such a document outside this app shows as a payment on the
+// document's date, since WME does not say when it was made. Payments are the
+// local collections, one line per receipt or payment document. Advances set
+// against invoices move money between documents, not into the account, so
+// they only count for the overdue days. Archived invoices and collections
+// are read too, so a period they fall in still adds up.
+
+pub const INVOICE: &str = "invoice";
+pub const CREDIT_NOTE: &str = "credit_note";
+pub const COLLECTION: &str = "collection";
+pub const PRIOR_PAYMENT: &str = "prior_payment";
+
+const EPSILON: f64 = 0.005;
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// A payment set against one invoice: a collection or an advance application.
+struct Payment {
+    serie: String,
+    numar: String,
+    cod_document: Option<String>,
+    valoare: f64,
+    date: Option<NaiveDate>,
+    synced_at: Option<String>,
+}
+
+struct Document {
+    line: PartnerStatementLine,
+    date: NaiveDate,
+    due: Option<NaiveDate>,
+    /// Paid before it reached this app (WME documents)
+    prior: f64,
+    /// Still to pay, before the local payments
+    open: f64,
+    serie: String,
+    numar: String,
+    cod_document: Option<String>,
+    /// Local payments synced by then are already in the WME balance
+    balance_synced_at: Option<String>,
+}
+
+fn line(date: NaiveDate, kind: &str, document: String, description: String, amount: f64) -> PartnerStatementLine {
+    PartnerStatementLine {
+        date: date.format("%Y-%m-%d").to_string(),
+        kind: kind.to_string(),
+        document,
+        description,
+        due_date: None,
+        debit: if amount > 0.0 { round(amount) } else { 0.0 },
+        credit: if amount < 0.0 { round(-amount) } else { 0.0 },
+        balance: 0.0,
+        days_overdue: None,
+    }
+}
+
+fn document_number(serie: &str, numar: &str) -> String {
+    format!("{}{}", serie.trim(), numar.trim())
+}
+
+fn invoice_kind(value: f64) -> (&'static str, &'static str) {
+    if value < 0.0 {
+        (CREDIT_NOTE, "Notă de credit")
+    } else {
+        (INVOICE, "Factură")
+    }
+}
+
+fn wme_documents(conn: &Connection, partner_id: &str) -> rusqlite::Result<Vec<Document>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT cb.tip_document, COALESCE(cb.serie, ''), COALESCE(cb.numar, ''), cb.cod_document,
+                cb.data, cb.termen, COALESCE(cb.valoare, 0), COALESCE(cb.rest, 0), cb.synced_at
+         FROM client_balances cb
+         WHERE TRIM(cb.id_partener) = TRIM(?1)
+           -- Local invoices come from the invoices table
+           AND NOT EXISTS (
+               SELECT 1 FROM {invoices} i_local
+               WHERE i_local.partner_id = cb.id_partener
+                 AND i_local.invoice_number = CAST(COALESCE(cb.numar, '0') AS INTEGER)
+                 AND (
+                     COALESCE(i_local.invoice_series, '') = COALESCE(cb.serie, '')
+                     OR trim(COALESCE(i_local.invoice_series, '')) = ''
+                     OR trim(COALESCE(cb.serie, '')) = ''
+                 )
+           )
+           AND NOT EXISTS (
+               SELECT 1 FROM ignored_balances ib
+               WHERE ib.id_partener = cb.id_partener
+                 AND ib.cod_document = COALESCE(cb.cod_document, '')
+                 AND ib.serie = COALESCE(cb.serie, '')
+                 AND ib.numar = COALESCE(cb.numar, '')
+           )",
+        invoices = archive::report_source(conn, "invoices", true)?,
+    ))?;
+    let rows = stmt.query_map([partner_id], |row| {
+        Ok((
+            row.get::<_, Option<String>>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, f64>(6)?,
+            row.get::<_, f64>(7)?,
+            row.get::<_, Option<String>>(8)?,
+        ))
+    })?;
+
+    let mut documents = Vec::new();
+    for row in rows {
+        let (tip_document, serie, numar, cod_document, data, termen, valoare, rest, synced_at) = row?;
+        let due = termen.as_deref().and_then(parse_date);
+        let Some(date) = data.as_deref().and_then(parse_date).or(due) else {
+            warn!("[STATEMENT] Document {}{} of {} has no date, left out", serie, numar, partner_id);
+            continue;
+        };
+        let (kind, label) = invoice_kind(valoare);
+        let description = tip_document
+            .map(|tip| tip.trim().to_string())
+            .filter(|tip| !tip.is_empty())
+            .unwrap_or_else(|| label.to_string());
+        let mut line = line(date, kind, document_number(&serie, &numar), description, valoare);
+        line.due_date = due.map(|due| due.format("%Y-%m-%d").to_string());
+        documents.push(Document {
+            line,
+            date,
+            due,
+            prior: if valoare > 0.0 { (valoare - rest).max(0.0) } else { 0.0 },
+            open: if valoare > 0.0 { rest } else { 0.0 },
+            serie,
+            numar,
+            cod_document,
+            balance_synced_at: synced_at,
+        });
+    }
+    Ok(documents)
+}
+
+fn local_invoices(conn: &Connection, partner_id: &str) -> rusqlite::Result<Vec<Document>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(i.invoice_series, ''), CAST(i.invoice_number AS TEXT), i.created_at,
+                CAST(COALESCE(NULLIF(trim(p.scadenta_la_vanzare), ''), '30') AS INTEGER),
+                (
+                    SELECT COALESCE(SUM(ii.total_price * (1.0 + COALESCE(CAST(pr.procent_tva AS REAL), 0) / 100.0)), 0)
+                    FROM {items} ii
+                    JOIN products pr ON pr.id = ii.product_id
+                    WHERE ii.invoice_id = i.id
+                )
+         FROM {invoices} i
+         JOIN partners p ON p.id = i.partner_id
+         WHERE TRIM(i.partner_id) = TRIM(?1) AND i.status IN ('pending', 'sending', 'sent', 'failed')",
+        invoices = archive::report_source(conn, "invoices", true)?,
+        items = archive::report_source(conn, "invoice_items", true)?,
+    ))?;
+    let rows = stmt.query_map([partner_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+            row.get::<_, f64>(4)?,
+        ))
+    })?;
+
+    let mut documents = Vec::new();
+    for row in rows {
+        let (serie, numar, created_at, payment_days, valoare) = row?;
+        let Some(date) = parse_date(&created_at) else {
+            continue;
+        };
+        let due = date.checked_add_days(chrono::Days::new(payment_days.max(0) as u64));
+        let (kind, label) = invoice_kind(valoare);
+        let mut line = line(date, kind, document_number(&serie, &numar), label.to_string(), valoare);
+        line.due_date = due.map(|due| due.format("%Y-%m-%d").to_string());
+        documents.push(Document {
+            line,
+            date,
+            due,
+            prior: 0.0,
+            open: valoare.max(0.0),
+            serie,
+            numar,
+            cod_document: None,
+            balance_synced_at: None,
+        });
+    }
+    Ok(documents)
+}
+
+fn invoice_payments(conn: &Connection, partner_id: &str) -> rusqlite::Result<Vec<Payment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(serie_factura, ''), COALESCE(numar_factura, ''), cod_document, valoare, data_incasare, synced_at
+         FROM {collections}
+         WHERE TRIM(id_partener) = TRIM(?1) AND COALESCE(transaction_type, 'invoice') != 'advance'
+           AND status IN ('pending', 'sending', 'synced')
+         UNION ALL
+         SELECT COALESCE(serie_factura, ''), COALESCE(numar_factura, ''), cod_document, valoare, created_at, synced_at
+         FROM advance_applications
+         WHERE TRIM(id_partener) = TRIM(?1) AND status IN ('pending', 'sending', 'synced')",
+        collections = archive::report_source(conn, "collections", true)?,
+    ))?;
+    let rows = stmt.query_map([partner_id], |row| {
+        Ok(Payment {
+            serie: row.get(0)?,
+            numar: row.get(1)?,
+            cod_document: row.get(2)?,
+            valoare: row.get(3)?,
+            date: row.get::<_, String>(4).ok().as_deref().and_then(parse_date),
+            synced_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Money received: one line per receipt or non-cash payment document.
+fn collections(conn: &Connection, partner_id: &str) -> rusqlite::Result<Vec<PartnerStatementLine>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT MIN(data_incasare), MAX(receipt_series), MAX(receipt_number),
+                MAX(payment_method), MAX(payment_reference), MAX(payment_due_date), SUM(valoare)
+         FROM {}
+         WHERE TRIM(id_partener) = TRIM(?1) AND status IN ('pending', 'sending', 'synced')
+         GROUP BY COALESCE(receipt_group_id, id)",
+        archive::report_source(conn, "collections", true)?
+    ))?;
+    let rows = stmt.query_map([partner_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, f64>(6)?,
+        ))
+    })?;
+
+    let mut lines = Vec::new();
+    for row in rows {
+        let (data_incasare, series, number, method, reference, due_date, valoare) = row?;
+        let Some(date) = parse_date(&data_incasare) else {
+            continue;
+        };
+        let method = method.unwrap_or_default();
+        let (document, description) = if payment_methods::is_cash(&method) {
+            (
+                document_number(series.as_deref().unwrap_or_default(), number.as_deref().unwrap_or_default()),
+                "Încasare numerar (chitanță)".to_string(),
+            )
+        } else {
+            let mut description = format!("Încasare {}", payment_methods::label(&method));
+            if let Some(due_date) = due_date {
+                description.push_str(&format!(", scadență {}", due_date));
+            }
+            (reference.unwrap_or_default(), description)
+        };
+        lines.push(line(date, COLLECTION, document, description, -valoare));
+    }
+    Ok(lines)
+}
+
+fn order(kind: &str) -> u8 {
+    match kind {
+        INVOICE | CREDIT_NOTE => 0,
+        PRIOR_PAYMENT => 1,
+        _ => 2,
+    }
+}
+
+pub fn build(conn: &Connection, partner_id: &str, from: &str, to: &str) -> Result<PartnerStatement, String> {
+    let from_date = NaiveDate::parse_from_str(from, "%Y-%m-%d").map_err(|e| format!("Dată invalidă {}: {}", from, e))?;
+    let to_date = NaiveDate::parse_from_str(to, "%Y-%m-%d").map_err(|e| format!("Dată invalidă {}: {}", to, e))?;
+    if from_date > to_date {
+        return Err("Data de început este după data de sfârșit".to_string());
+    }
+
+    let (partner_name, partner_cif): (String, Option<String>) = conn
+        .query_row(
+            "SELECT name, cif FROM partners WHERE TRIM(id) = TRIM(?1)",
+            params![partner_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| format!("Partenerul nu a fost găsit: {}", e))?;
+
+    let mut documents = wme_documents(conn, partner_id).map_err(|e| e.to_string())?;
+    documents.extend(local_invoices(conn, partner_id).map_err(|e| e.to_string())?);
+    let payments = invoice_payments(conn, partner_id).map_err(|e| e.to_string())?;
+
+    // Overdue days are counted at the end of the period, or today for a
+    // period that is not over yet
+    let as_of = to_date.min(Local::now().date_naive());
+    let mut lines = Vec::new();
+    let mut overdue_amount = 0.0;
+    for mut document in documents {
+        let mut prior = document.prior;
+        let mut open = document.open;
+        for payment in payments.iter().filter(|payment| {
+            payment.serie.trim() == document.serie.trim()
+                && payment.numar.trim() == document.numar.trim()
+                && (document.cod_document.is_none() || payment.cod_document == document.cod_document)
+        }) {
+            let in_wme_balance = match (&payment.synced_at, &document.balance_synced_at) {
+                (Some(synced_at), Some(balance_synced_at)) => synced_at <= balance_synced_at,
+                _ => false,
+            };
+            if in_wme_balance {
+                // Already paid in WME: shown as a collection, not as a prior payment
+                prior -= payment.valoare;
+            } else if payment.date.map_or(true, |date| date <= to_date) {
+                open -= payment.valoare;
+            }
+        }
+
+        if let Some(due) = document.due.filter(|due| *due < as_of && document.date <= to_date) {
+            if open > EPSILON {
+                document.line.days_overdue = Some((as_of - due).num_days());
+                overdue_amount += open;
+            }
+        }
+        if prior > EPSILON {
+            lines.push(line(
+                document.date,
+                PRIOR_PAYMENT,
+                document.line.document.clone(),
+                "Plăți înregistrate în WME".to_string(),
+                -prior,
+            ));
+        }
+        lines.push(document.line);
+    }
+    lines.extend(collections(conn, partner_id).map_err(|e| e.to_string())?);
+    lines.sort_by(|a, b| {
+        (&a.date, order(&a.kind), &a.document).cmp(&(&b.date, order(&b.kind), &b.document))
+    });
+
+    let mut opening_balance = 0.0;
+    let mut kept = Vec::new();
+    for line in lines {
+        if line.date.as_str() < from {
+            opening_balance += line.debit - line.credit;
+        } else if line.date.as_str() <= to {
+            kept.push(line);
+        }
+    }
+
+    let opening_balance = round(opening_balance);
+    let mut balance = opening_balance;
+    let (mut total_debit, mut total_credit) = (0.0, 0.0);
+    for line in kept.iter_mut() {
+        balance += line.debit - line.credit;
+        line.balance = round(balance);
+        total_debit += line.debit;
+        total_credit += line.credit;
+    }
+
+    Ok(PartnerStatement {
+        partner_id: partner_id.to_string(),
+        partner_name,
+        partner_cif,
+        from: from.to_string(),
+        to: to.to_string(),
+        opening_balance,
+        lines: kept,
+        total_debit: round(total_debit),
+        total_credit: round(total_credit),
+        closing_balance: round(balance),
+        overdue_amount: round(overdue_amount),
+        generated_at: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        document_path: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Partner with 15 days to pay. From WME: invoice FV100 of 500 with 200
+    /// left, 100 of which was paid here and is already in that balance, and a
+    /// credit note of 50. Here: invoice KRN5 of 98.10 (also back from WME) and
+    /// a payment of 50 on FV100 not yet synced.
+    fn statement_db() -> Connection {
+        let conn = crate::database::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO partners (id, name, cif, scadenta_la_vanzare, created_at, updated_at)
+                 VALUES ('P001', 'Ștefănescu Țară SRL', 'RO123456', '15', '2026-01-01', '2026-01-01');
+             INSERT INTO locations (id, partner_id, name) VALUES ('L001', 'P001', 'Magazin Iași');
+             INSERT INTO products (id, name, unit_of_measure, price, procent_tva) VALUES ('OUA-M', 'Ouă M', 'buc', 0.9, '9');
+
+             INSERT INTO client_balances (id_partener, tip_document, cod_document, serie, numar, data, termen, valoare, rest, synced_at) VALUES
+                 ('P001', 'Factură', 'D100', 'FV', '100', '10/01/2026', '25/01/2026', 500, 200, '2026-03-01T06:00:00+00:00'),
+                 ('P001', 'Notă de credit', 'D7', 'NC', '7', '15/01/2026', NULL, -50, -50, '2026-03-01T06:00:00+00:00'),
+                 ('P001', 'Factură', 'D5', 'KRN', '5', '20/02/2026', '07/03/2026', 98.1, 98.1, '2026-03-01T06:00:00+00:00');
+
+             INSERT INTO invoices (id, invoice_number, invoice_series, partner_id, location_id, status, created_at, sent_at)
+                 VALUES ('inv-5', 5, 'KRN', 'P001', 'L001', 'sent', '2026-02-20T10:00:00+02:00', '2026-02-20T10:05:00+02:00');
+             INSERT INTO invoice_items (id, invoice_id, product_id, quantity, unit_price, total_price)
+                 VALUES ('item-1', 'inv-5', 'OUA-M', 100, 0.9, 90);
+
+             INSERT INTO collections (id, receipt_series, receipt_number, id_partener, serie_factura, numar_factura, cod_document,
+                                      valoare, data_incasare, status, synced_at, created_at) VALUES
+                 ('c1', 'CH', '1', 'P001', 'FV', '100', 'D100', 100, '2026-02-10', 'synced', '2026-02-11T08:00:00+00:00', '2026-02-10'),
+                 ('c2', 'CH', '2', 'P001', 'FV', '100', 'D100', 50, '2026-03-05', 'pending', NULL, '2026-03-05');",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn entries(statement: &PartnerStatement) -> Vec<(&str, &str, &str, f64, f64, f64)> {
+        statement
+            .lines
+            .iter()
+            .map(|line| {
+                (line.date.as_str(), line.kind.as_str(), line.document.as_str(), line.debit, line.credit, line.balance)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn whole_history_adds_up_to_what_is_still_owed() {
+        let statement = build(&statement_db(), "P001", "2026-01-01", "2026-03-31").unwrap();
+
+        assert_eq!(statement.opening_balance, 0.0);
+        assert_eq!(
+            entries(&statement),
+            [
+                ("2026-01-10", INVOICE, "FV100", 500.0, 0.0, 500.0),
+                // 300 paid in WME, less the 100 paid here that WME already counts
+                ("2026-01-10", PRIOR_PAYMENT, "FV100", 0.0, 200.0, 300.0),
+                ("2026-01-15", CREDIT_NOTE, "NC7", 0.0, 50.0, 250.0),
+                ("2026-02-10", COLLECTION, "CH1", 0.0, 100.0, 150.0),
+                ("2026-02-20", INVOICE, "KRN5", 98.1, 0.0, 248.1),
+                ("2026-03-05", COLLECTION, "CH2", 0.0, 50.0, 198.1),
+            ]
+        );
+        // WME's 200 left on FV100, less the 50 paid since, plus KRN5, less the credit note
+        assert_eq!(statement.closing_balance, 198.1);
+        assert_eq!((statement.total_debit, statement.total_credit), (598.1, 400.0));
+        assert_eq!(statement.lines[0].days_overdue, Some(65));
+        assert_eq!(statement.lines[4].days_overdue, Some(24));
+        assert_eq!(statement.lines[4].due_date.as_deref(), Some("2026-03-07"));
+        assert_eq!(statement.overdue_amount, 248.1);
+    }
+
+    #[test]
+    fn period_starts_from_the_opening_balance() {
+        let statement = build(&statement_db(), "P001", "2026-02-01", "2026-02-28").unwrap();
+
+        assert_eq!(statement.opening_balance, 250.0);
+        assert_eq!(
+            entries(&statement),
+            [
+                ("2026-02-10", COLLECTION, "CH1", 0.0, 100.0, 150.0),
+                ("2026-02-20", INVOICE, "KRN5", 98.1, 0.0, 248.1),
+            ]
+        );
+        assert_eq!(statement.closing_balance, 248.1);
+        // The payment of March 5 did not happen yet at the end of February
+        assert_eq!(statement.lines[1].days_overdue, None);
+        assert_eq!(statement.overdue_amount, 200.0);
+    }
+
+    #[test]
+    fn archived_documents_stay_on_the_statement() {
+        let conn = statement_db();
+        let before = build(&conn, "P001", "2026-02-01", "2026-03-31").unwrap();
+
+        let moved = archive::archive_documents(&conn, archive::MIN_RETENTION_MONTHS).unwrap();
+        assert_eq!((moved.invoices, moved.collections), (1, 1));
+
+        let after = build(&conn, "P001", "2026-02-01", "2026-03-31").unwrap();
+        assert_eq!(after.opening_balance, before.opening_balance);
+        assert_eq!(entries(&after), entries(&before));
+        assert_eq!(after.overdue_amount, before.overdue_amount);
+    }
+
+    #[test]
+    fn refuses_a_period_that_ends_before_it_starts() {
+        assert!(build(&statement_db(), "P001", "2026-03-01", "2026-02-01").is_err());
+        assert!(build(&statement_db(), "P404", "2026-01-01", "2026-02-01").is_err());
+    }
+}
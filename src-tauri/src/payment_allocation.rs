@@ -0,0 +1,292 @@
+use chrono::NaiveDate;
+
+use crate::models::{ClientBalance, PaymentAllocation, PaymentAllocationLine};
+
+// Splitting a lump sum from a customer over their open invoices.
+//
+// Amounts are handled in bani so the lines always add up to the sum handed
+// over. Invoices are paid in full in the strategy's order until the money
+// runs out; the last one may be paid in part. Whatever is left over once
+// every invoice is paid is reported as unallocated.
+
+pub const OLDEST_DUE: &str = "oldest_due";
+pub const OVERDUE_FIRST: &str = "overdue_first";
+pub const EXACT_MATCH: &str = "exact_match";
+
+/// Dates in client_balances come as dd/mm/yyyy (local invoices), dd.mm.yyyy
+/// or ISO (WME).
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    ["%d/%m/%Y", "%d.%m.%Y", "%Y-%m-%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text.get(..10)?, "%Y-%m-%d").ok())
+}
+
+fn bani(value: f64) -> i64 {
+    (value * 100.0).round() as i64
+}
+
+struct Open<'a> {
+    balance: &'a ClientBalance,
+    rest: i64,
+    issued: Option<NaiveDate>,
+    due: Option<NaiveDate>,
+}
+
+impl Open<'_> {
+    /// Due date, the issue date when WME sent none
+    fn due_or_issued(&self) -> Option<NaiveDate> {
+        self.due.or(self.issued)
+    }
+
+    fn days_overdue(&self, today: NaiveDate) -> i64 {
+        self.due.map(|due| (today - due).num_days()).unwrap_or(0)
+    }
+}
+
+/// Order in which the invoices are paid. Invoices without any date come last.
+fn order(open: &mut [Open], strategy: &str, today: NaiveDate) {
+    let by_due = |a: &Open, b: &Open| {
+        (a.due_or_issued().is_none(), a.due_or_issued(), a.issued).cmp(&(
+            b.due_or_issued().is_none(),
+            b.due_or_issued(),
+            b.issued,
+        ))
+    };
+    match strategy {
+        // Overdue invoices first, the longest overdue first, then the
+        // others by due date; an invoice without a due date is not overdue
+        OVERDUE_FIRST => open.sort_by(|a, b| {
+            let (a_late, b_late) = (a.days_overdue(today) > 0, b.days_overdue(today) > 0);
+            b_late
+                .cmp(&a_late)
+                .then_with(|| (a.due.is_none(), a.due).cmp(&(b.due.is_none(), b.due)))
+                .then_with(|| by_due(a, b))
+        }),
+        _ => open.sort_by(by_due),
+    }
+}
+
+pub fn propose(
+    balances: &[ClientBalance],
+    amount: f64,
+    strategy: &str,
+    today: NaiveDate,
+) -> Result<PaymentAllocation, String> {
+    if ![OLDEST_DUE, OVERDUE_FIRST, EXACT_MATCH].contains(&strategy) {
+        return Err(format!("Strategie de alocare necunoscută: {}", strategy));
+    }
+    let total = bani(amount);
+    if total <= 0 {
+        return Err("Suma încasată trebuie să fie mai mare decât 0".to_string());
+    }
+
+    let mut open: Vec<Open> = balances
+        .iter()
+        .map(|balance| Open {
+            balance,
+            rest: bani(balance.rest.unwrap_or(0.0)),
+            issued: balance.data.as_deref().and_then(parse_date),
+            due: balance.termen.as_deref().and_then(parse_date),
+        })
+        .filter(|open| open.rest > 0)
+        .collect();
+    order(&mut open, strategy, today);
+
+    // An invoice for exactly the sum handed over is the one being paid,
+    // whatever its age
+    if strategy == EXACT_MATCH {
+        if let Some(position) = open.iter().position(|open| open.rest == total) {
+            let exact = open.remove(position);
+            open.insert(0, exact);
+        }
+    }
+
+    let mut left = total;
+    let mut lines = Vec::new();
+    for invoice in &open {
+        if left == 0 {
+            break;
+        }
+        let paid = invoice.rest.min(left);
+        left -= paid;
+        lines.push(PaymentAllocationLine {
+            serie_factura: invoice.balance.serie.clone(),
+            numar_factura: invoice.balance.numar.clone(),
+            cod_document: invoice.balance.cod_document.clone(),
+            data: invoice.balance.data.clone(),
+            termen: invoice.balance.termen.clone(),
+            rest: invoice.rest as f64 / 100.0,
+            valoare: paid as f64 / 100.0,
+            days_overdue: invoice.days_overdue(today).max(0),
+            fully_paid: paid == invoice.rest,
+        });
+    }
+
+    Ok(PaymentAllocation {
+        strategy: strategy.to_string(),
+        amount: total as f64 / 100.0,
+        allocated: (total - left) as f64 / 100.0,
+        unallocated: left as f64 / 100.0,
+        open_balance: open.iter().map(|invoice| invoice.rest).sum::<i64>() as f64 / 100.0,
+        lines,
+        receipt_group_id: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, 31).unwrap()
+    }
+
+    fn balance(numar: &str, data: Option<&str>, termen: Option<&str>, rest: f64) -> ClientBalance {
+        ClientBalance {
+            id: None,
+            id_partener: "P001".to_string(),
+            cod_fiscal: None,
+            denumire: None,
+            tip_document: Some("FACTURA".to_string()),
+            cod_document: Some(format!("D{}", numar)),
+            serie: Some("KRN".to_string()),
+            numar: Some(numar.to_string()),
+            data: data.map(str::to_string),
+            valoare: Some(rest),
+            rest: Some(rest),
+            termen: termen.map(str::to_string),
+            moneda: Some("RON".to_string()),
+            sediu: None,
+            id_sediu: None,
+            curs: None,
+            observatii: None,
+            cod_obligatie: None,
+            marca_agent: None,
+            synced_at: None,
+        }
+    }
+
+    /// 101 and 102 overdue, 103 not yet due, 104 issued without a due date,
+    /// 105 without any date, plus a credit note that is never paid
+    fn balances() -> Vec<ClientBalance> {
+        vec![
+            balance("103", Some("05/03/2026"), Some("2026-04-04"), 80.0),
+            balance("101", Some("01/02/2026"), Some("15/02/2026"), 100.0),
+            balance("105", None, None, 30.0),
+            balance("104", Some("01.01.2026"), None, 40.0),
+            balance("102", Some("10/01/2026"), Some("20.03.2026"), 50.5),
+            balance("106", Some("01/03/2026"), Some("15/03/2026"), -25.0),
+        ]
+    }
+
+    fn paid(allocation: &PaymentAllocation) -> Vec<(&str, f64)> {
+        allocation
+            .lines
+            .iter()
+            .map(|line| (line.numar_factura.as_deref().unwrap(), line.valoare))
+            .collect()
+    }
+
+    #[test]
+    fn oldest_due_pays_by_due_date_then_issue_date() {
+        let allocation = propose(&balances(), 1000.0, OLDEST_DUE, today()).unwrap();
+        assert_eq!(
+            paid(&allocation),
+            [("104", 40.0), ("101", 100.0), ("102", 50.5), ("103", 80.0), ("105", 30.0)]
+        );
+        assert_eq!(allocation.open_balance, 300.5);
+    }
+
+    #[test]
+    fn overdue_first_pays_the_longest_overdue_first() {
+        let allocation = propose(&balances(), 1000.0, OVERDUE_FIRST, today()).unwrap();
+        assert_eq!(
+            paid(&allocation),
+            [("101", 100.0), ("102", 50.5), ("103", 80.0), ("104", 40.0), ("105", 30.0)]
+        );
+        let days: Vec<i64> = allocation.lines.iter().map(|line| line.days_overdue).collect();
+        assert_eq!(days, [44, 11, 0, 0, 0]);
+    }
+
+    #[test]
+    fn exact_match_pays_the_invoice_for_that_sum() {
+        let allocation = propose(&balances(), 80.0, EXACT_MATCH, today()).unwrap();
+        assert_eq!(paid(&allocation), [("103", 80.0)]);
+        assert!(allocation.lines[0].fully_paid);
+
+        // No invoice for that sum: oldest due first
+        let allocation = propose(&balances(), 60.0, EXACT_MATCH, today()).unwrap();
+        assert_eq!(paid(&allocation), [("104", 40.0), ("101", 20.0)]);
+    }
+
+    #[test]
+    fn last_invoice_is_paid_in_part() {
+        let allocation = propose(&balances(), 150.0, OVERDUE_FIRST, today()).unwrap();
+        assert_eq!(paid(&allocation), [("101", 100.0), ("102", 50.0)]);
+        assert!(allocation.lines[0].fully_paid);
+        assert!(!allocation.lines[1].fully_paid);
+        assert_eq!(allocation.lines[1].rest, 50.5);
+        assert_eq!((allocation.allocated, allocation.unallocated), (150.0, 0.0));
+    }
+
+    #[test]
+    fn money_left_once_everything_is_paid_is_unallocated() {
+        let allocation = propose(&balances()[..2], 200.0, OLDEST_DUE, today()).unwrap();
+        assert_eq!(paid(&allocation), [("101", 100.0), ("103", 80.0)]);
+        assert_eq!((allocation.amount, allocation.allocated, allocation.unallocated), (200.0, 180.0, 20.0));
+
+        let allocation = propose(&[], 50.0, OLDEST_DUE, today()).unwrap();
+        assert!(allocation.lines.is_empty());
+        assert_eq!(allocation.unallocated, 50.0);
+    }
+
+    #[test]
+    fn amounts_add_up_to_the_ban() {
+        let balances = [
+            balance("1", Some("01/03/2026"), None, 0.1),
+            balance("2", Some("02/03/2026"), None, 0.2),
+            balance("3", Some("03/03/2026"), None, 33.34),
+        ];
+        let allocation = propose(&balances, 0.3, OLDEST_DUE, today()).unwrap();
+        assert_eq!(paid(&allocation), [("1", 0.1), ("2", 0.2)]);
+        assert_eq!((allocation.allocated, allocation.unallocated), (0.3, 0.0));
+
+        let allocation = propose(&balances, 10.004, OLDEST_DUE, today()).unwrap();
+        assert_eq!(allocation.amount, 10.0);
+        assert_eq!(paid(&allocation), [("1", 0.1), ("2", 0.2), ("3", 9.7)]);
+        let sum: i64 = allocation.lines.iter().map(|line| bani(line.valoare)).sum();
+        assert_eq!(sum, 1000);
+    }
+
+    #[test]
+    fn invoices_without_dates_are_paid_last_and_never_overdue() {
+        let balances = [balance("1", None, None, 10.0), balance("2", Some("garbage"), Some(""), 10.0)];
+        for strategy in [OLDEST_DUE, OVERDUE_FIRST, EXACT_MATCH] {
+            let allocation = propose(&balances, 20.0, strategy, today()).unwrap();
+            assert_eq!(allocation.allocated, 20.0, "{}", strategy);
+            assert!(allocation.lines.iter().all(|line| line.days_overdue == 0), "{}", strategy);
+        }
+
+        let mixed = [balance("1", None, None, 10.0), balance("2", Some("01/03/2026"), None, 10.0)];
+        let allocation = propose(&mixed, 10.0, OVERDUE_FIRST, today()).unwrap();
+        assert_eq!(paid(&allocation), [("2", 10.0)]);
+    }
+
+    #[test]
+    fn refuses_unknown_strategies_and_empty_sums() {
+        assert!(propose(&balances(), 10.0, "newest_first", today()).is_err());
+        assert!(propose(&balances(), 0.0, OLDEST_DUE, today()).is_err());
+        assert!(propose(&balances(), 0.004, OLDEST_DUE, today()).is_err());
+    }
+
+    #[test]
+    fn reads_the_date_formats_of_both_sources() {
+        let expected = NaiveDate::from_ymd_opt(2026, 3, 5);
+        for text in ["05/03/2026", "05.03.2026", "2026-03-05", "2026-03-05T10:00:00+02:00", " 05/03/2026 "] {
+            assert_eq!(parse_date(text), expected, "{}", text);
+        }
+        assert_eq!(parse_date("03/2026"), None);
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::NaiveDate;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::models::PaymentDetails;
+
+// How a partner paid a collection. Only cash goes into the agent's drawer:
+// it gets a receipt (chitanță) from the carnet, counts against the cash caps
+// and appears in the cash register. The other methods carry the number of
+// their own document (POS slip, transfer reference, promissory note or
+// cheque) and are booked in WME on a bank account instead of the cash desk.
+
+pub const CASH: &str = "cash";
+/// Card payment on the agent's POS terminal
+pub const CARD: &str = "card";
+pub const BANK_TRANSFER: &str = "bank_transfer";
+/// Bilet la ordin (BO)
+pub const PROMISSORY_NOTE: &str = "promissory_note";
+pub const CHEQUE: &str = "cheque";
+
+pub const ALL: [&str; 5] = [CASH, CARD, BANK_TRANSFER, PROMISSORY_NOTE, CHEQUE];
+
+pub fn is_cash(method: &str) -> bool {
+    method.is_empty() || method == CASH
+}
+
+pub fn label(method: &str) -> &'static str {
+    match method {
+        CARD => "card (POS)",
+        BANK_TRANSFER => "ordin de plată",
+        PROMISSORY_NOTE => "bilet la ordin",
+        CHEQUE => "cec",
+        _ => "numerar",
+    }
+}
+
+/// `TipDoc` of the CasaBanca transaction in WME.
+pub fn wme_document_type(method: &str) -> &'static str {
+    match method {
+        CARD => "Bon POS",
+        BANK_TRANSFER => "Ordin de plata",
+        PROMISSORY_NOTE => "Bilet la ordin",
+        CHEQUE => "Cec",
+        _ => "Chitanta",
+    }
+}
+
+/// `Sursa` of the CasaBanca document: the cash desk or a bank account.
+pub fn wme_source(method: &str) -> &'static str {
+    if is_cash(method) {
+        "CASA"
+    } else {
+        "BANCA"
+    }
+}
+
+/// Primary RON account of the active company, where non-cash payments go
+/// unless another account is given.
+pub fn default_bank_account(conn: &Connection) -> rusqlite::Result<Option<String>> {
+    let Some(company_id) = crate::company::active_id(conn)? else {
+        return Ok(None);
+    };
+    conn.query_row(
+        "SELECT iban FROM company_bank_accounts
+         WHERE company_id = ?1 AND TRIM(iban) <> ''
+         ORDER BY is_primary DESC, (currency = 'RON') DESC, sort_order, id LIMIT 1",
+        [company_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn trimmed(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|value| !value.is_empty()).map(str::to_string)
+}
+
+/// Checks the fields each method needs and fills in the bank account. Cash
+/// keeps none of them.
+pub fn validate(conn: &Connection, payment: Option<&PaymentDetails>) -> Result<PaymentDetails, String> {
+    let Some(payment) = payment else {
+        return Ok(PaymentDetails::default());
+    };
+    let method = payment.method.trim();
+    if !ALL.contains(&method) {
+        return Err(format!("Modalitate de plată necunoscută: {}", payment.method));
+    }
+    if is_cash(method) {
+        return Ok(PaymentDetails::default());
+    }
+
+    let reference = trimmed(&payment.reference).ok_or_else(|| match method {
+        CARD => "Numărul bonului POS este obligatoriu".to_string(),
+        BANK_TRANSFER => "Referința ordinului de plată este obligatorie".to_string(),
+        PROMISSORY_NOTE => "Numărul biletului la ordin este obligatoriu".to_string(),
+        _ => "Numărul cecului este obligatoriu".to_string(),
+    })?;
+
+    let due_date = trimmed(&payment.due_date);
+    if let Some(due_date) = &due_date {
+        NaiveDate::parse_from_str(due_date, "%Y-%m-%d")
+            .map_err(|e| format!("Scadență invalidă {}: {}", due_date, e))?;
+    }
+    if method == PROMISSORY_NOTE && due_date.is_none() {
+        return Err("Scadența biletului la ordin este obligatorie".to_string());
+    }
+
+    let bank_account = match trimmed(&payment.bank_account) {
+        Some(account) => Some(account.replace(' ', "").to_uppercase()),
+        None => default_bank_account(conn).map_err(|e| e.to_string())?,
+    }
+    .ok_or_else(|| {
+        "Firma nu are un cont bancar pentru încasări fără numerar; adaugă unul în setări.".to_string()
+    })?;
+
+    Ok(PaymentDetails {
+        method: method.to_string(),
+        reference: Some(reference),
+        due_date: if method == CARD || method == BANK_TRANSFER { None } else { due_date },
+        bank_account: Some(bank_account),
+    })
+}
+
+/// Reads `payment_method, payment_reference, payment_due_date, bank_account`
+/// starting at column `first`.
+pub fn from_row(row: &rusqlite::Row, first: usize) -> rusqlite::Result<PaymentDetails> {
+    Ok(PaymentDetails {
+        method: row.get(first)?,
+        reference: row.get(first + 1)?,
+        due_date: row.get(first + 2)?,
+        bank_account: row.get(first + 3)?,
+    })
+}
+
+/// Payment of a collection, by row id or receipt group.
+pub fn of_collection(conn: &Connection, collection_id: &str) -> rusqlite::Result<PaymentDetails> {
+    conn.query_row(
+        "SELECT payment_method, payment_reference, payment_due_date, bank_account FROM collections
+         WHERE COALESCE(receipt_group_id, id) = ?1 OR id = ?1 LIMIT 1",
+        [collection_id],
+        |row| from_row(row, 0),
+    )
+    .optional()
+    .map(Option::unwrap_or_default)
+}
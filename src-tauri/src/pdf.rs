@@ -0,0 +1,750 @@
+use flate2::{write::ZlibEncoder, Compression};
+use std::io::Write;
+
+use crate::codes::Bitmap;
+
+// Native PDF output for the documents we print: invoice, receipt, daily
+// report and quality certificate.
+//
+// Layouts are single-column flows on 80mm paper, written top to bottom with
+// `Flow`. Only the standard PDF fonts are used (Helvetica, Helvetica-Bold,
+// Courier-Bold), re-encoded so the Romanian letters ă ș ț have their own
+// codes, and nothing time- or platform-dependent is written into the file:
+// the same input always produces the same bytes.
+
+pub const MM: f32 = 72.0 / 25.4;
+
+const LINE_HEIGHT: f32 = 1.2;
+/// Baseline offset from the top of a line box, as a fraction of the font size.
+const BASELINE: f32 = 0.86;
+
+/// WinAnsi with the six undefined slots reused for Romanian letters.
+const ENCODING: &str = "<< /Type /Encoding /BaseEncoding /WinAnsiEncoding \
+    /Differences [127 /tcommaaccent 129 /Abreve 141 /abreve 143 /Scommaaccent 144 /scommaaccent 157 /Tcommaaccent] >>";
+
+// Advance widths (1/1000 em) of ASCII 32..=126, from the Adobe core font metrics.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // space .. /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // 0 .. ?
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // @ .. O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // P .. _
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // ` .. o
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p .. ~
+];
+
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, // space .. /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, // 0 .. ?
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778, // @ .. O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556, // P .. _
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611, // ` .. o
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, // p .. ~
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineStyle {
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+/// Maps a character to its byte in the font encoding above.
+fn encode_char(c: char) -> u8 {
+    match c {
+        ' '..='~' => c as u8,
+        'ț' | 'ţ' => 127,
+        'Ă' => 129,
+        'ă' => 141,
+        'Ș' | 'Ş' => 143,
+        'ș' | 'ş' => 144,
+        'Ț' | 'Ţ' => 157,
+        '€' => 0x80,
+        '„' => 0x84,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '\t' => b' ',
+        _ => b'?',
+    }
+}
+
+/// ASCII letter with (roughly) the same advance width, used for measuring.
+fn width_proxy(c: char) -> char {
+    match c {
+        'ă' | 'â' | 'à' | 'á' | 'ä' | 'ã' | 'å' => 'a',
+        'Ă' | 'Â' | 'À' | 'Á' | 'Ä' | 'Ã' | 'Å' => 'A',
+        'î' | 'ì' | 'í' | 'ï' => 'i',
+        'Î' | 'Ì' | 'Í' | 'Ï' => 'I',
+        'ș' | 'ş' => 's',
+        'Ș' | 'Ş' => 'S',
+        'ț' | 'ţ' => 't',
+        'Ț' | 'Ţ' => 'T',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'É' | 'È' | 'Ê' | 'Ë' => 'E',
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+        'Ó' | 'Ò' | 'Ô' | 'Ö' | 'Õ' => 'O',
+        'ú' | 'ù' | 'û' | 'ü' => 'u',
+        'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
+        '–' | '‘' | '’' | '“' | '”' | '„' | '•' => '-',
+        _ => c,
+    }
+}
+
+fn char_width(c: char, font: Font) -> f32 {
+    if font == Font::Mono {
+        return 600.0;
+    }
+    let table = if font == Font::Bold { &HELVETICA_BOLD_WIDTHS } else { &HELVETICA_WIDTHS };
+    let proxy = width_proxy(c);
+    let width = if (' '..='~').contains(&proxy) {
+        table[proxy as usize - 32]
+    } else {
+        556
+    };
+    width as f32
+}
+
+/// Width of `text` in points.
+pub fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    text.chars().map(|c| char_width(c, font)).sum::<f32>() * size / 1000.0
+}
+
+/// Greedy word wrap; words wider than the line are broken by character.
+/// Explicit newlines start a new line, other whitespace collapses as in HTML.
+fn wrap(text: &str, font: Font, size: f32, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if text_width(&candidate, font, size) <= width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            for c in word.chars() {
+                line.push(c);
+                if line.chars().count() > 1 && text_width(&line, font, size) > width {
+                    line.pop();
+                    lines.push(std::mem::take(&mut line));
+                    line.push(c);
+                }
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn hex_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2 + 2);
+    out.push('<');
+    for c in text.chars() {
+        out.push_str(&format!("{:02X}", encode_char(c)));
+    }
+    out.push('>');
+    out
+}
+
+/// RGB raster ready to be embedded as an image XObject.
+#[derive(Debug, Clone)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+/// Decodes a PNG, flattens transparency onto white and shrinks it so the
+/// longest side is at most `max_side` pixels. Returns width, height and the
+/// RGB bytes.
+pub fn decode_png_rgb(bytes: &[u8], max_side: u32) -> Result<(usize, usize, Vec<u8>), String> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| format!("PNG invalid: {}", e))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| format!("PNG invalid: {}", e))?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err("PNG indexat neexpandat".to_string()),
+    };
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    // Pixel as RGB over a white background
+    let pixel = |x: usize, y: usize| -> [u32; 3] {
+        let p = &buffer[(y * width + x) * channels..][..channels];
+        let (rgb, alpha) = match channels {
+            1 => ([p[0], p[0], p[0]], 255),
+            2 => ([p[0], p[0], p[0]], p[1]),
+            3 => ([p[0], p[1], p[2]], 255),
+            _ => ([p[0], p[1], p[2]], p[3]),
+        };
+        let a = alpha as u32;
+        rgb.map(|v| (v as u32 * a + 255 * (255 - a)) / 255)
+    };
+
+    let factor = (width.max(height) as u32).div_ceil(max_side.max(1)).max(1) as usize;
+    let (out_w, out_h) = ((width / factor).max(1), (height / factor).max(1));
+
+    let mut rgb = Vec::with_capacity(out_w * out_h * 3);
+    for oy in 0..out_h {
+        for ox in 0..out_w {
+            let mut sum = [0u32; 3];
+            let mut count = 0u32;
+            for y in oy * factor..((oy + 1) * factor).min(height) {
+                for x in ox * factor..((ox + 1) * factor).min(width) {
+                    let p = pixel(x, y);
+                    sum[0] += p[0];
+                    sum[1] += p[1];
+                    sum[2] += p[2];
+                    count += 1;
+                }
+            }
+            rgb.extend(sum.map(|s| (s / count.max(1)) as u8));
+        }
+    }
+
+    Ok((out_w, out_h, rgb))
+}
+
+impl Image {
+    /// Decodes a PNG, flattens transparency onto white and shrinks it so the
+    /// longest side is at most `max_side` pixels (the printers are 203 dpi,
+    /// full-size logos only make the file bigger).
+    pub fn from_png(bytes: &[u8], max_side: u32) -> Result<Image, String> {
+        let (out_w, out_h, rgb) = decode_png_rgb(bytes, max_side)?;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rgb).map_err(|e| e.to_string())?;
+        let data = encoder.finish().map_err(|e| e.to_string())?;
+
+        Ok(Image {
+            width: out_w as u32,
+            height: out_h as u32,
+            data,
+        })
+    }
+
+    /// Black and white modules (QR code, barcode), each drawn as a
+    /// `scale` x `scale` pixel square so viewers keep the edges sharp.
+    pub fn from_bitmap(bitmap: &Bitmap, scale: usize) -> Result<Image, String> {
+        let scale = scale.max(1);
+        let (out_w, out_h) = (bitmap.width * scale, bitmap.height * scale);
+        let mut rgb = Vec::with_capacity(out_w * out_h * 3);
+        for y in 0..out_h {
+            for x in 0..out_w {
+                let value = if bitmap.dark[(y / scale) * bitmap.width + x / scale] { 0 } else { 255 };
+                rgb.extend([value; 3]);
+            }
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rgb).map_err(|e| e.to_string())?;
+        let data = encoder.finish().map_err(|e| e.to_string())?;
+
+        Ok(Image {
+            width: out_w as u32,
+            height: out_h as u32,
+            data,
+        })
+    }
+
+    fn aspect(&self) -> f32 {
+        self.height as f32 / self.width as f32
+    }
+}
+
+/// Top-to-bottom layout on fixed-size pages; starts a new page when the
+/// next block does not fit. All positions and sizes in the public API are
+/// in millimetres, font sizes in points.
+pub struct Flow {
+    width: f32,
+    height: f32,
+    margins: [f32; 4],
+    pages: Vec<String>,
+    content: String,
+    y: f32,
+    images: Vec<Image>,
+}
+
+impl Flow {
+    /// `margins_mm` in CSS order: top, right, bottom, left.
+    pub fn new(width_mm: f32, height_mm: f32, margins_mm: [f32; 4]) -> Self {
+        let margins = margins_mm.map(|m| m * MM);
+        Flow {
+            width: width_mm * MM,
+            height: height_mm * MM,
+            margins,
+            pages: Vec::new(),
+            content: String::new(),
+            y: margins[0],
+            images: Vec::new(),
+        }
+    }
+
+    fn left(&self) -> f32 {
+        self.margins[3]
+    }
+
+    fn content_width(&self) -> f32 {
+        self.width - self.margins[1] - self.margins[3]
+    }
+
+    /// Moves to a new page unless `height` points still fit on this one.
+    fn ensure(&mut self, height: f32) {
+        let at_top = self.y <= self.margins[0] + 0.01;
+        if !at_top && self.y + height > self.height - self.margins[2] {
+            self.pages.push(std::mem::take(&mut self.content));
+            self.y = self.margins[0];
+        }
+    }
+
+    fn pdf_y(&self, y: f32) -> f32 {
+        self.height - y
+    }
+
+    fn draw_text(&mut self, text: &str, font: Font, size: f32, x: f32, baseline: f32, underline: bool) {
+        if text.is_empty() {
+            return;
+        }
+        let y = self.pdf_y(baseline);
+        self.content.push_str(&format!(
+            "BT /{} {:.2} Tf {:.2} {:.2} Td {} Tj ET\n",
+            font.resource(),
+            size,
+            x,
+            y,
+            hex_string(text)
+        ));
+        if underline {
+            let w = text_width(text, font, size);
+            let uy = y - size * 0.12;
+            self.content.push_str(&format!(
+                "{:.2} w {:.2} {:.2} m {:.2} {:.2} l S\n",
+                size * 0.06,
+                x,
+                uy,
+                x + w,
+                uy
+            ));
+        }
+    }
+
+    fn aligned_x(text: &str, font: Font, size: f32, x: f32, width: f32, align: Align) -> f32 {
+        let w = text_width(text, font, size);
+        match align {
+            Align::Left => x,
+            Align::Center => x + (width - w) / 2.0,
+            Align::Right => x + width - w,
+        }
+    }
+
+    fn paragraph(&mut self, text: &str, font: Font, size: f32, align: Align, underline: bool) {
+        let line_height = size * LINE_HEIGHT;
+        let (x, width) = (self.left(), self.content_width());
+        for line in wrap(text, font, size, width) {
+            self.ensure(line_height);
+            let lx = Self::aligned_x(&line, font, size, x, width, align);
+            self.draw_text(&line, font, size, lx, self.y + size * BASELINE, underline);
+            self.y += line_height;
+        }
+    }
+
+    /// Wrapped text across the full width; `\n` forces a line break.
+    pub fn text(&mut self, text: &str, font: Font, size: f32, align: Align) {
+        self.paragraph(text, font, size, align, false);
+    }
+
+    pub fn underlined(&mut self, text: &str, font: Font, size: f32, align: Align) {
+        self.paragraph(text, font, size, align, true);
+    }
+
+    /// One table row. Each cell is (text, width in mm, alignment); a width of
+    /// 0 takes whatever the other cells leave. Cells wrap independently and
+    /// the row is as tall as its tallest cell.
+    pub fn columns(&mut self, cells: &[(&str, f32, Align)], font: Font, size: f32) {
+        let fixed: f32 = cells.iter().map(|(_, w, _)| w * MM).sum();
+        let flexible = cells.iter().filter(|(_, w, _)| *w <= 0.0).count().max(1) as f32;
+        let rest = ((self.content_width() - fixed) / flexible).max(0.0);
+
+        let line_height = size * LINE_HEIGHT;
+        let mut x = self.left();
+        let mut laid_out = Vec::new();
+        let mut rows = 1;
+        for (text, w, align) in cells {
+            let width = if *w <= 0.0 { rest } else { w * MM };
+            let lines = wrap(text, font, size, width);
+            rows = rows.max(lines.len());
+            laid_out.push((x, width, *align, lines));
+            x += width;
+        }
+
+        self.ensure(line_height * rows as f32);
+        for (cx, width, align, lines) in laid_out {
+            for (i, line) in lines.iter().enumerate() {
+                let lx = Self::aligned_x(line, font, size, cx, width, align);
+                let baseline = self.y + i as f32 * line_height + size * BASELINE;
+                self.draw_text(line, font, size, lx, baseline, false);
+            }
+        }
+        self.y += line_height * rows as f32;
+    }
+
+    /// Left and right text on the same line (`justify-content: space-between`).
+    pub fn row(&mut self, left: &str, right: &str, font: Font, size: f32) {
+        let right_mm = (text_width(right, font, size) + size * 0.5) / MM;
+        self.columns(&[(left, 0.0, Align::Left), (right, right_mm, Align::Right)], font, size);
+    }
+
+    /// Horizontal rule across the full width.
+    pub fn rule(&mut self, thickness: f32, style: LineStyle) {
+        let pad = 1.5;
+        self.ensure(thickness + pad * 2.0);
+        let y = self.pdf_y(self.y + pad + thickness / 2.0);
+        let dash = match style {
+            LineStyle::Solid => "[] 0 d",
+            LineStyle::Dashed => "[3 2] 0 d",
+            LineStyle::Dotted => "[1 1.5] 0 d",
+        };
+        self.content.push_str(&format!(
+            "q {} {:.2} w {:.2} {:.2} m {:.2} {:.2} l S Q\n",
+            dash,
+            thickness,
+            self.left(),
+            y,
+            self.left() + self.content_width(),
+            y
+        ));
+        self.y += thickness + pad * 2.0;
+    }
+
+    pub fn space(&mut self, mm: f32) {
+        self.y += mm * MM;
+    }
+
+    /// Text centered in a bordered box, e.g. the "ANULAT" stamp.
+    pub fn banner(&mut self, text: &str, size: f32) {
+        let height = size * 1.6;
+        self.ensure(height);
+        let (x, width) = (self.left(), self.content_width());
+        self.content.push_str(&format!(
+            "q 2 w {:.2} {:.2} {:.2} {:.2} re S Q\n",
+            x + 1.0,
+            self.pdf_y(self.y + height - 1.0),
+            width - 2.0,
+            height - 2.0
+        ));
+        let lx = Self::aligned_x(text, Font::Bold, size, x, width, Align::Center);
+        let baseline = self.y + (height - size) / 2.0 + size * BASELINE;
+        self.draw_text(text, Font::Bold, size, lx, baseline, false);
+        self.y += height + 2.0 * MM;
+    }
+
+    fn add_image(&mut self, image: &Image) -> usize {
+        self.images.push(image.clone());
+        self.images.len() - 1
+    }
+
+    fn draw_image(&mut self, index: usize, x: f32, top: f32, width: f32, height: f32) {
+        self.content.push_str(&format!(
+            "q {:.2} 0 0 {:.2} {:.2} {:.2} cm /Im{} Do Q\n",
+            width,
+            height,
+            x,
+            self.pdf_y(top + height),
+            index
+        ));
+    }
+
+    /// Image scaled to `width_mm` (or the full width if narrower), capped at
+    /// `max_height_mm`, keeping its aspect ratio.
+    pub fn image(&mut self, image: &Image, width_mm: f32, max_height_mm: f32, align: Align) {
+        let mut width = (width_mm * MM).min(self.content_width());
+        let mut height = width * image.aspect();
+        if max_height_mm > 0.0 && height > max_height_mm * MM {
+            height = max_height_mm * MM;
+            width = height / image.aspect();
+        }
+
+        self.ensure(height);
+        let x = match align {
+            Align::Left => self.left(),
+            Align::Center => self.left() + (self.content_width() - width) / 2.0,
+            Align::Right => self.left() + self.content_width() - width,
+        };
+        let index = self.add_image(image);
+        self.draw_image(index, x, self.y, width, height);
+        self.y += height;
+    }
+
+    /// Images next to each other in equal-width cells, vertically centered.
+    pub fn image_row(&mut self, images: &[&Image], gap_mm: f32) {
+        if images.is_empty() {
+            return;
+        }
+        let gap = gap_mm * MM;
+        let cell = (self.content_width() - gap * (images.len() - 1) as f32) / images.len() as f32;
+        let row_height = images.iter().map(|i| cell * i.aspect()).fold(0.0, f32::max);
+
+        self.ensure(row_height);
+        let mut x = self.left();
+        for image in images {
+            let height = cell * image.aspect();
+            let index = self.add_image(image);
+            let top = self.y + (row_height - height) / 2.0;
+            self.draw_image(index, x, top, cell, height);
+            x += cell + gap;
+        }
+        self.y += row_height;
+    }
+
+    /// Serializes the document.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.pages.push(std::mem::take(&mut self.content));
+
+        let font_ids = [3, 4, 5];
+        let first_image = 6;
+        let first_page = first_image + self.images.len();
+        let page_ids: Vec<usize> = (0..self.pages.len()).map(|i| first_page + i * 2).collect();
+
+        let mut out: Vec<u8> = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets: Vec<usize> = Vec::new();
+
+        let mut object = |out: &mut Vec<u8>, body: &[u8]| {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", offsets.len()).as_bytes());
+            out.extend_from_slice(body);
+            out.extend_from_slice(b"\nendobj\n");
+        };
+
+        object(&mut out, b"<< /Type /Catalog /Pages 2 0 R >>");
+
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+        object(
+            &mut out,
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), page_ids.len()).as_bytes(),
+        );
+
+        for base_font in ["Helvetica", "Helvetica-Bold", "Courier-Bold"] {
+            object(
+                &mut out,
+                format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding {} >>", base_font, ENCODING).as_bytes(),
+            );
+        }
+
+        for image in &self.images {
+            let mut body = format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+                 /BitsPerComponent 8 /Filter /FlateDecode /Length {} >>\nstream\n",
+                image.width,
+                image.height,
+                image.data.len()
+            )
+            .into_bytes();
+            body.extend_from_slice(&image.data);
+            body.extend_from_slice(b"\nendstream");
+            object(&mut out, &body);
+        }
+
+        let xobjects: Vec<String> = (0..self.images.len())
+            .map(|i| format!("/Im{} {} 0 R", i, first_image + i))
+            .collect();
+
+        for (i, content) in self.pages.iter().enumerate() {
+            let page = format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                 /Resources << /Font << /F1 {} 0 R /F2 {} 0 R /F3 {} 0 R >> /XObject << {} >> >> /Contents {} 0 R >>",
+                self.width,
+                self.height,
+                font_ids[0],
+                font_ids[1],
+                font_ids[2],
+                xobjects.join(" "),
+                page_ids[i] + 1
+            );
+            object(&mut out, page.as_bytes());
+
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend_from_slice(content.as_bytes());
+            stream.extend_from_slice(b"\nendstream");
+            object(&mut out, &stream);
+        }
+
+        let xref_offset = out.len();
+        out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1).as_bytes());
+        for offset in &offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        out.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                offsets.len() + 1,
+                xref_offset
+            )
+            .as_bytes(),
+        );
+
+        out
+    }
+}
+
+/// Byte of the font encoding back to its character, the inverse of
+/// `encode_char` for everything the documents print.
+#[cfg(test)]
+fn decode_byte(byte: u8) -> char {
+    match byte {
+        127 => 'ț',
+        129 => 'Ă',
+        141 => 'ă',
+        143 => 'Ș',
+        144 => 'ș',
+        157 => 'Ț',
+        0x80 => '€',
+        0x84 => '„',
+        0x85 => '…',
+        0x91 => '‘',
+        0x92 => '’',
+        0x93 => '“',
+        0x94 => '”',
+        0x95 => '•',
+        0x96 => '–',
+        0x97 => '—',
+        _ => byte as char,
+    }
+}
+
+/// The text drawn by a document from this module, one entry per `Tj`, in
+/// drawing order. Only understands what `Flow` writes: uncompressed content
+/// streams with hex strings.
+#[cfg(test)]
+pub(crate) fn extract_text(pdf: &[u8]) -> Vec<String> {
+    let content = String::from_utf8_lossy(pdf);
+    let mut lines = Vec::new();
+    for (end, _) in content.match_indices("> Tj") {
+        let Some(start) = content[..end].rfind('<') else {
+            continue;
+        };
+        let hex = &content[start + 1..end];
+        let text = (0..hex.len())
+            .step_by(2)
+            .filter_map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .map(decode_byte)
+            .collect();
+        lines.push(text);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        let mut flow = Flow::new(80.0, 100.0, [3.0, 3.0, 3.0, 3.0]);
+        flow.text("Chitanță nr. 12", Font::Bold, 10.0, Align::Center);
+        flow.row("Încasat:", "150,00 lei", Font::Regular, 9.0);
+        flow.rule(0.75, LineStyle::Dashed);
+        flow.finish()
+    }
+
+    #[test]
+    fn same_input_gives_same_bytes() {
+        assert_eq!(sample(), sample());
+    }
+
+    #[test]
+    fn romanian_letters_round_trip_through_the_encoding() {
+        let text = "ĂăÂâÎîȘșȚț Şş Ţţ €";
+        for c in text.chars().filter(|c| *c != ' ') {
+            let decoded = decode_byte(encode_char(c));
+            let expected = match c {
+                'Ş' => 'Ș',
+                'ş' => 'ș',
+                'Ţ' => 'Ț',
+                'ţ' => 'ț',
+                _ => c,
+            };
+            assert_eq!(decoded, expected, "{}", c);
+        }
+        assert_eq!(encode_char('漢'), b'?');
+    }
+
+    #[test]
+    fn text_is_drawn_in_order() {
+        assert_eq!(extract_text(&sample()), ["Chitanță nr. 12", "Încasat:", "150,00 lei"]);
+    }
+
+    #[test]
+    fn xref_points_at_the_objects() {
+        let pdf = sample();
+        let content = String::from_utf8_lossy(&pdf);
+        let start: usize = content
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|rest| rest.lines().next())
+            .and_then(|offset| offset.parse().ok())
+            .unwrap();
+        assert!(pdf[start..].starts_with(b"xref\n"));
+
+        let entries = content[start..].lines().skip(3).take_while(|line| line.ends_with(" n "));
+        for (i, entry) in entries.enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn long_content_breaks_onto_a_new_page() {
+        let mut flow = Flow::new(80.0, 60.0, [3.0, 3.0, 3.0, 3.0]);
+        for i in 0..40 {
+            flow.text(&format!("Rândul {}", i), Font::Regular, 9.0, Align::Left);
+        }
+        let pdf = flow.finish();
+        let content = String::from_utf8_lossy(&pdf);
+        assert!(content.matches("/Type /Page ").count() > 1);
+        let lines = extract_text(&pdf);
+        assert_eq!(lines.len(), 40);
+        assert_eq!(lines[39], "Rândul 39");
+    }
+}
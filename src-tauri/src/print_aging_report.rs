@@ -0,0 +1,145 @@
+use crate::aging;
+use crate::models::{AgingBuckets, AgingReport, CompanyProfile};
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, CompanyContext, DocType, Images};
+use serde::Serialize;
+
+/// Data for templates/aging_report.html, see templates/README.md.
+#[derive(Serialize)]
+pub struct AgingReportTemplate<'a> {
+    pub company: CompanyContext<'a>,
+    pub images: &'a Images,
+    #[serde(flatten)]
+    pub report: &'a AgingReport,
+    pub group_label: &'static str,
+    pub footer_lines: Vec<String>,
+    pub preview: bool,
+}
+
+pub fn group_label(group_by: &str) -> &'static str {
+    match group_by {
+        aging::BY_LOCATION => "Partener / sediu",
+        aging::BY_AGENT => "Agent",
+        _ => "Partener",
+    }
+}
+
+pub fn aging_report_template<'a>(
+    report: &'a AgingReport,
+    company: &'a CompanyProfile,
+    images: &'a Images,
+) -> AgingReportTemplate<'a> {
+    AgingReportTemplate {
+        company: templates::company_context(company),
+        images,
+        report,
+        group_label: group_label(&report.group_by),
+        footer_lines: templates::footer_lines(DocType::AgingReport, company),
+        preview: false,
+    }
+}
+
+pub fn generate_aging_report_html(report: &AgingReport, company: &CompanyProfile, images: &Images) -> String {
+    log::info!("📄 Generating aging report HTML on {} by {}", report.as_of, report.group_by);
+
+    let ctx = aging_report_template(report, company, images);
+    templates::render(DocType::AgingReport, &ctx)
+}
+
+fn amount(value: f64) -> String {
+    if value.abs() < 0.005 {
+        String::new()
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn bucket_cells(buckets: &AgingBuckets) -> [String; 6] {
+    [
+        amount(buckets.current),
+        amount(buckets.days_1_30),
+        amount(buckets.days_31_60),
+        amount(buckets.days_61_90),
+        amount(buckets.days_over_90),
+        format!("{:.2}", buckets.total),
+    ]
+}
+
+/// Same layout as `generate_aging_report_html`, A4 pages rendered directly to
+/// PDF.
+pub fn generate_aging_report_pdf(report: &AgingReport, company: &CompanyProfile, logo: Option<&Image>) -> Vec<u8> {
+    let mut flow = Flow::new(210.0, 297.0, [10.0, 10.0, 10.0, 10.0]);
+
+    flow.text(
+        &format!("{}\nCIF: {}   Reg. Com.: {}", company.name, company.cif, company.reg_com),
+        Font::Regular,
+        9.0,
+        Align::Left,
+    );
+    flow.space(4.0);
+    flow.text("VECHIMEA CREANȚELOR", Font::Bold, 14.0, Align::Center);
+    flow.text(&format!("la data de {}", report.as_of), Font::Regular, 10.0, Align::Center);
+    flow.space(4.0);
+
+    flow.columns(
+        &[
+            (group_label(&report.group_by), 0.0, Align::Left),
+            ("Zile", 10.0, Align::Right),
+            ("Curent", 20.0, Align::Right),
+            ("1-30", 20.0, Align::Right),
+            ("31-60", 20.0, Align::Right),
+            ("61-90", 20.0, Align::Right),
+            ("> 90", 20.0, Align::Right),
+            ("Total", 22.0, Align::Right),
+        ],
+        Font::Bold,
+        8.5,
+    );
+    flow.rule(0.75, LineStyle::Solid);
+    for row in &report.rows {
+        let days = if row.max_days_overdue > 0 { row.max_days_overdue.to_string() } else { String::new() };
+        let cells = bucket_cells(&row.buckets);
+        flow.columns(
+            &[
+                (row.name.as_str(), 0.0, Align::Left),
+                (days.as_str(), 10.0, Align::Right),
+                (cells[0].as_str(), 20.0, Align::Right),
+                (cells[1].as_str(), 20.0, Align::Right),
+                (cells[2].as_str(), 20.0, Align::Right),
+                (cells[3].as_str(), 20.0, Align::Right),
+                (cells[4].as_str(), 20.0, Align::Right),
+                (cells[5].as_str(), 22.0, Align::Right),
+            ],
+            Font::Regular,
+            8.5,
+        );
+    }
+    flow.rule(0.75, LineStyle::Solid);
+    let totals = bucket_cells(&report.totals);
+    flow.columns(
+        &[
+            ("TOTAL", 0.0, Align::Left),
+            ("", 10.0, Align::Right),
+            (totals[0].as_str(), 20.0, Align::Right),
+            (totals[1].as_str(), 20.0, Align::Right),
+            (totals[2].as_str(), 20.0, Align::Right),
+            (totals[3].as_str(), 20.0, Align::Right),
+            (totals[4].as_str(), 20.0, Align::Right),
+            (totals[5].as_str(), 22.0, Align::Right),
+        ],
+        Font::Bold,
+        8.5,
+    );
+    flow.text(&format!("Generat la {}", report.generated_at), Font::Regular, 8.0, Align::Left);
+
+    flow.space(10.0);
+    if let Some(logo) = logo {
+        flow.image(logo, 40.0, 0.0, Align::Center);
+    }
+    let footer = templates::footer_lines(DocType::AgingReport, company);
+    if !footer.is_empty() {
+        flow.text(&footer.join("\n"), Font::Regular, 7.5, Align::Center);
+    }
+
+    flow.finish()
+}
@@ -0,0 +1,172 @@
+use crate::models::{BalanceConfirmation, CompanyProfile};
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, CompanyContext, DocType, Images};
+use serde::Serialize;
+
+/// Data for templates/balance_confirmation.html, see templates/README.md.
+#[derive(Serialize)]
+pub struct BalanceConfirmationTemplate<'a> {
+    pub company: CompanyContext<'a>,
+    pub images: &'a Images,
+    #[serde(flatten)]
+    pub confirmation: &'a BalanceConfirmation,
+    pub footer_lines: Vec<String>,
+    pub preview: bool,
+}
+
+pub fn balance_confirmation_template<'a>(
+    confirmation: &'a BalanceConfirmation,
+    company: &'a CompanyProfile,
+    images: &'a Images,
+) -> BalanceConfirmationTemplate<'a> {
+    BalanceConfirmationTemplate {
+        company: templates::company_context(company),
+        images,
+        confirmation,
+        footer_lines: templates::footer_lines(DocType::BalanceConfirmation, company),
+        preview: false,
+    }
+}
+
+pub fn generate_balance_confirmation_html(
+    confirmation: &BalanceConfirmation,
+    company: &CompanyProfile,
+    images: &Images,
+) -> String {
+    log::info!(
+        "📄 Generating balance confirmation HTML for {} on {}",
+        confirmation.partner_name,
+        confirmation.reference_date
+    );
+
+    let ctx = balance_confirmation_template(confirmation, company, images);
+    templates::render(DocType::BalanceConfirmation, &ctx)
+}
+
+/// Same letter as `generate_balance_confirmation_html`, an A4 page rendered
+/// directly to PDF.
+pub fn generate_balance_confirmation_pdf(
+    confirmation: &BalanceConfirmation,
+    company: &CompanyProfile,
+    logo: Option<&Image>,
+) -> Vec<u8> {
+    let mut flow = Flow::new(210.0, 297.0, [15.0, 15.0, 15.0, 15.0]);
+
+    flow.text(
+        &format!("{}\nCIF: {}   Reg. Com.: {}\n{}", company.name, company.cif, company.reg_com, company.address),
+        Font::Regular,
+        9.0,
+        Align::Left,
+    );
+    flow.space(4.0);
+
+    let mut partner = format!("Către: {}", confirmation.partner_name);
+    if let Some(cif) = &confirmation.partner_cif {
+        partner.push_str(&format!("\nCIF: {}", cif));
+    }
+    if let Some(reg_com) = &confirmation.partner_reg_com {
+        partner.push_str(&format!("   Reg. Com.: {}", reg_com));
+    }
+    if let Some(address) = &confirmation.partner_address {
+        partner.push_str(&format!("\n{}", address));
+    }
+    flow.text(&partner, Font::Regular, 9.0, Align::Right);
+    flow.space(6.0);
+
+    flow.text("CONFIRMARE DE SOLD", Font::Bold, 14.0, Align::Center);
+    flow.text(&format!("la data de {}", confirmation.reference_date), Font::Regular, 10.0, Align::Center);
+    flow.space(4.0);
+    flow.text(
+        &format!(
+            "Vă rugăm să confirmați că, la data de {}, în evidențele noastre figurați cu un sold de {:.2} RON \
+             de plată, reprezentând documentele de mai jos.",
+            confirmation.reference_date, confirmation.balance
+        ),
+        Font::Regular,
+        10.0,
+        Align::Left,
+    );
+    flow.space(3.0);
+
+    flow.columns(
+        &[
+            ("Nr.", 10.0, Align::Left),
+            ("Tip document", 0.0, Align::Left),
+            ("Document", 30.0, Align::Left),
+            ("Data", 22.0, Align::Left),
+            ("Scadență", 22.0, Align::Left),
+            ("Valoare", 24.0, Align::Right),
+            ("Rest de plată", 26.0, Align::Right),
+        ],
+        Font::Bold,
+        9.0,
+    );
+    flow.rule(0.75, LineStyle::Solid);
+    for (idx, document) in confirmation.documents.iter().enumerate() {
+        let number = (idx + 1).to_string();
+        let valoare = format!("{:.2}", document.valoare);
+        let rest = format!("{:.2}", document.rest);
+        flow.columns(
+            &[
+                (number.as_str(), 10.0, Align::Left),
+                (document.tip_document.as_str(), 0.0, Align::Left),
+                (document.document.as_str(), 30.0, Align::Left),
+                (document.data.as_deref().unwrap_or_default(), 22.0, Align::Left),
+                (document.termen.as_deref().unwrap_or_default(), 22.0, Align::Left),
+                (valoare.as_str(), 24.0, Align::Right),
+                (rest.as_str(), 26.0, Align::Right),
+            ],
+            Font::Regular,
+            9.0,
+        );
+    }
+    flow.rule(0.75, LineStyle::Solid);
+    flow.row("SOLD", &format!("{:.2} RON", confirmation.balance), Font::Bold, 10.0);
+    flow.space(4.0);
+    flow.text(
+        "Vă rugăm să ne returnați un exemplar al acestei scrisori, semnat și ștampilat, în termen de 15 zile \
+         de la primire. Dacă soldul din evidențele dumneavoastră diferă, vă rugăm să indicați soldul și \
+         documentele care explică diferența.",
+        Font::Regular,
+        9.0,
+        Align::Left,
+    );
+    flow.space(6.0);
+    flow.text("Emitent,\nsemnătura și ștampila", Font::Regular, 9.0, Align::Left);
+
+    flow.space(10.0);
+    flow.rule(0.5, LineStyle::Dashed);
+    flow.text("Se completează de client", Font::Bold, 10.0, Align::Left);
+    flow.space(2.0);
+    flow.text(
+        &format!(
+            "[  ] Confirmăm soldul de {:.2} RON la data de {}.\n\n\
+             [  ] Nu confirmăm soldul. Soldul din evidențele noastre este de ____________ RON.\n\
+             Explicații: ______________________________________________________________",
+            confirmation.balance, confirmation.reference_date
+        ),
+        Font::Regular,
+        10.0,
+        Align::Left,
+    );
+    flow.space(8.0);
+    flow.columns(
+        &[
+            ("Data: ______________", 0.0, Align::Left),
+            ("Semnătura și ștampila clientului", 0.0, Align::Right),
+        ],
+        Font::Regular,
+        9.0,
+    );
+
+    flow.space(10.0);
+    if let Some(logo) = logo {
+        flow.image(logo, 40.0, 0.0, Align::Center);
+    }
+    let footer = templates::footer_lines(DocType::BalanceConfirmation, company);
+    if !footer.is_empty() {
+        flow.text(&footer.join("\n"), Font::Regular, 7.5, Align::Center);
+    }
+
+    flow.finish()
+}
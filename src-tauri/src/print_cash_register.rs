@@ -0,0 +1,181 @@
+use crate::models::{CashRegisterDay, CompanyProfile};
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, CompanyContext, DocType, Images};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct CashRegisterRow<'a> {
+    pub index: usize,
+    pub time: &'a str,
+    pub document: &'a str,
+    pub description: &'a str,
+    pub receipt: f64,
+    pub payment: f64,
+}
+
+/// Data for templates/cash_register.html, see templates/README.md.
+#[derive(Serialize)]
+pub struct CashRegisterTemplate<'a> {
+    pub company: CompanyContext<'a>,
+    pub images: &'a Images,
+    pub nume_casa: &'a str,
+    pub date: &'a str,
+    pub page_number: Option<i64>,
+    pub closed: bool,
+    pub opening_balance: f64,
+    pub rows: Vec<CashRegisterRow<'a>>,
+    pub total_receipts: f64,
+    pub total_payments: f64,
+    pub closing_balance: f64,
+    pub footer_lines: Vec<String>,
+    pub preview: bool,
+}
+
+pub fn cash_register_template<'a>(
+    page: &'a CashRegisterDay,
+    company: &'a CompanyProfile,
+    images: &'a Images,
+) -> CashRegisterTemplate<'a> {
+    CashRegisterTemplate {
+        company: templates::company_context(company),
+        images,
+        nume_casa: &page.nume_casa,
+        date: &page.day,
+        page_number: page.page_number,
+        closed: page.closed_at.is_some(),
+        opening_balance: page.opening_balance,
+        rows: page
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| CashRegisterRow {
+                index: idx + 1,
+                time: &line.time,
+                document: &line.document,
+                description: &line.description,
+                receipt: line.receipt,
+                payment: line.payment,
+            })
+            .collect(),
+        total_receipts: page.total_receipts,
+        total_payments: page.total_payments,
+        closing_balance: page.closing_balance,
+        footer_lines: templates::footer_lines(DocType::CashRegister, company),
+        preview: false,
+    }
+}
+
+pub fn generate_cash_register_html(page: &CashRegisterDay, company: &CompanyProfile, images: &Images) -> String {
+    log::info!("📄 Generating cash register HTML for {} on {}", page.nume_casa, page.day);
+
+    let ctx = cash_register_template(page, company, images);
+    templates::render(DocType::CashRegister, &ctx)
+}
+
+fn amount(value: f64) -> String {
+    if value.abs() < 0.005 {
+        String::new()
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// Same layout as `generate_cash_register_html`, an A4 page rendered directly
+/// to PDF.
+pub fn generate_cash_register_pdf(page: &CashRegisterDay, company: &CompanyProfile, logo: Option<&Image>) -> Vec<u8> {
+    let mut flow = Flow::new(210.0, 297.0, [12.0, 12.0, 12.0, 12.0]);
+
+    flow.text(
+        &format!("{}\nCIF: {}   Reg. Com.: {}\n{}", company.name, company.cif, company.reg_com, company.address),
+        Font::Regular,
+        9.0,
+        Align::Left,
+    );
+    flow.space(4.0);
+    flow.text("REGISTRU DE CASĂ", Font::Bold, 14.0, Align::Center);
+    let page_number = page.page_number.map(|number| format!("   Fila nr. {}", number)).unwrap_or_default();
+    flow.text(
+        &format!("Casa: {}   Data: {}{}", page.nume_casa, page.day, page_number),
+        Font::Regular,
+        10.0,
+        Align::Center,
+    );
+    if page.closed_at.is_none() {
+        flow.text("(zi neînchisă - document provizoriu)", Font::Regular, 8.0, Align::Center);
+    }
+    flow.space(4.0);
+
+    flow.columns(
+        &[
+            ("Nr.", 10.0, Align::Left),
+            ("Ora", 14.0, Align::Left),
+            ("Document", 36.0, Align::Left),
+            ("Explicații", 0.0, Align::Left),
+            ("Încasări", 26.0, Align::Right),
+            ("Plăți", 26.0, Align::Right),
+        ],
+        Font::Bold,
+        9.0,
+    );
+    flow.rule(0.75, LineStyle::Solid);
+    flow.columns(
+        &[
+            ("", 60.0, Align::Left),
+            ("Sold din ziua precedentă", 0.0, Align::Left),
+            (&format!("{:.2}", page.opening_balance), 26.0, Align::Right),
+            ("", 26.0, Align::Right),
+        ],
+        Font::Regular,
+        9.0,
+    );
+
+    for (idx, line) in page.lines.iter().enumerate() {
+        let number = (idx + 1).to_string();
+        let receipt = amount(line.receipt);
+        let payment = amount(line.payment);
+        flow.columns(
+            &[
+                (number.as_str(), 10.0, Align::Left),
+                (line.time.as_str(), 14.0, Align::Left),
+                (line.document.as_str(), 36.0, Align::Left),
+                (line.description.as_str(), 0.0, Align::Left),
+                (receipt.as_str(), 26.0, Align::Right),
+                (payment.as_str(), 26.0, Align::Right),
+            ],
+            Font::Regular,
+            9.0,
+        );
+    }
+
+    flow.rule(0.75, LineStyle::Solid);
+    let total_receipts = format!("{:.2}", page.total_receipts);
+    let total_payments = format!("{:.2}", page.total_payments);
+    flow.columns(
+        &[
+            ("TOTAL ZI", 0.0, Align::Left),
+            (total_receipts.as_str(), 26.0, Align::Right),
+            (total_payments.as_str(), 26.0, Align::Right),
+        ],
+        Font::Bold,
+        9.0,
+    );
+    flow.row("SOLD FINAL", &format!("{:.2} RON", page.closing_balance), Font::Bold, 10.0);
+
+    flow.space(12.0);
+    flow.columns(
+        &[("Casier,", 0.0, Align::Left), ("Compartiment financiar-contabil,", 0.0, Align::Right)],
+        Font::Regular,
+        9.0,
+    );
+
+    flow.space(10.0);
+    if let Some(logo) = logo {
+        flow.image(logo, 40.0, 0.0, Align::Center);
+    }
+    let footer = templates::footer_lines(DocType::CashRegister, company);
+    if !footer.is_empty() {
+        flow.text(&footer.join("\n"), Font::Regular, 7.5, Align::Center);
+    }
+
+    flow.finish()
+}
@@ -1,210 +1,236 @@
-use crate::models::{Invoice};
-use crate::print_invoice::KARIN;
+use crate::escpos::{Options, Raster, Ticket};
+use crate::models::{Collection, CompanyProfile, Invoice};
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, CompanyContext, DocType, Images};
+use serde::Serialize;
+
+// Last 8 characters of the invoice id, as shown in the DOC column
+fn short_id(id: &str) -> &str {
+    if id.len() > 8 {
+        &id[id.len() - 8..]
+    } else {
+        id
+    }
+}
+
+#[derive(Serialize)]
+pub struct DailyReportRow<'a> {
+    pub index: usize,
+    pub document: &'a str,
+    pub partner: &'a str,
+    pub amount: f64,
+}
+
+/// Data for templates/daily_report.html, see templates/README.md.
+#[derive(Serialize)]
+pub struct DailyReportTemplate<'a> {
+    pub company: CompanyContext<'a>,
+    pub images: &'a Images,
+    pub date: &'a str,
+    pub rows: Vec<DailyReportRow<'a>>,
+    pub total: f64,
+    pub footer_lines: Vec<String>,
+    pub preview: bool,
+}
+
+pub fn daily_report_template<'a>(
+    invoices: &'a [Invoice],
+    date: &'a str,
+    total_sales: f64,
+    company: &'a CompanyProfile,
+    images: &'a Images,
+) -> DailyReportTemplate<'a> {
+    DailyReportTemplate {
+        company: templates::company_context(company),
+        images,
+        date,
+        rows: invoices
+            .iter()
+            .enumerate()
+            .map(|(idx, inv)| DailyReportRow {
+                index: idx + 1,
+                document: short_id(&inv.id),
+                partner: &inv.partner_name,
+                amount: inv.total_amount,
+            })
+            .collect(),
+        total: total_sales,
+        footer_lines: templates::footer_lines(DocType::DailyReport, company),
+        preview: false,
+    }
+}
 
 pub fn generate_daily_report_html(
     invoices: &[Invoice],
     date: &str,
     total_sales: f64,
-    logo_base64: Option<&str>,
+    company: &CompanyProfile,
+    images: &Images,
 ) -> String {
     log::info!("📄 Generating daily sales report HTML for date: {}", date);
 
-    let rows_html = invoices
-        .iter()
-        .enumerate()
-        .map(|(idx, inv)| {
-            // Extract last 8 chars of ID for display
-            let short_id = if inv.id.len() > 8 {
-                &inv.id[inv.id.len() - 8..]
-            } else {
-                &inv.id
-            };
-            format!(
-                r#"
-                <div class="report-row">
-                    <div class="col-idx">{}</div>
-                    <div class="col-inv">{}</div>
-                    <div class="col-partner">{}</div>
-                    <div class="col-amount">{:.2}</div>
-                </div>
-                "#,
-                idx + 1,
-                short_id,
-                inv.partner_name,
-                inv.total_amount
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    format!(
-        r#"<!DOCTYPE html>
-<html lang="ro">
-<head>
-    <meta charset="UTF-8">
-    <title>RAPORT ZILNIC - {}</title>
-    <style>
-        @media print {{
-            @page {{
-                size: 80mm 297mm;
-                margin: 2mm;
-            }}
-            body {{ 
-                margin: 0; 
-                padding: 0; 
-            }}
-            header, footer {{ 
-                display: none; 
-            }}
-        }}
-
-        html {{
-            height: 100%;
-        }}
-
-        body {{
-            font-family: 'Courier New', Courier, monospace;
-            width: 76mm;
-            margin: 0;
-            padding: 1mm;
-            font-size: 9.5px;
-            font-weight: bold;
-            color: #000000;
-            line-height: 1.15;
-            background: white;
-            box-sizing: border-box;
-            overflow-wrap: anywhere;
-        }}
-
-        h1 {{
-            font-size: 12px;
-            text-align: center;
-            margin: 3px 0;
-            text-transform: uppercase;
-            border-bottom: 1px dashed #000;
-            padding-bottom: 3px;
-        }}
-
-        .header-section {{
-            text-align: center;
-            margin-bottom: 6px;
-            border-bottom: 1px dashed #000;
-            padding-bottom: 3px;
-            font-size: 9px;
-            line-height: 1.1;
-        }}
-
-        .report-section {{
-            margin-top: 6px;
-        }}
-
-        .report-header {{
-            display: flex;
-            border-bottom: 1px solid #000;
-            padding-bottom: 2px;
-            margin-bottom: 3px;
-            font-size: 8.5px;
-        }}
-
-        .report-row {{
-            display: flex;
-            margin-bottom: 2px;
-            font-size: 9px;
-            align-items: flex-start;
-        }}
-
-        .col-idx {{ width: 4mm; flex: 0 0 4mm; }}
-        .col-inv {{ width: 15mm; flex: 0 0 15mm; }}
-        .col-partner {{ flex: 1; min-width: 0; word-break: break-word; overflow-wrap: anywhere; padding-right: 1mm; }}
-        .col-amount {{ width: 14mm; flex: 0 0 14mm; text-align: right; white-space: nowrap; }}
-
-        .total-section {{
-            margin-top: 6px;
-            border-top: 2px dashed #000;
-            padding-top: 3px;
-            text-align: right;
-            font-size: 11px;
-        }}
-
-        .footer-branding {{
-            text-align: center;
-            font-size: 8.5px;
-            margin-top: 10px;
-            font-style: italic;
-        }}
-
-        .footer-logo {{
-            width: 100%;
-            max-width: 66mm;
-            height: auto;
-            display: block;
-            margin: 0 auto 5px auto;
-        }}
-    </style>
-</head>
-<body>
-
-    <div class="header-section">
-        {}<br>
-        CIF: {}<br>
-        {}<br>
-        DATA: {}
-    </div>
-
-    <h1>RAPORT VANZARI ZILNIC</h1>
-    
-    <div class="report-section">
-        <div class="report-header">
-            <div class="col-idx">#</div>
-            <div class="col-inv">DOC</div>
-            <div class="col-partner">CLIENT</div>
-            <div class="col-amount">VAL</div>
-        </div>
-        
-        {}
-    </div>
-
-    <div class="total-section">
-        TOTAL VANZARI:<br>
-        {:.2} RON
-    </div>
-
-    <div class="footer-branding">
-        {}
-        <br>
-        printed by eSoft
-    </div>
-
-    <script>
-        function triggerPrint() {{
-            window.print();
-        }}
-        
-        if (document.readyState === 'loading') {{
-            document.addEventListener('DOMContentLoaded', function() {{
-                setTimeout(triggerPrint, 300);
-            }});
-        }} else {{
-            triggerPrint();
-        }}
-        
-        window.addEventListener('load', function() {{
-            setTimeout(triggerPrint, 100);
-        }});
-    </script>
-</body>
-</html>"#,
-        date,
-        KARIN.name,
-        KARIN.cif,
-        KARIN.address,
-        date,
-        rows_html,
-        total_sales,
-        if let Some(logo) = logo_base64 {
-            format!(r#"<img src="{}" class="footer-logo" alt="Logo" />"#, logo)
-        } else {
-            String::new()
-        }
-    )
+    let ctx = daily_report_template(invoices, date, total_sales, company, images);
+    templates::render(DocType::DailyReport, &ctx)
+}
+
+/// Same layout as `generate_daily_report_html`, rendered directly to PDF.
+pub fn generate_daily_report_pdf(
+    invoices: &[Invoice],
+    date: &str,
+    total_sales: f64,
+    company: &CompanyProfile,
+    logo: Option<&Image>,
+) -> Vec<u8> {
+    let mut flow = Flow::new(80.0, 297.0, [2.0, 3.0, 2.0, 3.0]);
+
+    flow.text(
+        &format!("{}\nCIF: {}\n{}\nDATA: {}", company.name, company.cif, company.address, date),
+        Font::Mono,
+        6.75,
+        Align::Center,
+    );
+    flow.rule(0.75, LineStyle::Dashed);
+    flow.text("RAPORT VANZARI ZILNIC", Font::Mono, 9.0, Align::Center);
+    flow.rule(0.75, LineStyle::Dashed);
+
+    flow.space(1.5);
+    flow.columns(
+        &[
+            ("#", 4.0, Align::Left),
+            ("DOC", 15.0, Align::Left),
+            ("CLIENT", 0.0, Align::Left),
+            ("VAL", 14.0, Align::Right),
+        ],
+        Font::Mono,
+        6.4,
+    );
+    flow.rule(0.75, LineStyle::Solid);
+
+    for (idx, inv) in invoices.iter().enumerate() {
+        let number = (idx + 1).to_string();
+        let amount = format!("{:.2}", inv.total_amount);
+        flow.columns(
+            &[
+                (number.as_str(), 4.0, Align::Left),
+                (short_id(&inv.id), 15.0, Align::Left),
+                (inv.partner_name.as_str(), 0.0, Align::Left),
+                (amount.as_str(), 14.0, Align::Right),
+            ],
+            Font::Mono,
+            6.75,
+        );
+    }
+
+    flow.space(1.5);
+    flow.rule(1.5, LineStyle::Dashed);
+    flow.text(
+        &format!("TOTAL VANZARI:\n{:.2} RON", total_sales),
+        Font::Mono,
+        8.25,
+        Align::Right,
+    );
+
+    flow.space(3.0);
+    if let Some(logo) = logo {
+        flow.image(logo, 66.0, 0.0, Align::Center);
+    }
+    let footer = templates::footer_lines(DocType::DailyReport, company);
+    if !footer.is_empty() {
+        flow.text(&footer.join("\n"), Font::Mono, 6.4, Align::Center);
+    }
+
+    flow.finish()
+}
+
+/// Daily collections report for a thermal printer (ESC/POS): one line per
+/// receipt row, with the total collected.
+pub fn generate_collections_report_escpos(
+    collections: &[Collection],
+    date: &str,
+    total_collected: f64,
+    company: &CompanyProfile,
+    logo: Option<&Raster>,
+    options: &Options,
+) -> Vec<u8> {
+    let mut ticket = Ticket::new(options);
+    let doc_width = if options.line_chars() > 32 { 14 } else { 10 };
+
+    ticket.align(Align::Center);
+    ticket.text(&format!("{}\nCIF: {}\n{}\nDATA: {}", company.name, company.cif, company.address, date));
+    ticket.rule('-');
+    ticket.bold(true);
+    ticket.text("RAPORT INCASARI ZILNIC");
+    ticket.bold(false);
+    ticket.align(Align::Left);
+    ticket.rule('-');
+
+    ticket.columns(&[
+        ("#", 3, Align::Left),
+        ("CHITANTA", doc_width, Align::Left),
+        ("CLIENT", 0, Align::Left),
+        ("VAL", 10, Align::Right),
+    ]);
+    ticket.rule('-');
+
+    for (idx, collection) in collections.iter().enumerate() {
+        let number = (idx + 1).to_string();
+        let receipt = format!(
+            "{}{}",
+            collection.receipt_series.as_deref().unwrap_or(""),
+            collection
+                .receipt_number
+                .as_deref()
+                .unwrap_or_else(|| short_id(&collection.id))
+        );
+        let partner = format!("{} ", collection.partner_name.as_deref().unwrap_or("N/A"));
+        let amount = format!("{:.2}", collection.valoare);
+        ticket.columns(&[
+            (number.as_str(), 3, Align::Left),
+            (receipt.as_str(), doc_width, Align::Left),
+            (partner.as_str(), 0, Align::Left),
+            (amount.as_str(), 10, Align::Right),
+        ]);
+    }
+
+    ticket.rule('=');
+    ticket.bold(true);
+    ticket.row(&format!("TOTAL ({} incasari):", collections.len()), &format!("{:.2} RON", total_collected));
+    ticket.bold(false);
+    ticket.feed(1);
+
+    if let Some(logo) = logo {
+        ticket.raster(logo);
+    }
+    ticket.align(Align::Center);
+    for line in templates::footer_lines(DocType::DailyReport, company) {
+        ticket.text(&line);
+    }
+    ticket.align(Align::Left);
+
+    ticket.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::company;
+    use crate::pdf::extract_text;
+    use crate::test_fixtures;
+
+    #[test]
+    fn pdf_lists_invoices_and_total() {
+        let mut second = test_fixtures::invoice();
+        second.id = "9f00aa11-0000-4000-8000-000000000000".to_string();
+        second.partner_name = "Alimentara Bucovina".to_string();
+        second.total_amount = 20.3;
+        let invoices = [test_fixtures::invoice(), second];
+
+        let pdf = generate_daily_report_pdf(&invoices, "02.03.2026", 175.0, &company::builtin(), None);
+        assert_eq!(pdf, generate_daily_report_pdf(&invoices, "02.03.2026", 175.0, &company::builtin(), None));
+
+        let text = extract_text(&pdf);
+        assert_eq!(text[4], "RAPORT VANZARI ZILNIC");
+        assert_eq!(text[9..17], ["1", "2e3f4a5b", "Ștefănescu Țară SRL", "154.70", "2", "00000000", "Alimentara Bucovina", "20.30"]);
+        assert_eq!(text[17..19], ["TOTAL VANZARI:", "175.00 RON"]);
+    }
 }
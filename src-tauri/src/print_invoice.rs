@@ -1,414 +1,494 @@
-use crate::models::{Invoice, InvoiceItem};
-
-pub struct CompanyInfo {
-    pub name: &'static str,
-    pub cif: &'static str,
-    pub reg_com: &'static str,
-    pub address: &'static str,
-    pub localitate: &'static str,
-    pub cod_postal: &'static str,
-    pub bank_name: &'static str,
-    pub bank_account: &'static str,
-    pub capital: &'static str,
+use crate::codes::DocumentCode;
+use crate::escpos::{Options, Raster, Ticket};
+use crate::models::{CompanyProfile, Invoice, InvoiceItem, InvoiceStatus};
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, CompanyContext, DocType, Images};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct InvoicePartner<'a> {
+    pub name: &'a str,
+    pub cif: Option<&'a str>,
+    pub reg_com: Option<&'a str>,
+    pub location: &'a str,
+    pub address: Option<&'a str>,
 }
 
-// KARIN company details
-pub const KARIN: CompanyInfo = CompanyInfo {
-    name: "KARIN SRL",
-    cif: "RO5379259",
-    reg_com: "J24/380/1994",
-    address: "Str. Nicolae Balcescu 43",
-    localitate: "Seini, Jud. Maramures",
-    cod_postal: "435500",
-    bank_name: "Banca Transilvania",
-    bank_account: "RO03BTRL02501202L70970XX",
-    capital: "200020 RON",
-};
+#[derive(Serialize)]
+pub struct InvoiceLine<'a> {
+    pub index: usize,
+    pub name: &'a str,
+    pub quantity: i64,
+    pub unit: &'a str,
+    pub unit_price: f64,
+    /// Without VAT
+    pub total: f64,
+    pub vat_percent: f64,
+    pub vat: f64,
+}
 
-pub fn generate_invoice_html(
-    invoice: &Invoice,
-    items: &[InvoiceItem],
-    invoice_number: i64,
-    logo_base64: Option<&str>,
-    payment_term_days: i64,
-    delegate_name: Option<&str>,
-    delegate_act: Option<&str>,
-    car_number: Option<&str>,
-    carnet_series: &str,
-) -> String {
-    log::info!("📄 Generating invoice HTML with payment_term_days: {} for partner: '{}'", 
-        payment_term_days, invoice.partner_name);
-    
-    let due_date = calculate_due_date(&invoice.created_at, payment_term_days);
+/// Data for templates/invoice.html, see templates/README.md.
+#[derive(Serialize)]
+pub struct InvoiceTemplate<'a> {
+    pub company: CompanyContext<'a>,
+    pub images: &'a Images,
+    pub series: &'a str,
+    pub number: i64,
+    pub issue_date: String,
+    pub due_date: String,
+    pub partner: InvoicePartner<'a>,
+    pub items: Vec<InvoiceLine<'a>>,
+    pub total_without_vat: f64,
+    pub total_vat: f64,
+    pub total: f64,
+    pub car_number: Option<&'a str>,
+    pub delegate_name: Option<&'a str>,
+    pub delegate_act: Option<&'a str>,
+    pub footer_lines: Vec<String>,
+    pub preview: bool,
+}
+
+/// What an invoice prints besides the invoice and its lines: the carnet
+/// number, the payment term and the delivery details.
+#[derive(Clone, Copy)]
+pub struct InvoiceDetails<'a> {
+    pub series: &'a str,
+    pub number: i64,
+    pub payment_term_days: i64,
+    pub delegate_name: Option<&'a str>,
+    pub delegate_act: Option<&'a str>,
+    pub car_number: Option<&'a str>,
+    /// Reprint, marked DUPLICAT (the HTML copy is marked by `mark_duplicate`)
+    pub duplicate: bool,
+}
+
+pub fn invoice_template<'a>(
+    invoice: &'a Invoice,
+    items: &'a [InvoiceItem],
+    company: &'a CompanyProfile,
+    images: &'a Images,
+    details: InvoiceDetails<'a>,
+) -> InvoiceTemplate<'a> {
+    let due_date = calculate_due_date(&invoice.created_at, details.payment_term_days);
     log::info!("📄 Calculated due date: {} (created: {}, +{} days)", 
-        due_date, invoice.created_at, payment_term_days);
-    
-    // Calculate total TVA by summing individual product TVAs
-    let mut total_without_vat = 0.0;
-    let mut total_vat = 0.0;
-    
-    let products_html = items
+        due_date, invoice.created_at, details.payment_term_days);
+
+    let lines: Vec<InvoiceLine> = items
         .iter()
         .enumerate()
         .map(|(idx, item)| {
             // Use product's TVA or default to 19%
-            let vat_rate = item.tva_percent.unwrap_or(19.0) / 100.0;
-            
+            let vat_percent = item.tva_percent.unwrap_or(19.0);
+
             // Calculate TVA as percentage of price (prices are without VAT)
-            let item_vat = (item.total_price * vat_rate * 100.0).round() / 100.0;
-            
-            total_without_vat += item.total_price;
-            total_vat += item_vat;
-            
-            let tva_display = item.tva_percent
-                .map(|t| format!("TVA: {:.0}%", t))
-                .unwrap_or_else(|| "TVA: 19%".to_string());
-            
-            format!(
-                r#"        <div class="product-item">
-            <span class="prod-name">{}. {}</span>
-            <div class="prod-math">
-                <span>{} {} x {:.2}</span>
-                <span>= {:.2}</span>
-            </div>
-            <div class="prod-vat-row">
-                <span class="tva-percent">{}</span>
-                <span class="tva-value">Valoare TVA: {:.2} RON</span>
-            </div>
-        </div>"#,
-                idx + 1,
-                item.product_name,
-                item.quantity as i32,
-                item.unit_of_measure,
-                item.unit_price,
-                item.total_price,
-                tva_display,
-                item_vat
-            )
+            let vat_rate = vat_percent / 100.0;
+            let vat = (item.total_price * vat_rate * 100.0).round() / 100.0;
+
+            InvoiceLine {
+                index: idx + 1,
+                name: &item.product_name,
+                quantity: item.quantity as i64,
+                unit: &item.unit_of_measure,
+                unit_price: item.unit_price,
+                total: item.total_price,
+                vat_percent,
+                vat,
+            }
         })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    format!(
-        r#"<!DOCTYPE html>
-<html lang="ro">
-<head>
-    <meta charset="UTF-8">
-    <title>Factura KARIN</title>
-    <style>
-        @media print {{
-            @page {{
-                size: 80mm 297mm;
-                margin: 3mm 6mm 3mm 0.5mm;
-            }}
-            body {{ 
-                margin: 0; 
-                padding: 0; 
-            }}
-            header, footer {{ 
-                display: none; 
-            }}
-        }}
-
-        html {{
-            height: 100%;
-        }}
-
-        body {{
-            font-family: Arial, Helvetica, sans-serif;
-            width: 68mm;
-            margin: 0 auto;
-            padding: 2mm;
-            font-size: 10.5px;
-            font-weight: bold;
-            color: #000000;
-            line-height: 1.15;
-           background: white;
-            box-sizing: border-box;
-        }}
-
-        h1 {{
-            font-size: 18px;
-            text-align: center;
-            margin: 0 0 5px 0;
-            border-bottom: 2px solid #000;
-            text-transform: uppercase;
-        }}
-
-        .header-meta {{
-            text-align: center;
-            font-size: 14px;
-            margin-bottom: 10px;
-            border-bottom: 1px dashed #000;
-            padding-bottom: 5px;
-        }}
-
-        .section {{
-            margin-bottom: 8px;
-            border-bottom: 1px dashed #000;
-            padding-bottom: 5px;
-            word-wrap: break-word;
-        }}
-
-        .section-title {{
-            text-decoration: underline;
-            font-size: 14px;
-            display: block;
-            margin-bottom: 2px;
-        }}
-
-        .compact-row {{
-            margin-top: 4px;
-            display: block;
-        }}
-
-        .row {{
-            display: flex;
-            justify-content: space-between;
-        }}
-
-        .products-container {{
-            border-top: 2px solid #000;
-            margin-top: 5px;
-        }}
-
-        .product-item {{
-            border-bottom: 1px dotted #000;
-            padding: 4px 0;
-        }}
-
-        .prod-name {{
-            display: block;
-            font-size: 13px;
-            margin-bottom: 2px;
-        }}
-
-        .prod-math {{
-            display: flex;
-            justify-content: space-between;
-            font-size: 13px;
-        }}
-        
-        .prod-vat-row {{
-            display: flex;
-            justify-content: space-between;
-            align-items: center;
-            font-size: 12px;
-            font-weight: bold;
-            color: #000;
-            margin-top: 2px;
-        }}
-        
-        .tva-percent {{
-            font-weight: bold;
-        }}
-        
-        .tva-value {{
-            font-weight: bold;
-        }}
-
-        .totals-section {{
-            text-align: right;
-            margin-top: 10px;
-            font-size: 14px;
-        }}
-
-        .grand-total {{
-            font-size: 18px;
-            border-top: 2px solid #000;
-            border-bottom: 2px solid #000;
-            margin-top: 5px;
-            padding: 5px 0;
-        }}
-
-        .legal-note {{
-            font-size: 11px;
-            text-align: justify;
-            margin-top: 10px;
-            font-weight: normal;
-            color: #000;
-        }}
-
-        .signatures {{
-            margin-top: 15px;
-        }}
-
-        .sig-block {{
-            margin-bottom: 15px;
-            page-break-inside: avoid;
-        }}
-
-        .dots {{
-            border-bottom: 1px dotted #000;
-            display: inline-block;
-            width: 100%;
-            height: 15px;
-        }}
-
-        .footer-branding {{
-            width: 100%;
-            text-align: center;
-            font-size: 20px;
-            color: #000000;
-            font-weight: normal;
-            font-style: italic;
-            border-top: 1px solid #000;
-            padding-top: 5px;
-            margin-top: 15px;
-            background-color: white;
-        }}
-
-        .footer-logo {{
-            width: 45mm;
-            height: auto;
-            display: block;
-            margin: 0 auto 5px auto;
-        }}
-
-    </style>
-</head>
-<body>
-
-    <h1>FACTURA FISCALA</h1>
-    
-    <div class="header-meta">
-        Seria: {} &nbsp; Nr: {}<br>
-        Data emitere: {}<br>
-        Data scadenta: {}
-    </div>
-
-    <div class="section">
-        <span class="section-title">FURNIZOR:</span>
-        {}<br>
-        CIF: {}<br>
-        Reg.Com: {}<br>
-        Capital Social: {}<br>
-        Localitate: {}<br>
-        Sediul: {}<br>
-        Cod Postal: {}<br>
-        <div class="compact-row">
-            Banca: {}<br>
-            Cont: {}
-        </div>
-    </div>
-
-    <div class="section">
-        <span class="section-title">CUMPARATOR:</span>
-        {}<br>
-        CIF: {}<br>
-        Reg.Com: {}<br>
-        Locatie: {}<br>
-        {}
-    </div>
-
-    <div class="products-container">
-        {}
-    </div>
-
-    <div class="totals-section">
-        <div class="row">
-            <span>Total Valoare:</span>
-            <span>{:.2} RON</span>
-        </div>
-        <div class="row">
-            <span>Total TVA:</span>
-            <span>{:.2} RON</span>
-        </div>
-        
-        <div class="grand-total">
-            TOTAL GENERAL: {:.2} RON
-        </div>
-    </div>
-
-    <div class="legal-note">
-        Produsele din prezenta factura raman proprietatea firmei noastre pana la achitarea lor integrala.<br>
-        Prezenta tine loc de contract ferm intre parti in lipsa altui acord scris.<br>
-        <strong>Data Scadenta: {}</strong>
-    </div>
-    {}
-    <div class="signatures">
-        
-        <div class="sig-block">
-            Semnatura si stampila Furnizor:<br>
-            <span class="dots"></span>
-        </div>
-
-        <div class="sig-block">
-            Numele Delegatului: {}<br>
-            Act Delegat: {}<br>
-            Semnatura: <span class="dots" style="width: 50%;"></span>
-        </div>
-
-        <div class="sig-block">
-            Semnatura de primire:<br>
-            <span class="dots"></span>
-        </div>
-
-    </div>
-
-    <div class="footer-branding">
-        {}
-        printed by eSOFT app
-    </div>
-
-    <script>
-        // Auto-open print dialog when page loads
-        function triggerPrint() {{
-            window.print();
-        }}
-        
-        // Try multiple triggers to ensure print dialog opens
-        if (document.readyState === 'loading') {{
-            document.addEventListener('DOMContentLoaded', function() {{
-                setTimeout(triggerPrint, 300);
-            }});
-        }} else {{
-            triggerPrint();
-        }}
-        
-        // Fallback after window load
-        window.addEventListener('load', function() {{
-            setTimeout(triggerPrint, 100);
-        }});
-    </script>
-</body>
-</html>"#,
-        carnet_series,
-        invoice_number,
+        .collect();
+
+    // Calculate total TVA by summing individual product TVAs
+    let total_without_vat: f64 = lines.iter().map(|line| line.total).sum();
+    let total_vat: f64 = lines.iter().map(|line| line.vat).sum();
+
+    InvoiceTemplate {
+        company: templates::company_context(company),
+        images,
+        series: details.series,
+        number: details.number,
+        issue_date: format_date(&invoice.created_at),
+        due_date,
+        partner: InvoicePartner {
+            name: &invoice.partner_name,
+            cif: invoice.partner_cif.as_deref(),
+            reg_com: invoice.partner_reg_com.as_deref(),
+            location: &invoice.location_name,
+            address: invoice.location_address.as_deref(),
+        },
+        items: lines,
+        total_without_vat,
+        total_vat,
+        // Total General = Subtotal + TVA
+        total: total_without_vat + total_vat,
+        car_number: details.car_number,
+        delegate_name: details.delegate_name,
+        delegate_act: details.delegate_act,
+        footer_lines: templates::footer_lines(DocType::Invoice, company),
+        preview: false,
+    }
+}
+
+pub fn generate_invoice_html(
+    invoice: &Invoice,
+    items: &[InvoiceItem],
+    company: &CompanyProfile,
+    images: &Images,
+    details: InvoiceDetails,
+) -> String {
+    log::info!("📄 Generating invoice HTML with payment_term_days: {} for partner: '{}'", 
+        details.payment_term_days, invoice.partner_name);
+
+    let ctx = invoice_template(invoice, items, company, images, details);
+    templates::render(DocType::Invoice, &ctx)
+}
+
+/// Same layout as `generate_invoice_html`, rendered directly to PDF.
+pub fn generate_invoice_pdf(
+    invoice: &Invoice,
+    items: &[InvoiceItem],
+    company: &CompanyProfile,
+    logo: Option<&Image>,
+    code: &DocumentCode,
+    details: InvoiceDetails,
+) -> Vec<u8> {
+    let due_date = calculate_due_date(&invoice.created_at, details.payment_term_days);
+    let mut flow = Flow::new(80.0, 297.0, [3.0, 6.0, 3.0, 4.0]);
+
+    if invoice.status == InvoiceStatus::Cancelled {
+        flow.banner("ANULAT", 20.0);
+    }
+    if details.duplicate {
+        flow.banner("DUPLICAT", 16.0);
+    }
+
+    flow.text("FACTURA FISCALA", Font::Bold, 13.5, Align::Center);
+    flow.rule(1.5, LineStyle::Solid);
+    flow.text(
+        &format!(
+            "Seria: {}  Nr: {}\nData emitere: {}\nData scadenta: {}",
+            details.series,
+            details.number,
+            format_date(&invoice.created_at),
+            due_date
+        ),
+        Font::Bold,
+        10.5,
+        Align::Center,
+    );
+    if company.vat_on_collection {
+        flow.text("TVA la încasare", Font::Bold, 10.5, Align::Center);
+    }
+    flow.rule(0.75, LineStyle::Dashed);
+
+    flow.underlined("FURNIZOR:", Font::Bold, 10.5, Align::Left);
+    flow.text(
+        &format!(
+            "{}\nCIF: {}\nReg.Com: {}\nCapital Social: {}\nLocalitate: {}\nSediul: {}\nCod Postal: {}",
+            company.name,
+            company.cif,
+            company.reg_com,
+            company.capital,
+            company.localitate_line(),
+            company.address,
+            company.cod_postal
+        ),
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+    for account in company.accounts_for_print() {
+        flow.space(1.0);
+        flow.text(
+            &format!("Banca: {}\nCont: {}", account.bank_name, account.iban),
+            Font::Bold,
+            7.9,
+            Align::Left,
+        );
+    }
+    flow.rule(0.75, LineStyle::Dashed);
+
+    flow.underlined("CUMPARATOR:", Font::Bold, 10.5, Align::Left);
+    flow.text(
+        &format!(
+            "{}\nCIF: {}\nReg.Com: {}\nLocatie: {}\nAdresa: {}",
+            invoice.partner_name,
+            invoice.partner_cif.as_deref().unwrap_or("N/A"),
+            invoice.partner_reg_com.as_deref().unwrap_or("N/A"),
+            invoice.location_name,
+            invoice.location_address.as_deref().unwrap_or("N/A")
+        ),
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+    flow.rule(0.75, LineStyle::Dashed);
+    flow.rule(1.5, LineStyle::Solid);
+
+    let mut total_without_vat = 0.0;
+    let mut total_vat = 0.0;
+
+    for (idx, item) in items.iter().enumerate() {
+        let vat_rate = item.tva_percent.unwrap_or(19.0) / 100.0;
+        let item_vat = (item.total_price * vat_rate * 100.0).round() / 100.0;
+        total_without_vat += item.total_price;
+        total_vat += item_vat;
+
+        let tva_display = item.tva_percent
+            .map(|t| format!("TVA: {:.0}%", t))
+            .unwrap_or_else(|| "TVA: 19%".to_string());
+
+        flow.text(&format!("{}. {}", idx + 1, item.product_name), Font::Bold, 9.75, Align::Left);
+        flow.row(
+            &format!("{} {} x {:.2}", item.quantity as i32, item.unit_of_measure, item.unit_price),
+            &format!("= {:.2}", item.total_price),
+            Font::Bold,
+            9.75,
+        );
+        flow.row(&tva_display, &format!("Valoare TVA: {:.2} RON", item_vat), Font::Bold, 9.0);
+        flow.rule(0.75, LineStyle::Dotted);
+    }
+
+    flow.space(2.5);
+    flow.row("Total Valoare:", &format!("{:.2} RON", total_without_vat), Font::Bold, 10.5);
+    flow.row("Total TVA:", &format!("{:.2} RON", total_vat), Font::Bold, 10.5);
+    flow.rule(1.5, LineStyle::Solid);
+    flow.text(
+        &format!("TOTAL GENERAL: {:.2} RON", total_without_vat + total_vat),
+        Font::Bold,
+        13.5,
+        Align::Right,
+    );
+    flow.rule(1.5, LineStyle::Solid);
+
+    flow.space(2.5);
+    flow.text(
+        "Produsele din prezenta factura raman proprietatea firmei noastre pana la achitarea lor integrala.\n\
+         Prezenta tine loc de contract ferm intre parti in lipsa altui acord scris.",
+        Font::Regular,
+        8.25,
+        Align::Left,
+    );
+    flow.text(&format!("Data Scadenta: {}", due_date), Font::Bold, 8.25, Align::Left);
+
+    if let Some(car_num) = details.car_number {
+        flow.space(2.5);
+        flow.rule(0.5, LineStyle::Solid);
+        flow.text(
+            &format!(
+                "Certificăm faptul că mașina cu numărul {} a fost dezinfectată cu Virocid 1% înainte de încărcare.",
+                car_num
+            ),
+            Font::Bold,
+            8.25,
+            Align::Left,
+        );
+    }
+
+    flow.space(4.0);
+    flow.text("Semnatura si stampila Furnizor:", Font::Bold, 7.9, Align::Left);
+    flow.space(4.0);
+    flow.rule(0.75, LineStyle::Dotted);
+    flow.space(3.0);
+    flow.text(
+        &format!(
+            "Numele Delegatului: {}\nAct Delegat: {}\nSemnatura:",
+            details.delegate_name.unwrap_or("........................"),
+            details.delegate_act.unwrap_or(".....................................")
+        ),
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+    flow.space(4.0);
+    flow.rule(0.75, LineStyle::Dotted);
+    flow.space(3.0);
+    flow.text("Semnatura de primire:", Font::Bold, 7.9, Align::Left);
+    flow.space(4.0);
+    flow.rule(0.75, LineStyle::Dotted);
+    flow.space(3.0);
+    code.draw_pdf(&mut flow);
+    flow.space(2.0);
+
+    flow.rule(0.75, LineStyle::Solid);
+    if let Some(logo) = logo {
+        flow.image(logo, 45.0, 0.0, Align::Center);
+    }
+    let footer = templates::footer_lines(DocType::Invoice, company);
+    if !footer.is_empty() {
+        flow.text(&footer.join("\n"), Font::Regular, 15.0, Align::Center);
+    }
+
+    flow.finish()
+}
+
+/// Compact invoice for a thermal printer (ESC/POS): same data as the PDF,
+/// one line per product plus its VAT line.
+pub fn generate_invoice_escpos(
+    invoice: &Invoice,
+    items: &[InvoiceItem],
+    company: &CompanyProfile,
+    logo: Option<&Raster>,
+    code: &DocumentCode,
+    options: &Options,
+    details: InvoiceDetails,
+) -> Vec<u8> {
+    let due_date = calculate_due_date(&invoice.created_at, details.payment_term_days);
+    let mut ticket = Ticket::new(options);
+
+    if invoice.status == InvoiceStatus::Cancelled {
+        ticket.banner("ANULAT");
+    }
+    if details.duplicate {
+        ticket.banner("DUPLICAT");
+    }
+
+    ticket.align(Align::Center);
+    ticket.bold(true);
+    ticket.double(true);
+    ticket.text("FACTURA FISCALA");
+    ticket.double(false);
+    ticket.text(&format!(
+        "Seria: {}  Nr: {}\nData emitere: {}\nData scadenta: {}",
+        details.series,
+        details.number,
         format_date(&invoice.created_at),
-        due_date.clone(),
-        KARIN.name,
-        KARIN.cif,
-        KARIN.reg_com,
-        KARIN.capital,
-        KARIN.localitate,
-        KARIN.address,
-        KARIN.cod_postal,
-        KARIN.bank_name,
-        KARIN.bank_account,
-        invoice.partner_name,
+        due_date
+    ));
+    if company.vat_on_collection {
+        ticket.text("TVA la încasare");
+    }
+    ticket.align(Align::Left);
+    ticket.rule('-');
+
+    ticket.text("FURNIZOR:");
+    ticket.bold(false);
+    ticket.text(&format!(
+        "{}\nCIF: {}  Reg.Com: {}\nCapital Social: {}\n{}, {}, CP {}",
+        company.name,
+        company.cif,
+        company.reg_com,
+        company.capital,
+        company.address,
+        company.localitate_line(),
+        company.cod_postal
+    ));
+    for account in company.accounts_for_print() {
+        ticket.text(&format!("Banca: {}\nCont: {}", account.bank_name, account.iban));
+    }
+    ticket.rule('-');
+
+    ticket.bold(true);
+    ticket.text("CUMPARATOR:");
+    ticket.text(&invoice.partner_name);
+    ticket.bold(false);
+    ticket.text(&format!(
+        "CIF: {}  Reg.Com: {}\nLocatie: {}\nAdresa: {}",
         invoice.partner_cif.as_deref().unwrap_or("N/A"),
         invoice.partner_reg_com.as_deref().unwrap_or("N/A"),
         invoice.location_name,
-        format!("Adresa: {}", invoice.location_address.as_deref().unwrap_or("N/A")),
-        products_html,
-        total_without_vat,
-        total_vat,
-        total_without_vat + total_vat,  // Total General = Subtotal + TVA
-        due_date,        if let Some(car_num) = car_number {
-            format!(r#"
-    <div class="legal-note" style="margin-top: 10px; border-top: 1px solid #ddd; padding-top: 8px;">
-        <strong>Certificăm faptul că mașina cu numărul {} a fost dezinfectată cu Virocid 1% înainte de încărcare.</strong>
-    </div>"#, car_num)
-        } else {
-            String::new()
-        },        delegate_name.unwrap_or("........................"),
-        delegate_act.unwrap_or("....................................."),
-        if let Some(logo) = logo_base64 {
-            format!(r#"<img src="{}" class="footer-logo" alt="Logo" />"#, logo)
-        } else {
-            String::new()
-        }
+        invoice.location_address.as_deref().unwrap_or("N/A")
+    ));
+    ticket.rule('=');
+
+    let mut total_without_vat = 0.0;
+    let mut total_vat = 0.0;
+
+    for (idx, item) in items.iter().enumerate() {
+        let vat_percent = item.tva_percent.unwrap_or(19.0);
+        let item_vat = (item.total_price * vat_percent / 100.0 * 100.0).round() / 100.0;
+        total_without_vat += item.total_price;
+        total_vat += item_vat;
+
+        ticket.bold(true);
+        ticket.text(&format!("{}. {}", idx + 1, item.product_name));
+        ticket.bold(false);
+        ticket.row(
+            &format!("{} {} x {:.2}", item.quantity as i32, item.unit_of_measure, item.unit_price),
+            &format!("{:.2}", item.total_price),
+        );
+        ticket.row(&format!("TVA {:.0}%", vat_percent), &format!("{:.2}", item_vat));
+    }
+
+    ticket.rule('-');
+    ticket.row("Total Valoare:", &format!("{:.2} RON", total_without_vat));
+    ticket.row("Total TVA:", &format!("{:.2} RON", total_vat));
+    ticket.rule('=');
+    ticket.bold(true);
+    ticket.row("TOTAL GENERAL:", &format!("{:.2} RON", total_without_vat + total_vat));
+    ticket.bold(false);
+    ticket.rule('=');
+
+    ticket.text(
+        "Produsele din prezenta factura raman proprietatea firmei noastre pana la achitarea lor integrala. \
+         Prezenta tine loc de contract ferm intre parti in lipsa altui acord scris.",
+    );
+    ticket.text(&format!("Data Scadenta: {}", due_date));
+
+    if let Some(car_num) = details.car_number {
+        ticket.rule('-');
+        ticket.text(&format!(
+            "Certificăm faptul că mașina cu numărul {} a fost dezinfectată cu Virocid 1% înainte de încărcare.",
+            car_num
+        ));
+    }
+
+    ticket.feed(1);
+    ticket.text("Semnatura si stampila Furnizor:");
+    ticket.feed(2);
+    ticket.text(&format!(
+        "Numele Delegatului: {}\nAct Delegat: {}\nSemnatura:",
+        details.delegate_name.unwrap_or("........................"),
+        details.delegate_act.unwrap_or("........................")
+    ));
+    ticket.feed(2);
+    ticket.text("Semnatura de primire:");
+    ticket.feed(2);
+    ticket.rule('-');
+    code.print_escpos(&mut ticket);
+
+    if let Some(logo) = logo {
+        ticket.raster(logo);
+    }
+    ticket.align(Align::Center);
+    for line in templates::footer_lines(DocType::Invoice, company) {
+        ticket.text(&line);
+    }
+    ticket.align(Align::Left);
+
+    ticket.finish()
+}
+
+/// Total with VAT, computed line by line as on the printed invoice.
+pub fn invoice_total(items: &[InvoiceItem]) -> f64 {
+    items
+        .iter()
+        .map(|item| {
+            let vat = (item.total_price * item.tva_percent.unwrap_or(19.0) / 100.0 * 100.0).round() / 100.0;
+            item.total_price + vat
+        })
+        .sum()
+}
+
+/// Stamps a generated invoice or receipt as cancelled. The document keeps its
+/// number and content; only an "ANULAT" banner is added at the top.
+pub fn mark_cancelled(html: String) -> String {
+    add_banner(html, "ANULAT", 20)
+}
+
+/// Stamps an invoice printed before as "DUPLICAT".
+pub fn mark_duplicate(html: String) -> String {
+    add_banner(html, "DUPLICAT", 16)
+}
+
+fn add_banner(html: String, text: &str, font_size_pt: u32) -> String {
+    html.replacen(
+        "<body>",
+        &format!(
+            r#"<body>
+    <div style="border: 3px solid #000; text-align: center; font-size: {}pt; font-weight: bold; letter-spacing: 4px; padding: 4px; margin-bottom: 8px;">{}</div>"#,
+            font_size_pt, text
+        ),
+        1,
     )
 }
 
@@ -429,3 +509,55 @@ fn calculate_due_date(created_at: &str, days: i64) -> String {
         created_at.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codes::Kind;
+    use crate::company;
+    use crate::pdf::extract_text;
+    use crate::test_fixtures;
+
+    fn pdf(invoice: &Invoice, duplicate: bool) -> Vec<u8> {
+        generate_invoice_pdf(
+            invoice,
+            &test_fixtures::invoice_items(),
+            &company::builtin(),
+            None,
+            &test_fixtures::code(Kind::Invoice),
+            InvoiceDetails { duplicate, ..test_fixtures::invoice_details() },
+        )
+    }
+
+    #[test]
+    fn pdf_is_deterministic() {
+        let invoice = test_fixtures::invoice();
+        assert_eq!(pdf(&invoice, false), pdf(&invoice, false));
+    }
+
+    #[test]
+    fn pdf_prints_header_lines_and_totals() {
+        let text = extract_text(&pdf(&test_fixtures::invoice(), false));
+        assert_eq!(text[0], "FACTURA FISCALA");
+        assert_eq!(text[1], "Seria: KRN Nr: 42");
+        assert_eq!(text[3], "Data scadenta: 17-03-2026");
+        assert!(text.contains(&"Ștefănescu Țară SRL".to_string()));
+        assert!(text.contains(&"1. Ouă categoria A, mărimea M".to_string()));
+        assert!(text.contains(&"Valoare TVA: 8.10 RON".to_string()));
+        assert!(text.contains(&"Valoare TVA: 8.55 RON".to_string()));
+        assert!(text.contains(&"135.00 RON".to_string()));
+        assert!(text.contains(&"16.65 RON".to_string()));
+        assert!(text.contains(&"TOTAL GENERAL: 151.65 RON".to_string()));
+        assert!(text.contains(&"Numele Delegatului: Ion Popescu".to_string()));
+        assert!(text.contains(&"FKRN-42".to_string()));
+        assert_eq!(text.last().unwrap(), "printed by eSOFT app");
+    }
+
+    #[test]
+    fn pdf_marks_cancelled_and_duplicate_copies() {
+        let mut invoice = test_fixtures::invoice();
+        invoice.status = InvoiceStatus::Cancelled;
+        let text = extract_text(&pdf(&invoice, true));
+        assert_eq!(text[..3], ["ANULAT", "DUPLICAT", "FACTURA FISCALA"]);
+    }
+}
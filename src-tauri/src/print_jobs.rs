@@ -0,0 +1,318 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::{AppHandle, Manager};
+
+use crate::database::Database;
+use crate::document_events;
+use crate::models::PrintJob;
+use crate::printing::{self, Backend, Destination};
+
+// Print job queue.
+//
+// Every print is a row in print_jobs: the document, the rendered file, the
+// destination it was resolved to and the outcome. Printing used to be
+// fire-and-forget; now the printer command is waited for, a failed job is
+// retried in the background a few times, and the office can list the jobs and
+// reprint any of them. The first successful print of an invoice is counted on
+// the invoice, so later prints carry "DUPLICAT".
+
+pub const QUEUED: &str = "queued";
+pub const PRINTING: &str = "printing";
+pub const PRINTED: &str = "printed";
+pub const FAILED: &str = "failed";
+
+/// Attempts (the first one included) before a failed job is left alone
+pub const MAX_ATTEMPTS: i64 = 3;
+/// Wait before an automatic retry, times the attempts made so far
+const RETRY_DELAY_SECS: i64 = 30;
+const RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS print_jobs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        doc_type TEXT NOT NULL,
+        document_id TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        backend TEXT NOT NULL,
+        printer TEXT,
+        profile_name TEXT,
+        paper_size TEXT,
+        duplex INTEGER NOT NULL DEFAULT 0,
+        copies INTEGER NOT NULL DEFAULT 1,
+        status TEXT NOT NULL DEFAULT 'queued',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        error_message TEXT,
+        reprint_of INTEGER,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        printed_at TEXT
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_print_jobs_status ON print_jobs(status);
+    CREATE INDEX IF NOT EXISTS idx_print_jobs_document ON print_jobs(doc_type, document_id);
+"#;
+
+const COLUMNS: &str = "id, doc_type, document_id, file_path, backend, printer, profile_name, paper_size, duplex, copies,
+                       status, attempts, error_message, reprint_of, created_at, updated_at, printed_at";
+
+fn read_job(row: &rusqlite::Row) -> rusqlite::Result<PrintJob> {
+    Ok(PrintJob {
+        id: row.get(0)?,
+        doc_type: row.get(1)?,
+        document_id: row.get(2)?,
+        file_path: row.get(3)?,
+        backend: row.get(4)?,
+        printer: row.get(5)?,
+        profile_name: row.get(6)?,
+        paper_size: row.get(7)?,
+        duplex: row.get::<_, i64>(8)? != 0,
+        copies: row.get(9)?,
+        status: row.get(10)?,
+        attempts: row.get(11)?,
+        error_message: row.get(12)?,
+        reprint_of: row.get(13)?,
+        created_at: row.get(14)?,
+        updated_at: row.get(15)?,
+        printed_at: row.get(16)?,
+    })
+}
+
+pub fn load(conn: &Connection, job_id: i64) -> rusqlite::Result<Option<PrintJob>> {
+    conn.query_row(
+        &format!("SELECT {} FROM print_jobs WHERE id = ?1", COLUMNS),
+        [job_id],
+        read_job,
+    )
+    .optional()
+}
+
+/// Newest first, optionally only the jobs with the given status.
+pub fn list(conn: &Connection, status: Option<&str>, limit: i64) -> rusqlite::Result<Vec<PrintJob>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM print_jobs WHERE (?1 IS NULL OR status = ?1) ORDER BY id DESC LIMIT ?2",
+        COLUMNS
+    ))?;
+    let jobs = stmt
+        .query_map(params![status, limit], read_job)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(jobs)
+}
+
+pub fn enqueue(
+    conn: &Connection,
+    destination: &Destination,
+    document_id: &str,
+    file_path: &str,
+    reprint_of: Option<i64>,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO print_jobs (doc_type, document_id, file_path, backend, printer, profile_name, paper_size,
+                                 duplex, copies, status, reprint_of, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)",
+        params![
+            destination.doc_type,
+            document_id,
+            file_path,
+            destination.backend.name(),
+            destination.printer,
+            destination.profile_name,
+            destination.paper_size,
+            destination.duplex as i64,
+            destination.copies,
+            QUEUED,
+            reprint_of,
+            Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// The destination a job was queued with.
+pub fn destination(job: &PrintJob) -> Result<Destination, String> {
+    Ok(Destination {
+        doc_type: printing::doc_type(&job.doc_type)?,
+        profile_name: job.profile_name.clone(),
+        printer: job.printer.clone(),
+        backend: Backend::parse(&job.backend)?,
+        paper_size: job.paper_size.clone(),
+        duplex: job.duplex,
+        copies: job.copies.clamp(0, 99) as u32,
+    })
+}
+
+/// Queues a document and prints it right away. A destination with 0 copies
+/// is not printed and gets no job.
+pub fn submit(
+    db: &Database,
+    destination: &Destination,
+    document_id: &str,
+    file_path: &str,
+) -> Result<Option<PrintJob>, String> {
+    if destination.copies == 0 {
+        info!("[PRINT] {} {} routed with 0 copies, not printed", destination.doc_type, document_id);
+        return Ok(None);
+    }
+
+    let job_id = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        enqueue(&conn, destination, document_id, file_path, None).map_err(|e| e.to_string())?
+    };
+    run(db, job_id).map(Some)
+}
+
+/// Prints a queued or failed job and records the outcome. The database is not
+/// locked while the printer command runs.
+pub fn run(db: &Database, job_id: i64) -> Result<PrintJob, String> {
+    let job = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        let job = load(&conn, job_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Lucrarea de printare {} nu există.", job_id))?;
+        if job.status == PRINTING || job.status == PRINTED {
+            return Err(format!("Lucrarea de printare {} este deja {}.", job_id, status_label(&job.status)));
+        }
+        conn.execute(
+            "UPDATE print_jobs SET status = ?1, attempts = attempts + 1, updated_at = ?2 WHERE id = ?3",
+            params![PRINTING, Utc::now().to_rfc3339(), job_id],
+        )
+        .map_err(|e| e.to_string())?;
+        job
+    };
+
+    let destination = destination(&job);
+    let result = destination
+        .as_ref()
+        .map_err(|e| e.clone())
+        .and_then(|destination| printing::print_file(destination, &job.file_path));
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let now = Utc::now().to_rfc3339();
+    match (&result, &destination) {
+        (Ok(()), Ok(destination)) => {
+            conn.execute(
+                "UPDATE print_jobs SET status = ?1, error_message = NULL, printed_at = ?2, updated_at = ?2 WHERE id = ?3",
+                params![PRINTED, now, job_id],
+            )
+            .map_err(|e| e.to_string())?;
+            record_printed(&conn, &job, destination);
+            info!("[PRINT] Job {} printed: {} {}", job_id, job.doc_type, job.document_id);
+        }
+        _ => {
+            let error = result.as_ref().err().cloned().unwrap_or_default();
+            conn.execute(
+                "UPDATE print_jobs SET status = ?1, error_message = ?2, updated_at = ?3 WHERE id = ?4",
+                params![FAILED, error, now, job_id],
+            )
+            .map_err(|e| e.to_string())?;
+            warn!("[PRINT] Job {} failed (attempt {}): {}", job_id, job.attempts + 1, error);
+        }
+    }
+
+    let job = load(&conn, job_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Lucrarea de printare {} nu există.", job_id))?;
+    match result {
+        Ok(()) => Ok(job),
+        Err(e) if job.attempts < MAX_ATTEMPTS => Err(format!("{} Printarea va fi reîncercată automat.", e)),
+        Err(e) => Err(e),
+    }
+}
+
+fn status_label(status: &str) -> &'static str {
+    match status {
+        PRINTING => "în curs de printare",
+        PRINTED => "printată",
+        FAILED => "eșuată",
+        _ => "în așteptare",
+    }
+}
+
+// Audit event for the document, and the print counter of invoices
+fn record_printed(conn: &Connection, job: &PrintJob, destination: &Destination) {
+    let mut message = destination.label();
+    if job.reprint_of.is_some() {
+        message = format!("retipărire, {}", message);
+    }
+
+    match destination.doc_type {
+        printing::INVOICE => {
+            if let Err(e) = conn.execute(
+                "UPDATE invoices SET printed_at = ?1, print_count = COALESCE(print_count, 0) + 1 WHERE id = ?2",
+                params![Utc::now().to_rfc3339(), job.document_id],
+            ) {
+                warn!("[PRINT] Could not update the print count of invoice {}: {}", job.document_id, e);
+            }
+            document_events::record_invoice(conn, &job.document_id, document_events::PRINTED, Some(&message));
+        }
+        printing::QUALITY_CERTIFICATE => {
+            let message = format!("certificat de calitate, {}", message);
+            document_events::record_invoice(conn, &job.document_id, document_events::PRINTED, Some(&message));
+        }
+        printing::RECEIPT => {
+            document_events::record_collection_group(conn, &job.document_id, document_events::PRINTED, Some(&message));
+        }
+        _ => {}
+    }
+}
+
+/// Jobs left "printing" when the app was closed did not finish; they become
+/// failed jobs and go through the normal retries.
+fn recover_interrupted(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE print_jobs SET status = ?1, error_message = 'Printare întreruptă la închiderea aplicației', updated_at = ?2
+         WHERE status = ?3",
+        params![FAILED, Utc::now().to_rfc3339(), PRINTING],
+    )
+}
+
+fn due_for_retry(conn: &Connection) -> rusqlite::Result<Vec<i64>> {
+    let now = Utc::now();
+    let mut stmt = conn.prepare("SELECT id, attempts, updated_at FROM print_jobs WHERE status = ?1 AND attempts < ?2")?;
+    let jobs = stmt
+        .query_map(params![FAILED, MAX_ATTEMPTS], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(jobs
+        .into_iter()
+        .filter(|(_, attempts, updated_at)| {
+            DateTime::parse_from_rfc3339(updated_at)
+                .map(|failed_at| now - failed_at.with_timezone(&Utc) >= Duration::seconds(RETRY_DELAY_SECS * attempts))
+                .unwrap_or(true)
+        })
+        .map(|(id, _, _)| id)
+        .collect())
+}
+
+/// Background loop retrying failed jobs, started with the app.
+pub fn retry_loop(app: AppHandle) {
+    let db = app.state::<Database>();
+    match db.conn.lock() {
+        Ok(conn) => match recover_interrupted(&conn) {
+            Ok(0) => {}
+            Ok(count) => warn!("[PRINT] {} interrupted print job(s) marked as failed", count),
+            Err(e) => warn!("[PRINT] Could not recover interrupted print jobs: {}", e),
+        },
+        Err(e) => warn!("[PRINT] {}", e),
+    }
+
+    loop {
+        std::thread::sleep(RETRY_INTERVAL);
+
+        let due = match db.conn.lock() {
+            Ok(conn) => due_for_retry(&conn).unwrap_or_else(|e| {
+                warn!("[PRINT] Could not read failed print jobs: {}", e);
+                Vec::new()
+            }),
+            Err(_) => continue,
+        };
+        for job_id in due {
+            info!("[PRINT] Retrying print job {}", job_id);
+            if let Err(e) = run(&db, job_id) {
+                warn!("[PRINT] Retry of job {} failed: {}", job_id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::models::{CompanyProfile, PartnerStatement};
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, CompanyContext, DocType, Images};
+use serde::Serialize;
+
+/// Data for templates/partner_statement.html, see templates/README.md.
+#[derive(Serialize)]
+pub struct PartnerStatementTemplate<'a> {
+    pub company: CompanyContext<'a>,
+    pub images: &'a Images,
+    #[serde(flatten)]
+    pub statement: &'a PartnerStatement,
+    pub footer_lines: Vec<String>,
+    pub preview: bool,
+}
+
+pub fn partner_statement_template<'a>(
+    statement: &'a PartnerStatement,
+    company: &'a CompanyProfile,
+    images: &'a Images,
+) -> PartnerStatementTemplate<'a> {
+    PartnerStatementTemplate {
+        company: templates::company_context(company),
+        images,
+        statement,
+        footer_lines: templates::footer_lines(DocType::PartnerStatement, company),
+        preview: false,
+    }
+}
+
+pub fn generate_partner_statement_html(
+    statement: &PartnerStatement,
+    company: &CompanyProfile,
+    images: &Images,
+) -> String {
+    log::info!(
+        "📄 Generating statement HTML for {} ({} - {})",
+        statement.partner_name,
+        statement.from,
+        statement.to
+    );
+
+    let ctx = partner_statement_template(statement, company, images);
+    templates::render(DocType::PartnerStatement, &ctx)
+}
+
+fn amount(value: f64) -> String {
+    if value.abs() < 0.005 {
+        String::new()
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// Same layout as `generate_partner_statement_html`, A4 pages rendered
+/// directly to PDF.
+pub fn generate_partner_statement_pdf(
+    statement: &PartnerStatement,
+    company: &CompanyProfile,
+    logo: Option<&Image>,
+) -> Vec<u8> {
+    let mut flow = Flow::new(210.0, 297.0, [12.0, 12.0, 12.0, 12.0]);
+
+    flow.text(
+        &format!("{}\nCIF: {}   Reg. Com.: {}\n{}", company.name, company.cif, company.reg_com, company.address),
+        Font::Regular,
+        9.0,
+        Align::Left,
+    );
+    flow.space(4.0);
+    flow.text("FIȘĂ DE CONT CLIENT", Font::Bold, 14.0, Align::Center);
+    flow.text(
+        &format!(
+            "{}{}\nPerioada: {} - {}",
+            statement.partner_name,
+            statement.partner_cif.as_deref().map(|cif| format!("   CIF: {}", cif)).unwrap_or_default(),
+            statement.from,
+            statement.to
+        ),
+        Font::Regular,
+        10.0,
+        Align::Center,
+    );
+    flow.space(4.0);
+
+    flow.columns(
+        &[
+            ("Data", 20.0, Align::Left),
+            ("Document", 28.0, Align::Left),
+            ("Explicații", 0.0, Align::Left),
+            ("Scadență", 20.0, Align::Left),
+            ("Debit", 22.0, Align::Right),
+            ("Credit", 22.0, Align::Right),
+            ("Sold", 24.0, Align::Right),
+            ("Zile", 10.0, Align::Right),
+        ],
+        Font::Bold,
+        9.0,
+    );
+    flow.rule(0.75, LineStyle::Solid);
+    let opening = format!("{:.2}", statement.opening_balance);
+    flow.columns(
+        &[
+            ("", 48.0, Align::Left),
+            ("Sold inițial", 0.0, Align::Left),
+            (opening.as_str(), 24.0, Align::Right),
+            ("", 10.0, Align::Right),
+        ],
+        Font::Regular,
+        9.0,
+    );
+
+    for line in &statement.lines {
+        let debit = amount(line.debit);
+        let credit = amount(line.credit);
+        let balance = format!("{:.2}", line.balance);
+        let days = line.days_overdue.map(|days| days.to_string()).unwrap_or_default();
+        flow.columns(
+            &[
+                (line.date.as_str(), 20.0, Align::Left),
+                (line.document.as_str(), 28.0, Align::Left),
+                (line.description.as_str(), 0.0, Align::Left),
+                (line.due_date.as_deref().unwrap_or_default(), 20.0, Align::Left),
+                (debit.as_str(), 22.0, Align::Right),
+                (credit.as_str(), 22.0, Align::Right),
+                (balance.as_str(), 24.0, Align::Right),
+                (days.as_str(), 10.0, Align::Right),
+            ],
+            Font::Regular,
+            9.0,
+        );
+    }
+
+    flow.rule(0.75, LineStyle::Solid);
+    let total_debit = format!("{:.2}", statement.total_debit);
+    let total_credit = format!("{:.2}", statement.total_credit);
+    flow.columns(
+        &[
+            ("TOTAL PERIOADĂ", 0.0, Align::Left),
+            (total_debit.as_str(), 22.0, Align::Right),
+            (total_credit.as_str(), 22.0, Align::Right),
+            ("", 34.0, Align::Right),
+        ],
+        Font::Bold,
+        9.0,
+    );
+    flow.row("SOLD FINAL", &format!("{:.2} RON", statement.closing_balance), Font::Bold, 10.0);
+    if statement.overdue_amount > 0.005 {
+        flow.row("din care restant", &format!("{:.2} RON", statement.overdue_amount), Font::Regular, 9.0);
+    }
+    flow.text(&format!("Generat la {}", statement.generated_at), Font::Regular, 8.0, Align::Left);
+
+    flow.space(10.0);
+    if let Some(logo) = logo {
+        flow.image(logo, 40.0, 0.0, Align::Center);
+    }
+    let footer = templates::footer_lines(DocType::PartnerStatement, company);
+    if !footer.is_empty() {
+        flow.text(&footer.join("\n"), Font::Regular, 7.5, Align::Center);
+    }
+
+    flow.finish()
+}
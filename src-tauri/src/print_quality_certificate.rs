@@ -0,0 +1,157 @@
+use crate::codes::DocumentCode;
+use crate::company::Issuer;
+use crate::models::QualityCertificateContext;
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, DocType};
+use log::warn;
+
+/// Same layout as `generate_quality_certificate_html` (commands.rs), rendered
+/// directly to PDF.
+pub fn generate_quality_certificate_pdf(
+    ctx: &QualityCertificateContext,
+    issuer: &Issuer,
+    code: &DocumentCode,
+) -> Vec<u8> {
+    let decode = |name: &str, bytes: &[u8]| {
+        Image::from_png(bytes, 400)
+            .map_err(|e| warn!("[CERT][PDF] Could not load {}: {}", name, e))
+            .ok()
+    };
+    let epc_img = decode("EPC 16 EC.png", include_bytes!("../../public/EPC 16 EC.png"));
+    let iso_img = decode("KARIN-ISO.png", include_bytes!("../../public/KARIN-ISO.png"));
+    let stamp_img = decode("stamp", &issuer.stamp);
+
+    let mut flow = Flow::new(80.0, 297.0, [2.5, 9.5, 2.5, 3.5]);
+
+    flow.text(
+        &format!(
+            "PO 7.5-03-F01Rev. 8/12012021\nSC {}\n{}, {}\n{}",
+            issuer.profile.name,
+            issuer.profile.reg_com,
+            issuer.profile.address,
+            issuer.profile.localitate_line()
+        ),
+        Font::Bold,
+        7.9,
+        Align::Center,
+    );
+    flow.rule(1.5, LineStyle::Solid);
+
+    let logos: Vec<&Image> = [&epc_img, &iso_img].into_iter().flatten().collect();
+    flow.space(1.0);
+    flow.image_row(&logos, 2.0);
+    flow.space(1.0);
+
+    flow.underlined(
+        "CERTIFICAT DE CALITATE - DECLARAȚIE DE CONFORMITATE",
+        Font::Bold,
+        9.75,
+        Align::Center,
+    );
+    flow.text(&format!("Data: {}", ctx.cert_date), Font::Bold, 7.9, Align::Center);
+    flow.text(&ctx.subtitle, Font::Bold, 9.0, Align::Center);
+
+    flow.space(1.0);
+    flow.text(
+        "În conformitate cu prevederile legale privind răspunderea, se atestă calitatea produselor livrate: \
+         ouă consum categoria A, cu data ouatului:",
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+
+    flow.space(1.0);
+    for line in &ctx.product_lines {
+        flow.text(
+            &format!("{} {} ddm {} Lot {}", line.denumire, line.data_productie, line.data_expirare, line.lot),
+            Font::Bold,
+            7.5,
+            Align::Left,
+        );
+    }
+
+    flow.space(1.0);
+    let paragraphs = [
+        format!(
+            "Ambalate la data de {}. Livrate beneficiarului: {}. Conform facturii/avizului nr. {} din {}.",
+            ctx.packed_date, ctx.beneficiary, ctx.invoice_display, ctx.invoice_date
+        ),
+        format!(
+            "Transport auto: {} indeplinesc parametri de calitate specificati conform {} (salmonella negativ).",
+            ctx.car_number, ctx.bon_analiza
+        ),
+        "Caracteristici tehnice de livrare: SALUBRE; Rasa LOHMANN BROWN, LOHMANN SANDY; Aspectul cojii intactă, curată de formă normală, uscată;".to_string(),
+        "Camera de aer: imobilă, cu înălțimea maximă 5 mm. Albușul: clar, translucid, consistență gelatinoasă si lipsit de corpuri străine de orice natura.".to_string(),
+        "Gălbenuș vizibil, în fascicol de lumina sub formă de umbră. Mirosul și gust caracteristic oului proaspăt, fără miros și gust străin.".to_string(),
+        "Data durabilității minime este de 28 zile iar data recomandata pentru vanzare este de 28 de zile de la momentul ouatului.".to_string(),
+        "Temperatura de păstrare: 5-18 grade Celsius,În magazine, ferite de razele soarelui si sursa de caldura.".to_string(),
+        "In magazinele de desfacere, ouale se pastreaza in locuri racoroase, curate, ferite de alte produse ale caror miros le pot imprumuta.".to_string(),
+        "Produs fragil! A se manipula cu atenție la transport și depozitare.".to_string(),
+        "Prezentul certificat întocmit conform Reg.(CE) nr.1234/22.10.2007 de instituire a unei organizari comune a pietelor agricole si privind \
+         dispozitii specifice referitoare la anumite produse agricole (\"Regulamentul unic OCP\"). Regulamentul (CE)NR.589/2008 al Comisiei din 23.06.2008 \
+         de stabilire a normelor de aplicare a Reg.(CE)nr.1234/2007 al Consiliului privind standardele de comercializare a oualelor, modificat de Regulamentul \
+         CE 598/2008. Mentionam ca ouale produse de noi cu cod pro.3RO MM 013 provin de la gaini crescute in custi imbunatatie si cu cod producator \
+         2RO MM 040 provin de la gaini cresute in sistem volieră. conform standardelor U.E. in vigoare.".to_string(),
+    ];
+    for paragraph in &paragraphs {
+        flow.text(paragraph, Font::Bold, 7.1, Align::Left);
+    }
+
+    flow.space(1.5);
+    flow.text(
+        "Țara de origine:România\nCod stație sortare RO MM 023\nCod producător 3RO MM 013\nCod producător 2RO MM 040",
+        Font::Bold,
+        7.1,
+        Align::Right,
+    );
+
+    if let Some(stamp) = &stamp_img {
+        flow.space(1.5);
+        flow.image(stamp, 26.5, 0.0, Align::Center);
+    }
+    code.draw_pdf(&mut flow);
+
+    let footer = templates::footer_lines(DocType::QualityCertificate, &issuer.profile);
+    if !footer.is_empty() {
+        flow.space(1.0);
+        flow.text(&footer.join("\n"), Font::Bold, 7.1, Align::Left);
+    }
+
+    flow.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codes::Kind;
+    use crate::company;
+    use crate::pdf::extract_text;
+    use crate::test_fixtures;
+
+    fn issuer() -> Issuer {
+        Issuer {
+            profile: company::builtin(),
+            logo: company::DEFAULT_LOGO.to_vec(),
+            stamp: company::DEFAULT_STAMP.to_vec(),
+        }
+    }
+
+    #[test]
+    fn pdf_prints_products_and_stamp() {
+        let ctx = test_fixtures::quality_certificate();
+        let code = test_fixtures::code(Kind::QualityCertificate);
+        let pdf = generate_quality_certificate_pdf(&ctx, &issuer(), &code);
+        assert_eq!(pdf, generate_quality_certificate_pdf(&ctx, &issuer(), &code));
+
+        let text = extract_text(&pdf);
+        assert_eq!(text[0], "PO 7.5-03-F01Rev. 8/12012021");
+        assert!(text.contains(&"Data: 02.03.2026".to_string()));
+        assert!(text.contains(&"Ouă M 01.03.2026 ddm 29.03.2026 Lot L0301".to_string()));
+        assert!(text.contains(&"Țara de origine:România".to_string()));
+        assert!(text.contains(&"QKRN-42".to_string()));
+
+        // EPC and ISO logos, the stamp, the QR code and the barcode
+        let content = String::from_utf8_lossy(&pdf);
+        assert_eq!(content.matches("/Subtype /Image").count(), 5);
+    }
+}
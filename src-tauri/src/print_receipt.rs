@@ -1,20 +1,47 @@
-use crate::models::Collection;
-use crate::print_invoice::KARIN;
+use crate::codes::DocumentCode;
+use crate::escpos::{Options, Raster, Ticket};
+use crate::models::{Collection, CollectionStatus, CompanyProfile};
+use crate::pdf::{Align, Flow, Font, Image, LineStyle};
+use crate::templates::{self, CompanyContext, DocType, Images};
+use serde::Serialize;
 
-pub fn generate_receipt_html(
-    collection: &Collection,
-    logo_base64: Option<&str>,
-    doc_series: &str,
-    doc_number: &str,
-    issue_date: &str,
-    agent: Option<&str>,
-    _nume_casa: &str,
-    partner_address: Option<&str>,
-    partner_localitate: Option<&str>,
-    partner_judet: Option<&str>,
-    partner_cui: Option<&str>,
-    partner_reg_com: Option<&str>,
-) -> String {
+/// What a receipt prints besides the collection: its number and date, the
+/// cashier, and the payer's details as WME has them.
+#[derive(Clone, Copy)]
+pub struct ReceiptDetails<'a> {
+    pub series: &'a str,
+    pub number: &'a str,
+    pub issue_date: &'a str,
+    pub agent: Option<&'a str>,
+    pub partner_address: Option<&'a str>,
+    pub partner_localitate: Option<&'a str>,
+    pub partner_judet: Option<&'a str>,
+    pub partner_cui: Option<&'a str>,
+    pub partner_reg_com: Option<&'a str>,
+}
+
+// Display values shared by the HTML, PDF and thermal receipts.
+struct ReceiptFields<'a> {
+    partner_name: &'a str,
+    factura_ref: String,
+    amount_display: String,
+    cashier_display: &'a str,
+    partner_address_display: &'a str,
+    partner_localitate_display: &'a str,
+    partner_judet_display: &'a str,
+    partner_cui_display: &'a str,
+    partner_reg_com_display: &'a str,
+    county: &'a str,
+    sediu_line: String,
+    phone_display: &'a str,
+    email_display: &'a str,
+}
+
+fn receipt_fields<'a>(
+    collection: &'a Collection,
+    company: &'a CompanyProfile,
+    details: ReceiptDetails<'a>,
+) -> ReceiptFields<'a> {
     let partner_name = collection
         .partner_name
         .as_deref()
@@ -31,258 +58,331 @@ pub fn generate_receipt_html(
     };
 
     let amount_display = format!("{:.2}", collection.valoare).replace('.', ",");
-    let cashier_display = agent
+    let cashier_display = details.agent
         .filter(|value| !value.trim().is_empty())
         .unwrap_or("-");
-    let partner_address_display = partner_address
+    let partner_address_display = details.partner_address
         .filter(|value| !value.trim().is_empty())
         .unwrap_or("... nedefinit ...");
-    let partner_localitate_display = partner_localitate
+    let partner_localitate_display = details.partner_localitate
         .filter(|value| !value.trim().is_empty())
         .unwrap_or("... nedefinit ...");
-    let partner_judet_display = partner_judet
+    let partner_judet_display = details.partner_judet
         .filter(|value| !value.trim().is_empty())
         .unwrap_or("... nedefinit ...");
-    let partner_cui_display = partner_cui
+    let partner_cui_display = details.partner_cui
         .filter(|value| !value.trim().is_empty())
         .unwrap_or(collection.id_partener.as_str());
-    let partner_reg_com_display = partner_reg_com
+    let partner_reg_com_display = details.partner_reg_com
         .filter(|value| !value.trim().is_empty())
         .unwrap_or("-");
-    let city = KARIN
-        .localitate
-        .split(',')
-        .next()
-        .map(|value| value.trim())
-        .filter(|value| !value.is_empty())
-        .unwrap_or(KARIN.localitate);
-    let county = KARIN
-        .localitate
-        .split("Jud.")
-        .nth(1)
-        .map(|value| value.trim())
+    let county = Some(company.judet.trim())
         .filter(|value| !value.is_empty())
         .unwrap_or("-");
-    let sediu_line = format!("{}, {} CP.{}", city, KARIN.address, KARIN.cod_postal);
+    let sediu_line = format!("{}, {} CP.{}", company.localitate, company.address, company.cod_postal);
+    let phone_display = company
+        .phone
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or("-");
+    let email_display = company
+        .email
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or("-");
 
-    format!(
-        r####"<!DOCTYPE html>
-<html lang="ro">
-<head>
-    <meta charset="UTF-8">
-    <title>Chitanta KARIN</title>
-    <style>
-        @media print {{
-            @page {{
-                size: 80mm 297mm;
-                margin: 3mm 6mm 3mm 0.5mm;
-            }}
-            body {{
-                margin: 0;
-                padding: 0;
-            }}
-            header, footer {{
-                display: none;
-            }}
-        }}
+    ReceiptFields {
+        partner_name,
+        factura_ref,
+        amount_display,
+        cashier_display,
+        partner_address_display,
+        partner_localitate_display,
+        partner_judet_display,
+        partner_cui_display,
+        partner_reg_com_display,
+        county,
+        sediu_line,
+        phone_display,
+        email_display,
+    }
+}
 
-        body {{
-            font-family: Arial, Helvetica, sans-serif;
-            width: 68mm;
-            margin: 0 auto;
-            padding: 2mm;
-            font-size: 10.5px;
-            font-weight: bold;
-            color: #000000;
-            line-height: 1.15;
-            background: white;
-            box-sizing: border-box;
-        }}
+#[derive(Serialize)]
+pub struct ReceiptPartner<'a> {
+    pub name: &'a str,
+    pub address: &'a str,
+    pub localitate: &'a str,
+    pub judet: &'a str,
+    pub cui: &'a str,
+    pub reg_com: &'a str,
+}
 
-        .page {{
-            width: 100%;
-            display: flex;
-            flex-direction: column;
-            justify-content: flex-start;
-        }}
+/// Data for templates/receipt.html, see templates/README.md.
+#[derive(Serialize)]
+pub struct ReceiptTemplate<'a> {
+    pub company: CompanyContext<'a>,
+    pub images: &'a Images,
+    pub series: &'a str,
+    pub number: &'a str,
+    pub issue_date: &'a str,
+    pub partner: ReceiptPartner<'a>,
+    /// "12,50"
+    pub amount: String,
+    pub value: f64,
+    pub invoice_ref: String,
+    pub representing: String,
+    pub cashier: &'a str,
+    pub footer_lines: Vec<String>,
+    pub preview: bool,
+}
 
-        .top {{
-            display: flex;
-            flex-direction: column;
-            align-items: stretch;
-            gap: 3mm;
-            border-bottom: 1px dashed #000;
-            padding-bottom: 5px;
-            margin-bottom: 8px;
-        }}
+pub fn receipt_template<'a>(
+    collection: &'a Collection,
+    company: &'a CompanyProfile,
+    images: &'a Images,
+    details: ReceiptDetails<'a>,
+) -> ReceiptTemplate<'a> {
+    let fields = receipt_fields(collection, company, details);
 
-        .left-meta, .right-meta {{
-            white-space: pre-line;
-            word-break: break-word;
-        }}
+    ReceiptTemplate {
+        company: templates::company_context(company),
+        images,
+        series: details.series,
+        number: details.number,
+        issue_date: details.issue_date,
+        partner: ReceiptPartner {
+            name: fields.partner_name,
+            address: fields.partner_address_display,
+            localitate: fields.partner_localitate_display,
+            judet: fields.partner_judet_display,
+            cui: fields.partner_cui_display,
+            reg_com: fields.partner_reg_com_display,
+        },
+        amount: fields.amount_display,
+        value: collection.valoare,
+        representing: format!("Încasare factură {}", fields.factura_ref),
+        invoice_ref: fields.factura_ref,
+        cashier: fields.cashier_display,
+        footer_lines: templates::footer_lines(DocType::Receipt, company),
+        preview: false,
+    }
+}
 
-        .left-meta {{
-            width: 100%;
-        }}
+pub fn generate_receipt_html(
+    collection: &Collection,
+    company: &CompanyProfile,
+    images: &Images,
+    details: ReceiptDetails,
+    _nume_casa: &str,
+) -> String {
+    let ctx = receipt_template(collection, company, images, details);
+    templates::render(DocType::Receipt, &ctx)
+}
 
-        .right-meta {{
-            width: 100%;
-            text-align: left;
-        }}
+/// Same layout as `generate_receipt_html`, rendered directly to PDF.
+pub fn generate_receipt_pdf(
+    collection: &Collection,
+    company: &CompanyProfile,
+    logo: Option<&Image>,
+    code: &DocumentCode,
+    details: ReceiptDetails,
+) -> Vec<u8> {
+    let fields = receipt_fields(collection, company, details);
+    let mut flow = Flow::new(80.0, 297.0, [3.0, 6.0, 3.0, 4.0]);
 
-        .title-wrap {{
-            margin-top: 4px;
-            margin-bottom: 6px;
-            text-align: center;
-        }}
+    if collection.status == CollectionStatus::Cancelled {
+        flow.banner("ANULAT", 20.0);
+    }
 
-        .title {{
-            font-size: 18px;
-            text-align: center;
-            margin: 0 0 5px 0;
-            border-bottom: 2px solid #000;
-            text-transform: uppercase;
-            display: inline-block;
-            width: 100%;
-        }}
+    flow.space(1.0);
+    flow.text("CHITANTA", Font::Bold, 13.5, Align::Center);
+    flow.rule(1.5, LineStyle::Solid);
+    flow.text(
+        &format!("Seria: {}\nNumar: {}", details.series, details.number),
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+    flow.underlined(&format!("DATA: {}", details.issue_date), Font::Bold, 7.9, Align::Left);
+    flow.space(3.0);
 
-        .section {{
-            margin-bottom: 8px;
-            border-bottom: 1px dashed #000;
-            padding-bottom: 5px;
-            word-wrap: break-word;
-        }}
+    flow.underlined("FURNIZOR:", Font::Bold, 10.5, Align::Left);
+    flow.text(
+        &format!(
+            "{}\nNR..INM. {}\nC.U.I.: {}\nSediul: {}\nJud.: {}\nCapital social: {}\nTel.: {}\nE-mail: {}",
+            company.name,
+            company.reg_com,
+            company.cif,
+            fields.sediu_line,
+            fields.county,
+            company.capital,
+            fields.phone_display,
+            fields.email_display
+        ),
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+    flow.rule(0.75, LineStyle::Dashed);
+    flow.space(1.0);
 
-        .row-label {{
-            margin-bottom: 2px;
-            text-decoration: underline;
-            font-size: 14px;
-        }}
+    flow.underlined("AM PRIMIT DE LA:", Font::Bold, 10.5, Align::Left);
+    flow.underlined(fields.partner_name, Font::Bold, 7.9, Align::Left);
+    flow.text(
+        &format!(
+            "Adresa: {}\nLocalitatea {}, Judetul {}\nCUI: {}\nNr. Inm. {}",
+            fields.partner_address_display,
+            fields.partner_localitate_display,
+            fields.partner_judet_display,
+            fields.partner_cui_display,
+            fields.partner_reg_com_display
+        ),
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+    flow.underlined(&format!("SUMA DE: {} LEI", fields.amount_display), Font::Bold, 7.9, Align::Left);
+    flow.text(
+        &format!("Reprezentand: Încasare factură {}", fields.factura_ref),
+        Font::Bold,
+        7.9,
+        Align::Left,
+    );
+    flow.rule(0.75, LineStyle::Dashed);
 
-        .details {{
-            margin-top: 2px;
-            white-space: pre-line;
-            word-break: break-word;
-        }}
+    flow.space(7.0);
+    flow.text(&format!("CASIER,\n{}", fields.cashier_display), Font::Bold, 7.9, Align::Right);
+    code.draw_pdf(&mut flow);
 
-        .cashier {{
-            margin-top: 7mm;
-            text-align: right;
-        }}
+    flow.space(5.0);
+    if let Some(logo) = logo {
+        flow.image(logo, 66.0, 48.0, Align::Center);
+    }
+    flow.space(2.0);
+    let footer = templates::footer_lines(DocType::Receipt, company);
+    if !footer.is_empty() {
+        flow.text(&footer.join("\n"), Font::Bold, 10.5, Align::Center);
+    }
 
-        .logo-wrap {{
-            margin-top: 5mm;
-            text-align: center;
-        }}
+    flow.finish()
+}
 
-        .footer-logo {{
-            width: 100%;
-            max-width: 66mm;
-            max-height: 48mm;
-            height: auto;
-            object-fit: contain;
-        }}
+/// Same content as `generate_receipt_html`, as ESC/POS for a thermal printer.
+pub fn generate_receipt_escpos(
+    collection: &Collection,
+    company: &CompanyProfile,
+    logo: Option<&Raster>,
+    code: &DocumentCode,
+    options: &Options,
+    details: ReceiptDetails,
+) -> Vec<u8> {
+    let fields = receipt_fields(collection, company, details);
+    let mut ticket = Ticket::new(options);
 
-        .printed-by {{
-            margin-top: 2mm;
-            font-size: 14px;
-            font-weight: bold;
-            text-align: center;
-        }}
+    if collection.status == CollectionStatus::Cancelled {
+        ticket.banner("ANULAT");
+    }
 
-        .underlined {{
-            border-bottom: 1px dotted #000;
-            padding: 0 4px;
-        }}
-    </style>
-</head>
-<body>
-    <div class="page">
-        <div>
-            <div class="top">
-                <div class="right-meta">
-                    <div class="title-wrap">
-                        <p class="title">CHITANTA</p>
-                    </div>
+    ticket.align(Align::Center);
+    ticket.bold(true);
+    ticket.double(true);
+    ticket.text("CHITANTA");
+    ticket.double(false);
+    ticket.align(Align::Left);
+    ticket.rule('=');
+    ticket.text(&format!("Seria: {}\nNumar: {}\nDATA: {}", details.series, details.number, details.issue_date));
+    ticket.rule('-');
 
-Seria: {}
-Numar: {}
-DATA: <span class="underlined">{}</span></div>
+    ticket.underline(true);
+    ticket.text("FURNIZOR:");
+    ticket.underline(false);
+    ticket.bold(false);
+    ticket.text(&format!(
+        "{}\nNR..INM. {}\nC.U.I.: {}\nSediul: {}\nJud.: {}\nCapital social: {}\nTel.: {}\nE-mail: {}",
+        company.name,
+        company.reg_com,
+        company.cif,
+        fields.sediu_line,
+        fields.county,
+        company.capital,
+        fields.phone_display,
+        fields.email_display
+    ));
+    ticket.rule('-');
 
-                <div class="left-meta"><span style="text-decoration: underline; font-size: 14px;">FURNIZOR:</span>
-{}
-NR..INM. {}
-C.U.I.: {}
-Sediul: {}
-Jud.: {}
-Capital social: {}
-Tel.: {}
-E-mail: {}</div>
-            </div>
+    ticket.bold(true);
+    ticket.underline(true);
+    ticket.text("AM PRIMIT DE LA:");
+    ticket.underline(false);
+    ticket.text(fields.partner_name);
+    ticket.bold(false);
+    ticket.text(&format!(
+        "Adresa: {}\nLocalitatea {}, Judetul {}\nCUI: {}\nNr. Inm. {}",
+        fields.partner_address_display,
+        fields.partner_localitate_display,
+        fields.partner_judet_display,
+        fields.partner_cui_display,
+        fields.partner_reg_com_display
+    ));
+    ticket.bold(true);
+    ticket.row("SUMA DE:", &format!("{} LEI", fields.amount_display));
+    ticket.bold(false);
+    ticket.text(&format!("Reprezentand: Încasare factură {}", fields.factura_ref));
+    ticket.rule('-');
 
-            <div class="section">
-                <div class="row-label">AM PRIMIT DE LA:</div>
-                <div class="details"><span class="underlined">{}</span>
-Adresa: {}
-Localitatea {}, Judetul {}
-CUI: {}
-Nr. Inm. {}
-SUMA DE: <span class="underlined">{} LEI</span>
-Reprezentand: {}</div>
-            </div>
+    ticket.feed(2);
+    ticket.align(Align::Right);
+    ticket.text(&format!("CASIER,\n{}", fields.cashier_display));
+    ticket.align(Align::Left);
+    ticket.feed(2);
+    code.print_escpos(&mut ticket);
 
-            <div class="cashier">CASIER,
-{}</div>
-        </div>
+    if let Some(logo) = logo {
+        ticket.raster(logo);
+    }
+    ticket.align(Align::Center);
+    for line in templates::footer_lines(DocType::Receipt, company) {
+        ticket.text(&line);
+    }
+    ticket.align(Align::Left);
 
-        <div class="logo-wrap">{}
-            <div class="printed-by">printed by eSoft</div>
-        </div>
-    </div>
+    ticket.finish()
+}
 
-    <script>
-        function triggerPrint() {{
-            window.print();
-        }}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codes::Kind;
+    use crate::company;
+    use crate::pdf::extract_text;
+    use crate::test_fixtures;
 
-        if (document.readyState === "loading") {{
-            document.addEventListener("DOMContentLoaded", function() {{
-                setTimeout(triggerPrint, 300);
-            }});
-        }} else {{
-            triggerPrint();
-        }}
+    fn pdf(collection: &Collection) -> Vec<u8> {
+        generate_receipt_pdf(
+            collection,
+            &company::builtin(),
+            None,
+            &test_fixtures::code(Kind::Receipt),
+            test_fixtures::receipt_details(),
+        )
+    }
 
-        window.addEventListener("load", function() {{
-            setTimeout(triggerPrint, 100);
-        }});
-    </script>
-</body>
-</html>"####,
-        doc_series,
-        doc_number,
-        issue_date,
-    KARIN.name,
-    KARIN.reg_com,
-    KARIN.cif,
-    sediu_line,
-    county,
-    KARIN.capital,
-    "0753068450",
-    "nasesem@yahoo.com",
-        partner_name,
-        partner_address_display,
-        partner_localitate_display,
-        partner_judet_display,
-        partner_cui_display,
-        partner_reg_com_display,
-        amount_display,
-        format!("Încasare factură {}", factura_ref),
-        cashier_display,
-        if let Some(logo) = logo_base64 {
-            format!(r#"<img src="{}" class="footer-logo" alt="Logo" />"#, logo)
-        } else {
-            String::new()
-        }
-    )
-}
\ No newline at end of file
+    #[test]
+    fn pdf_is_deterministic() {
+        let collection = test_fixtures::collection();
+        assert_eq!(pdf(&collection), pdf(&collection));
+    }
+
+    #[test]
+    fn pdf_prints_payer_amount_and_cashier() {
+        let text = extract_text(&pdf(&test_fixtures::collection()));
+        assert_eq!(text[..4], ["CHITANTA", "Seria: CH", "Numar: 7", "DATA: 02.03.2026"]);
+        assert!(text.contains(&"Ștefănescu Țară SRL".to_string()));
+        assert!(text.contains(&"Localitatea Iași, Judetul Iași".to_string()));
+        assert!(text.contains(&"SUMA DE: 154,70 LEI".to_string()));
+        assert!(text.contains(&"Reprezentand: Încasare factură KRN/42".to_string()));
+        assert!(text.contains(&"Ion Popescu".to_string()));
+        assert_eq!(text.last().unwrap(), "printed by eSoft");
+    }
+}
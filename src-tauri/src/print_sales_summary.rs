@@ -0,0 +1,279 @@
+use crate::escpos::{Options, Ticket};
+use crate::models::{DailyCollectionsReport, SalesPrintItem};
+use crate::pdf::{Align, Flow, Font, LineStyle};
+
+// "Centralizator zi": the invoices of the period totalled per partner, then
+// the receipts of the day, on 80mm paper. Partners are shown by the first
+// seven characters of their name, the code the agents know them by.
+
+pub struct SalesSummaryRow {
+    pub code: String,
+    pub quantity: f64,
+    pub cofrage: f64,
+    pub without_vat: f64,
+    pub with_vat: f64,
+}
+
+fn partner_code(name: &str) -> String {
+    name.chars().take(7).collect()
+}
+
+/// Sales rows come per partner and payment section; the summary has one row
+/// per partner, in name order.
+pub fn rows_by_partner(sales: &[SalesPrintItem]) -> Vec<SalesSummaryRow> {
+    let mut names: Vec<&str> = sales.iter().map(|item| item.partner_name.as_str()).collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let items = sales.iter().filter(|item| item.partner_name == name);
+            let mut row = SalesSummaryRow {
+                code: partner_code(name),
+                quantity: 0.0,
+                cofrage: 0.0,
+                without_vat: 0.0,
+                with_vat: 0.0,
+            };
+            for item in items {
+                row.quantity += item.total_quantity;
+                row.cofrage += item.total_cofrage;
+                row.without_vat += item.total_without_vat;
+                row.with_vat += item.total_with_vat;
+            }
+            row
+        })
+        .collect()
+}
+
+/// YYYY-MM-DD as DD.MM.YYYY
+fn date_label(date: &str) -> String {
+    let parts: Vec<&str> = date.split('-').collect();
+    match parts.as_slice() {
+        [year, month, day] => format!("{}.{}.{}", day, month, year),
+        _ => date.to_string(),
+    }
+}
+
+struct Totals {
+    quantity: f64,
+    cofrage: f64,
+    without_vat: f64,
+    with_vat: f64,
+    receipts_day: f64,
+    receipts_debt: f64,
+}
+
+fn totals(rows: &[SalesSummaryRow], collections: &DailyCollectionsReport) -> Totals {
+    Totals {
+        quantity: rows.iter().map(|row| row.quantity).sum(),
+        cofrage: rows.iter().map(|row| row.cofrage).sum(),
+        without_vat: rows.iter().map(|row| row.without_vat).sum(),
+        with_vat: rows.iter().map(|row| row.with_vat).sum(),
+        receipts_day: collections.items.iter().map(|item| item.amount_from_today_sales).sum(),
+        receipts_debt: collections.items.iter().map(|item| item.amount_from_previous_debt).sum(),
+    }
+}
+
+/// The day summary as an 80mm PDF.
+pub fn generate_sales_summary_pdf(
+    sales: &[SalesPrintItem],
+    collections: &DailyCollectionsReport,
+    date: &str,
+) -> Vec<u8> {
+    let rows = rows_by_partner(sales);
+    let totals = totals(&rows, collections);
+    let date = date_label(date);
+    let mut flow = Flow::new(80.0, 297.0, [3.0, 6.0, 3.0, 0.5]);
+
+    flow.text("CENTRALIZATOR ZI", Font::Bold, 14.0, Align::Center);
+    flow.rule(1.5, LineStyle::Solid);
+
+    flow.space(2.0);
+    flow.underlined(&format!("Facturi eliberate {}", date), Font::Bold, 10.0, Align::Left);
+    let header = [
+        ("Cod ext", 0.0, Align::Left),
+        ("Ouă", 11.0, Align::Right),
+        ("Cof", 10.0, Align::Right),
+        ("Fără TVA", 15.0, Align::Right),
+        ("Cu TVA", 15.0, Align::Right),
+    ];
+    flow.columns(&header, Font::Bold, 7.5);
+    flow.rule(0.75, LineStyle::Solid);
+    if rows.is_empty() {
+        flow.text("Fără date", Font::Bold, 7.5, Align::Left);
+    }
+    for row in &rows {
+        let quantity = format!("{:.0}", row.quantity);
+        let cofrage = format!("{:.2}", row.cofrage);
+        let without_vat = format!("{:.2}", row.without_vat);
+        let with_vat = format!("{:.2}", row.with_vat);
+        flow.columns(
+            &[
+                (row.code.as_str(), 0.0, Align::Left),
+                (quantity.as_str(), 11.0, Align::Right),
+                (cofrage.as_str(), 10.0, Align::Right),
+                (without_vat.as_str(), 15.0, Align::Right),
+                (with_vat.as_str(), 15.0, Align::Right),
+            ],
+            Font::Bold,
+            7.5,
+        );
+    }
+    flow.rule(0.75, LineStyle::Solid);
+    flow.text(
+        &format!(
+            "Total facturi: Ouă {:.0} | Cofraje {:.2} | Fără TVA {:.2} | Cu TVA {:.2}",
+            totals.quantity, totals.cofrage, totals.without_vat, totals.with_vat
+        ),
+        Font::Bold,
+        8.0,
+        Align::Left,
+    );
+
+    flow.space(4.0);
+    flow.underlined(&format!("Chitanțe {}", date), Font::Bold, 10.0, Align::Left);
+    flow.columns(&[("Cod ext", 0.0, Align::Left), ("Încasat", 30.0, Align::Right)], Font::Bold, 7.5);
+    flow.rule(0.75, LineStyle::Solid);
+    if collections.items.is_empty() {
+        flow.text("Fără date", Font::Bold, 7.5, Align::Left);
+    }
+    for item in &collections.items {
+        let code = partner_code(&item.partner_name);
+        let amount = format!("{:.2}", item.total_amount);
+        flow.columns(
+            &[(code.as_str(), 0.0, Align::Left), (amount.as_str(), 30.0, Align::Right)],
+            Font::Bold,
+            7.5,
+        );
+    }
+    flow.rule(0.75, LineStyle::Solid);
+    flow.text(
+        &format!(
+            "Total chitanțe: Total chitanțe pe zi {} | Total chitanțe pe solduri {} | \
+             Total încasat pe zi {:.2} | Total din solduri {:.2} | Total general {:.2}",
+            collections.receipts_today_invoices_count,
+            collections.receipts_previous_debt_count,
+            totals.receipts_day,
+            totals.receipts_debt,
+            totals.receipts_day + totals.receipts_debt
+        ),
+        Font::Bold,
+        8.0,
+        Align::Left,
+    );
+
+    flow.finish()
+}
+
+/// The day summary for a thermal printer (ESC/POS).
+pub fn generate_sales_summary_escpos(
+    sales: &[SalesPrintItem],
+    collections: &DailyCollectionsReport,
+    date: &str,
+    options: &Options,
+) -> Vec<u8> {
+    let rows = rows_by_partner(sales);
+    let totals = totals(&rows, collections);
+    let date = date_label(date);
+    let mut ticket = Ticket::new(options);
+    let amount_width = if options.line_chars() > 32 { 10 } else { 8 };
+
+    ticket.align(Align::Center);
+    ticket.bold(true);
+    ticket.text("CENTRALIZATOR ZI");
+    ticket.bold(false);
+    ticket.align(Align::Left);
+    ticket.rule('=');
+
+    ticket.bold(true);
+    ticket.text(&format!("Facturi eliberate {}", date));
+    ticket.bold(false);
+    ticket.columns(&[
+        ("Cod ext", 0, Align::Left),
+        ("Oua", 6, Align::Right),
+        ("Fara TVA", amount_width, Align::Right),
+        ("Cu TVA", amount_width, Align::Right),
+    ]);
+    ticket.rule('-');
+    if rows.is_empty() {
+        ticket.text("Fără date");
+    }
+    for row in &rows {
+        let quantity = format!("{:.0}", row.quantity);
+        let without_vat = format!("{:.2}", row.without_vat);
+        let with_vat = format!("{:.2}", row.with_vat);
+        ticket.columns(&[
+            (row.code.as_str(), 0, Align::Left),
+            (quantity.as_str(), 6, Align::Right),
+            (without_vat.as_str(), amount_width, Align::Right),
+            (with_vat.as_str(), amount_width, Align::Right),
+        ]);
+    }
+    ticket.rule('-');
+    ticket.row("Ouă:", &format!("{:.0}", totals.quantity));
+    ticket.row("Cofraje:", &format!("{:.2}", totals.cofrage));
+    ticket.row("Fără TVA:", &format!("{:.2}", totals.without_vat));
+    ticket.bold(true);
+    ticket.row("Total facturi:", &format!("{:.2}", totals.with_vat));
+    ticket.bold(false);
+    ticket.feed(1);
+
+    ticket.bold(true);
+    ticket.text(&format!("Chitanțe {}", date));
+    ticket.bold(false);
+    ticket.columns(&[("Cod ext", 0, Align::Left), ("Încasat", amount_width, Align::Right)]);
+    ticket.rule('-');
+    if collections.items.is_empty() {
+        ticket.text("Fără date");
+    }
+    for item in &collections.items {
+        let code = partner_code(&item.partner_name);
+        let amount = format!("{:.2}", item.total_amount);
+        ticket.columns(&[(code.as_str(), 0, Align::Left), (amount.as_str(), amount_width, Align::Right)]);
+    }
+    ticket.rule('-');
+    ticket.row("Chitanțe pe zi:", &collections.receipts_today_invoices_count.to_string());
+    ticket.row("Chitanțe pe solduri:", &collections.receipts_previous_debt_count.to_string());
+    ticket.row("Încasat pe zi:", &format!("{:.2}", totals.receipts_day));
+    ticket.row("Din solduri:", &format!("{:.2}", totals.receipts_debt));
+    ticket.rule('=');
+    ticket.bold(true);
+    ticket.row("TOTAL GENERAL:", &format!("{:.2} RON", totals.receipts_day + totals.receipts_debt));
+    ticket.bold(false);
+
+    ticket.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::extract_text;
+    use crate::test_fixtures;
+
+    #[test]
+    fn rows_are_one_per_partner_in_name_order() {
+        let rows = rows_by_partner(&test_fixtures::sales());
+        let codes: Vec<&str> = rows.iter().map(|row| row.code.as_str()).collect();
+        assert_eq!(codes, ["Aliment", "Ștefăne"]);
+        assert_eq!(rows[1].quantity, 360.0);
+        assert_eq!(rows[1].without_vat, 324.0);
+    }
+
+    #[test]
+    fn pdf_prints_both_tables_and_totals() {
+        let sales = test_fixtures::sales();
+        let collections = test_fixtures::collections_report();
+        let pdf = generate_sales_summary_pdf(&sales, &collections, "2026-03-02");
+        assert_eq!(pdf, generate_sales_summary_pdf(&sales, &collections, "2026-03-02"));
+
+        let text = extract_text(&pdf);
+        assert_eq!(text[..2], ["CENTRALIZATOR ZI", "Facturi eliberate 02.03.2026"]);
+        assert_eq!(text[7..17], ["Aliment", "90", "3.00", "81.00", "88.29", "Ștefăne", "360", "12.00", "324.00", "353.16"]);
+        assert_eq!(text[17], "Total facturi: Ouă 450 | Cofraje 15.00 | Fără TVA 405.00");
+        assert_eq!(text[19], "Chitanțe 02.03.2026");
+        assert_eq!(text[22..26], ["Ștefăne", "200.00", "Aliment", "88.29"]);
+        assert!(text.last().unwrap().ends_with("Total general 288.29"));
+    }
+}
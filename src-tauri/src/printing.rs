@@ -0,0 +1,604 @@
+use chrono::Utc;
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::escpos;
+use crate::models::{PrintRoute, PrinterProfile};
+
+// Printer profiles and per-document routing.
+//
+// A profile says how to reach a printer (system queue, SumatraPDF, lp or an
+// ESC/POS device) and how to print on it: paper, copies, duplex. The routes
+// table maps each document type to a profile, optionally with its own number
+// of copies, so the print commands know where a document goes without the UI
+// passing a printer. A printer passed explicitly by the UI still wins, for
+// that one print.
+
+pub const INVOICE: &str = "invoice";
+pub const QUALITY_CERTIFICATE: &str = "quality_certificate";
+pub const RECEIPT: &str = "receipt";
+pub const DAILY_REPORT: &str = "daily_report";
+pub const REPORT: &str = "report";
+
+pub const DOC_TYPES: [&str; 5] = [INVOICE, QUALITY_CERTIFICATE, RECEIPT, DAILY_REPORT, REPORT];
+
+pub const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS printer_profiles (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        printer TEXT,
+        backend TEXT NOT NULL DEFAULT 'system',
+        paper_size TEXT,
+        copies INTEGER NOT NULL DEFAULT 1,
+        duplex INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS print_routes (
+        doc_type TEXT PRIMARY KEY,
+        profile_id INTEGER REFERENCES printer_profiles(id) ON DELETE SET NULL,
+        copies INTEGER,
+        updated_at TEXT NOT NULL
+    );
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// The operating system's print handler for the file
+    System,
+    Sumatra,
+    EscPos,
+    Lp,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 4] = [Backend::System, Backend::Sumatra, Backend::EscPos, Backend::Lp];
+
+    pub fn parse(name: &str) -> Result<Backend, String> {
+        Backend::ALL
+            .into_iter()
+            .find(|backend| backend.name() == name)
+            .ok_or_else(|| format!("Mod de printare necunoscut: {}", name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::System => "system",
+            Backend::Sumatra => "sumatra",
+            Backend::EscPos => "escpos",
+            Backend::Lp => "lp",
+        }
+    }
+
+    /// What the print commands used before profiles existed
+    pub fn platform_default() -> Backend {
+        if cfg!(target_os = "windows") {
+            Backend::Sumatra
+        } else {
+            Backend::Lp
+        }
+    }
+}
+
+pub fn doc_type(name: &str) -> Result<&'static str, String> {
+    DOC_TYPES
+        .into_iter()
+        .find(|doc_type| *doc_type == name)
+        .ok_or_else(|| format!("Tip de document necunoscut: {}", name))
+}
+
+fn read_profile(row: &rusqlite::Row) -> rusqlite::Result<PrinterProfile> {
+    Ok(PrinterProfile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        printer: row.get(2)?,
+        backend: row.get(3)?,
+        paper_size: row.get(4)?,
+        copies: row.get(5)?,
+        duplex: row.get::<_, i64>(6)? != 0,
+    })
+}
+
+const PROFILE_COLUMNS: &str = "id, name, printer, backend, paper_size, copies, duplex";
+
+pub fn list_profiles(conn: &Connection) -> rusqlite::Result<Vec<PrinterProfile>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM printer_profiles ORDER BY name, id",
+        PROFILE_COLUMNS
+    ))?;
+    let profiles = stmt.query_map([], read_profile)?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(profiles)
+}
+
+pub fn load_profile(conn: &Connection, profile_id: i64) -> rusqlite::Result<Option<PrinterProfile>> {
+    conn.query_row(
+        &format!("SELECT {} FROM printer_profiles WHERE id = ?1", PROFILE_COLUMNS),
+        [profile_id],
+        read_profile,
+    )
+    .optional()
+}
+
+/// Inserts or updates a profile and returns its id.
+pub fn save_profile(conn: &Connection, profile: &PrinterProfile) -> rusqlite::Result<i64> {
+    let now = Utc::now().to_rfc3339();
+    match profile.id {
+        Some(id) => {
+            conn.execute(
+                "UPDATE printer_profiles SET name = ?1, printer = ?2, backend = ?3, paper_size = ?4,
+                        copies = ?5, duplex = ?6, updated_at = ?7
+                 WHERE id = ?8",
+                params![
+                    profile.name,
+                    profile.printer,
+                    profile.backend,
+                    profile.paper_size,
+                    profile.copies,
+                    profile.duplex as i64,
+                    now,
+                    id
+                ],
+            )?;
+            Ok(id)
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO printer_profiles (name, printer, backend, paper_size, copies, duplex, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                params![
+                    profile.name,
+                    profile.printer,
+                    profile.backend,
+                    profile.paper_size,
+                    profile.copies,
+                    profile.duplex as i64,
+                    now
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
+
+/// Deletes a profile; documents routed to it go back to the default printer.
+pub fn delete_profile(conn: &Connection, profile_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE print_routes SET profile_id = NULL, updated_at = ?1 WHERE profile_id = ?2",
+        params![Utc::now().to_rfc3339(), profile_id],
+    )?;
+    conn.execute("DELETE FROM printer_profiles WHERE id = ?1", [profile_id])?;
+    Ok(())
+}
+
+/// One route per document type, unconfigured ones included.
+pub fn routes(conn: &Connection) -> rusqlite::Result<Vec<PrintRoute>> {
+    DOC_TYPES
+        .iter()
+        .map(|doc_type| {
+            let saved = conn
+                .query_row(
+                    "SELECT profile_id, copies FROM print_routes WHERE doc_type = ?1",
+                    [doc_type],
+                    |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+                )
+                .optional()?;
+            let (profile_id, copies) = saved.unwrap_or((None, None));
+            Ok(PrintRoute {
+                doc_type: doc_type.to_string(),
+                profile_id,
+                copies,
+            })
+        })
+        .collect()
+}
+
+pub fn save_route(conn: &Connection, route: &PrintRoute) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO print_routes (doc_type, profile_id, copies, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(doc_type) DO UPDATE SET profile_id = excluded.profile_id, copies = excluded.copies,
+                updated_at = excluded.updated_at",
+        params![route.doc_type, route.profile_id, route.copies, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Where one document is printed and in how many copies. Print jobs keep
+/// these values, so a retry goes where the first attempt went.
+#[derive(Debug, Clone)]
+pub struct Destination {
+    pub doc_type: &'static str,
+    pub profile_name: Option<String>,
+    /// Printer name, or the ESC/POS target for `Backend::EscPos`
+    pub printer: Option<String>,
+    pub backend: Backend,
+    pub paper_size: Option<String>,
+    pub duplex: bool,
+    pub copies: u32,
+}
+
+impl Destination {
+    fn default_printer(doc_type: &'static str, copies: u32) -> Destination {
+        Destination {
+            doc_type,
+            profile_name: None,
+            printer: None,
+            backend: Backend::platform_default(),
+            paper_size: None,
+            duplex: false,
+            copies,
+        }
+    }
+
+    /// Paper width for an ESC/POS profile whose paper is "58mm" or "80mm"
+    pub fn paper_width_mm(&self) -> Option<u32> {
+        thermal_paper_width(self.paper_size.as_deref()?)
+    }
+
+    /// Description stored with the 'printed' audit event
+    pub fn label(&self) -> String {
+        let printer = match &self.printer {
+            Some(printer) => format!("imprimantă: {}", printer),
+            None => "imprimantă implicită".to_string(),
+        };
+        let printer = match &self.profile_name {
+            Some(profile) => format!("{} ({})", printer, profile),
+            None => printer,
+        };
+        if self.copies == 1 {
+            printer
+        } else {
+            format!("{}, {} exemplare", printer, self.copies)
+        }
+    }
+}
+
+/// "58mm"/"80mm" (or just the number) as a thermal paper width
+pub fn thermal_paper_width(paper: &str) -> Option<u32> {
+    match paper.to_lowercase().trim().trim_end_matches("mm").trim() {
+        "58" => Some(58),
+        "80" => Some(80),
+        _ => None,
+    }
+}
+
+fn clean(value: Option<&str>) -> Option<String> {
+    value.map(str::trim).filter(|value| !value.is_empty()).map(str::to_string)
+}
+
+/// Resolves the destination of a document: the configured route, otherwise
+/// the default printer. A printer passed by the UI replaces only the printer
+/// name; the backend, paper and duplex of the routed profile stay. The
+/// number of copies always comes from the route (then its profile), so a
+/// route with 0 copies is not printed.
+pub fn destination(conn: &Connection, doc_type: &'static str, printer_override: Option<&str>) -> Destination {
+    let (profile, route_copies) = match load_route(conn, doc_type) {
+        Ok(found) => found,
+        Err(e) => {
+            warn!("[PRINT] Could not read the route for {}: {}", doc_type, e);
+            (None, None)
+        }
+    };
+
+    let copies = route_copies
+        .or_else(|| profile.as_ref().map(|profile| profile.copies))
+        .unwrap_or(1)
+        .clamp(0, 99) as u32;
+
+    let mut destination = match profile {
+        Some(profile) => {
+            let backend = Backend::parse(&profile.backend).unwrap_or_else(|e| {
+                warn!("[PRINT] Profile {}: {}", profile.name, e);
+                Backend::platform_default()
+            });
+            Destination {
+                doc_type,
+                printer: clean(profile.printer.as_deref()),
+                backend,
+                paper_size: clean(profile.paper_size.as_deref()),
+                duplex: profile.duplex,
+                profile_name: Some(profile.name),
+                copies,
+            }
+        }
+        None => Destination::default_printer(doc_type, copies),
+    };
+    if let Some(printer) = clean(printer_override) {
+        destination.printer = Some(printer);
+    }
+    destination
+}
+
+fn load_route(conn: &Connection, doc_type: &str) -> rusqlite::Result<(Option<PrinterProfile>, Option<i64>)> {
+    let route = conn
+        .query_row(
+            "SELECT profile_id, copies FROM print_routes WHERE doc_type = ?1",
+            [doc_type],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+        )
+        .optional()?;
+
+    match route {
+        Some((Some(profile_id), copies)) => Ok((load_profile(conn, profile_id)?, copies)),
+        Some((None, copies)) => Ok((None, copies)),
+        None => Ok((None, None)),
+    }
+}
+
+/// SumatraPDF: bundled with the app, installed per user or machine wide, or
+/// the portable copy in the app data `tools` folder.
+pub fn find_sumatra() -> Option<PathBuf> {
+    let user_profile = std::env::var("USERPROFILE").unwrap_or_default();
+    let mut candidates: Vec<PathBuf> = Vec::new();
+
+    if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|p| p.to_path_buf())) {
+        candidates.push(dir.join("resources").join("SumatraPDF.exe"));
+    }
+    candidates.push(PathBuf::from(format!(r"{}\AppData\Local\SumatraPDF\SumatraPDF.exe", user_profile)));
+    candidates.push(PathBuf::from(r"C:\Program Files\SumatraPDF\SumatraPDF.exe"));
+    candidates.push(PathBuf::from(r"C:\Program Files (x86)\SumatraPDF\SumatraPDF.exe"));
+    if let Some(dir) = dirs::data_dir() {
+        candidates.push(dir.join("facturi.softconsulting.com").join("tools").join("SumatraPDF.exe"));
+    }
+
+    let found = candidates.into_iter().find(|path| path.exists());
+    if let Some(path) = &found {
+        info!("[PRINT] Found SumatraPDF at: {}", path.display());
+    }
+    found
+}
+
+// Runs a print command and turns a non-zero exit into an error carrying its output
+fn run(command: &mut Command, program: &str) -> Result<(), String> {
+    info!("[PRINT] {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| format!("Nu s-a putut porni {}: {}", program, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() };
+    Err(format!("{} a eșuat ({}): {}", program, output.status, detail))
+}
+
+fn print_sumatra(destination: &Destination, file: &str) -> Result<(), String> {
+    let sumatra = find_sumatra().ok_or(
+        "SumatraPDF not found. Instalează SumatraPDF sau configurează calea aplicației de printare.",
+    )?;
+
+    let mut settings = vec![
+        "noscale".to_string(),
+        format!("{}x", destination.copies),
+        if destination.duplex { "duplex" } else { "simplex" }.to_string(),
+    ];
+    if let Some(paper) = &destination.paper_size {
+        settings.push(format!("paper={}", paper));
+    }
+
+    let mut command = Command::new(sumatra);
+    match &destination.printer {
+        Some(printer) => command.arg("-print-to").arg(printer),
+        None => command.arg("-print-to-default"),
+    };
+    command
+        .arg("-print-settings")
+        .arg(settings.join(","))
+        .arg(file)
+        .arg("-silent")
+        .arg("-exit-when-done")
+        .arg("-exit-on-print");
+    run(&mut command, "SumatraPDF")
+}
+
+fn print_lp(destination: &Destination, file: &str) -> Result<(), String> {
+    // CUPS prints HTML as source text, or not at all
+    if is_html(file) {
+        return Err(format!("lp nu poate printa fișiere HTML: {}", file));
+    }
+
+    let mut command = Command::new("lp");
+    if let Some(printer) = &destination.printer {
+        command.arg("-d").arg(printer);
+    }
+    command.arg("-n").arg(destination.copies.to_string());
+    if destination.duplex {
+        command.arg("-o").arg("sides=two-sided-long-edge");
+    }
+    if let Some(paper) = &destination.paper_size {
+        command.arg("-o").arg(format!("media={}", paper));
+    }
+    command.arg(file);
+    run(&mut command, "lp")
+}
+
+// The handler registered for the file type prints it; it has no copies or
+// paper options, so each copy is a separate job
+fn print_system(destination: &Destination, file: &str) -> Result<(), String> {
+    if !cfg!(target_os = "windows") {
+        return print_lp(destination, file);
+    }
+
+    let quote = |value: &str| format!("'{}'", value.replace('\'', "''"));
+    let script = match &destination.printer {
+        Some(printer) => format!(
+            "Start-Process -FilePath {} -Verb PrintTo -ArgumentList {} -WindowStyle Hidden",
+            quote(file),
+            quote(&format!("\"{}\"", printer))
+        ),
+        None => format!("Start-Process -FilePath {} -Verb Print -WindowStyle Hidden", quote(file)),
+    };
+    for _ in 0..destination.copies {
+        run(Command::new("powershell").args(["-NoProfile", "-Command", &script]), "PowerShell")?;
+    }
+    Ok(())
+}
+
+// The file holds the ESC/POS bytes rendered for the thermal layout
+fn print_escpos(destination: &Destination, file: &str) -> Result<(), String> {
+    let target = escpos::Target::parse(destination.printer.as_deref().unwrap_or(""))?;
+    let bytes = std::fs::read(file).map_err(|e| format!("Nu am putut citi {}: {}", file, e))?;
+    for _ in 0..destination.copies {
+        escpos::send(&target, &bytes)?;
+    }
+    Ok(())
+}
+
+/// Prints a file: a PDF for the system backends, the ESC/POS bytes
+/// for a thermal printer.
+pub fn print_file(destination: &Destination, file: &str) -> Result<(), String> {
+    if destination.copies == 0 {
+        info!("[PRINT] {} routed with 0 copies, not printed: {}", destination.doc_type, file);
+        return Ok(());
+    }
+
+    info!(
+        "[PRINT] {} -> {} via {}: {}",
+        destination.doc_type,
+        destination.label(),
+        destination.backend.name(),
+        file
+    );
+    match destination.backend {
+        Backend::Sumatra => print_sumatra(destination, file),
+        Backend::Lp => print_lp(destination, file),
+        Backend::System => print_system(destination, file),
+        Backend::EscPos => print_escpos(destination, file),
+    }
+}
+
+/// Printers known to CUPS (Linux, macOS), the default one first.
+pub fn cups_printers() -> Result<Vec<String>, String> {
+    let output = Command::new("lpstat")
+        .arg("-e")
+        .output()
+        .map_err(|e| format!("lpstat nu este disponibil (CUPS): {}", e))?;
+
+    // `lpstat -e` lists destination names; older CUPS only has `-p`
+    let mut printers: Vec<String> = if output.status.success() {
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    } else {
+        let output = Command::new("lpstat")
+            .arg("-p")
+            .output()
+            .map_err(|e| format!("lpstat -p: {}", e))?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.strip_prefix("printer "))
+            .filter_map(|rest| rest.split_whitespace().next())
+            .map(str::to_string)
+            .collect()
+    };
+
+    if let Some(default) = cups_default_printer() {
+        if let Some(index) = printers.iter().position(|printer| *printer == default) {
+            let default = printers.remove(index);
+            printers.insert(0, default);
+        }
+    }
+    Ok(printers)
+}
+
+fn cups_default_printer() -> Option<String> {
+    let output = Command::new("lpstat").arg("-d").output().ok()?;
+    // "system default destination: NAME"
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.split_once(':').map(|(_, name)| name.trim().to_string()))
+        .filter(|name| !name.is_empty())
+}
+
+fn is_html(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    fn route_to(conn: &Connection, doc_type: &str, printer: Option<&str>, copies: Option<i64>) {
+        let profile_id = save_profile(
+            conn,
+            &PrinterProfile {
+                id: None,
+                name: "Termică mașină".to_string(),
+                printer: printer.map(str::to_string),
+                backend: Backend::EscPos.name().to_string(),
+                paper_size: Some("58mm".to_string()),
+                copies: 2,
+                duplex: true,
+            },
+        )
+        .unwrap();
+        save_route(
+            conn,
+            &PrintRoute {
+                doc_type: doc_type.to_string(),
+                profile_id: Some(profile_id),
+                copies,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn routed_profile_gives_the_destination() {
+        let conn = test_db();
+        route_to(&conn, RECEIPT, Some("tcp://192.168.1.50:9100"), None);
+        let destination = destination(&conn, RECEIPT, None);
+        assert_eq!(destination.printer.as_deref(), Some("tcp://192.168.1.50:9100"));
+        assert_eq!(destination.backend, Backend::EscPos);
+        assert_eq!(destination.paper_width_mm(), Some(58));
+        assert!(destination.duplex);
+        assert_eq!(destination.copies, 2);
+        assert_eq!(destination.profile_name.as_deref(), Some("Termică mașină"));
+    }
+
+    #[test]
+    fn printer_from_the_ui_replaces_only_the_printer_name() {
+        let conn = test_db();
+        route_to(&conn, RECEIPT, Some("tcp://192.168.1.50:9100"), Some(3));
+        let destination = destination(&conn, RECEIPT, Some(" /dev/usb/lp0 "));
+        assert_eq!(destination.printer.as_deref(), Some("/dev/usb/lp0"));
+        assert_eq!(destination.backend, Backend::EscPos);
+        assert_eq!(destination.paper_size.as_deref(), Some("58mm"));
+        assert!(destination.duplex);
+        assert_eq!(destination.copies, 3);
+        assert_eq!(destination.profile_name.as_deref(), Some("Termică mașină"));
+    }
+
+    #[test]
+    fn printer_named_default_is_kept() {
+        let conn = test_db();
+        assert_eq!(destination(&conn, INVOICE, Some("Default")).printer.as_deref(), Some("Default"));
+        route_to(&conn, INVOICE, Some("default"), None);
+        assert_eq!(destination(&conn, INVOICE, None).printer.as_deref(), Some("default"));
+        assert_eq!(destination(&conn, INVOICE, Some("  ")).printer.as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn unrouted_document_goes_to_the_default_printer() {
+        let conn = test_db();
+        let destination = destination(&conn, REPORT, None);
+        assert_eq!(destination.printer, None);
+        assert_eq!(destination.backend, Backend::platform_default());
+        assert_eq!(destination.copies, 1);
+        assert_eq!(destination.label(), "imprimantă implicită");
+    }
+}
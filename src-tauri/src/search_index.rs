@@ -0,0 +1,396 @@
+use rusqlite::{params, Connection};
+
+// Full-text search index for partners and products.
+//
+// Text is folded (lowercase, no diacritics) before it is written to the FTS5
+// tables and before the user query is turned into a MATCH expression, so
+// "stefanescu" finds "Ștefănescu" regardless of how the SQLite tokenizer
+// treats comma-below letters. Codes (CIF, reg. com., phone, cod extern) are
+// indexed in several normalized variants so the agent can type them in any
+// format.
+
+pub const SCHEMA: &str = r#"
+    CREATE VIRTUAL TABLE IF NOT EXISTS partner_search USING fts5(
+        partner_id UNINDEXED,
+        name,
+        codes,
+        locations,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+
+    CREATE VIRTUAL TABLE IF NOT EXISTS product_search USING fts5(
+        product_id UNINDEXED,
+        name,
+        codes,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+"#;
+
+/// Maximum number of ranked hits returned by a search.
+pub const MAX_RESULTS: i64 = 200;
+
+/// Lowercases the text and strips diacritics (Romanian and Hungarian letters
+/// plus the common Latin-1 accents).
+pub fn fold_diacritics(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|ch| ch.to_lowercase())
+        .map(|ch| match ch {
+            'ă' | 'â' | 'á' | 'à' | 'ä' | 'ã' | 'å' => 'a',
+            'î' | 'í' | 'ì' | 'ï' => 'i',
+            'ș' | 'ş' | 'š' | 'ś' => 's',
+            'ț' | 'ţ' | 'ť' => 't',
+            'é' | 'è' | 'ê' | 'ë' | 'ě' => 'e',
+            'ó' | 'ò' | 'ô' | 'ö' | 'ő' | 'õ' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' | 'ű' => 'u',
+            'ç' | 'č' | 'ć' => 'c',
+            'ñ' | 'ń' => 'n',
+            'ž' | 'ź' | 'ż' => 'z',
+            'ł' => 'l',
+            'ř' => 'r',
+            'ý' => 'y',
+            'đ' | 'ď' => 'd',
+            other => other,
+        })
+        .collect()
+}
+
+fn compact(value: &str) -> String {
+    fold_diacritics(value)
+        .chars()
+        .filter(|ch| ch.is_alphanumeric())
+        .collect()
+}
+
+fn digits_only(value: &str) -> String {
+    value.chars().filter(|ch| ch.is_ascii_digit()).collect()
+}
+
+/// Collects every searchable variant of a code: the folded raw text, the
+/// compacted form ("RO 123-45" -> "ro12345"), the digits only ("12345") and,
+/// for phone numbers, the national form without the 0 / 40 / +40 prefix.
+fn code_variants(value: &str, out: &mut Vec<String>) {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return;
+    }
+
+    let mut push = |variant: String| {
+        if !variant.is_empty() && !out.contains(&variant) {
+            out.push(variant);
+        }
+    };
+
+    push(fold_diacritics(trimmed));
+    push(compact(trimmed));
+
+    let digits = digits_only(trimmed);
+    if digits.len() >= 3 {
+        if let Some(national) = digits.strip_prefix("40").filter(|d| d.len() >= 9) {
+            push(national.to_string());
+        }
+        if let Some(local) = digits.strip_prefix('0').filter(|d| d.len() >= 8) {
+            push(local.to_string());
+        }
+        push(digits);
+    }
+}
+
+fn join_codes<'a>(values: impl IntoIterator<Item = &'a Option<String>>) -> String {
+    let mut variants = Vec::new();
+    for value in values.into_iter().flatten() {
+        code_variants(value, &mut variants);
+    }
+    variants.join(" ")
+}
+
+/// Turns free text typed by the agent into an FTS5 MATCH expression.
+/// Every word becomes a quoted prefix term, so "stef bai" matches
+/// "Ștefănescu Baia Mare". A word with punctuation is split where the
+/// unicode61 tokenizer splits it and matched as a phrase ("coca-cola" finds
+/// "Coca-Cola HBC", "s.r.l." finds "Alfa S.R.L."); its compacted form is also
+/// tried against the codes, which are indexed compacted ("ro-123" finds
+/// "RO123"). Returns `None` when nothing searchable is left.
+pub fn build_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter_map(|word| {
+            let folded = fold_diacritics(word);
+            let tokens: Vec<&str> = folded
+                .split(|ch: char| !ch.is_alphanumeric())
+                .filter(|token| !token.is_empty())
+                .collect();
+            match tokens.as_slice() {
+                [] => None,
+                [token] => Some(format!("\"{}\"*", token)),
+                _ => Some(format!("(\"{}\"* OR codes : \"{}\"*)", tokens.join(" "), tokens.concat())),
+            }
+        })
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" AND "))
+    }
+}
+
+/// name, cif, reg_com, cod, cod_intern, cod_extern
+type PartnerRow = (String, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>);
+/// name, localitate, strada, telefon, cod_sediu
+type LocationRow = (String, Option<String>, Option<String>, Option<String>, Option<String>);
+/// id, name, class, cod_obiect, cod_articol, cod_extern, cod_intern
+type ProductRow = (String, String, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>);
+
+fn index_partner(conn: &Connection, partner_id: &str) -> rusqlite::Result<()> {
+    let (name, cif, reg_com, cod, cod_intern, cod_extern): PartnerRow = conn.query_row(
+        "SELECT name, cif, reg_com, cod, cod_intern, cod_extern FROM partners WHERE id = ?1",
+        [partner_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT name, localitate, strada, telefon, cod_sediu FROM locations WHERE partner_id = ?1",
+    )?;
+    let location_rows: Vec<LocationRow> = stmt
+        .query_map([partner_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut location_text = Vec::new();
+    let mut phone_codes = Vec::new();
+    for (loc_name, localitate, strada, telefon, cod_sediu) in &location_rows {
+        location_text.push(fold_diacritics(loc_name));
+        if let Some(value) = localitate {
+            location_text.push(fold_diacritics(value));
+        }
+        if let Some(value) = strada {
+            location_text.push(fold_diacritics(value));
+        }
+        phone_codes.push(telefon.clone());
+        phone_codes.push(cod_sediu.clone());
+    }
+
+    let mut code_values = vec![cif, reg_com, cod, cod_intern, cod_extern];
+    code_values.extend(phone_codes);
+
+    conn.execute(
+        "INSERT INTO partner_search (partner_id, name, codes, locations) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            partner_id,
+            fold_diacritics(&name),
+            join_codes(code_values.iter()),
+            location_text.join(" "),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Rebuilds the partner index from the `partners` and `locations` tables.
+pub fn rebuild_partner_index(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute("DELETE FROM partner_search", [])?;
+
+    let partner_ids: Vec<String> = conn
+        .prepare("SELECT id FROM partners")?
+        .query_map([], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for partner_id in &partner_ids {
+        index_partner(conn, partner_id)?;
+    }
+
+    Ok(partner_ids.len())
+}
+
+/// Rebuilds the product index from the `products` table.
+pub fn rebuild_product_index(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.execute("DELETE FROM product_search", [])?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, name, class, cod_obiect, cod_articol, cod_extern, cod_intern FROM products",
+    )?;
+    let rows: Vec<ProductRow> = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?))
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for (id, name, class, cod_obiect, cod_articol, cod_extern, cod_intern) in &rows {
+        let mut name_text = fold_diacritics(name);
+        if let Some(class) = class {
+            name_text.push(' ');
+            name_text.push_str(&fold_diacritics(class));
+        }
+
+        let id_code = Some(id.clone());
+        conn.execute(
+            "INSERT INTO product_search (product_id, name, codes) VALUES (?1, ?2, ?3)",
+            params![
+                id,
+                name_text,
+                join_codes([&id_code, cod_obiect, cod_articol, cod_extern, cod_intern]),
+            ],
+        )?;
+    }
+
+    Ok(rows.len())
+}
+
+/// Rebuilds both indexes. Called after every sync and when the index is first created.
+pub fn rebuild_all(conn: &Connection) -> rusqlite::Result<()> {
+    let partners = rebuild_partner_index(conn)?;
+    let products = rebuild_product_index(conn)?;
+    log::info!("[SEARCH] Index rebuilt: {} partners, {} products", partners, products);
+    Ok(())
+}
+
+/// Returns the agent's partner ids (class AGENTI) matching the query, best
+/// match first. Name hits weigh more than code hits, which weigh more than
+/// address hits. The class filter runs before the limit, so partners of other
+/// classes cannot push the agent's own partners out of the results.
+pub fn search_partner_ids(conn: &Connection, match_query: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT partner_search.partner_id FROM partner_search
+         JOIN partners p ON p.id = partner_search.partner_id
+         WHERE partner_search MATCH ?1
+           AND (p.simbol_clasa = 'AGENTI' OR p.clasa = 'AGENTI')
+         ORDER BY bm25(partner_search, 0.0, 10.0, 6.0, 2.0)
+         LIMIT ?2",
+    )?;
+
+    let ids = stmt
+        .query_map(params![match_query, MAX_RESULTS], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(ids)
+}
+
+/// Returns product ids matching the query, best match first.
+pub fn search_product_ids(conn: &Connection, match_query: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT product_id FROM product_search
+         WHERE product_search MATCH ?1
+         ORDER BY bm25(product_search, 0.0, 10.0, 6.0)
+         LIMIT ?2",
+    )?;
+
+    let ids = stmt
+        .query_map(params![match_query, MAX_RESULTS], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE partners (
+                 id TEXT PRIMARY KEY, name TEXT NOT NULL, cif TEXT, reg_com TEXT, cod TEXT,
+                 cod_intern TEXT, cod_extern TEXT, clasa TEXT, simbol_clasa TEXT
+             );
+             CREATE TABLE locations (
+                 id TEXT PRIMARY KEY, partner_id TEXT NOT NULL, name TEXT NOT NULL,
+                 localitate TEXT, strada TEXT, telefon TEXT, cod_sediu TEXT
+             );",
+        )
+        .unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    fn add_partner(conn: &Connection, id: &str, name: &str, clasa: &str) {
+        conn.execute(
+            "INSERT INTO partners (id, name, cif, simbol_clasa) VALUES (?1, ?2, 'RO 123 456', ?3)",
+            params![id, name, clasa],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn folds_romanian_diacritics() {
+        assert_eq!(fold_diacritics("Ștefănescu Țânțăreni"), "stefanescu tantareni");
+        assert_eq!(
+            build_match_query("  Ștef  ro-123 ").as_deref(),
+            Some("\"stef\"* AND (\"ro 123\"* OR codes : \"ro123\"*)")
+        );
+        assert_eq!(build_match_query(" - "), None);
+    }
+
+    #[test]
+    fn finds_partners_without_diacritics_and_by_code() {
+        let conn = test_db();
+        add_partner(&conn, "P1", "Ștefănescu SRL", "AGENTI");
+        rebuild_partner_index(&conn).unwrap();
+
+        let query = build_match_query("stefanescu").unwrap();
+        assert_eq!(search_partner_ids(&conn, &query).unwrap(), vec!["P1".to_string()]);
+        let query = build_match_query("123456").unwrap();
+        assert_eq!(search_partner_ids(&conn, &query).unwrap(), vec!["P1".to_string()]);
+    }
+
+    #[test]
+    fn other_classes_do_not_use_up_the_limit() {
+        let conn = test_db();
+        for idx in 0..MAX_RESULTS {
+            add_partner(&conn, &format!("X{}", idx), "Ștefănescu Ștefănescu", "FURNIZORI");
+        }
+        add_partner(&conn, "P1", "Ștefănescu Construct", "AGENTI");
+        rebuild_partner_index(&conn).unwrap();
+
+        let query = build_match_query("stefanescu").unwrap();
+        assert_eq!(search_partner_ids(&conn, &query).unwrap(), vec!["P1".to_string()]);
+    }
+
+    #[test]
+    fn finds_hyphenated_names() {
+        let conn = test_db();
+        add_partner(&conn, "P1", "Coca-Cola HBC România", "AGENTI");
+        add_partner(&conn, "P2", "Cola Coca Distribuție", "AGENTI");
+        rebuild_partner_index(&conn).unwrap();
+
+        for typed in ["coca-cola", "Coca-Cola hbc", "coca-c"] {
+            let query = build_match_query(typed).unwrap();
+            assert_eq!(search_partner_ids(&conn, &query).unwrap(), vec!["P1".to_string()], "{}", typed);
+        }
+    }
+
+    #[test]
+    fn finds_names_with_dots() {
+        let conn = test_db();
+        add_partner(&conn, "P1", "S.C. Alfa Impex S.R.L.", "AGENTI");
+        add_partner(&conn, "P2", "Beta Com", "AGENTI");
+        rebuild_partner_index(&conn).unwrap();
+
+        for typed in ["s.r.l.", "alfa s.c.", "S.R.L"] {
+            let query = build_match_query(typed).unwrap();
+            assert_eq!(search_partner_ids(&conn, &query).unwrap(), vec!["P1".to_string()], "{}", typed);
+        }
+    }
+
+    #[test]
+    fn codes_typed_with_punctuation_match_their_compacted_form() {
+        let conn = test_db();
+        conn.execute(
+            "INSERT INTO partners (id, name, cif, reg_com, simbol_clasa) VALUES ('P1', 'Gama SRL', 'RO123456', 'J24/380/1994', 'AGENTI')",
+            [],
+        )
+        .unwrap();
+        rebuild_partner_index(&conn).unwrap();
+
+        for typed in ["j24/380", "J24-380-1994", "ro-123", "123.456"] {
+            let query = build_match_query(typed).unwrap();
+            assert_eq!(search_partner_ids(&conn, &query).unwrap(), vec!["P1".to_string()], "{}", typed);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use log::{error, warn};
+use minijinja::Environment;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::models::{CompanyBankAccount, CompanyProfile, DocumentTemplateInfo};
+
+// The HTML version of the documents (the preview and the copy saved next to
+// the PDF) is rendered from Jinja templates so the office can change it
+// without a new release. Each document type has a bundled default
+// (src-tauri/templates) that a file with the same name in the app data
+// `templates` folder overrides. The data each template receives is described
+// in templates/README.md, which is also copied next to the overrides.
+//
+// The printed PDF and ESC/POS layouts are fixed in the print_* modules; of
+// the templates only the footer text reaches paper (`footer_lines`).
+//
+// A broken override is logged and the bundled template is used instead:
+// saving a document must not stop because of a typo in a template. The
+// preview reports the error so it can be fixed.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocType {
+    Invoice,
+    Receipt,
+    DailyReport,
+    QualityCertificate,
+    CashRegister,
+    PartnerStatement,
+    BalanceConfirmation,
+    AgingReport,
+}
+
+impl DocType {
+    pub const ALL: [DocType; 8] = [
+        DocType::Invoice,
+        DocType::Receipt,
+        DocType::DailyReport,
+        DocType::QualityCertificate,
+        DocType::CashRegister,
+        DocType::PartnerStatement,
+        DocType::BalanceConfirmation,
+        DocType::AgingReport,
+    ];
+
+    pub fn parse(name: &str) -> Result<DocType, String> {
+        DocType::ALL
+            .into_iter()
+            .find(|doc_type| doc_type.name() == name)
+            .ok_or_else(|| format!("Tip de document necunoscut: {}", name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DocType::Invoice => "invoice",
+            DocType::Receipt => "receipt",
+            DocType::DailyReport => "daily_report",
+            DocType::QualityCertificate => "quality_certificate",
+            DocType::CashRegister => "cash_register",
+            DocType::PartnerStatement => "partner_statement",
+            DocType::BalanceConfirmation => "balance_confirmation",
+            DocType::AgingReport => "aging_report",
+        }
+    }
+
+    pub fn file_name(self) -> String {
+        format!("{}.html", self.name())
+    }
+
+    pub fn footer_file_name(self) -> String {
+        format!("{}_footer.txt", self.name())
+    }
+
+    fn bundled(self) -> &'static str {
+        match self {
+            DocType::Invoice => include_str!("../templates/invoice.html"),
+            DocType::Receipt => include_str!("../templates/receipt.html"),
+            DocType::DailyReport => include_str!("../templates/daily_report.html"),
+            DocType::QualityCertificate => include_str!("../templates/quality_certificate.html"),
+            DocType::CashRegister => include_str!("../templates/cash_register.html"),
+            DocType::PartnerStatement => include_str!("../templates/partner_statement.html"),
+            DocType::BalanceConfirmation => include_str!("../templates/balance_confirmation.html"),
+            DocType::AgingReport => include_str!("../templates/aging_report.html"),
+        }
+    }
+
+    fn bundled_footer(self) -> &'static str {
+        match self {
+            DocType::Invoice => include_str!("../templates/invoice_footer.txt"),
+            DocType::Receipt => include_str!("../templates/receipt_footer.txt"),
+            DocType::DailyReport => include_str!("../templates/daily_report_footer.txt"),
+            DocType::QualityCertificate => include_str!("../templates/quality_certificate_footer.txt"),
+            DocType::CashRegister => include_str!("../templates/cash_register_footer.txt"),
+            DocType::PartnerStatement => include_str!("../templates/partner_statement_footer.txt"),
+            DocType::BalanceConfirmation => include_str!("../templates/balance_confirmation_footer.txt"),
+            DocType::AgingReport => include_str!("../templates/aging_report_footer.txt"),
+        }
+    }
+}
+
+pub const README: &str = include_str!("../templates/README.md");
+
+/// Issuing company as the templates see it: the profile fields plus a few
+/// values every document formats the same way.
+#[derive(Debug, Serialize)]
+pub struct CompanyContext<'a> {
+    #[serde(flatten)]
+    pub profile: &'a CompanyProfile,
+    pub localitate_line: String,
+    /// Bank accounts in print order, the primary one first
+    pub accounts: Vec<&'a CompanyBankAccount>,
+}
+
+pub fn company_context(profile: &CompanyProfile) -> CompanyContext<'_> {
+    CompanyContext {
+        profile,
+        localitate_line: profile.localitate_line(),
+        accounts: profile.accounts_for_print(),
+    }
+}
+
+/// Images as data URLs, `None` when there is nothing to show.
+#[derive(Debug, Default, Serialize)]
+pub struct Images {
+    pub logo: Option<String>,
+    pub stamp: Option<String>,
+    pub epc: Option<String>,
+    pub iso: Option<String>,
+    /// Document QR code (SVG data URL)
+    pub qr: Option<String>,
+    /// Code128 barcode, when enabled (SVG data URL)
+    pub barcode: Option<String>,
+}
+
+pub fn templates_dir() -> Option<PathBuf> {
+    // Tests render the bundled templates, whatever the machine has installed
+    if cfg!(test) {
+        return None;
+    }
+    dirs::config_dir().map(|dir| dir.join("facturi.softconsulting.com").join("templates"))
+}
+
+fn read_override(file_name: &str) -> Option<(PathBuf, String)> {
+    let path = templates_dir()?.join(file_name);
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Some((path, source)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!("[TEMPLATES] Could not read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn money(value: f64) -> String {
+    format!("{:.2}", value)
+}
+
+// Auto-escaping follows the file name: on for .html, off for the .txt footers
+fn render_source<S: Serialize>(file_name: &str, source: &str, ctx: &S) -> Result<String, minijinja::Error> {
+    let mut env = Environment::new();
+    env.add_filter("money", money);
+    env.add_template(file_name, source)?;
+    env.get_template(file_name)?.render(ctx)
+}
+
+fn render_file<S: Serialize>(file_name: &str, bundled: &str, ctx: &S, strict: bool) -> Result<String, String> {
+    if let Some((path, source)) = read_override(file_name) {
+        match render_source(file_name, &source, ctx) {
+            Ok(rendered) => return Ok(rendered),
+            Err(e) if strict => {
+                return Err(format!("Șablonul {} conține o eroare: {:#}", path.display(), e));
+            }
+            Err(e) => warn!("[TEMPLATES] {} failed, using the bundled template: {:#}", path.display(), e),
+        }
+    }
+
+    render_source(file_name, bundled, ctx).map_err(|e| format!("Bundled template {} failed: {:#}", file_name, e))
+}
+
+/// Renders the HTML for a document, falling back to the bundled template.
+pub fn render<S: Serialize>(doc_type: DocType, ctx: &S) -> String {
+    render_file(&doc_type.file_name(), doc_type.bundled(), ctx, false).unwrap_or_else(|e| {
+        error!("[TEMPLATES] {}", e);
+        format!("<!DOCTYPE html><html><body><pre>{}</pre></body></html>", e)
+    })
+}
+
+/// Like `render`, but an error in the user's templates (including the footer)
+/// is returned instead of falling back, so the preview shows what to fix.
+pub fn render_preview<S: Serialize>(doc_type: DocType, company: &CompanyProfile, ctx: &S) -> Result<String, String> {
+    render_footer(doc_type, company, true)?;
+    render_file(&doc_type.file_name(), doc_type.bundled(), ctx, true)
+}
+
+fn render_footer(doc_type: DocType, company: &CompanyProfile, strict: bool) -> Result<String, String> {
+    #[derive(Serialize)]
+    struct FooterContext<'a> {
+        company: CompanyContext<'a>,
+    }
+
+    let ctx = FooterContext {
+        company: company_context(company),
+    };
+    render_file(&doc_type.footer_file_name(), doc_type.bundled_footer(), &ctx, strict)
+}
+
+/// Footer lines for a document. Shared by the HTML, PDF and thermal outputs,
+/// so the office can change them in one place.
+pub fn footer_lines(doc_type: DocType, company: &CompanyProfile) -> Vec<String> {
+    let text = render_footer(doc_type, company, false).unwrap_or_else(|e| {
+        error!("[TEMPLATES] {}", e);
+        String::new()
+    });
+
+    text.lines()
+        .map(|line| line.trim_end().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+pub fn info(doc_type: DocType) -> DocumentTemplateInfo {
+    let dir = templates_dir();
+    let html = dir.as_ref().map(|dir| dir.join(doc_type.file_name()));
+    let footer = dir.as_ref().map(|dir| dir.join(doc_type.footer_file_name()));
+
+    DocumentTemplateInfo {
+        doc_type: doc_type.name().to_string(),
+        overridden: html.as_ref().is_some_and(|path| path.exists()),
+        path: html.map(|path| path.to_string_lossy().to_string()),
+        footer_overridden: footer.as_ref().is_some_and(|path| path.exists()),
+        footer_path: footer.map(|path| path.to_string_lossy().to_string()),
+    }
+}
+
+/// Copies the bundled template and footer of a document into the overrides
+/// folder, together with the data model description. Existing files are kept.
+pub fn create_override(doc_type: DocType) -> Result<DocumentTemplateInfo, String> {
+    let dir = templates_dir().ok_or("Could not find app data directory")?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create templates directory: {}", e))?;
+
+    let files = [
+        (doc_type.file_name(), doc_type.bundled()),
+        (doc_type.footer_file_name(), doc_type.bundled_footer()),
+        ("README.md".to_string(), README),
+    ];
+    for (file_name, contents) in files {
+        let path = dir.join(&file_name);
+        if path.exists() && file_name != "README.md" {
+            continue;
+        }
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    Ok(info(doc_type))
+}
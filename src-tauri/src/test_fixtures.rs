@@ -0,0 +1,189 @@
+// Fixed documents for the layout tests of the PDF and ESC/POS generators.
+// Nothing here depends on the clock or the database, so a generator fed with
+// these values always writes the same bytes.
+
+use crate::codes::{DocumentCode, Kind};
+use crate::models::{
+    Collection, CollectionStatus, DailyCollectionsPartnerItem, DailyCollectionsReport, Invoice,
+    InvoiceItem, InvoiceStatus, PaymentDetails, QualityCertificateContext,
+    QualityCertificateProductLine, SalesPrintItem,
+};
+use crate::print_invoice::InvoiceDetails;
+use crate::print_receipt::ReceiptDetails;
+
+pub fn invoice() -> Invoice {
+    Invoice {
+        id: "0b7d4c1e-2f4a-4f43-9d55-6a1c2e3f4a5b".to_string(),
+        invoice_number: 42,
+        invoice_series: Some("KRN".to_string()),
+        partner_id: "P001".to_string(),
+        partner_name: "Ștefănescu Țară SRL".to_string(),
+        partner_cif: Some("RO123456".to_string()),
+        partner_reg_com: Some("J22/100/2010".to_string()),
+        location_id: "L001".to_string(),
+        location_name: "Magazin Iași".to_string(),
+        location_address: Some("Str. Păcurari 5".to_string()),
+        status: InvoiceStatus::Sent,
+        total_amount: 154.7,
+        item_count: 2,
+        notes: None,
+        created_at: "2026-03-02T09:15:00+02:00".to_string(),
+        sent_at: None,
+        error_message: None,
+        partner_payment_term: Some("15".to_string()),
+    }
+}
+
+pub fn invoice_items() -> Vec<InvoiceItem> {
+    vec![
+        InvoiceItem {
+            id: "I1".to_string(),
+            invoice_id: invoice().id,
+            product_id: "OUA-M".to_string(),
+            product_name: "Ouă categoria A, mărimea M".to_string(),
+            quantity: 100.0,
+            unit_price: 0.9,
+            unit_of_measure: "buc".to_string(),
+            total_price: 90.0,
+            tva_percent: Some(9.0),
+        },
+        InvoiceItem {
+            id: "I2".to_string(),
+            invoice_id: invoice().id,
+            product_id: "COF".to_string(),
+            product_name: "Cofraje".to_string(),
+            quantity: 10.0,
+            unit_price: 4.5,
+            unit_of_measure: "buc".to_string(),
+            total_price: 45.0,
+            tva_percent: Some(19.0),
+        },
+    ]
+}
+
+pub fn invoice_details() -> InvoiceDetails<'static> {
+    InvoiceDetails {
+        series: "KRN",
+        number: 42,
+        payment_term_days: 15,
+        delegate_name: Some("Ion Popescu"),
+        delegate_act: None,
+        car_number: Some("IS-01-ABC"),
+        duplicate: false,
+    }
+}
+
+pub fn collection() -> Collection {
+    Collection {
+        id: "c-1".to_string(),
+        id_partener: "P001".to_string(),
+        partner_name: Some("Ștefănescu Țară SRL".to_string()),
+        numar_factura: Some("42".to_string()),
+        serie_factura: Some("KRN".to_string()),
+        cod_document: None,
+        valoare: 154.7,
+        data_incasare: "2026-03-02T10:00:00+02:00".to_string(),
+        status: CollectionStatus::Synced,
+        synced_at: None,
+        error_message: None,
+        created_at: "2026-03-02T10:00:00+02:00".to_string(),
+        receipt_series: Some("CH".to_string()),
+        receipt_number: Some("7".to_string()),
+        payment: PaymentDetails::default(),
+    }
+}
+
+pub fn receipt_details() -> ReceiptDetails<'static> {
+    ReceiptDetails {
+        series: "CH",
+        number: "7",
+        issue_date: "02.03.2026",
+        agent: Some("Ion Popescu"),
+        partner_address: Some("Str. Păcurari 5"),
+        partner_localitate: Some("Iași"),
+        partner_judet: Some("Iași"),
+        partner_cui: Some("RO123456"),
+        partner_reg_com: Some("J22/100/2010"),
+    }
+}
+
+pub fn code(kind: Kind) -> DocumentCode {
+    DocumentCode {
+        kind,
+        series: "KRN".to_string(),
+        number: "42".to_string(),
+        date: "2026-03-02".to_string(),
+        total: 154.7,
+        cif: "RO123456".to_string(),
+        id: invoice().id,
+        barcode: true,
+    }
+}
+
+pub fn quality_certificate() -> QualityCertificateContext {
+    QualityCertificateContext {
+        cert_date: "02.03.2026".to_string(),
+        subtitle: "Ouă consum".to_string(),
+        packed_date: "01.03.2026".to_string(),
+        beneficiary: "Ștefănescu Țară SRL".to_string(),
+        invoice_display: "KRN 42".to_string(),
+        invoice_date: "02.03.2026".to_string(),
+        car_number: "IS-01-ABC".to_string(),
+        bon_analiza: "123/2026".to_string(),
+        product_lines: vec![QualityCertificateProductLine {
+            denumire: "Ouă M".to_string(),
+            lot: "L0301".to_string(),
+            data_productie: "01.03.2026".to_string(),
+            data_expirare: "29.03.2026".to_string(),
+        }],
+    }
+}
+
+fn sales_item(partner_name: &str, quantity: f64, without_vat: f64, payment_section: &str) -> SalesPrintItem {
+    let vat = (without_vat * 9.0).round() / 100.0;
+    SalesPrintItem {
+        partner_name: partner_name.to_string(),
+        invoice_count: 1,
+        total_quantity: quantity,
+        total_cofrage: quantity / 30.0,
+        total_without_vat: without_vat,
+        total_vat: vat,
+        total_with_vat: without_vat + vat,
+        payment_section: payment_section.to_string(),
+    }
+}
+
+/// Sales of a day: one partner in two payment sections, one in a single one.
+pub fn sales() -> Vec<SalesPrintItem> {
+    vec![
+        sales_item("Ștefănescu Țară SRL", 300.0, 270.0, "cash"),
+        sales_item("Alimentara Bucovina", 90.0, 81.0, "cash"),
+        sales_item("Ștefănescu Țară SRL", 60.0, 54.0, "ordin de plată"),
+    ]
+}
+
+pub fn collections_report() -> DailyCollectionsReport {
+    DailyCollectionsReport {
+        items: vec![
+            DailyCollectionsPartnerItem {
+                partner_name: "Ștefănescu Țară SRL".to_string(),
+                amount_from_today_sales: 154.7,
+                amount_from_previous_debt: 45.3,
+                total_amount: 200.0,
+            },
+            DailyCollectionsPartnerItem {
+                partner_name: "Alimentara Bucovina".to_string(),
+                amount_from_today_sales: 0.0,
+                amount_from_previous_debt: 88.29,
+                total_amount: 88.29,
+            },
+        ],
+        receipts_today_invoices_count: 1,
+        receipts_previous_debt_count: 2,
+        current_day_receipts_count: 3,
+        previous_day_receipts_count: 0,
+        current_day_collections_total: 288.29,
+        previous_day_collections_total: 0.0,
+        total_day_collections: 288.29,
+    }
+}
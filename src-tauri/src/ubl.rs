@@ -0,0 +1,874 @@
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+
+use crate::models::UblRuleViolation;
+
+// e-Factura: invoices as UBL 2.1 XML following the Romanian CIUS-RO profile
+// of EN 16931.
+//
+// The document is filled by the caller from the invoice, the issuing company
+// profile and the partner's location; this module turns it into XML and
+// checks it.
+//
+// `validate` is a pre-check, not the official rule set: it is a hand-written
+// subset of the EN 16931 and CIUS-RO schematron rules, those that can fail for
+// the invoices this app issues (one currency, no allowances, standard, zero
+// rated and reverse charge VAT, storno lines with negative quantities), with
+// their rule ids, so the common problems are reported here and not by ANAF
+// days later. An invoice it accepts can still be rejected: ANAF's validator
+// is the authority, and its answer is what the e-Factura upload reports.
+//
+// Amounts are computed in bani: line totals are rounded first, the VAT of each
+// category is computed on the category total, and every total is a sum of
+// rounded values, as EN 16931 requires.
+
+pub const CUSTOMIZATION_ID: &str = "urn:cen.eu:en16931:2017#compliant#urn:efactura.mfinante.ro:CIUS-RO:1.0.1";
+/// Commercial invoice
+pub const INVOICE_TYPE_CODE: &str = "380";
+/// UNTDID 2005 code for "VAT on collection" (TVA la încasare), BT-8
+const VAT_ON_COLLECTION_CODE: &str = "432";
+/// UNTDID 4461 "Credit transfer"
+const CREDIT_TRANSFER: &str = "30";
+/// VATEX code and text of reverse charge (taxare inversă), BT-121 and BT-120
+const REVERSE_CHARGE_REASON: (&str, &str) = ("VATEX-EU-AE", "Taxare inversă");
+
+pub struct Party {
+    pub name: String,
+    /// VAT identifier with country prefix ("RO5379259"); None = not registered for VAT
+    pub vat_id: Option<String>,
+    /// CUI without prefix, or another legal registration identifier
+    pub legal_id: Option<String>,
+    /// Seller only, BT-33: trade register number and share capital
+    pub legal_form: Option<String>,
+    pub street: String,
+    pub city: String,
+    pub postal_zone: Option<String>,
+    /// County as written ("Maramureș", "Jud. MM", "RO-MM")
+    pub county: String,
+    /// ISO 3166-1 alpha-2
+    pub country: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+}
+
+pub struct Line {
+    pub name: String,
+    pub seller_item_id: Option<String>,
+    pub quantity: f64,
+    /// Unit as used in the app ("BUC", "KG"); converted to UN/ECE Rec 20
+    pub unit: String,
+    pub unit_price: f64,
+    pub net_amount: f64,
+    pub vat_percent: f64,
+    /// VAT paid by the buyer (taxare inversă, art. 331 Cod fiscal), category AE
+    pub reverse_charge: bool,
+}
+
+pub struct PaymentAccount {
+    pub iban: String,
+    pub bank_name: Option<String>,
+}
+
+pub struct Document {
+    pub number: String,
+    pub issue_date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub currency: String,
+    pub note: Option<String>,
+    pub vat_on_collection: bool,
+    pub seller: Party,
+    pub buyer: Party,
+    pub account: Option<PaymentAccount>,
+    pub payment_terms: Option<String>,
+    pub lines: Vec<Line>,
+}
+
+fn cents(value: f64) -> i64 {
+    (value * 100.0).round() as i64
+}
+
+// Rate in hundredths of a percent, so 19% and 9% can be grouped exactly
+fn rate_key(percent: f64) -> i64 {
+    (percent * 100.0).round() as i64
+}
+
+fn category(line: &Line) -> &'static str {
+    if line.reverse_charge {
+        "AE"
+    } else if rate_key(line.vat_percent) == 0 {
+        "Z"
+    } else {
+        "S"
+    }
+}
+
+/// VAT breakdown of one category (BG-23), in bani.
+struct Subtotal {
+    category: &'static str,
+    rate: i64,
+    taxable: i64,
+    tax: i64,
+}
+
+struct Totals {
+    lines: i64,
+    tax: i64,
+    subtotals: Vec<Subtotal>,
+}
+
+impl Totals {
+    fn with_vat(&self) -> i64 {
+        self.lines + self.tax
+    }
+}
+
+// taxable × rate, half away from zero, in bani
+fn tax_of(taxable: i64, rate: i64) -> i64 {
+    let product = taxable as i128 * rate as i128;
+    let rounded = (product.abs() + 5_000) / 10_000;
+    (if product < 0 { -rounded } else { rounded }) as i64
+}
+
+fn totals(document: &Document) -> Totals {
+    let mut groups: BTreeMap<(&'static str, i64), i64> = BTreeMap::new();
+    for line in &document.lines {
+        *groups
+            .entry((category(line), rate_key(line.vat_percent)))
+            .or_insert(0) += cents(line.net_amount);
+    }
+
+    let subtotals: Vec<Subtotal> = groups
+        .into_iter()
+        .map(|((category, rate), taxable)| Subtotal {
+            category,
+            rate,
+            taxable,
+            tax: tax_of(taxable, rate),
+        })
+        .collect();
+
+    Totals {
+        lines: document.lines.iter().map(|line| cents(line.net_amount)).sum(),
+        tax: subtotals.iter().map(|subtotal| subtotal.tax).sum(),
+        subtotals,
+    }
+}
+
+fn amount(bani: i64) -> String {
+    let sign = if bani < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, bani.abs() / 100, bani.abs() % 100)
+}
+
+// Quantities and unit prices keep up to 4 decimals, without trailing zeros
+fn decimal(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0');
+    let text = text.strip_suffix('.').unwrap_or(text);
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn percent(rate: i64) -> String {
+    amount(rate)
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ă' | 'â' | 'Ă' | 'Â' => 'A',
+            'î' | 'Î' => 'I',
+            'ș' | 'ş' | 'Ș' | 'Ş' => 'S',
+            'ț' | 'ţ' | 'Ț' | 'Ţ' => 'T',
+            c => c.to_ascii_uppercase(),
+        })
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
+
+const COUNTIES: [(&str, &str); 42] = [
+    ("AB", "ALBA"),
+    ("AR", "ARAD"),
+    ("AG", "ARGES"),
+    ("BC", "BACAU"),
+    ("BH", "BIHOR"),
+    ("BN", "BISTRITANASAUD"),
+    ("BT", "BOTOSANI"),
+    ("BV", "BRASOV"),
+    ("BR", "BRAILA"),
+    ("B", "BUCURESTI"),
+    ("BZ", "BUZAU"),
+    ("CS", "CARASSEVERIN"),
+    ("CL", "CALARASI"),
+    ("CJ", "CLUJ"),
+    ("CT", "CONSTANTA"),
+    ("CV", "COVASNA"),
+    ("DB", "DAMBOVITA"),
+    ("DJ", "DOLJ"),
+    ("GL", "GALATI"),
+    ("GR", "GIURGIU"),
+    ("GJ", "GORJ"),
+    ("HR", "HARGHITA"),
+    ("HD", "HUNEDOARA"),
+    ("IL", "IALOMITA"),
+    ("IS", "IASI"),
+    ("IF", "ILFOV"),
+    ("MM", "MARAMURES"),
+    ("MH", "MEHEDINTI"),
+    ("MS", "MURES"),
+    ("NT", "NEAMT"),
+    ("OT", "OLT"),
+    ("PH", "PRAHOVA"),
+    ("SM", "SATUMARE"),
+    ("SJ", "SALAJ"),
+    ("SB", "SIBIU"),
+    ("SV", "SUCEAVA"),
+    ("TR", "TELEORMAN"),
+    ("TM", "TIMIS"),
+    ("TL", "TULCEA"),
+    ("VS", "VASLUI"),
+    ("VL", "VALCEA"),
+    ("VN", "VRANCEA"),
+];
+
+/// ISO 3166-2:RO code of a county written as a name or a code: "Maramureș",
+/// "Jud. MM", "RO-MM", "Municipiul București".
+pub fn county_code(county: &str) -> Option<String> {
+    let mut key = normalize(county);
+    for prefix in ["JUDETUL", "JUDET", "JUD", "MUNICIPIUL", "MUN"] {
+        if let Some(rest) = key.strip_prefix(prefix) {
+            key = rest.to_string();
+            break;
+        }
+    }
+    if let Some(rest) = key.strip_prefix("RO") {
+        if COUNTIES.iter().any(|(code, _)| *code == rest) {
+            key = rest.to_string();
+        }
+    }
+
+    COUNTIES
+        .iter()
+        .find(|(code, name)| *code == key || *name == key)
+        .map(|(code, _)| format!("RO-{}", code))
+}
+
+/// "SECTOR1".."SECTOR6" for a Bucharest city such as "București Sector 3".
+fn bucharest_sector(city: &str) -> Option<String> {
+    let key = normalize(city);
+    let digits = key.split("SECTOR").nth(1)?;
+    match digits {
+        "1" | "2" | "3" | "4" | "5" | "6" => Some(format!("SECTOR{}", digits)),
+        _ => None,
+    }
+}
+
+/// UN/ECE Recommendation 20 code for a unit of measure; "C62" (one) when
+/// the unit is not known.
+pub fn unit_code(unit: &str) -> &'static str {
+    match normalize(unit).as_str() {
+        "BUC" | "BC" | "BUCATA" | "BUCATI" | "PCS" | "H87" => "H87",
+        "KG" | "KGM" => "KGM",
+        "G" | "GR" | "GRM" => "GRM",
+        "T" | "TO" | "TONA" | "TNE" => "TNE",
+        "L" | "LT" | "LITRU" | "LITRI" | "LTR" => "LTR",
+        "ML" | "MLT" => "MLT",
+        "M" | "ML0" | "METRU" | "MTR" => "MTR",
+        "MP" | "M2" | "MTK" => "MTK",
+        "MC" | "M3" | "MTQ" => "MTQ",
+        "SET" => "SET",
+        "PER" | "PERECHE" | "PR" => "PR",
+        "PAC" | "PACHET" | "PK" => "PK",
+        "CUT" | "CUTIE" | "BAX" | "CT" => "CT",
+        "ORA" | "ORE" | "H" | "HUR" => "HUR",
+        "ZI" | "ZILE" | "DAY" => "DAY",
+        "KWH" => "KWH",
+        _ => "C62",
+    }
+}
+
+fn violation(rule: &str, message: String) -> UblRuleViolation {
+    UblRuleViolation {
+        rule: rule.to_string(),
+        message,
+    }
+}
+
+// CIUS-RO numbers the seller rule ...0 and the buyer rule ...1
+fn check_party(party: &Party, buyer: bool, violations: &mut Vec<UblRuleViolation>) {
+    let (role, name_rule, country_rule) = if buyer {
+        ("cumpărătorului", "BR-07", "BR-11")
+    } else {
+        ("furnizorului", "BR-06", "BR-09")
+    };
+    let ro_rule = |base: u32| format!("BR-RO-{:03}", base + buyer as u32);
+
+    if party.name.trim().is_empty() {
+        violations.push(violation(name_rule, format!("Denumirea {} lipsește.", role)));
+    }
+    if party.country.trim().len() != 2 {
+        violations.push(violation(country_rule, format!("Țara {} lipsește sau nu este un cod ISO.", role)));
+    }
+    if party.street.trim().is_empty() {
+        violations.push(violation(&ro_rule(80), format!("Strada din adresa {} lipsește.", role)));
+    }
+    if party.city.trim().is_empty() {
+        violations.push(violation(&ro_rule(90), format!("Localitatea {} lipsește.", role)));
+    }
+
+    if party.country == "RO" {
+        match county_code(&party.county) {
+            None => violations.push(violation(
+                &ro_rule(100),
+                format!("Județul {} („{}”) nu este un cod ISO 3166-2:RO valid.", role, party.county),
+            )),
+            Some(code) if code == "RO-B" && bucharest_sector(&party.city).is_none() => {
+                violations.push(violation(
+                    &ro_rule(110),
+                    format!("Pentru București, localitatea {} trebuie să fie SECTOR1..SECTOR6 (este „{}”).", role, party.city),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(vat_id) = &party.vat_id {
+        let prefix: String = vat_id.chars().take(2).collect();
+        if prefix.len() != 2 || !prefix.chars().all(|c| c.is_ascii_uppercase()) {
+            violations.push(violation(
+                "BR-CO-09",
+                format!("Codul de TVA al {} trebuie să înceapă cu codul țării: {}", role, vat_id),
+            ));
+        }
+    }
+}
+
+/// Rules the document breaks; empty when it can be exported.
+pub fn validate(document: &Document) -> Vec<UblRuleViolation> {
+    let mut violations = Vec::new();
+
+    if document.number.trim().is_empty() {
+        violations.push(violation("BR-02", "Numărul facturii lipsește.".to_string()));
+    } else if !document.number.chars().any(|c| c.is_ascii_digit()) {
+        violations.push(violation(
+            "BR-RO-010",
+            format!("Numărul facturii trebuie să conțină cel puțin o cifră: {}", document.number),
+        ));
+    }
+    if document.currency.trim().len() != 3 {
+        violations.push(violation("BR-05", "Moneda facturii lipsește.".to_string()));
+    } else if document.currency != "RON" {
+        violations.push(violation(
+            "BR-RO-030",
+            format!(
+                "Factura în {} trebuie să aibă și TVA în RON, iar cursul de schimb nu este cunoscut.",
+                document.currency
+            ),
+        ));
+    }
+    if document.due_date < document.issue_date {
+        violations.push(violation(
+            "BR-CO-25",
+            "Data scadenței este înaintea datei facturii.".to_string(),
+        ));
+    }
+
+    check_party(&document.seller, false, &mut violations);
+    check_party(&document.buyer, true, &mut violations);
+
+    if document.lines.is_empty() {
+        violations.push(violation("BR-16", "Factura nu are nicio linie.".to_string()));
+    }
+    for (index, line) in document.lines.iter().enumerate() {
+        let at = format!("Linia {}", index + 1);
+        if line.name.trim().is_empty() {
+            violations.push(violation("BR-25", format!("{}: denumirea produsului lipsește.", at)));
+        }
+        if line.quantity == 0.0 {
+            violations.push(violation("BR-22", format!("{}: cantitatea lipsește.", at)));
+        }
+        if line.unit_price < 0.0 {
+            violations.push(violation("BR-27", format!("{}: prețul unitar este negativ.", at)));
+        }
+        if line.vat_percent < 0.0 {
+            violations.push(violation("BR-S-05", format!("{}: cota de TVA este negativă.", at)));
+        }
+        match category(line) {
+            "S" if document.seller.vat_id.is_none() => violations.push(violation(
+                "BR-S-02",
+                format!("{}: linie cu TVA, dar furnizorul nu are cod de TVA.", at),
+            )),
+            "AE" => {
+                if rate_key(line.vat_percent) != 0 {
+                    violations.push(violation(
+                        "BR-AE-05",
+                        format!("{}: la taxare inversă cota de TVA trebuie să fie 0.", at),
+                    ));
+                }
+                if document.seller.vat_id.is_none()
+                    || (document.buyer.vat_id.is_none() && document.buyer.legal_id.is_none())
+                {
+                    violations.push(violation(
+                        "BR-AE-02",
+                        format!("{}: taxarea inversă cere codul de TVA al furnizorului și al cumpărătorului.", at),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The totals are sums by construction; checked so a change here cannot
+    // silently produce an invoice ANAF rejects
+    let totals = totals(document);
+    for subtotal in &totals.subtotals {
+        if subtotal.tax != tax_of(subtotal.taxable, subtotal.rate) {
+            violations.push(violation(
+                "BR-CO-17",
+                format!("TVA pentru cota {}% nu corespunde bazei de impozitare.", percent(subtotal.rate)),
+            ));
+        }
+    }
+    if totals.tax != totals.subtotals.iter().map(|subtotal| subtotal.tax).sum::<i64>() {
+        violations.push(violation("BR-CO-14", "Totalul TVA nu este suma pe cote.".to_string()));
+    }
+
+    violations
+}
+
+struct Xml {
+    out: String,
+    depth: usize,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if (c as u32) < 0x20 && c != '\t' && c != '\n' && c != '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Xml {
+    fn new() -> Xml {
+        Xml {
+            out: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            depth: 0,
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.open_with(tag, &[]);
+    }
+
+    fn open_with(&mut self, tag: &str, attributes: &[(&str, &str)]) {
+        self.indent();
+        self.out.push('<');
+        self.out.push_str(tag);
+        for (name, value) in attributes {
+            self.out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        self.out.push_str(">\n");
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.indent();
+        self.out.push_str(&format!("</{}>\n", tag));
+    }
+
+    fn leaf(&mut self, tag: &str, value: &str) {
+        self.leaf_with(tag, &[], value);
+    }
+
+    fn leaf_with(&mut self, tag: &str, attributes: &[(&str, &str)], value: &str) {
+        self.indent();
+        self.out.push('<');
+        self.out.push_str(tag);
+        for (name, value) in attributes {
+            self.out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        self.out.push_str(&format!(">{}</{}>\n", escape(value), tag));
+    }
+
+    fn optional(&mut self, tag: &str, value: Option<&str>) {
+        if let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) {
+            self.leaf(tag, value);
+        }
+    }
+
+    fn money(&mut self, tag: &str, currency: &str, bani: i64) {
+        self.leaf_with(tag, &[("currencyID", currency)], &amount(bani));
+    }
+
+    fn tax_scheme(&mut self) {
+        self.open("cac:TaxScheme");
+        self.leaf("cbc:ID", "VAT");
+        self.close("cac:TaxScheme");
+    }
+}
+
+fn write_party(xml: &mut Xml, party: &Party) {
+    xml.open("cac:Party");
+
+    xml.open("cac:PostalAddress");
+    xml.leaf("cbc:StreetName", party.street.trim());
+    let county = county_code(&party.county);
+    let city = match county.as_deref() {
+        Some("RO-B") => bucharest_sector(&party.city).unwrap_or_else(|| party.city.trim().to_string()),
+        _ => party.city.trim().to_string(),
+    };
+    xml.leaf("cbc:CityName", &city);
+    xml.optional("cbc:PostalZone", party.postal_zone.as_deref());
+    match &county {
+        Some(code) if party.country == "RO" => xml.leaf("cbc:CountrySubentity", code),
+        _ => xml.optional("cbc:CountrySubentity", Some(&party.county)),
+    }
+    xml.open("cac:Country");
+    xml.leaf("cbc:IdentificationCode", &party.country);
+    xml.close("cac:Country");
+    xml.close("cac:PostalAddress");
+
+    if let Some(vat_id) = &party.vat_id {
+        xml.open("cac:PartyTaxScheme");
+        xml.leaf("cbc:CompanyID", vat_id);
+        xml.tax_scheme();
+        xml.close("cac:PartyTaxScheme");
+    }
+
+    xml.open("cac:PartyLegalEntity");
+    xml.leaf("cbc:RegistrationName", party.name.trim());
+    xml.optional("cbc:CompanyID", party.legal_id.as_deref());
+    xml.optional("cbc:CompanyLegalForm", party.legal_form.as_deref());
+    xml.close("cac:PartyLegalEntity");
+
+    if party.phone.is_some() || party.email.is_some() {
+        xml.open("cac:Contact");
+        xml.optional("cbc:Telephone", party.phone.as_deref());
+        xml.optional("cbc:ElectronicMail", party.email.as_deref());
+        xml.close("cac:Contact");
+    }
+
+    xml.close("cac:Party");
+}
+
+fn write_tax_category(xml: &mut Xml, tag: &str, category: &str, rate: i64) {
+    xml.open(tag);
+    xml.leaf("cbc:ID", category);
+    xml.leaf("cbc:Percent", &percent(rate));
+    // The exemption reason belongs to the VAT breakdown, not to the lines
+    if category == "AE" && tag == "cac:TaxCategory" {
+        xml.leaf("cbc:TaxExemptionReasonCode", REVERSE_CHARGE_REASON.0);
+        xml.leaf("cbc:TaxExemptionReason", REVERSE_CHARGE_REASON.1);
+    }
+    xml.tax_scheme();
+    xml.close(tag);
+}
+
+/// UBL 2.1 XML of the invoice, or the rules it breaks.
+pub fn to_xml(document: &Document) -> Result<String, Vec<UblRuleViolation>> {
+    let violations = validate(document);
+    if !violations.is_empty() {
+        return Err(violations);
+    }
+
+    let currency = document.currency.as_str();
+    let totals = totals(document);
+    let mut xml = Xml::new();
+
+    xml.open_with(
+        "Invoice",
+        &[
+            ("xmlns", "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2"),
+            ("xmlns:cac", "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2"),
+            ("xmlns:cbc", "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2"),
+        ],
+    );
+    xml.leaf("cbc:CustomizationID", CUSTOMIZATION_ID);
+    xml.leaf("cbc:ID", document.number.trim());
+    xml.leaf("cbc:IssueDate", &document.issue_date.format("%Y-%m-%d").to_string());
+    xml.leaf("cbc:DueDate", &document.due_date.format("%Y-%m-%d").to_string());
+    xml.leaf("cbc:InvoiceTypeCode", INVOICE_TYPE_CODE);
+    xml.optional("cbc:Note", document.note.as_deref());
+    xml.leaf("cbc:DocumentCurrencyCode", currency);
+    if document.vat_on_collection {
+        xml.open("cac:InvoicePeriod");
+        xml.leaf("cbc:DescriptionCode", VAT_ON_COLLECTION_CODE);
+        xml.close("cac:InvoicePeriod");
+    }
+
+    xml.open("cac:AccountingSupplierParty");
+    write_party(&mut xml, &document.seller);
+    xml.close("cac:AccountingSupplierParty");
+
+    xml.open("cac:AccountingCustomerParty");
+    write_party(&mut xml, &document.buyer);
+    xml.close("cac:AccountingCustomerParty");
+
+    if let Some(account) = &document.account {
+        xml.open("cac:PaymentMeans");
+        xml.leaf("cbc:PaymentMeansCode", CREDIT_TRANSFER);
+        xml.open("cac:PayeeFinancialAccount");
+        xml.leaf("cbc:ID", &account.iban.split_whitespace().collect::<String>());
+        xml.optional("cbc:Name", account.bank_name.as_deref());
+        xml.close("cac:PayeeFinancialAccount");
+        xml.close("cac:PaymentMeans");
+    }
+    if let Some(terms) = &document.payment_terms {
+        xml.open("cac:PaymentTerms");
+        xml.leaf("cbc:Note", terms);
+        xml.close("cac:PaymentTerms");
+    }
+
+    xml.open("cac:TaxTotal");
+    xml.money("cbc:TaxAmount", currency, totals.tax);
+    for subtotal in &totals.subtotals {
+        xml.open("cac:TaxSubtotal");
+        xml.money("cbc:TaxableAmount", currency, subtotal.taxable);
+        xml.money("cbc:TaxAmount", currency, subtotal.tax);
+        write_tax_category(&mut xml, "cac:TaxCategory", subtotal.category, subtotal.rate);
+        xml.close("cac:TaxSubtotal");
+    }
+    xml.close("cac:TaxTotal");
+
+    xml.open("cac:LegalMonetaryTotal");
+    xml.money("cbc:LineExtensionAmount", currency, totals.lines);
+    xml.money("cbc:TaxExclusiveAmount", currency, totals.lines);
+    xml.money("cbc:TaxInclusiveAmount", currency, totals.with_vat());
+    xml.money("cbc:PayableAmount", currency, totals.with_vat());
+    xml.close("cac:LegalMonetaryTotal");
+
+    for (index, line) in document.lines.iter().enumerate() {
+        xml.open("cac:InvoiceLine");
+        xml.leaf("cbc:ID", &(index + 1).to_string());
+        xml.leaf_with("cbc:InvoicedQuantity", &[("unitCode", unit_code(&line.unit))], &decimal(line.quantity));
+        xml.money("cbc:LineExtensionAmount", currency, cents(line.net_amount));
+        xml.open("cac:Item");
+        xml.leaf("cbc:Name", line.name.trim());
+        if let Some(id) = line.seller_item_id.as_deref().map(str::trim).filter(|id| !id.is_empty()) {
+            xml.open("cac:SellersItemIdentification");
+            xml.leaf("cbc:ID", id);
+            xml.close("cac:SellersItemIdentification");
+        }
+        write_tax_category(
+            &mut xml,
+            "cac:ClassifiedTaxCategory",
+            category(line),
+            rate_key(line.vat_percent),
+        );
+        xml.close("cac:Item");
+        xml.open("cac:Price");
+        xml.leaf_with("cbc:PriceAmount", &[("currencyID", currency)], &decimal(line.unit_price));
+        xml.close("cac:Price");
+        xml.close("cac:InvoiceLine");
+    }
+
+    xml.close("Invoice");
+    Ok(xml.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Compares with tests/fixtures/ubl/`name`. After a deliberate change,
+    /// check the file with ANAF's validator and rewrite it with UPDATE_GOLDEN=1.
+    fn assert_fixture(name: &str, xml: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ubl").join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, xml).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(xml, expected, "{} differs from the fixture", name);
+    }
+
+    fn seller() -> Party {
+        Party {
+            name: "Soft Consulting SRL".to_string(),
+            vat_id: Some("RO5379259".to_string()),
+            legal_id: Some("5379259".to_string()),
+            legal_form: Some("J24/1234/1994, Capital social: 200 lei".to_string()),
+            street: "Str. Unirii nr. 2".to_string(),
+            city: "Seini".to_string(),
+            postal_zone: Some("435400".to_string()),
+            county: "Maramureș".to_string(),
+            country: "RO".to_string(),
+            phone: Some("0262 491 000".to_string()),
+            email: None,
+        }
+    }
+
+    fn buyer() -> Party {
+        Party {
+            name: "Ștefănescu & Țară SRL".to_string(),
+            vat_id: Some("RO123456".to_string()),
+            legal_id: None,
+            legal_form: None,
+            street: "Str. Păcurari nr. 5".to_string(),
+            city: "București Sector 3".to_string(),
+            postal_zone: None,
+            county: "Municipiul București".to_string(),
+            country: "RO".to_string(),
+            phone: None,
+            email: Some("office@stefanescu.ro".to_string()),
+        }
+    }
+
+    fn line(name: &str, quantity: f64, unit_price: f64, vat_percent: f64) -> Line {
+        Line {
+            name: name.to_string(),
+            seller_item_id: Some(name.chars().take(3).collect::<String>().to_uppercase()),
+            quantity,
+            unit: "BUC".to_string(),
+            unit_price,
+            net_amount: (quantity * unit_price * 100.0).round() / 100.0,
+            vat_percent,
+            reverse_charge: false,
+        }
+    }
+
+    fn document(number: &str, lines: Vec<Line>) -> Document {
+        Document {
+            number: number.to_string(),
+            issue_date: NaiveDate::from_ymd_opt(2026, 3, 2).unwrap(),
+            due_date: NaiveDate::from_ymd_opt(2026, 3, 17).unwrap(),
+            currency: "RON".to_string(),
+            note: Some("Livrare <ouă> & cofraje".to_string()),
+            vat_on_collection: false,
+            seller: seller(),
+            buyer: buyer(),
+            account: Some(PaymentAccount {
+                iban: "RO49 AAAA 1B31 0075 9384 0000".to_string(),
+                bank_name: Some("Banca Transilvania".to_string()),
+            }),
+            payment_terms: Some("Termen de plată: 15 zile".to_string()),
+            lines,
+        }
+    }
+
+    fn rules(document: &Document) -> Vec<String> {
+        validate(document).into_iter().map(|violation| violation.rule).collect()
+    }
+
+    /// Text of the first `<tag ...>value</tag>` after `after`.
+    fn value_after<'a>(xml: &'a str, after: &str, tag: &str) -> &'a str {
+        let rest = &xml[xml.find(after).unwrap_or_else(|| panic!("{} not found", after))..];
+        let start = rest.find(&format!("<{}", tag)).unwrap();
+        let rest = &rest[start..];
+        let value = &rest[rest.find('>').unwrap() + 1..];
+        &value[..value.find('<').unwrap()]
+    }
+
+    #[test]
+    fn standard_invoice_matches_fixture() {
+        let document = document(
+            "KRN42",
+            vec![line("Ouă categoria A", 100.0, 0.9, 9.0), line("Cofraje", 10.0, 4.5, 19.0)],
+        );
+        assert!(validate(&document).is_empty());
+        let xml = to_xml(&document).unwrap();
+        assert_fixture("standard_invoice.xml", &xml);
+
+        // 90.00 × 9% + 45.00 × 19% = 8.10 + 8.55
+        assert_eq!(value_after(&xml, "<cac:TaxTotal>", "cbc:TaxAmount"), "16.65");
+        assert_eq!(value_after(&xml, "<cac:LegalMonetaryTotal>", "cbc:LineExtensionAmount"), "135.00");
+        assert_eq!(value_after(&xml, "<cac:LegalMonetaryTotal>", "cbc:PayableAmount"), "151.65");
+        assert_eq!(value_after(&xml, "<cac:AccountingCustomerParty>", "cbc:CityName"), "SECTOR3");
+        assert_eq!(value_after(&xml, "<cac:AccountingCustomerParty>", "cbc:CountrySubentity"), "RO-B");
+        assert_eq!(value_after(&xml, "<cac:AccountingSupplierParty>", "cbc:CountrySubentity"), "RO-MM");
+        assert_eq!(value_after(&xml, "<cac:PayeeFinancialAccount>", "cbc:ID"), "RO49AAAA1B31007593840000");
+        assert!(xml.contains("<cbc:Note>Livrare &lt;ouă&gt; &amp; cofraje</cbc:Note>"));
+        assert!(xml.contains("<cbc:RegistrationName>Ștefănescu &amp; Țară SRL</cbc:RegistrationName>"));
+    }
+
+    #[test]
+    fn credit_note_matches_fixture() {
+        // Storno of part of KRN42: type 380 with negative quantities, as ANAF accepts
+        let mut document = document("KRN43", vec![line("Ouă categoria A", -30.0, 0.9, 9.0)]);
+        document.note = Some("Stornare parțială factura KRN42 din 02.03.2026".to_string());
+        assert!(validate(&document).is_empty());
+        let xml = to_xml(&document).unwrap();
+        assert_fixture("credit_note.xml", &xml);
+
+        assert_eq!(value_after(&xml, "<cbc:ID>KRN43", "cbc:InvoiceTypeCode"), INVOICE_TYPE_CODE);
+        assert_eq!(value_after(&xml, "<cac:InvoiceLine>", "cbc:InvoicedQuantity"), "-30");
+        assert_eq!(value_after(&xml, "<cac:InvoiceLine>", "cbc:PriceAmount"), "0.9");
+        // -27.00 × 9% = -2.43
+        assert_eq!(value_after(&xml, "<cac:TaxTotal>", "cbc:TaxAmount"), "-2.43");
+        assert_eq!(value_after(&xml, "<cac:LegalMonetaryTotal>", "cbc:PayableAmount"), "-29.43");
+    }
+
+    #[test]
+    fn reverse_charge_invoice_matches_fixture() {
+        let mut grain = line("Grâu furajer", 1250.0, 1.1, 0.0);
+        grain.unit = "KG".to_string();
+        grain.reverse_charge = true;
+        let document = document("KRN44", vec![grain, line("Cofraje", 10.0, 4.5, 19.0)]);
+        assert!(validate(&document).is_empty());
+        let xml = to_xml(&document).unwrap();
+        assert_fixture("reverse_charge_invoice.xml", &xml);
+
+        assert_eq!(value_after(&xml, "<cac:TaxSubtotal>", "cbc:TaxableAmount"), "1375.00");
+        assert_eq!(value_after(&xml, "<cac:TaxSubtotal>", "cbc:TaxAmount"), "0.00");
+        assert_eq!(value_after(&xml, "<cac:TaxSubtotal>", "cbc:TaxExemptionReasonCode"), "VATEX-EU-AE");
+        assert_eq!(value_after(&xml, "<cac:TaxTotal>", "cbc:TaxAmount"), "8.55");
+        assert_eq!(value_after(&xml, "<cac:LegalMonetaryTotal>", "cbc:PayableAmount"), "1428.55");
+        assert_eq!(value_after(&xml, "<cac:ClassifiedTaxCategory>", "cbc:ID"), "AE");
+        assert_eq!(value_after(&xml, "<cac:InvoiceLine>", "cbc:InvoicedQuantity"), "1250");
+        // The exemption reason is written once, in the VAT breakdown
+        assert_eq!(xml.matches("TaxExemptionReasonCode").count(), 2);
+    }
+
+    #[test]
+    fn broken_documents_report_their_rules() {
+        let mut document = document("KRN", vec![]);
+        document.due_date = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        document.buyer.county = "Judetul Nicaieri".to_string();
+        document.seller.city = " ".to_string();
+        assert_eq!(rules(&document), ["BR-RO-010", "BR-CO-25", "BR-RO-090", "BR-RO-101", "BR-16"]);
+        assert!(to_xml(&document).is_err());
+
+        let mut document = self::document("KRN45", vec![line("Ouă", 0.0, -1.0, 9.0)]);
+        document.seller.vat_id = None;
+        document.buyer.city = "București".to_string();
+        assert_eq!(rules(&document), ["BR-RO-111", "BR-22", "BR-27", "BR-S-02"]);
+
+        let mut grain = line("Grâu", 100.0, 1.0, 9.0);
+        grain.reverse_charge = true;
+        let mut document = self::document("KRN46", vec![grain]);
+        document.buyer.vat_id = None;
+        assert_eq!(rules(&document), ["BR-AE-05", "BR-AE-02"]);
+    }
+
+    #[test]
+    fn counties_and_units_map_to_their_codes() {
+        assert_eq!(county_code("Maramureș").as_deref(), Some("RO-MM"));
+        assert_eq!(county_code("Jud. MM").as_deref(), Some("RO-MM"));
+        assert_eq!(county_code("RO-IS").as_deref(), Some("RO-IS"));
+        assert_eq!(county_code("Municipiul București").as_deref(), Some("RO-B"));
+        assert_eq!(county_code("Nicaieri"), None);
+        assert_eq!(unit_code("BUC"), "H87");
+        assert_eq!(unit_code("kg"), "KGM");
+        assert_eq!(unit_code("bax"), "CT");
+        assert_eq!(unit_code("sac"), "C62");
+    }
+}